@@ -297,6 +297,10 @@ pub struct LibComgr {
         action_info: amd_comgr_action_info_t,
         language: amd_comgr_language_t,
     ) -> amd_comgr_status_t,
+    pub amd_comgr_action_info_set_logging: unsafe extern "C" fn(
+        action_info: amd_comgr_action_info_t,
+        logging: bool,
+    ) -> amd_comgr_status_t,
     pub amd_comgr_action_info_set_option_list: unsafe extern "C" fn(
         action_info: amd_comgr_action_info_t,
         options: *mut *const ::std::os::raw::c_char,
@@ -351,6 +355,9 @@ impl LibComgr {
         let amd_comgr_action_info_set_language = __library
             .get(b"amd_comgr_action_info_set_language\0")
             .map(|sym| *sym)?;
+        let amd_comgr_action_info_set_logging = __library
+            .get(b"amd_comgr_action_info_set_logging\0")
+            .map(|sym| *sym)?;
         let amd_comgr_action_info_set_option_list = __library
             .get(b"amd_comgr_action_info_set_option_list\0")
             .map(|sym| *sym)?;
@@ -370,6 +377,7 @@ impl LibComgr {
             amd_comgr_destroy_action_info,
             amd_comgr_action_info_set_isa_name,
             amd_comgr_action_info_set_language,
+            amd_comgr_action_info_set_logging,
             amd_comgr_action_info_set_option_list,
             amd_comgr_do_action,
         })
@@ -488,6 +496,15 @@ impl LibComgr {
         (self.amd_comgr_action_info_set_language)(action_info, language)
     }
     #[must_use]
+    #[doc = " @brief Set whether logging is enabled for an action info object.\n\n @param[in] action_info A handle to the action info object to be\n updated.\n\n @param[in] logging Whether logging should be enabled or disable.\n\n @retval ::AMD_COMGR_STATUS_SUCCESS The function has\n been executed successfully.\n\n @retval ::AMD_COMGR_STATUS_ERROR_INVALID_ARGUMENT @p\n action_info is an invalid action info object.\n\n @retval ::AMD_COMGR_STATUS_ERROR_OUT_OF_RESOURCES\n Unable to update action info object as out of resources."]
+    pub unsafe fn amd_comgr_action_info_set_logging(
+        &self,
+        action_info: amd_comgr_action_info_t,
+        logging: bool,
+    ) -> amd_comgr_status_t {
+        (self.amd_comgr_action_info_set_logging)(action_info, logging)
+    }
+    #[must_use]
     #[doc = " @brief Set the options array of an action info object.\n\n This overrides any option strings or arrays previously set by calls to this\n function.\n\n An @p action_info object which had its options set with this function can\n only have its option inspected with @p\n amd_comgr_action_info_get_option_list_count and @p\n amd_comgr_action_info_get_option_list_item.\n\n @param[in] action_info A handle to the action info object to be updated.\n\n @param[in] options An array of null terminated strings. May be NULL if @p\n count is zero, which will result in an empty options array.\n\n @param[in] count The number of null terminated strings in @p options.\n\n @retval ::AMD_COMGR_STATUS_SUCCESS The function has been executed\n successfully.\n\n @retval ::AMD_COMGR_STATUS_ERROR_INVALID_ARGUMENT @p action_info is an\n invalid action info object, or @p options is NULL and @p count is non-zero.\n\n @retval ::AMD_COMGR_STATUS_ERROR_OUT_OF_RESOURCES Unable to update action\n info object as out of resources."]
     pub unsafe fn amd_comgr_action_info_set_option_list(
         &self,
@@ -300,6 +300,14 @@ impl<'a> ActionInfo<'a> {
         })
     }
 
+    pub(crate) fn set_logging(&self, logging: bool) -> Result<()> {
+        with!(self.1, comgr => {
+            comgr
+                .amd_comgr_action_info_set_logging(self.0.into(), logging)
+                .into_result()
+        })
+    }
+
     pub(crate) fn execute(
         &self,
         kind: ActionKind,
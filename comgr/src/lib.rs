@@ -24,15 +24,50 @@ static CODE_OBJECT_VERSION_FLAG: &'static [u8] = b"code_object_v5\0";
 
 pub struct Comgr(LibComgr, AtomicU64, u32);
 
+const DEFAULT_OPTIMIZATION_LEVEL: u32 = 3;
+
+fn optimization_flag<'cstr>(level: u32) -> &'cstr CStr {
+    let flag: &'static [u8] = match level {
+        0 => b"-O0\0",
+        1 => b"-O1\0",
+        2 => b"-O2\0",
+        _ => b"-O3\0",
+    };
+    unsafe { CStr::from_bytes_with_nul_unchecked(flag) }
+}
+
 static WAVE32_MODULE: &'static [u8] = include_bytes!("wave32.ll");
 static WAVE32_ON_WAVE64_MODULE: &'static [u8] = include_bytes!("wave32_on_wave64.ll");
 static DOUBLE_WAVE32_ON_WAVE64_MODULE: &'static [u8] = include_bytes!("double_wave32_on_wave64.ll");
 
+static NO_MATRIX_CORES_MODULE: &'static [u8] = include_bytes!("matrix_cores_none.ll");
+static WMMA_MATRIX_CORES_MODULE: &'static [u8] = include_bytes!("matrix_cores_wmma.ll");
+static MFMA_MATRIX_CORES_MODULE: &'static [u8] = include_bytes!("matrix_cores_mfma.ll");
+
 #[cfg(windows)]
 static OS_MODULE: &'static [u8] = include_bytes!("windows.ll");
 #[cfg(not(windows))]
 static OS_MODULE: &'static [u8] = include_bytes!("linux.ll");
 
+// Selects hardware paths of matrix instructions in zluda_ptx_impl and links
+// in matrix core builtins the target supports, `isa` is in the form
+// amdgcn-amd-amdhsa--gfx90a:sramecc+:xnack-. v_wmma_* used by zluda_ptx_impl
+// are wave32-only
+fn matrix_cores_module(isa: &CStr, compilation_mode: CompilationMode) -> &'static [u8] {
+    let isa = isa.to_bytes();
+    let arch = isa.strip_prefix(b"amdgcn-amd-amdhsa--").unwrap_or(isa);
+    if arch.starts_with(b"gfx11") && compilation_mode == CompilationMode::Wave32 {
+        WMMA_MATRIX_CORES_MODULE
+    } else if arch.starts_with(b"gfx908")
+        || arch.starts_with(b"gfx90a")
+        || arch.starts_with(b"gfx94")
+    {
+        MFMA_MATRIX_CORES_MODULE
+    } else {
+        NO_MATRIX_CORES_MODULE
+    }
+}
+
 impl Comgr {
     pub fn find_and_load() -> Result<Self> {
         match unsafe { LibComgr::new() } {
@@ -59,10 +94,39 @@ impl Comgr {
         input_bitcode: impl Iterator<Item = (impl AsRef<[u8]>, impl AsRef<CStr>)>,
         linker_module: &[u8],
     ) -> Result<Vec<u8>> {
-        let bitcode = self.link_bitcode_impl(compilation_mode, isa, input_bitcode)?;
-        let relocatable = self.build_relocatable_impl(compilation_mode, isa, &bitcode)?;
+        self.compile_with_options(
+            compilation_mode,
+            isa,
+            input_bitcode,
+            linker_module,
+            DEFAULT_OPTIMIZATION_LEVEL,
+            None,
+        )
+    }
+
+    // Same as compile(...), but with an explicit optimization level (0-4, as in
+    // CU_JIT_OPTIMIZATION_LEVEL) and, if `log` is set, comgr diagnostics from
+    // every step appended to it. Diagnostics are appended even on failure
+    pub fn compile_with_options<'a>(
+        &self,
+        compilation_mode: CompilationMode,
+        isa: &'a CStr,
+        input_bitcode: impl Iterator<Item = (impl AsRef<[u8]>, impl AsRef<CStr>)>,
+        linker_module: &[u8],
+        optimization_level: u32,
+        mut log: Option<&mut String>,
+    ) -> Result<Vec<u8>> {
+        let bitcode =
+            self.link_bitcode_impl(compilation_mode, isa, input_bitcode, log.as_deref_mut())?;
+        let relocatable = self.build_relocatable_impl(
+            compilation_mode,
+            isa,
+            &bitcode,
+            optimization_level,
+            log.as_deref_mut(),
+        )?;
         if !linker_module.is_empty() {
-            let source = self.assemble_source(isa, linker_module)?;
+            let source = self.assemble_source(isa, linker_module, log.as_deref_mut())?;
             self.link_relocatable_impl(
                 isa,
                 IntoIterator::into_iter([
@@ -72,6 +136,7 @@ impl Comgr {
                     )?,
                     &source,
                 ]),
+                log,
             )
         } else {
             self.link_relocatable_impl(
@@ -80,6 +145,7 @@ impl Comgr {
                     amd_comgr_3::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_RELOCATABLE,
                     0,
                 )?),
+                log,
             )
         }
     }
@@ -121,7 +187,8 @@ impl Comgr {
         isa: &'a CStr,
         input_bitcode: impl Iterator<Item = (impl AsRef<[u8]>, &'a CStr)>,
     ) -> Result<Bitcode<'this>> {
-        let data_set_bitcode = self.link_bitcode_impl(compilation_mode, isa, input_bitcode)?;
+        let data_set_bitcode =
+            self.link_bitcode_impl(compilation_mode, isa, input_bitcode, None)?;
         Ok(Bitcode(data_set_bitcode))
     }
 
@@ -131,7 +198,13 @@ impl Comgr {
         isa: &'a CStr,
         bc: &'this Bitcode,
     ) -> Result<Relocatable<'this>> {
-        let data_set_relocatable = self.build_relocatable_impl(compilation_mode, isa, &bc.0)?;
+        let data_set_relocatable = self.build_relocatable_impl(
+            compilation_mode,
+            isa,
+            &bc.0,
+            DEFAULT_OPTIMIZATION_LEVEL,
+            None,
+        )?;
         let suffix = self.1.fetch_add(1, Ordering::Relaxed);
         Ok(Relocatable::from_data(
             data_set_relocatable.get_data(
@@ -148,8 +221,30 @@ impl Comgr {
         isa: &'a CStr,
         input_bitcode: impl Iterator<Item = (impl AsRef<[u8]>, &'a CStr)>,
     ) -> Result<Relocatable<'this>> {
-        let bitcode = self.link_bitcode_impl(compilation_mode, isa, input_bitcode)?;
-        let data_set_relocatable = self.build_relocatable_impl(compilation_mode, isa, &bitcode)?;
+        self.build_relocatable_with_options(
+            compilation_mode,
+            isa,
+            input_bitcode,
+            DEFAULT_OPTIMIZATION_LEVEL,
+            None,
+        )
+    }
+
+    // First half of compile_with_options(...). Relocatables can be built on
+    // separate threads and then linked together with
+    // link_relocatable_with_options(...)
+    pub fn build_relocatable_with_options<'this, 'a>(
+        &'this self,
+        compilation_mode: CompilationMode,
+        isa: &'a CStr,
+        input_bitcode: impl Iterator<Item = (impl AsRef<[u8]>, impl AsRef<CStr>)>,
+        optimization_level: u32,
+        mut log: Option<&mut String>,
+    ) -> Result<Relocatable<'this>> {
+        let bitcode =
+            self.link_bitcode_impl(compilation_mode, isa, input_bitcode, log.as_deref_mut())?;
+        let data_set_relocatable =
+            self.build_relocatable_impl(compilation_mode, isa, &bitcode, optimization_level, log)?;
         let suffix = self.1.fetch_add(1, Ordering::Relaxed);
         Ok(Relocatable::from_data(
             data_set_relocatable.get_data(
@@ -165,7 +260,28 @@ impl Comgr {
         isa: &'a CStr,
         modules: impl Iterator<Item = &'this Relocatable<'this>>,
     ) -> Result<Vec<u8>> {
-        self.link_relocatable_impl(isa, modules.map(|reloc| &reloc.0))
+        self.link_relocatable_impl(isa, modules.map(|reloc| &reloc.0), None)
+    }
+
+    // Second half of compile_with_options(...), `linker_module` is assembly
+    // source linked together with the relocatables
+    pub fn link_relocatable_with_options<'this, 'a>(
+        &'this self,
+        isa: &'a CStr,
+        modules: impl Iterator<Item = &'this Relocatable<'this>>,
+        linker_module: &[u8],
+        mut log: Option<&mut String>,
+    ) -> Result<Vec<u8>> {
+        if linker_module.is_empty() {
+            self.link_relocatable_impl(isa, modules.map(|reloc| &reloc.0), log)
+        } else {
+            let source = self.assemble_source(isa, linker_module, log.as_deref_mut())?;
+            self.link_relocatable_impl(
+                isa,
+                modules.map(|reloc| &reloc.0).chain(iter::once(&source)),
+                log,
+            )
+        }
     }
 
     pub fn version(&self) -> Result<String> {
@@ -183,6 +299,7 @@ impl Comgr {
             unsafe { CStr::from_bytes_with_nul_unchecked(b"\0") },
             iter::once(unsafe { CStr::from_bytes_with_nul_unchecked(b"-nogpuinc\0") }),
             Some(amd_comgr_3::amd_comgr_language_t::AMD_COMGR_LANGUAGE_HIP),
+            None,
         )?;
         let result = result.get_data(
             amd_comgr_3::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_SOURCE,
@@ -207,6 +324,7 @@ impl Comgr {
         compilation_mode: CompilationMode,
         isa: &'a CStr,
         input_bitcode: impl Iterator<Item = (impl AsRef<[u8]>, impl AsRef<CStr>)>,
+        mut log: Option<&mut String>,
     ) -> Result<DataSet<'this>> {
         let mut bitcode_modules = DataSet::new(self.get())?;
         for (bc, name) in input_bitcode {
@@ -229,6 +347,13 @@ impl Comgr {
             unsafe { CStr::from_bytes_with_nul_unchecked(b"wave.ll\0") },
         )?;
         bitcode_modules.add(&wave_module)?;
+        let matrix_cores_module = Data::new(
+            self.get(),
+            amd_comgr_3::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_BC,
+            matrix_cores_module(isa, compilation_mode),
+            unsafe { CStr::from_bytes_with_nul_unchecked(b"matrix_cores.ll\0") },
+        )?;
+        bitcode_modules.add(&matrix_cores_module)?;
         let os_module = Data::new(
             self.get(),
             amd_comgr_3::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_BC,
@@ -244,6 +369,7 @@ impl Comgr {
                 isa,
                 compiler_options.iter().copied(),
                 None,
+                log.as_deref_mut(),
             )?;
             self.do_action(
                 ActionKind::CompileSourceWithDeviceLibsToBcV3,
@@ -261,6 +387,7 @@ impl Comgr {
                     .into_iter(),
                 ),
                 Some(amd_comgr_3::amd_comgr_language_t::AMD_COMGR_LANGUAGE_LLVM_IR),
+                log,
             )
         } else {
             let mut comgr_options =
@@ -277,6 +404,7 @@ impl Comgr {
                 isa,
                 comgr_options.iter().copied(), //.chain([wavefront_option].into_iter()),
                 Some(amd_comgr_3::amd_comgr_language_t::AMD_COMGR_LANGUAGE_OPENCL_2_0),
+                log.as_deref_mut(),
             )?;
             comgr_options.truncate(1);
             self.do_action(
@@ -285,6 +413,7 @@ impl Comgr {
                 isa,
                 comgr_options.into_iter(),
                 None,
+                log,
             )
         }
     }
@@ -294,6 +423,8 @@ impl Comgr {
         compilation_mode: CompilationMode,
         isa: &'a CStr,
         bc_linking_output: &DataSet<'this>,
+        optimization_level: u32,
+        log: Option<&mut String>,
     ) -> Result<DataSet<'this>> {
         let debug_level = if cfg!(debug_assertions) {
             unsafe {
@@ -328,7 +459,7 @@ impl Comgr {
                 .chain(
                     unsafe {
                         [
-                            CStr::from_bytes_with_nul_unchecked(b"-O3\0"),
+                            optimization_flag(optimization_level),
                             // TODO: measure more
                             // Slightly more efficient in Blender
                             CStr::from_bytes_with_nul_unchecked(b"-mcumode\0"),
@@ -350,6 +481,7 @@ impl Comgr {
                 )
                 .chain(debug_level.into_iter()),
             None,
+            log,
         )?;
         Ok(relocatable)
     }
@@ -358,6 +490,7 @@ impl Comgr {
         &'this self,
         isa: &'a CStr,
         modules: impl Iterator<Item = &'this Data<'this>>,
+        log: Option<&mut String>,
     ) -> Result<Vec<u8>> {
         let mut input = DataSet::new(self.get())?;
         for module in modules {
@@ -375,6 +508,7 @@ impl Comgr {
             }
             .into_iter(),
             None,
+            log,
         )?;
         let executable_data = executable_set.get_data(
             amd_comgr_3::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_EXECUTABLE,
@@ -383,7 +517,7 @@ impl Comgr {
         executable_data.get_data()
     }
 
-    fn assemble_source(&self, isa: &CStr, src: &[u8]) -> Result<Data> {
+    fn assemble_source(&self, isa: &CStr, src: &[u8], log: Option<&mut String>) -> Result<Data> {
         let data = Data::new(
             self.get(),
             amd_comgr_3::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_SOURCE,
@@ -398,6 +532,7 @@ impl Comgr {
             isa,
             iter::once(unsafe { self.get_code_object_version_flag() }),
             None,
+            log,
         )?;
         assembled.get_data(
             amd_comgr_3::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_RELOCATABLE,
@@ -412,6 +547,7 @@ impl Comgr {
         isa: &CStr,
         options: impl Iterator<Item = &'cstr CStr>,
         language: Option<amd_comgr_3::amd_comgr_language_t>,
+        log: Option<&mut String>,
     ) -> Result<DataSet<'a>> {
         let output = DataSet::new(self.get())?;
         let action = ActionInfo::new(self.get(), isa)?;
@@ -421,18 +557,73 @@ impl Comgr {
         if let Some(lang) = language {
             action.set_language(lang)?;
         }
-        if let LibComgr::V3(_) = self.0 {
-            action.execute3(kind, &input, &output)?;
+        if log.is_some() {
+            action.set_logging(true)?;
+        }
+        let result = if let LibComgr::V3(_) = self.0 {
+            action.execute3(kind, &input, &output)
         } else {
-            action.execute(kind, &input, &output)?;
+            action.execute(kind, &input, &output)
+        };
+        if let Some(log) = log {
+            // Comgr produces the log even if the action itself has failed
+            if let Ok(log_data) = output
+                .get_data(
+                    amd_comgr_3::amd_comgr_data_kind_t::AMD_COMGR_DATA_KIND_LOG,
+                    0,
+                )
+                .and_then(|data| data.get_data())
+            {
+                log.push_str(&String::from_utf8_lossy(&log_data));
+            }
         }
+        result?;
         Ok(output)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::Comgr;
+    use crate::{
+        matrix_cores_module, Comgr, MFMA_MATRIX_CORES_MODULE, NO_MATRIX_CORES_MODULE,
+        WMMA_MATRIX_CORES_MODULE,
+    };
+    use hip_common::CompilationMode;
+    use std::ffi::CStr;
+
+    #[test]
+    fn matrix_cores_by_isa() {
+        let module = |isa: &[u8]| {
+            matrix_cores_module(
+                CStr::from_bytes_with_nul(isa).unwrap(),
+                CompilationMode::Wave32,
+            )
+        };
+        assert_eq!(
+            module(b"amdgcn-amd-amdhsa--gfx1100\0"),
+            WMMA_MATRIX_CORES_MODULE
+        );
+        assert_eq!(
+            matrix_cores_module(
+                CStr::from_bytes_with_nul(b"amdgcn-amd-amdhsa--gfx1100\0").unwrap(),
+                CompilationMode::Wave32OnWave64
+            ),
+            NO_MATRIX_CORES_MODULE
+        );
+        assert_eq!(
+            module(b"amdgcn-amd-amdhsa--gfx90a:sramecc+:xnack-\0"),
+            MFMA_MATRIX_CORES_MODULE
+        );
+        assert_eq!(module(b"gfx942\0"), MFMA_MATRIX_CORES_MODULE);
+        assert_eq!(
+            module(b"amdgcn-amd-amdhsa--gfx1030\0"),
+            NO_MATRIX_CORES_MODULE
+        );
+        assert_eq!(
+            module(b"amdgcn-amd-amdhsa--gfx906:sramecc-:xnack-\0"),
+            NO_MATRIX_CORES_MODULE
+        );
+    }
 
     #[test]
     fn version() {
@@ -0,0 +1,31 @@
+target datalayout = "e-p:64:64-p1:64:64-p2:32:32-p3:32:32-p4:64:64-p5:32:32-p6:32:32-i64:64-v16:16-v24:32-v32:32-v48:64-v96:128-v192:256-v256:256-v512:512-v1024:1024-v2048:2048-n32:64-S32-A5-G1-ni:7"
+target triple = "amdgcn-amd-amdhsa"
+
+@__zluda_ptx_impl__MATRIX_CORES = linkonce_odr local_unnamed_addr addrspace(4) constant i8 2, align 1
+
+define linkonce_odr <8 x float> @__zluda_ptx_impl__matrix_wmma_f32_16x16x16_f16(<16 x half> %a, <16 x half> %b, <8 x float> %c) #0 {
+  unreachable
+}
+
+define linkonce_odr <8 x float> @__zluda_ptx_impl__matrix_wmma_f32_16x16x16_bf16(<16 x i16> %a, <16 x i16> %b, <8 x float> %c) #0 {
+  unreachable
+}
+
+define linkonce_odr <8 x i32> @__zluda_ptx_impl__matrix_wmma_i32_16x16x16_iu8(i1 zeroext %a_signed, <4 x i32> %a, i1 zeroext %b_signed, <4 x i32> %b, <8 x i32> %c, i1 zeroext %clamp) #0 {
+  unreachable
+}
+
+define linkonce_odr <4 x float> @__zluda_ptx_impl__matrix_mfma_f32_4x4x4_f16(<4 x half> %a, <4 x half> %b, <4 x float> %c) #0 {
+  %d = call <4 x float> @llvm.amdgcn.mfma.f32.4x4x4f16(<4 x half> %a, <4 x half> %b, <4 x float> %c, i32 0, i32 0, i32 0)
+  ret <4 x float> %d
+}
+
+define linkonce_odr <4 x i32> @__zluda_ptx_impl__matrix_mfma_i32_4x4x4_i8(i32 %a, i32 %b, <4 x i32> %c) #0 {
+  %d = call <4 x i32> @llvm.amdgcn.mfma.i32.4x4x4i8(i32 %a, i32 %b, <4 x i32> %c, i32 0, i32 0, i32 0)
+  ret <4 x i32> %d
+}
+
+declare <4 x float> @llvm.amdgcn.mfma.f32.4x4x4f16(<4 x half>, <4 x half>, <4 x float>, i32 immarg, i32 immarg, i32 immarg)
+declare <4 x i32> @llvm.amdgcn.mfma.i32.4x4x4i8(i32, i32, <4 x i32>, i32 immarg, i32 immarg, i32 immarg)
+
+attributes #0 = { alwaysinline convergent nounwind }
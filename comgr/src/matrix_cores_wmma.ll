@@ -0,0 +1,80 @@
+target datalayout = "e-p:64:64-p1:64:64-p2:32:32-p3:32:32-p4:64:64-p5:32:32-p6:32:32-i64:64-v16:16-v24:32-v32:32-v48:64-v96:128-v192:256-v256:256-v512:512-v1024:1024-v2048:2048-n32:64-S32-A5-G1-ni:7"
+target triple = "amdgcn-amd-amdhsa"
+
+@__zluda_ptx_impl__MATRIX_CORES = linkonce_odr local_unnamed_addr addrspace(4) constant i8 1, align 1
+
+define linkonce_odr <8 x float> @__zluda_ptx_impl__matrix_wmma_f32_16x16x16_f16(<16 x half> %a, <16 x half> %b, <8 x float> %c) #0 {
+  %d = call <8 x float> @llvm.amdgcn.wmma.f32.16x16x16.f16(<16 x half> %a, <16 x half> %b, <8 x float> %c)
+  ret <8 x float> %d
+}
+
+define linkonce_odr <8 x float> @__zluda_ptx_impl__matrix_wmma_f32_16x16x16_bf16(<16 x i16> %a, <16 x i16> %b, <8 x float> %c) #0 {
+  %d = call <8 x float> @llvm.amdgcn.wmma.f32.16x16x16.bf16(<16 x i16> %a, <16 x i16> %b, <8 x float> %c)
+  ret <8 x float> %d
+}
+
+; Signedness and clamping are immediate operands of the instruction
+define linkonce_odr <8 x i32> @__zluda_ptx_impl__matrix_wmma_i32_16x16x16_iu8(i1 zeroext %a_signed, <4 x i32> %a, i1 zeroext %b_signed, <4 x i32> %b, <8 x i32> %c, i1 zeroext %clamp) #0 {
+  %a_bit = zext i1 %a_signed to i32
+  %b_bit = zext i1 %b_signed to i32
+  %clamp_bit = zext i1 %clamp to i32
+  %a_shifted = shl i32 %a_bit, 2
+  %b_shifted = shl i32 %b_bit, 1
+  %ab = or i32 %a_shifted, %b_shifted
+  %variant = or i32 %ab, %clamp_bit
+  switch i32 %variant, label %uuc [
+    i32 0, label %uu
+    i32 2, label %us
+    i32 3, label %usc
+    i32 4, label %su
+    i32 5, label %suc
+    i32 6, label %ss
+    i32 7, label %ssc
+  ]
+
+uu:
+  %d_uu = call <8 x i32> @llvm.amdgcn.wmma.i32.16x16x16.iu8(i1 false, <4 x i32> %a, i1 false, <4 x i32> %b, <8 x i32> %c, i1 false)
+  ret <8 x i32> %d_uu
+
+uuc:
+  %d_uuc = call <8 x i32> @llvm.amdgcn.wmma.i32.16x16x16.iu8(i1 false, <4 x i32> %a, i1 false, <4 x i32> %b, <8 x i32> %c, i1 true)
+  ret <8 x i32> %d_uuc
+
+us:
+  %d_us = call <8 x i32> @llvm.amdgcn.wmma.i32.16x16x16.iu8(i1 false, <4 x i32> %a, i1 true, <4 x i32> %b, <8 x i32> %c, i1 false)
+  ret <8 x i32> %d_us
+
+usc:
+  %d_usc = call <8 x i32> @llvm.amdgcn.wmma.i32.16x16x16.iu8(i1 false, <4 x i32> %a, i1 true, <4 x i32> %b, <8 x i32> %c, i1 true)
+  ret <8 x i32> %d_usc
+
+su:
+  %d_su = call <8 x i32> @llvm.amdgcn.wmma.i32.16x16x16.iu8(i1 true, <4 x i32> %a, i1 false, <4 x i32> %b, <8 x i32> %c, i1 false)
+  ret <8 x i32> %d_su
+
+suc:
+  %d_suc = call <8 x i32> @llvm.amdgcn.wmma.i32.16x16x16.iu8(i1 true, <4 x i32> %a, i1 false, <4 x i32> %b, <8 x i32> %c, i1 true)
+  ret <8 x i32> %d_suc
+
+ss:
+  %d_ss = call <8 x i32> @llvm.amdgcn.wmma.i32.16x16x16.iu8(i1 true, <4 x i32> %a, i1 true, <4 x i32> %b, <8 x i32> %c, i1 false)
+  ret <8 x i32> %d_ss
+
+ssc:
+  %d_ssc = call <8 x i32> @llvm.amdgcn.wmma.i32.16x16x16.iu8(i1 true, <4 x i32> %a, i1 true, <4 x i32> %b, <8 x i32> %c, i1 true)
+  ret <8 x i32> %d_ssc
+}
+
+declare <8 x float> @llvm.amdgcn.wmma.f32.16x16x16.f16(<16 x half>, <16 x half>, <8 x float>)
+declare <8 x float> @llvm.amdgcn.wmma.f32.16x16x16.bf16(<16 x i16>, <16 x i16>, <8 x float>)
+declare <8 x i32> @llvm.amdgcn.wmma.i32.16x16x16.iu8(i1 immarg, <4 x i32>, i1 immarg, <4 x i32>, <8 x i32>, i1 immarg)
+
+define linkonce_odr <4 x float> @__zluda_ptx_impl__matrix_mfma_f32_4x4x4_f16(<4 x half> %a, <4 x half> %b, <4 x float> %c) #0 {
+  unreachable
+}
+
+define linkonce_odr <4 x i32> @__zluda_ptx_impl__matrix_mfma_i32_4x4x4_i8(i32 %a, i32 %b, <4 x i32> %c) #0 {
+  unreachable
+}
+
+attributes #0 = { alwaysinline convergent nounwind }
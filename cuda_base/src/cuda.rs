@@ -3642,6 +3642,19 @@ extern "C" {
         name: *const ::std::os::raw::c_char,
     ) -> CUresult;
 }
+extern "C" {
+    pub fn cuModuleGetFunctionCount(
+        count: *mut ::std::os::raw::c_uint,
+        mod_: CUmodule,
+    ) -> CUresult;
+}
+extern "C" {
+    pub fn cuModuleEnumerateFunctions(
+        functions: *mut CUfunction,
+        numFunctions: ::std::os::raw::c_uint,
+        mod_: CUmodule,
+    ) -> CUresult;
+}
 extern "C" {
     pub fn cuModuleGetGlobal_v2(
         dptr: *mut CUdeviceptr,
@@ -4775,6 +4788,9 @@ extern "C" {
 extern "C" {
     pub fn cuFuncGetModule(hmod: *mut CUmodule, hfunc: CUfunction) -> CUresult;
 }
+extern "C" {
+    pub fn cuFuncGetName(name: *mut *const ::std::os::raw::c_char, hfunc: CUfunction) -> CUresult;
+}
 extern "C" {
     pub fn cuLaunchKernel_ptsz(
         f: CUfunction,
@@ -358,22 +358,21 @@ fn cuda_derive_display_trait_for_item(
                 let original_fn_name = ident.to_string();
                 Some(match arg_name_iter.next() {
                     Some(first_arg_name) => quote! {
-                        pub fn #fn_name(writer: &mut (impl std::io::Write + ?Sized), #(#inputs_iter,)*) -> std::io::Result<()> {
-                            writer.write_all(concat!("(", stringify!(#first_arg_name), ": ").as_bytes())?;
+                        pub fn #fn_name(writer: &mut ArgumentsWriter, #(#inputs_iter,)*) -> std::io::Result<()> {
+                            writer.begin_argument(stringify!(#first_arg_name))?;
                             let mut arg_idx = 0usize;
                             CudaDisplay::write(&#first_arg_name, #original_fn_name, arg_idx, writer)?;
                             #(
-                                writer.write_all(b", ")?;
-                                writer.write_all(concat!(stringify!(#arg_name_iter), ": ").as_bytes())?;
+                                writer.begin_argument(stringify!(#arg_name_iter))?;
                                 CudaDisplay::write(&#arg_name_iter, #original_fn_name, arg_idx, writer)?;
                                 arg_idx += 1;
                             )*
-                            writer.write_all(b")")
+                            writer.finish()
                         }
                     },
                     None => quote! {
-                        pub fn #fn_name(writer: &mut (impl std::io::Write + ?Sized)) -> std::io::Result<()> {
-                            writer.write_all(b"()")
+                        pub fn #fn_name(writer: &mut ArgumentsWriter) -> std::io::Result<()> {
+                            writer.finish()
                         }
                     },
                 })
@@ -1,18 +1,56 @@
 use itertools::Itertools;
 use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
-use rusqlite::Connection;
+use rusqlite::{Connection, OpenFlags};
 use std::ffi::{CStr, CString};
 use std::fmt::Display;
 use std::marker::PhantomData;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{self, SystemTime};
+use std::{env, fs};
+
+// Name of the database file in the cache directory used by ZLUDA
+pub const CACHE_FILE: &'static str = "zluda.db";
+pub const DEFAULT_MAX_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+
+// Removes least recently used kernels, keeping only as many of the most
+// recently used as fit in max_size bytes
+const EVICT_KERNELS: &'static str = "
+    DELETE FROM
+        kernels
+    WHERE
+        id IN (
+            SELECT
+                id
+            FROM
+                (
+                    SELECT
+                        id,
+                        SUM(length(binary)) OVER (ORDER BY last_used DESC, id DESC) AS kept_size
+                    FROM
+                        kernels
+                )
+            WHERE
+                kept_size > ?1
+        );";
 
 pub trait KernelExtendedData {
     const INPUT_COLUMNS: &'static [[&'static str; 2]];
 }
 
+// Columns identifying a kernel in every repository, values of
+// KernelExtendedData::INPUT_COLUMNS are passed separately
+pub struct KernelKey<'a> {
+    pub hash: &'a str,
+    pub compiler_version: &'a str,
+    pub git_hash: &'a str,
+    pub device: &'a CStr,
+}
+
 pub struct KernelRepository<T: KernelExtendedData> {
     cache_file: Option<PathBuf>,
+    // Read-only repositories are never modified, not even last_used
+    read_only: bool,
+    max_size: Option<u64>,
     insert_kernel: String,
     update_last_used: String,
     select_kernel: String,
@@ -25,6 +63,45 @@ pub struct KernelRepository<T: KernelExtendedData> {
 // const generics, but &'static str const generics are currently illegal
 impl<T: KernelExtendedData> KernelRepository<T> {
     pub fn new(cache_file: Option<PathBuf>) -> rusqlite::Result<Self> {
+        let result = Self::new_impl(cache_file, false);
+        let mut connection = result.connect()?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "synchronous", "normal")?;
+        // Deferred transaction here can lead to SQLITE_BUSY errors
+        let tx = connection.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+        {
+            tx.set_db_config(
+                rusqlite::config::DbConfig::SQLITE_DBCONFIG_ENABLE_FKEY,
+                true,
+            )?;
+            tx.set_db_config(
+                rusqlite::config::DbConfig::SQLITE_DBCONFIG_ENABLE_TRIGGER,
+                true,
+            )?;
+            tx.execute_batch(&Self::create_tables())?;
+        }
+        tx.commit()?;
+        Ok(result)
+    }
+
+    // Opens existing cache file without creating or modifying anything. Used
+    // for caches shared between multiple users
+    pub fn new_read_only(cache_file: PathBuf) -> rusqlite::Result<Self> {
+        let result = Self::new_impl(Some(cache_file), true);
+        result
+            .connect()?
+            .query_row("SELECT count(*) FROM kernels", [], |_| Ok(()))?;
+        Ok(result)
+    }
+
+    // Least recently used kernels are removed when the total size of
+    // binaries in the repository exceeds max_size
+    pub fn with_max_size(mut self, max_size: Option<u64>) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    fn create_tables() -> String {
         let create_tables = {
             let input_columns = Self::columns_comma(T::INPUT_COLUMNS);
             let input_columns_with_type = Self::columns_comma_type(T::INPUT_COLUMNS);
@@ -68,6 +145,10 @@ impl<T: KernelExtendedData> KernelRepository<T> {
             END;
             INSERT OR IGNORE INTO globals (key, value) VALUES ('total_binary_size', 0);")
         };
+        create_tables
+    }
+
+    fn new_impl(cache_file: Option<PathBuf>, read_only: bool) -> Self {
         let insert_kernel = {
             let input_columns = Self::columns_comma(T::INPUT_COLUMNS);
             let arg_markers = (0..T::INPUT_COLUMNS.len())
@@ -102,35 +183,25 @@ impl<T: KernelExtendedData> KernelRepository<T> {
             WHERE
                 hash = ?1 AND compiler_version = ?2 AND git_hash = ?3 AND device = ?4 AND is_windows = ?5 AND is_debug = ?6 {input_columns};")
         };
-        let result = Self {
+        Self {
             cache_file,
+            read_only,
+            max_size: None,
             insert_kernel,
             update_last_used,
             select_kernel,
             _marker: PhantomData,
-        };
-        let mut connection = result.connect()?;
-        connection.pragma_update(None, "journal_mode", "WAL")?;
-        connection.pragma_update(None, "synchronous", "normal")?;
-        // Deferred transaction here can lead to SQLITE_BUSY errors
-        let tx = connection.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
-        {
-            tx.set_db_config(
-                rusqlite::config::DbConfig::SQLITE_DBCONFIG_ENABLE_FKEY,
-                true,
-            )?;
-            tx.set_db_config(
-                rusqlite::config::DbConfig::SQLITE_DBCONFIG_ENABLE_TRIGGER,
-                true,
-            )?;
-            tx.execute_batch(&create_tables)?;
         }
-        tx.commit()?;
-        Ok(result)
     }
 
     pub fn connect(&self) -> rusqlite::Result<Connection> {
         match self.cache_file {
+            Some(ref file) if self.read_only => rusqlite::Connection::open_with_flags(
+                file,
+                OpenFlags::SQLITE_OPEN_READ_ONLY
+                    | OpenFlags::SQLITE_OPEN_URI
+                    | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            ),
             Some(ref file) => rusqlite::Connection::open(file),
             None => rusqlite::Connection::open_in_memory(),
         }
@@ -139,23 +210,23 @@ impl<T: KernelExtendedData> KernelRepository<T> {
     pub fn save_program(
         &self,
         now: i64,
-        hash: &str,
-        compiler_version: &str,
-        git_hash: &str,
-        device: &CStr,
+        key: &KernelKey,
         binary: &[u8],
         input_values: &[&dyn rusqlite::ToSql],
     ) -> rusqlite::Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
         let connection = self.connect()?;
         let tx = connection.unchecked_transaction()?;
         {
             let mut insert_kernel = tx.prepare(&self.insert_kernel)?;
             let common_values = rusqlite::params![
                 now,
-                hash,
-                compiler_version,
-                git_hash,
-                SqlCStrRef(device),
+                key.hash,
+                key.compiler_version,
+                key.git_hash,
+                SqlCStrRef(key.device),
                 cfg!(windows),
                 cfg!(debug_assertions),
                 binary
@@ -163,6 +234,9 @@ impl<T: KernelExtendedData> KernelRepository<T> {
             insert_kernel.execute(rusqlite::params_from_iter(
                 IntoIterator::into_iter([common_values, input_values]).flatten(),
             ))?;
+            if let Some(max_size) = self.max_size {
+                evict_least_recently_used(&tx, max_size)?;
+            }
         }
         tx.commit()
     }
@@ -170,10 +244,7 @@ impl<T: KernelExtendedData> KernelRepository<T> {
     pub fn try_load_program(
         &self,
         now: i64,
-        hash: &str,
-        compiler_version: &str,
-        git_hash: &str,
-        device: &CStr,
+        key: &KernelKey,
         input_values: &[&dyn rusqlite::ToSql],
     ) -> rusqlite::Result<Option<Vec<u8>>> {
         let connection = self.connect()?;
@@ -182,19 +253,21 @@ impl<T: KernelExtendedData> KernelRepository<T> {
         {
             let common_params = rusqlite::params![
                 now,
-                hash,
-                compiler_version,
-                git_hash,
-                SqlCStrRef(device),
+                key.hash,
+                key.compiler_version,
+                key.git_hash,
+                SqlCStrRef(key.device),
                 cfg!(windows),
                 cfg!(debug_assertions)
             ];
-            let mut statement = tx.prepare(&self.update_last_used)?;
-            let rows_affected = statement.execute(rusqlite::params_from_iter(
-                IntoIterator::into_iter([common_params, input_values]).flatten(),
-            ))?;
-            if rows_affected == 0 {
-                return Ok(None);
+            if !self.read_only {
+                let mut statement = tx.prepare(&self.update_last_used)?;
+                let rows_affected = statement.execute(rusqlite::params_from_iter(
+                    IntoIterator::into_iter([common_params, input_values]).flatten(),
+                ))?;
+                if rows_affected == 0 {
+                    return Ok(None);
+                }
             }
             let mut select_kernel = tx.prepare(&self.select_kernel)?;
             let mut kernel_rows = select_kernel.query(rusqlite::params_from_iter(
@@ -235,6 +308,161 @@ impl<T: KernelExtendedData> KernelRepository<T> {
     }
 }
 
+// Returns the number of removed kernels
+pub fn evict_least_recently_used(
+    connection: &Connection,
+    max_size: u64,
+) -> rusqlite::Result<usize> {
+    let total_size = connection.query_row(
+        "SELECT value FROM globals WHERE key = 'total_binary_size'",
+        [],
+        |row| row.get::<_, i64>(0),
+    )?;
+    if total_size as u64 <= max_size {
+        return Ok(0);
+    }
+    connection.execute(EVICT_KERNELS, [max_size as i64])
+}
+
+// Cache configuration shared by ZLUDA and zluda-cache. Settings are read from
+// the config file (ZLUDA_CACHE_CONFIG or <config directory>/ZLUDA/cache.conf)
+// and then overridden by environment variables:
+// * ZLUDA_CACHE_DIR: directory with the cache
+// * ZLUDA_CACHE_SHARED_DIR: directory with an additional read-only cache,
+//   checked when a kernel is not found in the main cache
+// * ZLUDA_CACHE_MAX_SIZE: size limit of the main cache, e.g. 512M or 10G,
+//   0 means no limit
+// * ZLUDA_CACHE_READ_ONLY: if set to 1, the main cache is never written to
+// Config file has one `key = value` pair per line with keys `directory`,
+// `shared_directory`, `max_size` and `read_only`, lines starting with # are
+// ignored
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheSettings {
+    pub directory: Option<PathBuf>,
+    pub shared_directory: Option<PathBuf>,
+    pub max_size: Option<u64>,
+    pub read_only: bool,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        let directory = dirs::cache_dir().map(|mut directory| {
+            directory.push("ZLUDA");
+            directory.push("ComputeCache");
+            directory
+        });
+        Self {
+            directory,
+            shared_directory: None,
+            max_size: Some(DEFAULT_MAX_SIZE),
+            read_only: false,
+        }
+    }
+}
+
+impl CacheSettings {
+    const ENV_VARIABLES: [(&'static str, &'static str); 4] = [
+        ("directory", "ZLUDA_CACHE_DIR"),
+        ("shared_directory", "ZLUDA_CACHE_SHARED_DIR"),
+        ("max_size", "ZLUDA_CACHE_MAX_SIZE"),
+        ("read_only", "ZLUDA_CACHE_READ_ONLY"),
+    ];
+
+    // Invalid values are ignored
+    pub fn load() -> Self {
+        let mut settings = Self::default();
+        if let Some(config) = Self::config_file().and_then(|file| fs::read_to_string(file).ok()) {
+            settings.apply_config(&config);
+        }
+        for (key, variable) in Self::ENV_VARIABLES {
+            if let Ok(value) = env::var(variable) {
+                settings.set(key, &value);
+            }
+        }
+        settings
+    }
+
+    pub fn config_file() -> Option<PathBuf> {
+        match env::var_os("ZLUDA_CACHE_CONFIG") {
+            Some(file) => Some(PathBuf::from(file)),
+            None => dirs::config_dir().map(|mut file| {
+                file.push("ZLUDA");
+                file.push("cache.conf");
+                file
+            }),
+        }
+    }
+
+    pub fn cache_file(&self) -> Option<PathBuf> {
+        self.directory
+            .as_deref()
+            .map(|directory| directory.join(CACHE_FILE))
+    }
+
+    pub fn shared_cache_file(&self) -> Option<PathBuf> {
+        self.shared_directory
+            .as_deref()
+            .map(|directory| directory.join(CACHE_FILE))
+    }
+
+    pub fn apply_config(&mut self, config: &str) {
+        for line in config.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                self.set(key.trim(), value.trim());
+            }
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Option<()> {
+        match key {
+            "directory" => self.directory = Some(non_empty_path(value)?),
+            "shared_directory" => self.shared_directory = Some(non_empty_path(value)?),
+            "max_size" => self.max_size = Some(parse_size(value)?).filter(|size| *size != 0),
+            "read_only" => {
+                self.read_only = match value {
+                    "0" | "false" => false,
+                    "1" | "true" => true,
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+fn non_empty_path(value: &str) -> Option<PathBuf> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(Path::new(value).to_path_buf())
+    }
+}
+
+// Accepts plain number of bytes or a number with K, M, G or T suffix (powers
+// of 1024), optionally followed by B or iB
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let value = value
+        .strip_suffix("iB")
+        .or_else(|| value.strip_suffix('B'))
+        .unwrap_or(value);
+    let (number, shift) = match value.char_indices().last()? {
+        (index, 'K') | (index, 'k') => (&value[..index], 10),
+        (index, 'M') | (index, 'm') => (&value[..index], 20),
+        (index, 'G') | (index, 'g') => (&value[..index], 30),
+        (index, 'T') | (index, 't') => (&value[..index], 40),
+        _ => (value, 0),
+    };
+    str::parse::<u64>(number.trim())
+        .ok()?
+        .checked_mul(1 << shift)
+}
+
 pub struct SqlCStrRef<'a>(pub &'a CStr);
 
 impl<'a> rusqlite::ToSql for SqlCStrRef<'a> {
@@ -255,3 +483,128 @@ impl FromSql for SqlCString {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_size, CacheSettings, KernelExtendedData, KernelKey, KernelRepository};
+    use std::ffi::CString;
+
+    struct NoExtendedData;
+
+    impl KernelExtendedData for NoExtendedData {
+        const INPUT_COLUMNS: &'static [[&'static str; 2]] = &[];
+    }
+
+    fn key<'a>(hash: &'a str, device: &'a CString) -> KernelKey<'a> {
+        KernelKey {
+            hash,
+            compiler_version: "Clang 15",
+            git_hash: "EEEE",
+            device,
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let directory = tempfile::tempdir().unwrap();
+        let repository =
+            KernelRepository::<NoExtendedData>::new(Some(directory.path().join("test.db")))
+                .unwrap()
+                .with_max_size(Some(8));
+        let device = CString::new("gfx1030").unwrap();
+        for (now, hash) in [(1, "A"), (2, "B")] {
+            repository
+                .save_program(now, &key(hash, &device), &[0; 4], &[])
+                .unwrap();
+        }
+        // Loading A makes B the least recently used kernel
+        assert!(repository
+            .try_load_program(3, &key("A", &device), &[])
+            .unwrap()
+            .is_some());
+        repository
+            .save_program(4, &key("C", &device), &[0; 4], &[])
+            .unwrap();
+        let load = |hash| {
+            repository
+                .try_load_program(5, &key(hash, &device), &[])
+                .unwrap()
+                .is_some()
+        };
+        assert!(load("A"));
+        assert!(!load("B"));
+        assert!(load("C"));
+        let total_size = repository
+            .connect()
+            .unwrap()
+            .query_row(
+                "SELECT value FROM globals WHERE key = 'total_binary_size'",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .unwrap();
+        assert_eq!(total_size, 8);
+    }
+
+    #[test]
+    fn read_only_repository_is_not_modified() {
+        let directory = tempfile::tempdir().unwrap();
+        let file = directory.path().join("test.db");
+        let device = CString::new("gfx1030").unwrap();
+        KernelRepository::<NoExtendedData>::new(Some(file.clone()))
+            .unwrap()
+            .save_program(1, &key("A", &device), &[1, 2], &[])
+            .unwrap();
+        let repository = KernelRepository::<NoExtendedData>::new_read_only(file).unwrap();
+        repository
+            .save_program(2, &key("B", &device), &[3, 4], &[])
+            .unwrap();
+        assert_eq!(
+            repository
+                .try_load_program(3, &key("A", &device), &[])
+                .unwrap(),
+            Some(vec![1, 2])
+        );
+        assert_eq!(
+            repository
+                .try_load_program(3, &key("B", &device), &[])
+                .unwrap(),
+            None
+        );
+        let last_used = repository
+            .connect()
+            .unwrap()
+            .query_row("SELECT last_used FROM kernels", [], |row| {
+                row.get::<_, i64>(0)
+            })
+            .unwrap();
+        assert_eq!(last_used, 1);
+    }
+
+    #[test]
+    fn parse_sizes() {
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("512M"), Some(512 * 1024 * 1024));
+        assert_eq!(parse_size("10 GiB"), Some(10 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("2kB"), Some(2048));
+        assert_eq!(parse_size("G"), None);
+        assert_eq!(parse_size("-1"), None);
+    }
+
+    #[test]
+    fn config_file() {
+        let mut settings = CacheSettings::default();
+        settings.apply_config(
+            "
+            # comment
+            directory = /tmp/zluda
+            max_size = 0
+            read_only = true
+            unknown = 1
+            ",
+        );
+        assert_eq!(settings.directory.as_deref(), Some("/tmp/zluda".as_ref()));
+        assert_eq!(settings.max_size, None);
+        assert!(settings.read_only);
+    }
+}
@@ -114,4 +114,16 @@ pub mod elf {
         // sections header table
         std::slice::from_raw_parts(ptr as _, size)
     }
+
+    // Every kernel in an AMDGPU code object has a matching `<name>.kd` kernel
+    // descriptor symbol
+    pub fn kernel_names(binary: &[u8]) -> Option<Vec<&str>> {
+        let elf = goblin::elf::Elf::parse(binary).ok()?;
+        Some(
+            elf.dynsyms
+                .iter()
+                .filter_map(|symbol| elf.dynstrtab.get_at(symbol.st_name)?.strip_suffix(".kd"))
+                .collect(),
+        )
+    }
 }
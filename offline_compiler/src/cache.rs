@@ -0,0 +1,115 @@
+use hip_common::{
+    cache::{KernelExtendedData, KernelKey, KernelRepository},
+    CompilationMode,
+};
+use std::{ffi::CStr, path::PathBuf};
+
+// Keys must be the same as in zluda/src/impl/cache.rs, otherwise ZLUDA
+// won't find kernels compiled here
+pub(crate) struct KernelCache {
+    repository: KernelRepository<NoExtendedData>,
+    compiler_version: String,
+}
+
+impl KernelCache {
+    pub(crate) fn new(file: PathBuf, compiler_version: String) -> rusqlite::Result<Self> {
+        Ok(Self {
+            repository: KernelRepository::new(Some(file))?,
+            compiler_version,
+        })
+    }
+
+    pub(crate) fn contains(
+        &self,
+        device: &CStr,
+        ptx_text: &str,
+        compilation_mode: CompilationMode,
+    ) -> bool {
+        let now = match KernelRepository::<NoExtendedData>::now() {
+            Ok(now) => now,
+            Err(_) => return false,
+        };
+        let hash = blake3::hash(ptx_text.as_bytes()).to_hex();
+        let key = KernelKey {
+            hash: hash.as_str(),
+            compiler_version: &self.compiler_version,
+            git_hash: env!("VERGEN_GIT_SHA"),
+            device,
+        };
+        self.repository
+            .try_load_program(now, &key, rusqlite::params![compilation_mode as u8])
+            .ok()
+            .flatten()
+            .is_some()
+    }
+
+    pub(crate) fn save_program(
+        &self,
+        device: &CStr,
+        ptx_text: &str,
+        compilation_mode: CompilationMode,
+        binary: &[u8],
+    ) -> rusqlite::Result<()> {
+        let now = KernelRepository::<NoExtendedData>::now()
+            .map_err(|err| rusqlite::Error::ToSqlConversionFailure(Box::new(err)))?;
+        let hash = blake3::hash(ptx_text.as_bytes()).to_hex();
+        let key = KernelKey {
+            hash: hash.as_str(),
+            compiler_version: &self.compiler_version,
+            git_hash: env!("VERGEN_GIT_SHA"),
+            device,
+        };
+        self.repository
+            .save_program(now, &key, binary, rusqlite::params![compilation_mode as u8])
+    }
+}
+
+struct NoExtendedData;
+
+impl KernelExtendedData for NoExtendedData {
+    const INPUT_COLUMNS: &'static [[&'static str; 2]] = &[["compilation_mode", "INTEGER NOT NULL"]];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KernelCache;
+    use hip_common::CompilationMode;
+    use std::ffi::CString;
+
+    #[test]
+    fn kernels_are_keyed_like_in_zluda() {
+        let directory = tempfile::tempdir().unwrap();
+        let file = directory.path().join("zluda.db");
+        let cache = KernelCache::new(file.clone(), "Clang 17".to_string()).unwrap();
+        let isa = CString::new("amdgcn-amd-amdhsa--gfx90a:sramecc+:xnack-").unwrap();
+        let text = ".version 6.5\n.target sm_80\n.address_size 64\n";
+        cache
+            .save_program(&isa, text, CompilationMode::Wave32OnWave64, &[1, 2, 3])
+            .unwrap();
+        assert!(cache.contains(&isa, text, CompilationMode::Wave32OnWave64));
+        assert!(!cache.contains(&isa, text, CompilationMode::Wave32));
+        let other_isa = CString::new("amdgcn-amd-amdhsa--gfx90a").unwrap();
+        assert!(!cache.contains(&other_isa, text, CompilationMode::Wave32OnWave64));
+        // ZLUDA hashes the text of all modules linked together, a kernel
+        // unit is always a single module
+        let (hash, device, compiler_version, compilation_mode) = rusqlite::Connection::open(&file)
+            .unwrap()
+            .query_row(
+                "SELECT hash, device, compiler_version, compilation_mode FROM kernels",
+                [],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, u8>(3)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(hash, blake3::hash(text.as_bytes()).to_hex().as_str());
+        assert_eq!(device, "amdgcn-amd-amdhsa--gfx90a:sramecc+:xnack-");
+        assert_eq!(compiler_version, "Clang 17");
+        assert_eq!(compilation_mode, CompilationMode::Wave32OnWave64 as u8);
+    }
+}
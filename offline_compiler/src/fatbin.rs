@@ -0,0 +1,301 @@
+// Ahead-of-time compilation of CUDA executables and shared libraries. PTX
+// modules from .nv_fatbin section are split and compiled the same way ZLUDA
+// does it on the first run and then saved to the kernel cache, so ZLUDA loads
+// them without compiling anything
+use crate::cache::KernelCache;
+use comgr::Comgr;
+use goblin::elf::Elf;
+use hip_common::CompilationMode;
+use ptx::ModuleParserExt;
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::{CStr, CString};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::{cmp, fs, iter, mem, slice, thread};
+use zluda_dark_api::{CudaFatbin, FatbinFileKind, FatbinModule};
+
+pub(crate) fn is_elf(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && &magic == b"\x7FELF"
+}
+
+// Every ELF input is compiled for every ISA and every compilation mode
+pub(crate) fn compile_to_cache(
+    comgr: &Comgr,
+    inputs: &[PathBuf],
+    isas: &[CString],
+    compilation_modes: &[CompilationMode],
+    cache_file: PathBuf,
+    eager: bool,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(directory) = cache_file.parent() {
+        fs::create_dir_all(directory)
+            .map_err(|err| format!("Could not create {}: {}", directory.display(), err))?;
+    }
+    let compiler_version = comgr
+        .version()
+        .map_err(|err| format!("Could not get LLVM version: {:?}", err))?;
+    let cache = KernelCache::new(cache_file.clone(), compiler_version)
+        .map_err(|err| format!("Could not open {}: {}", cache_file.display(), err))?;
+    let mut units = Vec::new();
+    for input in inputs {
+        let fatbins = load_fatbins(input)?;
+        let modules = fatbins.len();
+        let input_units = fatbins
+            .iter()
+            .flat_map(|candidates| compilation_units(candidates, eager))
+            .collect::<Vec<_>>();
+        println!(
+            "{}: {} modules, {} compilation units",
+            input.display(),
+            modules,
+            input_units.len()
+        );
+        units.extend(input_units);
+    }
+    let mut jobs = Vec::new();
+    let mut cached = 0;
+    for unit in units.iter() {
+        for isa in isas {
+            for &mode in compilation_modes {
+                if unit.iter().any(|text| cache.contains(isa, text, mode)) {
+                    cached += 1;
+                } else {
+                    jobs.push((&unit[..], &**isa, mode));
+                }
+            }
+        }
+    }
+    let (mut compiled, mut failed) = (0, 0);
+    compile_parallel(comgr, jobs, |text, isa, mode, result| {
+        let error = match result {
+            Ok(binary) => match cache.save_program(isa, text, mode, &binary) {
+                Ok(()) => {
+                    compiled += 1;
+                    return;
+                }
+                Err(err) => format!("could not save to the cache: {}", err),
+            },
+            Err(error) => error,
+        };
+        eprintln!("{} (mode {}): {}", isa.to_string_lossy(), mode as u8, error);
+        failed += 1;
+    });
+    println!(
+        "Compiled: {}, already cached: {}, failed: {}",
+        compiled, cached, failed
+    );
+    Ok(())
+}
+
+// Returns candidate PTX texts of every fatbin in the file, ordered the same
+// way as in ZLUDA: newest PTX version first
+fn load_fatbins(path: &Path) -> Result<Vec<Vec<String>>, String> {
+    let file =
+        fs::read(path).map_err(|err| format!("Could not read {}: {}", path.display(), err))?;
+    let elf =
+        Elf::parse(&file).map_err(|err| format!("Could not parse {}: {}", path.display(), err))?;
+    let section = elf
+        .section_headers
+        .iter()
+        .find(|section| elf.shdr_strtab.get_at(section.sh_name) == Some(".nv_fatbin"));
+    let range = match section.and_then(|section| section.file_range()) {
+        Some(range) => range,
+        None => return Ok(Vec::new()),
+    };
+    // Fatbin headers must be aligned
+    let section = &file[range];
+    let mut buffer =
+        vec![0u64; (section.len() + mem::size_of::<u64>() - 1) / mem::size_of::<u64>()];
+    let aligned =
+        unsafe { slice::from_raw_parts_mut(buffer.as_mut_ptr().cast::<u8>(), section.len()) };
+    aligned.copy_from_slice(section);
+    Ok(unsafe { CudaFatbin::from_elf_section(aligned) }
+        .into_iter()
+        .map(|fatbin| {
+            let module = match fatbin {
+                CudaFatbin::Version1(module) => module,
+                CudaFatbin::Version2 { post_link, .. } => post_link,
+            };
+            let files = match unsafe { module.get() } {
+                Ok(FatbinModule::Files(files)) => files,
+                _ => return Vec::new(),
+            };
+            let mut ptx_files = files
+                .filter_map(|file| {
+                    let file = file.ok()?;
+                    if file.kind != FatbinFileKind::Ptx {
+                        return None;
+                    }
+                    let text = unsafe { file.get_or_decompress() }.ok()?;
+                    Some((String::from_utf8(text.into_owned()).ok()?, file.sm_version))
+                })
+                .collect::<Vec<_>>();
+            ptx_files.sort_by_key(|(_, sm_version)| cmp::Reverse(*sm_version));
+            ptx_files.into_iter().map(|(text, _)| text).collect()
+        })
+        .filter(|candidates: &Vec<String>| !candidates.is_empty())
+        .collect())
+}
+
+// Compilation unit is a list of alternative PTX texts, ZLUDA uses the first
+// one that compiles. With lazy loading every kernel is a separate unit
+fn compilation_units(candidates: &[String], eager: bool) -> Vec<Vec<String>> {
+    if !eager {
+        if let Some(units) = kernel_units(candidates) {
+            return units;
+        }
+    }
+    vec![candidates.to_vec()]
+}
+
+// Same rules as in LazyModule::new(...) in ZLUDA
+fn kernel_units(candidates: &[String]) -> Option<Vec<Vec<String>>> {
+    let mut units = Vec::<Vec<String>>::new();
+    let mut kernel_to_unit = HashMap::<String, usize>::new();
+    let mut any_parsed = false;
+    for text in candidates {
+        let ast = match ptx::ModuleParser::parse_checked(text) {
+            Ok(ast) => ast,
+            Err(_) => continue,
+        };
+        if !ast.has_independent_kernels() {
+            return None;
+        }
+        any_parsed = true;
+        let split = ptx::KernelSplit::new(text, &ast);
+        for (kernel, (name, _)) in ast.kernel_spans.iter().enumerate() {
+            let kernel_text = split.kernel_text(text, kernel);
+            let unit = *kernel_to_unit.entry(name.to_string()).or_insert_with(|| {
+                units.push(Vec::new());
+                units.len() - 1
+            });
+            units[unit].push(kernel_text);
+        }
+    }
+    if any_parsed {
+        Some(units)
+    } else {
+        None
+    }
+}
+
+// Results are passed to `on_result` on the calling thread as they come.
+// Text passed to `on_result` is the alternative that compiled successfully
+fn compile_parallel<'a>(
+    comgr: &Comgr,
+    jobs: Vec<(&'a [String], &'a CStr, CompilationMode)>,
+    mut on_result: impl FnMut(&'a str, &'a CStr, CompilationMode, Result<Vec<u8>, String>),
+) {
+    let threads = thread::available_parallelism().map_or(1, |count| count.get());
+    let threads = cmp::max(1, cmp::min(threads, jobs.len()));
+    let jobs = Mutex::new(jobs.into_iter());
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let jobs = &jobs;
+            scope.spawn(move || loop {
+                let job = match jobs.lock() {
+                    Ok(mut jobs) => jobs.next(),
+                    Err(_) => None,
+                };
+                let (alternatives, isa, mode) = match job {
+                    Some(job) => job,
+                    None => break,
+                };
+                let mut result = Err(String::new());
+                let mut text = &alternatives[0][..];
+                for alternative in alternatives {
+                    text = alternative;
+                    result = compile(comgr, isa, mode, alternative);
+                    if result.is_ok() {
+                        break;
+                    }
+                }
+                sender.send((text, isa, mode, result)).ok();
+            });
+        }
+        drop(sender);
+        for (text, isa, mode, result) in receiver {
+            on_result(text, isa, mode, result);
+        }
+    });
+}
+
+fn compile(
+    comgr: &Comgr,
+    isa: &CStr,
+    compilation_mode: CompilationMode,
+    text: &str,
+) -> Result<Vec<u8>, String> {
+    let ast = ptx::ModuleParser::parse_checked(text).map_err(|errors| match errors.first() {
+        Some(error) => ptx::Diagnostic::from_parse_error(text, error).to_string(),
+        None => "could not parse PTX".to_string(),
+    })?;
+    let module = ptx::to_llvm_module(compilation_mode, vec![ast])
+        .map_err(|error| ptx::Diagnostic::from_translate_error(&[text], &error).to_string())?;
+    comgr
+        .compile(
+            compilation_mode,
+            isa,
+            ptx::Module::get_bitcode_multi(iter::once(&module)).into_iter(),
+            &module.metadata.to_elf_section(),
+        )
+        .map_err(|error| format!("{:?}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compilation_units;
+
+    fn module(sm_version: u32, kernels: &[&str]) -> String {
+        let mut text = format!(
+            ".version 6.5\n.target sm_{}\n.address_size 64\n.func helper() {{ ret; }}\n",
+            sm_version
+        );
+        for kernel in kernels {
+            text.push_str(&format!(
+                ".visible .entry {}()\n{{\n    call helper, ();\n    ret;\n}}\n",
+                kernel
+            ));
+        }
+        text
+    }
+
+    #[test]
+    fn kernels_are_grouped_by_name() {
+        let candidates = [
+            module(80, &["first", "second"]),
+            "not PTX".to_string(),
+            module(70, &["second", "first"]),
+        ];
+        let units = compilation_units(&candidates, false);
+        assert_eq!(units.len(), 2);
+        for (unit, kernel, other) in [
+            (&units[0], "first", "second"),
+            (&units[1], "second", "first"),
+        ] {
+            assert_eq!(unit.len(), 2);
+            assert!(unit[0].contains("sm_80") && unit[1].contains("sm_70"));
+            for text in unit {
+                assert!(text.contains(kernel) && text.contains("helper"));
+                assert!(!text.contains(other));
+            }
+        }
+        assert_eq!(compilation_units(&candidates, true), [candidates.to_vec()]);
+    }
+
+    #[test]
+    fn modules_with_globals_are_not_split() {
+        let mut text = module(80, &["first", "second"]);
+        text.push_str(".global .u32 counter;\n");
+        let candidates = [text];
+        assert_eq!(compilation_units(&candidates, false), [candidates.to_vec()]);
+    }
+}
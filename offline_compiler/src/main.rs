@@ -5,19 +5,26 @@ use hip_runtime_sys::*;
 use hiprt_sys::*;
 use ptx::ModuleParserExt;
 use std::borrow::Cow;
+use std::error::Error;
 use std::ffi::{c_void, CStr};
 use std::path::Path;
 use std::rc::Rc;
 use std::{ffi::CString, fs, path::PathBuf};
 use std::{iter, ptr};
 
+mod cache;
+mod fatbin;
+
 #[derive(FromArgs)]
 /// ZLUDA offline compiler
 struct CompilerArguments {
-    /// LLVM AMDGPU ISA, defaults to "gfx1030"
-    #[argh(option, default = "default_isa()")]
-    isa: String,
-    /// paths to PTX files
+    /// LLVM AMDGPU ISA, defaults to "gfx1030", can be repeated when compiling executables.
+    /// Kernel cache entries are keyed by the full name of the GPU as reported by rocminfo,
+    /// including target features, e.g. "gfx90a:sramecc+:xnack-"
+    #[argh(option)]
+    isa: Vec<String>,
+    /// paths to PTX files or to executables and shared libraries with CUDA fatbins,
+    /// kernels from executables are compiled into the kernel cache
     #[argh(positional)]
     inputs: Vec<PathBuf>,
     /// directory with output, will be created if does not exist
@@ -26,9 +33,15 @@ struct CompilerArguments {
     /// name of an OptiX program, if provided PTX will be compiled in raytracing mode
     #[argh(option)]
     rt_program: Option<String>,
-    /// compilation mode: 1 - Wave32, 2 - Wave32OnWave64, 3 - DoubleWave32OnWave64, defaults to Wave32
+    /// compilation mode: 1 - Wave32, 2 - Wave32OnWave64, 3 - DoubleWave32OnWave64, defaults to Wave32, can be repeated when compiling executables
     #[argh(option, short = 'm')]
-    mode: Option<u8>,
+    mode: Vec<u8>,
+    /// kernel cache file written when compiling executables, defaults to the cache used by ZLUDA
+    #[argh(option)]
+    cache: Option<PathBuf>,
+    /// compile whole modules, as with CUDA_MODULE_LOADING=EAGER, instead of single kernels
+    #[argh(switch)]
+    eager: bool,
     /// print LLVM version
     #[argh(switch, short = 'V')]
     version: bool,
@@ -38,36 +51,125 @@ fn default_isa() -> String {
     "gfx1030".to_string()
 }
 
-fn main() {
+// Accepts both "gfx90a:xnack-" and "amdgcn-amd-amdhsa--gfx90a:xnack-",
+// returns the latter, the same way ZLUDA names the device in the kernel cache
+// (see hip_common::comgr_isa(...))
+fn full_isa(isa: &str) -> Result<String, String> {
+    let arch = isa.strip_prefix("amdgcn-amd-amdhsa--").unwrap_or(isa);
+    if !arch.starts_with("gfx") || arch.contains(|c: char| c.is_whitespace() || c == '\0') {
+        return Err(format!("Invalid ISA {}, expected e.g. gfx1030", isa));
+    }
+    Ok(format!("amdgcn-amd-amdhsa--{}", arch))
+}
+
+// Devices of these architectures always report sramecc and/or xnack target
+// features, so kernels compiled without them are never found in the cache
+fn missing_target_features(isa: &str) -> bool {
+    let arch = isa.strip_prefix("amdgcn-amd-amdhsa--").unwrap_or(isa);
+    ["gfx906", "gfx908", "gfx90a", "gfx940", "gfx941", "gfx942"].contains(&arch)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
     let args: CompilerArguments = argh::from_env();
-    let comgr = comgr::Comgr::find_and_load().unwrap();
+    let comgr =
+        comgr::Comgr::find_and_load().map_err(|err| format!("Could not load comgr: {:?}", err))?;
     if args.version {
-        println!("{}", comgr.version().unwrap());
-        return;
+        println!(
+            "{}",
+            comgr
+                .version()
+                .map_err(|err| format!("Could not get LLVM version: {:?}", err))?
+        );
+        return Ok(());
+    }
+    if args.inputs.is_empty() {
+        return Err("No inputs".into());
+    }
+    let isas = if args.isa.is_empty() {
+        vec![default_isa()]
+    } else {
+        args.isa
+    };
+    let isas = isas
+        .iter()
+        .map(|isa| full_isa(isa))
+        .collect::<Result<Vec<_>, _>>()?;
+    let modes = if args.mode.is_empty() {
+        vec![CompilationMode::Wave32]
+    } else {
+        args.mode
+            .iter()
+            .map(|mode| {
+                CompilationMode::from_u8(*mode)
+                    .ok_or_else(|| format!("Unknown compilation mode {}", mode))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    if args.inputs.iter().any(|input| fatbin::is_elf(input)) {
+        let cache_file = args
+            .cache
+            .or_else(|| hip_common::cache::CacheSettings::load().cache_file())
+            .ok_or("Could not find cache directory, use --cache")?;
+        for isa in isas.iter().filter(|isa| missing_target_features(isa)) {
+            eprintln!(
+                "Warning: {} has no target features, ZLUDA will not find its kernels in the cache unless the ISA matches the GPU as reported by rocminfo, e.g. {}:sramecc+:xnack-",
+                isa, isa
+            );
+        }
+        let isas = isas
+            .into_iter()
+            .map(CString::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        return fatbin::compile_to_cache(
+            &comgr,
+            &args.inputs,
+            &isas,
+            &modes,
+            cache_file,
+            args.eager,
+        );
+    }
+    if isas.len() > 1 || modes.len() > 1 {
+        return Err(
+            "Multiple ISAs and compilation modes are supported only for executables".into(),
+        );
     }
-    let mut full_isa = "amdgcn-amd-amdhsa--".to_string();
-    full_isa.push_str(&args.isa);
+    let full_isa = isas.into_iter().next().unwrap();
     let main_path = &args.inputs[0];
     let inputs = args
         .inputs
         .iter()
-        .map(|input| fs::read_to_string(&input).unwrap())
-        .collect::<Vec<_>>();
+        .map(|input| {
+            fs::read_to_string(&input)
+                .map_err(|err| format!("Could not read {}: {}", input.display(), err))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
     let asts = inputs
         .iter()
-        .map(|input| ptx::ModuleParser::parse_checked(input).unwrap())
-        .collect::<Vec<_>>();
+        .zip(args.inputs.iter())
+        .map(|(input, path)| {
+            ptx::ModuleParser::parse_checked(input).map_err(|errors| match errors.first() {
+                Some(error) => format!(
+                    "{}:{}",
+                    path.display(),
+                    ptx::Diagnostic::from_parse_error(input, error)
+                ),
+                None => format!("Could not parse {}", path.display()),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
     let output_dir = if let Some(output) = args.output {
-        fs::create_dir_all(&output).unwrap();
+        fs::create_dir_all(&output)
+            .map_err(|err| format!("Could not create {}: {}", output.display(), err))?;
         Cow::Owned(output)
     } else {
-        Cow::Borrowed(args.inputs[0].parent().unwrap())
+        Cow::Borrowed(main_path.parent().unwrap_or(Path::new(".")))
     };
     match args.rt_program {
         Some(rt_program) => {
             let comgr = Rc::new(comgr);
             if asts.len() != 1 {
-                panic!("Raytracing compiler expects single .ptx input file")
+                return Err("Raytracing compiler expects single .ptx input file".into());
             }
             compile_and_dump_raytracing(
                 full_isa,
@@ -78,15 +180,9 @@ fn main() {
                 asts.into_iter().next().unwrap(),
             )
         }
-        None => {
-            let mode = args
-                .mode
-                .map(CompilationMode::from_u8)
-                .flatten()
-                .unwrap_or(CompilationMode::Wave32);
-            compile_and_dump(full_isa, output_dir, mode, comgr, main_path, asts)
-        }
+        None => compile_and_dump(full_isa, output_dir, modes[0], comgr, main_path, asts),
     }
+    Ok(())
 }
 
 fn compile_and_dump<'input>(
@@ -303,3 +399,22 @@ fn dump_headers(output_path: &PathBuf, rt_module: &ptx::raytracing::Module) {
         fs::write(&header_path, header.as_bytes()).unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{full_isa, missing_target_features};
+
+    #[test]
+    fn isa_names() {
+        assert_eq!(full_isa("gfx1030").unwrap(), "amdgcn-amd-amdhsa--gfx1030");
+        assert_eq!(
+            full_isa("amdgcn-amd-amdhsa--gfx90a:sramecc+:xnack-").unwrap(),
+            "amdgcn-amd-amdhsa--gfx90a:sramecc+:xnack-"
+        );
+        assert!(full_isa("1030").is_err());
+        assert!(full_isa("gfx1030 ").is_err());
+        assert!(missing_target_features("amdgcn-amd-amdhsa--gfx90a"));
+        assert!(!missing_target_features("amdgcn-amd-amdhsa--gfx90a:xnack-"));
+        assert!(!missing_target_features("amdgcn-amd-amdhsa--gfx1030"));
+    }
+}
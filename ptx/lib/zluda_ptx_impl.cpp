@@ -90,7 +90,19 @@ enum class CompilationMode : uint8_t
 
 typedef half half4 __attribute__((ext_vector_type(4)));
 
+// Set by the compiler from the target ISA and compilation mode, selects
+// hardware paths of matrix instructions
+enum class MatrixCores : uint8_t
+{
+    None = 0,
+    // gfx11 in wave32 mode: v_wmma_*
+    Wmma = 1,
+    // gfx908, gfx90a, gfx94x: v_mfma_*
+    Mfma = 2
+};
+
 extern "C" __device__ const CONSTANT_SPACE CompilationMode FUNC_CALL(COMPILATION_MODE);
+extern "C" __device__ const CONSTANT_SPACE MatrixCores FUNC_CALL(MATRIX_CORES);
 extern "C" __device__ const CONSTANT_SPACE bool FUNC_CALL(IS_WINDOWS);
 
 template <
@@ -1709,6 +1721,30 @@ extern "C"
         return __ockl_cyclectr_u64();
     }
 
+    // %envreg registers are set up by the CUDA driver and their meaning is
+    // undocumented. The only known user is cooperative groups' grid sync:
+    // this_grid().sync() reads the address of the "grid workspace" from
+    // %envreg1 (low half) and %envreg2 (high half) and implements the grid
+    // barrier inline in PTX with atomics on workspace.barrier:
+    //   struct { uint32_t ws_size; uint32_t barrier; }
+    // Host allocates a fresh workspace for every cooperative launch and
+    // stores its address here before the kernel starts (see
+    // launch_cooperative_kernel in zluda). Other launches see null, just
+    // like on CUDA grid sync is only valid in a cooperative launch.
+    // Weak, because kernels of a module can be compiled separately, every
+    // relocatable gets its own copy and the linker must keep only one
+    __device__ __attribute__((used, weak)) uint64_t FUNC_CALL(grid_workspace) = 0;
+
+    uint32_t FUNC(sreg_envreg)(uchar index)
+    {
+        uint64_t workspace = FUNC_CALL(grid_workspace);
+        if (index == 1)
+            return (uint32_t)workspace;
+        if (index == 2)
+            return (uint32_t)(workspace >> 32);
+        return 0;
+    }
+
     void FUNC(barrier_sync)(uint32_t)
     {
         // I'm not 100% how should a barrier be defined:
@@ -1906,3 +1942,1117 @@ extern "C"
             __builtin_amdgcn_s_sleep(1);
     }
 }
+
+// Warp-level matrix instructions: wmma.*, mma.sync and ldmatrix
+// Every thread holds its share of a fragment packed into 32-bit registers,
+// exactly like on NVIDIA hardware, and fetches elements owned by other threads
+// with cross-lane shuffles.
+// Layout of wmma fragments is opaque, so we use the simplest possible one:
+// thread i holds row-major elements [i*E, (i+1)*E). Fragment layouts of
+// mma.sync and ldmatrix are documented and followed exactly:
+//   https://docs.nvidia.com/cuda/parallel-thread-execution/index.html#warp-level-matrix-fragment-mma-16816
+// Multiplication itself is done by matrix cores if the target has them and
+// they support the types:
+// * gfx11 (MatrixCores::Wmma): v_wmma_*_16x16x16, the operation is split into
+//   16x16x16 tiles, padded with zeros
+// * gfx9 (MatrixCores::Mfma): v_mfma_*_4x4x4 with 16 blocks, every 32-lane half
+//   of a wavefront computes eight 4x4 output tiles at a time. Blocks never mix
+//   lanes from both halves, so this works in every wave64 compilation mode
+// Operands are reshuffled from the fragment layout to the hardware layout and
+// back. Everything else (tf32, .satfinite on gfx9, etc.) is done in software,
+// which is slow, but it's only meant as a fallback.
+// Matrix core instructions are not called directly, but through builtins
+// defined by the compiler in matrix_cores_*.ll: targets without the matrix
+// cores get stubs. This way checks of MATRIX_CORES that are not folded (-O0)
+// never bring instructions unsupported by the target to instruction selection
+
+template <typename T, uint32_t N>
+using matrix_vec = T __attribute__((ext_vector_type(N)));
+
+// Input types that can be multiplied by matrix cores
+enum class MatrixHwType
+{
+    None,
+    F16,
+    BF16,
+    Int8
+};
+
+struct matrix_f16
+{
+    using memory_t = uint16_t;
+    using register_t = uint32_t;
+    using acc_t = float;
+    static constexpr uint32_t bits = 16;
+    static constexpr MatrixHwType hw = MatrixHwType::F16;
+    static __device__ acc_t unpack(uint32_t x) { return (float)std::bit_cast<half>((uint16_t)x); }
+    static __device__ uint32_t pack(acc_t x, bool) { return std::bit_cast<uint16_t>((half)x); }
+};
+
+struct matrix_bf16
+{
+    using memory_t = uint16_t;
+    using register_t = uint32_t;
+    using acc_t = float;
+    static constexpr uint32_t bits = 16;
+    static constexpr MatrixHwType hw = MatrixHwType::BF16;
+    static __device__ acc_t unpack(uint32_t x) { return std::bit_cast<float>(x << 16); }
+    static __device__ uint32_t pack(acc_t x, bool)
+    {
+        uint32_t value = std::bit_cast<uint32_t>(x);
+        return (value + 0x7FFFU + ((value >> 16) & 1U)) >> 16;
+    }
+};
+
+struct matrix_tf32
+{
+    using memory_t = uint32_t;
+    using register_t = uint32_t;
+    using acc_t = float;
+    static constexpr uint32_t bits = 32;
+    static constexpr MatrixHwType hw = MatrixHwType::None;
+    // Hardware ignores lower 13 bits of mantissa
+    static __device__ acc_t unpack(uint32_t x) { return std::bit_cast<float>(x & 0xFFFFE000U); }
+    static __device__ uint32_t pack(acc_t x, bool) { return std::bit_cast<uint32_t>(x); }
+};
+
+struct matrix_f32
+{
+    using memory_t = uint32_t;
+    using register_t = float;
+    using acc_t = float;
+    static constexpr uint32_t bits = 32;
+    static constexpr MatrixHwType hw = MatrixHwType::None;
+    static __device__ acc_t unpack(uint32_t x) { return std::bit_cast<float>(x); }
+    static __device__ uint32_t pack(acc_t x, bool) { return std::bit_cast<uint32_t>(x); }
+};
+
+struct matrix_s8
+{
+    using memory_t = uint8_t;
+    using register_t = uint32_t;
+    using acc_t = int64_t;
+    static constexpr uint32_t bits = 8;
+    static constexpr MatrixHwType hw = MatrixHwType::Int8;
+    static __device__ acc_t unpack(uint32_t x) { return (int64_t)(int8_t)(uint8_t)x; }
+    static __device__ uint32_t pack(acc_t x, bool) { return (uint32_t)(uint8_t)x; }
+};
+
+struct matrix_u8
+{
+    using memory_t = uint8_t;
+    using register_t = uint32_t;
+    using acc_t = int64_t;
+    static constexpr uint32_t bits = 8;
+    static constexpr MatrixHwType hw = MatrixHwType::Int8;
+    static __device__ acc_t unpack(uint32_t x) { return (int64_t)(uint8_t)x; }
+    static __device__ uint32_t pack(acc_t x, bool) { return (uint32_t)(uint8_t)x; }
+};
+
+struct matrix_s32
+{
+    using memory_t = uint32_t;
+    using register_t = int32_t;
+    using acc_t = int64_t;
+    static constexpr uint32_t bits = 32;
+    static constexpr MatrixHwType hw = MatrixHwType::None;
+    static __device__ acc_t unpack(uint32_t x) { return (int64_t)(int32_t)x; }
+    static __device__ uint32_t pack(acc_t x, bool satfinite)
+    {
+        if (satfinite)
+        {
+            if (x < (int64_t)INT32_MIN)
+                x = INT32_MIN;
+            if (x > (int64_t)INT32_MAX)
+                x = INT32_MAX;
+        }
+        return (uint32_t)(int32_t)x;
+    }
+};
+
+struct matrix_row
+{
+    static __device__ uint32_t offset(uint32_t row, uint32_t col, uint32_t stride) { return row * stride + col; }
+};
+
+struct matrix_col
+{
+    static __device__ uint32_t offset(uint32_t row, uint32_t col, uint32_t stride) { return col * stride + row; }
+};
+
+// Every geometry maps (lane, element index) to (row, col) with coord(...)
+// and (row, col) back to (lane, element index) with owner(...)
+template <uint32_t ROWS, uint32_t COLS>
+struct wmma_geometry
+{
+    static constexpr uint32_t rows = ROWS;
+    static constexpr uint32_t cols = COLS;
+    static constexpr uint32_t elements = ROWS * COLS / 32;
+    static __device__ void coord(uint32_t lane, uint32_t i, uint32_t &row, uint32_t &col)
+    {
+        uint32_t position = lane * elements + i;
+        row = position / COLS;
+        col = position % COLS;
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        uint32_t position = row * COLS + col;
+        lane = position / elements;
+        i = position % elements;
+    }
+};
+
+// In all the mma geometries below: group = lane / 4, thread_in_group = lane % 4
+// A, m16n8k16, .f16/.bf16
+struct mma_a_m16k16_b16
+{
+    static constexpr uint32_t elements = 8;
+    static __device__ void coord(uint32_t lane, uint32_t i, uint32_t &row, uint32_t &col)
+    {
+        row = (lane / 4) + 8 * ((i >> 1) & 1);
+        col = (lane % 4) * 2 + (i & 1) + 8 * (i >> 2);
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        lane = (row % 8) * 4 + (col % 8) / 2;
+        i = (col & 1) + 2 * (row / 8) + 4 * (col / 8);
+    }
+};
+
+// A, m16n8k8, .f16/.bf16
+struct mma_a_m16k8_b16
+{
+    static constexpr uint32_t elements = 4;
+    static __device__ void coord(uint32_t lane, uint32_t i, uint32_t &row, uint32_t &col)
+    {
+        row = (lane / 4) + 8 * (i >> 1);
+        col = (lane % 4) * 2 + (i & 1);
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        lane = (row % 8) * 4 + col / 2;
+        i = (col & 1) + 2 * (row / 8);
+    }
+};
+
+// B, m16n8k16, .f16/.bf16
+struct mma_b_k16n8_b16
+{
+    static constexpr uint32_t elements = 4;
+    static __device__ void coord(uint32_t lane, uint32_t i, uint32_t &row, uint32_t &col)
+    {
+        row = (lane % 4) * 2 + (i & 1) + 8 * (i >> 1);
+        col = lane / 4;
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        lane = col * 4 + (row % 8) / 2;
+        i = (row & 1) + 2 * (row / 8);
+    }
+};
+
+// B, m16n8k8, .f16/.bf16
+struct mma_b_k8n8_b16
+{
+    static constexpr uint32_t elements = 2;
+    static __device__ void coord(uint32_t lane, uint32_t i, uint32_t &row, uint32_t &col)
+    {
+        row = (lane % 4) * 2 + i;
+        col = lane / 4;
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        lane = col * 4 + row / 2;
+        i = row & 1;
+    }
+};
+
+// A, m16n8k4, .tf32
+struct mma_a_m16k4_b32
+{
+    static constexpr uint32_t elements = 2;
+    static __device__ void coord(uint32_t lane, uint32_t i, uint32_t &row, uint32_t &col)
+    {
+        row = (lane / 4) + 8 * i;
+        col = lane % 4;
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        lane = (row % 8) * 4 + col;
+        i = row / 8;
+    }
+};
+
+// A, m16n8k8, .tf32
+struct mma_a_m16k8_b32
+{
+    static constexpr uint32_t elements = 4;
+    static __device__ void coord(uint32_t lane, uint32_t i, uint32_t &row, uint32_t &col)
+    {
+        row = (lane / 4) + 8 * (i & 1);
+        col = (lane % 4) + 4 * (i >> 1);
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        lane = (row % 8) * 4 + col % 4;
+        i = (row / 8) + 2 * (col / 4);
+    }
+};
+
+// B, m16n8k4, .tf32
+struct mma_b_k4n8_b32
+{
+    static constexpr uint32_t elements = 1;
+    static __device__ void coord(uint32_t lane, uint32_t, uint32_t &row, uint32_t &col)
+    {
+        row = lane % 4;
+        col = lane / 4;
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        lane = col * 4 + row;
+        i = 0;
+    }
+};
+
+// B, m16n8k8, .tf32
+struct mma_b_k8n8_b32
+{
+    static constexpr uint32_t elements = 2;
+    static __device__ void coord(uint32_t lane, uint32_t i, uint32_t &row, uint32_t &col)
+    {
+        row = (lane % 4) + 4 * i;
+        col = lane / 4;
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        lane = col * 4 + row % 4;
+        i = row / 4;
+    }
+};
+
+// A, m8n8k16, .s8/.u8
+struct mma_a_m8k16_b8
+{
+    static constexpr uint32_t elements = 4;
+    static __device__ void coord(uint32_t lane, uint32_t i, uint32_t &row, uint32_t &col)
+    {
+        row = lane / 4;
+        col = (lane % 4) * 4 + i;
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        lane = row * 4 + col / 4;
+        i = col & 3;
+    }
+};
+
+// A, m16n8k16, .s8/.u8
+struct mma_a_m16k16_b8
+{
+    static constexpr uint32_t elements = 8;
+    static __device__ void coord(uint32_t lane, uint32_t i, uint32_t &row, uint32_t &col)
+    {
+        row = (lane / 4) + 8 * (i >> 2);
+        col = (lane % 4) * 4 + (i & 3);
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        lane = (row % 8) * 4 + col / 4;
+        i = (col & 3) + 4 * (row / 8);
+    }
+};
+
+// A, m16n8k32, .s8/.u8
+struct mma_a_m16k32_b8
+{
+    static constexpr uint32_t elements = 16;
+    static __device__ void coord(uint32_t lane, uint32_t i, uint32_t &row, uint32_t &col)
+    {
+        row = (lane / 4) + 8 * ((i >> 2) & 1);
+        col = (lane % 4) * 4 + (i & 3) + 16 * (i >> 3);
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        lane = (row % 8) * 4 + (col % 16) / 4;
+        i = (col & 3) + 4 * (row / 8) + 8 * (col / 16);
+    }
+};
+
+// B, m8n8k16 and m16n8k16, .s8/.u8
+struct mma_b_k16n8_b8
+{
+    static constexpr uint32_t elements = 4;
+    static __device__ void coord(uint32_t lane, uint32_t i, uint32_t &row, uint32_t &col)
+    {
+        row = (lane % 4) * 4 + i;
+        col = lane / 4;
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        lane = col * 4 + row / 4;
+        i = row & 3;
+    }
+};
+
+// B, m16n8k32, .s8/.u8
+struct mma_b_k32n8_b8
+{
+    static constexpr uint32_t elements = 8;
+    static __device__ void coord(uint32_t lane, uint32_t i, uint32_t &row, uint32_t &col)
+    {
+        row = (lane % 4) * 4 + (i & 3) + 16 * (i >> 2);
+        col = lane / 4;
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        lane = col * 4 + (row % 16) / 4;
+        i = (row & 3) + 4 * (row / 16);
+    }
+};
+
+// C and D, all m16n8 shapes
+struct mma_cd_m16n8
+{
+    static constexpr uint32_t rows = 16;
+    static constexpr uint32_t cols = 8;
+    static constexpr uint32_t elements = 4;
+    static __device__ void coord(uint32_t lane, uint32_t i, uint32_t &row, uint32_t &col)
+    {
+        row = (lane / 4) + 8 * (i >> 1);
+        col = (lane % 4) * 2 + (i & 1);
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        lane = (row % 8) * 4 + col / 2;
+        i = (col & 1) + 2 * (row / 8);
+    }
+};
+
+// C and D, m8n8k16
+struct mma_cd_m8n8
+{
+    static constexpr uint32_t rows = 8;
+    static constexpr uint32_t cols = 8;
+    static constexpr uint32_t elements = 2;
+    static __device__ void coord(uint32_t lane, uint32_t i, uint32_t &row, uint32_t &col)
+    {
+        row = lane / 4;
+        col = (lane % 4) * 2 + i;
+    }
+    static __device__ void owner(uint32_t row, uint32_t col, uint32_t &lane, uint32_t &i)
+    {
+        lane = row * 4 + col / 2;
+        i = col & 1;
+    }
+};
+
+template <typename Element, typename Geometry>
+static constexpr uint32_t matrix_registers()
+{
+    return (Geometry::elements * Element::bits + 31) / 32;
+}
+
+template <typename Element>
+static constexpr uint32_t matrix_mask()
+{
+    return (uint32_t)((1ULL << Element::bits) - 1);
+}
+
+template <typename Element, uint32_t N>
+__device__ static inline uint32_t matrix_extract(const uint32_t (&regs)[N], uint32_t i)
+{
+    uint32_t bit = i * Element::bits;
+    return (regs[bit / 32] >> (bit % 32)) & matrix_mask<Element>();
+}
+
+template <typename Element, uint32_t N>
+__device__ static inline void matrix_insert(uint32_t (&regs)[N], uint32_t i, uint32_t value)
+{
+    uint32_t bit = i * Element::bits;
+    uint32_t mask = matrix_mask<Element>() << (bit % 32);
+    regs[bit / 32] = (regs[bit / 32] & ~mask) | ((value << (bit % 32)) & mask);
+}
+
+// Fetch i-th element held by `lane`. Register index can be different in every
+// thread, but a shuffle reads the same register in all threads, so we have to
+// shuffle every register and pick the right one
+template <typename Element, uint32_t N>
+__device__ static inline uint32_t matrix_shfl(const uint32_t (&regs)[N], uint32_t lane, uint32_t i)
+{
+    uint32_t bit = i * Element::bits;
+    uint32_t value = 0;
+    for (uint32_t r = 0; r < N; r++)
+    {
+        uint32_t shuffled = __shfl(regs[r], (int32_t)lane, 32);
+        if (r == bit / 32)
+            value = shuffled;
+    }
+    return (value >> (bit % 32)) & matrix_mask<Element>();
+}
+
+template <typename T, uint32_t LEN, uint32_t N>
+__device__ static inline void matrix_from_vec(matrix_vec<T, LEN> vec, uint32_t (&regs)[N])
+{
+    static_assert(N <= LEN);
+    for (uint32_t r = 0; r < N; r++)
+        regs[r] = std::bit_cast<uint32_t>((T)vec[r]);
+}
+
+template <typename T, uint32_t LEN, uint32_t N>
+__device__ static inline matrix_vec<T, LEN> matrix_to_vec(const uint32_t (&regs)[N])
+{
+    static_assert(N <= LEN);
+    matrix_vec<T, LEN> result = {};
+    for (uint32_t r = 0; r < N; r++)
+        result[r] = std::bit_cast<T>(regs[r]);
+    return result;
+}
+
+template <typename Element, typename Geometry, typename Layout, uint32_t LEN, typename Pointer>
+__device__ static inline matrix_vec<typename Element::register_t, LEN> wmma_load(Pointer ptr, uint32_t stride)
+{
+    uint32_t lane = FUNC_CALL(sreg_laneid)();
+    uint32_t regs[matrix_registers<Element, Geometry>()] = {};
+    for (uint32_t i = 0; i < Geometry::elements; i++)
+    {
+        uint32_t row, col;
+        Geometry::coord(lane, i, row, col);
+        matrix_insert<Element>(regs, i, (uint32_t)ptr[Layout::offset(row, col, stride)]);
+    }
+    return matrix_to_vec<typename Element::register_t, LEN>(regs);
+}
+
+template <typename Element, typename Geometry, typename Layout, uint32_t LEN, typename Pointer>
+__device__ static inline void wmma_store(Pointer ptr, matrix_vec<typename Element::register_t, LEN> vec, uint32_t stride)
+{
+    uint32_t lane = FUNC_CALL(sreg_laneid)();
+    uint32_t regs[matrix_registers<Element, Geometry>()];
+    matrix_from_vec<typename Element::register_t>(vec, regs);
+    for (uint32_t i = 0; i < Geometry::elements; i++)
+    {
+        uint32_t row, col;
+        Geometry::coord(lane, i, row, col);
+        ptr[Layout::offset(row, col, stride)] = (typename Element::memory_t)matrix_extract<Element>(regs, i);
+    }
+}
+
+// Raw bits of element (row, col) of a ROWS x COLS matrix, zero if it's out of
+// bounds. Must be called by all threads of a warp
+template <typename Element, typename Geometry, uint32_t ROWS, uint32_t COLS, uint32_t N>
+__device__ static inline uint32_t matrix_gather(const uint32_t (&regs)[N], uint32_t row, uint32_t col)
+{
+    uint32_t lane, i;
+    Geometry::owner(row % ROWS, col % COLS, lane, i);
+    uint32_t value = matrix_shfl<Element>(regs, lane, i);
+    return (row < ROWS && col < COLS) ? value : 0;
+}
+
+// Fetch `item` of `vec` held by `lane`
+template <typename T, uint32_t LEN>
+__device__ static inline T matrix_vec_shfl(matrix_vec<T, LEN> vec, uint32_t lane, uint32_t item)
+{
+    T value = 0;
+    for (uint32_t r = 0; r < LEN; r++)
+    {
+        T shuffled = __shfl(vec[r], (int32_t)lane, 32);
+        if (r == item)
+            value = shuffled;
+    }
+    return value;
+}
+
+template <typename A>
+using matrix_hw_acc_t = typename std::conditional<A::hw == MatrixHwType::Int8, int32_t, float>::type;
+
+extern "C"
+{
+    __device__ matrix_vec<float, 8> FUNC_CALL(matrix_wmma_f32_16x16x16_f16)(matrix_vec<_Float16, 16> a, matrix_vec<_Float16, 16> b, matrix_vec<float, 8> c);
+    __device__ matrix_vec<float, 8> FUNC_CALL(matrix_wmma_f32_16x16x16_bf16)(matrix_vec<short, 16> a, matrix_vec<short, 16> b, matrix_vec<float, 8> c);
+    __device__ matrix_vec<int32_t, 8> FUNC_CALL(matrix_wmma_i32_16x16x16_iu8)(bool a_signed, matrix_vec<int32_t, 4> a, bool b_signed, matrix_vec<int32_t, 4> b, matrix_vec<int32_t, 8> c, bool clamp);
+    __device__ matrix_vec<float, 4> FUNC_CALL(matrix_mfma_f32_4x4x4_f16)(matrix_vec<_Float16, 4> a, matrix_vec<_Float16, 4> b, matrix_vec<float, 4> c);
+    __device__ matrix_vec<int32_t, 4> FUNC_CALL(matrix_mfma_i32_4x4x4_i8)(int32_t a, int32_t b, matrix_vec<int32_t, 4> c);
+}
+
+template <typename A, typename B>
+static constexpr bool wmma_supported()
+{
+    return A::hw != MatrixHwType::None && A::hw == B::hw;
+}
+
+// .satfinite is not supported, because v_mfma_i32_* wraps around
+template <typename A, typename B, bool SATFINITE>
+static constexpr bool mfma_supported()
+{
+    return (std::is_same_v<A, matrix_f16> && std::is_same_v<B, matrix_f16>) ||
+           (std::is_same_v<A, matrix_s8> && std::is_same_v<B, matrix_s8> && !SATFINITE);
+}
+
+// Lane l holds row l % 16 of A and column l % 16 of B, element k of a vector is
+// k-th element of that row/column. Lane l holds rows 2*i + l/16 of column
+// l % 16 of C and D
+template <typename A, typename B, bool SATFINITE>
+__device__ static inline matrix_vec<matrix_hw_acc_t<A>, 8> wmma_gfx11(
+    const uint32_t (&a)[16], const uint32_t (&b)[16], matrix_vec<matrix_hw_acc_t<A>, 8> c)
+{
+    if constexpr (A::hw == MatrixHwType::F16)
+    {
+        matrix_vec<_Float16, 16> a_vec, b_vec;
+        for (uint32_t k = 0; k < 16; k++)
+        {
+            a_vec[k] = std::bit_cast<_Float16>((uint16_t)a[k]);
+            b_vec[k] = std::bit_cast<_Float16>((uint16_t)b[k]);
+        }
+        return FUNC_CALL(matrix_wmma_f32_16x16x16_f16)(a_vec, b_vec, c);
+    }
+    else if constexpr (A::hw == MatrixHwType::BF16)
+    {
+        matrix_vec<short, 16> a_vec, b_vec;
+        for (uint32_t k = 0; k < 16; k++)
+        {
+            a_vec[k] = (short)a[k];
+            b_vec[k] = (short)b[k];
+        }
+        return FUNC_CALL(matrix_wmma_f32_16x16x16_bf16)(a_vec, b_vec, c);
+    }
+    else
+    {
+        matrix_vec<int32_t, 4> a_vec = {}, b_vec = {};
+        for (uint32_t k = 0; k < 16; k++)
+        {
+            a_vec[k / 4] |= (int32_t)((a[k] & 0xFFU) << (8 * (k % 4)));
+            b_vec[k / 4] |= (int32_t)((b[k] & 0xFFU) << (8 * (k % 4)));
+        }
+        return FUNC_CALL(matrix_wmma_i32_16x16x16_iu8)(std::is_same_v<A, matrix_s8>, a_vec, std::is_same_v<B, matrix_s8>, b_vec, c, SATFINITE);
+    }
+}
+
+// Lane 4*b + i holds row i of A and column i of B of block b, element k of
+// a vector is k-th element of that row/column. Lane 4*b + j holds column j of
+// C and D of block b, element i of a vector is in row i
+template <typename A>
+__device__ static inline matrix_vec<matrix_hw_acc_t<A>, 4> mfma_gfx9(
+    const uint32_t (&a)[4], const uint32_t (&b)[4], matrix_vec<matrix_hw_acc_t<A>, 4> c)
+{
+    if constexpr (A::hw == MatrixHwType::F16)
+    {
+        matrix_vec<_Float16, 4> a_vec, b_vec;
+        for (uint32_t k = 0; k < 4; k++)
+        {
+            a_vec[k] = std::bit_cast<_Float16>((uint16_t)a[k]);
+            b_vec[k] = std::bit_cast<_Float16>((uint16_t)b[k]);
+        }
+        return FUNC_CALL(matrix_mfma_f32_4x4x4_f16)(a_vec, b_vec, c);
+    }
+    else
+    {
+        int32_t a_packed = 0, b_packed = 0;
+        for (uint32_t k = 0; k < 4; k++)
+        {
+            a_packed |= (int32_t)((a[k] & 0xFFU) << (8 * k));
+            b_packed |= (int32_t)((b[k] & 0xFFU) << (8 * k));
+        }
+        return FUNC_CALL(matrix_mfma_i32_4x4x4_i8)(a_packed, b_packed, c);
+    }
+}
+
+template <typename D, typename A, typename B, typename C,
+          typename AGeometry, typename BGeometry, typename CGeometry,
+          uint32_t M, uint32_t N, uint32_t K, bool SATFINITE,
+          uint32_t NA, uint32_t NB, uint32_t NC, uint32_t ND>
+__device__ static inline void matrix_mma_wmma(const uint32_t (&a)[NA], const uint32_t (&b)[NB], const uint32_t (&c)[NC], uint32_t (&d)[ND])
+{
+    using hw_acc_t = matrix_hw_acc_t<A>;
+    uint32_t lane = FUNC_CALL(sreg_laneid)();
+    for (uint32_t m0 = 0; m0 < M; m0 += 16)
+    {
+        for (uint32_t n0 = 0; n0 < N; n0 += 16)
+        {
+            matrix_vec<hw_acc_t, 8> acc;
+            for (uint32_t i = 0; i < 8; i++)
+                acc[i] = (hw_acc_t)C::unpack(matrix_gather<C, CGeometry, M, N>(c, m0 + 2 * i + lane / 16, n0 + lane % 16));
+            for (uint32_t k0 = 0; k0 < K; k0 += 16)
+            {
+                uint32_t a_tile[16], b_tile[16];
+                for (uint32_t k = 0; k < 16; k++)
+                {
+                    a_tile[k] = matrix_gather<A, AGeometry, M, K>(a, m0 + lane % 16, k0 + k);
+                    b_tile[k] = matrix_gather<B, BGeometry, K, N>(b, k0 + k, n0 + lane % 16);
+                }
+                acc = wmma_gfx11<A, B, SATFINITE>(a_tile, b_tile, acc);
+            }
+            for (uint32_t i = 0; i < CGeometry::elements; i++)
+            {
+                uint32_t row, col;
+                CGeometry::coord(lane, i, row, col);
+                uint32_t tile_row = row - m0;
+                uint32_t tile_col = col - n0;
+                hw_acc_t value = matrix_vec_shfl(acc, tile_col % 16 + 16 * (tile_row % 2), (tile_row / 2) % 8);
+                if (tile_row < 16 && tile_col < 16)
+                    matrix_insert<D>(d, i, D::pack((typename D::acc_t)value, SATFINITE));
+            }
+        }
+    }
+}
+
+template <typename D, typename A, typename B, typename C,
+          typename AGeometry, typename BGeometry, typename CGeometry,
+          uint32_t M, uint32_t N, uint32_t K, bool SATFINITE,
+          uint32_t NA, uint32_t NB, uint32_t NC, uint32_t ND>
+__device__ static inline void matrix_mma_mfma(const uint32_t (&a)[NA], const uint32_t (&b)[NB], const uint32_t (&c)[NC], uint32_t (&d)[ND])
+{
+    using hw_acc_t = matrix_hw_acc_t<A>;
+    constexpr uint32_t tiles = (M / 4) * (N / 4);
+    uint32_t lane = FUNC_CALL(sreg_laneid)();
+    for (uint32_t t0 = 0; t0 < tiles; t0 += 8)
+    {
+        // Shapes with fewer than 8 tiles compute some of them twice
+        uint32_t tile = (t0 + lane / 4) % tiles;
+        uint32_t tile_row = 4 * (tile / (N / 4));
+        uint32_t tile_col = 4 * (tile % (N / 4));
+        matrix_vec<hw_acc_t, 4> acc;
+        for (uint32_t i = 0; i < 4; i++)
+            acc[i] = (hw_acc_t)C::unpack(matrix_gather<C, CGeometry, M, N>(c, tile_row + i, tile_col + lane % 4));
+        for (uint32_t k0 = 0; k0 < K; k0 += 4)
+        {
+            uint32_t a_tile[4], b_tile[4];
+            for (uint32_t k = 0; k < 4; k++)
+            {
+                a_tile[k] = matrix_gather<A, AGeometry, M, K>(a, tile_row + lane % 4, k0 + k);
+                b_tile[k] = matrix_gather<B, BGeometry, K, N>(b, k0 + k, tile_col + lane % 4);
+            }
+            acc = mfma_gfx9<A>(a_tile, b_tile, acc);
+        }
+        for (uint32_t i = 0; i < CGeometry::elements; i++)
+        {
+            uint32_t row, col;
+            CGeometry::coord(lane, i, row, col);
+            uint32_t block = (row / 4) * (N / 4) + col / 4 - t0;
+            hw_acc_t value = matrix_vec_shfl(acc, 4 * (block % 8) + col % 4, row % 4);
+            if (block < 8)
+                matrix_insert<D>(d, i, D::pack((typename D::acc_t)value, SATFINITE));
+        }
+    }
+}
+
+template <typename D, typename A, typename B, typename C,
+          typename AGeometry, typename BGeometry, typename CGeometry,
+          uint32_t K, bool SATFINITE,
+          uint32_t NA, uint32_t NB, uint32_t NC, uint32_t ND>
+__device__ static inline void matrix_mma_software(const uint32_t (&a)[NA], const uint32_t (&b)[NB], const uint32_t (&c)[NC], uint32_t (&d)[ND])
+{
+    using acc_t = typename D::acc_t;
+    uint32_t lane = FUNC_CALL(sreg_laneid)();
+    for (uint32_t i = 0; i < CGeometry::elements; i++)
+    {
+        uint32_t row, col;
+        CGeometry::coord(lane, i, row, col);
+        acc_t acc = (acc_t)C::unpack(matrix_extract<C>(c, i));
+        for (uint32_t k = 0; k < K; k++)
+        {
+            uint32_t a_lane, a_index, b_lane, b_index;
+            AGeometry::owner(row, k, a_lane, a_index);
+            BGeometry::owner(k, col, b_lane, b_index);
+            acc_t a_value = (acc_t)A::unpack(matrix_shfl<A>(a, a_lane, a_index));
+            acc_t b_value = (acc_t)B::unpack(matrix_shfl<B>(b, b_lane, b_index));
+            acc += a_value * b_value;
+        }
+        matrix_insert<D>(d, i, D::pack(acc, SATFINITE));
+    }
+}
+
+template <typename D, typename A, typename B, typename C,
+          typename AGeometry, typename BGeometry, typename CGeometry,
+          uint32_t K, bool SATFINITE,
+          uint32_t A_LEN, uint32_t B_LEN, uint32_t C_LEN, uint32_t D_LEN>
+__device__ static inline matrix_vec<typename D::register_t, D_LEN> matrix_mma(
+    matrix_vec<typename A::register_t, A_LEN> a_vec,
+    matrix_vec<typename B::register_t, B_LEN> b_vec,
+    matrix_vec<typename C::register_t, C_LEN> c_vec)
+{
+    constexpr uint32_t M = CGeometry::rows;
+    constexpr uint32_t N = CGeometry::cols;
+    __builtin_amdgcn_wave_barrier();
+    uint32_t a[matrix_registers<A, AGeometry>()];
+    uint32_t b[matrix_registers<B, BGeometry>()];
+    uint32_t c[matrix_registers<C, CGeometry>()];
+    uint32_t d[matrix_registers<D, CGeometry>()] = {};
+    matrix_from_vec<typename A::register_t>(a_vec, a);
+    matrix_from_vec<typename B::register_t>(b_vec, b);
+    matrix_from_vec<typename C::register_t>(c_vec, c);
+    if constexpr (wmma_supported<A, B>())
+    {
+        if (FUNC_CALL(MATRIX_CORES) == MatrixCores::Wmma)
+        {
+            matrix_mma_wmma<D, A, B, C, AGeometry, BGeometry, CGeometry, M, N, K, SATFINITE>(a, b, c, d);
+            return matrix_to_vec<typename D::register_t, D_LEN>(d);
+        }
+    }
+    if constexpr (mfma_supported<A, B, SATFINITE>())
+    {
+        if (FUNC_CALL(MATRIX_CORES) == MatrixCores::Mfma)
+        {
+            matrix_mma_mfma<D, A, B, C, AGeometry, BGeometry, CGeometry, M, N, K, SATFINITE>(a, b, c, d);
+            return matrix_to_vec<typename D::register_t, D_LEN>(d);
+        }
+    }
+    matrix_mma_software<D, A, B, C, AGeometry, BGeometry, CGeometry, K, SATFINITE>(a, b, c, d);
+    return matrix_to_vec<typename D::register_t, D_LEN>(d);
+}
+
+// Threads 8*i to 8*i+7 hold addresses of rows of i-th matrix. Without .trans
+// thread t receives elements (t/4, 2*(t%4)) and (t/4, 2*(t%4)+1) of every
+// matrix, with .trans the same elements of the transposed matrix
+template <uint32_t COUNT, bool TRANSPOSE>
+__device__ static inline matrix_vec<uint32_t, COUNT> ldmatrix(SHARED_SPACE uint16_t *ptr)
+{
+    __builtin_amdgcn_wave_barrier();
+    uint32_t lane = FUNC_CALL(sreg_laneid)();
+    uint32_t group = lane / 4;
+    uint32_t thread_in_group = lane % 4;
+    uint32_t address = (uint32_t)(size_t)ptr;
+    matrix_vec<uint32_t, COUNT> result;
+    for (uint32_t m = 0; m < COUNT; m++)
+    {
+        if constexpr (TRANSPOSE)
+        {
+            uint32_t row0 = __shfl(address, (int32_t)(m * 8 + thread_in_group * 2), 32);
+            uint32_t row1 = __shfl(address, (int32_t)(m * 8 + thread_in_group * 2 + 1), 32);
+            uint32_t lo = ((SHARED_SPACE uint16_t *)(size_t)row0)[group];
+            uint32_t hi = ((SHARED_SPACE uint16_t *)(size_t)row1)[group];
+            result[m] = lo | (hi << 16);
+        }
+        else
+        {
+            uint32_t row = __shfl(address, (int32_t)(m * 8 + group), 32);
+            result[m] = ((SHARED_SPACE uint32_t *)(size_t)row)[thread_in_group];
+        }
+    }
+    return result;
+}
+
+extern "C"
+{
+#define WMMA_LOAD(FRAGMENT, SHAPE, TYPE, ROWS, COLS, LEN, LAYOUT, SPACE_NAME, SPACE)                                                                                          \
+    matrix_vec<matrix_##TYPE::register_t, LEN> FUNC(wmma_load_##FRAGMENT##_##LAYOUT##_##SHAPE##_##SPACE_NAME##_##TYPE)(SPACE matrix_##TYPE::memory_t * ptr, uint32_t stride) \
+    {                                                                                                                                                                          \
+        return wmma_load<matrix_##TYPE, wmma_geometry<ROWS, COLS>, matrix_##LAYOUT, LEN>(ptr, stride);                                                                        \
+    }
+
+#define WMMA_STORE(SHAPE, TYPE, ROWS, COLS, LEN, LAYOUT, SPACE_NAME, SPACE)                                                                                                                   \
+    void FUNC(wmma_store_d_##LAYOUT##_##SHAPE##_##SPACE_NAME##_##TYPE)(SPACE matrix_##TYPE::memory_t * ptr, matrix_vec<matrix_##TYPE::register_t, LEN> d, uint32_t stride) \
+    {                                                                                                                                                                          \
+        wmma_store<matrix_##TYPE, wmma_geometry<ROWS, COLS>, matrix_##LAYOUT, LEN>(ptr, d, stride);                                                                           \
+    }
+
+#define WMMA_LOAD_ALL(FRAGMENT, SHAPE, TYPE, ROWS, COLS, LEN)                        \
+    WMMA_LOAD(FRAGMENT, SHAPE, TYPE, ROWS, COLS, LEN, row, generic, GENERIC_SPACE); \
+    WMMA_LOAD(FRAGMENT, SHAPE, TYPE, ROWS, COLS, LEN, row, global, GLOBAL_SPACE);   \
+    WMMA_LOAD(FRAGMENT, SHAPE, TYPE, ROWS, COLS, LEN, row, shared, SHARED_SPACE);   \
+    WMMA_LOAD(FRAGMENT, SHAPE, TYPE, ROWS, COLS, LEN, col, generic, GENERIC_SPACE); \
+    WMMA_LOAD(FRAGMENT, SHAPE, TYPE, ROWS, COLS, LEN, col, global, GLOBAL_SPACE);   \
+    WMMA_LOAD(FRAGMENT, SHAPE, TYPE, ROWS, COLS, LEN, col, shared, SHARED_SPACE);
+
+#define WMMA_STORE_ALL(SHAPE, TYPE, ROWS, COLS, LEN)                        \
+    WMMA_STORE(SHAPE, TYPE, ROWS, COLS, LEN, row, generic, GENERIC_SPACE); \
+    WMMA_STORE(SHAPE, TYPE, ROWS, COLS, LEN, row, global, GLOBAL_SPACE);   \
+    WMMA_STORE(SHAPE, TYPE, ROWS, COLS, LEN, row, shared, SHARED_SPACE);   \
+    WMMA_STORE(SHAPE, TYPE, ROWS, COLS, LEN, col, generic, GENERIC_SPACE); \
+    WMMA_STORE(SHAPE, TYPE, ROWS, COLS, LEN, col, global, GLOBAL_SPACE);   \
+    WMMA_STORE(SHAPE, TYPE, ROWS, COLS, LEN, col, shared, SHARED_SPACE);
+
+#define WMMA_MMA(ALAYOUT, BLAYOUT, SHAPE, D, A, B, C, SUFFIX, SATFINITE, M, N, K, A_LEN, B_LEN, C_LEN, D_LEN)                                              \
+    matrix_vec<matrix_##D::register_t, D_LEN> FUNC(wmma_mma_##ALAYOUT##_##BLAYOUT##_##SHAPE##_##D##_##A##_##B##_##C##SUFFIX)(                           \
+        matrix_vec<matrix_##A::register_t, A_LEN> a,                                                                                                      \
+        matrix_vec<matrix_##B::register_t, B_LEN> b,                                                                                                      \
+        matrix_vec<matrix_##C::register_t, C_LEN> c)                                                                                                      \
+    {                                                                                                                                                      \
+        return matrix_mma<matrix_##D, matrix_##A, matrix_##B, matrix_##C, wmma_geometry<M, K>, wmma_geometry<K, N>, wmma_geometry<M, N>, K, SATFINITE, \
+                          A_LEN, B_LEN, C_LEN, D_LEN>(a, b, c);                                                                                           \
+    }
+
+// wmma fragments are always loaded into our canonical layout, so .alayout and
+// .blayout only change the name
+#define WMMA_MMA_ALL(SHAPE, D, A, B, C, SUFFIX, SATFINITE, M, N, K, A_LEN, B_LEN, C_LEN, D_LEN)            \
+    WMMA_MMA(row, row, SHAPE, D, A, B, C, SUFFIX, SATFINITE, M, N, K, A_LEN, B_LEN, C_LEN, D_LEN); \
+    WMMA_MMA(row, col, SHAPE, D, A, B, C, SUFFIX, SATFINITE, M, N, K, A_LEN, B_LEN, C_LEN, D_LEN); \
+    WMMA_MMA(col, row, SHAPE, D, A, B, C, SUFFIX, SATFINITE, M, N, K, A_LEN, B_LEN, C_LEN, D_LEN); \
+    WMMA_MMA(col, col, SHAPE, D, A, B, C, SUFFIX, SATFINITE, M, N, K, A_LEN, B_LEN, C_LEN, D_LEN);
+
+// .f16 input fragments are 8 registers long, because NVIDIA hardware keeps
+// two copies of every element, we use only the first half
+#define WMMA_SHAPE(SHAPE, M, N, K)                                                                         \
+    WMMA_LOAD_ALL(a, SHAPE, f16, M, K, 8)                                                                  \
+    WMMA_LOAD_ALL(b, SHAPE, f16, K, N, 8)                                                                  \
+    WMMA_LOAD_ALL(a, SHAPE, bf16, M, K, M * K / 64)                                                        \
+    WMMA_LOAD_ALL(b, SHAPE, bf16, K, N, K * N / 64)                                                        \
+    WMMA_LOAD_ALL(a, SHAPE, s8, M, K, M * K / 128)                                                         \
+    WMMA_LOAD_ALL(b, SHAPE, s8, K, N, K * N / 128)                                                         \
+    WMMA_LOAD_ALL(a, SHAPE, u8, M, K, M * K / 128)                                                         \
+    WMMA_LOAD_ALL(b, SHAPE, u8, K, N, K * N / 128)                                                         \
+    WMMA_LOAD_ALL(c, SHAPE, f16, M, N, M * N / 64)                                                         \
+    WMMA_LOAD_ALL(c, SHAPE, f32, M, N, M * N / 32)                                                         \
+    WMMA_LOAD_ALL(c, SHAPE, s32, M, N, M * N / 32)                                                         \
+    WMMA_STORE_ALL(SHAPE, f16, M, N, M * N / 64)                                                           \
+    WMMA_STORE_ALL(SHAPE, f32, M, N, M * N / 32)                                                           \
+    WMMA_STORE_ALL(SHAPE, s32, M, N, M * N / 32)                                                           \
+    WMMA_MMA_ALL(SHAPE, f16, f16, f16, f16, , false, M, N, K, 8, 8, M * N / 64, M * N / 64)                \
+    WMMA_MMA_ALL(SHAPE, f16, f16, f16, f32, , false, M, N, K, 8, 8, M * N / 32, M * N / 64)                \
+    WMMA_MMA_ALL(SHAPE, f32, f16, f16, f16, , false, M, N, K, 8, 8, M * N / 64, M * N / 32)                \
+    WMMA_MMA_ALL(SHAPE, f32, f16, f16, f32, , false, M, N, K, 8, 8, M * N / 32, M * N / 32)                \
+    WMMA_MMA_ALL(SHAPE, f32, bf16, bf16, f32, , false, M, N, K, M * K / 64, K * N / 64, M * N / 32, M * N / 32) \
+    WMMA_MMA_ALL(SHAPE, s32, s8, s8, s32, , false, M, N, K, M * K / 128, K * N / 128, M * N / 32, M * N / 32) \
+    WMMA_MMA_ALL(SHAPE, s32, s8, s8, s32, _satfinite, true, M, N, K, M * K / 128, K * N / 128, M * N / 32, M * N / 32) \
+    WMMA_MMA_ALL(SHAPE, s32, u8, u8, s32, , false, M, N, K, M * K / 128, K * N / 128, M * N / 32, M * N / 32) \
+    WMMA_MMA_ALL(SHAPE, s32, u8, u8, s32, _satfinite, true, M, N, K, M * K / 128, K * N / 128, M * N / 32, M * N / 32)
+
+    WMMA_SHAPE(m16n16k16, 16, 16, 16)
+    WMMA_SHAPE(m8n32k16, 8, 32, 16)
+    WMMA_SHAPE(m32n8k16, 32, 8, 16)
+
+    WMMA_LOAD_ALL(a, m16n16k8, tf32, 16, 8, 4)
+    WMMA_LOAD_ALL(b, m16n16k8, tf32, 8, 16, 4)
+    WMMA_LOAD_ALL(c, m16n16k8, f32, 16, 16, 8)
+    WMMA_STORE_ALL(m16n16k8, f32, 16, 16, 8)
+    WMMA_MMA_ALL(m16n16k8, f32, tf32, tf32, f32, , false, 16, 16, 8, 4, 4, 8, 8)
+
+#define MMA(SHAPE, D, A, B, C, SUFFIX, SATFINITE, A_GEOMETRY, B_GEOMETRY, CD_GEOMETRY, K, A_LEN, B_LEN, C_LEN, D_LEN)                   \
+    matrix_vec<matrix_##D::register_t, D_LEN> FUNC(mma_##SHAPE##_##D##_##A##_##B##_##C##SUFFIX)(                                        \
+        matrix_vec<matrix_##A::register_t, A_LEN> a,                                                                                    \
+        matrix_vec<matrix_##B::register_t, B_LEN> b,                                                                                    \
+        matrix_vec<matrix_##C::register_t, C_LEN> c)                                                                                    \
+    {                                                                                                                                    \
+        return matrix_mma<matrix_##D, matrix_##A, matrix_##B, matrix_##C, A_GEOMETRY, B_GEOMETRY, CD_GEOMETRY, K, SATFINITE, \
+                          A_LEN, B_LEN, C_LEN, D_LEN>(a, b, c);                                                                         \
+    }
+
+#define MMA_F16(SHAPE, A_GEOMETRY, B_GEOMETRY, K, A_LEN, B_LEN)                             \
+    MMA(SHAPE, f16, f16, f16, f16, , false, A_GEOMETRY, B_GEOMETRY, mma_cd_m16n8, K, A_LEN, B_LEN, 2, 2); \
+    MMA(SHAPE, f16, f16, f16, f32, , false, A_GEOMETRY, B_GEOMETRY, mma_cd_m16n8, K, A_LEN, B_LEN, 4, 2); \
+    MMA(SHAPE, f32, f16, f16, f16, , false, A_GEOMETRY, B_GEOMETRY, mma_cd_m16n8, K, A_LEN, B_LEN, 2, 4); \
+    MMA(SHAPE, f32, f16, f16, f32, , false, A_GEOMETRY, B_GEOMETRY, mma_cd_m16n8, K, A_LEN, B_LEN, 4, 4); \
+    MMA(SHAPE, f32, bf16, bf16, f32, , false, A_GEOMETRY, B_GEOMETRY, mma_cd_m16n8, K, A_LEN, B_LEN, 4, 4);
+
+#define MMA_INT_AB(SHAPE, A, B, A_GEOMETRY, B_GEOMETRY, CD_GEOMETRY, K, A_LEN, B_LEN, CD_LEN)                       \
+    MMA(SHAPE, s32, A, B, s32, , false, A_GEOMETRY, B_GEOMETRY, CD_GEOMETRY, K, A_LEN, B_LEN, CD_LEN, CD_LEN); \
+    MMA(SHAPE, s32, A, B, s32, _satfinite, true, A_GEOMETRY, B_GEOMETRY, CD_GEOMETRY, K, A_LEN, B_LEN, CD_LEN, CD_LEN);
+
+#define MMA_INT(SHAPE, A_GEOMETRY, B_GEOMETRY, CD_GEOMETRY, K, A_LEN, B_LEN, CD_LEN)              \
+    MMA_INT_AB(SHAPE, s8, s8, A_GEOMETRY, B_GEOMETRY, CD_GEOMETRY, K, A_LEN, B_LEN, CD_LEN) \
+    MMA_INT_AB(SHAPE, s8, u8, A_GEOMETRY, B_GEOMETRY, CD_GEOMETRY, K, A_LEN, B_LEN, CD_LEN) \
+    MMA_INT_AB(SHAPE, u8, s8, A_GEOMETRY, B_GEOMETRY, CD_GEOMETRY, K, A_LEN, B_LEN, CD_LEN) \
+    MMA_INT_AB(SHAPE, u8, u8, A_GEOMETRY, B_GEOMETRY, CD_GEOMETRY, K, A_LEN, B_LEN, CD_LEN)
+
+    MMA_F16(m16n8k16, mma_a_m16k16_b16, mma_b_k16n8_b16, 16, 4, 2)
+    MMA_F16(m16n8k8, mma_a_m16k8_b16, mma_b_k8n8_b16, 8, 2, 1)
+    MMA(m16n8k4, f32, tf32, tf32, f32, , false, mma_a_m16k4_b32, mma_b_k4n8_b32, mma_cd_m16n8, 4, 2, 1, 4, 4);
+    MMA(m16n8k8, f32, tf32, tf32, f32, , false, mma_a_m16k8_b32, mma_b_k8n8_b32, mma_cd_m16n8, 8, 4, 2, 4, 4);
+    MMA_INT(m8n8k16, mma_a_m8k16_b8, mma_b_k16n8_b8, mma_cd_m8n8, 16, 1, 1, 2)
+    MMA_INT(m16n8k16, mma_a_m16k16_b8, mma_b_k16n8_b8, mma_cd_m16n8, 16, 2, 1, 4)
+    MMA_INT(m16n8k32, mma_a_m16k32_b8, mma_b_k32n8_b8, mma_cd_m16n8, 32, 4, 2, 4)
+
+// Generic address must point to shared memory
+#define LDMATRIX(COUNT, SUFFIX, TRANSPOSE)                                                                            \
+    matrix_vec<uint32_t, COUNT> FUNC(ldmatrix_m8n8_x##COUNT##SUFFIX##_shared_b16)(SHARED_SPACE uint16_t * ptr)   \
+    {                                                                                                                 \
+        return ldmatrix<COUNT, TRANSPOSE>(ptr);                                                                       \
+    }                                                                                                                 \
+    matrix_vec<uint32_t, COUNT> FUNC(ldmatrix_m8n8_x##COUNT##SUFFIX##_generic_b16)(GENERIC_SPACE uint16_t * ptr) \
+    {                                                                                                                 \
+        return ldmatrix<COUNT, TRANSPOSE>((SHARED_SPACE uint16_t *)ptr);                                              \
+    }
+
+    LDMATRIX(1, , false);
+    LDMATRIX(2, , false);
+    LDMATRIX(4, , false);
+    LDMATRIX(1, _trans, true);
+    LDMATRIX(2, _trans, true);
+    LDMATRIX(4, _trans, true);
+}
+
+// Asynchronous copies: cp.async and cp.async.bulk
+// We don't have an asynchronous global->shared copy engine, so every copy is
+// done synchronously by the issuing thread. As a result cp.async.commit_group
+// and cp.async.wait_group have nothing to wait for and are dropped by the compiler
+template <typename T>
+__device__ static inline void cp_async(SHARED_SPACE uint8_t *dst, GLOBAL_SPACE uint8_t *src, uint32_t src_size)
+{
+    if (src_size >= sizeof(T))
+    {
+        *(SHARED_SPACE T *)dst = *(GLOBAL_SPACE T *)src;
+        return;
+    }
+    // Bytes past src-size are filled with zeros
+    for (uint32_t i = 0; i < sizeof(T); i++)
+        dst[i] = i < src_size ? src[i] : 0;
+}
+
+// Size and both addresses of cp.async.bulk are required to be multiples of 16
+template <typename Dst, typename Src>
+__device__ static inline void cp_async_bulk(Dst dst, Src src, uint32_t size)
+{
+    for (uint32_t i = 0; i < size / 16; i++)
+        dst[i] = src[i];
+}
+
+// mbarrier emulation
+// Layout of the 64-bit mbarrier object is opaque, we pack into it:
+//   bits 0-20:  pending arrival count of the current phase
+//   bits 21-41: expected arrival count, as set by mbarrier.init
+//   bits 42-62: pending transaction count (wraps around, because in theory
+//               complete_tx can come before matching expect_tx)
+//   bit 63:     phase parity
+// Current phase completes when both pending arrivals and pending transactions
+// reach zero. State token returned by mbarrier.arrive has the same layout: the
+// phase bit of the arrival and the pending arrival count left after it
+#define MBARRIER_FIELD_MASK ((1ULL << 21) - 1)
+#define MBARRIER_EXPECTED_SHIFT 21
+#define MBARRIER_TX_SHIFT 42
+#define MBARRIER_PHASE_SHIFT 63
+
+template <typename T>
+__device__ static inline uint64_t mbarrier_update(T *ptr, uint32_t arrivals, int32_t transactions, bool drop = false)
+{
+    uint64_t expected = __hip_atomic_load(ptr, __ATOMIC_RELAXED, __HIP_MEMORY_SCOPE_WORKGROUP);
+    uint64_t desired;
+    uint64_t state;
+    do
+    {
+        uint64_t pending = ((expected & MBARRIER_FIELD_MASK) - arrivals) & MBARRIER_FIELD_MASK;
+        uint64_t count = (expected >> MBARRIER_EXPECTED_SHIFT) & MBARRIER_FIELD_MASK;
+        // .arrive_drop: the thread does not take part in the following phases
+        if (drop)
+            count = (count - arrivals) & MBARRIER_FIELD_MASK;
+        uint64_t tx = (((expected >> MBARRIER_TX_SHIFT) & MBARRIER_FIELD_MASK) + (uint64_t)(int64_t)transactions) & MBARRIER_FIELD_MASK;
+        uint64_t phase = expected >> MBARRIER_PHASE_SHIFT;
+        state = pending | (phase << MBARRIER_PHASE_SHIFT);
+        if (pending == 0 && tx == 0)
+        {
+            pending = count;
+            phase ^= 1;
+        }
+        desired = pending | (count << MBARRIER_EXPECTED_SHIFT) | (tx << MBARRIER_TX_SHIFT) | (phase << MBARRIER_PHASE_SHIFT);
+    } while (!__hip_atomic_compare_exchange_strong(ptr, &expected, desired, __ATOMIC_ACQ_REL, __ATOMIC_RELAXED, __HIP_MEMORY_SCOPE_WORKGROUP));
+    return state;
+}
+
+template <typename T>
+__device__ static inline bool mbarrier_phase_completed(T *ptr, uint64_t phase)
+{
+    uint64_t value = __hip_atomic_load(ptr, __ATOMIC_ACQUIRE, __HIP_MEMORY_SCOPE_WORKGROUP);
+    return (value >> MBARRIER_PHASE_SHIFT) != phase;
+}
+
+// mbarrier.try_wait is allowed to suspend the thread until the phase completes
+// or a system-dependent time limit passes, we give it a short while
+template <typename T>
+__device__ static inline bool mbarrier_phase_completed_wait(T *ptr, uint64_t phase)
+{
+    for (uint32_t i = 0; i < 64; i++)
+    {
+        if (mbarrier_phase_completed(ptr, phase))
+            return true;
+        __builtin_amdgcn_s_sleep(1);
+    }
+    return mbarrier_phase_completed(ptr, phase);
+}
+
+extern "C"
+{
+    void FUNC(cp_async_4_shared_global)(SHARED_SPACE uint8_t *dst, GLOBAL_SPACE uint8_t *src, uint32_t src_size)
+    {
+        cp_async<uint32_t>(dst, src, src_size);
+    }
+
+    void FUNC(cp_async_8_shared_global)(SHARED_SPACE uint8_t *dst, GLOBAL_SPACE uint8_t *src, uint32_t src_size)
+    {
+        cp_async<uint64_t>(dst, src, src_size);
+    }
+
+    void FUNC(cp_async_16_shared_global)(SHARED_SPACE uint8_t *dst, GLOBAL_SPACE uint8_t *src, uint32_t src_size)
+    {
+        cp_async<uint4>(dst, src, src_size);
+    }
+
+    void FUNC(cp_async_bulk_shared_global)(SHARED_SPACE uint8_t *dst, GLOBAL_SPACE uint8_t *src, uint32_t size, SHARED_SPACE uint64_t *mbarrier)
+    {
+        cp_async_bulk((SHARED_SPACE uint4 *)dst, (GLOBAL_SPACE uint4 *)src, size);
+        // .mbarrier::complete_tx::bytes
+        mbarrier_update(mbarrier, 0, -(int32_t)size);
+    }
+
+    void FUNC(cp_async_bulk_global_shared)(GLOBAL_SPACE uint8_t *dst, SHARED_SPACE uint8_t *src, uint32_t size)
+    {
+        cp_async_bulk((GLOBAL_SPACE uint4 *)dst, (SHARED_SPACE uint4 *)src, size);
+    }
+
+#define MBARRIER_ARRIVE(NAME, SPACE_NAME, SPACE, DROP)                                                \
+    uint64_t FUNC(mbarrier_##NAME##_##SPACE_NAME)(SPACE uint64_t * ptr, uint32_t count)               \
+    {                                                                                                 \
+        return mbarrier_update(ptr, count, 0, DROP);                                                  \
+    }                                                                                                 \
+                                                                                                      \
+    void FUNC(mbarrier_##NAME##_nostate_##SPACE_NAME)(SPACE uint64_t * ptr, uint32_t count)           \
+    {                                                                                                 \
+        mbarrier_update(ptr, count, 0, DROP);                                                         \
+    }                                                                                                 \
+                                                                                                      \
+    uint64_t FUNC(mbarrier_##NAME##_expect_tx_##SPACE_NAME)(SPACE uint64_t * ptr, uint32_t tx_count)  \
+    {                                                                                                 \
+        return mbarrier_update(ptr, 1, (int32_t)tx_count, DROP);                                      \
+    }                                                                                                 \
+                                                                                                      \
+    void FUNC(mbarrier_##NAME##_expect_tx_nostate_##SPACE_NAME)(SPACE uint64_t * ptr, uint32_t tx_count) \
+    {                                                                                                 \
+        mbarrier_update(ptr, 1, (int32_t)tx_count, DROP);                                             \
+    }
+
+#define MBARRIER(SPACE_NAME, SPACE)                                                                   \
+    void FUNC(mbarrier_init_##SPACE_NAME)(SPACE uint64_t * ptr, uint32_t count)                       \
+    {                                                                                                 \
+        uint64_t value = ((uint64_t)count & MBARRIER_FIELD_MASK) |                                    \
+                         (((uint64_t)count & MBARRIER_FIELD_MASK) << MBARRIER_EXPECTED_SHIFT);        \
+        __hip_atomic_store(ptr, value, __ATOMIC_RELAXED, __HIP_MEMORY_SCOPE_WORKGROUP);               \
+    }                                                                                                 \
+                                                                                                      \
+    MBARRIER_ARRIVE(arrive, SPACE_NAME, SPACE, false)                                                 \
+    MBARRIER_ARRIVE(arrive_drop, SPACE_NAME, SPACE, true)                                             \
+                                                                                                      \
+    void FUNC(mbarrier_inval_##SPACE_NAME)(SPACE uint64_t * ptr)                                      \
+    {                                                                                                 \
+        __hip_atomic_store(ptr, 0, __ATOMIC_RELAXED, __HIP_MEMORY_SCOPE_WORKGROUP);                   \
+    }                                                                                                 \
+                                                                                                      \
+    void FUNC(mbarrier_expect_tx_##SPACE_NAME)(SPACE uint64_t * ptr, uint32_t tx_count)               \
+    {                                                                                                 \
+        mbarrier_update(ptr, 0, (int32_t)tx_count);                                                   \
+    }                                                                                                 \
+                                                                                                      \
+    bool FUNC(mbarrier_test_wait_##SPACE_NAME)(SPACE uint64_t * ptr, uint64_t state)                  \
+    {                                                                                                 \
+        return mbarrier_phase_completed(ptr, state >> MBARRIER_PHASE_SHIFT);                          \
+    }                                                                                                 \
+                                                                                                      \
+    bool FUNC(mbarrier_test_wait_parity_##SPACE_NAME)(SPACE uint64_t * ptr, uint32_t parity)          \
+    {                                                                                                 \
+        return mbarrier_phase_completed(ptr, parity & 1);                                             \
+    }                                                                                                 \
+                                                                                                      \
+    bool FUNC(mbarrier_try_wait_##SPACE_NAME)(SPACE uint64_t * ptr, uint64_t state)                   \
+    {                                                                                                 \
+        return mbarrier_phase_completed_wait(ptr, state >> MBARRIER_PHASE_SHIFT);                     \
+    }                                                                                                 \
+                                                                                                      \
+    bool FUNC(mbarrier_try_wait_parity_##SPACE_NAME)(SPACE uint64_t * ptr, uint32_t parity)           \
+    {                                                                                                 \
+        return mbarrier_phase_completed_wait(ptr, parity & 1);                                        \
+    }
+
+    uint32_t FUNC(mbarrier_pending_count)(uint64_t state)
+    {
+        return (uint32_t)(state & MBARRIER_FIELD_MASK);
+    }
+
+    MBARRIER(generic, GENERIC_SPACE)
+    MBARRIER(shared, SHARED_SPACE)
+}
@@ -1,7 +1,7 @@
 use half::f16;
 use lalrpop_util::{lexer::Token, ParseError};
 use std::alloc::Layout;
-use std::{convert::From, mem, num::ParseFloatError, str::FromStr};
+use std::{convert::From, mem, num::ParseFloatError, ops::Range, str::FromStr};
 use std::{marker::PhantomData, num::ParseIntError};
 
 #[derive(Debug, thiserror::Error)]
@@ -25,6 +25,8 @@ pub enum PtxError {
     #[error("")]
     WrongVectorElement,
     #[error("")]
+    WrongMatrixFragment,
+    #[error("")]
     MultiArrayVariable,
     #[error("")]
     ZeroDimensionArray,
@@ -109,6 +111,24 @@ impl<
 pub struct Module<'a> {
     pub sm_version: u32,
     pub directives: Vec<Directive<'a, ParsedArgParams<'a>>>,
+    // (file index, file name) pairs declared with .file
+    pub debug_files: Vec<(u32, &'a str)>,
+    // Byte ranges of kernel and .func definitions in the module text, used
+    // for compiling kernels one by one
+    pub kernel_spans: Vec<(&'a str, Range<usize>)>,
+    pub function_spans: Vec<(&'a str, Range<usize>)>,
+}
+
+impl<'a> Module<'a> {
+    // Kernels can't call each other, so unless they share module-scope
+    // variables, every kernel can be compiled on its own
+    // (see crate::KernelSplit). Shared memory is per-kernel anyway
+    pub fn has_independent_kernels(&self) -> bool {
+        self.directives.iter().all(|directive| match directive {
+            Directive::Variable(_, variable) => variable.variable.state_space == StateSpace::Shared,
+            Directive::Method(..) => true,
+        })
+    }
 }
 
 pub enum Directive<'a, P: ArgParams> {
@@ -292,12 +312,32 @@ impl StructField {
     }
 }
 
+// Variables and instructions remember their byte range in the module text,
+// so translation errors can point at the offending statement
 pub enum Statement<P: ArgParams> {
     Label(P::Id),
     Callprototype(Callprototype<P::Id>),
-    Variable(Vec<MultiVariableDefinition<P::Id>>),
-    Instruction(Option<PredAt<P::Id>>, Instruction<P>),
+    Variable(Vec<MultiVariableDefinition<P::Id>>, Range<usize>),
+    Instruction(Option<PredAt<P::Id>>, Instruction<P>, Range<usize>),
     Block(Vec<Statement<P>>),
+    Location(SourceLocation),
+}
+
+// Statement's byte range in the text of `module`, which is an index into the
+// list of modules passed for translation
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StatementSpan {
+    pub module: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+// https://docs.nvidia.com/cuda/parallel-thread-execution/index.html#debugging-directives-loc
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: u32,
+    pub line: u32,
+    pub column: u32,
 }
 
 #[derive(Clone)]
@@ -486,6 +526,24 @@ pub enum Instruction<P: ArgParams> {
     Nanosleep(Arg1<P>),
     Isspacep(StateSpace, Arg2<P>),
     Sad(ScalarType, Arg4<P>),
+    WmmaLoad(WmmaLoadDetails, Arg3<P>),
+    WmmaStore(WmmaStoreDetails, Arg3St<P>),
+    WmmaMma(MmaDetails, Arg4<P>),
+    Mma(MmaDetails, Arg4<P>),
+    Ldmatrix(LdmatrixDetails, Arg2Ld<P>),
+    CpAsync(CpAsyncDetails, Arg3St<P>),
+    CpAsyncCommitGroup,
+    CpAsyncWaitGroup(u32),
+    CpAsyncBulk(Arg4St<P>),
+    CpAsyncBulkGroup(Arg3St<P>),
+    MbarrierInit(StateSpace, Arg2St<P>),
+    MbarrierArrive(MbarrierArriveDetails, Arg3<P>),
+    // mbarrier.arrive with the state discarded into `_`
+    MbarrierArriveNoState(MbarrierArriveDetails, Arg2St<P>),
+    MbarrierExpectTx(StateSpace, Arg2St<P>),
+    MbarrierTestWait(MbarrierTestWaitDetails, Arg3<P>),
+    MbarrierPendingCount(Arg2<P>),
+    MbarrierInval(StateSpace, Arg1Bar<P>),
 }
 
 #[derive(Copy, Clone)]
@@ -612,6 +670,12 @@ pub struct Arg3<P: ArgParams> {
     pub src2: P::Operand,
 }
 
+pub struct Arg3St<P: ArgParams> {
+    pub src1: P::Operand,
+    pub src2: P::Operand,
+    pub src3: P::Operand,
+}
+
 pub struct Arg4<P: ArgParams> {
     pub dst: P::Operand,
     pub src1: P::Operand,
@@ -619,6 +683,13 @@ pub struct Arg4<P: ArgParams> {
     pub src3: P::Operand,
 }
 
+pub struct Arg4St<P: ArgParams> {
+    pub src1: P::Operand,
+    pub src2: P::Operand,
+    pub src3: P::Operand,
+    pub src4: P::Operand,
+}
+
 pub struct Arg4Setp<P: ArgParams> {
     pub dst1: P::Id,
     pub dst2: Option<P::Id>,
@@ -1416,6 +1487,113 @@ pub enum TextureGeometry {
     Array2D,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MatrixShape {
+    M8n8k16,
+    M16n8k4,
+    M16n8k8,
+    M16n8k16,
+    M16n8k32,
+    M16n16k8,
+    M16n16k16,
+    M8n32k16,
+    M32n8k16,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MatrixLayout {
+    Row,
+    Col,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MatrixFragment {
+    A,
+    B,
+    C,
+    D,
+}
+
+// Element type of a matrix, this is separate from ScalarType, because .tf32
+// is only valid in matrix instructions
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MatrixType {
+    F16,
+    BF16,
+    TF32,
+    F32,
+    S8,
+    U8,
+    S32,
+}
+
+#[derive(Clone, Copy)]
+pub struct WmmaLoadDetails {
+    pub fragment: MatrixFragment,
+    pub layout: MatrixLayout,
+    pub shape: MatrixShape,
+    pub state_space: StateSpace,
+    pub type_: MatrixType,
+}
+
+#[derive(Clone, Copy)]
+pub struct WmmaStoreDetails {
+    pub layout: MatrixLayout,
+    pub shape: MatrixShape,
+    pub state_space: StateSpace,
+    pub type_: MatrixType,
+}
+
+// Shared by wmma.mma and mma
+#[derive(Clone, Copy)]
+pub struct MmaDetails {
+    pub shape: MatrixShape,
+    pub a_layout: MatrixLayout,
+    pub b_layout: MatrixLayout,
+    pub d_type: MatrixType,
+    pub a_type: MatrixType,
+    pub b_type: MatrixType,
+    pub c_type: MatrixType,
+    pub satfinite: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct LdmatrixDetails {
+    // number of 8x8 matrices: 1, 2 or 4
+    pub count: u8,
+    pub transpose: bool,
+    // .shared or generic
+    pub state_space: StateSpace,
+}
+
+#[derive(Clone, Copy)]
+pub struct CpAsyncDetails {
+    // only .ca and .cg are legal
+    pub cache: LdCacheOperator,
+    // number of bytes copied: 4, 8 or 16
+    pub cp_size: u8,
+}
+
+#[derive(Clone, Copy)]
+pub struct MbarrierArriveDetails {
+    pub state_space: StateSpace,
+    // For .expect_tx the last operand is the transaction count,
+    // otherwise it's the arrival count
+    pub expect_tx: bool,
+    // .arrive_drop
+    pub drop: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct MbarrierTestWaitDetails {
+    pub state_space: StateSpace,
+    // .try_wait is allowed to suspend the thread for a while
+    pub try_wait: bool,
+    // With .parity the last operand is the phase parity, otherwise it's
+    // the state returned by mbarrier.arrive
+    pub parity: bool,
+}
+
 #[derive(Clone)]
 pub enum Initializer<ID> {
     Constant(ImmediateValue),
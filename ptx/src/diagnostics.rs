@@ -0,0 +1,247 @@
+// Human-readable explanation of why a module could not be compiled. Parser
+// errors know their position in the source text, translation errors know the
+// function and, if it came from one, the PTX statement that failed
+use crate::ast::PtxError;
+use crate::translate::TranslateError;
+use lalrpop_util::{lexer::Token, ParseError};
+use std::{fmt, ops::Range};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagnosticReason {
+    UnsupportedInstruction,
+    UnsupportedDirective,
+    SyntaxError,
+    TypeMismatch,
+    UnknownSymbol,
+    SymbolRedefinition,
+    Unsupported,
+    Internal,
+}
+
+impl DiagnosticReason {
+    pub fn is_unsupported(self) -> bool {
+        matches!(
+            self,
+            DiagnosticReason::UnsupportedInstruction
+                | DiagnosticReason::UnsupportedDirective
+                | DiagnosticReason::Unsupported
+        )
+    }
+}
+
+impl fmt::Display for DiagnosticReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            DiagnosticReason::UnsupportedInstruction => "unsupported instruction",
+            DiagnosticReason::UnsupportedDirective => "unsupported directive",
+            DiagnosticReason::SyntaxError => "syntax error",
+            DiagnosticReason::TypeMismatch => "type mismatch",
+            DiagnosticReason::UnknownSymbol => "unknown symbol",
+            DiagnosticReason::SymbolRedefinition => "symbol redefinition",
+            DiagnosticReason::Unsupported => "unsupported feature",
+            DiagnosticReason::Internal => "internal compiler error",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub reason: DiagnosticReason,
+    // Byte range in the module text
+    pub span: Option<Range<usize>>,
+    // 1-based line and column of the start of the span
+    pub position: Option<(usize, usize)>,
+    // Offending source text with whitespace collapsed
+    pub text: Option<String>,
+    pub function: Option<String>,
+    pub details: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn from_parse_error(
+        module_text: &str,
+        error: &ParseError<usize, Token<'_>, PtxError>,
+    ) -> Self {
+        let (reason, span, first_line_only, details) = match error {
+            ParseError::User {
+                error: PtxError::UnrecognizedStatement { start, end },
+            } => (
+                DiagnosticReason::UnsupportedInstruction,
+                Some(*start..*end),
+                false,
+                None,
+            ),
+            // Unrecognized directive can span the whole function
+            ParseError::User {
+                error: PtxError::UnrecognizedDirective { start, end },
+            } => (
+                DiagnosticReason::UnsupportedDirective,
+                Some(*start..*end),
+                true,
+                None,
+            ),
+            ParseError::User { error } => (
+                DiagnosticReason::SyntaxError,
+                None,
+                true,
+                Some(format!("{:?}", error)),
+            ),
+            ParseError::InvalidToken { location } => (
+                DiagnosticReason::SyntaxError,
+                Some(*location..*location),
+                true,
+                Some("invalid token".to_string()),
+            ),
+            ParseError::UnrecognizedEof { location, expected } => (
+                DiagnosticReason::SyntaxError,
+                Some(*location..*location),
+                true,
+                Some(format!(
+                    "unexpected end of file, expected {}",
+                    expected.join(", ")
+                )),
+            ),
+            ParseError::UnrecognizedToken {
+                token: (start, token, end),
+                expected,
+            } => (
+                DiagnosticReason::SyntaxError,
+                Some(*start..*end),
+                true,
+                Some(format!(
+                    "unexpected token `{}`, expected {}",
+                    token,
+                    expected.join(", ")
+                )),
+            ),
+            ParseError::ExtraToken {
+                token: (start, token, end),
+            } => (
+                DiagnosticReason::SyntaxError,
+                Some(*start..*end),
+                true,
+                Some(format!("extra token `{}`", token)),
+            ),
+        };
+        let (position, text) = match span {
+            Some(ref span) => (
+                Some(line_and_column(module_text, span.start)),
+                source_text(module_text, span.clone(), first_line_only),
+            ),
+            None => (None, None),
+        };
+        Diagnostic {
+            reason,
+            span,
+            position,
+            text,
+            function: None,
+            details,
+        }
+    }
+
+    // `module_texts` are the modules passed for translation, in the same order
+    pub fn from_translate_error<T: AsRef<str>>(module_texts: &[T], error: &TranslateError) -> Self {
+        let (function, span, error) = match error {
+            TranslateError::InFunction(context) => {
+                (context.function.clone(), context.span, &context.error)
+            }
+            error => (None, None, error),
+        };
+        let (reason, details) = match error {
+            TranslateError::MismatchedType => (DiagnosticReason::TypeMismatch, None),
+            TranslateError::UnknownSymbol | TranslateError::UntypedSymbol => {
+                (DiagnosticReason::UnknownSymbol, None)
+            }
+            TranslateError::SymbolRedefinition => (DiagnosticReason::SymbolRedefinition, None),
+            TranslateError::Todo | TranslateError::UnexpectedPattern => {
+                (DiagnosticReason::Unsupported, None)
+            }
+            TranslateError::Unsupported(what) => {
+                (DiagnosticReason::Unsupported, Some(what.to_string()))
+            }
+            TranslateError::LLVM(message) => (
+                DiagnosticReason::Internal,
+                Some(message.as_cstr().to_string_lossy().into_owned()),
+            ),
+            TranslateError::Unreachable | TranslateError::InFunction(_) => {
+                (DiagnosticReason::Internal, None)
+            }
+        };
+        let module_text = span.and_then(|span| module_texts.get(span.module));
+        let (span, position, text) = match (span, module_text) {
+            (Some(span), Some(module_text)) => {
+                let module_text = module_text.as_ref();
+                (
+                    Some(span.start..span.end),
+                    Some(line_and_column(module_text, span.start)),
+                    source_text(module_text, span.start..span.end, false),
+                )
+            }
+            _ => (None, None, None),
+        };
+        Diagnostic {
+            reason,
+            span,
+            position,
+            text,
+            function,
+            details,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((line, column)) = self.position {
+            write!(f, "{}:{}: ", line, column)?;
+        }
+        write!(f, "{}", self.reason)?;
+        if let Some(ref text) = self.text {
+            write!(f, " `{}`", text)?;
+        }
+        if let Some(ref function) = self.function {
+            write!(f, " in function {}", function)?;
+        }
+        if let Some(ref details) = self.details {
+            write!(f, " ({})", details)?;
+        }
+        Ok(())
+    }
+}
+
+fn line_and_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = match text.get(..offset) {
+        Some(before) => before,
+        None => return (0, 0),
+    };
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+fn source_text(text: &str, span: Range<usize>, first_line_only: bool) -> Option<String> {
+    let span = if span.start == span.end || first_line_only {
+        // Point at the whole line
+        let line_start = text
+            .get(..span.start)?
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+        let line_end = text[span.start..]
+            .find('\n')
+            .map_or(text.len(), |newline| span.start + newline);
+        line_start..line_end
+    } else {
+        span
+    };
+    let text = text
+        .get(span)?
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
@@ -9,6 +9,7 @@ use std::io::Write;
 use std::ptr::null_mut;
 use std::{iter, mem, ptr};
 use zluda_llvm::core::*;
+use zluda_llvm::debuginfo::*;
 use zluda_llvm::prelude::*;
 use zluda_llvm::zluda::*;
 use zluda_llvm::*;
@@ -38,6 +39,7 @@ struct EmitContext<'a> {
     names: NamedIdGenerator,
     denorm_statistics: FxHashMap<Id, DenormSummary>,
     compilation_mode: CompilationMode,
+    debug_info: Option<DebugInfo>,
 }
 
 impl<'a> EmitContext<'a> {
@@ -49,6 +51,7 @@ impl<'a> EmitContext<'a> {
         directive: &[Directive<'input>],
         denorm_statistics: FxHashMap<Id, DenormSummary>,
         compilation_mode: CompilationMode,
+        debug_info: Option<DebugInfo>,
     ) -> Self {
         let builder = unsafe { llvm::Builder::create(context.get()) };
         let texref_underlying_type =
@@ -62,6 +65,94 @@ impl<'a> EmitContext<'a> {
             names: NamedIdGenerator::new(id_gen, id_defs, directive),
             denorm_statistics,
             compilation_mode,
+            debug_info,
+        }
+    }
+}
+
+// Line tables built from .file and .loc directives. Every method with a body
+// gets a DISubprogram and every instruction inherits location of the last
+// preceding .loc (or beginning of the method if there's none)
+struct DebugInfo {
+    builder: llvm::DIBuilder,
+    compile_unit: LLVMMetadataRef,
+    subroutine_type: LLVMMetadataRef,
+    files: Vec<LLVMMetadataRef>,
+    // DISubprogram of the method currently being emitted
+    subprogram: Option<(LLVMMetadataRef, LLVMMetadataRef)>,
+}
+
+impl DebugInfo {
+    const PRODUCER: &'static str = "ZLUDA";
+
+    unsafe fn new(module: &llvm::Module, debug_files: &[&str]) -> Option<Self> {
+        if debug_files.is_empty() {
+            return None;
+        }
+        let builder = llvm::DIBuilder::create(module.get());
+        let files = debug_files
+            .iter()
+            .map(|file_name| {
+                LLVMDIBuilderCreateFile(
+                    builder.get(),
+                    file_name.as_ptr().cast(),
+                    file_name.len(),
+                    LLVM_UNNAMED,
+                    0,
+                )
+            })
+            .collect::<Vec<_>>();
+        let compile_unit = LLVMDIBuilderCreateCompileUnit(
+            builder.get(),
+            LLVMDWARFSourceLanguage::LLVMDWARFSourceLanguageC_plus_plus,
+            files[0],
+            Self::PRODUCER.as_ptr().cast(),
+            Self::PRODUCER.len(),
+            1,
+            LLVM_UNNAMED,
+            0,
+            0,
+            LLVM_UNNAMED,
+            0,
+            LLVMDWARFEmissionKind::LLVMDWARFEmissionKindLineTablesOnly,
+            0,
+            0,
+            0,
+            LLVM_UNNAMED,
+            0,
+            LLVM_UNNAMED,
+            0,
+        );
+        let subroutine_type = LLVMDIBuilderCreateSubroutineType(
+            builder.get(),
+            files[0],
+            ptr::null_mut(),
+            0,
+            LLVMDIFlagZero,
+        );
+        Some(Self {
+            builder,
+            compile_unit,
+            subroutine_type,
+            files,
+            subprogram: None,
+        })
+    }
+
+    unsafe fn finalize(self, context: &llvm::Context, module: &llvm::Module) {
+        LLVMDIBuilderFinalize(self.builder.get());
+        let i32_type = LLVMInt32TypeInContext(context.get());
+        for (key, value) in [
+            (&b"Debug Info Version"[..], LLVMDebugMetadataVersion()),
+            (&b"Dwarf Version"[..], 5),
+        ] {
+            LLVMAddModuleFlag(
+                module.get(),
+                LLVMModuleFlagBehavior::LLVMModuleFlagBehaviorWarning,
+                key.as_ptr().cast(),
+                key.len(),
+                LLVMValueAsMetadata(LLVMConstInt(i32_type, value as u64, 0)),
+            );
         }
     }
 }
@@ -217,11 +308,17 @@ impl Constants {
 pub(crate) unsafe fn emit_llvm_bitcode_and_linker_module<'input>(
     module: TranslationModule<'input, ExpandedArgParams>,
     denorm_statistics: FxHashMap<Id, DenormSummary>,
+    line_info: bool,
 ) -> Result<(llvm::Context, llvm::Module), TranslateError> {
     let context = llvm::Context::create();
     LLVMContextSetOpaquePointers(context.get(), 1);
     let llvm_module = llvm::Module::create(b"\0".as_ptr() as _, context.get());
     {
+        let debug_info = if line_info {
+            DebugInfo::new(&llvm_module, &module.debug_files)
+        } else {
+            None
+        };
         let mut emit_ctx = EmitContext::new(
             &context,
             &llvm_module,
@@ -230,11 +327,15 @@ pub(crate) unsafe fn emit_llvm_bitcode_and_linker_module<'input>(
             &module.directives,
             denorm_statistics,
             module.compilation_mode,
+            debug_info,
         );
         emit_prelude(&mut emit_ctx);
         for d in module.directives {
             emit_directive(&mut emit_ctx, d)?;
         }
+        if let Some(debug_info) = emit_ctx.debug_info.take() {
+            debug_info.finalize(&context, &llvm_module);
+        }
     }
     if cfg!(debug_assertions) {
         if let Some(error_msg) = llvm_module.verify() {
@@ -245,6 +346,50 @@ pub(crate) unsafe fn emit_llvm_bitcode_and_linker_module<'input>(
     Ok((context, llvm_module))
 }
 
+// Limits coming from CU_JIT_MAX_REGISTERS and CU_JIT_THREADS_PER_BLOCK. They
+// are applied to every kernel, but explicit .maxntid/.reqntid take precedence
+pub(crate) unsafe fn emit_kernel_resource_limits(
+    context: &llvm::Context,
+    module: &llvm::Module,
+    max_registers: Option<u32>,
+    threads_per_block: Option<u32>,
+) {
+    const FLAT_WORK_GROUP_SIZE: &[u8] = b"amdgpu-flat-work-group-size";
+    const NUM_VGPR: &[u8] = b"amdgpu-num-vgpr";
+    let mut function = LLVMGetFirstFunction(module.get());
+    while function != ptr::null_mut() {
+        if LLVMGetFunctionCallConv(function) == LLVMCallConv::LLVMAMDGPUKERNELCallConv as u32
+            && LLVMIsDeclaration(function) == 0
+        {
+            if let Some(max_registers) = max_registers {
+                add_string_attribute(
+                    context,
+                    function,
+                    NUM_VGPR,
+                    max_registers.to_string().as_bytes(),
+                );
+            }
+            if let Some(threads_per_block) = threads_per_block {
+                let existing = LLVMGetStringAttributeAtIndex(
+                    function,
+                    LLVMAttributeFunctionIndex,
+                    FLAT_WORK_GROUP_SIZE.as_ptr().cast(),
+                    FLAT_WORK_GROUP_SIZE.len() as u32,
+                );
+                if existing == ptr::null_mut() {
+                    add_string_attribute(
+                        context,
+                        function,
+                        FLAT_WORK_GROUP_SIZE,
+                        format!("1,{}", threads_per_block).as_bytes(),
+                    );
+                }
+            }
+        }
+        function = LLVMGetNextFunction(function);
+    }
+}
+
 pub(crate) fn emit_section(section_name: &str, metadata: &[u8], text_buffer: &mut Vec<u8>) {
     writeln!(text_buffer, ".section {}", section_name).ok();
     writeln!(text_buffer, ".p2align 3").ok();
@@ -537,19 +682,102 @@ fn emit_method<'a, 'input>(
     let starting_bb =
         unsafe { LLVMAppendBasicBlockInContext(ctx.context.get(), llvm_method, LLVM_UNNAMED) };
     unsafe { LLVMPositionBuilderAtEnd(ctx.builder.get(), starting_bb) };
+    unsafe { emit_subprogram(ctx, method.name, llvm_method, &statements) };
     for statement in statements.iter() {
         register_basic_blocks(ctx, llvm_method, statement);
     }
+    let mut span = None;
     for statement in statements.into_iter() {
-        emit_statement(ctx, is_kernel, statement)?;
+        if let crate::translate::Statement::Span(statement_span) = statement {
+            span = Some(statement_span);
+        }
+        emit_statement(ctx, is_kernel, statement).map_err(|err| {
+            err.with_span(span)
+                .with_function(method.source_name.as_deref())
+        })?;
     }
     // happens if there is a post-ret trailing label
     terminate_current_block_if_not_terminated(ctx, None);
     unsafe { LLVMPositionBuilderAtEnd(ctx.builder.get(), bb_with_variables) };
     unsafe { LLVMBuildBr(ctx.builder.get(), starting_bb) };
+    if let Some(ref mut debug_info) = ctx.debug_info {
+        debug_info.subprogram = None;
+        unsafe { LLVMSetCurrentDebugLocation2(ctx.builder.get(), ptr::null_mut()) };
+    }
     Ok(())
 }
 
+unsafe fn emit_subprogram(
+    ctx: &mut EmitContext,
+    method_name: Id,
+    llvm_method: LLVMValueRef,
+    statements: &[crate::translate::ExpandedStatement],
+) {
+    let debug_info = match ctx.debug_info {
+        Some(ref mut debug_info) => debug_info,
+        None => return,
+    };
+    let first_location = statements.iter().find_map(|statement| match statement {
+        crate::translate::Statement::Location(location) => Some(*location),
+        _ => None,
+    });
+    let (file, line) = match first_location {
+        Some(location) => (debug_info.files[location.file as usize], location.line),
+        None => (debug_info.files[0], 0),
+    };
+    let name = ctx.names.name(method_name);
+    let subprogram = LLVMDIBuilderCreateFunction(
+        debug_info.builder.get(),
+        debug_info.compile_unit,
+        name.as_ptr(),
+        name.len(),
+        name.as_ptr(),
+        name.len(),
+        file,
+        line,
+        debug_info.subroutine_type,
+        (LLVMGetLinkage(llvm_method) == LLVMLinkage::LLVMPrivateLinkage) as i32,
+        1,
+        line,
+        LLVMDIFlagZero,
+        1,
+    );
+    LLVMSetSubprogram(llvm_method, subprogram);
+    debug_info.subprogram = Some((subprogram, file));
+    // Instructions before the first .loc still need a location in this
+    // subprogram, otherwise LLVM verifier rejects calls to inlinable functions
+    let location =
+        LLVMDIBuilderCreateDebugLocation(ctx.context.get(), line, 0, subprogram, ptr::null_mut());
+    LLVMSetCurrentDebugLocation2(ctx.builder.get(), location);
+}
+
+fn emit_location(ctx: &mut EmitContext, location: ast::SourceLocation) {
+    let debug_info = match ctx.debug_info {
+        Some(ref debug_info) => debug_info,
+        None => return,
+    };
+    let (subprogram, subprogram_file) = match debug_info.subprogram {
+        Some(subprogram) => subprogram,
+        None => return,
+    };
+    let file = debug_info.files[location.file as usize];
+    unsafe {
+        let scope = if file == subprogram_file {
+            subprogram
+        } else {
+            LLVMDIBuilderCreateLexicalBlockFile(debug_info.builder.get(), subprogram, file, 0)
+        };
+        let location = LLVMDIBuilderCreateDebugLocation(
+            ctx.context.get(),
+            location.line,
+            location.column,
+            scope,
+            ptr::null_mut(),
+        );
+        LLVMSetCurrentDebugLocation2(ctx.builder.get(), location);
+    }
+}
+
 fn emit_tuning<'a>(
     ctx: &mut EmitContext<'a>,
     llvm_method: *mut LLVMValue,
@@ -625,7 +853,12 @@ fn emit_statement(
     is_kernel: bool,
     statement: crate::translate::ExpandedStatement,
 ) -> Result<(), TranslateError> {
-    if !matches!(statement, crate::translate::Statement::Label(..)) {
+    if !matches!(
+        statement,
+        crate::translate::Statement::Label(..)
+            | crate::translate::Statement::Location(..)
+            | crate::translate::Statement::Span(..)
+    ) {
         start_next_block_if_terminated(ctx);
     }
     Ok(match statement {
@@ -659,6 +892,8 @@ fn emit_statement(
         crate::translate::Statement::AsmVolatile { asm, constraints } => unsafe {
             emit_asm_volatile(ctx, asm, constraints)?
         },
+        crate::translate::Statement::Location(location) => emit_location(ctx, location),
+        crate::translate::Statement::Span(..) => {}
     })
 }
 
@@ -1165,6 +1400,8 @@ fn emit_instruction(
         ast::Instruction::Red(details, arg) => emit_inst_red(ctx, details, arg)?,
         ast::Instruction::Isspacep(space, arg) => emit_inst_isspacep(ctx, *space, arg)?,
         ast::Instruction::Sad(type_, arg) => emit_inst_sad(ctx, *type_, arg)?,
+        // cp.async copies are lowered to synchronous copies, there's nothing to wait for
+        ast::Instruction::CpAsyncCommitGroup | ast::Instruction::CpAsyncWaitGroup(..) => {}
         // replaced by function calls or Statement variants
         ast::Instruction::Activemask { .. }
         | ast::Instruction::Bar(..)
@@ -1185,7 +1422,22 @@ fn emit_instruction(
         | ast::Instruction::Shfl(..)
         | ast::Instruction::Dp4a(..)
         | ast::Instruction::Nanosleep(..)
-        | ast::Instruction::MatchAny(..) => return Err(TranslateError::unreachable()),
+        | ast::Instruction::MatchAny(..)
+        | ast::Instruction::WmmaLoad(..)
+        | ast::Instruction::WmmaStore(..)
+        | ast::Instruction::WmmaMma(..)
+        | ast::Instruction::Mma(..)
+        | ast::Instruction::Ldmatrix(..)
+        | ast::Instruction::CpAsync(..)
+        | ast::Instruction::CpAsyncBulk(..)
+        | ast::Instruction::CpAsyncBulkGroup(..)
+        | ast::Instruction::MbarrierInit(..)
+        | ast::Instruction::MbarrierArrive(..)
+        | ast::Instruction::MbarrierArriveNoState(..)
+        | ast::Instruction::MbarrierExpectTx(..)
+        | ast::Instruction::MbarrierTestWait(..)
+        | ast::Instruction::MbarrierPendingCount(..)
+        | ast::Instruction::MbarrierInval(..) => return Err(TranslateError::unreachable()),
     })
 }
 
@@ -3765,10 +4017,19 @@ fn emit_llvm_string_attribute(
     llvm_func: LLVMValueRef,
     key: &[u8],
     value: &[u8],
+) {
+    add_string_attribute(ctx.context, llvm_func, key, value)
+}
+
+fn add_string_attribute(
+    context: &llvm::Context,
+    llvm_func: LLVMValueRef,
+    key: &[u8],
+    value: &[u8],
 ) {
     let denorm_attr = unsafe {
         LLVMCreateStringAttribute(
-            ctx.context.get(),
+            context.get(),
             key.as_ptr() as _,
             key.len() as u32,
             value.as_ptr() as _,
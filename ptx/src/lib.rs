@@ -10,6 +10,7 @@ extern crate hip_runtime_sys as hip;
 lalrpop_mod!(ptx);
 
 pub mod ast;
+pub mod diagnostics;
 mod emit;
 pub mod llvm;
 pub mod raytracing;
@@ -19,11 +20,15 @@ pub mod translate;
 
 pub use crate::ptx::ModuleParser;
 use ast::PtxError;
+pub use diagnostics::{Diagnostic, DiagnosticReason};
 pub use lalrpop_util::lexer::Token;
 pub use lalrpop_util::ParseError;
+use std::collections::HashMap;
 use std::fmt;
+use std::ops::Range;
 pub use translate::to_llvm_module;
 pub use translate::to_llvm_module_for_raytracing;
+pub use translate::to_llvm_module_with_line_info;
 pub use translate::Module;
 pub use translate::TranslateError;
 
@@ -73,6 +78,9 @@ impl ModuleParserExt for ModuleParser {
                 ast::Module {
                     sm_version: 0,
                     directives: Vec::new(),
+                    debug_files: Vec::new(),
+                    kernel_spans: Vec::new(),
+                    function_spans: Vec::new(),
                 }
             }
         };
@@ -124,6 +132,115 @@ impl<'a, Loc, Tok, Err> DisplayParseError<'a, Loc, Tok, Err> {
     }
 }
 
+// Where the kernels and .func definitions of a module are in its text, used
+// for compiling kernels one by one. Kernels can't be called by other
+// functions, so a module with a single kernel and the .func definitions it
+// calls (directly or not) is valid and behaves the same for that kernel
+pub struct KernelSplit {
+    kernels: Vec<Range<usize>>,
+    functions: Vec<Range<usize>>,
+    // Sorted indices into `functions` of the definitions every kernel needs
+    dependencies: Vec<Vec<usize>>,
+}
+
+impl KernelSplit {
+    // `ast` must be parsed from `module_text`
+    pub fn new(module_text: &str, ast: &ast::Module) -> Self {
+        let mut function_indices = HashMap::<&str, Vec<usize>>::new();
+        for (index, (name, _)) in ast.function_spans.iter().enumerate() {
+            function_indices.entry(*name).or_default().push(index);
+        }
+        // Call targets are found by looking at identifiers in the function
+        // text. This is conservative: a name mentioned in any other way (e.g.
+        // taken as an address or in a comment) keeps the definition too
+        let calls = |span: &Range<usize>| {
+            identifiers(&module_text[span.clone()])
+                .filter_map(|name| function_indices.get(name))
+                .flatten()
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        let function_calls = ast
+            .function_spans
+            .iter()
+            .map(|(_, span)| calls(span))
+            .collect::<Vec<_>>();
+        let dependencies = ast
+            .kernel_spans
+            .iter()
+            .map(|(_, span)| {
+                let mut reachable = vec![false; function_calls.len()];
+                let mut stack = calls(span);
+                while let Some(function) = stack.pop() {
+                    if !reachable[function] {
+                        reachable[function] = true;
+                        stack.extend_from_slice(&function_calls[function]);
+                    }
+                }
+                reachable
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(index, reachable)| if reachable { Some(index) } else { None })
+                    .collect()
+            })
+            .collect();
+        KernelSplit {
+            kernels: ast
+                .kernel_spans
+                .iter()
+                .map(|(_, span)| span.clone())
+                .collect(),
+            functions: ast
+                .function_spans
+                .iter()
+                .map(|(_, span)| span.clone())
+                .collect(),
+            dependencies,
+        }
+    }
+
+    pub fn kernel_count(&self) -> usize {
+        self.kernels.len()
+    }
+
+    // Returns the module text with all other kernels and all .func definitions
+    // the kernel doesn't need replaced by empty lines, so the line numbers of
+    // the remaining code stay the same
+    pub fn kernel_text(&self, module_text: &str, kernel: usize) -> String {
+        let mut removed = self
+            .kernels
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != kernel)
+            .map(|(_, span)| span)
+            .chain(
+                self.functions
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| self.dependencies[kernel].binary_search(index).is_err())
+                    .map(|(_, span)| span),
+            )
+            .collect::<Vec<_>>();
+        removed.sort_unstable_by_key(|span| span.start);
+        let mut result = String::with_capacity(module_text.len());
+        let mut position = 0;
+        for span in removed {
+            result.push_str(&module_text[position..span.start]);
+            for _ in module_text[span.clone()].matches('\n') {
+                result.push('\n');
+            }
+            position = span.end;
+        }
+        result.push_str(&module_text[position..]);
+        result
+    }
+}
+
+fn identifiers(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '%'))
+        .filter(|token| !token.is_empty())
+}
+
 pub(crate) mod lalrpop {
     use crate::ast;
     use lalrpop_util::{lexer::Token, ParseError};
@@ -466,11 +583,31 @@ pub(crate) mod lalrpop {
             }),
         }
     }
+
+    pub(crate) fn matrix_fragment<'input>(
+        inp: &'input str,
+    ) -> Result<
+        ast::MatrixFragment,
+        ParseError<usize, lalrpop_util::lexer::Token<'input>, ast::PtxError>,
+    > {
+        // .b is a keyword, so it's handled directly in the grammar
+        match inp {
+            ".a" => Ok(ast::MatrixFragment::A),
+            ".c" => Ok(ast::MatrixFragment::C),
+            ".d" => Ok(ast::MatrixFragment::D),
+            _ => Err(ParseError::User {
+                error: ast::PtxError::WrongMatrixFragment,
+            }),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{DisplayParseError, ModuleParser, ModuleParserExt};
+    use crate::{
+        ast, Diagnostic, DiagnosticReason, DisplayParseError, KernelSplit, ModuleParser,
+        ModuleParserExt,
+    };
 
     #[test]
     fn error_report_unknown_instructions() {
@@ -496,4 +633,117 @@ mod tests {
         let build_log_string = format!("{}", reporter);
         assert!(build_log_string.contains("does_not_exist"));
     }
+
+    #[test]
+    fn diagnostic_unknown_instructions() {
+        let module = r#"
+            .version 6.5
+            .target sm_30
+            .address_size 64
+
+            .visible .entry add(
+                .param .u64 input,
+            )
+            {
+                .reg .u64 	        x;
+                does_not_exist.u64  x, x;
+                ret;
+            }"#;
+        let errors = match ModuleParser::parse_checked(module) {
+            Err(e) => e,
+            Ok(_) => panic!(),
+        };
+        let diagnostic = Diagnostic::from_parse_error(module, &errors[0]);
+        assert_eq!(diagnostic.reason, DiagnosticReason::UnsupportedInstruction);
+        assert_eq!(diagnostic.position, Some((11, 17)));
+        assert_eq!(diagnostic.text.as_deref(), Some("does_not_exist.u64 x, x;"));
+        assert_eq!(
+            diagnostic.to_string(),
+            "11:17: unsupported instruction `does_not_exist.u64 x, x;`"
+        );
+    }
+
+    #[test]
+    fn diagnostic_translate_error() {
+        let module = r#"
+            .version 6.5
+            .target sm_30
+            .address_size 64
+
+            .visible .entry add(
+                .param .u64 input
+            )
+            {
+                .reg .u64 	        x;
+                add.u64             x, x, does_not_exist;
+                ret;
+            }"#;
+        let ast = ModuleParser::parse_checked(module).unwrap();
+        let error = match crate::to_llvm_module(hip_common::CompilationMode::Wave32, vec![ast]) {
+            Err(e) => e,
+            Ok(_) => panic!(),
+        };
+        let diagnostic = Diagnostic::from_translate_error(&[module], &error);
+        assert_eq!(diagnostic.reason, DiagnosticReason::UnknownSymbol);
+        assert_eq!(diagnostic.position, Some((11, 17)));
+        assert_eq!(
+            diagnostic.text.as_deref(),
+            Some("add.u64 x, x, does_not_exist;")
+        );
+        assert_eq!(
+            diagnostic.to_string(),
+            "11:17: unknown symbol `add.u64 x, x, does_not_exist;` in function add"
+        );
+    }
+
+    #[test]
+    fn kernel_text_keeps_lines() {
+        let module = r#"
+            .version 6.5
+            .target sm_30
+            .address_size 64
+
+            .func helper() { ret; }
+
+            .func outer() { call helper, (); ret; }
+
+            .func unused() { ret; }
+
+            .visible .entry first()
+            {
+                ret;
+            }
+
+            .visible .entry second()
+            {
+                call outer, ();
+                ret;
+            }"#;
+        let ast = ModuleParser::parse_checked(module).unwrap();
+        let names = ast
+            .kernel_spans
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["first", "second"]);
+        let split = KernelSplit::new(module, &ast);
+        assert_eq!(split.kernel_count(), 2);
+        let first = split.kernel_text(module, 0);
+        assert_eq!(first.matches('\n').count(), module.matches('\n').count());
+        assert!(!first.contains("helper") && !first.contains("outer"));
+        assert!(!first.contains("unused") && !first.contains("second"));
+        let second = split.kernel_text(module, 1);
+        assert_eq!(second.matches('\n').count(), module.matches('\n').count());
+        assert!(!second.contains("first") && !second.contains("unused"));
+        let second_ast = ModuleParser::parse_checked(&second).unwrap();
+        assert_eq!(second_ast.directives.len(), 3);
+        assert!(matches!(
+            second_ast.kernel_spans[..],
+            [("second", ref span)] if second[span.clone()].lines().count() == 5
+        ));
+        assert!(matches!(
+            second_ast.directives[2],
+            ast::Directive::Method(_, ref method) if method.func_directive.name == ast::MethodName::Kernel("second")
+        ));
+    }
 }
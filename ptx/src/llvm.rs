@@ -14,6 +14,7 @@ use std::ptr;
 
 use zluda_llvm::analysis::*;
 use zluda_llvm::core::*;
+use zluda_llvm::debuginfo::*;
 use zluda_llvm::prelude::*;
 
 macro_rules! llvm_wrapper {
@@ -71,6 +72,12 @@ llvm_wrapper!(
         LLVMCreateBuilderInContext(C: LLVMContextRef),
         LLVMDisposeBuilder
     ],
+    [
+        DIBuilder,
+        LLVMDIBuilderRef,
+        LLVMCreateDIBuilder(M: LLVMModuleRef),
+        LLVMDisposeDIBuilder
+    ],
     [
         MemoryBuffer,
         LLVMMemoryBufferRef,
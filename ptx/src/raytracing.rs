@@ -385,6 +385,7 @@ pub(crate) fn create_module_with_builtins<'input>() -> TranslationModule<'input,
         id_defs,
         ptx_impl_imports,
         directives,
+        debug_files: Vec::new(),
     }
 }
 
@@ -2727,6 +2728,8 @@ fn fixup_variable_with_empty_semantic_method<'a, 'input>(
                     | s @ Statement::StoreVar(..)
                     | s @ Statement::RetValue(..)
                     | s @ Statement::AsmVolatile { .. }
+                    | s @ Statement::Location(..)
+                    | s @ Statement::Span(..)
                     | s @ Statement::FunctionPointer(..) => s,
                 };
                 result.push(new_statement);
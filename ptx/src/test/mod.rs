@@ -37,6 +37,22 @@ fn vectorAdd_kernel64_ptx() -> Result<(), TranslateError> {
     compile_and_assert(CompilationMode::Wave32, vector_add)
 }
 
+#[test]
+#[allow(non_snake_case)]
+fn vectorAdd_kernel64_ptx_line_info() -> Result<(), TranslateError> {
+    let vector_add = include_str!("vectorAdd_kernel64.ptx");
+    let mut errors = Vec::new();
+    let ast = ptx::ModuleParser::new()
+        .parse(&mut errors, vector_add)
+        .unwrap();
+    let module = crate::to_llvm_module_with_line_info(CompilationMode::Wave32, vec![ast])?;
+    let llvm_text = module.get_llvm_text().to_string();
+    assert!(llvm_text.contains("!DISubprogram(name: \"VecAdd_kernel\""));
+    assert!(llvm_text.contains("vectorAdd_kernel.cu"));
+    assert!(llvm_text.contains("!DILocation(line: 26, column: 9"));
+    Ok(())
+}
+
 #[test]
 #[allow(non_snake_case)]
 fn _Z9vectorAddPKfS0_Pfi_ptx() -> Result<(), TranslateError> {
@@ -50,3 +66,136 @@ fn vectorAdd_11_ptx() -> Result<(), TranslateError> {
     let vector_add = include_str!("vectorAdd_11.ptx");
     compile_and_assert(CompilationMode::Wave32, vector_add)
 }
+
+#[test]
+fn ldmatrix_syntax() {
+    parse_and_assert(
+        "
+        .version 7.8
+        .target sm_75
+        .address_size 64
+        .visible .entry ldmatrix_syntax(.param .u64 input)
+        {
+            .reg .u64 addr;
+            .reg .u32 shared_addr;
+            .reg .b32 r<4>;
+            ld.param.u64 addr, [input];
+            cvt.u32.u64 shared_addr, addr;
+            ldmatrix.sync.aligned.m8n8.x4.shared.b16 {r0, r1, r2, r3}, [shared_addr];
+            ldmatrix.sync.aligned.x4.m8n8.shared::cta.b16 {r0, r1, r2, r3}, [shared_addr];
+            ldmatrix.sync.aligned.m8n8.x2.trans.b16 {r0, r1}, [addr];
+            ldmatrix.sync.aligned.x1.m8n8.trans.shared.b16 {r0}, [shared_addr];
+            ret;
+        }",
+    );
+}
+
+#[test]
+fn mbarrier_syntax() {
+    parse_and_assert(
+        "
+        .version 8.0
+        .target sm_90
+        .address_size 64
+        .visible .entry mbarrier_syntax(.param .u64 input)
+        {
+            .reg .u64 addr;
+            .reg .b64 state;
+            .reg .u32 count;
+            .reg .pred done;
+            ld.param.u64 addr, [input];
+            mbarrier.init.b64 [addr], 32;
+            mbarrier.arrive.b64 state, [addr];
+            mbarrier.arrive.release.cluster.shared::cluster.b64 _, [addr], 2;
+            mbarrier.arrive_drop.shared.b64 state, [addr];
+            mbarrier.arrive_drop.expect_tx.shared::cluster.b64 _, [addr], 64;
+            mbarrier.expect_tx.relaxed.cta.shared::cta.b64 [addr], 64;
+            mbarrier.pending_count.b64 count, state;
+            mbarrier.try_wait.parity.acquire.cta.shared.b64 done, [addr], 1;
+            mbarrier.inval.shared.b64 [addr];
+            ret;
+        }",
+    );
+}
+
+#[test]
+fn mma_unsupported_layout() {
+    let ptx = "
+        .version 7.0
+        .target sm_80
+        .address_size 64
+        .visible .entry mma_unsupported_layout()
+        {
+            .reg .f32 d<4>;
+            .reg .b32 a<4>;
+            .reg .b32 b<2>;
+            mma.sync.aligned.m16n8k16.row.row.f32.f16.f16.f32
+                {d0, d1, d2, d3}, {a0, a1, a2, a3}, {b0, b1}, {d0, d1, d2, d3};
+            ret;
+        }";
+    let error = compile_and_assert(CompilationMode::Wave32, ptx).unwrap_err();
+    match error {
+        TranslateError::InFunction(ref context) => {
+            assert_eq!(context.function.as_deref(), Some("mma_unsupported_layout"));
+            assert!(matches!(context.error, TranslateError::Unsupported(_)));
+        }
+        ref error => panic!("{:?}", error),
+    }
+    let diagnostic = crate::Diagnostic::from_translate_error(&[ptx], &error);
+    assert_eq!(diagnostic.reason, crate::DiagnosticReason::Unsupported);
+    assert_eq!(
+        diagnostic.text.as_deref(),
+        Some("mma.sync.aligned.m16n8k16.row.row.f32.f16.f16.f32 {d0, d1, d2, d3}, {a0, a1, a2, a3}, {b0, b1}, {d0, d1, d2, d3};")
+    );
+}
+
+#[test]
+fn envreg_ptx() -> Result<(), TranslateError> {
+    let ptx = "
+        .version 7.0
+        .target sm_70
+        .address_size 64
+        .visible .entry envreg(.param .u64 output)
+        {
+            .reg .u64 out_addr;
+            .reg .u32 lo;
+            .reg .u32 hi;
+            ld.param.u64 out_addr, [output];
+            mov.u32 lo, %envreg1;
+            mov.u32 hi, %envreg2;
+            st.u32 [out_addr], lo;
+            st.u32 [out_addr+4], hi;
+            ret;
+        }";
+    let mut errors = Vec::new();
+    let ast = ptx::ModuleParser::new().parse(&mut errors, ptx).unwrap();
+    assert!(errors.is_empty());
+    let module = crate::to_llvm_module(CompilationMode::Wave32, vec![ast])?;
+    let llvm_text = module.get_llvm_text().to_string();
+    assert!(llvm_text.contains("@__zluda_ptx_impl__sreg_envreg(i8 1)"));
+    assert!(llvm_text.contains("@__zluda_ptx_impl__sreg_envreg(i8 2)"));
+    Ok(())
+}
+
+#[test]
+fn kernel_resource_limits() -> Result<(), TranslateError> {
+    let vector_add = include_str!("vectorAdd_kernel64.ptx");
+    for (mode, flat_work_group_size) in [
+        (CompilationMode::Wave32, "1,128"),
+        (CompilationMode::Wave32OnWave64, "1,256"),
+    ] {
+        let mut errors = Vec::new();
+        let ast = ptx::ModuleParser::new()
+            .parse(&mut errors, vector_add)
+            .unwrap();
+        let mut module = crate::to_llvm_module(mode, vec![ast])?;
+        module.set_kernel_resource_limits(Some(64), Some(128));
+        let llvm_text = module.get_llvm_text().to_string();
+        assert!(llvm_text.contains("\"amdgpu-num-vgpr\"=\"64\""));
+        assert!(llvm_text.contains(&format!(
+            "\"amdgpu-flat-work-group-size\"=\"{}\"",
+            flat_work_group_size
+        )));
+    }
+    Ok(())
+}
@@ -463,7 +463,385 @@ test_ptx_warp!(
         47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64
     ]
 );
+test_ptx_warp!(
+    wmma_f16,
+    [
+        15u32, 5, 20, 15, 25, 16, 22, 8, 21, 13, 10, 22, 17, 17, 12, 22, 13, 21, 14, 22, 16, 25, 9,
+        8, 5, 15, 25, 15, 15, 5, 20, 15, 25, 16, 22, 8, 21, 13, 10, 22, 17, 17, 12, 22, 13, 21, 14,
+        22, 16, 25, 9, 8, 5, 15, 25, 15, 15, 5, 20, 15, 25, 16, 22, 8
+    ]
+);
+test_ptx_warp!(
+    mma_s8,
+    [
+        4658u32, 13618, 22578, 31538, 7730, 22834, 37938, 53042, 10802, 32050, 53298, 74546, 13874,
+        41266, 68658, 96050, 16946, 50482, 84018, 117554, 20018, 59698, 99378, 139058, 23090,
+        68914, 114738, 160562, 26162, 78130, 130098, 182066, 4658, 13618, 22578, 31538, 7730,
+        22834, 37938, 53042, 10802, 32050, 53298, 74546, 13874, 41266, 68658, 96050, 16946, 50482,
+        84018, 117554, 20018, 59698, 99378, 139058, 23090, 68914, 114738, 160562, 26162, 78130,
+        130098, 182066
+    ]
+);
+test_ptx_warp!(
+    ldmatrix,
+    [
+        428352u32, 952648, 476944, 1240, 525536, 49832, 574128, 98424, 622720, 147016, 671312,
+        195608, 719904, 244200, 768496, 292792, 817088, 341384, 865680, 389976, 914272, 438568,
+        962864, 487160, 11456, 535752, 60048, 584344, 108640, 632936, 157232, 681528, 428352,
+        952648, 476944, 1240, 525536, 49832, 574128, 98424, 622720, 147016, 671312, 195608, 719904,
+        244200, 768496, 292792, 817088, 341384, 865680, 389976, 914272, 438568, 962864, 487160,
+        11456, 535752, 60048, 584344, 108640, 632936, 157232, 681528
+    ]
+);
+test_ptx_warp!(
+    ldmatrix_generic,
+    [
+        1699u32, 2283, 2867, 3451, 1767, 2351, 2935, 3519, 1835, 2419, 3003, 3587, 1903, 2487,
+        3071, 3655, 1971, 2555, 3139, 3723, 2039, 2623, 3207, 3791, 2107, 2691, 3275, 3859, 2175,
+        2759, 3343, 3927, 1699, 2283, 2867, 3451, 1767, 2351, 2935, 3519, 1835, 2419, 3003, 3587,
+        1903, 2487, 3071, 3655, 1971, 2555, 3139, 3723, 2039, 2623, 3207, 3791, 2107, 2691, 3275,
+        3859, 2175, 2759, 3343, 3927
+    ]
+);
+test_ptx_warp!(
+    cp_async,
+    [
+        131074u32, 3, 262148, 5, 393222, 7, 524296, 9, 655370, 11, 786444, 13, 917518, 15, 1048592,
+        17, 1179666, 19, 1310740, 21, 1441814, 23, 1572888, 25, 1703962, 27, 1835036, 29, 1966110,
+        31, 2097184, 33, 2228258, 35, 2359332, 37, 2490406, 39, 2621480, 41, 2752554, 43, 2883628,
+        45, 3014702, 47, 3145776, 49, 3276850, 51, 3407924, 53, 3538998, 55, 3670072, 57, 3801146,
+        59, 3932220, 61, 4063294, 63, 4194368, 1
+    ]
+);
+test_ptx_warp!(
+    mbarrier_bulk_copy,
+    [
+        97016u32, 100019, 103022, 106025, 109028, 112031, 115034, 118037, 121040, 124043, 127046,
+        130049, 133052, 136055, 139058, 142061, 145064, 148067, 151070, 154073, 157076, 160079,
+        163082, 166085, 169088, 172091, 175094, 178097, 181100, 184103, 187106, 190109, 97112,
+        100115, 103118, 106121, 109124, 112127, 115130, 118133, 121136, 124139, 127142, 130145,
+        133148, 136151, 139154, 142157, 145160, 148163, 151166, 154169, 157172, 160175, 163178,
+        166181, 169184, 172187, 175190, 178001, 181004, 184007, 187010, 190013
+    ]
+);
+test_ptx_warp!(mbarrier_arrive_drop, [102103u32; 64]);
 
+test_ptx_warp!(
+    wmma_m16n16k16_f16,
+    [
+        967008u32, 212, 24, 967044, 268, 967076, 184, 967280, 967112, 240, 967036, 252, 967252,
+        967072, 308, 967008, 967088, 72, 164, 966908, 392, 967060, 32, 232, 966880, 352, 967128, 4,
+        192, 966948, 324, 967088, 304, 966756, 232, 967232, 72, 264, 966824, 204, 967192, 140, 236,
+        966784, 272, 967164, 100, 304, 344, 967192, 200, 967252, 966864, 412, 967164, 160, 24,
+        966836, 372, 967232, 132, 967280, 966904, 344
+    ]
+);
+test_ptx_warp!(
+    wmma_m16n16k16_bf16,
+    [
+        967272u32, 35, 967278, 967281, 19, 967277, 34, 967274, 967286, 18, 967273, 39, 967273,
+        967282, 23, 967272, 967262, 6, 17, 967265, 44, 967261, 2, 22, 967264, 40, 967266, 1, 18,
+        967269, 39, 967262, 22, 967257, 16, 967280, 18, 18, 967262, 15, 967276, 23, 17, 967258, 20,
+        967275, 19, 22, 20, 967288, 32, 967279, 967266, 25, 967287, 28, 967284, 967265, 21, 967292,
+        27, 967280, 967270, 20
+    ]
+);
+test_ptx_warp!(
+    wmma_m16n16k16_s8,
+    [
+        955570u32, 6572, 962100, 11620, 960896, 5130, 955726, 6458, 962076, 11746, 960752, 5286,
+        955612, 6434, 962202, 11602, 6410, 962140, 11732, 960720, 5316, 955564, 6498, 962188,
+        11570, 960938, 5124, 955652, 6546, 962026, 11788, 960746, 962200, 11540, 960886, 5160,
+        955686, 6558, 961996, 11736, 960782, 5246, 955712, 6354, 962192, 11632, 960868, 5272,
+        11766, 960798, 5268, 955620, 6378, 962222, 11648, 960890, 5180, 955532, 6580, 962104,
+        11740, 960802, 5092, 955734
+    ]
+);
+test_ptx_warp!(
+    wmma_m16n16k16_u8,
+    [
+        958814u32, 10140, 965452, 15296, 964356, 8374, 959294, 9810, 965752, 15206, 963996, 8854,
+        958964, 10110, 965662, 14846, 9722, 965776, 15152, 964464, 8844, 958876, 10134, 965608,
+        15314, 964466, 8436, 959288, 9966, 965770, 15316, 964058, 965384, 15048, 964178, 8776,
+        959086, 9742, 965504, 15028, 964398, 8646, 958896, 9862, 965484, 15248, 964268, 8456,
+        14934, 964290, 8544, 959220, 9762, 965390, 15140, 964166, 8780, 958916, 9748, 965596,
+        15016, 964402, 8476, 958902
+    ]
+);
+test_ptx_warp!(
+    wmma_m8n32k16_f16,
+    [
+        966724u32, 967040, 608, 967152, 332, 966792, 967000, 580, 967220, 292, 966764, 967068, 540,
+        967192, 360, 966724, 967040, 608, 967152, 332, 966792, 967000, 580, 967220, 292, 966764,
+        967068, 540, 967192, 360, 966724, 967040, 360, 967280, 967008, 967084, 192, 320, 967252,
+        967076, 967044, 164, 388, 967212, 967048, 967112, 124, 360, 967280, 967008, 967084, 192,
+        320, 967252, 967076, 967044, 164, 388, 967212, 967048, 967112, 124, 360, 967280
+    ]
+);
+test_ptx_warp!(
+    wmma_m8n32k16_bf16,
+    [
+        967231u32, 8, 38, 967278, 35, 967236, 4, 37, 967283, 31, 967235, 9, 33, 967282, 36, 967231,
+        8, 38, 967278, 35, 967236, 4, 37, 967283, 31, 967235, 9, 33, 967282, 36, 967231, 8, 12,
+        967286, 967278, 967279, 36, 8, 967285, 967283, 967275, 35, 13, 967281, 967282, 967280, 31,
+        12, 967286, 967278, 967279, 36, 8, 967285, 967283, 967275, 35, 13, 967281, 967282, 967280,
+        31, 12, 967286
+    ]
+);
+test_ptx_warp!(
+    wmma_m8n32k16_s8,
+    [
+        955468u32, 6438, 962264, 11634, 960908, 5020, 955606, 6616, 962082, 11772, 960636, 5158,
+        955784, 6434, 962220, 11500, 960774, 5336, 955602, 6572, 961948, 11638, 960952, 5154,
+        955740, 6300, 962086, 11816, 960770, 5292, 955468, 6438, 5268, 955598, 6408, 962062, 11726,
+        960880, 5170, 955560, 6410, 962194, 11728, 960782, 5132, 955562, 6542, 962196, 11630,
+        960744, 5134, 955694, 6544, 962098, 11592, 960746, 5266, 955696, 6446, 962060, 11594,
+        960878, 5268, 955598
+    ]
+);
+test_ptx_warp!(
+    wmma_m8n32k16_u8,
+    [
+        958800u32, 10094, 965704, 15398, 964456, 8352, 959262, 10056, 965846, 15320, 963968, 8814,
+        959224, 10198, 965768, 14832, 964430, 8776, 959366, 10120, 965280, 15294, 964392, 8918,
+        959288, 9632, 965742, 15256, 964534, 8840, 958800, 10094, 8416, 959070, 9664, 965642,
+        15090, 964028, 8642, 958816, 9990, 965558, 14876, 964254, 8388, 959142, 9906, 965344,
+        15102, 964000, 8714, 959058, 9692, 965570, 14848, 964326, 8630, 958844, 9918, 965316,
+        15174, 964242, 8416, 959070
+    ]
+);
+test_ptx_warp!(
+    wmma_m32n8k16_f16,
+    [
+        1080u32, 966592, 160, 248, 966480, 1148, 966552, 132, 612, 967280, 967036, 824, 966172,
+        572, 967252, 967104, 20, 380, 966444, 1024, 966720, 967288, 448, 966404, 966432, 940,
+        966708, 8, 472, 966500, 900, 966680, 966328, 284, 140, 966760, 1116, 966288, 256, 208,
+        967052, 967220, 500, 966260, 852, 967024, 967288, 460, 328, 966492, 932, 966728, 80, 396,
+        966452, 904, 1120, 966620, 92, 288, 966508, 1080, 966592, 160
+    ]
+);
+test_ptx_warp!(
+    wmma_m32n8k16_bf16,
+    [
+        90u32, 967258, 967282, 44, 967212, 95, 967254, 967281, 27, 14, 967261, 80, 967213, 23, 13,
+        967266, 967271, 53, 967221, 64, 967278, 967270, 58, 967217, 967206, 73, 967269, 967268, 70,
+        967211, 69, 967268, 967234, 967295, 29, 967234, 99, 967230, 967294, 34, 5, 967271, 59,
+        967211, 45, 4, 967276, 55, 52, 967221, 65, 967280, 967268, 57, 967217, 64, 94, 967259,
+        967277, 48, 967213, 90, 967258, 967282
+    ]
+);
+test_ptx_warp!(
+    wmma_m32n8k16_s8,
+    [
+        955950u32, 6302, 962120, 11782, 960558, 5502, 955470, 6472, 5330, 955630, 6390, 962364,
+        11348, 960942, 5202, 955542, 960796, 5310, 955390, 6746, 961984, 11628, 960978, 4922,
+        11418, 961060, 5050, 955614, 6642, 961866, 11924, 960666, 961876, 11692, 960878, 5020,
+        955932, 6224, 962160, 11726, 6456, 962058, 11826, 960540, 5378, 955592, 6462, 962254,
+        955774, 6236, 962354, 11566, 960782, 5326, 955404, 6706, 5504, 955452, 6470, 962232, 11404,
+        961116, 5024, 955622
+    ]
+);
+test_ptx_warp!(
+    wmma_m32n8k16_u8,
+    [
+        959130u32, 9806, 965408, 15394, 963954, 8682, 958974, 9760, 8578, 959202, 9746, 966044,
+        14812, 964190, 8774, 958898, 964084, 8922, 958786, 10466, 965488, 14916, 964590, 8318,
+        14662, 964628, 8402, 959290, 10102, 965110, 15492, 964018, 965132, 15272, 964242, 8708,
+        959404, 9480, 965740, 15090, 9640, 965566, 15118, 964156, 8778, 958776, 9970, 965546,
+        959054, 9840, 965742, 15278, 964278, 8606, 959008, 10094, 8684, 958956, 9758, 965844,
+        14800, 964296, 8528, 958910
+    ]
+);
+test_ptx_warp!(
+    wmma_m16n16k8_tf32,
+    [
+        7u32, 967288, 967291, 967265, 34, 12, 967287, 967287, 967270, 33, 8, 967292, 967286,
+        967266, 38, 7, 967290, 967276, 7, 967291, 26, 967289, 967272, 12, 967290, 22, 967294,
+        967271, 8, 967295, 21, 967290, 967294, 967269, 39, 3, 967284, 967290, 967274, 38, 967295,
+        967289, 967289, 967270, 43, 967294, 967285, 967294, 28, 21, 967293, 967270, 967273, 33, 20,
+        967289, 967275, 967272, 29, 25, 967288, 967271, 967277, 28
+    ]
+);
+test_ptx_warp!(
+    mma_m16n8k16_f16,
+    [
+        967152u32, 967248, 460, 160, 24, 460, 152, 967152, 456, 392, 967256, 966720, 360, 280,
+        966768, 967132, 80, 967104, 966908, 8, 967164, 966644, 967276, 388, 966620, 967092, 360,
+        296, 967180, 967180, 500, 188, 967152, 967248, 460, 160, 24, 460, 152, 967152, 456, 392,
+        967256, 966720, 360, 280, 966768, 967132, 80, 967104, 966908, 8, 967164, 966644, 967276,
+        388, 966620, 967092, 360, 296, 967180, 967180, 500, 188
+    ]
+);
+test_ptx_warp!(
+    mma_m16n8k16_bf16,
+    [
+        967290u32, 967278, 31, 28, 967278, 43, 20, 967284, 36, 26, 14, 967254, 18, 40, 967254,
+        967285, 20, 967278, 967271, 967280, 967293, 967247, 967279, 31, 967247, 967275, 18, 20,
+        967291, 967273, 35, 29, 967290, 967278, 31, 28, 967278, 43, 20, 967284, 36, 26, 14, 967254,
+        18, 40, 967254, 967285, 20, 967278, 967271, 967280, 967293, 967247, 967279, 31, 967247,
+        967275, 18, 20, 967291, 967273, 35, 29
+    ]
+);
+test_ptx_warp!(
+    mma_m16n8k8_f16,
+    [
+        60u32, 967244, 408, 967236, 264, 124, 80, 966864, 200, 284, 966856, 967008, 448, 967164,
+        966796, 224, 967220, 967060, 40, 48, 966876, 967052, 304, 108, 966788, 292, 104, 368, 88,
+        967176, 448, 967264, 60, 967244, 408, 967236, 264, 124, 80, 966864, 200, 284, 966856,
+        967008, 448, 967164, 966796, 224, 967220, 967060, 40, 48, 966876, 967052, 304, 108, 966788,
+        292, 104, 368, 88, 967176, 448, 967264
+    ]
+);
+test_ptx_warp!(
+    mma_m16n8k8_bf16,
+    [
+        9u32, 967271, 42, 967293, 6, 31, 2, 967260, 20, 23, 967270, 967272, 46, 967293, 967255, 14,
+        967283, 967279, 10, 967284, 967269, 967271, 10, 21, 967253, 19, 2, 38, 10, 967266, 46,
+        967294, 9, 967271, 42, 967293, 6, 31, 2, 967260, 20, 23, 967270, 967272, 46, 967293,
+        967255, 14, 967283, 967279, 10, 967284, 967269, 967271, 10, 21, 967253, 19, 2, 38, 10,
+        967266, 46, 967294
+    ]
+);
+test_ptx_warp!(
+    mma_m16n8k4_tf32,
+    [
+        23u32, 967284, 19, 967279, 967277, 36, 967283, 967270, 26, 13, 967284, 967285, 13, 967289,
+        967270, 23, 967281, 967281, 26, 967289, 967284, 967286, 967281, 36, 967278, 967294, 20, 0,
+        24, 967279, 23, 967280, 23, 967284, 19, 967279, 967277, 36, 967283, 967270, 26, 13, 967284,
+        967285, 13, 967289, 967270, 23, 967281, 967281, 26, 967289, 967284, 967286, 967281, 36,
+        967278, 967294, 20, 0, 24, 967279, 23, 967280
+    ]
+);
+test_ptx_warp!(
+    mma_m16n8k8_tf32,
+    [
+        9u32, 967271, 42, 967293, 6, 31, 2, 967260, 20, 23, 967270, 967272, 46, 967293, 967255, 14,
+        967283, 967279, 10, 967284, 967269, 967271, 10, 21, 967253, 19, 2, 38, 10, 967266, 46,
+        967294, 9, 967271, 42, 967293, 6, 31, 2, 967260, 20, 23, 967270, 967272, 46, 967293,
+        967255, 14, 967283, 967279, 10, 967284, 967269, 967271, 10, 21, 967253, 19, 2, 38, 10,
+        967266, 46, 967294
+    ]
+);
+test_ptx_warp!(
+    mma_m8n8k16_s8,
+    [
+        964636u32, 3884, 48, 964716, 963432, 967266, 2620, 963442, 967256, 964792, 3896, 967266,
+        2660, 963420, 24, 2530, 3894, 42, 964624, 3904, 26, 2556, 963392, 967262, 964684, 3832,
+        967282, 964764, 963340, 967284, 2628, 963420, 964636, 3884, 48, 964716, 963432, 967266,
+        2620, 963442, 967256, 964792, 3896, 967266, 2660, 963420, 24, 2530, 3894, 42, 964624, 3904,
+        26, 2556, 963392, 967262, 964684, 3832, 967282, 964764, 963340, 967284, 2628, 963420
+    ]
+);
+test_ptx_warp!(
+    mma_m8n8k16_s8_u8,
+    [
+        964616u32, 3864, 28, 964696, 963392, 967226, 2580, 963402, 40, 964872, 3976, 50, 2580,
+        963340, 967240, 2450, 3934, 82, 964664, 3944, 46, 2576, 963412, 967282, 964684, 3832,
+        967282, 964764, 963320, 967264, 2608, 963400, 964616, 3864, 28, 964696, 963392, 967226,
+        2580, 963402, 40, 964872, 3976, 50, 2580, 963340, 967240, 2450, 3934, 82, 964664, 3944, 46,
+        2576, 963412, 967282, 964684, 3832, 967282, 964764, 963320, 967264, 2608, 963400
+    ]
+);
+test_ptx_warp!(
+    mma_m8n8k16_u8_s8,
+    [
+        964630u32, 3908, 12, 964710, 963426, 967290, 2584, 963436, 967250, 964816, 3860, 967260,
+        2654, 963444, 967284, 2524, 3888, 66, 964588, 3898, 20, 2580, 963356, 967256, 964678, 3856,
+        967246, 964758, 963334, 12, 2592, 963414, 964630, 3908, 12, 964710, 963426, 967290, 2584,
+        963436, 967250, 964816, 3860, 967260, 2654, 963444, 967284, 2524, 3888, 66, 964588, 3898,
+        20, 2580, 963356, 967256, 964678, 3856, 967246, 964758, 963334, 12, 2592, 963414
+    ]
+);
+test_ptx_warp!(
+    mma_m8n8k16_u8,
+    [
+        965570u32, 4848, 952, 965650, 964346, 914, 3504, 964356, 994, 965856, 4900, 1004, 3534,
+        964324, 868, 3404, 4888, 1066, 965588, 4898, 1000, 3560, 964336, 940, 965638, 4816, 910,
+        965718, 964274, 952, 3532, 964354, 965570, 4848, 952, 965650, 964346, 914, 3504, 964356,
+        994, 965856, 4900, 1004, 3534, 964324, 868, 3404, 4888, 1066, 965588, 4898, 1000, 3560,
+        964336, 940, 965638, 4816, 910, 965718, 964274, 952, 3532, 964354
+    ]
+);
+test_ptx_warp!(
+    mma_m16n8k16_s8,
+    [
+        963382u32, 965976, 7894, 963478, 1278, 959666, 3934, 1258, 7888, 963508, 966002, 7612,
+        3978, 1404, 959376, 3852, 966042, 7732, 963304, 965982, 959482, 3714, 1260, 959614, 963236,
+        965946, 7860, 963490, 1272, 959482, 4012, 1368, 963382, 965976, 7894, 963478, 1278, 959666,
+        3934, 1258, 7888, 963508, 966002, 7612, 3978, 1404, 959376, 3852, 966042, 7732, 963304,
+        965982, 959482, 3714, 1260, 959614, 963236, 965946, 7860, 963490, 1272, 959482, 4012, 1368
+    ]
+);
+test_ptx_warp!(
+    mma_m16n8k16_s8_u8,
+    [
+        963266u32, 965860, 7778, 963362, 1398, 959786, 4054, 1378, 7824, 963444, 965938, 7548,
+        3954, 1380, 959352, 3828, 966142, 7832, 963404, 966082, 959510, 3742, 1288, 959642, 963192,
+        965902, 7816, 963446, 1156, 959366, 3896, 1252, 963266, 965860, 7778, 963362, 1398, 959786,
+        4054, 1378, 7824, 963444, 965938, 7548, 3954, 1380, 959352, 3828, 966142, 7832, 963404,
+        966082, 959510, 3742, 1288, 959642, 963192, 965902, 7816, 963446, 1156, 959366, 3896, 1252
+    ]
+);
+test_ptx_warp!(
+    mma_m16n8k16_u8_s8,
+    [
+        963346u32, 966048, 7774, 963466, 1242, 959738, 3814, 1246, 7852, 963580, 965882, 7600,
+        3942, 1476, 959256, 3840, 966006, 7804, 963184, 965970, 959446, 3786, 1140, 959602, 963200,
+        966018, 7740, 963478, 1236, 959554, 3892, 1356, 963346, 966048, 7774, 963466, 1242, 959738,
+        3814, 1246, 7852, 963580, 965882, 7600, 3942, 1476, 959256, 3840, 966006, 7804, 963184,
+        965970, 959446, 3786, 1140, 959602, 963200, 966018, 7740, 963478, 1236, 959554, 3892, 1356
+    ]
+);
+test_ptx_warp!(
+    mma_m16n8k16_u8,
+    [
+        966686u32, 2092, 11114, 966806, 4818, 963314, 7390, 4822, 11244, 966972, 1978, 10992, 7374,
+        4908, 962688, 7272, 2266, 11360, 966740, 2230, 962930, 7270, 4624, 963086, 966612, 2134,
+        11152, 966890, 4576, 962894, 7232, 4696, 966686, 2092, 11114, 966806, 4818, 963314, 7390,
+        4822, 11244, 966972, 1978, 10992, 7374, 4908, 962688, 7272, 2266, 11360, 966740, 2230,
+        962930, 7270, 4624, 963086, 966612, 2134, 11152, 966890, 4576, 962894, 7232, 4696
+    ]
+);
+test_ptx_warp!(
+    mma_m16n8k32_s8,
+    [
+        963424u32, 965938, 7766, 963460, 1298, 959504, 3860, 1372, 7746, 963474, 965996, 7704,
+        3870, 1386, 959508, 3894, 966024, 7828, 963434, 965946, 959568, 3798, 1332, 959444, 963356,
+        966018, 7734, 963466, 1314, 959444, 3884, 1350, 963424, 965938, 7766, 963460, 1298, 959504,
+        3860, 1372, 7746, 963474, 965996, 7704, 3870, 1386, 959508, 3894, 966024, 7828, 963434,
+        965946, 959568, 3798, 1332, 959444, 963356, 966018, 7734, 963466, 1314, 959444, 3884, 1350
+    ]
+);
+test_ptx_warp!(
+    mma_m16n8k32_s8_u8,
+    [
+        963428u32, 965942, 7770, 963464, 1354, 959560, 3916, 1428, 7658, 963386, 965908, 7616,
+        3946, 1462, 959584, 3970, 966152, 7956, 963562, 966074, 959552, 3782, 1316, 959428, 963196,
+        965858, 7574, 963306, 1318, 959448, 3888, 1354, 963428, 965942, 7770, 963464, 1354, 959560,
+        3916, 1428, 7658, 963386, 965908, 7616, 3946, 1462, 959584, 3970, 966152, 7956, 963562,
+        966074, 959552, 3782, 1316, 959428, 963196, 965858, 7574, 963306, 1318, 959448, 3888, 1354
+    ]
+);
+test_ptx_warp!(
+    mma_m16n8k32_u8_s8,
+    [
+        963268u32, 965998, 7742, 963412, 1142, 959564, 3836, 1324, 7590, 963534, 965972, 7656,
+        3714, 1446, 959484, 3846, 965868, 7888, 963410, 965898, 959412, 3858, 1308, 959396, 963200,
+        966078, 7710, 963418, 1158, 959504, 3860, 1302, 963268, 965998, 7742, 963412, 1142, 959564,
+        3836, 1324, 7590, 963534, 965972, 7656, 3714, 1446, 959484, 3846, 965868, 7888, 963410,
+        965898, 959412, 3858, 1308, 959396, 963200, 966078, 7710, 963418, 1158, 959504, 3860, 1302
+    ]
+);
+test_ptx_warp!(
+    mma_m16n8k32_u8,
+    [
+        2888u32, 5618, 14658, 3032, 8110, 966532, 10804, 8292, 14414, 3062, 5500, 14480, 10702,
+        8434, 966472, 10834, 5612, 14928, 3154, 5642, 966308, 10754, 8204, 966292, 2656, 5534,
+        14462, 2874, 8074, 966420, 10776, 8218, 2888, 5618, 14658, 3032, 8110, 966532, 10804, 8292,
+        14414, 3062, 5500, 14480, 10702, 8434, 966472, 10834, 5612, 14928, 3154, 5642, 966308,
+        10754, 8204, 966292, 2656, 5534, 14462, 2874, 8074, 966420, 10776, 8218
+    ]
+);
 test_ptx!(barrier);
 test_ptx!(assertfail);
 test_ptx!(func_ptr);
@@ -11,6 +11,7 @@ use std::cell::RefCell;
 use std::collections::{btree_map, hash_map, BTreeMap};
 use std::ffi::{CStr, CString};
 use std::num::NonZeroU32;
+use std::ops::Range;
 use std::{borrow::Cow, collections::BTreeSet, hash::Hash, iter, mem, rc::Rc};
 use zluda_llvm::bit_writer::*;
 use zluda_llvm::core::LLVMPrintModuleToString;
@@ -34,12 +35,7 @@ macro_rules! derive_error {
                 paste! {
                     #[allow(dead_code)]
                     pub(crate) fn [<$variant:snake>] ( $(x: $underlying)? ) -> Self {
-                        let result = Self :: $variant $((x as $underlying))?;
-                        if cfg!(debug_assertions) {
-                            panic!("{:?}", result);
-                        } else {
-                            result
-                        }
+                        Self :: $variant $((x as $underlying))?
                     }
                 }
             )+
@@ -62,12 +58,65 @@ derive_error! {
         Unreachable,
         Todo,
         UnexpectedPattern,
-        SymbolRedefinition
+        SymbolRedefinition,
+        // Valid PTX that we don't implement
+        Unsupported(&'static str),
+        InFunction(Box<ErrorContext>)
     }
 }
 
 impl std::error::Error for TranslateError {}
 
+// Where in the module an error happened. Passes fill it in as the error
+// propagates up: first the statement, then the function
+#[derive(Debug)]
+pub struct ErrorContext {
+    pub function: Option<String>,
+    pub span: Option<ast::StatementSpan>,
+    pub error: TranslateError,
+}
+
+impl TranslateError {
+    // Records the function in which the error happened, nested functions
+    // don't exist in PTX so the first recorded name wins
+    pub(crate) fn with_function(self, name: Option<&str>) -> Self {
+        let name = match name {
+            Some(name) => name,
+            None => return self,
+        };
+        let mut context = self.into_context();
+        if context.function.is_none() {
+            context.function = Some(name.to_string());
+        }
+        TranslateError::InFunction(context)
+    }
+
+    // Records the statement which failed to translate, passes see statements
+    // in order, so the innermost span wins
+    pub(crate) fn with_span(self, span: Option<ast::StatementSpan>) -> Self {
+        let span = match span {
+            Some(span) => span,
+            None => return self,
+        };
+        let mut context = self.into_context();
+        if context.span.is_none() {
+            context.span = Some(span);
+        }
+        TranslateError::InFunction(context)
+    }
+
+    fn into_context(self) -> Box<ErrorContext> {
+        match self {
+            TranslateError::InFunction(context) => context,
+            error => Box::new(ErrorContext {
+                function: None,
+                span: None,
+                error,
+            }),
+        }
+    }
+}
+
 pub struct Module<'input> {
     pub(crate) llvm_module: llvm::Module,
     pub(crate) _llvm_context: llvm::Context,
@@ -88,6 +137,27 @@ impl<'input> Module<'input> {
         unsafe { llvm::Message::from_ffi(LLVMPrintModuleToString(self.llvm_module.get())) }
     }
 
+    // Register and block size limits as set by CU_JIT_MAX_REGISTERS and
+    // CU_JIT_THREADS_PER_BLOCK. Block size is in CUDA threads, it gets
+    // doubled the same way as .maxntid in insert_compilation_mode_prologue(...)
+    pub fn set_kernel_resource_limits(
+        &mut self,
+        max_registers: Option<u32>,
+        mut threads_per_block: Option<u32>,
+    ) {
+        if self.compilation_mode == CompilationMode::Wave32OnWave64 {
+            threads_per_block = threads_per_block.map(|threads| threads * 2);
+        }
+        unsafe {
+            emit::emit_kernel_resource_limits(
+                &self._llvm_context,
+                &self.llvm_module,
+                max_registers,
+                threads_per_block,
+            )
+        }
+    }
+
     pub fn get_bitcode_all<'a>(
         &'a self,
     ) -> impl Iterator<Item = (llvm::MemoryBuffer, &'a CStr)> + 'a {
@@ -183,6 +253,9 @@ pub(crate) struct TranslationModule<'input, P: ast::ArgParams> {
     pub(crate) id_defs: IdNameMapBuilder<'input>,
     pub(crate) ptx_impl_imports: BTreeMap<String, Rc<RefCell<ast::MethodDeclaration<'input, Id>>>>,
     pub(crate) directives: Vec<TranslationDirective<'input, P>>,
+    // File names referenced by Statement::Location, files from all linked
+    // modules are merged into this single list
+    pub(crate) debug_files: Vec<&'input str>,
 }
 
 impl<'input, P: ast::ArgParams> TranslationModule<'input, P> {
@@ -196,6 +269,7 @@ impl<'input, P: ast::ArgParams> TranslationModule<'input, P> {
             id_defs,
             ptx_impl_imports,
             directives,
+            debug_files: Vec::new(),
         }
     }
 }
@@ -902,23 +976,34 @@ fn link_and_normalize_modules<'input>(
     let mut id_defs = module.id_defs;
     let ptx_impl_imports = module.ptx_impl_imports;
     let mut directives = module.directives;
+    let mut debug_files = module.debug_files;
     let mut sm_version = 0;
     let mut string_resolver = StringIdResolver::new(&mut id_defs, &directives)?;
     for (mut module_index, ast) in asts.into_iter().enumerate() {
         module_index += 1;
         sm_version = sm_version.max(ast.sm_version);
+        let mut file_indices = FxHashMap::default();
+        for (file_index, file_name) in ast.debug_files {
+            file_indices.insert(file_index, debug_files.len() as u32);
+            debug_files.push(file_name);
+        }
         let mut module_scope = string_resolver.start_module();
         for (directive_index, directive) in ast.directives.into_iter().enumerate() {
             match directive {
                 ast::Directive::Method(linking_directive, method) => {
-                    directives.push(TranslationDirective::Method(normalize_method(
-                        &mut linking_resolver,
-                        &mut functions,
-                        (module_index, directive_index),
-                        &mut module_scope,
-                        linking_directive,
-                        method,
-                    )?));
+                    let name = method.func_directive.name();
+                    directives.push(TranslationDirective::Method(
+                        normalize_method(
+                            &mut linking_resolver,
+                            &mut functions,
+                            (module_index, directive_index),
+                            &mut module_scope,
+                            &file_indices,
+                            linking_directive,
+                            method,
+                        )
+                        .map_err(|err| err.with_function(Some(name)))?,
+                    ));
                 }
                 ast::Directive::Variable(mut linking_directive, vars) => {
                     expand_multivariable2(
@@ -976,6 +1061,7 @@ fn link_and_normalize_modules<'input>(
             id_defs,
             ptx_impl_imports,
             directives,
+            debug_files,
         },
         functions,
     ))
@@ -1019,6 +1105,7 @@ fn normalize_method<'a, 'b, 'input>(
     >,
     (module, directive): (usize, usize),
     module_scope: &mut StringIdResolverScope<'a, 'b, 'input>,
+    file_indices: &FxHashMap<u32, u32>,
     linking_directive: ast::LinkingDirective,
     method: ast::Function<'input, &'input str, ast::Statement<ast::ParsedArgParams<'input>>>,
 ) -> Result<TranslationMethod<'input, NormalizedArgParams>, TranslateError> {
@@ -1054,7 +1141,7 @@ fn normalize_method<'a, 'b, 'input>(
         method
             .body
             .map(|body| {
-                let body = normalize_identifiers2(&mut fn_scope, body)?;
+                let body = normalize_identifiers2(&mut fn_scope, file_indices, module, body)?;
                 normalize_predicates2(&mut fn_scope, body)
             })
             .transpose()?
@@ -1094,27 +1181,59 @@ fn normalize_method_params<'a, 'b, 'input>(
         .collect()
 }
 
+// `module` is the index of the module as seen by the linker, the first
+// module passed for translation has index 1
 fn normalize_identifiers2<'a, 'b, 'input>(
     scope: &mut StringIdResolverScope<'a, 'b, 'input>,
+    file_indices: &FxHashMap<u32, u32>,
+    module: usize,
     func: Vec<ast::Statement<ast::ParsedArgParams<'input>>>,
 ) -> Result<Vec<NormalizedStatement>, TranslateError> {
     gather_labels_in_scope(scope, &func)?;
     let mut result = Vec::with_capacity(func.len());
+    let statement_span = |span: Range<usize>| ast::StatementSpan {
+        module: module - 1,
+        start: span.start,
+        end: span.end,
+    };
     for statement in func {
         match statement {
             ast::Statement::Block(block) => {
                 let mut scope = scope.start_scope();
-                result.extend(normalize_identifiers2(&mut scope, block)?);
+                result.extend(normalize_identifiers2(
+                    &mut scope,
+                    file_indices,
+                    module,
+                    block,
+                )?);
+            }
+            // .loc referring to an undeclared file carries no useful information
+            ast::Statement::Location(location) => {
+                if let Some(file) = file_indices.get(&location.file) {
+                    result.push(Statement::Location(ast::SourceLocation {
+                        file: *file,
+                        ..location
+                    }))
+                }
             }
             ast::Statement::Label(name) => {
                 result.push(Statement::Label(scope.get_id_in_function_scopes(name)?))
             }
-            ast::Statement::Instruction(p, i) => result.push(Statement::Instruction((
-                p.map(|p| p.map_variable(&mut |id| scope.get_id_in_module_scopes(id)))
-                    .transpose()?,
-                i.map_variable(&mut |id| scope.get_id_in_module_scopes(id))?,
-            ))),
-            ast::Statement::Variable(vars) => {
+            ast::Statement::Instruction(p, i, span) => {
+                let span = statement_span(span);
+                result.push(Statement::Span(span));
+                let p = p
+                    .map(|p| p.map_variable(&mut |id| scope.get_id_in_module_scopes(id)))
+                    .transpose()
+                    .map_err(|err| err.with_span(Some(span)))?;
+                let i = i
+                    .map_variable(&mut |id| scope.get_id_in_module_scopes(id))
+                    .map_err(|err| err.with_span(Some(span)))?;
+                result.push(Statement::Instruction((p, i)));
+            }
+            ast::Statement::Variable(vars, span) => {
+                let span = statement_span(span);
+                result.push(Statement::Span(span));
                 expand_multivariable2(
                     scope,
                     vars.into_iter(),
@@ -1128,7 +1247,8 @@ fn normalize_identifiers2<'a, 'b, 'input>(
                         )?));
                         Ok(())
                     },
-                )?;
+                )
+                .map_err(|err| err.with_span(Some(span)))?;
             }
             ast::Statement::Callprototype(proto) => {
                 let name = scope.add_untyped_checked(proto.name)?;
@@ -1222,7 +1342,7 @@ fn normalize_predicates2<'a, 'b, 'input>(
     func: Vec<NormalizedStatement>,
 ) -> Result<Vec<UnconditionalStatement>, TranslateError> {
     let mut result = Vec::with_capacity(func.len());
-    for s in func {
+    for_each_statement(func, |s| {
         match s {
             Statement::Label(id) => result.push(Statement::Label(id)),
             Statement::Instruction((pred, inst)) => {
@@ -1252,10 +1372,13 @@ fn normalize_predicates2<'a, 'b, 'input>(
                 }
             }
             Statement::Variable(var) => result.push(Statement::Variable(var)),
+            Statement::Location(location) => result.push(Statement::Location(location)),
+            Statement::Span(span) => result.push(Statement::Span(span)),
             // Blocks are flattened when resolving ids
             _ => return Err(TranslateError::unreachable()),
         }
-    }
+        Ok(())
+    })?;
     Ok(result)
 }
 
@@ -1296,11 +1419,10 @@ fn resolve_instruction_types<'input>(
                 }
                 TranslationDirective::Method(method) => {
                     let body = match method.body {
-                        Some(body) => Some(resolve_instruction_types_method(
-                            id_defs,
-                            &function_decls,
-                            body,
-                        )?),
+                        Some(body) => Some(
+                            resolve_instruction_types_method(id_defs, &function_decls, body)
+                                .map_err(|err| err.with_function(method.source_name.as_deref()))?,
+                        ),
                         None => None,
                     };
                     TranslationDirective::Method(TranslationMethod {
@@ -1323,6 +1445,7 @@ fn resolve_instruction_types<'input>(
         directives,
         id_defs: module.id_defs,
         ptx_impl_imports: module.ptx_impl_imports,
+        debug_files: module.debug_files,
     })
 }
 
@@ -1339,7 +1462,7 @@ fn resolve_instruction_types_method<'input>(
 ) -> Result<Vec<TypedStatement>, TranslateError> {
     let mut result = Vec::<TypedStatement>::with_capacity(fn_body.len());
     let mut constants = KernelConstantsVisitor::new();
-    for statement in fn_body {
+    for_each_statement(fn_body, |statement| {
         match statement {
             Statement::Instruction(inst) => match inst {
                 // TODO: Replace this with proper constant propagation
@@ -1466,9 +1589,12 @@ fn resolve_instruction_types_method<'input>(
             Statement::Label(i) => result.push(Statement::Label(i)),
             Statement::Variable(v) => result.push(Statement::Variable(v)),
             Statement::Conditional(c) => result.push(Statement::Conditional(c)),
+            Statement::Location(l) => result.push(Statement::Location(l)),
+            Statement::Span(span) => result.push(Statement::Span(span)),
             _ => return Err(TranslateError::unreachable()),
         }
-    }
+        Ok(())
+    })?;
     Ok(result)
 }
 
@@ -1592,15 +1718,18 @@ fn convert_methods<'input, From: ast::ArgParams<Id = Id>, To: ast::ArgParams<Id
             Ok(match directive {
                 TranslationDirective::Method(mut method) => {
                     let body = match method.body {
-                        Some(body) => Some(mapper(
-                            compilation_mode,
-                            id_defs,
-                            &mut additional_declarations,
-                            &mut method.return_arguments,
-                            &mut method.input_arguments,
-                            method.is_kernel,
-                            body,
-                        )?),
+                        Some(body) => Some(
+                            mapper(
+                                compilation_mode,
+                                id_defs,
+                                &mut additional_declarations,
+                                &mut method.return_arguments,
+                                &mut method.input_arguments,
+                                method.is_kernel,
+                                body,
+                            )
+                            .map_err(|err| err.with_function(method.source_name.as_deref()))?,
+                        ),
                         None => None,
                     };
                     TranslationDirective::Method(TranslationMethod {
@@ -1629,6 +1758,7 @@ fn convert_methods<'input, From: ast::ArgParams<Id = Id>, To: ast::ArgParams<Id
         directives: directives,
         id_defs: module.id_defs,
         ptx_impl_imports: module.ptx_impl_imports,
+        debug_files: module.debug_files,
     })
 }
 
@@ -1788,7 +1918,7 @@ fn deparamize_function_body<'input>(
     } else {
         return Ok(());
     };
-    for statement in body {
+    for_each_statement(body, |statement| {
         match statement {
             Statement::Instruction(ast::Instruction::Exit) => {
                 deparamize_instruction_ret(
@@ -1813,7 +1943,8 @@ fn deparamize_function_body<'input>(
             }
             statement => result.push(statement),
         }
-    }
+        Ok(())
+    })?;
     method.body = Some(result);
     Ok(())
 }
@@ -1951,7 +2082,7 @@ fn insert_hardware_registers_impl<'input>(
     );
     let carry_flag = carry_flag_variable.name;
     result.push(Statement::Variable(carry_flag_variable));
-    for statement in typed_statements {
+    for_each_statement(typed_statements, |statement| {
         match statement {
             Statement::Instruction(ast::Instruction::MadC {
                 type_,
@@ -2046,7 +2177,8 @@ fn insert_hardware_registers_impl<'input>(
             }
             s => result.push(s),
         }
-    }
+        Ok(())
+    })?;
     Ok(result)
 }
 
@@ -2071,7 +2203,7 @@ fn fix_special_registers_impl<'input>(
         id_defs,
         result,
     };
-    for s in typed_statements {
+    for_each_statement(typed_statements, |s| {
         match s {
             Statement::Call(details) => {
                 let new_statement = details.visit(&mut sreg_sresolver)?;
@@ -2124,10 +2256,13 @@ fn fix_special_registers_impl<'input>(
             s @ Statement::Variable(_)
             | s @ Statement::Constant(_)
             | s @ Statement::Label(_)
+            | s @ Statement::Location(_)
+            | s @ Statement::Span(_)
             | s @ Statement::FunctionPointer(_) => sreg_sresolver.result.push(s),
             _ => return Err(TranslateError::unreachable()),
         }
-    }
+        Ok(())
+    })?;
     Ok(sreg_sresolver.result)
 }
 
@@ -2209,7 +2344,7 @@ fn insert_mem_ssa_statements_impl<'input>(
     for arg in return_arguments.iter() {
         insert_mem_ssa_argument_reg_return(&mut result, arg);
     }
-    for statement in typed_statements {
+    for_each_statement(typed_statements, |statement| {
         match statement {
             Statement::Call(call) => {
                 insert_mem_ssa_statement_default(id_def, &mut result, call.cast())?
@@ -2266,12 +2401,15 @@ fn insert_mem_ssa_statements_impl<'input>(
             Statement::SubCC(type_, arg) => {
                 insert_mem_ssa_statement_default(id_def, &mut result, VisitSubCC(type_, arg))?
             }
-            s @ Statement::Variable(_) | s @ Statement::Label(_) | s @ Statement::Constant(..) => {
-                result.push(s)
-            }
+            s @ Statement::Variable(_)
+            | s @ Statement::Label(_)
+            | s @ Statement::Constant(..)
+            | s @ Statement::Location(..)
+            | s @ Statement::Span(..) => result.push(s),
             _ => return Err(TranslateError::unreachable()),
         }
-    }
+        Ok(())
+    })?;
     Ok(result)
 }
 
@@ -2318,7 +2456,7 @@ fn expand_arguments2_impl<'input>(
     fn_body: Vec<TypedStatement>,
 ) -> Result<Vec<ExpandedStatement>, TranslateError> {
     let mut result = Vec::with_capacity(fn_body.len());
-    for statment in fn_body {
+    for_each_statement(fn_body, |statment| {
         match statment {
             Statement::Call(call) => {
                 let mut visitor = FlattenArguments::new(&mut result, id_defs);
@@ -2416,8 +2554,11 @@ fn expand_arguments2_impl<'input>(
             Statement::AsmVolatile { asm, constraints } => {
                 result.push(Statement::AsmVolatile { asm, constraints })
             }
+            Statement::Location(location) => result.push(Statement::Location(location)),
+            Statement::Span(span) => result.push(Statement::Span(span)),
         }
-    }
+        Ok(())
+    })?;
     Ok(result)
 }
 
@@ -2447,7 +2588,7 @@ fn insert_implicit_conversions2_impl<'input>(
     fn_body: Vec<ExpandedStatement>,
 ) -> Result<Vec<ExpandedStatement>, TranslateError> {
     let mut result = Vec::with_capacity(fn_body.len());
-    for statement in fn_body {
+    for_each_statement(fn_body, |statement| {
         match statement {
             Statement::Call(call) => {
                 insert_implicit_conversions_impl(&mut result, id_def, call)?;
@@ -2488,9 +2629,12 @@ fn insert_implicit_conversions2_impl<'input>(
             | s @ Statement::StoreVar(..)
             | s @ Statement::RetValue(..)
             | s @ Statement::AsmVolatile { .. }
+            | s @ Statement::Location(..)
+            | s @ Statement::Span(..)
             | s @ Statement::FunctionPointer(..) => result.push(s),
         }
-    }
+        Ok(())
+    })?;
     Ok(result)
 }
 
@@ -2559,7 +2703,7 @@ fn replace_instructions_with_builtins_impl<'input>(
     fn_body: Vec<ExpandedStatement>,
 ) -> Result<Vec<ExpandedStatement>, TranslateError> {
     let mut statements = Vec::with_capacity(fn_body.len());
-    for statement in fn_body {
+    for_each_statement(fn_body, |statement| {
         match statement {
             Statement::Instruction(ast::Instruction::Nanosleep(arg)) => {
                 let fn_name = [ZLUDA_PTX_PREFIX, "nanosleep_u32"].concat();
@@ -2579,6 +2723,225 @@ fn replace_instructions_with_builtins_impl<'input>(
                     fn_name,
                 )?);
             }
+            Statement::Instruction(ast::Instruction::WmmaLoad(details, arg)) => {
+                let fn_name = [
+                    ZLUDA_PTX_PREFIX,
+                    "wmma_load_",
+                    details.fragment.to_ptx_name(),
+                    "_",
+                    details.layout.to_ptx_name(),
+                    "_",
+                    details.shape.to_ptx_name(),
+                    "_",
+                    details.state_space.to_ptx_name(),
+                    "_",
+                    details.type_.to_ptx_name(),
+                ]
+                .concat();
+                statements.push(instruction_to_fn_call(
+                    id_def,
+                    ptx_impl_imports,
+                    ast::Instruction::WmmaLoad(details, arg),
+                    fn_name,
+                )?);
+            }
+            Statement::Instruction(ast::Instruction::WmmaStore(details, arg)) => {
+                let fn_name = [
+                    ZLUDA_PTX_PREFIX,
+                    "wmma_store_d_",
+                    details.layout.to_ptx_name(),
+                    "_",
+                    details.shape.to_ptx_name(),
+                    "_",
+                    details.state_space.to_ptx_name(),
+                    "_",
+                    details.type_.to_ptx_name(),
+                ]
+                .concat();
+                statements.push(instruction_to_fn_call(
+                    id_def,
+                    ptx_impl_imports,
+                    ast::Instruction::WmmaStore(details, arg),
+                    fn_name,
+                )?);
+            }
+            Statement::Instruction(ast::Instruction::WmmaMma(details, arg)) => {
+                let fn_name = [
+                    ZLUDA_PTX_PREFIX,
+                    "wmma_mma_",
+                    details.a_layout.to_ptx_name(),
+                    "_",
+                    details.b_layout.to_ptx_name(),
+                    "_",
+                    &mma_fn_suffix(&details),
+                ]
+                .concat();
+                statements.push(instruction_to_fn_call(
+                    id_def,
+                    ptx_impl_imports,
+                    ast::Instruction::WmmaMma(details, arg),
+                    fn_name,
+                )?);
+            }
+            Statement::Instruction(ast::Instruction::Mma(details, arg)) => {
+                // Other layouts are only allowed for .m8n8k4, which we don't
+                // support
+                if details.a_layout != ast::MatrixLayout::Row
+                    || details.b_layout != ast::MatrixLayout::Col
+                {
+                    return Err(TranslateError::Unsupported(
+                        "mma layouts other than .row.col",
+                    ));
+                }
+                let fn_name = [ZLUDA_PTX_PREFIX, "mma_", &mma_fn_suffix(&details)].concat();
+                statements.push(instruction_to_fn_call(
+                    id_def,
+                    ptx_impl_imports,
+                    ast::Instruction::Mma(details, arg),
+                    fn_name,
+                )?);
+            }
+            Statement::Instruction(ast::Instruction::Ldmatrix(details, arg)) => {
+                let fn_name = [
+                    ZLUDA_PTX_PREFIX,
+                    "ldmatrix_m8n8_x",
+                    &details.count.to_string(),
+                    if details.transpose { "_trans" } else { "" },
+                    "_",
+                    details.state_space.to_ptx_name(),
+                    "_b16",
+                ]
+                .concat();
+                statements.push(instruction_to_fn_call(
+                    id_def,
+                    ptx_impl_imports,
+                    ast::Instruction::Ldmatrix(details, arg),
+                    fn_name,
+                )?);
+            }
+            Statement::Instruction(ast::Instruction::CpAsync(details, arg)) => {
+                let fn_name = [
+                    ZLUDA_PTX_PREFIX,
+                    "cp_async_",
+                    &details.cp_size.to_string(),
+                    "_shared_global",
+                ]
+                .concat();
+                statements.push(instruction_to_fn_call(
+                    id_def,
+                    ptx_impl_imports,
+                    ast::Instruction::CpAsync(details, arg),
+                    fn_name,
+                )?);
+            }
+            Statement::Instruction(ast::Instruction::CpAsyncBulk(arg)) => {
+                let fn_name = [ZLUDA_PTX_PREFIX, "cp_async_bulk_shared_global"].concat();
+                statements.push(instruction_to_fn_call(
+                    id_def,
+                    ptx_impl_imports,
+                    ast::Instruction::CpAsyncBulk(arg),
+                    fn_name,
+                )?);
+            }
+            Statement::Instruction(ast::Instruction::CpAsyncBulkGroup(arg)) => {
+                let fn_name = [ZLUDA_PTX_PREFIX, "cp_async_bulk_global_shared"].concat();
+                statements.push(instruction_to_fn_call(
+                    id_def,
+                    ptx_impl_imports,
+                    ast::Instruction::CpAsyncBulkGroup(arg),
+                    fn_name,
+                )?);
+            }
+            Statement::Instruction(ast::Instruction::MbarrierInit(state_space, arg)) => {
+                let fn_name = [
+                    ZLUDA_PTX_PREFIX,
+                    "mbarrier_init_",
+                    state_space.to_ptx_name(),
+                ]
+                .concat();
+                statements.push(instruction_to_fn_call(
+                    id_def,
+                    ptx_impl_imports,
+                    ast::Instruction::MbarrierInit(state_space, arg),
+                    fn_name,
+                )?);
+            }
+            Statement::Instruction(ast::Instruction::MbarrierArrive(details, arg)) => {
+                let fn_name =
+                    [ZLUDA_PTX_PREFIX, &mbarrier_arrive_fn_name(&details, false)].concat();
+                statements.push(instruction_to_fn_call(
+                    id_def,
+                    ptx_impl_imports,
+                    ast::Instruction::MbarrierArrive(details, arg),
+                    fn_name,
+                )?);
+            }
+            Statement::Instruction(ast::Instruction::MbarrierArriveNoState(details, arg)) => {
+                let fn_name = [ZLUDA_PTX_PREFIX, &mbarrier_arrive_fn_name(&details, true)].concat();
+                statements.push(instruction_to_fn_call(
+                    id_def,
+                    ptx_impl_imports,
+                    ast::Instruction::MbarrierArriveNoState(details, arg),
+                    fn_name,
+                )?);
+            }
+            Statement::Instruction(ast::Instruction::MbarrierExpectTx(state_space, arg)) => {
+                let fn_name = [
+                    ZLUDA_PTX_PREFIX,
+                    "mbarrier_expect_tx_",
+                    state_space.to_ptx_name(),
+                ]
+                .concat();
+                statements.push(instruction_to_fn_call(
+                    id_def,
+                    ptx_impl_imports,
+                    ast::Instruction::MbarrierExpectTx(state_space, arg),
+                    fn_name,
+                )?);
+            }
+            Statement::Instruction(ast::Instruction::MbarrierTestWait(details, arg)) => {
+                let fn_name = [
+                    ZLUDA_PTX_PREFIX,
+                    "mbarrier_",
+                    if details.try_wait {
+                        "try_wait_"
+                    } else {
+                        "test_wait_"
+                    },
+                    if details.parity { "parity_" } else { "" },
+                    details.state_space.to_ptx_name(),
+                ]
+                .concat();
+                statements.push(instruction_to_fn_call(
+                    id_def,
+                    ptx_impl_imports,
+                    ast::Instruction::MbarrierTestWait(details, arg),
+                    fn_name,
+                )?);
+            }
+            Statement::Instruction(ast::Instruction::MbarrierPendingCount(arg)) => {
+                let fn_name = [ZLUDA_PTX_PREFIX, "mbarrier_pending_count"].concat();
+                statements.push(instruction_to_fn_call(
+                    id_def,
+                    ptx_impl_imports,
+                    ast::Instruction::MbarrierPendingCount(arg),
+                    fn_name,
+                )?);
+            }
+            Statement::Instruction(ast::Instruction::MbarrierInval(state_space, arg)) => {
+                let fn_name = [
+                    ZLUDA_PTX_PREFIX,
+                    "mbarrier_inval_",
+                    state_space.to_ptx_name(),
+                ]
+                .concat();
+                statements.push(instruction_to_fn_call(
+                    id_def,
+                    ptx_impl_imports,
+                    ast::Instruction::MbarrierInval(state_space, arg),
+                    fn_name,
+                )?);
+            }
             Statement::Instruction(ast::Instruction::Dp4a(type_, arg)) => {
                 let fn_name = [
                     ZLUDA_PTX_PREFIX,
@@ -3088,10 +3451,40 @@ fn replace_instructions_with_builtins_impl<'input>(
             }
             s => statements.push(s),
         }
-    }
+        Ok(())
+    })?;
     Ok(statements)
 }
 
+// shape_dtype_atype_btype_ctype[_satfinite]
+fn mma_fn_suffix(details: &ast::MmaDetails) -> String {
+    [
+        details.shape.to_ptx_name(),
+        "_",
+        details.d_type.to_ptx_name(),
+        "_",
+        details.a_type.to_ptx_name(),
+        "_",
+        details.b_type.to_ptx_name(),
+        "_",
+        details.c_type.to_ptx_name(),
+        if details.satfinite { "_satfinite" } else { "" },
+    ]
+    .concat()
+}
+
+// mbarrier_arrive[_drop][_expect_tx][_nostate]_space
+fn mbarrier_arrive_fn_name(details: &ast::MbarrierArriveDetails, no_state: bool) -> String {
+    [
+        "mbarrier_arrive_",
+        if details.drop { "drop_" } else { "" },
+        if details.expect_tx { "expect_tx_" } else { "" },
+        if no_state { "nostate_" } else { "" },
+        details.state_space.to_ptx_name(),
+    ]
+    .concat()
+}
+
 fn compilation_mode_suffix(compilation_mode: CompilationMode) -> &'static str {
     match compilation_mode {
         CompilationMode::Wave32 => "_32",
@@ -3248,6 +3641,7 @@ fn compute_denorm_statistics<'input, P: ast::ArgParams<Id = Id>>(
                         Statement::SubC(..) => {}
                         Statement::SubCC(..) => {}
                         Statement::AsmVolatile { .. } => {}
+                        Statement::Location(..) | Statement::Span(..) => {}
                     }
                 }
                 let summary = DenormSummary {
@@ -3279,7 +3673,16 @@ pub fn to_llvm_module<'input>(
     compilation_mode: CompilationMode,
     ast: Vec<ast::Module<'input>>,
 ) -> Result<Module<'input>, TranslateError> {
-    to_llvm_module_impl2(compilation_mode, ast, None)
+    to_llvm_module_impl2(compilation_mode, ast, false, None)
+}
+
+// Same as above, but additionally emits DWARF line tables built from .file and
+// .loc directives
+pub fn to_llvm_module_with_line_info<'input>(
+    compilation_mode: CompilationMode,
+    ast: Vec<ast::Module<'input>>,
+) -> Result<Module<'input>, TranslateError> {
+    to_llvm_module_impl2(compilation_mode, ast, true, None)
 }
 
 pub fn to_llvm_module_for_raytracing<'input>(
@@ -3292,6 +3695,7 @@ pub fn to_llvm_module_for_raytracing<'input>(
     let compilation_module = to_llvm_module_impl2(
         CompilationMode::Wave32,
         vec![ast],
+        false,
         Some(&mut raytracing_state),
     )?;
     let entry_point_kind: RaytracingEntryPointKind = raytracing_state.entry_point_kind.unwrap();
@@ -3355,9 +3759,17 @@ pub(crate) enum RaytracingEntryPointKind {
 
 fn to_llvm_module_impl2<'a, 'input>(
     compilation_mode: CompilationMode,
-    asts: Vec<ast::Module<'input>>,
+    mut asts: Vec<ast::Module<'input>>,
+    line_info: bool,
     mut raytracing: Option<&mut RaytracingTranslationState<'a, 'input>>,
 ) -> Result<Module<'input>, TranslateError> {
+    // Without files .loc directives are dropped during normalization, so
+    // later passes never see Statement::Location
+    if !line_info {
+        for ast in asts.iter_mut() {
+            ast.debug_files.clear();
+        }
+    }
     let empty_module = if raytracing.is_some() {
         raytracing::create_module_with_builtins()
     } else {
@@ -3403,7 +3815,7 @@ fn to_llvm_module_impl2<'a, 'input>(
     }
     let metadata = create_metadata(&translation_module);
     let (llvm_context, llvm_module) = unsafe {
-        emit::emit_llvm_bitcode_and_linker_module(translation_module, denorm_statistics)?
+        emit::emit_llvm_bitcode_and_linker_module(translation_module, denorm_statistics, line_info)?
     };
     Ok(Module {
         metadata,
@@ -3428,7 +3840,9 @@ fn return_from_noreturn(
         match directive {
             TranslationDirective::Method(method) => {
                 if let Some(ref mut body) = method.body {
-                    if body.is_empty() && method.tuning.contains(&ast::TuningDirective::Noreturn) {
+                    if body.iter().all(|s| matches!(s, Statement::Location(..)))
+                        && method.tuning.contains(&ast::TuningDirective::Noreturn)
+                    {
                         body.push(Statement::Instruction(ast::Instruction::Ret(
                             ast::RetData { uniform: false },
                         )));
@@ -3496,7 +3910,7 @@ fn deparamize_variable_declarations_convert_body<'input>(
     fn_body: Vec<ExpandedStatement>,
 ) -> Result<Vec<ExpandedStatement>, TranslateError> {
     let mut result = Vec::with_capacity(fn_body.len());
-    for statement in fn_body {
+    for_each_statement(fn_body, |statement| {
         match statement {
             Statement::Instruction(ast::Instruction::Mov(details, mut args)) => {
                 if let Some((new_name, _)) = new_space.get(&args.src) {
@@ -3570,7 +3984,8 @@ fn deparamize_variable_declarations_convert_body<'input>(
             }
             s => result.push(s),
         }
-    }
+        Ok(())
+    })?;
     Ok(result)
 }
 
@@ -4188,6 +4603,7 @@ impl<'a, 'input> SpecialRegisterResolver<'a, 'input> {
             if desc.is_dst {
                 return Err(TranslateError::mismatched_type());
             }
+            let vector_index = vector_index.or(sreg.get_implicit_index());
             let input_arguments = match (vector_index, sreg.get_function_input_type()) {
                 (Some(idx), Some(inp_type)) => {
                     if inp_type != ast::ScalarType::U8 {
@@ -4351,29 +4767,185 @@ impl ast::ShflMode {
         }
     }
 }
-struct VectorRepackVisitor<'a, 'input, V> {
-    extra_vistor: &'a mut V,
-    func: &'a mut Vec<TypedStatement>,
-    id_def: &'a mut IdNameMapBuilder<'input>,
-    post_stmts: Option<TypedStatement>,
+
+impl ast::MatrixShape {
+    fn to_ptx_name(self) -> &'static str {
+        match self {
+            ast::MatrixShape::M8n8k16 => "m8n8k16",
+            ast::MatrixShape::M16n8k4 => "m16n8k4",
+            ast::MatrixShape::M16n8k8 => "m16n8k8",
+            ast::MatrixShape::M16n8k16 => "m16n8k16",
+            ast::MatrixShape::M16n8k32 => "m16n8k32",
+            ast::MatrixShape::M16n16k8 => "m16n16k8",
+            ast::MatrixShape::M16n16k16 => "m16n16k16",
+            ast::MatrixShape::M8n32k16 => "m8n32k16",
+            ast::MatrixShape::M32n8k16 => "m32n8k16",
+        }
+    }
+
+    // (m, n, k)
+    fn dimensions(self) -> (u32, u32, u32) {
+        match self {
+            ast::MatrixShape::M8n8k16 => (8, 8, 16),
+            ast::MatrixShape::M16n8k4 => (16, 8, 4),
+            ast::MatrixShape::M16n8k8 => (16, 8, 8),
+            ast::MatrixShape::M16n8k16 => (16, 8, 16),
+            ast::MatrixShape::M16n8k32 => (16, 8, 32),
+            ast::MatrixShape::M16n16k8 => (16, 16, 8),
+            ast::MatrixShape::M16n16k16 => (16, 16, 16),
+            ast::MatrixShape::M8n32k16 => (8, 32, 16),
+            ast::MatrixShape::M32n8k16 => (32, 8, 16),
+        }
+    }
+
+    fn is_wmma(self) -> bool {
+        match self {
+            ast::MatrixShape::M16n16k8
+            | ast::MatrixShape::M16n16k16
+            | ast::MatrixShape::M8n32k16
+            | ast::MatrixShape::M32n8k16 => true,
+            ast::MatrixShape::M8n8k16
+            | ast::MatrixShape::M16n8k4
+            | ast::MatrixShape::M16n8k8
+            | ast::MatrixShape::M16n8k16
+            | ast::MatrixShape::M16n8k32 => false,
+        }
+    }
 }
 
-impl<'a, 'input, V> VectorRepackVisitor<'a, 'input, V> {
-    fn new(
-        extra_vistor: &'a mut V,
-        func: &'a mut Vec<TypedStatement>,
-        id_def: &'a mut IdNameMapBuilder<'input>,
-    ) -> Self {
-        VectorRepackVisitor {
-            extra_vistor,
-            func,
-            id_def,
-            post_stmts: None,
+impl ast::MatrixLayout {
+    fn to_ptx_name(self) -> &'static str {
+        match self {
+            ast::MatrixLayout::Row => "row",
+            ast::MatrixLayout::Col => "col",
         }
     }
+}
 
-    fn convert_vector(
-        &mut self,
+impl ast::MatrixFragment {
+    fn to_ptx_name(self) -> &'static str {
+        match self {
+            ast::MatrixFragment::A => "a",
+            ast::MatrixFragment::B => "b",
+            ast::MatrixFragment::C => "c",
+            ast::MatrixFragment::D => "d",
+        }
+    }
+}
+
+impl ast::MatrixType {
+    fn to_ptx_name(self) -> &'static str {
+        match self {
+            ast::MatrixType::F16 => "f16",
+            ast::MatrixType::BF16 => "bf16",
+            ast::MatrixType::TF32 => "tf32",
+            ast::MatrixType::F32 => "f32",
+            ast::MatrixType::S8 => "s8",
+            ast::MatrixType::U8 => "u8",
+            ast::MatrixType::S32 => "s32",
+        }
+    }
+
+    fn size_of_bits(self) -> u32 {
+        match self {
+            ast::MatrixType::S8 | ast::MatrixType::U8 => 8,
+            ast::MatrixType::F16 | ast::MatrixType::BF16 => 16,
+            ast::MatrixType::TF32 | ast::MatrixType::F32 | ast::MatrixType::S32 => 32,
+        }
+    }
+
+    // Type of a single matrix element as stored in memory
+    fn memory_type(self) -> ast::ScalarType {
+        match self {
+            ast::MatrixType::F16 => ast::ScalarType::F16,
+            ast::MatrixType::BF16 => ast::ScalarType::BF16,
+            ast::MatrixType::TF32 | ast::MatrixType::F32 => ast::ScalarType::F32,
+            ast::MatrixType::S8 => ast::ScalarType::S8,
+            ast::MatrixType::U8 => ast::ScalarType::U8,
+            ast::MatrixType::S32 => ast::ScalarType::S32,
+        }
+    }
+
+    // Type of a single fragment register, narrower types are packed into .b32
+    fn register_type(self) -> ast::ScalarType {
+        match self {
+            ast::MatrixType::F32 => ast::ScalarType::F32,
+            ast::MatrixType::S32 => ast::ScalarType::S32,
+            ast::MatrixType::F16
+            | ast::MatrixType::BF16
+            | ast::MatrixType::TF32
+            | ast::MatrixType::S8
+            | ast::MatrixType::U8 => ast::ScalarType::B32,
+        }
+    }
+}
+
+// Matrix fragments are passed as a vector of registers, exactly as they are
+// written in the PTX source, e.g. {%r1, %r2, %r3, %r4}. The number of
+// registers is the same as on NVIDIA hardware: every thread holds its share of
+// the matrix packed into 32-bit registers
+fn matrix_fragment_type(
+    is_wmma: bool,
+    shape: ast::MatrixShape,
+    fragment: ast::MatrixFragment,
+    type_: ast::MatrixType,
+) -> Result<ast::Type, TranslateError> {
+    if shape.is_wmma() != is_wmma {
+        return Err(TranslateError::mismatched_type());
+    }
+    let is_input = match (fragment, type_) {
+        (
+            ast::MatrixFragment::A | ast::MatrixFragment::B,
+            ast::MatrixType::F16
+            | ast::MatrixType::BF16
+            | ast::MatrixType::TF32
+            | ast::MatrixType::S8
+            | ast::MatrixType::U8,
+        ) => true,
+        (
+            ast::MatrixFragment::C | ast::MatrixFragment::D,
+            ast::MatrixType::F16 | ast::MatrixType::F32 | ast::MatrixType::S32,
+        ) => false,
+        _ => return Err(TranslateError::mismatched_type()),
+    };
+    let (m, n, k) = shape.dimensions();
+    let (rows, cols) = match fragment {
+        ast::MatrixFragment::A => (m, k),
+        ast::MatrixFragment::B => (k, n),
+        ast::MatrixFragment::C | ast::MatrixFragment::D => (m, n),
+    };
+    // wmma .f16 input fragments hold two copies of each element
+    let registers = if is_wmma && is_input && type_ == ast::MatrixType::F16 {
+        8
+    } else {
+        (rows * cols * type_.size_of_bits()) / (32 * 32)
+    };
+    Ok(ast::Type::Vector(type_.register_type(), registers as u8))
+}
+
+struct VectorRepackVisitor<'a, 'input, V> {
+    extra_vistor: &'a mut V,
+    func: &'a mut Vec<TypedStatement>,
+    id_def: &'a mut IdNameMapBuilder<'input>,
+    post_stmts: Option<TypedStatement>,
+}
+
+impl<'a, 'input, V> VectorRepackVisitor<'a, 'input, V> {
+    fn new(
+        extra_vistor: &'a mut V,
+        func: &'a mut Vec<TypedStatement>,
+        id_def: &'a mut IdNameMapBuilder<'input>,
+    ) -> Self {
+        VectorRepackVisitor {
+            extra_vistor,
+            func,
+            id_def,
+            post_stmts: None,
+        }
+    }
+
+    fn convert_vector(
+        &mut self,
         is_dst: bool,
         non_default_implicit_conversion: Option<
             fn(
@@ -4954,6 +5526,8 @@ enum PtxSpecialRegister {
     LanemaskGe,
     Laneid,
     Clock64,
+    // %envreg0..%envreg31
+    Envreg(u8),
 }
 
 impl PtxSpecialRegister {
@@ -4969,7 +5543,14 @@ impl PtxSpecialRegister {
             "%lanemask_ge" => Some(Self::LanemaskGe),
             "%laneid" => Some(Self::Laneid),
             "%clock64" => Some(Self::Clock64),
-            _ => None,
+            _ => {
+                let index = s.strip_prefix("%envreg")?.parse::<u8>().ok()?;
+                if index < 32 {
+                    Some(Self::Envreg(index))
+                } else {
+                    None
+                }
+            }
         }
     }
 
@@ -4995,6 +5576,7 @@ impl PtxSpecialRegister {
             | PtxSpecialRegister::LanemaskGe
             | PtxSpecialRegister::Laneid => ast::ScalarType::U32,
             PtxSpecialRegister::Clock64 => ast::ScalarType::U64,
+            PtxSpecialRegister::Envreg(_) => ast::ScalarType::B32,
         }
     }
 
@@ -5003,7 +5585,8 @@ impl PtxSpecialRegister {
             PtxSpecialRegister::Tid
             | PtxSpecialRegister::Ntid
             | PtxSpecialRegister::Ctaid
-            | PtxSpecialRegister::Nctaid => Some(ast::ScalarType::U8),
+            | PtxSpecialRegister::Nctaid
+            | PtxSpecialRegister::Envreg(_) => Some(ast::ScalarType::U8),
             PtxSpecialRegister::Clock
             | PtxSpecialRegister::Clock64
             | PtxSpecialRegister::LanemaskLt
@@ -5025,6 +5608,16 @@ impl PtxSpecialRegister {
             PtxSpecialRegister::LanemaskLe => "sreg_lanemask_le",
             PtxSpecialRegister::LanemaskGe => "sreg_lanemask_ge",
             PtxSpecialRegister::Laneid => "sreg_laneid",
+            PtxSpecialRegister::Envreg(_) => "sreg_envreg",
+        }
+    }
+
+    // Register index passed to the runtime function for registers which are
+    // not accessed through a vector member
+    fn get_implicit_index(self) -> Option<u8> {
+        match self {
+            PtxSpecialRegister::Envreg(index) => Some(index),
+            _ => None,
         }
     }
 }
@@ -5600,6 +6193,11 @@ pub(crate) enum Statement<I, P: ast::ArgParams> {
     // SPIR-V compatible replacement for PTX predicates
     Conditional(BrachCondition),
     Call(ResolvedCall<P>),
+    // Source location of all the statements that follow, .file index is
+    // resolved to an index into TranslationModule::debug_files
+    Location(ast::SourceLocation),
+    // PTX statement that all the statements that follow were translated from
+    Span(ast::StatementSpan),
     LoadVar(LoadVarDetails),
     StoreVar(StoreVarDetails),
     Conversion(ImplicitConversion),
@@ -5620,6 +6218,21 @@ pub(crate) enum Statement<I, P: ast::ArgParams> {
     },
 }
 
+// Errors returned by `f` are attributed to the PTX statement being translated
+fn for_each_statement<I, P: ast::ArgParams>(
+    statements: Vec<Statement<I, P>>,
+    mut f: impl FnMut(Statement<I, P>) -> Result<(), TranslateError>,
+) -> Result<(), TranslateError> {
+    let mut span = None;
+    for statement in statements {
+        if let Statement::Span(statement_span) = statement {
+            span = Some(statement_span);
+        }
+        f(statement).map_err(|err| err.with_span(span))?;
+    }
+    Ok(())
+}
+
 impl ExpandedStatement {
     pub(crate) fn map_id(self, f: &mut impl FnMut(Id, bool) -> Id) -> ExpandedStatement {
         match self {
@@ -5758,6 +6371,8 @@ impl ExpandedStatement {
             Statement::AsmVolatile { asm, constraints } => {
                 Statement::AsmVolatile { asm, constraints }
             }
+            Statement::Location(location) => Statement::Location(location),
+            Statement::Span(span) => Statement::Span(span),
         }
     }
 }
@@ -6628,6 +7243,88 @@ impl<T: ArgParamsEx> ast::Instruction<T> {
                     &ast::Type::Scalar(ast::ScalarType::U64),
                 )?,
             ),
+            ast::Instruction::WmmaLoad(details, arg) => {
+                let arg = arg.map_wmma_load(visitor, &details)?;
+                ast::Instruction::WmmaLoad(details, arg)
+            }
+            ast::Instruction::WmmaStore(details, arg) => {
+                let arg = arg.map(visitor, &details)?;
+                ast::Instruction::WmmaStore(details, arg)
+            }
+            ast::Instruction::WmmaMma(details, arg) => {
+                let arg = arg.map_mma(visitor, &details, true)?;
+                ast::Instruction::WmmaMma(details, arg)
+            }
+            ast::Instruction::Mma(details, arg) => {
+                let arg = arg.map_mma(visitor, &details, false)?;
+                ast::Instruction::Mma(details, arg)
+            }
+            ast::Instruction::Ldmatrix(details, arg) => {
+                let arg = arg.map_ldmatrix(visitor, &details)?;
+                ast::Instruction::Ldmatrix(details, arg)
+            }
+            ast::Instruction::CpAsync(details, arg) => {
+                let arg =
+                    arg.map_cp_async(visitor, ast::StateSpace::Shared, ast::StateSpace::Global)?;
+                ast::Instruction::CpAsync(details, arg)
+            }
+            ast::Instruction::CpAsyncCommitGroup => ast::Instruction::CpAsyncCommitGroup,
+            ast::Instruction::CpAsyncWaitGroup(n) => ast::Instruction::CpAsyncWaitGroup(n),
+            ast::Instruction::CpAsyncBulk(arg) => {
+                ast::Instruction::CpAsyncBulk(arg.map_cp_async_bulk(visitor)?)
+            }
+            ast::Instruction::CpAsyncBulkGroup(arg) => ast::Instruction::CpAsyncBulkGroup(
+                arg.map_cp_async(visitor, ast::StateSpace::Global, ast::StateSpace::Shared)?,
+            ),
+            ast::Instruction::MbarrierInit(state_space, arg) => {
+                ast::Instruction::MbarrierInit(state_space, arg.map_mbarrier(visitor, state_space)?)
+            }
+            ast::Instruction::MbarrierArrive(details, arg) => {
+                let arg = arg.map_mbarrier(
+                    visitor,
+                    ast::ScalarType::B64,
+                    details.state_space,
+                    ast::ScalarType::U32,
+                )?;
+                ast::Instruction::MbarrierArrive(details, arg)
+            }
+            ast::Instruction::MbarrierArriveNoState(details, arg) => {
+                ast::Instruction::MbarrierArriveNoState(
+                    details,
+                    arg.map_mbarrier(visitor, details.state_space)?,
+                )
+            }
+            ast::Instruction::MbarrierExpectTx(state_space, arg) => {
+                ast::Instruction::MbarrierExpectTx(
+                    state_space,
+                    arg.map_mbarrier(visitor, state_space)?,
+                )
+            }
+            ast::Instruction::MbarrierTestWait(details, arg) => {
+                let src2_type = if details.parity {
+                    ast::ScalarType::U32
+                } else {
+                    ast::ScalarType::B64
+                };
+                let arg = arg.map_mbarrier(
+                    visitor,
+                    ast::ScalarType::Pred,
+                    details.state_space,
+                    src2_type,
+                )?;
+                ast::Instruction::MbarrierTestWait(details, arg)
+            }
+            ast::Instruction::MbarrierPendingCount(arg) => {
+                ast::Instruction::MbarrierPendingCount(arg.map_different_types(
+                    visitor,
+                    &ast::Type::Scalar(ast::ScalarType::U32),
+                    &ast::Type::Scalar(ast::ScalarType::B64),
+                )?)
+            }
+            ast::Instruction::MbarrierInval(state_space, arg) => ast::Instruction::MbarrierInval(
+                state_space,
+                arg.map_mbarrier(visitor, state_space)?,
+            ),
         })
     }
 }
@@ -6980,6 +7677,23 @@ impl<T: ast::ArgParams> ast::Instruction<T> {
             ast::Instruction::Vote(..) => None,
             ast::Instruction::Nanosleep(..) => None,
             ast::Instruction::Sad(_, _) => None,
+            ast::Instruction::WmmaLoad(..) => None,
+            ast::Instruction::WmmaStore(..) => None,
+            ast::Instruction::WmmaMma(..) => None,
+            ast::Instruction::Mma(..) => None,
+            ast::Instruction::Ldmatrix(..) => None,
+            ast::Instruction::CpAsync(..) => None,
+            ast::Instruction::CpAsyncCommitGroup => None,
+            ast::Instruction::CpAsyncWaitGroup(..) => None,
+            ast::Instruction::CpAsyncBulk(..) => None,
+            ast::Instruction::CpAsyncBulkGroup(..) => None,
+            ast::Instruction::MbarrierInit(..) => None,
+            ast::Instruction::MbarrierArrive(..) => None,
+            ast::Instruction::MbarrierArriveNoState(..) => None,
+            ast::Instruction::MbarrierExpectTx(..) => None,
+            ast::Instruction::MbarrierTestWait(..) => None,
+            ast::Instruction::MbarrierPendingCount(..) => None,
+            ast::Instruction::MbarrierInval(..) => None,
             ast::Instruction::Sub(ast::ArithDetails::Float(float_control), _)
             | ast::Instruction::Add(ast::ArithDetails::Float(float_control), _)
             | ast::Instruction::Mul(ast::MulDetails::Float(float_control), _)
@@ -7600,6 +8314,24 @@ impl<T: ArgParamsEx> ast::Arg1Bar<T> {
         )?;
         Ok(ast::Arg1Bar { src: new_src })
     }
+
+    fn map_mbarrier<U: ArgParamsEx, V: ArgumentMapVisitor<T, U>>(
+        self,
+        visitor: &mut V,
+        state_space: ast::StateSpace,
+    ) -> Result<ast::Arg1Bar<U>, TranslateError> {
+        let src = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src,
+                is_dst: false,
+                is_memory_access: true,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(ast::ScalarType::B64),
+            state_space,
+        )?;
+        Ok(ast::Arg1Bar { src })
+    }
 }
 
 impl<T: ArgParamsEx> ast::Arg2<T> {
@@ -7730,6 +8462,34 @@ impl<T: ArgParamsEx> ast::Arg2Ld<T> {
         )?;
         Ok(ast::Arg2Ld { dst, src })
     }
+
+    fn map_ldmatrix<U: ArgParamsEx, V: ArgumentMapVisitor<T, U>>(
+        self,
+        visitor: &mut V,
+        details: &ast::LdmatrixDetails,
+    ) -> Result<ast::Arg2Ld<U>, TranslateError> {
+        let dst = visitor.operand(
+            ArgumentDescriptor {
+                op: self.dst,
+                is_dst: true,
+                is_memory_access: false,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Vector(ast::ScalarType::B32, details.count),
+            ast::StateSpace::Reg,
+        )?;
+        let src = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src,
+                is_dst: false,
+                is_memory_access: true,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(ast::ScalarType::B16),
+            details.state_space,
+        )?;
+        Ok(ast::Arg2Ld { dst, src })
+    }
 }
 
 impl<T: ArgParamsEx> ast::Arg2St<T> {
@@ -7761,6 +8521,34 @@ impl<T: ArgParamsEx> ast::Arg2St<T> {
         )?;
         Ok(ast::Arg2St { src1, src2 })
     }
+
+    fn map_mbarrier<U: ArgParamsEx, V: ArgumentMapVisitor<T, U>>(
+        self,
+        visitor: &mut V,
+        state_space: ast::StateSpace,
+    ) -> Result<ast::Arg2St<U>, TranslateError> {
+        let src1 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src1,
+                is_dst: false,
+                is_memory_access: true,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(ast::ScalarType::B64),
+            state_space,
+        )?;
+        let src2 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src2,
+                is_dst: false,
+                is_memory_access: false,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(ast::ScalarType::U32),
+            ast::StateSpace::Reg,
+        )?;
+        Ok(ast::Arg2St { src1, src2 })
+    }
 }
 
 impl<T: ArgParamsEx> ast::Arg2Mov<T> {
@@ -7877,6 +8665,46 @@ impl<T: ArgParamsEx> ast::Arg3<T> {
         Ok(ast::Arg3 { dst, src1, src2 })
     }
 
+    fn map_mbarrier<U: ArgParamsEx, V: ArgumentMapVisitor<T, U>>(
+        self,
+        visitor: &mut V,
+        dst_type: ast::ScalarType,
+        state_space: ast::StateSpace,
+        src2_type: ast::ScalarType,
+    ) -> Result<ast::Arg3<U>, TranslateError> {
+        let dst = visitor.operand(
+            ArgumentDescriptor {
+                op: self.dst,
+                is_dst: true,
+                is_memory_access: false,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(dst_type),
+            ast::StateSpace::Reg,
+        )?;
+        let src1 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src1,
+                is_dst: false,
+                is_memory_access: true,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(ast::ScalarType::B64),
+            state_space,
+        )?;
+        let src2 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src2,
+                is_dst: false,
+                is_memory_access: false,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(src2_type),
+            ast::StateSpace::Reg,
+        )?;
+        Ok(ast::Arg3 { dst, src1, src2 })
+    }
+
     fn map_shift<U: ArgParamsEx, V: ArgumentMapVisitor<T, U>>(
         self,
         visitor: &mut V,
@@ -8071,6 +8899,177 @@ impl<T: ArgParamsEx> ast::Arg3<T> {
         )?;
         Ok(ast::Arg3 { dst, src1, src2 })
     }
+
+    fn map_wmma_load<U: ArgParamsEx, V: ArgumentMapVisitor<T, U>>(
+        self,
+        visitor: &mut V,
+        details: &ast::WmmaLoadDetails,
+    ) -> Result<ast::Arg3<U>, TranslateError> {
+        let dst = visitor.operand(
+            ArgumentDescriptor {
+                op: self.dst,
+                is_dst: true,
+                is_memory_access: false,
+                non_default_implicit_conversion: None,
+            },
+            &matrix_fragment_type(true, details.shape, details.fragment, details.type_)?,
+            ast::StateSpace::Reg,
+        )?;
+        let src1 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src1,
+                is_dst: false,
+                is_memory_access: true,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(details.type_.memory_type()),
+            details.state_space,
+        )?;
+        let src2 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src2,
+                is_dst: false,
+                is_memory_access: false,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(ast::ScalarType::U32),
+            ast::StateSpace::Reg,
+        )?;
+        Ok(ast::Arg3 { dst, src1, src2 })
+    }
+}
+
+impl<T: ArgParamsEx> ast::Arg3St<T> {
+    fn map<U: ArgParamsEx, V: ArgumentMapVisitor<T, U>>(
+        self,
+        visitor: &mut V,
+        details: &ast::WmmaStoreDetails,
+    ) -> Result<ast::Arg3St<U>, TranslateError> {
+        let src1 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src1,
+                is_dst: false,
+                is_memory_access: true,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(details.type_.memory_type()),
+            details.state_space,
+        )?;
+        let src2 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src2,
+                is_dst: false,
+                is_memory_access: false,
+                non_default_implicit_conversion: None,
+            },
+            &matrix_fragment_type(true, details.shape, ast::MatrixFragment::D, details.type_)?,
+            ast::StateSpace::Reg,
+        )?;
+        let src3 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src3,
+                is_dst: false,
+                is_memory_access: false,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(ast::ScalarType::U32),
+            ast::StateSpace::Reg,
+        )?;
+        Ok(ast::Arg3St { src1, src2, src3 })
+    }
+
+    fn map_cp_async<U: ArgParamsEx, V: ArgumentMapVisitor<T, U>>(
+        self,
+        visitor: &mut V,
+        dst_space: ast::StateSpace,
+        src_space: ast::StateSpace,
+    ) -> Result<ast::Arg3St<U>, TranslateError> {
+        let src1 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src1,
+                is_dst: false,
+                is_memory_access: true,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(ast::ScalarType::B8),
+            dst_space,
+        )?;
+        let src2 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src2,
+                is_dst: false,
+                is_memory_access: true,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(ast::ScalarType::B8),
+            src_space,
+        )?;
+        let src3 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src3,
+                is_dst: false,
+                is_memory_access: false,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(ast::ScalarType::U32),
+            ast::StateSpace::Reg,
+        )?;
+        Ok(ast::Arg3St { src1, src2, src3 })
+    }
+}
+
+impl<T: ArgParamsEx> ast::Arg4St<T> {
+    fn map_cp_async_bulk<U: ArgParamsEx, V: ArgumentMapVisitor<T, U>>(
+        self,
+        visitor: &mut V,
+    ) -> Result<ast::Arg4St<U>, TranslateError> {
+        let src1 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src1,
+                is_dst: false,
+                is_memory_access: true,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(ast::ScalarType::B8),
+            ast::StateSpace::Shared,
+        )?;
+        let src2 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src2,
+                is_dst: false,
+                is_memory_access: true,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(ast::ScalarType::B8),
+            ast::StateSpace::Global,
+        )?;
+        let src3 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src3,
+                is_dst: false,
+                is_memory_access: false,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(ast::ScalarType::U32),
+            ast::StateSpace::Reg,
+        )?;
+        let src4 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src4,
+                is_dst: false,
+                is_memory_access: true,
+                non_default_implicit_conversion: None,
+            },
+            &ast::Type::Scalar(ast::ScalarType::B64),
+            ast::StateSpace::Shared,
+        )?;
+        Ok(ast::Arg4St {
+            src1,
+            src2,
+            src3,
+            src4,
+        })
+    }
 }
 
 fn texture_geometry_to_vec_length(geometry: ast::TextureGeometry) -> u8 {
@@ -8482,6 +9481,62 @@ impl<T: ArgParamsEx> ast::Arg4<T> {
             src3,
         })
     }
+
+    fn map_mma<U: ArgParamsEx, V: ArgumentMapVisitor<T, U>>(
+        self,
+        visitor: &mut V,
+        details: &ast::MmaDetails,
+        is_wmma: bool,
+    ) -> Result<ast::Arg4<U>, TranslateError> {
+        let fragment_type =
+            |fragment, type_| matrix_fragment_type(is_wmma, details.shape, fragment, type_);
+        let dst = visitor.operand(
+            ArgumentDescriptor {
+                op: self.dst,
+                is_dst: true,
+                is_memory_access: false,
+                non_default_implicit_conversion: None,
+            },
+            &fragment_type(ast::MatrixFragment::D, details.d_type)?,
+            ast::StateSpace::Reg,
+        )?;
+        let src1 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src1,
+                is_dst: false,
+                is_memory_access: false,
+                non_default_implicit_conversion: None,
+            },
+            &fragment_type(ast::MatrixFragment::A, details.a_type)?,
+            ast::StateSpace::Reg,
+        )?;
+        let src2 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src2,
+                is_dst: false,
+                is_memory_access: false,
+                non_default_implicit_conversion: None,
+            },
+            &fragment_type(ast::MatrixFragment::B, details.b_type)?,
+            ast::StateSpace::Reg,
+        )?;
+        let src3 = visitor.operand(
+            ArgumentDescriptor {
+                op: self.src3,
+                is_dst: false,
+                is_memory_access: false,
+                non_default_implicit_conversion: None,
+            },
+            &fragment_type(ast::MatrixFragment::C, details.c_type)?,
+            ast::StateSpace::Reg,
+        )?;
+        Ok(ast::Arg4 {
+            dst,
+            src1,
+            src2,
+            src3,
+        })
+    }
 }
 
 impl<T: ArgParamsEx> ast::Arg4Setp<T> {
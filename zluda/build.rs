@@ -1,5 +1 @@
-use vergen::{Config, vergen};
-
-fn main() {
-  vergen(Config::default()).unwrap()
-}
\ No newline at end of file
+fn main(){}
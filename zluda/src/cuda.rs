@@ -36,9 +36,12 @@ cuda_function_declarations!(
         cuGetExportTable,
         cuDriverGetVersion,
         cuDeviceCanAccessPeer,
+        cuDeviceGetP2PAttribute,
         cuDeviceGet,
         cuDeviceGetCount,
         cuDeviceGetMemPool,
+        cuDeviceGetDefaultMemPool,
+        cuDeviceSetMemPool,
         cuDeviceGetName,
         cuDeviceGetUuid,
         cuDeviceGetUuid_v2,
@@ -72,17 +75,28 @@ cuda_function_declarations!(
         cuCtxSetFlags,
         cuCtxGetStreamPriorityRange,
         cuCtxSynchronize,
+        cuCtxEnablePeerAccess,
+        cuCtxDisablePeerAccess,
         cuCtxSetCacheConfig,
         cuCtxGetApiVersion,
         cuFuncSetCacheConfig,
         cuLibraryLoadData,
         cuLibraryGetModule,
         cuLibraryUnload,
+        cuLibraryLoadFromFile,
+        cuLibraryGetKernel,
+        cuLibraryGetGlobal,
+        cuLibraryGetManaged,
+        cuKernelGetFunction,
+        cuKernelGetAttribute,
         cuModuleLoad,
         cuModuleLoadData,
         cuModuleLoadDataEx,
+        cuModuleLoadFatBinary,
         cuModuleUnload,
         cuModuleGetFunction,
+        cuModuleGetFunctionCount,
+        cuModuleEnumerateFunctions,
         cuModuleGetGlobal_v2,
         cuModuleGetLoadingMode,
         cuModuleGetSurfRef,
@@ -93,14 +107,33 @@ cuda_function_declarations!(
         cuMemAllocPitch_v2,
         cuMemFree_v2,
         cuMemFreeAsync,
+        cuMemFreeAsync_ptsz,
+        cuMemAllocAsync,
+        cuMemAllocAsync_ptsz,
+        cuMemAllocFromPoolAsync,
+        cuMemAllocFromPoolAsync_ptsz,
         cuMemFreeHost,
         cuMemHostAlloc,
         cuMemHostRegister,
         cuMemHostRegister_v2,
         cuMemHostUnregister,
         cuMemGetAddressRange_v2,
+        cuMemPoolCreate,
+        cuMemPoolDestroy,
+        cuMemPoolTrimTo,
+        cuMemPoolGetAttribute,
         cuMemPoolSetAttribute,
+        cuMemPoolSetAccess,
+        cuMemPoolGetAccess,
+        cuMemPoolExportToShareableHandle,
+        cuMemPoolImportFromShareableHandle,
+        cuMemPoolExportPointer,
+        cuMemPoolImportPointer,
         cuMemPrefetchAsync,
+        cuMemAdvise,
+        cuMemAdvise_v2,
+        cuMemRangeGetAttribute,
+        cuMemRangeGetAttributes,
         cuDeviceGetPCIBusId,
         cuMemcpy,
         cuMemcpy_ptds,
@@ -122,6 +155,10 @@ cuda_function_declarations!(
         cuMemcpy2DUnaligned_v2,
         cuMemcpy3D_v2,
         cuMemcpy3DAsync_v2,
+        cuMemcpyPeer,
+        cuMemcpyPeerAsync,
+        cuMemcpy3DPeer,
+        cuMemcpy3DPeerAsync,
         cuMemsetD8_v2,
         cuMemsetD8_v2_ptds,
         cuMemsetD8Async,
@@ -131,6 +168,19 @@ cuda_function_declarations!(
         cuMemsetD32_v2,
         cuMemsetD32_v2_ptds,
         cuMemsetD2D8_v2,
+        cuMemAddressReserve,
+        cuMemAddressFree,
+        cuMemCreate,
+        cuMemRelease,
+        cuMemMap,
+        cuMemUnmap,
+        cuMemSetAccess,
+        cuMemGetAccess,
+        cuMemExportToShareableHandle,
+        cuMemImportFromShareableHandle,
+        cuMemGetAllocationGranularity,
+        cuMemGetAllocationPropertiesFromHandle,
+        cuMemRetainAllocationHandle,
         cuOccupancyMaxPotentialBlockSize,
         cuArrayCreate_v2,
         cuArrayDestroy,
@@ -148,6 +198,28 @@ cuda_function_declarations!(
         cuStreamGetFlags,
         cuStreamGetPriority,
         cuStreamIsCapturing,
+        cuStreamWriteValue32,
+        cuStreamWriteValue64,
+        cuStreamWaitValue32,
+        cuStreamWaitValue64,
+        cuStreamBatchMemOp,
+        cuStreamAttachMemAsync,
+        cuStreamAttachMemAsync_ptsz,
+        cuStreamWriteValue32_ptsz,
+        cuStreamWriteValue64_ptsz,
+        cuStreamWaitValue32_ptsz,
+        cuStreamWaitValue64_ptsz,
+        cuStreamBatchMemOp_ptsz,
+        cuStreamWriteValue32_v2,
+        cuStreamWriteValue64_v2,
+        cuStreamWaitValue32_v2,
+        cuStreamWaitValue64_v2,
+        cuStreamBatchMemOp_v2,
+        cuStreamWriteValue32_v2_ptsz,
+        cuStreamWriteValue64_v2_ptsz,
+        cuStreamWaitValue32_v2_ptsz,
+        cuStreamWaitValue64_v2_ptsz,
+        cuStreamBatchMemOp_v2_ptsz,
         cuStreamQuery,
         cuStreamSynchronize,
         cuStreamSynchronize_ptsz,
@@ -157,10 +229,16 @@ cuda_function_declarations!(
         cuStreamWaitEvent,
         cuStreamWaitEvent_ptsz,
         cuFuncGetAttribute,
+        cuFuncGetName,
         cuFuncSetAttribute,
         cuLaunchHostFunc,
         cuLaunchKernel,
         cuLaunchKernel_ptsz,
+        cuLaunchCooperativeKernel,
+        cuLaunchCooperativeKernel_ptsz,
+        cuLaunchCooperativeKernelMultiDevice,
+        cuLaunchKernelEx,
+        cuLaunchKernelEx_ptsz,
         cuMemHostGetDevicePointer_v2,
         cuOccupancyMaxActiveBlocksPerMultiprocessorWithFlags,
         cuSurfObjectCreate,
@@ -198,6 +276,12 @@ cuda_function_declarations!(
         cuEventRecord,
         cuEventRecord_ptsz,
         cuEventSynchronize,
+        cuIpcGetMemHandle,
+        cuIpcOpenMemHandle,
+        cuIpcOpenMemHandle_v2,
+        cuIpcCloseMemHandle,
+        cuIpcGetEventHandle,
+        cuIpcOpenEventHandle,
         cuGraphGetNodes,
         cuGraphAddDependencies,
         cuGraphAddEmptyNode,
@@ -209,6 +293,53 @@ cuda_function_declarations!(
         cuGraphInstantiate,
         cuGraphInstantiate_v2,
         cuGraphLaunch,
+        cuGraphAddKernelNode_v2,
+        cuGraphKernelNodeGetParams,
+        cuGraphKernelNodeGetParams_v2,
+        cuGraphKernelNodeSetParams,
+        cuGraphKernelNodeSetParams_v2,
+        cuGraphExecKernelNodeSetParams,
+        cuGraphExecKernelNodeSetParams_v2,
+        cuGraphAddMemcpyNode,
+        cuGraphMemcpyNodeGetParams,
+        cuGraphMemcpyNodeSetParams,
+        cuGraphExecMemcpyNodeSetParams,
+        cuGraphAddMemsetNode,
+        cuGraphMemsetNodeGetParams,
+        cuGraphMemsetNodeSetParams,
+        cuGraphExecMemsetNodeSetParams,
+        cuGraphAddHostNode,
+        cuGraphHostNodeGetParams,
+        cuGraphHostNodeSetParams,
+        cuGraphExecHostNodeSetParams,
+        cuGraphAddChildGraphNode,
+        cuGraphChildGraphNodeGetGraph,
+        cuGraphExecChildGraphNodeSetParams,
+        cuGraphAddEventRecordNode,
+        cuGraphEventRecordNodeGetEvent,
+        cuGraphEventRecordNodeSetEvent,
+        cuGraphExecEventRecordNodeSetEvent,
+        cuGraphAddEventWaitNode,
+        cuGraphEventWaitNodeGetEvent,
+        cuGraphEventWaitNodeSetEvent,
+        cuGraphExecEventWaitNodeSetEvent,
+        cuGraphAddMemAllocNode,
+        cuGraphMemAllocNodeGetParams,
+        cuGraphAddMemFreeNode,
+        cuGraphMemFreeNodeGetParams,
+        cuGraphClone,
+        cuGraphNodeFindInClone,
+        cuGraphNodeGetType,
+        cuGraphGetRootNodes,
+        cuGraphGetEdges,
+        cuGraphNodeGetDependencies,
+        cuGraphNodeGetDependentNodes,
+        cuGraphRemoveDependencies,
+        cuGraphDestroyNode,
+        cuGraphExecUpdate,
+        cuGraphExecUpdate_v2,
+        cuGraphUpload,
+        cuGraphDebugDotPrint,
         cuGraphicsSubResourceGetMappedArray,
         cuGraphicsGLRegisterBuffer,
         cuGraphicsGLRegisterImage,
@@ -216,6 +347,16 @@ cuda_function_declarations!(
         cuGraphicsResourceGetMappedPointer_v2,
         cuGraphicsUnmapResources,
         cuGraphicsUnregisterResource,
+        cuImportExternalMemory,
+        cuExternalMemoryGetMappedBuffer,
+        cuExternalMemoryGetMappedMipmappedArray,
+        cuDestroyExternalMemory,
+        cuImportExternalSemaphore,
+        cuSignalExternalSemaphoresAsync,
+        cuSignalExternalSemaphoresAsync_ptsz,
+        cuWaitExternalSemaphoresAsync,
+        cuWaitExternalSemaphoresAsync_ptsz,
+        cuDestroyExternalSemaphore,
         cuLinkAddData_v2,
         cuLinkComplete,
         cuLinkDestroy,
@@ -238,6 +379,7 @@ mod definitions {
     use crate::r#impl::context;
     use crate::r#impl::dark_api;
     use crate::r#impl::device;
+    use crate::r#impl::external;
     use crate::r#impl::function;
     use crate::r#impl::gl;
     use crate::r#impl::graph;
@@ -246,13 +388,16 @@ mod definitions {
     use crate::r#impl::link;
     use crate::r#impl::memcpy2d_from_cuda;
     use crate::r#impl::memory;
+    use crate::r#impl::mempool;
     use crate::r#impl::module;
+    use crate::r#impl::peer;
     use crate::r#impl::pointer;
     use crate::r#impl::stream;
     use crate::r#impl::surface;
     use crate::r#impl::surfref;
     use crate::r#impl::texobj;
     use crate::r#impl::texref;
+    use crate::r#impl::vmm;
 
     pub(crate) unsafe fn cuGetErrorString(
         error: hipError_t,
@@ -305,6 +450,15 @@ mod definitions {
         hipDeviceCanAccessPeer(canAccessPeer, dev, peerDev)
     }
 
+    pub(crate) unsafe fn cuDeviceGetP2PAttribute(
+        value: *mut ::std::os::raw::c_int,
+        attrib: CUdevice_P2PAttribute,
+        srcDevice: hipDevice_t,
+        dstDevice: hipDevice_t,
+    ) -> Result<(), CUresult> {
+        peer::get_attribute(value, attrib, srcDevice, dstDevice)
+    }
+
     pub(crate) unsafe fn cuDeviceGet(
         device: *mut hipDevice_t,
         ordinal: ::std::os::raw::c_int,
@@ -317,10 +471,24 @@ mod definitions {
     }
 
     pub(crate) unsafe fn cuDeviceGetMemPool(
-        pool: *mut hipMemPool_t,
+        pool: *mut *mut mempool::MemoryPool,
         dev: hipDevice_t,
-    ) -> hipError_t {
-        hipDeviceGetMemPool(pool, dev)
+    ) -> Result<(), CUresult> {
+        mempool::get_current(pool, dev)
+    }
+
+    pub(crate) unsafe fn cuDeviceGetDefaultMemPool(
+        pool_out: *mut *mut mempool::MemoryPool,
+        dev: hipDevice_t,
+    ) -> Result<(), CUresult> {
+        mempool::get_default(pool_out, dev)
+    }
+
+    pub(crate) unsafe fn cuDeviceSetMemPool(
+        dev: hipDevice_t,
+        pool: *mut mempool::MemoryPool,
+    ) -> Result<(), CUresult> {
+        mempool::set_current(dev, pool)
     }
 
     pub(crate) unsafe fn cuDeviceGetName(
@@ -510,6 +678,19 @@ mod definitions {
         context::synchronize()
     }
 
+    pub(crate) unsafe fn cuCtxEnablePeerAccess(
+        peerContext: *mut context::Context,
+        Flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        peer::enable_access(peerContext, Flags)
+    }
+
+    pub(crate) unsafe fn cuCtxDisablePeerAccess(
+        peerContext: *mut context::Context,
+    ) -> Result<(), CUresult> {
+        peer::disable_access(peerContext)
+    }
+
     // TODO
     pub(crate) unsafe fn cuCtxSetCacheConfig(config: CUfunc_cache) -> CUresult {
         CUresult::CUDA_SUCCESS
@@ -562,6 +743,70 @@ mod definitions {
         library::unload(library)
     }
 
+    pub(crate) unsafe fn cuLibraryLoadFromFile(
+        library: *mut *mut library::Library,
+        fileName: *const ::std::os::raw::c_char,
+        jitOptions: *mut CUjit_option,
+        jitOptionsValues: *mut *mut ::std::os::raw::c_void,
+        numJitOptions: ::std::os::raw::c_uint,
+        libraryOptions: *mut CUlibraryOption,
+        libraryOptionValues: *mut *mut ::std::os::raw::c_void,
+        numLibraryOptions: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        library::load_from_file(
+            library,
+            fileName,
+            jitOptions,
+            jitOptionsValues,
+            numJitOptions,
+            libraryOptions,
+            libraryOptionValues,
+            numLibraryOptions,
+        )
+    }
+
+    pub(crate) unsafe fn cuLibraryGetKernel(
+        pKernel: *mut *mut library::Kernel,
+        library: *mut library::Library,
+        name: *const ::std::os::raw::c_char,
+    ) -> Result<(), CUresult> {
+        library::get_kernel(pKernel, library, name)
+    }
+
+    pub(crate) unsafe fn cuLibraryGetGlobal(
+        dptr: *mut hipDeviceptr_t,
+        bytes: *mut usize,
+        library: *mut library::Library,
+        name: *const ::std::os::raw::c_char,
+    ) -> Result<(), CUresult> {
+        library::get_global(dptr, bytes, library, name)
+    }
+
+    pub(crate) unsafe fn cuLibraryGetManaged(
+        dptr: *mut hipDeviceptr_t,
+        bytes: *mut usize,
+        library: *mut library::Library,
+        name: *const ::std::os::raw::c_char,
+    ) -> Result<(), CUresult> {
+        library::get_managed(dptr, bytes, library, name)
+    }
+
+    pub(crate) unsafe fn cuKernelGetFunction(
+        pFunc: *mut *mut function::Function,
+        kernel: *mut library::Kernel,
+    ) -> Result<(), CUresult> {
+        library::kernel_get_function(pFunc, kernel)
+    }
+
+    pub(crate) unsafe fn cuKernelGetAttribute(
+        pi: *mut ::std::os::raw::c_int,
+        attrib: hipFunction_attribute,
+        kernel: *mut library::Kernel,
+        dev: hipDevice_t,
+    ) -> Result<(), CUresult> {
+        library::kernel_get_attribute(pi, attrib, kernel, dev)
+    }
+
     pub(crate) unsafe fn cuModuleLoad(
         module: *mut *mut module::Module,
         fname: *const ::std::os::raw::c_char,
@@ -576,7 +821,6 @@ mod definitions {
         module::load_data(module, image)
     }
 
-    // TODO: parse jit options
     pub(crate) unsafe fn cuModuleLoadDataEx(
         module: *mut *mut module::Module,
         image: *const ::std::os::raw::c_void,
@@ -584,7 +828,14 @@ mod definitions {
         options: *mut CUjit_option,
         optionValues: *mut *mut ::std::os::raw::c_void,
     ) -> Result<(), CUresult> {
-        module::load_data(module, image)
+        module::load_data_ex(module, image, numOptions, options, optionValues)
+    }
+
+    pub(crate) unsafe fn cuModuleLoadFatBinary(
+        module: *mut *mut module::Module,
+        fatCubin: *const ::std::os::raw::c_void,
+    ) -> Result<(), CUresult> {
+        module::load_data(module, fatCubin)
     }
 
     pub(crate) unsafe fn cuModuleUnload(hmod: *mut module::Module) -> Result<(), CUresult> {
@@ -599,6 +850,21 @@ mod definitions {
         module::get_function(hfunc, hmod, name)
     }
 
+    pub(crate) unsafe fn cuModuleGetFunctionCount(
+        count: *mut ::std::os::raw::c_uint,
+        mod_: *mut module::Module,
+    ) -> Result<(), CUresult> {
+        module::get_function_count(count, mod_)
+    }
+
+    pub(crate) unsafe fn cuModuleEnumerateFunctions(
+        functions: *mut *mut function::Function,
+        numFunctions: ::std::os::raw::c_uint,
+        mod_: *mut module::Module,
+    ) -> Result<(), CUresult> {
+        module::enumerate_functions(functions, numFunctions, mod_)
+    }
+
     pub(crate) unsafe fn cuModuleGetGlobal_v2(
         dptr: *mut hipDeviceptr_t,
         bytes: *mut usize,
@@ -657,15 +923,56 @@ mod definitions {
         hipMallocPitch(dptr as _, ptr_pitch, width_in_bytes, height)
     }
 
-    pub(crate) unsafe fn cuMemFree_v2(dptr: hipDeviceptr_t) -> hipError_t {
-        hipFree(dptr.0)
+    pub(crate) unsafe fn cuMemFree_v2(dptr: hipDeviceptr_t) -> Result<(), CUresult> {
+        mempool::free(dptr)
     }
 
     pub(crate) unsafe fn cuMemFreeAsync(
         dptr: hipDeviceptr_t,
         hStream: *mut stream::Stream,
     ) -> Result<(), CUresult> {
-        memory::free_async(dptr, hStream)
+        mempool::free_async(dptr, hStream, false)
+    }
+
+    pub(crate) unsafe fn cuMemFreeAsync_ptsz(
+        dptr: hipDeviceptr_t,
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        mempool::free_async(dptr, hStream, true)
+    }
+
+    pub(crate) unsafe fn cuMemAllocAsync(
+        dptr: *mut hipDeviceptr_t,
+        bytesize: usize,
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        mempool::alloc_async(dptr, bytesize, hStream, false)
+    }
+
+    pub(crate) unsafe fn cuMemAllocAsync_ptsz(
+        dptr: *mut hipDeviceptr_t,
+        bytesize: usize,
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        mempool::alloc_async(dptr, bytesize, hStream, true)
+    }
+
+    pub(crate) unsafe fn cuMemAllocFromPoolAsync(
+        dptr: *mut hipDeviceptr_t,
+        bytesize: usize,
+        pool: *mut mempool::MemoryPool,
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        mempool::alloc_from_pool_async(dptr, bytesize, pool, hStream, false)
+    }
+
+    pub(crate) unsafe fn cuMemAllocFromPoolAsync_ptsz(
+        dptr: *mut hipDeviceptr_t,
+        bytesize: usize,
+        pool: *mut mempool::MemoryPool,
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        mempool::alloc_from_pool_async(dptr, bytesize, pool, hStream, true)
     }
 
     pub(crate) unsafe fn cuMemFreeHost(p: *mut ::std::os::raw::c_void) -> hipError_t {
@@ -708,12 +1015,87 @@ mod definitions {
         memory::get_address_range(pbase, psize, dptr)
     }
 
+    pub(crate) unsafe fn cuMemPoolCreate(
+        pool: *mut *mut mempool::MemoryPool,
+        poolProps: *const CUmemPoolProps,
+    ) -> Result<(), CUresult> {
+        mempool::create(pool, poolProps)
+    }
+
+    pub(crate) unsafe fn cuMemPoolDestroy(pool: *mut mempool::MemoryPool) -> Result<(), CUresult> {
+        mempool::destroy(pool)
+    }
+
+    pub(crate) unsafe fn cuMemPoolTrimTo(
+        pool: *mut mempool::MemoryPool,
+        minBytesToKeep: usize,
+    ) -> Result<(), CUresult> {
+        mempool::trim_to(pool, minBytesToKeep)
+    }
+
+    pub(crate) unsafe fn cuMemPoolGetAttribute(
+        pool: *mut mempool::MemoryPool,
+        attr: CUmemPool_attribute,
+        value: *mut ::std::os::raw::c_void,
+    ) -> Result<(), CUresult> {
+        mempool::get_attribute(pool, attr, value)
+    }
+
     pub(crate) unsafe fn cuMemPoolSetAttribute(
-        pool: hipMemPool_t,
-        attr: hipMemPoolAttr,
+        pool: *mut mempool::MemoryPool,
+        attr: CUmemPool_attribute,
         value: *mut ::std::os::raw::c_void,
-    ) -> hipError_t {
-        hipMemPoolGetAttribute(pool, attr, value)
+    ) -> Result<(), CUresult> {
+        mempool::set_attribute(pool, attr, value)
+    }
+
+    pub(crate) unsafe fn cuMemPoolSetAccess(
+        pool: *mut mempool::MemoryPool,
+        map: *const CUmemAccessDesc,
+        count: usize,
+    ) -> Result<(), CUresult> {
+        mempool::set_access(pool, map, count)
+    }
+
+    pub(crate) unsafe fn cuMemPoolGetAccess(
+        flags: *mut CUmemAccess_flags,
+        memPool: *mut mempool::MemoryPool,
+        location: *mut CUmemLocation,
+    ) -> Result<(), CUresult> {
+        mempool::get_access(flags, memPool, location)
+    }
+
+    pub(crate) unsafe fn cuMemPoolExportToShareableHandle(
+        handle_out: *mut ::std::os::raw::c_void,
+        pool: *mut mempool::MemoryPool,
+        handleType: CUmemAllocationHandleType,
+        flags: ::std::os::raw::c_ulonglong,
+    ) -> Result<(), CUresult> {
+        mempool::export_to_shareable_handle(handle_out, pool, handleType, flags)
+    }
+
+    pub(crate) unsafe fn cuMemPoolImportFromShareableHandle(
+        pool_out: *mut *mut mempool::MemoryPool,
+        handle: *mut ::std::os::raw::c_void,
+        handleType: CUmemAllocationHandleType,
+        flags: ::std::os::raw::c_ulonglong,
+    ) -> Result<(), CUresult> {
+        mempool::import_from_shareable_handle(pool_out, handle, handleType, flags)
+    }
+
+    pub(crate) unsafe fn cuMemPoolExportPointer(
+        shareData_out: *mut CUmemPoolPtrExportData,
+        ptr: hipDeviceptr_t,
+    ) -> Result<(), CUresult> {
+        mempool::export_pointer(shareData_out, ptr)
+    }
+
+    pub(crate) unsafe fn cuMemPoolImportPointer(
+        ptr_out: *mut hipDeviceptr_t,
+        pool: *mut mempool::MemoryPool,
+        shareData: *mut CUmemPoolPtrExportData,
+    ) -> Result<(), CUresult> {
+        mempool::import_pointer(ptr_out, pool, shareData)
     }
 
     pub(crate) unsafe fn cuMemPrefetchAsync(
@@ -725,6 +1107,45 @@ mod definitions {
         memory::prefetch_async(devPtr, count, dev, hStream)
     }
 
+    pub(crate) unsafe fn cuMemAdvise(
+        devPtr: hipDeviceptr_t,
+        count: usize,
+        advice: hipMemoryAdvise,
+        device: hipDevice_t,
+    ) -> Result<(), CUresult> {
+        memory::advise(devPtr, count, advice, device)
+    }
+
+    pub(crate) unsafe fn cuMemAdvise_v2(
+        devPtr: hipDeviceptr_t,
+        count: usize,
+        advice: hipMemoryAdvise,
+        location: CUmemLocation,
+    ) -> Result<(), CUresult> {
+        memory::advise_v2(devPtr, count, advice, location)
+    }
+
+    pub(crate) unsafe fn cuMemRangeGetAttribute(
+        data: *mut ::std::os::raw::c_void,
+        dataSize: usize,
+        attribute: CUmem_range_attribute,
+        devPtr: hipDeviceptr_t,
+        count: usize,
+    ) -> Result<(), CUresult> {
+        memory::range_get_attribute(data, dataSize, attribute, devPtr, count)
+    }
+
+    pub(crate) unsafe fn cuMemRangeGetAttributes(
+        data: *mut *mut ::std::os::raw::c_void,
+        dataSizes: *mut usize,
+        attributes: *mut CUmem_range_attribute,
+        numAttributes: usize,
+        devPtr: hipDeviceptr_t,
+        count: usize,
+    ) -> Result<(), CUresult> {
+        memory::range_get_attributes(data, dataSizes, attributes, numAttributes, devPtr, count)
+    }
+
     pub(crate) unsafe fn cuDeviceGetPCIBusId(
         pciBusId: *mut ::std::os::raw::c_char,
         len: ::std::os::raw::c_int,
@@ -897,6 +1318,40 @@ mod definitions {
         memory::copy3d_async(copy, hStream)
     }
 
+    pub(crate) unsafe fn cuMemcpyPeer(
+        dstDevice: hipDeviceptr_t,
+        dstContext: *mut context::Context,
+        srcDevice: hipDeviceptr_t,
+        srcContext: *mut context::Context,
+        ByteCount: usize,
+    ) -> Result<(), CUresult> {
+        peer::copy(dstDevice, dstContext, srcDevice, srcContext, ByteCount)
+    }
+
+    pub(crate) unsafe fn cuMemcpyPeerAsync(
+        dstDevice: hipDeviceptr_t,
+        dstContext: *mut context::Context,
+        srcDevice: hipDeviceptr_t,
+        srcContext: *mut context::Context,
+        ByteCount: usize,
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        peer::copy_async(
+            dstDevice, dstContext, srcDevice, srcContext, ByteCount, hStream,
+        )
+    }
+
+    pub(crate) unsafe fn cuMemcpy3DPeer(pCopy: *const CUDA_MEMCPY3D_PEER) -> Result<(), CUresult> {
+        peer::copy3d(pCopy)
+    }
+
+    pub(crate) unsafe fn cuMemcpy3DPeerAsync(
+        pCopy: *const CUDA_MEMCPY3D_PEER,
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        peer::copy3d_async(pCopy, hStream)
+    }
+
     pub(crate) unsafe fn cuMemsetD8_v2(
         dstDevice: hipDeviceptr_t,
         uc: ::std::os::raw::c_uchar,
@@ -988,6 +1443,106 @@ mod definitions {
         )
     }
 
+    pub(crate) unsafe fn cuMemAddressReserve(
+        ptr: *mut hipDeviceptr_t,
+        size: usize,
+        alignment: usize,
+        addr: hipDeviceptr_t,
+        flags: ::std::os::raw::c_ulonglong,
+    ) -> Result<(), CUresult> {
+        vmm::address_reserve(ptr, size, alignment, addr, flags)
+    }
+
+    pub(crate) unsafe fn cuMemAddressFree(
+        ptr: hipDeviceptr_t,
+        size: usize,
+    ) -> Result<(), CUresult> {
+        vmm::address_free(ptr, size)
+    }
+
+    pub(crate) unsafe fn cuMemCreate(
+        handle: *mut *mut vmm::Allocation,
+        size: usize,
+        prop: *const CUmemAllocationProp,
+        flags: ::std::os::raw::c_ulonglong,
+    ) -> Result<(), CUresult> {
+        vmm::create(handle, size, prop, flags)
+    }
+
+    pub(crate) unsafe fn cuMemRelease(handle: *mut vmm::Allocation) -> Result<(), CUresult> {
+        vmm::release(handle)
+    }
+
+    pub(crate) unsafe fn cuMemMap(
+        ptr: hipDeviceptr_t,
+        size: usize,
+        offset: usize,
+        handle: *mut vmm::Allocation,
+        flags: ::std::os::raw::c_ulonglong,
+    ) -> Result<(), CUresult> {
+        vmm::map(ptr, size, offset, handle, flags)
+    }
+
+    pub(crate) unsafe fn cuMemUnmap(ptr: hipDeviceptr_t, size: usize) -> Result<(), CUresult> {
+        vmm::unmap(ptr, size)
+    }
+
+    pub(crate) unsafe fn cuMemSetAccess(
+        ptr: hipDeviceptr_t,
+        size: usize,
+        desc: *const CUmemAccessDesc,
+        count: usize,
+    ) -> Result<(), CUresult> {
+        vmm::set_access(ptr, size, desc, count)
+    }
+
+    pub(crate) unsafe fn cuMemGetAccess(
+        flags: *mut ::std::os::raw::c_ulonglong,
+        location: *const CUmemLocation,
+        ptr: hipDeviceptr_t,
+    ) -> Result<(), CUresult> {
+        vmm::get_access(flags, location, ptr)
+    }
+
+    pub(crate) unsafe fn cuMemExportToShareableHandle(
+        shareableHandle: *mut ::std::os::raw::c_void,
+        handle: *mut vmm::Allocation,
+        handleType: CUmemAllocationHandleType,
+        flags: ::std::os::raw::c_ulonglong,
+    ) -> Result<(), CUresult> {
+        vmm::export_to_shareable_handle(shareableHandle, handle, handleType, flags)
+    }
+
+    pub(crate) unsafe fn cuMemImportFromShareableHandle(
+        handle: *mut *mut vmm::Allocation,
+        osHandle: *mut ::std::os::raw::c_void,
+        shHandleType: CUmemAllocationHandleType,
+    ) -> Result<(), CUresult> {
+        vmm::import_from_shareable_handle(handle, osHandle, shHandleType)
+    }
+
+    pub(crate) unsafe fn cuMemGetAllocationGranularity(
+        granularity: *mut usize,
+        prop: *const CUmemAllocationProp,
+        option: CUmemAllocationGranularity_flags,
+    ) -> Result<(), CUresult> {
+        vmm::get_allocation_granularity(granularity, prop, option)
+    }
+
+    pub(crate) unsafe fn cuMemGetAllocationPropertiesFromHandle(
+        prop: *mut CUmemAllocationProp,
+        handle: *mut vmm::Allocation,
+    ) -> Result<(), CUresult> {
+        vmm::get_allocation_properties_from_handle(prop, handle)
+    }
+
+    pub(crate) unsafe fn cuMemRetainAllocationHandle(
+        handle: *mut *mut vmm::Allocation,
+        addr: *mut ::std::os::raw::c_void,
+    ) -> Result<(), CUresult> {
+        vmm::retain_allocation_handle(handle, addr)
+    }
+
     pub(crate) unsafe fn cuOccupancyMaxPotentialBlockSize(
         minGridSize: *mut ::std::os::raw::c_int,
         blockSize: *mut ::std::os::raw::c_int,
@@ -1121,27 +1676,225 @@ mod definitions {
         stream::is_capturing(hStream, captureStatus)
     }
 
-    pub(crate) unsafe fn cuStreamQuery(hStream: *mut stream::Stream) -> Result<(), CUresult> {
-        stream::query(hStream)
+    pub(crate) unsafe fn cuStreamWriteValue32(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u32,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::write_value(stream, addr, stream::MemOpValue::U32(value), flags, false)
     }
 
-    pub(crate) unsafe fn cuStreamSynchronize(hStream: *mut stream::Stream) -> Result<(), CUresult> {
-        stream::synchronize(hStream, false)
+    pub(crate) unsafe fn cuStreamWriteValue64(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u64,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::write_value(stream, addr, stream::MemOpValue::U64(value), flags, false)
     }
 
-    pub(crate) unsafe fn cuStreamSynchronize_ptsz(
-        hStream: *mut stream::Stream,
+    pub(crate) unsafe fn cuStreamWaitValue32(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u32,
+        flags: ::std::os::raw::c_uint,
     ) -> Result<(), CUresult> {
-        stream::synchronize(hStream, true)
+        stream::wait_value(stream, addr, stream::MemOpValue::U32(value), flags, false)
     }
 
-    pub(crate) unsafe fn cuThreadExchangeStreamCaptureMode(
-        mode: *mut hipStreamCaptureMode,
+    pub(crate) unsafe fn cuStreamWaitValue64(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u64,
+        flags: ::std::os::raw::c_uint,
     ) -> Result<(), CUresult> {
-        stream::thread_exchange_capture_mode(mode)
+        stream::wait_value(stream, addr, stream::MemOpValue::U64(value), flags, false)
     }
 
-    pub(crate) unsafe fn cuStreamDestroy(hStream: *mut stream::Stream) -> Result<(), CUresult> {
+    pub(crate) unsafe fn cuStreamBatchMemOp(
+        stream: *mut stream::Stream,
+        count: ::std::os::raw::c_uint,
+        paramArray: *mut CUstreamBatchMemOpParams,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::batch_mem_op(stream, count, paramArray, flags, false)
+    }
+
+    pub(crate) unsafe fn cuStreamAttachMemAsync(
+        hStream: *mut stream::Stream,
+        dptr: hipDeviceptr_t,
+        length: usize,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::attach_mem_async(hStream, dptr, length, flags, false)
+    }
+
+    pub(crate) unsafe fn cuStreamAttachMemAsync_ptsz(
+        hStream: *mut stream::Stream,
+        dptr: hipDeviceptr_t,
+        length: usize,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::attach_mem_async(hStream, dptr, length, flags, true)
+    }
+
+    pub(crate) unsafe fn cuStreamWriteValue32_ptsz(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u32,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::write_value(stream, addr, stream::MemOpValue::U32(value), flags, true)
+    }
+
+    pub(crate) unsafe fn cuStreamWriteValue64_ptsz(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u64,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::write_value(stream, addr, stream::MemOpValue::U64(value), flags, true)
+    }
+
+    pub(crate) unsafe fn cuStreamWaitValue32_ptsz(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u32,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::wait_value(stream, addr, stream::MemOpValue::U32(value), flags, true)
+    }
+
+    pub(crate) unsafe fn cuStreamWaitValue64_ptsz(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u64,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::wait_value(stream, addr, stream::MemOpValue::U64(value), flags, true)
+    }
+
+    pub(crate) unsafe fn cuStreamBatchMemOp_ptsz(
+        stream: *mut stream::Stream,
+        count: ::std::os::raw::c_uint,
+        paramArray: *mut CUstreamBatchMemOpParams,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::batch_mem_op(stream, count, paramArray, flags, true)
+    }
+
+    pub(crate) unsafe fn cuStreamWriteValue32_v2(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u32,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::write_value(stream, addr, stream::MemOpValue::U32(value), flags, false)
+    }
+
+    pub(crate) unsafe fn cuStreamWriteValue64_v2(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u64,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::write_value(stream, addr, stream::MemOpValue::U64(value), flags, false)
+    }
+
+    pub(crate) unsafe fn cuStreamWaitValue32_v2(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u32,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::wait_value(stream, addr, stream::MemOpValue::U32(value), flags, false)
+    }
+
+    pub(crate) unsafe fn cuStreamWaitValue64_v2(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u64,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::wait_value(stream, addr, stream::MemOpValue::U64(value), flags, false)
+    }
+
+    pub(crate) unsafe fn cuStreamBatchMemOp_v2(
+        stream: *mut stream::Stream,
+        count: ::std::os::raw::c_uint,
+        paramArray: *mut CUstreamBatchMemOpParams,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::batch_mem_op(stream, count, paramArray, flags, false)
+    }
+
+    pub(crate) unsafe fn cuStreamWriteValue32_v2_ptsz(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u32,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::write_value(stream, addr, stream::MemOpValue::U32(value), flags, true)
+    }
+
+    pub(crate) unsafe fn cuStreamWriteValue64_v2_ptsz(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u64,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::write_value(stream, addr, stream::MemOpValue::U64(value), flags, true)
+    }
+
+    pub(crate) unsafe fn cuStreamWaitValue32_v2_ptsz(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u32,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::wait_value(stream, addr, stream::MemOpValue::U32(value), flags, true)
+    }
+
+    pub(crate) unsafe fn cuStreamWaitValue64_v2_ptsz(
+        stream: *mut stream::Stream,
+        addr: hipDeviceptr_t,
+        value: u64,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::wait_value(stream, addr, stream::MemOpValue::U64(value), flags, true)
+    }
+
+    pub(crate) unsafe fn cuStreamBatchMemOp_v2_ptsz(
+        stream: *mut stream::Stream,
+        count: ::std::os::raw::c_uint,
+        paramArray: *mut CUstreamBatchMemOpParams,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        stream::batch_mem_op(stream, count, paramArray, flags, true)
+    }
+
+    pub(crate) unsafe fn cuStreamQuery(hStream: *mut stream::Stream) -> Result<(), CUresult> {
+        stream::query(hStream)
+    }
+
+    pub(crate) unsafe fn cuStreamSynchronize(hStream: *mut stream::Stream) -> Result<(), CUresult> {
+        stream::synchronize(hStream, false)
+    }
+
+    pub(crate) unsafe fn cuStreamSynchronize_ptsz(
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        stream::synchronize(hStream, true)
+    }
+
+    pub(crate) unsafe fn cuThreadExchangeStreamCaptureMode(
+        mode: *mut hipStreamCaptureMode,
+    ) -> Result<(), CUresult> {
+        stream::thread_exchange_capture_mode(mode)
+    }
+
+    pub(crate) unsafe fn cuStreamDestroy(hStream: *mut stream::Stream) -> Result<(), CUresult> {
         stream::destroy(hStream)
     }
 
@@ -1173,6 +1926,13 @@ mod definitions {
         function::get_attribute(pi, attrib, func)
     }
 
+    pub(crate) unsafe fn cuFuncGetName(
+        name: *mut *const ::std::os::raw::c_char,
+        hfunc: *mut function::Function,
+    ) -> Result<(), CUresult> {
+        function::get_name(name, hfunc)
+    }
+
     pub(crate) unsafe fn cuFuncSetAttribute(
         func: *mut function::Function,
         attrib: hipFunction_attribute,
@@ -1247,6 +2007,86 @@ mod definitions {
         )
     }
 
+    pub(crate) unsafe fn cuLaunchKernelEx(
+        config: *const CUlaunchConfig,
+        f: *mut function::Function,
+        kernelParams: *mut *mut ::std::os::raw::c_void,
+        extra: *mut *mut ::std::os::raw::c_void,
+    ) -> Result<(), CUresult> {
+        function::launch_kernel_ex(config, f, kernelParams, extra, false)
+    }
+
+    pub(crate) unsafe fn cuLaunchKernelEx_ptsz(
+        config: *const CUlaunchConfig,
+        f: *mut function::Function,
+        kernelParams: *mut *mut ::std::os::raw::c_void,
+        extra: *mut *mut ::std::os::raw::c_void,
+    ) -> Result<(), CUresult> {
+        function::launch_kernel_ex(config, f, kernelParams, extra, true)
+    }
+
+    pub(crate) unsafe fn cuLaunchCooperativeKernel(
+        f: *mut function::Function,
+        gridDimX: ::std::os::raw::c_uint,
+        gridDimY: ::std::os::raw::c_uint,
+        gridDimZ: ::std::os::raw::c_uint,
+        blockDimX: ::std::os::raw::c_uint,
+        blockDimY: ::std::os::raw::c_uint,
+        blockDimZ: ::std::os::raw::c_uint,
+        sharedMemBytes: ::std::os::raw::c_uint,
+        hStream: *mut stream::Stream,
+        kernelParams: *mut *mut ::std::os::raw::c_void,
+    ) -> Result<(), CUresult> {
+        function::launch_cooperative_kernel(
+            f,
+            gridDimX,
+            gridDimY,
+            gridDimZ,
+            blockDimX,
+            blockDimY,
+            blockDimZ,
+            sharedMemBytes,
+            hStream,
+            kernelParams,
+            false,
+        )
+    }
+
+    pub(crate) unsafe fn cuLaunchCooperativeKernel_ptsz(
+        f: *mut function::Function,
+        gridDimX: ::std::os::raw::c_uint,
+        gridDimY: ::std::os::raw::c_uint,
+        gridDimZ: ::std::os::raw::c_uint,
+        blockDimX: ::std::os::raw::c_uint,
+        blockDimY: ::std::os::raw::c_uint,
+        blockDimZ: ::std::os::raw::c_uint,
+        sharedMemBytes: ::std::os::raw::c_uint,
+        hStream: *mut stream::Stream,
+        kernelParams: *mut *mut ::std::os::raw::c_void,
+    ) -> Result<(), CUresult> {
+        function::launch_cooperative_kernel(
+            f,
+            gridDimX,
+            gridDimY,
+            gridDimZ,
+            blockDimX,
+            blockDimY,
+            blockDimZ,
+            sharedMemBytes,
+            hStream,
+            kernelParams,
+            true,
+        )
+    }
+
+    pub(crate) unsafe fn cuLaunchCooperativeKernelMultiDevice(
+        launchParamsList: *mut CUDA_LAUNCH_PARAMS,
+        numDevices: ::std::os::raw::c_uint,
+        flags: ::std::os::raw::c_uint,
+    ) -> Result<(), CUresult> {
+        function::launch_cooperative_kernel_multi_device(launchParamsList, numDevices, flags)
+    }
+
     pub(crate) unsafe fn cuMemHostGetDevicePointer_v2(
         pdptr: *mut hipDeviceptr_t,
         p: *mut ::std::os::raw::c_void,
@@ -1467,7 +2307,13 @@ mod definitions {
         phEvent: *mut hipEvent_t,
         Flags: ::std::os::raw::c_uint,
     ) -> hipError_t {
-        hipEventCreate(phEvent)
+        // Other flags are ignored, but interprocess events can't be created
+        // without them. CUDA and HIP event flags have the same values
+        if Flags & hipEventInterprocess != 0 {
+            hipEventCreateWithFlags(phEvent, Flags)
+        } else {
+            hipEventCreate(phEvent)
+        }
     }
 
     pub(crate) unsafe fn cuEventDestroy(event: hipEvent_t) -> hipError_t {
@@ -1508,8 +2354,50 @@ mod definitions {
         Ok(())
     }
 
-    pub(crate) unsafe fn cuEventSynchronize(event: hipEvent_t) -> hipError_t {
-        hipEventSynchronize(event)
+    pub(crate) unsafe fn cuEventSynchronize(event: hipEvent_t) -> Result<(), CUresult> {
+        hip_call_cuda!(hipEventSynchronize(event));
+        mempool::release_unused()
+    }
+
+    pub(crate) unsafe fn cuIpcGetMemHandle(
+        pHandle: *mut hipIpcMemHandle_t,
+        dptr: hipDeviceptr_t,
+    ) -> hipError_t {
+        hipIpcGetMemHandle(pHandle, dptr.0)
+    }
+
+    pub(crate) unsafe fn cuIpcOpenMemHandle(
+        pdptr: *mut hipDeviceptr_t,
+        handle: hipIpcMemHandle_t,
+        Flags: ::std::os::raw::c_uint,
+    ) -> hipError_t {
+        cuIpcOpenMemHandle_v2(pdptr, handle, Flags)
+    }
+
+    pub(crate) unsafe fn cuIpcOpenMemHandle_v2(
+        pdptr: *mut hipDeviceptr_t,
+        handle: hipIpcMemHandle_t,
+        Flags: ::std::os::raw::c_uint,
+    ) -> hipError_t {
+        hipIpcOpenMemHandle(pdptr.cast(), handle, Flags)
+    }
+
+    pub(crate) unsafe fn cuIpcCloseMemHandle(dptr: hipDeviceptr_t) -> hipError_t {
+        hipIpcCloseMemHandle(dptr.0)
+    }
+
+    pub(crate) unsafe fn cuIpcGetEventHandle(
+        pHandle: *mut hipIpcEventHandle_t,
+        event: hipEvent_t,
+    ) -> hipError_t {
+        hipIpcGetEventHandle(pHandle, event)
+    }
+
+    pub(crate) unsafe fn cuIpcOpenEventHandle(
+        phEvent: *mut hipEvent_t,
+        handle: hipIpcEventHandle_t,
+    ) -> hipError_t {
+        hipIpcOpenEventHandle(phEvent, handle)
     }
 
     pub(crate) unsafe fn cuGraphGetNodes(
@@ -1604,6 +2492,419 @@ mod definitions {
         graph::launch(hGraph, hStream)
     }
 
+    pub(crate) unsafe fn cuGraphAddKernelNode_v2(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        nodeParams: *const CUDA_KERNEL_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::add_kernel_node_v2(
+            phGraphNode,
+            hGraph,
+            dependencies,
+            numDependencies,
+            nodeParams,
+        )
+    }
+
+    pub(crate) unsafe fn cuGraphKernelNodeGetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *mut CUDA_KERNEL_NODE_PARAMS_v1,
+    ) -> Result<(), CUresult> {
+        graph::kernel_node_get_params(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphKernelNodeGetParams_v2(
+        hNode: hipGraphNode_t,
+        nodeParams: *mut CUDA_KERNEL_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::kernel_node_get_params_v2(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphKernelNodeSetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *const CUDA_KERNEL_NODE_PARAMS_v1,
+    ) -> Result<(), CUresult> {
+        graph::kernel_node_set_params(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphKernelNodeSetParams_v2(
+        hNode: hipGraphNode_t,
+        nodeParams: *const CUDA_KERNEL_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::kernel_node_set_params_v2(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphExecKernelNodeSetParams(
+        hGraphExec: hipGraphExec_t,
+        hNode: hipGraphNode_t,
+        nodeParams: *const CUDA_KERNEL_NODE_PARAMS_v1,
+    ) -> Result<(), CUresult> {
+        graph::exec_kernel_node_set_params(hGraphExec, hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphExecKernelNodeSetParams_v2(
+        hGraphExec: hipGraphExec_t,
+        hNode: hipGraphNode_t,
+        nodeParams: *const CUDA_KERNEL_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::exec_kernel_node_set_params_v2(hGraphExec, hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphAddMemcpyNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        copyParams: *const CUDA_MEMCPY3D,
+        _ctx: *mut context::Context,
+    ) -> Result<(), CUresult> {
+        graph::add_memcpy_node(
+            phGraphNode,
+            hGraph,
+            dependencies,
+            numDependencies,
+            copyParams,
+        )
+    }
+
+    pub(crate) unsafe fn cuGraphMemcpyNodeGetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *mut CUDA_MEMCPY3D,
+    ) -> Result<(), CUresult> {
+        graph::memcpy_node_get_params(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphMemcpyNodeSetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *const CUDA_MEMCPY3D,
+    ) -> Result<(), CUresult> {
+        graph::memcpy_node_set_params(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphExecMemcpyNodeSetParams(
+        hGraphExec: hipGraphExec_t,
+        hNode: hipGraphNode_t,
+        copyParams: *const CUDA_MEMCPY3D,
+        _ctx: *mut context::Context,
+    ) -> Result<(), CUresult> {
+        graph::exec_memcpy_node_set_params(hGraphExec, hNode, copyParams)
+    }
+
+    pub(crate) unsafe fn cuGraphAddMemsetNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        memsetParams: *const CUDA_MEMSET_NODE_PARAMS,
+        _ctx: *mut context::Context,
+    ) -> Result<(), CUresult> {
+        graph::add_memset_node(
+            phGraphNode,
+            hGraph,
+            dependencies,
+            numDependencies,
+            memsetParams,
+        )
+    }
+
+    pub(crate) unsafe fn cuGraphMemsetNodeGetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *mut CUDA_MEMSET_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::memset_node_get_params(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphMemsetNodeSetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *const CUDA_MEMSET_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::memset_node_set_params(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphExecMemsetNodeSetParams(
+        hGraphExec: hipGraphExec_t,
+        hNode: hipGraphNode_t,
+        memsetParams: *const CUDA_MEMSET_NODE_PARAMS,
+        _ctx: *mut context::Context,
+    ) -> Result<(), CUresult> {
+        graph::exec_memset_node_set_params(hGraphExec, hNode, memsetParams)
+    }
+
+    pub(crate) unsafe fn cuGraphAddHostNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        nodeParams: *const CUDA_HOST_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::add_host_node(
+            phGraphNode,
+            hGraph,
+            dependencies,
+            numDependencies,
+            nodeParams,
+        )
+    }
+
+    pub(crate) unsafe fn cuGraphHostNodeGetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *mut CUDA_HOST_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::host_node_get_params(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphHostNodeSetParams(
+        hNode: hipGraphNode_t,
+        nodeParams: *const CUDA_HOST_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::host_node_set_params(hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphExecHostNodeSetParams(
+        hGraphExec: hipGraphExec_t,
+        hNode: hipGraphNode_t,
+        nodeParams: *const CUDA_HOST_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::exec_host_node_set_params(hGraphExec, hNode, nodeParams)
+    }
+
+    pub(crate) unsafe fn cuGraphAddChildGraphNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        childGraph: hipGraph_t,
+    ) -> hipError_t {
+        hipGraphAddChildGraphNode(
+            phGraphNode,
+            hGraph,
+            dependencies,
+            numDependencies,
+            childGraph,
+        )
+    }
+
+    pub(crate) unsafe fn cuGraphChildGraphNodeGetGraph(
+        hNode: hipGraphNode_t,
+        phGraph: *mut hipGraph_t,
+    ) -> hipError_t {
+        hipGraphChildGraphNodeGetGraph(hNode, phGraph)
+    }
+
+    pub(crate) unsafe fn cuGraphExecChildGraphNodeSetParams(
+        hGraphExec: hipGraphExec_t,
+        hNode: hipGraphNode_t,
+        childGraph: hipGraph_t,
+    ) -> hipError_t {
+        hipGraphExecChildGraphNodeSetParams(hGraphExec, hNode, childGraph)
+    }
+
+    pub(crate) unsafe fn cuGraphAddEventRecordNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        event: hipEvent_t,
+    ) -> hipError_t {
+        hipGraphAddEventRecordNode(phGraphNode, hGraph, dependencies, numDependencies, event)
+    }
+
+    pub(crate) unsafe fn cuGraphEventRecordNodeGetEvent(
+        hNode: hipGraphNode_t,
+        event_out: *mut hipEvent_t,
+    ) -> hipError_t {
+        hipGraphEventRecordNodeGetEvent(hNode, event_out)
+    }
+
+    pub(crate) unsafe fn cuGraphEventRecordNodeSetEvent(
+        hNode: hipGraphNode_t,
+        event: hipEvent_t,
+    ) -> hipError_t {
+        hipGraphEventRecordNodeSetEvent(hNode, event)
+    }
+
+    pub(crate) unsafe fn cuGraphExecEventRecordNodeSetEvent(
+        hGraphExec: hipGraphExec_t,
+        hNode: hipGraphNode_t,
+        event: hipEvent_t,
+    ) -> hipError_t {
+        hipGraphExecEventRecordNodeSetEvent(hGraphExec, hNode, event)
+    }
+
+    pub(crate) unsafe fn cuGraphAddEventWaitNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        event: hipEvent_t,
+    ) -> hipError_t {
+        hipGraphAddEventWaitNode(phGraphNode, hGraph, dependencies, numDependencies, event)
+    }
+
+    pub(crate) unsafe fn cuGraphEventWaitNodeGetEvent(
+        hNode: hipGraphNode_t,
+        event_out: *mut hipEvent_t,
+    ) -> hipError_t {
+        hipGraphEventWaitNodeGetEvent(hNode, event_out)
+    }
+
+    pub(crate) unsafe fn cuGraphEventWaitNodeSetEvent(
+        hNode: hipGraphNode_t,
+        event: hipEvent_t,
+    ) -> hipError_t {
+        hipGraphEventWaitNodeSetEvent(hNode, event)
+    }
+
+    pub(crate) unsafe fn cuGraphExecEventWaitNodeSetEvent(
+        hGraphExec: hipGraphExec_t,
+        hNode: hipGraphNode_t,
+        event: hipEvent_t,
+    ) -> hipError_t {
+        hipGraphExecEventWaitNodeSetEvent(hGraphExec, hNode, event)
+    }
+
+    pub(crate) unsafe fn cuGraphAddMemAllocNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        nodeParams: *mut CUDA_MEM_ALLOC_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::add_mem_alloc_node(
+            phGraphNode,
+            hGraph,
+            dependencies,
+            numDependencies,
+            nodeParams,
+        )
+    }
+
+    pub(crate) unsafe fn cuGraphMemAllocNodeGetParams(
+        hNode: hipGraphNode_t,
+        params_out: *mut CUDA_MEM_ALLOC_NODE_PARAMS,
+    ) -> Result<(), CUresult> {
+        graph::mem_alloc_node_get_params(hNode, params_out)
+    }
+
+    pub(crate) unsafe fn cuGraphAddMemFreeNode(
+        phGraphNode: *mut hipGraphNode_t,
+        hGraph: hipGraph_t,
+        dependencies: *const hipGraphNode_t,
+        numDependencies: usize,
+        dptr: hipDeviceptr_t,
+    ) -> hipError_t {
+        hipGraphAddMemFreeNode(phGraphNode, hGraph, dependencies, numDependencies, dptr.0)
+    }
+
+    pub(crate) unsafe fn cuGraphMemFreeNodeGetParams(
+        hNode: hipGraphNode_t,
+        dptr_out: *mut hipDeviceptr_t,
+    ) -> Result<(), CUresult> {
+        graph::mem_free_node_get_params(hNode, dptr_out)
+    }
+
+    pub(crate) unsafe fn cuGraphClone(
+        phGraphClone: *mut hipGraph_t,
+        originalGraph: hipGraph_t,
+    ) -> hipError_t {
+        hipGraphClone(phGraphClone, originalGraph)
+    }
+
+    pub(crate) unsafe fn cuGraphNodeFindInClone(
+        phNode: *mut hipGraphNode_t,
+        hOriginalNode: hipGraphNode_t,
+        hClonedGraph: hipGraph_t,
+    ) -> hipError_t {
+        hipGraphNodeFindInClone(phNode, hOriginalNode, hClonedGraph)
+    }
+
+    pub(crate) unsafe fn cuGraphNodeGetType(
+        hNode: hipGraphNode_t,
+        type_: *mut CUgraphNodeType,
+    ) -> Result<(), CUresult> {
+        graph::node_get_type(hNode, type_)
+    }
+
+    pub(crate) unsafe fn cuGraphGetRootNodes(
+        hGraph: hipGraph_t,
+        rootNodes: *mut hipGraphNode_t,
+        numRootNodes: *mut usize,
+    ) -> hipError_t {
+        hipGraphGetRootNodes(hGraph, rootNodes, numRootNodes)
+    }
+
+    pub(crate) unsafe fn cuGraphGetEdges(
+        hGraph: hipGraph_t,
+        from: *mut hipGraphNode_t,
+        to: *mut hipGraphNode_t,
+        numEdges: *mut usize,
+    ) -> hipError_t {
+        hipGraphGetEdges(hGraph, from, to, numEdges)
+    }
+
+    pub(crate) unsafe fn cuGraphNodeGetDependencies(
+        hNode: hipGraphNode_t,
+        dependencies: *mut hipGraphNode_t,
+        numDependencies: *mut usize,
+    ) -> hipError_t {
+        hipGraphNodeGetDependencies(hNode, dependencies, numDependencies)
+    }
+
+    pub(crate) unsafe fn cuGraphNodeGetDependentNodes(
+        hNode: hipGraphNode_t,
+        dependentNodes: *mut hipGraphNode_t,
+        numDependentNodes: *mut usize,
+    ) -> hipError_t {
+        hipGraphNodeGetDependentNodes(hNode, dependentNodes, numDependentNodes)
+    }
+
+    pub(crate) unsafe fn cuGraphRemoveDependencies(
+        hGraph: hipGraph_t,
+        from: *const hipGraphNode_t,
+        to: *const hipGraphNode_t,
+        numDependencies: usize,
+    ) -> hipError_t {
+        hipGraphRemoveDependencies(hGraph, from, to, numDependencies)
+    }
+
+    pub(crate) unsafe fn cuGraphDestroyNode(hNode: hipGraphNode_t) -> hipError_t {
+        hipGraphDestroyNode(hNode)
+    }
+
+    pub(crate) unsafe fn cuGraphExecUpdate(
+        hGraphExec: hipGraphExec_t,
+        hGraph: hipGraph_t,
+        hErrorNode_out: *mut hipGraphNode_t,
+        updateResult_out: *mut hipGraphExecUpdateResult,
+    ) -> Result<(), CUresult> {
+        graph::exec_update(hGraphExec, hGraph, hErrorNode_out, updateResult_out)
+    }
+
+    pub(crate) unsafe fn cuGraphExecUpdate_v2(
+        hGraphExec: hipGraphExec_t,
+        hGraph: hipGraph_t,
+        resultInfo: *mut CUgraphExecUpdateResultInfo,
+    ) -> Result<(), CUresult> {
+        graph::exec_update_v2(hGraphExec, hGraph, resultInfo)
+    }
+
+    pub(crate) unsafe fn cuGraphUpload(
+        hGraph: hipGraphExec_t,
+        hStream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        graph::upload(hGraph, hStream)
+    }
+
+    pub(crate) unsafe fn cuGraphDebugDotPrint(
+        hGraph: hipGraph_t,
+        path: *const ::std::os::raw::c_char,
+        flags: ::std::os::raw::c_uint,
+    ) -> hipError_t {
+        hipGraphDebugDotPrint(hGraph, path, flags)
+    }
+
     pub(crate) unsafe fn cuGraphicsSubResourceGetMappedArray(
         pArray: *mut CUarray,
         resource: hipGraphicsResource_t,
@@ -1660,6 +2961,80 @@ mod definitions {
         hipGraphicsUnregisterResource(resource)
     }
 
+    pub(crate) unsafe fn cuImportExternalMemory(
+        extMem_out: *mut hipExternalMemory_t,
+        memHandleDesc: *const CUDA_EXTERNAL_MEMORY_HANDLE_DESC,
+    ) -> Result<(), CUresult> {
+        external::import_memory(extMem_out, memHandleDesc)
+    }
+
+    pub(crate) unsafe fn cuExternalMemoryGetMappedBuffer(
+        devPtr: *mut hipDeviceptr_t,
+        extMem: hipExternalMemory_t,
+        bufferDesc: *const CUDA_EXTERNAL_MEMORY_BUFFER_DESC,
+    ) -> Result<(), CUresult> {
+        external::get_mapped_buffer(devPtr, extMem, bufferDesc)
+    }
+
+    pub(crate) unsafe fn cuExternalMemoryGetMappedMipmappedArray(
+        mipmap: *mut CUmipmappedArray,
+        extMem: hipExternalMemory_t,
+        mipmapDesc: *const CUDA_EXTERNAL_MEMORY_MIPMAPPED_ARRAY_DESC,
+    ) -> Result<(), CUresult> {
+        external::get_mapped_mipmapped_array(mipmap, extMem, mipmapDesc)
+    }
+
+    pub(crate) unsafe fn cuDestroyExternalMemory(extMem: hipExternalMemory_t) -> hipError_t {
+        external::destroy_memory(extMem)
+    }
+
+    pub(crate) unsafe fn cuImportExternalSemaphore(
+        extSem_out: *mut hipExternalSemaphore_t,
+        semHandleDesc: *const CUDA_EXTERNAL_SEMAPHORE_HANDLE_DESC,
+    ) -> Result<(), CUresult> {
+        external::import_semaphore(extSem_out, semHandleDesc)
+    }
+
+    pub(crate) unsafe fn cuSignalExternalSemaphoresAsync(
+        extSemArray: *const hipExternalSemaphore_t,
+        paramsArray: *const CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS,
+        numExtSems: ::std::os::raw::c_uint,
+        stream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        external::signal_semaphores_async(extSemArray, paramsArray, numExtSems, stream, false)
+    }
+
+    pub(crate) unsafe fn cuSignalExternalSemaphoresAsync_ptsz(
+        extSemArray: *const hipExternalSemaphore_t,
+        paramsArray: *const CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS,
+        numExtSems: ::std::os::raw::c_uint,
+        stream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        external::signal_semaphores_async(extSemArray, paramsArray, numExtSems, stream, true)
+    }
+
+    pub(crate) unsafe fn cuWaitExternalSemaphoresAsync(
+        extSemArray: *const hipExternalSemaphore_t,
+        paramsArray: *const CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS,
+        numExtSems: ::std::os::raw::c_uint,
+        stream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        external::wait_semaphores_async(extSemArray, paramsArray, numExtSems, stream, false)
+    }
+
+    pub(crate) unsafe fn cuWaitExternalSemaphoresAsync_ptsz(
+        extSemArray: *const hipExternalSemaphore_t,
+        paramsArray: *const CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS,
+        numExtSems: ::std::os::raw::c_uint,
+        stream: *mut stream::Stream,
+    ) -> Result<(), CUresult> {
+        external::wait_semaphores_async(extSemArray, paramsArray, numExtSems, stream, true)
+    }
+
+    pub(crate) unsafe fn cuDestroyExternalSemaphore(extSem: hipExternalSemaphore_t) -> hipError_t {
+        external::destroy_semaphore(extSem)
+    }
+
     pub(crate) unsafe fn cuLinkAddData_v2(
         state: *mut link::LinkState,
         type_: CUjitInputType,
@@ -1,20 +1,39 @@
 use hip_common::{
-    cache::{KernelExtendedData, KernelRepository},
+    cache::{CacheSettings, KernelExtendedData, KernelKey, KernelRepository},
     unwrap_or_return, CompilationMode,
 };
 use static_assertions::assert_impl_one;
-use std::{borrow::Cow, ffi::CStr, path::Path};
+use std::{borrow::Cow, ffi::CStr, fs};
 
-pub(crate) struct KernelCache(KernelRepository<NoExtendedData>);
+pub(crate) struct KernelCache {
+    // Read-only if ZLUDA_CACHE_READ_ONLY is set
+    main: Option<KernelRepository<NoExtendedData>>,
+    // Always read-only, used only if the kernel is not in the main cache
+    shared: Option<KernelRepository<NoExtendedData>>,
+}
 assert_impl_one!(KernelCache: Sync);
 
 impl KernelCache {
-    pub(crate) const CACHE_FILE: &'static str = "zluda.db";
-
-    pub(crate) fn new(cache_dir: &Path) -> Option<Self> {
-        let mut file = cache_dir.to_path_buf();
-        file.push(Self::CACHE_FILE);
-        Some(Self(KernelRepository::new(Some(file)).ok()?))
+    pub(crate) fn new(settings: &CacheSettings) -> Option<Self> {
+        let main = settings.cache_file().and_then(|file| {
+            if settings.read_only {
+                KernelRepository::new_read_only(file).ok()
+            } else {
+                fs::create_dir_all(file.parent()?).ok()?;
+                Some(
+                    KernelRepository::new(Some(file))
+                        .ok()?
+                        .with_max_size(settings.max_size),
+                )
+            }
+        });
+        let shared = settings
+            .shared_cache_file()
+            .and_then(|file| KernelRepository::new_read_only(file).ok());
+        if main.is_none() && shared.is_none() {
+            return None;
+        }
+        Some(Self { main, shared })
     }
 
     pub(crate) fn save_program(
@@ -32,16 +51,17 @@ impl KernelCache {
         }
         let hash = hasher.finalize().to_hex();
         let git_hash = env!("VERGEN_GIT_SHA");
-        self.0
-            .save_program(
-                now,
-                hash.as_str(),
-                compiler_version,
-                git_hash,
-                device,
-                binary,
-                rusqlite::params![compilation_mode as u8],
-            )
+        let main = match self.main {
+            Some(ref main) => main,
+            None => return,
+        };
+        let key = KernelKey {
+            hash: hash.as_str(),
+            compiler_version,
+            git_hash,
+            device,
+        };
+        main.save_program(now, &key, binary, rusqlite::params![compilation_mode as u8])
             .ok();
     }
 
@@ -58,20 +78,21 @@ impl KernelCache {
             hasher.update(module.as_bytes());
         }
         let hash = hasher.finalize().to_hex();
-        let git_hash = env!("VERGEN_GIT_SHA");
-        Some(
-            self.0
-                .try_load_program(
-                    now,
-                    hash.as_str(),
-                    compiler_version,
-                    git_hash,
-                    device,
-                    rusqlite::params![compilation_mode as u8],
-                )
-                .ok()
-                .flatten()?,
-        )
+        let key = KernelKey {
+            hash: hash.as_str(),
+            compiler_version,
+            git_hash: env!("VERGEN_GIT_SHA"),
+            device,
+        };
+        self.main
+            .iter()
+            .chain(self.shared.iter())
+            .find_map(|repository| {
+                repository
+                    .try_load_program(now, &key, rusqlite::params![compilation_mode as u8])
+                    .ok()
+                    .flatten()
+            })
     }
 }
 
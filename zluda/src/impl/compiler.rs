@@ -0,0 +1,96 @@
+// Thread pools used for compiling PTX. Kernels of a single module and
+// candidate PTX files of a fatbin are compiled on scoped worker threads,
+// modules registered from fatbins can be additionally compiled ahead of time
+// on a background thread
+use std::cmp;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+type BackgroundJob = Box<dyn FnOnce() + Send>;
+
+static BACKGROUND_QUEUE: Mutex<Option<mpsc::Sender<BackgroundJob>>> = Mutex::new(None);
+
+// ZLUDA_COMPILER_THREADS=N limits the number of threads used for compiling a
+// single module, by default we use all cores
+pub(crate) fn thread_count() -> usize {
+    match std::env::var("ZLUDA_COMPILER_THREADS")
+        .ok()
+        .and_then(|value| str::parse::<usize>(&value).ok())
+    {
+        Some(count) if count > 0 => count,
+        _ => thread::available_parallelism().map_or(1, |count| count.get()),
+    }
+}
+
+// Calls `f` for every input on a pool of worker threads. Results are returned
+// in the same order as inputs
+pub(crate) fn map_parallel<T: Send, R: Send>(inputs: Vec<T>, f: impl Fn(T) -> R + Sync) -> Vec<R> {
+    let threads = cmp::min(thread_count(), inputs.len());
+    if threads <= 1 {
+        return inputs.into_iter().map(f).collect();
+    }
+    let inputs = Mutex::new(inputs.into_iter().enumerate());
+    let mut results = thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let next = match inputs.lock() {
+                            Ok(mut inputs) => inputs.next(),
+                            Err(_) => None,
+                        };
+                        match next {
+                            Some((index, input)) => results.push((index, f(input))),
+                            None => break results,
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .flat_map(|worker| match worker.join() {
+                Ok(results) => results,
+                Err(panic) => std::panic::resume_unwind(panic),
+            })
+            .collect::<Vec<_>>()
+    });
+    results.sort_unstable_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+// Jobs are executed one by one on a single background thread, which is
+// started with the first job. Jobs still in the queue when the application
+// exits are never executed
+pub(crate) fn run_in_background(job: impl FnOnce() + Send + 'static) {
+    let mut queue = match BACKGROUND_QUEUE.lock() {
+        Ok(queue) => queue,
+        Err(_) => return,
+    };
+    let sender = queue.get_or_insert_with(|| {
+        let (sender, receiver) = mpsc::channel::<BackgroundJob>();
+        thread::Builder::new()
+            .name("zluda_compiler".to_string())
+            .spawn(move || {
+                for job in receiver {
+                    job();
+                }
+            })
+            .ok();
+        sender
+    });
+    sender.send(Box::new(job)).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::map_parallel;
+
+    #[test]
+    fn map_parallel_keeps_order() {
+        let inputs = (0..100u32).collect::<Vec<_>>();
+        let results = map_parallel(inputs, |x| x * 2);
+        assert_eq!(results, (0..100u32).map(|x| x * 2).collect::<Vec<_>>());
+    }
+}
@@ -1,7 +1,7 @@
 // HIP does not implement context APIs:
 // https://rocmdocs.amd.com/en/latest/Programming_Guides/HIP_API_Guide.html#hip-context-management-apis
 
-use super::{fold_cuda_errors, module, stream, LiveCheck, ZludaObject};
+use super::{fold_cuda_errors, mempool, module, peer, stream, LiveCheck, ZludaObject};
 use crate::hip_call_cuda;
 use cuda_types::*;
 use hip_runtime_sys::*;
@@ -106,6 +106,8 @@ impl ContextData {
 pub(crate) struct ContextInnerMutable {
     pub(crate) streams: FxHashSet<*mut stream::Stream>,
     pub(crate) modules: FxHashSet<*mut module::Module>,
+    // Fallback kernels for stream memory operations, loaded on first use
+    pub(crate) mem_ops_module: Option<*mut module::Module>,
     // Field below is here to support CUDA Driver Dark API
     pub(crate) local_storage: FxHashMap<*mut c_void, LocalStorageValue>,
 }
@@ -115,6 +117,7 @@ impl ContextInnerMutable {
         ContextInnerMutable {
             streams: FxHashSet::default(),
             modules: FxHashSet::default(),
+            mem_ops_module: None,
             local_storage: FxHashMap::default(),
         }
     }
@@ -166,6 +169,7 @@ pub(crate) unsafe fn destroy(ctx: *mut Context) -> Result<(), CUresult> {
         }
         Ok(())
     })?;
+    peer::remove_context(ctx);
     LiveCheck::drop_box_with_result(ctx, false)
 }
 
@@ -251,7 +255,7 @@ pub(crate) unsafe fn synchronize() -> Result<(), CUresult> {
     // We currently do this to sync with default stream which syncs whole device anyway,
     // figure out if we can do something smarter here
     hip_call_cuda!(hipDeviceSynchronize());
-    Ok(())
+    mempool::release_unused()
 }
 
 pub(crate) fn with_current<T>(f: impl FnOnce(&ContextData) -> T) -> Result<T, CUresult> {
@@ -0,0 +1,320 @@
+use super::{hipfix, stream};
+use crate::hip_call_cuda;
+use cuda_types::*;
+use hip_runtime_sys::*;
+use std::{mem, ptr};
+
+// On Linux ROCm backs opaque file descriptors with DMA-BUF, so both Vulkan
+// opaque FD exports and raw DMA-BUF file descriptors go through this path
+#[cfg(not(windows))]
+fn memory_handle_type_from_cuda(
+    type_: CUexternalMemoryHandleType,
+) -> Result<hipExternalMemoryHandleType, CUresult> {
+    match type_ {
+        CUexternalMemoryHandleType::CU_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD => {
+            Ok(hipExternalMemoryHandleType::hipExternalMemoryHandleTypeOpaqueFd)
+        }
+        CUexternalMemoryHandleType(0) => Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+        _ => Err(CUresult::CUDA_ERROR_NOT_SUPPORTED),
+    }
+}
+
+#[cfg(windows)]
+fn memory_handle_type_from_cuda(
+    type_: CUexternalMemoryHandleType,
+) -> Result<hipExternalMemoryHandleType, CUresult> {
+    match type_ {
+        CUexternalMemoryHandleType::CU_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_WIN32 => {
+            Ok(hipExternalMemoryHandleType::hipExternalMemoryHandleTypeOpaqueWin32)
+        }
+        CUexternalMemoryHandleType::CU_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_WIN32_KMT => {
+            Ok(hipExternalMemoryHandleType::hipExternalMemoryHandleTypeOpaqueWin32Kmt)
+        }
+        CUexternalMemoryHandleType(0) => Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+        _ => Err(CUresult::CUDA_ERROR_NOT_SUPPORTED),
+    }
+}
+
+#[cfg(not(windows))]
+fn semaphore_handle_type_from_cuda(
+    type_: CUexternalSemaphoreHandleType,
+) -> Result<hipExternalSemaphoreHandleType, CUresult> {
+    match type_ {
+        CUexternalSemaphoreHandleType::CU_EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_FD => {
+            Ok(hipExternalSemaphoreHandleType::hipExternalSemaphoreHandleTypeOpaqueFd)
+        }
+        #[cfg(not(feature = "rocm5"))]
+        CUexternalSemaphoreHandleType::CU_EXTERNAL_SEMAPHORE_HANDLE_TYPE_TIMELINE_SEMAPHORE_FD => {
+            Ok(hipExternalSemaphoreHandleType::hipExternalSemaphoreHandleTypeTimelineSemaphoreFd)
+        }
+        CUexternalSemaphoreHandleType(0) => Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+        _ => Err(CUresult::CUDA_ERROR_NOT_SUPPORTED),
+    }
+}
+
+#[cfg(windows)]
+fn semaphore_handle_type_from_cuda(
+    type_: CUexternalSemaphoreHandleType,
+) -> Result<hipExternalSemaphoreHandleType, CUresult> {
+    match type_ {
+        CUexternalSemaphoreHandleType::CU_EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_WIN32 => {
+            Ok(hipExternalSemaphoreHandleType::hipExternalSemaphoreHandleTypeOpaqueWin32)
+        }
+        CUexternalSemaphoreHandleType::CU_EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_WIN32_KMT => {
+            Ok(hipExternalSemaphoreHandleType::hipExternalSemaphoreHandleTypeOpaqueWin32Kmt)
+        }
+        CUexternalSemaphoreHandleType(0) => Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+        _ => Err(CUresult::CUDA_ERROR_NOT_SUPPORTED),
+    }
+}
+
+// HIP descriptors are built field by field, ROCm 5 and ROCm 6 disagree on
+// their layout and neither of them matches CUDA exactly
+pub(crate) unsafe fn import_memory(
+    ext_mem: *mut hipExternalMemory_t,
+    desc: *const CUDA_EXTERNAL_MEMORY_HANDLE_DESC,
+) -> Result<(), CUresult> {
+    if ext_mem == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let desc = desc.as_ref().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut hip_desc = mem::zeroed::<hipExternalMemoryHandleDesc>();
+    hip_desc.type_ = memory_handle_type_from_cuda(desc.type_)?;
+    #[cfg(not(windows))]
+    {
+        hip_desc.handle.fd = desc.handle.fd;
+    }
+    #[cfg(windows)]
+    {
+        hip_desc.handle.win32.handle = desc.handle.win32.handle;
+        hip_desc.handle.win32.name = desc.handle.win32.name;
+    }
+    hip_desc.size = desc.size;
+    hip_desc.flags = desc.flags;
+    hip_call_cuda!(hipImportExternalMemory(ext_mem, &hip_desc));
+    Ok(())
+}
+
+pub(crate) unsafe fn get_mapped_buffer(
+    dev_ptr: *mut hipDeviceptr_t,
+    ext_mem: hipExternalMemory_t,
+    desc: *const CUDA_EXTERNAL_MEMORY_BUFFER_DESC,
+) -> Result<(), CUresult> {
+    if dev_ptr == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let desc = desc.as_ref().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut hip_desc = mem::zeroed::<hipExternalMemoryBufferDesc>();
+    hip_desc.offset = desc.offset;
+    hip_desc.size = desc.size;
+    hip_desc.flags = desc.flags;
+    hip_call_cuda!(hipExternalMemoryGetMappedBuffer(
+        dev_ptr.cast(),
+        ext_mem,
+        &hip_desc
+    ));
+    Ok(())
+}
+
+#[cfg(feature = "rocm5")]
+pub(crate) unsafe fn get_mapped_mipmapped_array(
+    _mipmap: *mut CUmipmappedArray,
+    _ext_mem: hipExternalMemory_t,
+    _desc: *const CUDA_EXTERNAL_MEMORY_MIPMAPPED_ARRAY_DESC,
+) -> Result<(), CUresult> {
+    Err(CUresult::CUDA_ERROR_NOT_SUPPORTED)
+}
+
+#[cfg(not(feature = "rocm5"))]
+pub(crate) unsafe fn get_mapped_mipmapped_array(
+    mipmap: *mut CUmipmappedArray,
+    ext_mem: hipExternalMemory_t,
+    desc: *const CUDA_EXTERNAL_MEMORY_MIPMAPPED_ARRAY_DESC,
+) -> Result<(), CUresult> {
+    if mipmap == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let desc = desc.as_ref().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let array_desc = &desc.arrayDesc;
+    let (hack_flag, format) =
+        hipfix::get_non_broken_format(super::FromCuda::from_cuda(array_desc.Format));
+    let hip_desc = hipExternalMemoryMipmappedArrayDesc {
+        offset: desc.offset,
+        formatDesc: to_channel_format_desc(format, array_desc.NumChannels)?,
+        extent: hipExtent {
+            width: array_desc.Width,
+            height: array_desc.Height,
+            depth: array_desc.Depth,
+        },
+        flags: array_desc.Flags,
+        numLevels: desc.numLevels,
+    };
+    let mut hip_array = ptr::null_mut();
+    hip_call_cuda!(hipExternalMemoryGetMappedMipmappedArray(
+        &mut hip_array,
+        ext_mem,
+        &hip_desc
+    ));
+    // Same tagging as in array::mipmapped_create(...)
+    if (hip_array as usize & 0b11) != 0 {
+        hip_call_cuda!(hipMipmappedArrayDestroy(hip_array));
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    *mipmap = (hip_array as usize | hack_flag as usize) as _;
+    Ok(())
+}
+
+#[cfg(not(feature = "rocm5"))]
+fn to_channel_format_desc(
+    format: hipArray_Format,
+    num_channels: u32,
+) -> Result<hipChannelFormatDesc, CUresult> {
+    let (kind, bits) = match format {
+        hipArray_Format::HIP_AD_FORMAT_UNSIGNED_INT8 => {
+            (hipChannelFormatKind::hipChannelFormatKindUnsigned, 8)
+        }
+        hipArray_Format::HIP_AD_FORMAT_UNSIGNED_INT16 => {
+            (hipChannelFormatKind::hipChannelFormatKindUnsigned, 16)
+        }
+        hipArray_Format::HIP_AD_FORMAT_UNSIGNED_INT32 => {
+            (hipChannelFormatKind::hipChannelFormatKindUnsigned, 32)
+        }
+        hipArray_Format::HIP_AD_FORMAT_SIGNED_INT8 => {
+            (hipChannelFormatKind::hipChannelFormatKindSigned, 8)
+        }
+        hipArray_Format::HIP_AD_FORMAT_SIGNED_INT16 => {
+            (hipChannelFormatKind::hipChannelFormatKindSigned, 16)
+        }
+        hipArray_Format::HIP_AD_FORMAT_SIGNED_INT32 => {
+            (hipChannelFormatKind::hipChannelFormatKindSigned, 32)
+        }
+        hipArray_Format::HIP_AD_FORMAT_HALF => {
+            (hipChannelFormatKind::hipChannelFormatKindFloat, 16)
+        }
+        hipArray_Format::HIP_AD_FORMAT_FLOAT => {
+            (hipChannelFormatKind::hipChannelFormatKindFloat, 32)
+        }
+        _ => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    };
+    let channel = |index| if num_channels > index { bits } else { 0 };
+    match num_channels {
+        1 | 2 | 4 => Ok(hipChannelFormatDesc {
+            x: channel(0),
+            y: channel(1),
+            z: channel(2),
+            w: channel(3),
+            f: kind,
+        }),
+        _ => Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    }
+}
+
+pub(crate) unsafe fn destroy_memory(ext_mem: hipExternalMemory_t) -> hipError_t {
+    hipDestroyExternalMemory(ext_mem)
+}
+
+pub(crate) unsafe fn import_semaphore(
+    ext_sem: *mut hipExternalSemaphore_t,
+    desc: *const CUDA_EXTERNAL_SEMAPHORE_HANDLE_DESC,
+) -> Result<(), CUresult> {
+    if ext_sem == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let desc = desc.as_ref().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut hip_desc = mem::zeroed::<hipExternalSemaphoreHandleDesc>();
+    hip_desc.type_ = semaphore_handle_type_from_cuda(desc.type_)?;
+    #[cfg(not(windows))]
+    {
+        hip_desc.handle.fd = desc.handle.fd;
+    }
+    #[cfg(windows)]
+    {
+        hip_desc.handle.win32.handle = desc.handle.win32.handle;
+        hip_desc.handle.win32.name = desc.handle.win32.name;
+    }
+    hip_desc.flags = desc.flags;
+    hip_call_cuda!(hipImportExternalSemaphore(ext_sem, &hip_desc));
+    Ok(())
+}
+
+pub(crate) unsafe fn signal_semaphores_async(
+    ext_sems: *const hipExternalSemaphore_t,
+    params: *const CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS,
+    num_ext_sems: u32,
+    stream: *mut stream::Stream,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    let params = semaphore_params(ext_sems, params, num_ext_sems)?;
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    if params.is_empty() {
+        return Ok(());
+    }
+    let hip_params = params
+        .iter()
+        .map(|param| {
+            let mut hip_param = mem::zeroed::<hipExternalSemaphoreSignalParams>();
+            hip_param.params.fence.value = param.params.fence.value;
+            hip_param.params.keyedMutex.key = param.params.keyedMutex.key;
+            hip_param.flags = param.flags;
+            hip_param
+        })
+        .collect::<Vec<_>>();
+    hip_call_cuda!(hipSignalExternalSemaphoresAsync(
+        ext_sems,
+        hip_params.as_ptr(),
+        num_ext_sems,
+        hip_stream
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn wait_semaphores_async(
+    ext_sems: *const hipExternalSemaphore_t,
+    params: *const CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS,
+    num_ext_sems: u32,
+    stream: *mut stream::Stream,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    let params = semaphore_params(ext_sems, params, num_ext_sems)?;
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    if params.is_empty() {
+        return Ok(());
+    }
+    let hip_params = params
+        .iter()
+        .map(|param| {
+            let mut hip_param = mem::zeroed::<hipExternalSemaphoreWaitParams>();
+            hip_param.params.fence.value = param.params.fence.value;
+            hip_param.params.keyedMutex.key = param.params.keyedMutex.key;
+            hip_param.params.keyedMutex.timeoutMs = param.params.keyedMutex.timeoutMs;
+            hip_param.flags = param.flags;
+            hip_param
+        })
+        .collect::<Vec<_>>();
+    hip_call_cuda!(hipWaitExternalSemaphoresAsync(
+        ext_sems,
+        hip_params.as_ptr(),
+        num_ext_sems,
+        hip_stream
+    ));
+    Ok(())
+}
+
+// Signaling or waiting on no semaphores does nothing
+unsafe fn semaphore_params<'a, T>(
+    ext_sems: *const hipExternalSemaphore_t,
+    params: *const T,
+    num_ext_sems: u32,
+) -> Result<&'a [T], CUresult> {
+    if num_ext_sems == 0 {
+        return Ok(&[]);
+    }
+    if ext_sems == ptr::null() || params == ptr::null() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    Ok(std::slice::from_raw_parts(params, num_ext_sems as usize))
+}
+
+pub(crate) unsafe fn destroy_semaphore(ext_sem: hipExternalSemaphore_t) -> hipError_t {
+    hipDestroyExternalSemaphore(ext_sem)
+}
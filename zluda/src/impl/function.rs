@@ -1,9 +1,14 @@
-use super::{stream, LiveCheck, ZludaObject};
+use super::{stream, FromCuda, LiveCheck, ZludaObject};
 use crate::{hip_call_cuda, r#impl::hipfix};
 use cuda_types::*;
 use hip_common::CompilationMode;
 use hip_runtime_sys::*;
-use std::{ffi::c_void, ptr};
+use std::{
+    collections::BTreeMap,
+    ffi::{c_void, CString},
+    ptr,
+    sync::Mutex,
+};
 
 const CU_LAUNCH_PARAM_BUFFER_POINTER: *mut c_void = 1 as *mut _;
 const CU_LAUNCH_PARAM_BUFFER_SIZE: *mut c_void = 2 as *mut _;
@@ -12,6 +17,25 @@ const HIP_LAUNCH_PARAM_END: *mut c_void = 3 as *mut _;
 
 pub(crate) type Function = LiveCheck<FunctionData>;
 
+// HIP function -> ZLUDA function. Graph kernel nodes created by HIP (e.g.
+// during stream capture) only know the HIP function
+static FUNCTIONS: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+// Module global read by %envreg1 and %envreg2, see sreg_envreg in
+// zluda_ptx_impl
+pub(crate) const GRID_WORKSPACE_POINTER: &[u8] = b"__zluda_ptx_impl__grid_workspace\0";
+// struct { uint32_t ws_size; uint32_t barrier; }
+const GRID_WORKSPACE_SIZE: usize = 8;
+
+// Grid workspace pointer -> event recorded after the last cooperative
+// launch that set it. The pointer is written on the launch stream, so
+// cooperative launches sharing it must not overlap
+static GRID_WORKSPACE_LAUNCHES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+// (device, priority) -> HIP stream for kernels launched with a priority other
+// than the priority of their stream. Created on the first use, never destroyed
+static PRIORITY_STREAMS: Mutex<BTreeMap<(hipDevice_t, i32), usize>> = Mutex::new(BTreeMap::new());
+
 impl ZludaObject for FunctionData {
     #[cfg(target_pointer_width = "64")]
     const LIVENESS_COOKIE: usize = 0x86b7301e5869d145;
@@ -20,16 +44,28 @@ impl ZludaObject for FunctionData {
     const LIVENESS_FAIL: CUresult = CUresult::CUDA_ERROR_INVALID_HANDLE;
 
     fn drop_with_result(&mut self, _by_owner: bool) -> Result<(), CUresult> {
+        if self.grid_workspace_pointer != ptr::null_mut() {
+            let last_launch = GRID_WORKSPACE_LAUNCHES
+                .lock()
+                .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?
+                .remove(&(self.grid_workspace_pointer as usize));
+            if let Some(event) = last_launch {
+                hip_call_cuda!(hipEventDestroy(event as _));
+            }
+        }
         Ok(())
     }
 }
 
 pub(crate) struct FunctionData {
     pub(crate) base: hipFunction_t,
+    pub(crate) name: CString,
     pub(crate) ptx_version: u32,
     pub(crate) binary_version: u32,
     pub(crate) group_size: Option<(u32, u32)>,
     pub(crate) compilation_mode: CompilationMode,
+    // Null if the kernel does not read %envreg
+    pub(crate) grid_workspace_pointer: *mut c_void,
 }
 
 pub(crate) unsafe fn launch_kernel(
@@ -39,7 +75,7 @@ pub(crate) unsafe fn launch_kernel(
     grid_dim_z: ::std::os::raw::c_uint,
     block_dim_x: ::std::os::raw::c_uint,
     block_dim_y: ::std::os::raw::c_uint,
-    mut block_dim_z: ::std::os::raw::c_uint,
+    block_dim_z: ::std::os::raw::c_uint,
     shared_mem_bytes: ::std::os::raw::c_uint,
     stream: *mut stream::Stream,
     kernel_params: *mut *mut ::std::os::raw::c_void,
@@ -47,6 +83,34 @@ pub(crate) unsafe fn launch_kernel(
     default_stream_per_thread: bool,
 ) -> Result<(), CUresult> {
     let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    launch_kernel_on_stream(
+        f,
+        grid_dim_x,
+        grid_dim_y,
+        grid_dim_z,
+        block_dim_x,
+        block_dim_y,
+        block_dim_z,
+        shared_mem_bytes,
+        hip_stream,
+        kernel_params,
+        extra,
+    )
+}
+
+unsafe fn launch_kernel_on_stream(
+    f: *mut Function,
+    grid_dim_x: ::std::os::raw::c_uint,
+    grid_dim_y: ::std::os::raw::c_uint,
+    grid_dim_z: ::std::os::raw::c_uint,
+    block_dim_x: ::std::os::raw::c_uint,
+    block_dim_y: ::std::os::raw::c_uint,
+    mut block_dim_z: ::std::os::raw::c_uint,
+    shared_mem_bytes: ::std::os::raw::c_uint,
+    hip_stream: hipStream_t,
+    kernel_params: *mut *mut ::std::os::raw::c_void,
+    extra: *mut *mut ::std::os::raw::c_void,
+) -> Result<(), CUresult> {
     let function = LiveCheck::as_result(f)?;
     hipfix::validate_block_size(function, block_dim_x, block_dim_y, block_dim_z)?;
     if function.compilation_mode == CompilationMode::Wave32OnWave64 {
@@ -56,15 +120,7 @@ pub(crate) unsafe fn launch_kernel(
         if kernel_params != ptr::null_mut() {
             return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
         }
-        let mut extra_params = *(extra as *mut [*mut c_void; 5]);
-        if extra_params[0] != CU_LAUNCH_PARAM_BUFFER_POINTER
-            || extra_params[2] != CU_LAUNCH_PARAM_BUFFER_SIZE
-            || extra_params[4] != CU_LAUNCH_PARAM_END
-        {
-            return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
-        }
-        // CU_LAUNCH_PARAM_END is 0, while HIP_LAUNCH_PARAM_END is 3
-        extra_params[4] = HIP_LAUNCH_PARAM_END;
+        let mut extra_params = extra_to_hip(extra)?;
         hip_call_cuda!(hipModuleLaunchKernel(
             function.base,
             grid_dim_x,
@@ -97,6 +153,385 @@ pub(crate) unsafe fn launch_kernel(
     Ok(())
 }
 
+pub(crate) unsafe fn extra_to_hip(extra: *mut *mut c_void) -> Result<[*mut c_void; 5], CUresult> {
+    let mut extra_params = *(extra as *mut [*mut c_void; 5]);
+    if extra_params[0] != CU_LAUNCH_PARAM_BUFFER_POINTER
+        || extra_params[2] != CU_LAUNCH_PARAM_BUFFER_SIZE
+        || extra_params[4] != CU_LAUNCH_PARAM_END
+    {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    // CU_LAUNCH_PARAM_END is 0, while HIP_LAUNCH_PARAM_END is 3
+    extra_params[4] = HIP_LAUNCH_PARAM_END;
+    Ok(extra_params)
+}
+
+pub(crate) fn register(function: &Function) {
+    let hip_function = unsafe { function.as_ref_unchecked() }.base;
+    if let Ok(mut functions) = FUNCTIONS.lock() {
+        functions.insert(hip_function as usize, function as *const Function as usize);
+    }
+}
+
+pub(crate) fn unregister(function: &FunctionData) {
+    if let Ok(mut functions) = FUNCTIONS.lock() {
+        functions.remove(&(function.base as usize));
+    }
+}
+
+pub(crate) fn from_hip(hip_function: hipFunction_t) -> *mut Function {
+    FUNCTIONS
+        .lock()
+        .ok()
+        .and_then(|functions| functions.get(&(hip_function as usize)).copied())
+        .unwrap_or(0) as *mut Function
+}
+
+pub(crate) unsafe fn launch_cooperative_kernel(
+    f: *mut Function,
+    grid_dim_x: ::std::os::raw::c_uint,
+    grid_dim_y: ::std::os::raw::c_uint,
+    grid_dim_z: ::std::os::raw::c_uint,
+    block_dim_x: ::std::os::raw::c_uint,
+    block_dim_y: ::std::os::raw::c_uint,
+    block_dim_z: ::std::os::raw::c_uint,
+    shared_mem_bytes: ::std::os::raw::c_uint,
+    stream: *mut stream::Stream,
+    kernel_params: *mut *mut ::std::os::raw::c_void,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    launch_cooperative_kernel_on_stream(
+        f,
+        grid_dim_x,
+        grid_dim_y,
+        grid_dim_z,
+        block_dim_x,
+        block_dim_y,
+        block_dim_z,
+        shared_mem_bytes,
+        hip_stream,
+        kernel_params,
+    )
+}
+
+unsafe fn launch_cooperative_kernel_on_stream(
+    f: *mut Function,
+    grid_dim_x: ::std::os::raw::c_uint,
+    grid_dim_y: ::std::os::raw::c_uint,
+    grid_dim_z: ::std::os::raw::c_uint,
+    block_dim_x: ::std::os::raw::c_uint,
+    block_dim_y: ::std::os::raw::c_uint,
+    mut block_dim_z: ::std::os::raw::c_uint,
+    shared_mem_bytes: ::std::os::raw::c_uint,
+    hip_stream: hipStream_t,
+    kernel_params: *mut *mut ::std::os::raw::c_void,
+) -> Result<(), CUresult> {
+    let function = LiveCheck::as_result(f)?;
+    hipfix::validate_block_size(function, block_dim_x, block_dim_y, block_dim_z)?;
+    if function.compilation_mode == CompilationMode::Wave32OnWave64 {
+        block_dim_z *= 2;
+    }
+    with_grid_workspaces(&[(function, hip_stream)], || {
+        hip_call_cuda!(hipModuleLaunchCooperativeKernel(
+            function.base,
+            grid_dim_x,
+            grid_dim_y,
+            grid_dim_z,
+            block_dim_x,
+            block_dim_y,
+            block_dim_z,
+            shared_mem_bytes,
+            hip_stream,
+            kernel_params,
+        ));
+        Ok(())
+    })
+}
+
+// Allocates a grid workspace for every launch, points the kernel's %envreg
+// at it, runs `launch` and frees the workspaces once the kernels finish
+unsafe fn with_grid_workspaces(
+    launches: &[(&FunctionData, hipStream_t)],
+    launch: impl FnOnce() -> Result<(), CUresult>,
+) -> Result<(), CUresult> {
+    let mut last_launches = GRID_WORKSPACE_LAUNCHES
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    let mut workspaces = Vec::new();
+    for (function, hip_stream) in launches.iter().copied() {
+        let pointer = function.grid_workspace_pointer;
+        if pointer == ptr::null_mut() {
+            continue;
+        }
+        if let Some(event) = last_launches.get(&(pointer as usize)) {
+            hip_call_cuda!(hipStreamWaitEvent(hip_stream, *event as _, 0));
+        }
+        let mut workspace = ptr::null_mut();
+        hip_call_cuda!(hipMallocAsync(
+            &mut workspace,
+            GRID_WORKSPACE_SIZE,
+            hip_stream
+        ));
+        workspaces.push((pointer, workspace, hip_stream));
+        // ws_size and barrier
+        hip_call_cuda!(hipMemsetD32Async(
+            hipDeviceptr_t(workspace),
+            GRID_WORKSPACE_SIZE as i32,
+            1,
+            hip_stream
+        ));
+        hip_call_cuda!(hipMemsetD32Async(
+            hipDeviceptr_t(workspace.cast::<u8>().add(4).cast()),
+            0,
+            1,
+            hip_stream
+        ));
+        let address = workspace as u64;
+        hip_call_cuda!(hipMemsetD32Async(
+            hipDeviceptr_t(pointer),
+            address as u32 as i32,
+            1,
+            hip_stream
+        ));
+        hip_call_cuda!(hipMemsetD32Async(
+            hipDeviceptr_t(pointer.cast::<u8>().add(4).cast()),
+            (address >> 32) as u32 as i32,
+            1,
+            hip_stream
+        ));
+    }
+    let result = launch();
+    for (pointer, workspace, hip_stream) in workspaces {
+        hip_call_cuda!(hipFreeAsync(workspace, hip_stream));
+        if result.is_err() {
+            continue;
+        }
+        let mut event = ptr::null_mut();
+        hip_call_cuda!(hipEventCreateWithFlags(&mut event, hipEventDisableTiming));
+        hip_call_cuda!(hipEventRecord(event, hip_stream));
+        if let Some(previous) = last_launches.insert(pointer as usize, event as usize) {
+            hip_call_cuda!(hipEventDestroy(previous as _));
+        }
+    }
+    result
+}
+
+// Attributes are either honoured or rejected, we never silently drop
+// something that would change kernel semantics
+pub(crate) unsafe fn launch_kernel_ex(
+    config: *const CUlaunchConfig,
+    f: *mut Function,
+    kernel_params: *mut *mut ::std::os::raw::c_void,
+    extra: *mut *mut ::std::os::raw::c_void,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    let config = config.as_ref().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let attributes = if config.numAttrs == 0 {
+        &[]
+    } else {
+        if config.attrs == ptr::null_mut() {
+            return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+        }
+        std::slice::from_raw_parts(config.attrs, config.numAttrs as usize)
+    };
+    let mut cooperative = false;
+    let mut priority = None;
+    for attribute in attributes {
+        match attribute.id {
+            CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_IGNORE => {}
+            CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_COOPERATIVE => {
+                cooperative = attribute.value.cooperative != 0;
+            }
+            CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_PRIORITY => {
+                priority = Some(attribute.value.priority);
+            }
+            // Launches are always fully serialized on a stream
+            CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_PROGRAMMATIC_STREAM_SERIALIZATION => {}
+            CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_CLUSTER_DIMENSION => {
+                let cluster_dim = attribute.value.clusterDim;
+                match (cluster_dim.x, cluster_dim.y, cluster_dim.z) {
+                    (1, 1, 1) => {}
+                    (0, _, _) | (_, 0, _) | (_, _, 0) => {
+                        return Err(CUresult::CUDA_ERROR_INVALID_VALUE)
+                    }
+                    _ => return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED),
+                }
+            }
+            CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_ACCESS_POLICY_WINDOW
+            | CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_SYNCHRONIZATION_POLICY
+            | CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_CLUSTER_SCHEDULING_POLICY_PREFERENCE
+            | CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_PROGRAMMATIC_EVENT
+            | CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_MEM_SYNC_DOMAIN_MAP
+            | CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_MEM_SYNC_DOMAIN => {
+                return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED)
+            }
+            _ => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+        }
+    }
+    // HIP has no way to pass `extra` to a cooperative launch
+    if cooperative && extra != ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
+    }
+    let hip_stream = hipfix::as_hip_stream_per_thread(
+        FromCuda::from_cuda(config.hStream),
+        default_stream_per_thread,
+    )?;
+    with_priority(hip_stream, priority, |hip_stream| {
+        if cooperative {
+            launch_cooperative_kernel_on_stream(
+                f,
+                config.gridDimX,
+                config.gridDimY,
+                config.gridDimZ,
+                config.blockDimX,
+                config.blockDimY,
+                config.blockDimZ,
+                config.sharedMemBytes,
+                hip_stream,
+                kernel_params,
+            )
+        } else {
+            launch_kernel_on_stream(
+                f,
+                config.gridDimX,
+                config.gridDimY,
+                config.gridDimZ,
+                config.blockDimX,
+                config.blockDimY,
+                config.blockDimZ,
+                config.sharedMemBytes,
+                hip_stream,
+                kernel_params,
+                extra,
+            )
+        }
+    })
+}
+
+// HIP kernel launches have no priority of their own. If the priority differs
+// from the priority of the stream, the kernel is launched on a stream with
+// that priority, ordered after the earlier work of the stream and before the
+// later work with events
+unsafe fn with_priority(
+    hip_stream: hipStream_t,
+    priority: Option<i32>,
+    launch: impl FnOnce(hipStream_t) -> Result<(), CUresult>,
+) -> Result<(), CUresult> {
+    let priority = match priority {
+        Some(priority) => priority,
+        None => return launch(hip_stream),
+    };
+    let mut least_priority = 0;
+    let mut greatest_priority = 0;
+    hip_call_cuda!(hipDeviceGetStreamPriorityRange(
+        &mut least_priority,
+        &mut greatest_priority
+    ));
+    // Like in CUDA, out of range priorities are clamped
+    let priority = priority.clamp(greatest_priority, least_priority);
+    let mut stream_priority = 0;
+    hip_call_cuda!(hipStreamGetPriority(hip_stream, &mut stream_priority));
+    if priority == stream_priority {
+        return launch(hip_stream);
+    }
+    let mut device = 0;
+    hip_call_cuda!(hipStreamGetDevice(hip_stream, &mut device));
+    let priority_stream = {
+        let mut streams = PRIORITY_STREAMS
+            .lock()
+            .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+        match streams.get(&(device, priority)) {
+            Some(stream) => *stream as hipStream_t,
+            None => {
+                // hipStreamCreateWithPriority(...) creates the stream on the
+                // current device, which is not necessarily the stream's device
+                let mut current_device = 0;
+                hip_call_cuda!(hipGetDevice(&mut current_device));
+                hip_call_cuda!(hipSetDevice(device));
+                let mut stream = ptr::null_mut();
+                let result =
+                    hipStreamCreateWithPriority(&mut stream, hipStreamNonBlocking, priority);
+                hip_call_cuda!(hipSetDevice(current_device));
+                hip_call_cuda!(result);
+                streams.insert((device, priority), stream as usize);
+                stream
+            }
+        }
+    };
+    chain_streams(hip_stream, priority_stream)?;
+    let result = launch(priority_stream);
+    chain_streams(priority_stream, hip_stream)?;
+    result
+}
+
+// Work submitted to `next` from now on waits for work submitted to `previous`
+// so far
+unsafe fn chain_streams(previous: hipStream_t, next: hipStream_t) -> Result<(), CUresult> {
+    let mut event = ptr::null_mut();
+    hip_call_cuda!(hipEventCreateWithFlags(&mut event, hipEventDisableTiming));
+    let mut result = hipEventRecord(event, previous);
+    if result == hipError_t::hipSuccess {
+        result = hipStreamWaitEvent(next, event, 0);
+    }
+    hip_call_cuda!(hipEventDestroy(event));
+    hip_call_cuda!(result);
+    Ok(())
+}
+
+pub(crate) unsafe fn launch_cooperative_kernel_multi_device(
+    launch_params_list: *mut CUDA_LAUNCH_PARAMS,
+    num_devices: ::std::os::raw::c_uint,
+    flags: ::std::os::raw::c_uint,
+) -> Result<(), CUresult> {
+    if launch_params_list == ptr::null_mut() || num_devices == 0 {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let launch_params_list = std::slice::from_raw_parts(launch_params_list, num_devices as usize);
+    let mut functions = Vec::with_capacity(launch_params_list.len());
+    let mut hip_launch_params = launch_params_list
+        .iter()
+        .map(|params| {
+            let hip_stream =
+                hipfix::as_hip_stream_per_thread(FromCuda::from_cuda(params.hStream), false)?;
+            let function = LiveCheck::as_result(params.function as *mut Function)?;
+            hipfix::validate_block_size(
+                function,
+                params.blockDimX,
+                params.blockDimY,
+                params.blockDimZ,
+            )?;
+            let mut block_dim_z = params.blockDimZ;
+            if function.compilation_mode == CompilationMode::Wave32OnWave64 {
+                block_dim_z *= 2;
+            }
+            functions.push((function, hip_stream));
+            Ok(hipFunctionLaunchParams {
+                function: function.base,
+                gridDimX: params.gridDimX,
+                gridDimY: params.gridDimY,
+                gridDimZ: params.gridDimZ,
+                blockDimX: params.blockDimX,
+                blockDimY: params.blockDimY,
+                blockDimZ: block_dim_z,
+                sharedMemBytes: params.sharedMemBytes,
+                hStream: hip_stream,
+                kernelParams: params.kernelParams,
+            })
+        })
+        .collect::<Result<Vec<_>, CUresult>>()?;
+    // CUDA_COOPERATIVE_LAUNCH_MULTI_DEVICE_NO_*_LAUNCH_SYNC flags have the
+    // same values as hipCooperativeLaunchMultiDeviceNo*Sync
+    with_grid_workspaces(&functions, || {
+        hip_call_cuda!(hipModuleLaunchCooperativeKernelMultiDevice(
+            hip_launch_params.as_mut_ptr(),
+            num_devices,
+            flags,
+        ));
+        Ok(())
+    })
+}
+
 pub(crate) unsafe fn occupancy_max_potential_block_size(
     min_grid_size: *mut i32,
     block_size: *mut i32,
@@ -145,6 +580,18 @@ pub(crate) unsafe fn occupancy_max_potential_blocks_per_multiprocessor(
     Ok(())
 }
 
+pub(crate) unsafe fn get_name(
+    name: *mut *const ::std::os::raw::c_char,
+    func: *mut Function,
+) -> Result<(), CUresult> {
+    if name == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let function = LiveCheck::as_result(func)?;
+    *name = function.name.as_ptr();
+    Ok(())
+}
+
 pub(crate) unsafe fn get_attribute(
     pi: *mut i32,
     attrib: hipFunction_attribute,
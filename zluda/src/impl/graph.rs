@@ -1,7 +1,9 @@
-use super::{function, stream, LiveCheck};
+use super::{function, hipfix, mempool, stream, surface, vmm, LiveCheck};
 use crate::hip_call_cuda;
 use cuda_types::*;
+use hip_common::CompilationMode;
 use hip_runtime_sys::*;
+use std::{ffi::c_void, mem, ptr};
 
 pub(crate) unsafe fn add_kernel_node(
     ph_graph_node: *mut hipGraphNode_t,
@@ -13,7 +15,8 @@ pub(crate) unsafe fn add_kernel_node(
     let node_params = node_params
         .as_ref()
         .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
-    let node_params = hip_node_params(node_params)?;
+    let mut extra = [ptr::null_mut(); 5];
+    let node_params = hip_node_params(node_params, &mut extra)?;
     hip_call_cuda!(hipGraphAddKernelNode(
         ph_graph_node,
         h_graph,
@@ -24,18 +27,181 @@ pub(crate) unsafe fn add_kernel_node(
     Ok(())
 }
 
+pub(crate) unsafe fn add_kernel_node_v2(
+    ph_graph_node: *mut hipGraphNode_t,
+    h_graph: hipGraph_t,
+    dependencies: *const hipGraphNode_t,
+    num_dependencies: usize,
+    node_params: *const CUDA_KERNEL_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    let node_params = kernel_node_params_v1(node_params)?;
+    add_kernel_node(
+        ph_graph_node,
+        h_graph,
+        dependencies,
+        num_dependencies,
+        &node_params,
+    )
+}
+
+pub(crate) unsafe fn kernel_node_get_params(
+    node: hipGraphNode_t,
+    node_params: *mut CUDA_KERNEL_NODE_PARAMS_v1,
+) -> Result<(), CUresult> {
+    if node_params == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let mut hip_params = mem::zeroed::<hipKernelNodeParams>();
+    hip_call_cuda!(hipGraphKernelNodeGetParams(node, &mut hip_params));
+    let func = function::from_hip(hip_params.func.cast());
+    let mut block_dim_z = hip_params.blockDim.z;
+    if let Ok(function) = LiveCheck::as_result(func) {
+        if function.compilation_mode == CompilationMode::Wave32OnWave64 {
+            block_dim_z /= 2;
+        }
+    }
+    *node_params = CUDA_KERNEL_NODE_PARAMS_v1 {
+        func: func.cast(),
+        gridDimX: hip_params.gridDim.x,
+        gridDimY: hip_params.gridDim.y,
+        gridDimZ: hip_params.gridDim.z,
+        blockDimX: hip_params.blockDim.x,
+        blockDimY: hip_params.blockDim.y,
+        blockDimZ: block_dim_z,
+        sharedMemBytes: hip_params.sharedMemBytes,
+        kernelParams: hip_params.kernelParams,
+        // HIP keeps `extra` converted by hip_node_params(...), it points to
+        // buffers that are long gone
+        extra: ptr::null_mut(),
+    };
+    Ok(())
+}
+
+pub(crate) unsafe fn kernel_node_get_params_v2(
+    node: hipGraphNode_t,
+    node_params: *mut CUDA_KERNEL_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    if node_params == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let mut params_v1 = mem::zeroed::<CUDA_KERNEL_NODE_PARAMS_v1>();
+    kernel_node_get_params(node, &mut params_v1)?;
+    *node_params = CUDA_KERNEL_NODE_PARAMS {
+        func: params_v1.func,
+        gridDimX: params_v1.gridDimX,
+        gridDimY: params_v1.gridDimY,
+        gridDimZ: params_v1.gridDimZ,
+        blockDimX: params_v1.blockDimX,
+        blockDimY: params_v1.blockDimY,
+        blockDimZ: params_v1.blockDimZ,
+        sharedMemBytes: params_v1.sharedMemBytes,
+        kernelParams: params_v1.kernelParams,
+        extra: params_v1.extra,
+        kern: ptr::null_mut(),
+        ctx: ptr::null_mut(),
+    };
+    Ok(())
+}
+
+pub(crate) unsafe fn kernel_node_set_params(
+    node: hipGraphNode_t,
+    node_params: *const CUDA_KERNEL_NODE_PARAMS_v1,
+) -> Result<(), CUresult> {
+    let node_params = node_params
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut extra = [ptr::null_mut(); 5];
+    let node_params = hip_node_params(node_params, &mut extra)?;
+    hip_call_cuda!(hipGraphKernelNodeSetParams(node, &node_params));
+    Ok(())
+}
+
+pub(crate) unsafe fn kernel_node_set_params_v2(
+    node: hipGraphNode_t,
+    node_params: *const CUDA_KERNEL_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    kernel_node_set_params(node, &kernel_node_params_v1(node_params)?)
+}
+
+pub(crate) unsafe fn exec_kernel_node_set_params(
+    graph_exec: hipGraphExec_t,
+    node: hipGraphNode_t,
+    node_params: *const CUDA_KERNEL_NODE_PARAMS_v1,
+) -> Result<(), CUresult> {
+    let node_params = node_params
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let mut extra = [ptr::null_mut(); 5];
+    let node_params = hip_node_params(node_params, &mut extra)?;
+    hip_call_cuda!(hipGraphExecKernelNodeSetParams(
+        graph_exec,
+        node,
+        &node_params
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn exec_kernel_node_set_params_v2(
+    graph_exec: hipGraphExec_t,
+    node: hipGraphNode_t,
+    node_params: *const CUDA_KERNEL_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    exec_kernel_node_set_params(graph_exec, node, &kernel_node_params_v1(node_params)?)
+}
+
+// Kernels from libraries (`kern` field) are not supported yet
+unsafe fn kernel_node_params_v1(
+    node_params: *const CUDA_KERNEL_NODE_PARAMS,
+) -> Result<CUDA_KERNEL_NODE_PARAMS_v1, CUresult> {
+    let node_params = node_params
+        .as_ref()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    if node_params.func == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
+    }
+    Ok(CUDA_KERNEL_NODE_PARAMS_v1 {
+        func: node_params.func,
+        gridDimX: node_params.gridDimX,
+        gridDimY: node_params.gridDimY,
+        gridDimZ: node_params.gridDimZ,
+        blockDimX: node_params.blockDimX,
+        blockDimY: node_params.blockDimY,
+        blockDimZ: node_params.blockDimZ,
+        sharedMemBytes: node_params.sharedMemBytes,
+        kernelParams: node_params.kernelParams,
+        extra: node_params.extra,
+    })
+}
+
+// Same rules as in function::launch_kernel(...), `extra` is the storage for
+// the converted `extra` array
 unsafe fn hip_node_params(
     cuda: &CUDA_KERNEL_NODE_PARAMS_v1,
+    extra: &mut [*mut c_void; 5],
 ) -> Result<hipKernelNodeParams, CUresult> {
     let zluda_func = cuda.func.cast::<function::Function>();
     let zluda_func = LiveCheck::as_result(zluda_func)?;
+    hipfix::validate_block_size(zluda_func, cuda.blockDimX, cuda.blockDimY, cuda.blockDimZ)?;
+    let mut block_dim_z = cuda.blockDimZ;
+    if zluda_func.compilation_mode == CompilationMode::Wave32OnWave64 {
+        block_dim_z *= 2;
+    }
+    let hip_extra = if cuda.extra != ptr::null_mut() {
+        if cuda.kernelParams != ptr::null_mut() {
+            return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+        }
+        *extra = function::extra_to_hip(cuda.extra)?;
+        extra.as_mut_ptr()
+    } else {
+        ptr::null_mut()
+    };
     Ok(hipKernelNodeParams {
         blockDim: dim3 {
             x: cuda.blockDimX,
             y: cuda.blockDimY,
-            z: cuda.blockDimZ,
+            z: block_dim_z,
         },
-        extra: cuda.extra,
+        extra: hip_extra,
         func: zluda_func.base.cast(),
         gridDim: dim3 {
             x: cuda.gridDimX,
@@ -47,6 +213,450 @@ unsafe fn hip_node_params(
     })
 }
 
+// HIP uses the current device instead of `ctx`
+pub(crate) unsafe fn add_memcpy_node(
+    ph_graph_node: *mut hipGraphNode_t,
+    h_graph: hipGraph_t,
+    dependencies: *const hipGraphNode_t,
+    num_dependencies: usize,
+    copy_params: *const CUDA_MEMCPY3D,
+) -> Result<(), CUresult> {
+    let copy_params = memcpy_params_from_cuda(copy_params)?;
+    hip_call_cuda!(hipGraphAddMemcpyNode(
+        ph_graph_node,
+        h_graph,
+        dependencies,
+        num_dependencies,
+        &copy_params
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn memcpy_node_get_params(
+    node: hipGraphNode_t,
+    node_params: *mut CUDA_MEMCPY3D,
+) -> Result<(), CUresult> {
+    if node_params == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let mut hip_params = mem::zeroed::<hipMemcpy3DParms>();
+    hip_call_cuda!(hipGraphMemcpyNodeGetParams(node, &mut hip_params));
+    *node_params = memcpy_params_to_cuda(&hip_params)?;
+    Ok(())
+}
+
+pub(crate) unsafe fn memcpy_node_set_params(
+    node: hipGraphNode_t,
+    node_params: *const CUDA_MEMCPY3D,
+) -> Result<(), CUresult> {
+    let node_params = memcpy_params_from_cuda(node_params)?;
+    hip_call_cuda!(hipGraphMemcpyNodeSetParams(node, &node_params));
+    Ok(())
+}
+
+pub(crate) unsafe fn exec_memcpy_node_set_params(
+    graph_exec: hipGraphExec_t,
+    node: hipGraphNode_t,
+    copy_params: *const CUDA_MEMCPY3D,
+) -> Result<(), CUresult> {
+    let mut copy_params = memcpy_params_from_cuda(copy_params)?;
+    hip_call_cuda!(hipGraphExecMemcpyNodeSetParams(
+        graph_exec,
+        node,
+        &mut copy_params
+    ));
+    Ok(())
+}
+
+// Unlike CUDA_MEMCPY3D, hipMemcpy3DParms positions and extent are in elements
+// if there's an array involved
+unsafe fn memcpy_params_from_cuda(
+    cuda: *const CUDA_MEMCPY3D,
+) -> Result<hipMemcpy3DParms, CUresult> {
+    let cuda = cuda.as_ref().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let (src_array, src_ptr) = memcpy_side_from_cuda(
+        cuda.srcMemoryType,
+        cuda.srcHost,
+        cuda.srcDevice,
+        cuda.srcArray,
+        cuda.srcPitch,
+        cuda.srcHeight,
+        cuda.Height,
+    )?;
+    let (dst_array, dst_ptr) = memcpy_side_from_cuda(
+        cuda.dstMemoryType,
+        cuda.dstHost as *const c_void,
+        cuda.dstDevice,
+        cuda.dstArray,
+        cuda.dstPitch,
+        cuda.dstHeight,
+        cuda.Height,
+    )?;
+    let src_element_size = array_element_size(src_array)?;
+    let dst_element_size = array_element_size(dst_array)?;
+    let extent_element_size = src_element_size.or(dst_element_size).unwrap_or(1);
+    Ok(hipMemcpy3DParms {
+        srcArray: src_array,
+        srcPos: hipPos {
+            x: cuda.srcXInBytes / src_element_size.unwrap_or(1),
+            y: cuda.srcY,
+            z: cuda.srcZ,
+        },
+        srcPtr: src_ptr,
+        dstArray: dst_array,
+        dstPos: hipPos {
+            x: cuda.dstXInBytes / dst_element_size.unwrap_or(1),
+            y: cuda.dstY,
+            z: cuda.dstZ,
+        },
+        dstPtr: dst_ptr,
+        extent: hipExtent {
+            width: cuda.WidthInBytes / extent_element_size,
+            height: cuda.Height,
+            depth: cuda.Depth,
+        },
+        kind: hipMemcpyKind::hipMemcpyDefault,
+    })
+}
+
+unsafe fn memcpy_side_from_cuda(
+    memory_type: CUmemorytype,
+    host: *const c_void,
+    device: CUdeviceptr,
+    array: CUarray,
+    pitch: usize,
+    height: usize,
+    default_height: usize,
+) -> Result<(hipArray_t, hipPitchedPtr), CUresult> {
+    let ptr = match memory_type {
+        CUmemorytype::CU_MEMORYTYPE_ARRAY => return Ok((hipfix::array::get(array), mem::zeroed())),
+        CUmemorytype::CU_MEMORYTYPE_HOST => host as *mut c_void,
+        CUmemorytype::CU_MEMORYTYPE_DEVICE | CUmemorytype::CU_MEMORYTYPE_UNIFIED => device.0,
+        _ => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    };
+    Ok((
+        ptr::null_mut(),
+        hipPitchedPtr {
+            ptr,
+            pitch,
+            xsize: pitch,
+            ysize: if height != 0 { height } else { default_height },
+        },
+    ))
+}
+
+unsafe fn array_element_size(array: hipArray_t) -> Result<Option<usize>, CUresult> {
+    Ok(match array.as_ref() {
+        Some(array) => Some(surface::format_size(array.Format)? * array.NumChannels as usize),
+        None => None,
+    })
+}
+
+// Memory type of pointers is not known, they are returned as unified
+unsafe fn memcpy_params_to_cuda(hip: &hipMemcpy3DParms) -> Result<CUDA_MEMCPY3D, CUresult> {
+    let mut result = mem::zeroed::<CUDA_MEMCPY3D>();
+    let src_element_size = array_element_size(hip.srcArray)?;
+    let dst_element_size = array_element_size(hip.dstArray)?;
+    if hip.srcArray != ptr::null_mut() {
+        result.srcMemoryType = CUmemorytype::CU_MEMORYTYPE_ARRAY;
+        result.srcArray = hipfix::array::to_cuda(hip.srcArray, 0);
+    } else {
+        result.srcMemoryType = CUmemorytype::CU_MEMORYTYPE_UNIFIED;
+        result.srcDevice = CUdeviceptr_v2(hip.srcPtr.ptr);
+        result.srcPitch = hip.srcPtr.pitch;
+        result.srcHeight = hip.srcPtr.ysize;
+    }
+    if hip.dstArray != ptr::null_mut() {
+        result.dstMemoryType = CUmemorytype::CU_MEMORYTYPE_ARRAY;
+        result.dstArray = hipfix::array::to_cuda(hip.dstArray, 0);
+    } else {
+        result.dstMemoryType = CUmemorytype::CU_MEMORYTYPE_UNIFIED;
+        result.dstDevice = CUdeviceptr_v2(hip.dstPtr.ptr);
+        result.dstPitch = hip.dstPtr.pitch;
+        result.dstHeight = hip.dstPtr.ysize;
+    }
+    result.srcXInBytes = hip.srcPos.x * src_element_size.unwrap_or(1);
+    result.srcY = hip.srcPos.y;
+    result.srcZ = hip.srcPos.z;
+    result.dstXInBytes = hip.dstPos.x * dst_element_size.unwrap_or(1);
+    result.dstY = hip.dstPos.y;
+    result.dstZ = hip.dstPos.z;
+    result.WidthInBytes = hip.extent.width * src_element_size.or(dst_element_size).unwrap_or(1);
+    result.Height = hip.extent.height;
+    result.Depth = hip.extent.depth;
+    Ok(result)
+}
+
+// HIP uses the current device instead of `ctx`
+pub(crate) unsafe fn add_memset_node(
+    ph_graph_node: *mut hipGraphNode_t,
+    h_graph: hipGraph_t,
+    dependencies: *const hipGraphNode_t,
+    num_dependencies: usize,
+    memset_params: *const CUDA_MEMSET_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    let memset_params = memset_params_from_cuda(memset_params)?;
+    hip_call_cuda!(hipGraphAddMemsetNode(
+        ph_graph_node,
+        h_graph,
+        dependencies,
+        num_dependencies,
+        &memset_params
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn memset_node_get_params(
+    node: hipGraphNode_t,
+    node_params: *mut CUDA_MEMSET_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    if node_params == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let mut hip_params = mem::zeroed::<hipMemsetParams>();
+    hip_call_cuda!(hipGraphMemsetNodeGetParams(node, &mut hip_params));
+    *node_params = CUDA_MEMSET_NODE_PARAMS {
+        dst: CUdeviceptr_v2(hip_params.dst),
+        pitch: hip_params.pitch,
+        value: hip_params.value,
+        elementSize: hip_params.elementSize,
+        width: hip_params.width,
+        height: hip_params.height,
+    };
+    Ok(())
+}
+
+pub(crate) unsafe fn memset_node_set_params(
+    node: hipGraphNode_t,
+    node_params: *const CUDA_MEMSET_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    let node_params = memset_params_from_cuda(node_params)?;
+    hip_call_cuda!(hipGraphMemsetNodeSetParams(node, &node_params));
+    Ok(())
+}
+
+pub(crate) unsafe fn exec_memset_node_set_params(
+    graph_exec: hipGraphExec_t,
+    node: hipGraphNode_t,
+    memset_params: *const CUDA_MEMSET_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    let memset_params = memset_params_from_cuda(memset_params)?;
+    hip_call_cuda!(hipGraphExecMemsetNodeSetParams(
+        graph_exec,
+        node,
+        &memset_params
+    ));
+    Ok(())
+}
+
+unsafe fn memset_params_from_cuda(
+    cuda: *const CUDA_MEMSET_NODE_PARAMS,
+) -> Result<hipMemsetParams, CUresult> {
+    let cuda = cuda.as_ref().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    Ok(hipMemsetParams {
+        dst: cuda.dst.0,
+        elementSize: cuda.elementSize,
+        height: cuda.height,
+        pitch: cuda.pitch,
+        value: cuda.value,
+        width: cuda.width,
+    })
+}
+
+pub(crate) unsafe fn add_host_node(
+    ph_graph_node: *mut hipGraphNode_t,
+    h_graph: hipGraph_t,
+    dependencies: *const hipGraphNode_t,
+    num_dependencies: usize,
+    node_params: *const CUDA_HOST_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    let node_params = host_params_from_cuda(node_params)?;
+    hip_call_cuda!(hipGraphAddHostNode(
+        ph_graph_node,
+        h_graph,
+        dependencies,
+        num_dependencies,
+        &node_params
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn host_node_get_params(
+    node: hipGraphNode_t,
+    node_params: *mut CUDA_HOST_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    if node_params == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let mut hip_params = mem::zeroed::<hipHostNodeParams>();
+    hip_call_cuda!(hipGraphHostNodeGetParams(node, &mut hip_params));
+    *node_params = CUDA_HOST_NODE_PARAMS {
+        fn_: mem::transmute(hip_params.fn_),
+        userData: hip_params.userData,
+    };
+    Ok(())
+}
+
+pub(crate) unsafe fn host_node_set_params(
+    node: hipGraphNode_t,
+    node_params: *const CUDA_HOST_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    let node_params = host_params_from_cuda(node_params)?;
+    hip_call_cuda!(hipGraphHostNodeSetParams(node, &node_params));
+    Ok(())
+}
+
+pub(crate) unsafe fn exec_host_node_set_params(
+    graph_exec: hipGraphExec_t,
+    node: hipGraphNode_t,
+    node_params: *const CUDA_HOST_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    let node_params = host_params_from_cuda(node_params)?;
+    hip_call_cuda!(hipGraphExecHostNodeSetParams(
+        graph_exec,
+        node,
+        &node_params
+    ));
+    Ok(())
+}
+
+unsafe fn host_params_from_cuda(
+    cuda: *const CUDA_HOST_NODE_PARAMS,
+) -> Result<hipHostNodeParams, CUresult> {
+    let cuda = cuda.as_ref().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    if cuda.fn_.is_none() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    // "system" and "C" calling conventions are the same on 64-bit targets
+    Ok(hipHostNodeParams {
+        fn_: mem::transmute(cuda.fn_),
+        userData: cuda.userData,
+    })
+}
+
+pub(crate) unsafe fn add_mem_alloc_node(
+    ph_graph_node: *mut hipGraphNode_t,
+    h_graph: hipGraph_t,
+    dependencies: *const hipGraphNode_t,
+    num_dependencies: usize,
+    node_params: *mut CUDA_MEM_ALLOC_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    let node_params = node_params
+        .as_mut()
+        .ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    let access_descs = if node_params.accessDescCount != 0 {
+        vmm::access_descs_from_cuda(node_params.accessDescs, node_params.accessDescCount)?
+    } else {
+        Vec::new()
+    };
+    let mut hip_params = hipMemAllocNodeParams {
+        poolProps: mempool::pool_props_from_cuda(&node_params.poolProps),
+        accessDescs: access_descs.as_ptr(),
+        accessDescCount: access_descs.len(),
+        bytesize: node_params.bytesize,
+        dptr: ptr::null_mut(),
+    };
+    hip_call_cuda!(hipGraphAddMemAllocNode(
+        ph_graph_node,
+        h_graph,
+        dependencies,
+        num_dependencies,
+        &mut hip_params
+    ));
+    node_params.dptr = CUdeviceptr_v2(hip_params.dptr);
+    Ok(())
+}
+
+pub(crate) unsafe fn mem_alloc_node_get_params(
+    node: hipGraphNode_t,
+    params_out: *mut CUDA_MEM_ALLOC_NODE_PARAMS,
+) -> Result<(), CUresult> {
+    if params_out == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let mut hip_params = mem::zeroed::<hipMemAllocNodeParams>();
+    hip_call_cuda!(hipGraphMemAllocNodeGetParams(node, &mut hip_params));
+    *params_out = CUDA_MEM_ALLOC_NODE_PARAMS {
+        poolProps: mempool::pool_props_to_cuda(&hip_params.poolProps),
+        // Layout of hipMemAccessDesc is the same as CUmemAccessDesc
+        accessDescs: hip_params.accessDescs.cast(),
+        accessDescCount: hip_params.accessDescCount,
+        bytesize: hip_params.bytesize,
+        dptr: CUdeviceptr_v2(hip_params.dptr),
+    };
+    Ok(())
+}
+
+pub(crate) unsafe fn mem_free_node_get_params(
+    node: hipGraphNode_t,
+    dptr_out: *mut hipDeviceptr_t,
+) -> Result<(), CUresult> {
+    if dptr_out == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    hip_call_cuda!(hipGraphMemFreeNodeGetParams(node, dptr_out.cast()));
+    Ok(())
+}
+
+// HIP has extra node types for copies from and to symbols, CUDA reports them
+// as ordinary copies
+pub(crate) unsafe fn node_get_type(
+    node: hipGraphNode_t,
+    type_: *mut CUgraphNodeType,
+) -> Result<(), CUresult> {
+    if type_ == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let mut hip_type = hipGraphNodeType::hipGraphNodeTypeEmpty;
+    hip_call_cuda!(hipGraphNodeGetType(node, &mut hip_type));
+    *type_ = match hip_type {
+        hipGraphNodeType::hipGraphNodeTypeMemcpyFromSymbol
+        | hipGraphNodeType::hipGraphNodeTypeMemcpyToSymbol => {
+            CUgraphNodeType::CU_GRAPH_NODE_TYPE_MEMCPY
+        }
+        hipGraphNodeType(value) => CUgraphNodeType(value as _),
+    };
+    Ok(())
+}
+
+pub(crate) unsafe fn exec_update(
+    graph_exec: hipGraphExec_t,
+    graph: hipGraph_t,
+    error_node_out: *mut hipGraphNode_t,
+    update_result_out: *mut hipGraphExecUpdateResult,
+) -> Result<(), CUresult> {
+    let mut error_node = ptr::null_mut();
+    let mut update_result = hipGraphExecUpdateResult::hipGraphExecUpdateSuccess;
+    let error = hipGraphExecUpdate(graph_exec, graph, &mut error_node, &mut update_result);
+    if let Some(error_node_out) = error_node_out.as_mut() {
+        *error_node_out = error_node;
+    }
+    if let Some(update_result_out) = update_result_out.as_mut() {
+        *update_result_out = update_result;
+    }
+    hip_call_cuda!(error);
+    Ok(())
+}
+
+pub(crate) unsafe fn exec_update_v2(
+    graph_exec: hipGraphExec_t,
+    graph: hipGraph_t,
+    result_info: *mut CUgraphExecUpdateResultInfo,
+) -> Result<(), CUresult> {
+    let mut error_node = ptr::null_mut();
+    let mut update_result = hipGraphExecUpdateResult::hipGraphExecUpdateSuccess;
+    let result = exec_update(graph_exec, graph, &mut error_node, &mut update_result);
+    if let Some(result_info) = result_info.as_mut() {
+        *result_info = CUgraphExecUpdateResultInfo {
+            // values are compatible
+            result: CUgraphExecUpdateResult(update_result.0 as _),
+            errorNode: error_node.cast(),
+            errorFromNode: ptr::null_mut(),
+        };
+    }
+    result
+}
+
 pub(crate) unsafe fn launch(
     graph: hipGraphExec_t,
     stream: *mut stream::Stream,
@@ -55,3 +665,12 @@ pub(crate) unsafe fn launch(
     hip_call_cuda!(hipGraphLaunch(graph, stream));
     Ok(())
 }
+
+pub(crate) unsafe fn upload(
+    graph: hipGraphExec_t,
+    stream: *mut stream::Stream,
+) -> Result<(), CUresult> {
+    let stream = stream::as_hip_stream(stream)?;
+    hip_call_cuda!(hipGraphUpload(graph, stream));
+    Ok(())
+}
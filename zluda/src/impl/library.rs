@@ -1,14 +1,22 @@
 // Library is a module that is not context-bound, see here:
 // https://developer.nvidia.com/blog/cuda-context-independent-module-loading/
 // It's supposed to be lazy-loaded for each device (depending on cuModuleGetLoadingMode(...)),
-// but we do eager loading right now for simplicity
-// TODO: make libraries lazy-loadable
+// we create a module for every device up-front, but with lazy loading those
+// modules compile their kernels only when they are first used
 use super::{
-    context, fold_cuda_errors,
+    context, fold_cuda_errors, function,
     module::{self, ModuleData},
     LiveCheck, ZludaObject, GLOBAL_STATE,
 };
 use cuda_types::{CUjit_option, CUlibraryOption, CUresult};
+use hip_runtime_sys::{hipDevice_t, hipDeviceptr_t, hipFunction_attribute};
+use rustc_hash::FxHashMap;
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::ptr;
+use std::sync::Mutex;
+use std::time::Instant;
+use zluda_dark_api::CUmoduleContent;
 
 pub(crate) type Library = LiveCheck<LibraryData>;
 
@@ -22,48 +30,142 @@ impl ZludaObject for LibraryData {
     fn drop_with_result(&mut self, _by_owner: bool) -> Result<(), CUresult> {
         fold_cuda_errors(
             self.modules
-                .iter_mut()
+                .values()
                 .map(|module| unsafe { LiveCheck::drop_box_with_result(*module, true) }),
         )
     }
 }
 
+pub(crate) type Kernel = LiveCheck<KernelData>;
+
+impl ZludaObject for KernelData {
+    #[cfg(target_pointer_width = "64")]
+    const LIVENESS_COOKIE: usize = 0x2c5e1f0a9b7d6e43;
+    #[cfg(target_pointer_width = "32")]
+    const LIVENESS_COOKIE: usize = 0x4a1d93e5;
+    const LIVENESS_FAIL: CUresult = CUresult::CUDA_ERROR_INVALID_HANDLE;
+
+    fn drop_with_result(&mut self, _by_owner: bool) -> Result<(), CUresult> {
+        Ok(())
+    }
+}
+
 pub(crate) struct LibraryData {
-    modules: Vec<*mut module::Module>,
+    // Device ordinal -> module loaded for that device
+    modules: BTreeMap<hipDevice_t, *mut module::Module>,
+    // Owned by the library, released together with it
+    kernels: Mutex<FxHashMap<CString, Box<Kernel>>>,
+}
+
+// Kernel is a context-independent function, it resolves to the function of
+// the module loaded for a given device
+pub(crate) struct KernelData {
+    library: *mut Library,
+    name: CString,
+}
+
+impl LibraryData {
+    fn module(&self, device: hipDevice_t) -> Result<*mut module::Module, CUresult> {
+        self.modules
+            .get(&device)
+            .copied()
+            .ok_or(CUresult::CUDA_ERROR_INVALID_DEVICE)
+    }
+}
+
+impl KernelData {
+    unsafe fn function(&self, device: hipDevice_t) -> Result<*mut function::Function, CUresult> {
+        let library = LiveCheck::as_result(self.library)?;
+        let mut function = ptr::null_mut();
+        module::get_function(&mut function, library.module(device)?, self.name.as_ptr())?;
+        Ok(function)
+    }
 }
 
 pub(crate) unsafe fn load_data(
     library: *mut *mut Library,
     code: *const ::std::os::raw::c_void,
-    // TODO: start handling JIT options
-    _jit_options: *mut CUjit_option,
-    _jit_options_values: *mut *mut ::std::os::raw::c_void,
-    _num_jit_options: ::std::os::raw::c_uint,
+    jit_options: *mut CUjit_option,
+    jit_options_values: *mut *mut ::std::os::raw::c_void,
+    num_jit_options: ::std::os::raw::c_uint,
+    library_options: *mut CUlibraryOption,
+    _library_option_values: *mut *mut ::std::os::raw::c_void,
+    num_library_options: ::std::os::raw::c_uint,
+) -> Result<(), CUresult> {
+    load_impl(
+        library,
+        || CUmoduleContent::from_ptr(code.cast()).map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE),
+        jit_options,
+        jit_options_values,
+        num_jit_options,
+        library_options,
+        num_library_options,
+    )
+}
+
+pub(crate) unsafe fn load_from_file(
+    library: *mut *mut Library,
+    file_name: *const ::std::os::raw::c_char,
+    jit_options: *mut CUjit_option,
+    jit_options_values: *mut *mut ::std::os::raw::c_void,
+    num_jit_options: ::std::os::raw::c_uint,
     library_options: *mut CUlibraryOption,
     _library_option_values: *mut *mut ::std::os::raw::c_void,
     num_library_options: ::std::os::raw::c_uint,
+) -> Result<(), CUresult> {
+    if file_name == ptr::null() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    load_impl(
+        library,
+        || Ok(CUmoduleContent::File(file_name)),
+        jit_options,
+        jit_options_values,
+        num_jit_options,
+        library_options,
+        num_library_options,
+    )
+}
+
+unsafe fn load_impl(
+    library: *mut *mut Library,
+    content: impl Fn() -> Result<CUmoduleContent, CUresult>,
+    jit_options: *mut CUjit_option,
+    jit_options_values: *mut *mut ::std::os::raw::c_void,
+    num_jit_options: ::std::os::raw::c_uint,
+    library_options: *mut CUlibraryOption,
+    num_library_options: ::std::os::raw::c_uint,
 ) -> Result<(), CUresult> {
     for option in std::slice::from_raw_parts(library_options, num_library_options as usize) {
         if !matches!(*option, CUlibraryOption::CU_LIBRARY_BINARY_IS_PRESERVED) {
             return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
         }
     }
+    let start = Instant::now();
+    let mut jit_options =
+        module::JitOptions::parse(num_jit_options, jit_options, jit_options_values)?;
     let global_state = GLOBAL_STATE.get()?;
     let modules = global_state
         .devices
         .iter()
-        .map(|device| {
+        .enumerate()
+        .map(|(index, device)| {
             let module_data = module::load_data_any(
                 None,
                 device.compilation_mode,
                 &device.comgr_isa,
-                zluda_dark_api::CUmoduleContent::from_ptr(code.cast())
-                    .map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)?,
+                content()?,
+                &mut jit_options,
             )?;
-            Ok(ModuleData::alloc(module_data))
+            Ok((index as hipDevice_t, ModuleData::alloc(module_data)))
         })
-        .collect::<Result<Vec<_>, _>>()?;
-    let library_data = LibraryData { modules };
+        .collect::<Result<BTreeMap<_, _>, _>>();
+    jit_options.write_back(start);
+    let modules = modules?;
+    let library_data = LibraryData {
+        modules,
+        kernels: Mutex::new(FxHashMap::default()),
+    };
     *library = Box::into_raw(Box::new(LiveCheck::new(library_data)));
     Ok(())
 }
@@ -73,16 +175,95 @@ pub(crate) unsafe fn get_module(
     library: *mut Library,
 ) -> Result<(), CUresult> {
     let library = LiveCheck::as_result(library)?;
-    context::with_current(|ctx| {
-        let device = ctx.device as usize;
-        let module = library
-            .modules
-            .get(device)
-            .copied()
-            .ok_or(CUresult::CUDA_ERROR_UNKNOWN)?;
-        *output = module;
-        Ok(())
-    })?
+    let device = context::with_current(|ctx| ctx.device)?;
+    *output = library.module(device)?;
+    Ok(())
+}
+
+pub(crate) unsafe fn get_kernel(
+    kernel: *mut *mut Kernel,
+    library: *mut Library,
+    name: *const ::std::os::raw::c_char,
+) -> Result<(), CUresult> {
+    if kernel == ptr::null_mut() || name == ptr::null() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let library_data = LiveCheck::as_result(library)?;
+    let name = CStr::from_ptr(name);
+    // Every module is loaded from the same image, so they all have the same kernels
+    let module = library_data
+        .modules
+        .values()
+        .next()
+        .ok_or(CUresult::CUDA_ERROR_NO_DEVICE)?;
+    if !LiveCheck::as_result(*module)?
+        .kernel_names
+        .iter()
+        .any(|kernel_name| &**kernel_name == name)
+    {
+        return Err(CUresult::CUDA_ERROR_NOT_FOUND);
+    }
+    let mut kernels = library_data
+        .kernels
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    let result: &Kernel = kernels.entry(name.to_owned()).or_insert_with(|| {
+        Box::new(LiveCheck::new(KernelData {
+            library,
+            name: name.to_owned(),
+        }))
+    });
+    *kernel = result as *const Kernel as *mut _;
+    Ok(())
+}
+
+pub(crate) unsafe fn kernel_get_function(
+    function: *mut *mut function::Function,
+    kernel: *mut Kernel,
+) -> Result<(), CUresult> {
+    if function == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let kernel = LiveCheck::as_result(kernel)?;
+    let device = context::with_current(|ctx| ctx.device)?;
+    *function = kernel.function(device)?;
+    Ok(())
+}
+
+pub(crate) unsafe fn kernel_get_attribute(
+    pi: *mut i32,
+    attrib: hipFunction_attribute,
+    kernel: *mut Kernel,
+    device: hipDevice_t,
+) -> Result<(), CUresult> {
+    let kernel = LiveCheck::as_result(kernel)?;
+    function::get_attribute(pi, attrib, kernel.function(device)?)
+}
+
+pub(crate) unsafe fn get_global(
+    dptr: *mut hipDeviceptr_t,
+    bytes: *mut usize,
+    library: *mut Library,
+    name: *const ::std::os::raw::c_char,
+) -> Result<(), CUresult> {
+    let library = LiveCheck::as_result(library)?;
+    let device = context::with_current(|ctx| ctx.device)?;
+    module::get_global(dptr, bytes, library.module(device)?, name)
+}
+
+// Managed variables are not supported, the PTX parser does not accept
+// .attribute(.managed)
+pub(crate) unsafe fn get_managed(
+    dptr: *mut hipDeviceptr_t,
+    bytes: *mut usize,
+    library: *mut Library,
+    name: *const ::std::os::raw::c_char,
+) -> Result<(), CUresult> {
+    if (dptr == ptr::null_mut() && bytes == ptr::null_mut()) || name == ptr::null() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    LiveCheck::as_result(library)?;
+    Err(CUresult::CUDA_ERROR_NOT_SUPPORTED)
 }
 
 pub(crate) unsafe fn unload(library: *mut Library) -> Result<(), CUresult> {
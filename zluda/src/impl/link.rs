@@ -1,6 +1,6 @@
 use super::{context, module, LiveCheck, ZludaObject, GLOBAL_STATE};
 use cuda_types::*;
-use std::{borrow::Cow, ptr, sync::Mutex};
+use std::{borrow::Cow, ptr, sync::Mutex, time::Instant};
 
 pub(crate) type LinkState = LiveCheck<LinkStateData>;
 
@@ -18,6 +18,9 @@ impl ZludaObject for LinkStateData {
 
 pub(crate) struct LinkStateData {
     ptx_modules: Mutex<Vec<Cow<'static, str>>>,
+    // CUDA requires option values passed to cuLinkCreate to stay valid until
+    // cuLinkDestroy, logs and wall time are written on cuLinkComplete
+    jit_options: Mutex<module::JitOptions>,
 }
 
 pub(crate) unsafe fn add_data(
@@ -26,11 +29,21 @@ pub(crate) unsafe fn add_data(
     data: *mut ::std::os::raw::c_void,
     mut size: usize,
     _name: *const ::std::os::raw::c_char,
-    _num_options: ::std::os::raw::c_uint,
-    _options: *mut CUjit_option,
-    _option_values: *mut *mut ::std::os::raw::c_void,
+    num_options: ::std::os::raw::c_uint,
+    options: *mut CUjit_option,
+    option_values: *mut *mut ::std::os::raw::c_void,
 ) -> Result<(), CUresult> {
+    let start = Instant::now();
     let state = LiveCheck::as_result(state)?;
+    // PTX is compiled only in cuLinkComplete, so logs for this input are empty
+    let input_options = module::JitOptions::parse(num_options, options, option_values)?;
+    state
+        .jit_options
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?
+        .compilation
+        .merge(&input_options.compilation);
+    input_options.write_back(start);
     match type_ {
         CUjitInputType::CU_JIT_INPUT_PTX => {
             let data = data.cast::<u8>();
@@ -68,11 +81,16 @@ pub(crate) unsafe fn complete(
     if cubin_out == std::ptr::null_mut() || size_out == std::ptr::null_mut() {
         return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
     }
+    let start = Instant::now();
     let state = LiveCheck::as_result(state)?;
     let modules = state
         .ptx_modules
         .lock()
         .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    let mut jit_options = state
+        .jit_options
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
     let device = context::with_current(|ctx| ctx.device)?;
     let global_state = GLOBAL_STATE.get()?;
     let device_object = global_state.device(device)?;
@@ -81,7 +99,10 @@ pub(crate) unsafe fn complete(
         device_object.compilation_mode,
         &device_object.comgr_isa,
         &modules,
-    )?;
+        &mut jit_options,
+    );
+    jit_options.write_back(start);
+    let module = module?;
     let module = module.into_boxed_slice();
     let size = module.len();
     let ptr = Box::into_raw(module);
@@ -91,13 +112,15 @@ pub(crate) unsafe fn complete(
 }
 
 pub(crate) unsafe fn create(
-    _num_options: ::std::os::raw::c_uint,
-    _options: *mut CUjit_option,
-    _option_values: *mut *mut ::std::os::raw::c_void,
+    num_options: ::std::os::raw::c_uint,
+    options: *mut CUjit_option,
+    option_values: *mut *mut ::std::os::raw::c_void,
     state_out: *mut *mut LinkState,
 ) -> Result<(), CUresult> {
+    let jit_options = module::JitOptions::parse(num_options, options, option_values)?;
     let link_state = LinkState::new(LinkStateData {
         ptx_modules: Mutex::new(Vec::new()),
+        jit_options: Mutex::new(jit_options),
     });
     let link_state = Box::into_raw(Box::new(link_state));
     *state_out = link_state;
@@ -1,5 +1,5 @@
 use super::stream::Stream;
-use super::{hipfix, stream};
+use super::{hipfix, stream, vmm};
 use crate::hip_call_cuda;
 use crate::r#impl::{memcpy2d_from_cuda, GLOBAL_STATE};
 use cuda_types::*;
@@ -60,7 +60,12 @@ pub(crate) unsafe fn get_address_range(
 ) -> hipError_t {
     let mut base = hipDeviceptr_t(ptr::null_mut());
     let mut size = 0;
-    let result = hipMemGetAddressRange(&mut base, &mut size, dptr);
+    let result = if let Some(range) = vmm::get_address_range(dptr) {
+        (base, size) = range;
+        hipError_t::hipSuccess
+    } else {
+        hipMemGetAddressRange(&mut base, &mut size, dptr)
+    };
     if pbase != ptr::null_mut() {
         *pbase = base;
     }
@@ -186,23 +191,139 @@ pub(crate) unsafe fn copy_async(
     Ok(())
 }
 
-pub(crate) unsafe fn free_async(
-    dptr: hipDeviceptr_t,
+pub(crate) unsafe fn prefetch_async(
+    dev_ptr: hipDeviceptr_t,
+    count: usize,
+    dst_device: hipDevice_t,
     stream: *mut stream::Stream,
 ) -> Result<(), CUresult> {
     let hip_stream = stream::as_hip_stream(stream)?;
-    hip_call_cuda! { hipFreeAsync(dptr.0, hip_stream) };
+    hip_call_cuda! { hipMemPrefetchAsync(dev_ptr.0, count, dst_device, hip_stream) };
     Ok(())
 }
 
-pub(crate) unsafe fn prefetch_async(
+// Same values as hipCpuDeviceId and hipInvalidDeviceId
+const CU_DEVICE_CPU: hipDevice_t = -1;
+const CU_DEVICE_INVALID: hipDevice_t = -2;
+
+pub(crate) unsafe fn advise(
     dev_ptr: hipDeviceptr_t,
     count: usize,
-    dst_device: hipDevice_t,
-    stream: *mut stream::Stream,
+    advice: hipMemoryAdvise,
+    device: hipDevice_t,
 ) -> Result<(), CUresult> {
-    let hip_stream = stream::as_hip_stream(stream)?;
-    hip_call_cuda! { hipMemPrefetchAsync(dev_ptr.0, count, dst_device, hip_stream) };
+    hip_call_cuda! { hipMemAdvise(dev_ptr.0, count, advice, device) };
+    Ok(())
+}
+
+pub(crate) unsafe fn advise_v2(
+    dev_ptr: hipDeviceptr_t,
+    count: usize,
+    advice: hipMemoryAdvise,
+    location: CUmemLocation,
+) -> Result<(), CUresult> {
+    let device = match location.type_ {
+        CUmemLocationType::CU_MEM_LOCATION_TYPE_DEVICE => location.id,
+        // HIP does not differentiate between NUMA nodes
+        CUmemLocationType::CU_MEM_LOCATION_TYPE_HOST
+        | CUmemLocationType::CU_MEM_LOCATION_TYPE_HOST_NUMA
+        | CUmemLocationType::CU_MEM_LOCATION_TYPE_HOST_NUMA_CURRENT => CU_DEVICE_CPU,
+        _ => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    };
+    advise(dev_ptr, count, advice, device)
+}
+
+pub(crate) unsafe fn range_get_attribute(
+    data: *mut std::ffi::c_void,
+    data_size: usize,
+    attribute: CUmem_range_attribute,
+    dev_ptr: hipDeviceptr_t,
+    count: usize,
+) -> Result<(), CUresult> {
+    if data == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let (hip_attribute, location_type) = match attribute {
+        // values are compatible
+        CUmem_range_attribute::CU_MEM_RANGE_ATTRIBUTE_READ_MOSTLY
+        | CUmem_range_attribute::CU_MEM_RANGE_ATTRIBUTE_PREFERRED_LOCATION
+        | CUmem_range_attribute::CU_MEM_RANGE_ATTRIBUTE_ACCESSED_BY
+        | CUmem_range_attribute::CU_MEM_RANGE_ATTRIBUTE_LAST_PREFETCH_LOCATION => {
+            hip_call_cuda! { hipMemRangeGetAttribute(
+                data,
+                data_size,
+                hipMemRangeAttribute(attribute.0),
+                dev_ptr.0,
+                count
+            ) };
+            return Ok(());
+        }
+        CUmem_range_attribute::CU_MEM_RANGE_ATTRIBUTE_PREFERRED_LOCATION_TYPE => (
+            hipMemRangeAttribute::hipMemRangeAttributePreferredLocation,
+            true,
+        ),
+        CUmem_range_attribute::CU_MEM_RANGE_ATTRIBUTE_PREFERRED_LOCATION_ID => (
+            hipMemRangeAttribute::hipMemRangeAttributePreferredLocation,
+            false,
+        ),
+        CUmem_range_attribute::CU_MEM_RANGE_ATTRIBUTE_LAST_PREFETCH_LOCATION_TYPE => (
+            hipMemRangeAttribute::hipMemRangeAttributeLastPrefetchLocation,
+            true,
+        ),
+        CUmem_range_attribute::CU_MEM_RANGE_ATTRIBUTE_LAST_PREFETCH_LOCATION_ID => (
+            hipMemRangeAttribute::hipMemRangeAttributeLastPrefetchLocation,
+            false,
+        ),
+        _ => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    };
+    if data_size != mem::size_of::<i32>() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    // HIP only knows about device ordinals, split them into CUDA's (type, id) pairs
+    let mut device: hipDevice_t = CU_DEVICE_INVALID;
+    hip_call_cuda! { hipMemRangeGetAttribute(
+        &mut device as *mut _ as _,
+        mem::size_of::<hipDevice_t>(),
+        hip_attribute,
+        dev_ptr.0,
+        count
+    ) };
+    if location_type {
+        *(data as *mut CUmemLocationType) = match device {
+            CU_DEVICE_CPU => CUmemLocationType::CU_MEM_LOCATION_TYPE_HOST,
+            CU_DEVICE_INVALID => CUmemLocationType::CU_MEM_LOCATION_TYPE_INVALID,
+            _ => CUmemLocationType::CU_MEM_LOCATION_TYPE_DEVICE,
+        };
+    } else {
+        *(data as *mut i32) = device;
+    }
+    Ok(())
+}
+
+pub(crate) unsafe fn range_get_attributes(
+    data: *mut *mut std::ffi::c_void,
+    data_sizes: *mut usize,
+    attributes: *mut CUmem_range_attribute,
+    num_attributes: usize,
+    dev_ptr: hipDeviceptr_t,
+    count: usize,
+) -> Result<(), CUresult> {
+    if data == ptr::null_mut()
+        || data_sizes == ptr::null_mut()
+        || attributes == ptr::null_mut()
+        || num_attributes == 0
+    {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    for i in 0..num_attributes {
+        range_get_attribute(
+            *data.add(i),
+            *data_sizes.add(i),
+            *attributes.add(i),
+            dev_ptr,
+            count,
+        )?;
+    }
     Ok(())
 }
 
@@ -0,0 +1,886 @@
+// Stream-ordered allocator. Memory pools are backed by HIP memory pools when
+// the device supports them. Otherwise (or when the pool is created with
+// properties HIP pools can't honor, e.g. maxSize) we fall back to our own
+// suballocator: it keeps freed blocks around and hands them out again once
+// the stream that freed them has moved past the free. Only HIP pools can be
+// shared between processes, so shareable pools with maxSize are not supported
+use super::{context, hipfix, stream, vmm, LiveCheck, ZludaObject, GLOBAL_STATE};
+use crate::hip_call_cuda;
+use cuda_types::*;
+use hip_runtime_sys::*;
+use std::{
+    collections::BTreeMap,
+    ffi::c_void,
+    ptr,
+    sync::{Mutex, MutexGuard},
+};
+
+pub(crate) type MemoryPool = LiveCheck<MemoryPoolData>;
+
+impl ZludaObject for MemoryPoolData {
+    #[cfg(target_pointer_width = "64")]
+    const LIVENESS_COOKIE: usize = 0x6c1f4e0a2b9d7358;
+    #[cfg(target_pointer_width = "32")]
+    const LIVENESS_COOKIE: usize = 0x2b9d7358;
+    const LIVENESS_FAIL: CUresult = CUresult::CUDA_ERROR_INVALID_VALUE;
+
+    fn drop_with_result(&mut self, _by_owner: bool) -> Result<(), CUresult> {
+        match self.backend {
+            Backend::Hip { base, owned: true } => {
+                hip_call_cuda!(hipMemPoolDestroy(base));
+                Ok(())
+            }
+            Backend::Hip { owned: false, .. } => Ok(()),
+            Backend::Fallback => {
+                let mut state = self.lock()?;
+                unsafe { state.release(0) }
+            }
+        }
+    }
+}
+
+pub(crate) struct MemoryPoolData {
+    device: hipDevice_t,
+    is_default: bool,
+    backend: Backend,
+    state: Mutex<PoolState>,
+}
+
+enum Backend {
+    Hip { base: hipMemPool_t, owned: bool },
+    Fallback,
+}
+
+// For HIP pools only `used` and `used_high` are tracked and only used if HIP
+// can't answer the attribute query itself
+struct PoolState {
+    used: usize,
+    used_high: usize,
+    reserved: usize,
+    reserved_high: usize,
+    // Unused memory of the fallback pool above this is returned to the OS at
+    // synchronization points. All of it is returned on cuMemPoolTrimTo(...),
+    // when the pool is destroyed or when an allocation would fail otherwise
+    release_threshold: u64,
+    reuse_follow_event_dependencies: i32,
+    reuse_allow_opportunistic: i32,
+    reuse_allow_internal_dependencies: i32,
+    max_size: usize,
+    // Blocks of completed frees, keyed by size
+    free: BTreeMap<usize, Vec<usize>>,
+    pending: Vec<PendingFree>,
+    // Set by cuMemPoolDestroy(...) while allocations are outstanding, the pool
+    // is destroyed once the last of them is freed
+    destroyed: bool,
+}
+
+// Block freed by cuMemFreeAsync(...) that is not safe to reuse until `event`
+// completes
+struct PendingFree {
+    ptr: usize,
+    size: usize,
+    stream: hipStream_t,
+    event: hipEvent_t,
+}
+
+// Per-device default and current pool, created on the first use
+pub(crate) struct DevicePools {
+    supported: bool,
+    pools: Mutex<(*mut MemoryPool, *mut MemoryPool)>,
+}
+
+// Every live allocation made from a pool: pointer -> (pool, block size).
+// cuMemFreeAsync(...) does not tell us which pool the pointer comes from
+static ALLOCATIONS: Mutex<BTreeMap<usize, (usize, usize)>> = Mutex::new(BTreeMap::new());
+
+// Every live pool with the fallback backend, their unused memory is released
+// at synchronization points
+static FALLBACK_POOLS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+// Sizes of fallback pool blocks are rounded up to this. Free blocks are
+// reused for allocations up to twice smaller than the block
+const BLOCK_ALIGNMENT: usize = 512;
+
+impl DevicePools {
+    pub(crate) fn new(device: hipDevice_t) -> Self {
+        let mut supported = 0;
+        let error = unsafe {
+            hipDeviceGetAttribute(
+                &mut supported,
+                hipDeviceAttribute_t::hipDeviceAttributeMemoryPoolsSupported,
+                device,
+            )
+        };
+        Self {
+            supported: error == hipError_t::hipSuccess && supported != 0,
+            pools: Mutex::new((ptr::null_mut(), ptr::null_mut())),
+        }
+    }
+
+    unsafe fn default_pool(
+        &self,
+        pools: &mut (*mut MemoryPool, *mut MemoryPool),
+        device: hipDevice_t,
+    ) -> Result<*mut MemoryPool, CUresult> {
+        if pools.0 == ptr::null_mut() {
+            let backend = if self.supported {
+                let mut base = ptr::null_mut();
+                hip_call_cuda!(hipDeviceGetDefaultMemPool(&mut base, device));
+                Backend::Hip { base, owned: false }
+            } else {
+                Backend::Fallback
+            };
+            pools.0 = new_pool(MemoryPoolData::new(device, true, backend, 0))?;
+        }
+        Ok(pools.0)
+    }
+}
+
+impl MemoryPoolData {
+    fn new(device: hipDevice_t, is_default: bool, backend: Backend, max_size: usize) -> Self {
+        Self {
+            device,
+            is_default,
+            backend,
+            state: Mutex::new(PoolState {
+                used: 0,
+                used_high: 0,
+                reserved: 0,
+                reserved_high: 0,
+                release_threshold: 0,
+                reuse_follow_event_dependencies: 1,
+                reuse_allow_opportunistic: 1,
+                reuse_allow_internal_dependencies: 1,
+                max_size,
+                free: BTreeMap::new(),
+                pending: Vec::new(),
+                destroyed: false,
+            }),
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, PoolState>, CUresult> {
+        self.state.lock().map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)
+    }
+
+    unsafe fn alloc(&self, size: usize, stream: hipStream_t) -> Result<(usize, usize), CUresult> {
+        let size = hipfix::alloc_round_up(size);
+        match self.backend {
+            Backend::Hip { base, .. } => {
+                let mut ptr = ptr::null_mut();
+                hip_call_cuda!(hipMallocFromPoolAsync(&mut ptr, size, base, stream));
+                let mut state = self.lock()?;
+                state.used += size;
+                state.used_high = state.used_high.max(state.used);
+                Ok((ptr as usize, size))
+            }
+            Backend::Fallback => {
+                let size = (size.max(1) + BLOCK_ALIGNMENT - 1) / BLOCK_ALIGNMENT * BLOCK_ALIGNMENT;
+                let mut state = self.lock()?;
+                let block = match state.reuse(size, stream)? {
+                    Some(block) => block,
+                    None => state.alloc_block(size)?,
+                };
+                state.used += block.1;
+                state.used_high = state.used_high.max(state.used);
+                Ok(block)
+            }
+        }
+    }
+
+    unsafe fn free(&self, ptr: usize, size: usize, stream: hipStream_t) -> Result<(), CUresult> {
+        match self.backend {
+            Backend::Hip { .. } => {
+                hip_call_cuda!(hipFreeAsync(ptr as _, stream));
+                let mut state = self.lock()?;
+                state.used = state.used.saturating_sub(size);
+                Ok(())
+            }
+            Backend::Fallback => {
+                let mut event = ptr::null_mut();
+                hip_call_cuda!(hipEventCreateWithFlags(&mut event, hipEventDisableTiming));
+                let error = hipEventRecord(event, stream);
+                if error != hipError_t::hipSuccess {
+                    let _ = hipEventDestroy(event);
+                    return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+                }
+                let mut state = self.lock()?;
+                state.used = state.used.saturating_sub(size);
+                state.pending.push(PendingFree {
+                    ptr,
+                    size,
+                    stream,
+                    event,
+                });
+                Ok(())
+            }
+        }
+    }
+}
+
+impl PoolState {
+    // In order of preference: block freed earlier on the same stream, block
+    // whose free has completed, block freed on another stream (the allocating
+    // stream is made to wait for the free)
+    unsafe fn reuse(
+        &mut self,
+        size: usize,
+        stream: hipStream_t,
+    ) -> Result<Option<(usize, usize)>, CUresult> {
+        let fits = |block_size: usize| block_size >= size && block_size / 2 < size;
+        if let Some(index) = self
+            .pending
+            .iter()
+            .position(|free| free.stream == stream && fits(free.size))
+        {
+            return Ok(Some(self.take_pending(index)));
+        }
+        self.poll();
+        if self.reuse_allow_opportunistic != 0 {
+            let free_block = self
+                .free
+                .range_mut(size..size.saturating_mul(2))
+                .find_map(|(block_size, ptrs)| Some((ptrs.pop()?, *block_size)));
+            if let Some(block) = free_block {
+                self.free.retain(|_, ptrs| !ptrs.is_empty());
+                return Ok(Some(block));
+            }
+        }
+        if self.reuse_allow_internal_dependencies != 0 {
+            if let Some(index) = self.pending.iter().position(|free| fits(free.size)) {
+                hip_call_cuda!(hipStreamWaitEvent(stream, self.pending[index].event, 0));
+                return Ok(Some(self.take_pending(index)));
+            }
+        }
+        Ok(None)
+    }
+
+    unsafe fn take_pending(&mut self, index: usize) -> (usize, usize) {
+        let free = self.pending.swap_remove(index);
+        let _ = hipEventDestroy(free.event);
+        (free.ptr, free.size)
+    }
+
+    // Moves blocks whose frees have completed to the free list
+    unsafe fn poll(&mut self) {
+        let mut index = 0;
+        while index < self.pending.len() {
+            if hipEventQuery(self.pending[index].event) == hipError_t::hipSuccess {
+                let (ptr, size) = self.take_pending(index);
+                self.free.entry(size).or_default().push(ptr);
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    unsafe fn alloc_block(&mut self, size: usize) -> Result<(usize, usize), CUresult> {
+        if self.max_size != 0 && self.reserved + size > self.max_size {
+            self.release(self.max_size.saturating_sub(size))?;
+            if self.reserved + size > self.max_size {
+                return Err(CUresult::CUDA_ERROR_OUT_OF_MEMORY);
+            }
+        }
+        let mut ptr = ptr::null_mut();
+        if hipMalloc(&mut ptr, size) != hipError_t::hipSuccess {
+            // Retry after returning all unused memory to the OS
+            self.release(0)?;
+            hip_call_cuda!(hipMalloc(&mut ptr, size));
+        }
+        self.reserved += size;
+        self.reserved_high = self.reserved_high.max(self.reserved);
+        Ok((ptr as usize, size))
+    }
+
+    // Frees unused blocks until at most `keep` bytes are reserved, waits for
+    // all pending frees to complete
+    unsafe fn release(&mut self, keep: usize) -> Result<(), CUresult> {
+        for index in 0..self.pending.len() {
+            hip_call_cuda!(hipEventSynchronize(self.pending[index].event));
+        }
+        self.trim(keep)
+    }
+
+    // Frees blocks of completed frees until at most `keep` bytes are reserved
+    unsafe fn trim(&mut self, keep: usize) -> Result<(), CUresult> {
+        self.poll();
+        while self.reserved > keep {
+            let (size, ptr) = match self.free.iter_mut().next_back() {
+                Some((size, ptrs)) => (*size, ptrs.pop()),
+                None => break,
+            };
+            match ptr {
+                Some(ptr) => {
+                    hip_call_cuda!(hipFree(ptr as _));
+                    self.reserved -= size;
+                }
+                None => {
+                    self.free.remove(&size);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+unsafe fn new_pool(pool_data: MemoryPoolData) -> Result<*mut MemoryPool, CUresult> {
+    let is_fallback = matches!(pool_data.backend, Backend::Fallback);
+    let pool = Box::into_raw(Box::new(LiveCheck::new(pool_data)));
+    if is_fallback {
+        FALLBACK_POOLS
+            .lock()
+            .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?
+            .push(pool as usize);
+    }
+    Ok(pool)
+}
+
+unsafe fn drop_pool(pool: *mut MemoryPool) -> Result<(), CUresult> {
+    if let Ok(mut pools) = FALLBACK_POOLS.lock() {
+        pools.retain(|fallback_pool| *fallback_pool != pool as usize);
+    }
+    LiveCheck::drop_box_with_result(pool, false)
+}
+
+// Called at synchronization points (cuCtxSynchronize(...),
+// cuStreamSynchronize(...) and cuEventSynchronize(...)). HIP pools do this on
+// their own
+pub(crate) unsafe fn release_unused() -> Result<(), CUresult> {
+    let pools = FALLBACK_POOLS
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    for pool in pools.iter() {
+        let pool = LiveCheck::as_result(*pool as *mut MemoryPool)?;
+        let mut state = pool.lock()?;
+        let keep = state.release_threshold.min(usize::MAX as u64) as usize;
+        state.trim(keep)?;
+    }
+    Ok(())
+}
+
+// Only HIP pools can be exported
+pub(crate) unsafe fn supported_handle_types(device: hipDevice_t) -> Result<i32, CUresult> {
+    if !GLOBAL_STATE.get()?.device(device)?.memory_pools.supported {
+        return Ok(0);
+    }
+    #[cfg(not(feature = "rocm5"))]
+    {
+        let mut handle_types = 0;
+        hip_call_cuda!(hipDeviceGetAttribute(
+            &mut handle_types,
+            hipDeviceAttribute_t::hipDeviceAttributeMemoryPoolSupportedHandleTypes,
+            device
+        ));
+        Ok(handle_types)
+    }
+    #[cfg(feature = "rocm5")]
+    {
+        Ok(if cfg!(windows) {
+            0
+        } else {
+            CUmemAllocationHandleType::CU_MEM_HANDLE_TYPE_POSIX_FILE_DESCRIPTOR.0 as i32
+        })
+    }
+}
+
+unsafe fn stream_device(stream: *mut stream::Stream) -> Result<hipDevice_t, CUresult> {
+    if stream::as_default_stream(stream).is_some() {
+        context::with_current(|ctx| ctx.device)
+    } else {
+        let ctx = LiveCheck::as_result(stream)?.ctx;
+        Ok(LiveCheck::as_result(ctx)?.device)
+    }
+}
+
+pub(crate) unsafe fn get_default(
+    pool: *mut *mut MemoryPool,
+    device: hipDevice_t,
+) -> Result<(), CUresult> {
+    if pool == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let device_pools = &GLOBAL_STATE.get()?.device(device)?.memory_pools;
+    let mut pools = device_pools
+        .pools
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    *pool = device_pools.default_pool(&mut pools, device)?;
+    Ok(())
+}
+
+pub(crate) unsafe fn get_current(
+    pool: *mut *mut MemoryPool,
+    device: hipDevice_t,
+) -> Result<(), CUresult> {
+    if pool == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    *pool = current_pool(device)?;
+    Ok(())
+}
+
+unsafe fn current_pool(device: hipDevice_t) -> Result<*mut MemoryPool, CUresult> {
+    let device_pools = &GLOBAL_STATE.get()?.device(device)?.memory_pools;
+    let mut pools = device_pools
+        .pools
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    if pools.1 != ptr::null_mut() {
+        Ok(pools.1)
+    } else {
+        device_pools.default_pool(&mut pools, device)
+    }
+}
+
+pub(crate) unsafe fn set_current(
+    device: hipDevice_t,
+    pool: *mut MemoryPool,
+) -> Result<(), CUresult> {
+    let pool_data = LiveCheck::as_result(pool)?;
+    if pool_data.device != device {
+        return Err(CUresult::CUDA_ERROR_INVALID_DEVICE);
+    }
+    let device_pools = &GLOBAL_STATE.get()?.device(device)?.memory_pools;
+    let mut pools = device_pools
+        .pools
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    if let Backend::Hip { base, .. } = pool_data.backend {
+        hip_call_cuda!(hipDeviceSetMemPool(device, base));
+    }
+    pools.1 = pool;
+    Ok(())
+}
+
+pub(crate) unsafe fn create(
+    pool: *mut *mut MemoryPool,
+    props: *const CUmemPoolProps,
+) -> Result<(), CUresult> {
+    let props = match props.as_ref() {
+        Some(props) => props,
+        None => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    };
+    if pool == ptr::null_mut()
+        || props.location.type_ != CUmemLocationType::CU_MEM_LOCATION_TYPE_DEVICE
+    {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let device = props.location.id;
+    let supported = GLOBAL_STATE.get()?.device(device)?.memory_pools.supported;
+    let shareable = props.handleTypes != CUmemAllocationHandleType::CU_MEM_HANDLE_TYPE_NONE;
+    if shareable && (!supported || props.maxSize != 0) {
+        return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
+    }
+    let backend = if supported && props.maxSize == 0 {
+        let mut base = ptr::null_mut();
+        hip_call_cuda!(hipMemPoolCreate(&mut base, &pool_props_from_cuda(props)));
+        Backend::Hip { base, owned: true }
+    } else {
+        Backend::Fallback
+    };
+    *pool = new_pool(MemoryPoolData::new(device, false, backend, props.maxSize))?;
+    Ok(())
+}
+
+pub(crate) unsafe fn destroy(pool: *mut MemoryPool) -> Result<(), CUresult> {
+    let pool_data = LiveCheck::as_result(pool)?;
+    if pool_data.is_default || pool_data.lock()?.destroyed {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    if let Ok(mut pools) = GLOBAL_STATE
+        .get()?
+        .device(pool_data.device)?
+        .memory_pools
+        .pools
+        .lock()
+    {
+        if pools.1 == pool {
+            pools.1 = ptr::null_mut();
+        }
+    }
+    // Like in CUDA, the pool goes away once outstanding allocations are freed
+    let allocations = ALLOCATIONS
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    if allocations
+        .values()
+        .any(|(allocation_pool, _)| *allocation_pool == pool as usize)
+    {
+        pool_data.lock()?.destroyed = true;
+        return Ok(());
+    }
+    drop(allocations);
+    drop_pool(pool)
+}
+
+pub(crate) unsafe fn alloc_async(
+    dptr: *mut hipDeviceptr_t,
+    bytesize: usize,
+    stream: *mut stream::Stream,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    let pool = current_pool(stream_device(stream)?)?;
+    alloc_from_pool_async(dptr, bytesize, pool, stream, default_stream_per_thread)
+}
+
+pub(crate) unsafe fn alloc_from_pool_async(
+    dptr: *mut hipDeviceptr_t,
+    bytesize: usize,
+    pool: *mut MemoryPool,
+    stream: *mut stream::Stream,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    if dptr == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let pool_data = LiveCheck::as_result(pool)?;
+    if pool_data.lock()?.destroyed {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    let (ptr, size) = pool_data.alloc(bytesize, hip_stream)?;
+    ALLOCATIONS
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?
+        .insert(ptr, (pool as usize, size));
+    *dptr = hipDeviceptr_t(ptr as _);
+    Ok(())
+}
+
+pub(crate) unsafe fn free_async(
+    dptr: hipDeviceptr_t,
+    stream: *mut stream::Stream,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    if !free_from_pool(dptr.0 as usize, hip_stream)? {
+        hip_call_cuda!(hipFreeAsync(dptr.0, hip_stream));
+    }
+    Ok(())
+}
+
+// cuMemFree(...) of memory allocated from a pool. It's synchronous, so the
+// free is ordered on the legacy default stream and waited for
+pub(crate) unsafe fn free(dptr: hipDeviceptr_t) -> Result<(), CUresult> {
+    if free_from_pool(dptr.0 as usize, ptr::null_mut())? {
+        hip_call_cuda!(hipStreamSynchronize(ptr::null_mut()));
+    } else {
+        hip_call_cuda!(hipFree(dptr.0));
+    }
+    Ok(())
+}
+
+// Returns false if `ptr` was not allocated from a pool
+unsafe fn free_from_pool(ptr: usize, stream: hipStream_t) -> Result<bool, CUresult> {
+    let mut allocations = ALLOCATIONS
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    let (pool, size) = match allocations.remove(&ptr) {
+        Some(allocation) => allocation,
+        None => return Ok(false),
+    };
+    let pool = pool as *mut MemoryPool;
+    let pool_data = LiveCheck::as_result(pool)?;
+    pool_data.free(ptr, size, stream)?;
+    if pool_data.lock()?.destroyed
+        && !allocations
+            .values()
+            .any(|(allocation_pool, _)| *allocation_pool == pool as usize)
+    {
+        drop(allocations);
+        drop_pool(pool)?;
+    }
+    Ok(true)
+}
+
+pub(crate) unsafe fn trim_to(
+    pool: *mut MemoryPool,
+    min_bytes_to_keep: usize,
+) -> Result<(), CUresult> {
+    let pool = LiveCheck::as_result(pool)?;
+    match pool.backend {
+        Backend::Hip { base, .. } => {
+            hip_call_cuda!(hipMemPoolTrimTo(base, min_bytes_to_keep));
+            Ok(())
+        }
+        Backend::Fallback => pool.lock()?.release(min_bytes_to_keep),
+    }
+}
+
+pub(crate) unsafe fn get_attribute(
+    pool: *mut MemoryPool,
+    attr: CUmemPool_attribute,
+    value: *mut c_void,
+) -> Result<(), CUresult> {
+    let pool = LiveCheck::as_result(pool)?;
+    if value == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    if let Backend::Hip { base, .. } = pool.backend {
+        if hipMemPoolGetAttribute(base, hipMemPoolAttr(attr.0 as _), value)
+            == hipError_t::hipSuccess
+        {
+            return Ok(());
+        }
+    }
+    let state = pool.lock()?;
+    match attr {
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_REUSE_FOLLOW_EVENT_DEPENDENCIES => {
+            *(value as *mut i32) = state.reuse_follow_event_dependencies
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_REUSE_ALLOW_OPPORTUNISTIC => {
+            *(value as *mut i32) = state.reuse_allow_opportunistic
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_REUSE_ALLOW_INTERNAL_DEPENDENCIES => {
+            *(value as *mut i32) = state.reuse_allow_internal_dependencies
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_RELEASE_THRESHOLD => {
+            *(value as *mut u64) = state.release_threshold
+        }
+        // HIP pools don't tell us how much they have reserved, the best we
+        // can do is the memory in use
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_RESERVED_MEM_CURRENT => {
+            *(value as *mut u64) = state.reserved.max(state.used) as u64
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_RESERVED_MEM_HIGH => {
+            *(value as *mut u64) = state.reserved_high.max(state.used_high) as u64
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_USED_MEM_CURRENT => {
+            *(value as *mut u64) = state.used as u64
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_USED_MEM_HIGH => {
+            *(value as *mut u64) = state.used_high as u64
+        }
+        _ => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    }
+    Ok(())
+}
+
+pub(crate) unsafe fn set_attribute(
+    pool: *mut MemoryPool,
+    attr: CUmemPool_attribute,
+    value: *mut c_void,
+) -> Result<(), CUresult> {
+    let pool = LiveCheck::as_result(pool)?;
+    if value == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    if let Backend::Hip { base, .. } = pool.backend {
+        let error = hipMemPoolSetAttribute(base, hipMemPoolAttr(attr.0 as _), value);
+        // Watermarks are tracked by us if HIP doesn't
+        if error != hipError_t::hipSuccess
+            && attr != CUmemPool_attribute::CU_MEMPOOL_ATTR_RESERVED_MEM_HIGH
+            && attr != CUmemPool_attribute::CU_MEMPOOL_ATTR_USED_MEM_HIGH
+        {
+            return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+        }
+    }
+    let mut state = pool.lock()?;
+    match attr {
+        // The fallback pool does not track stream ordering dependencies
+        // between frees and allocations, so it can't change how they are used
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_REUSE_FOLLOW_EVENT_DEPENDENCIES
+            if matches!(pool.backend, Backend::Fallback) && *(value as *mut i32) != 1 =>
+        {
+            return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED)
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_REUSE_FOLLOW_EVENT_DEPENDENCIES => {
+            state.reuse_follow_event_dependencies = *(value as *mut i32)
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_REUSE_ALLOW_OPPORTUNISTIC => {
+            state.reuse_allow_opportunistic = *(value as *mut i32)
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_REUSE_ALLOW_INTERNAL_DEPENDENCIES => {
+            state.reuse_allow_internal_dependencies = *(value as *mut i32)
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_RELEASE_THRESHOLD => {
+            state.release_threshold = *(value as *mut u64)
+        }
+        // High watermarks can only be reset
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_RESERVED_MEM_HIGH if *(value as *mut u64) == 0 => {
+            state.reserved_high = state.reserved
+        }
+        CUmemPool_attribute::CU_MEMPOOL_ATTR_USED_MEM_HIGH if *(value as *mut u64) == 0 => {
+            state.used_high = state.used
+        }
+        _ => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    }
+    Ok(())
+}
+
+pub(crate) unsafe fn set_access(
+    pool: *mut MemoryPool,
+    map: *const CUmemAccessDesc,
+    count: usize,
+) -> Result<(), CUresult> {
+    let pool = LiveCheck::as_result(pool)?;
+    let descs = vmm::access_descs_from_cuda(map, count)?;
+    match pool.backend {
+        Backend::Hip { base, .. } => {
+            hip_call_cuda!(hipMemPoolSetAccess(base, descs.as_ptr(), descs.len()));
+            Ok(())
+        }
+        // Memory of the fallback pool comes from hipMalloc(...) and is
+        // accessible only from its own device
+        Backend::Fallback => {
+            for desc in descs {
+                let expected = if desc.location.id == pool.device {
+                    hipMemAccessFlags::hipMemAccessFlagsProtReadWrite
+                } else {
+                    hipMemAccessFlags::hipMemAccessFlagsProtNone
+                };
+                if desc.flags != expected {
+                    return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+pub(crate) unsafe fn get_access(
+    flags: *mut CUmemAccess_flags,
+    pool: *mut MemoryPool,
+    location: *mut CUmemLocation,
+) -> Result<(), CUresult> {
+    let pool = LiveCheck::as_result(pool)?;
+    let location = match location.as_ref() {
+        Some(location) => location,
+        None => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    };
+    if flags == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    *flags = match pool.backend {
+        Backend::Hip { base, .. } => {
+            let mut hip_location = vmm::location_from_cuda(location);
+            let mut hip_flags = hipMemAccessFlags::hipMemAccessFlagsProtNone;
+            hip_call_cuda!(hipMemPoolGetAccess(&mut hip_flags, base, &mut hip_location));
+            CUmemAccess_flags(hip_flags.0 as _)
+        }
+        Backend::Fallback if location.id == pool.device => {
+            CUmemAccess_flags::CU_MEM_ACCESS_FLAGS_PROT_READWRITE
+        }
+        Backend::Fallback => CUmemAccess_flags::CU_MEM_ACCESS_FLAGS_PROT_NONE,
+    };
+    Ok(())
+}
+
+pub(crate) unsafe fn export_to_shareable_handle(
+    handle_out: *mut c_void,
+    pool: *mut MemoryPool,
+    handle_type: CUmemAllocationHandleType,
+    flags: u64,
+) -> Result<(), CUresult> {
+    let base = hip_pool(pool)?;
+    hip_call_cuda!(hipMemPoolExportToShareableHandle(
+        handle_out,
+        base,
+        hipMemAllocationHandleType(handle_type.0 as _),
+        flags as _
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn import_from_shareable_handle(
+    pool_out: *mut *mut MemoryPool,
+    handle: *mut c_void,
+    handle_type: CUmemAllocationHandleType,
+    flags: u64,
+) -> Result<(), CUresult> {
+    if pool_out == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let mut base = ptr::null_mut();
+    hip_call_cuda!(hipMemPoolImportFromShareableHandle(
+        &mut base,
+        handle,
+        hipMemAllocationHandleType(handle_type.0 as _),
+        flags as _
+    ));
+    let device = match imported_pool_device(base) {
+        Ok(device) => device,
+        Err(error) => {
+            let _ = hipMemPoolDestroy(base);
+            return Err(error);
+        }
+    };
+    *pool_out = new_pool(MemoryPoolData::new(
+        device,
+        false,
+        Backend::Hip { base, owned: true },
+        0,
+    ))?;
+    Ok(())
+}
+
+// HIP does not tell us the device of an imported pool. Right after the import
+// the only device with access to the pool is the one it lives on
+unsafe fn imported_pool_device(base: hipMemPool_t) -> Result<hipDevice_t, CUresult> {
+    let mut device_count = 0;
+    hip_call_cuda!(hipGetDeviceCount(&mut device_count));
+    for device in 0..device_count {
+        let mut location = hipMemLocation {
+            type_: hipMemLocationType::hipMemLocationTypeDevice,
+            id: device,
+        };
+        let mut flags = hipMemAccessFlags::hipMemAccessFlagsProtNone;
+        hip_call_cuda!(hipMemPoolGetAccess(&mut flags, base, &mut location));
+        if flags == hipMemAccessFlags::hipMemAccessFlagsProtReadWrite {
+            return Ok(device);
+        }
+    }
+    Err(CUresult::CUDA_ERROR_NOT_SUPPORTED)
+}
+
+pub(crate) unsafe fn export_pointer(
+    share_data: *mut CUmemPoolPtrExportData,
+    ptr: hipDeviceptr_t,
+) -> Result<(), CUresult> {
+    hip_call_cuda!(hipMemPoolExportPointer(share_data.cast(), ptr.0));
+    Ok(())
+}
+
+pub(crate) unsafe fn import_pointer(
+    ptr_out: *mut hipDeviceptr_t,
+    pool: *mut MemoryPool,
+    share_data: *mut CUmemPoolPtrExportData,
+) -> Result<(), CUresult> {
+    let base = hip_pool(pool)?;
+    hip_call_cuda!(hipMemPoolImportPointer(
+        ptr_out.cast(),
+        base,
+        share_data.cast()
+    ));
+    Ok(())
+}
+
+// HIP pools have no maximum size
+pub(crate) fn pool_props_from_cuda(props: &CUmemPoolProps) -> hipMemPoolProps {
+    let mut result = unsafe { std::mem::zeroed::<hipMemPoolProps>() };
+    result.allocType = hipMemAllocationType(props.allocType.0 as _);
+    result.handleTypes = hipMemAllocationHandleType(props.handleTypes.0 as _);
+    result.location = vmm::location_from_cuda(&props.location);
+    result.win32SecurityAttributes = props.win32SecurityAttributes;
+    result
+}
+
+pub(crate) fn pool_props_to_cuda(props: &hipMemPoolProps) -> CUmemPoolProps {
+    let mut result = unsafe { std::mem::zeroed::<CUmemPoolProps>() };
+    result.allocType = CUmemAllocationType(props.allocType.0 as _);
+    result.handleTypes = CUmemAllocationHandleType(props.handleTypes.0 as _);
+    result.location = CUmemLocation {
+        type_: CUmemLocationType(props.location.type_.0 as _),
+        id: props.location.id,
+    };
+    result.win32SecurityAttributes = props.win32SecurityAttributes;
+    result
+}
+
+// Sharing is only supported for pools backed by HIP
+unsafe fn hip_pool(pool: *mut MemoryPool) -> Result<hipMemPool_t, CUresult> {
+    match LiveCheck::as_result(pool)?.backend {
+        Backend::Hip { base, .. } => Ok(base),
+        Backend::Fallback => Err(CUresult::CUDA_ERROR_NOT_SUPPORTED),
+    }
+}
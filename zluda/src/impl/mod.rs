@@ -6,19 +6,21 @@ use static_assertions::assert_impl_one;
 use std::{
     cell::Cell,
     ffi::{c_void, CStr},
-    fs,
     mem::{self, ManuallyDrop, MaybeUninit},
     ptr::{self, NonNull},
     sync::{atomic::AtomicI32, Once},
 };
 
 use self::cache::KernelCache;
+use hip_common::cache::CacheSettings;
 
 pub(crate) mod array;
 pub(crate) mod cache;
+pub(crate) mod compiler;
 pub(crate) mod context;
 pub(crate) mod dark_api;
 pub(crate) mod device;
+pub(crate) mod external;
 pub(crate) mod function;
 pub(crate) mod gl;
 pub(crate) mod graph;
@@ -26,16 +28,19 @@ pub(crate) mod hipfix;
 pub(crate) mod library;
 pub(crate) mod link;
 pub(crate) mod memory;
+pub(crate) mod mempool;
 pub(crate) mod module;
 #[cfg_attr(windows, path = "os_win.rs")]
 #[cfg_attr(not(windows), path = "os_unix.rs")]
 pub(crate) mod os;
+pub(crate) mod peer;
 pub(crate) mod pointer;
 pub(crate) mod stream;
 pub(crate) mod surface;
 pub(crate) mod surfref;
 pub(crate) mod texobj;
 pub(crate) mod texref;
+pub(crate) mod vmm;
 
 #[cfg(debug_assertions)]
 pub(crate) fn unimplemented() -> cuda_types::CUresult {
@@ -194,6 +199,7 @@ impl FromCuda<usize> for usize {}
 impl FromCuda<u64> for u64 {}
 impl FromCuda<CUuuid> for CUuuid {}
 impl FromCuda<CUdevice_attribute> for CUdevice_attribute {}
+impl FromCuda<CUdevice_P2PAttribute> for CUdevice_P2PAttribute {}
 impl FromCuda<CUdevprop> for CUdevprop {}
 impl FromCuda<CUlimit> for CUlimit {}
 impl FromCuda<CUfunc_cache> for CUfunc_cache {}
@@ -202,6 +208,7 @@ impl FromCuda<CUfunction_attribute> for CUfunction_attribute {}
 // Same layout, but if it's a an array resource it needs an adjustment in hipfix
 impl FromCuda<CUDA_MEMCPY2D> for CUDA_MEMCPY2D {}
 impl FromCuda<CUDA_MEMCPY3D> for CUDA_MEMCPY3D {}
+impl FromCuda<CUDA_MEMCPY3D_PEER> for CUDA_MEMCPY3D_PEER {}
 impl FromCuda<CUDA_ARRAY3D_DESCRIPTOR> for CUDA_ARRAY3D_DESCRIPTOR {}
 impl FromCuda<c_void> for c_void {}
 impl FromCuda<CUarray> for CUarray {}
@@ -211,9 +218,37 @@ impl FromCuda<CUdriverProcAddressQueryResult_enum> for CUdriverProcAddressQueryR
 impl FromCuda<CUmoduleLoadingMode> for CUmoduleLoadingMode {}
 impl FromCuda<CUlibraryOption> for CUlibraryOption {}
 impl FromCuda<CUDA_KERNEL_NODE_PARAMS_v1> for CUDA_KERNEL_NODE_PARAMS_v1 {}
+impl FromCuda<CUDA_KERNEL_NODE_PARAMS> for CUDA_KERNEL_NODE_PARAMS {}
+impl FromCuda<CUDA_MEMSET_NODE_PARAMS> for CUDA_MEMSET_NODE_PARAMS {}
+impl FromCuda<CUDA_HOST_NODE_PARAMS> for CUDA_HOST_NODE_PARAMS {}
+impl FromCuda<CUDA_MEM_ALLOC_NODE_PARAMS> for CUDA_MEM_ALLOC_NODE_PARAMS {}
+impl FromCuda<CUgraphNodeType> for CUgraphNodeType {}
+impl FromCuda<CUgraphExecUpdateResultInfo> for CUgraphExecUpdateResultInfo {}
+impl FromCuda<CUDA_LAUNCH_PARAMS> for CUDA_LAUNCH_PARAMS {}
+impl FromCuda<CUlaunchConfig> for CUlaunchConfig {}
+impl FromCuda<CUstreamBatchMemOpParams> for CUstreamBatchMemOpParams {}
 impl FromCuda<CUjitInputType> for CUjitInputType {}
 impl FromCuda<CUDA_RESOURCE_DESC> for CUDA_RESOURCE_DESC {}
 impl FromCuda<CUmipmappedArray> for CUmipmappedArray {}
+impl FromCuda<CUmemAllocationProp> for CUmemAllocationProp {}
+impl FromCuda<CUmemAllocationHandleType> for CUmemAllocationHandleType {}
+impl FromCuda<CUmemAllocationGranularity_flags> for CUmemAllocationGranularity_flags {}
+impl FromCuda<CUmemAccessDesc> for CUmemAccessDesc {}
+impl FromCuda<CUmemLocation> for CUmemLocation {}
+impl FromCuda<CUmem_range_attribute> for CUmem_range_attribute {}
+impl FromCuda<CUmemPoolProps> for CUmemPoolProps {}
+impl FromCuda<CUmemPool_attribute> for CUmemPool_attribute {}
+impl FromCuda<CUmemAccess_flags> for CUmemAccess_flags {}
+impl FromCuda<CUmemPoolPtrExportData> for CUmemPoolPtrExportData {}
+impl FromCuda<CUDA_EXTERNAL_MEMORY_HANDLE_DESC> for CUDA_EXTERNAL_MEMORY_HANDLE_DESC {}
+impl FromCuda<CUDA_EXTERNAL_MEMORY_BUFFER_DESC> for CUDA_EXTERNAL_MEMORY_BUFFER_DESC {}
+impl FromCuda<CUDA_EXTERNAL_MEMORY_MIPMAPPED_ARRAY_DESC>
+    for CUDA_EXTERNAL_MEMORY_MIPMAPPED_ARRAY_DESC
+{
+}
+impl FromCuda<CUDA_EXTERNAL_SEMAPHORE_HANDLE_DESC> for CUDA_EXTERNAL_SEMAPHORE_HANDLE_DESC {}
+impl FromCuda<CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS> for CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS {}
+impl FromCuda<CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS> for CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS {}
 
 impl FromCuda<CUcontext> for *mut context::Context {}
 impl FromCuda<CUstream> for *mut stream::Stream {}
@@ -221,19 +256,21 @@ impl FromCuda<CUdevice> for hipDevice_t {}
 impl FromCuda<CUdeviceptr> for hipDeviceptr_t {}
 impl FromCuda<CUmodule> for *mut module::Module {}
 impl FromCuda<CUlibrary> for *mut library::Library {}
+impl FromCuda<CUkernel> for *mut library::Kernel {}
 impl FromCuda<CUfunction> for *mut function::Function {}
 impl FromCuda<CUlinkState> for *mut link::LinkState {}
+impl FromCuda<CUmemGenericAllocationHandle> for *mut vmm::Allocation {}
 impl FromCuda<CUtexref> for *mut textureReference {}
 impl FromCuda<CUsurfref> for *mut textureReference {}
 impl FromCuda<CUevent> for hipEvent_t {}
 impl FromCuda<CUtexObject> for hipTextureObject_t {}
-impl FromCuda<CUmemoryPool> for hipMemPool_t {}
+impl FromCuda<CUmemoryPool> for *mut mempool::MemoryPool {}
 // values are compatible
 impl FromCuda<CUstreamCaptureStatus> for hipStreamCaptureStatus {}
 // values are compatible
-impl FromCuda<CUmemPool_attribute> for hipMemPoolAttr {}
-// values are compatible
 impl FromCuda<CUpointer_attribute> for hipPointer_attribute {}
+// values are compatible
+impl FromCuda<CUmem_advise> for hipMemoryAdvise {}
 impl FromCuda<CUfunction_attribute> for hipFunction_attribute {}
 impl FromCuda<CUfilter_mode> for hipTextureFilterMode {}
 impl FromCuda<CUaddress_mode> for hipTextureAddressMode {}
@@ -249,9 +286,15 @@ impl FromCuda<CUgraph> for hipGraph_t {}
 impl FromCuda<CUgraphNode> for hipGraphNode_t {}
 impl FromCuda<CUgraphExec> for hipGraphExec_t {}
 impl FromCuda<CUgraphicsResource> for hipGraphicsResource_t {}
+impl FromCuda<CUexternalMemory> for hipExternalMemory_t {}
+impl FromCuda<CUexternalSemaphore> for hipExternalSemaphore_t {}
 impl FromCuda<CUlimit> for hipLimit_t {}
 impl FromCuda<CUsurfObject> for hipSurfaceObject_t {}
 impl FromCuda<CUstreamCaptureMode> for hipStreamCaptureMode {}
+impl FromCuda<CUgraphExecUpdateResult> for hipGraphExecUpdateResult {}
+// Both are opaque 64 byte blobs, HIP handle is passed through unchanged
+impl FromCuda<CUipcMemHandle> for hipIpcMemHandle_t {}
+impl FromCuda<CUipcEventHandle> for hipIpcEventHandle_t {}
 
 impl<From, Into: FromCuda<From>> FromCuda<*mut From> for *mut Into {}
 impl<From, Into: FromCuda<From>> FromCuda<*const From> for *const Into {}
@@ -491,11 +534,7 @@ pub(crate) fn init(flags: u32) -> Result<(), CUresult> {
 }
 
 fn create_default_cache() -> Option<KernelCache> {
-    let mut disk_cache_location = dirs::cache_dir()?;
-    disk_cache_location.push("ZLUDA");
-    disk_cache_location.push("ComputeCache");
-    fs::create_dir_all(&disk_cache_location).ok()?;
-    KernelCache::new(&disk_cache_location)
+    KernelCache::new(&CacheSettings::load())
 }
 
 pub(crate) static MAXIMUM_PROC_VERSION: AtomicI32 = AtomicI32::new(0);
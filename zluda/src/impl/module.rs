@@ -1,19 +1,22 @@
 use super::context::Context;
-use super::{context, function, LiveCheck, ZludaObject};
+use super::{compiler, context, function, LiveCheck, ZludaObject};
 use crate::hip_call_cuda;
 use crate::r#impl::function::FunctionData;
 use crate::r#impl::{comgr_error_to_cuda, device, hipfix, GLOBAL_STATE};
-use cuda_types::{CUmoduleLoadingMode, CUresult};
+use cuda_types::{CUjit_option, CUmoduleLoadingMode, CUresult};
 use hip_common::CompilationMode;
 use hip_runtime_sys::*;
 use ptx::ModuleParserExt;
 use rustc_hash::FxHashMap;
 use std::borrow::Cow;
 use std::cmp;
-use std::collections::hash_map;
 use std::ffi::{CStr, CString};
+use std::fmt::Write;
+use std::iter;
+use std::os::raw::{c_char, c_uint, c_void};
 use std::ptr::{self, NonNull};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use zluda_dark_api::{CUmoduleContent, FatbinFileKind};
 
 const EMPTY_MODULE: &'static str = include_str!("empty_module.ptx");
@@ -41,6 +44,11 @@ impl ZludaObject for ModuleData {
         } else {
             Ok(())
         };
+        if let Ok(functions) = self.functions.lock() {
+            for function in functions.values() {
+                function::unregister(unsafe { function.as_ref_unchecked() });
+            }
+        }
         // Crashes HIP in 5.6 and 5.7.1
         //deregistration_err.and(unsafe { hipModuleUnload(self.base) }.into_cuda().into())
         deregistration_err
@@ -50,12 +58,34 @@ impl ZludaObject for ModuleData {
 pub(crate) struct ModuleData {
     // If module is part of a library, then there's no owning context
     pub(crate) owner: Option<NonNull<Context>>,
+    // Null for lazily loaded modules, every kernel has its own HIP module
     pub(crate) base: hipModule_t,
     functions: Mutex<FxHashMap<CString, Box<function::Function>>>,
+    // In the order they appear in the module, for cuModuleEnumerateFunctions(...)
+    pub(crate) kernel_names: Vec<CString>,
     sm_version: u32,
     device_version: u32,
     hipfix_max_group_sizes: FxHashMap<CString, (u32, u32)>,
     compilation_mode: CompilationMode,
+    lazy: Option<LazyModule>,
+}
+
+// With CUDA_MODULE_LOADING=LAZY (the default) we only parse the PTX during
+// module load and compile every kernel separately on its first
+// cuModuleGetFunction(...). Compiled kernels are cached one by one
+struct LazyModule {
+    isa: CString,
+    // Logs can't be written this late, so we keep only compilation options
+    compilation_options: CompilationOptions,
+    // Same order as in eager loading: we use the first candidate that compiles.
+    // Shared with the background compilation thread
+    candidates: Arc<[LazyCandidate]>,
+}
+
+struct LazyCandidate {
+    text: String,
+    split: ptx::KernelSplit,
+    kernels: FxHashMap<CString, usize>,
 }
 
 impl ModuleData {
@@ -84,9 +114,219 @@ pub(crate) unsafe fn load_data(
     )
 }
 
+pub(crate) unsafe fn load_data_ex(
+    module: *mut *mut Module,
+    image: *const ::std::os::raw::c_void,
+    num_options: ::std::os::raw::c_uint,
+    options: *mut CUjit_option,
+    option_values: *mut *mut ::std::os::raw::c_void,
+) -> Result<(), CUresult> {
+    if image == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let mut jit_options = JitOptions::parse(num_options, options, option_values)?;
+    let start = Instant::now();
+    let result = load_impl_with_options(
+        module,
+        CUmoduleContent::from_ptr(image.cast()).map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)?,
+        &mut jit_options,
+    );
+    jit_options.write_back(start);
+    result
+}
+
+// Options passed to cuModuleLoadDataEx, cuLinkCreate, cuLinkAddData and
+// cuLibraryLoadData. Output options (log buffers, wall time) point to the
+// application memory and are written only in write_back(...)
+#[derive(Default)]
+pub(crate) struct JitOptions {
+    pub(crate) compilation: CompilationOptions,
+    info_log: Option<JitLogBuffer>,
+    error_log: Option<JitLogBuffer>,
+    // Set if either log buffer is present, compiler threads have no buffers,
+    // but still have to collect logs
+    collect_log: bool,
+    wall_time: Option<*mut *mut c_void>,
+    threads_per_block_value: Option<*mut *mut c_void>,
+    pub(crate) info_log_text: String,
+    pub(crate) error_log_text: String,
+}
+
+// Part of JitOptions that affects the compiled binary. It does not point to
+// the application memory, so it can be kept for later and sent to other threads
+#[derive(Clone, Copy, Default)]
+pub(crate) struct CompilationOptions {
+    pub(crate) optimization_level: Option<u32>,
+    pub(crate) max_registers: Option<u32>,
+    pub(crate) threads_per_block: Option<u32>,
+    pub(crate) line_info: bool,
+}
+
+struct JitLogBuffer {
+    buffer: *mut c_char,
+    size: usize,
+    size_value: *mut *mut c_void,
+}
+
+impl JitOptions {
+    pub(crate) unsafe fn parse(
+        num_options: c_uint,
+        options: *mut CUjit_option,
+        option_values: *mut *mut c_void,
+    ) -> Result<Self, CUresult> {
+        let mut result = Self::default();
+        if num_options == 0 {
+            return Ok(result);
+        }
+        if options == ptr::null_mut() || option_values == ptr::null_mut() {
+            return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+        }
+        let mut info_log = (ptr::null_mut(), None);
+        let mut error_log = (ptr::null_mut(), None);
+        for i in 0..num_options as usize {
+            let value_ptr = option_values.add(i);
+            // Integer option values are passed directly in the pointer
+            let value = *value_ptr as usize;
+            match *options.add(i) {
+                CUjit_option::CU_JIT_OPTIMIZATION_LEVEL => {
+                    result.compilation.optimization_level = Some(value as u32)
+                }
+                CUjit_option::CU_JIT_MAX_REGISTERS => {
+                    result.compilation.max_registers = Some(value as u32)
+                }
+                CUjit_option::CU_JIT_THREADS_PER_BLOCK => {
+                    result.compilation.threads_per_block = Some(value as u32);
+                    result.threads_per_block_value = Some(value_ptr);
+                }
+                CUjit_option::CU_JIT_GENERATE_LINE_INFO => {
+                    result.compilation.line_info = value != 0
+                }
+                CUjit_option::CU_JIT_WALL_TIME => result.wall_time = Some(value_ptr),
+                CUjit_option::CU_JIT_INFO_LOG_BUFFER => info_log.0 = (*value_ptr).cast(),
+                CUjit_option::CU_JIT_INFO_LOG_BUFFER_SIZE_BYTES => {
+                    info_log.1 = Some((value, value_ptr))
+                }
+                CUjit_option::CU_JIT_ERROR_LOG_BUFFER => error_log.0 = (*value_ptr).cast(),
+                CUjit_option::CU_JIT_ERROR_LOG_BUFFER_SIZE_BYTES => {
+                    error_log.1 = Some((value, value_ptr))
+                }
+                _ => {}
+            }
+        }
+        result.info_log = JitLogBuffer::new(info_log);
+        result.error_log = JitLogBuffer::new(error_log);
+        result.collect_log = result.info_log.is_some() || result.error_log.is_some();
+        Ok(result)
+    }
+
+    pub(crate) fn wants_log(&self) -> bool {
+        self.collect_log
+    }
+
+    // Same as in CUDA: logs are NUL-terminated and truncated to the buffer
+    // size, log size options are overwritten with the number of bytes used
+    pub(crate) unsafe fn write_back(&self, start: Instant) {
+        if let Some(ref info_log) = self.info_log {
+            info_log.write(&self.info_log_text);
+        }
+        if let Some(ref error_log) = self.error_log {
+            error_log.write(&self.error_log_text);
+        }
+        if let Some(wall_time) = self.wall_time {
+            *wall_time.cast::<f32>() = start.elapsed().as_secs_f32() * 1000.0;
+        }
+        if let (Some(value), Some(threads_per_block)) = (
+            self.threads_per_block_value,
+            self.compilation.threads_per_block,
+        ) {
+            *value = threads_per_block as usize as *mut c_void;
+        }
+    }
+}
+
+impl From<CompilationOptions> for JitOptions {
+    fn from(compilation: CompilationOptions) -> Self {
+        JitOptions {
+            compilation,
+            ..Default::default()
+        }
+    }
+}
+
+impl CompilationOptions {
+    // Kernel cache is keyed only on the PTX text, so anything that changes
+    // the compiled binary has to bypass it
+    pub(crate) fn is_default(&self) -> bool {
+        !self.line_info
+            && self.max_registers.is_none()
+            && self.threads_per_block.is_none()
+            && self.optimization_level.map_or(true, |level| level >= 3)
+    }
+
+    // Options that affect compilation can be also passed to cuLinkAddData,
+    // they override those set in cuLinkCreate
+    pub(crate) fn merge(&mut self, other: &CompilationOptions) {
+        if other.optimization_level.is_some() {
+            self.optimization_level = other.optimization_level;
+        }
+        if other.max_registers.is_some() {
+            self.max_registers = other.max_registers;
+        }
+        if other.threads_per_block.is_some() {
+            self.threads_per_block = other.threads_per_block;
+        }
+        self.line_info |= other.line_info;
+    }
+}
+
+impl JitLogBuffer {
+    fn new((buffer, size): (*mut c_char, Option<(usize, *mut *mut c_void)>)) -> Option<Self> {
+        match size {
+            Some((size, size_value)) if buffer != ptr::null_mut() && size > 0 => Some(Self {
+                buffer,
+                size,
+                size_value,
+            }),
+            _ => None,
+        }
+    }
+
+    unsafe fn write(&self, text: &str) {
+        let length = cmp::min(text.len(), self.size - 1);
+        ptr::copy_nonoverlapping(text.as_ptr(), self.buffer.cast::<u8>(), length);
+        *self.buffer.add(length) = 0;
+        *self.size_value = (length + 1) as *mut c_void;
+    }
+}
+
+// Line info is normally requested with CU_JIT_GENERATE_LINE_INFO, but most
+// applications never do it, ZLUDA_PTX_LINE_INFO=1 forces it for all modules
+fn line_info_from_env() -> bool {
+    env_flag("ZLUDA_PTX_LINE_INFO")
+}
+
+fn lazy_loading() -> bool {
+    !matches!(std::env::var("CUDA_MODULE_LOADING").as_deref(), Ok("EAGER"))
+}
+
+fn env_flag(name: &str) -> bool {
+    match std::env::var(name) {
+        Ok(value) => matches!(str::parse::<u32>(&value), Ok(value) if value != 0),
+        Err(_) => false,
+    }
+}
+
 pub(crate) unsafe fn load_impl(
     output: *mut *mut Module,
     input: CUmoduleContent,
+) -> Result<(), CUresult> {
+    load_impl_with_options(output, input, &mut JitOptions::default())
+}
+
+unsafe fn load_impl_with_options(
+    output: *mut *mut Module,
+    input: CUmoduleContent,
+    jit_options: &mut JitOptions,
 ) -> Result<(), CUresult> {
     if output == ptr::null_mut() {
         return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
@@ -101,6 +341,7 @@ pub(crate) unsafe fn load_impl(
             device.compilation_mode,
             isa,
             input,
+            jit_options,
         )?);
         ctx.with_inner_mut(|ctx_mutable| {
             ctx_mutable.modules.insert(module);
@@ -115,6 +356,7 @@ unsafe fn link_build_or_load_cuda_module(
     compilation_mode: CompilationMode,
     isa: &CStr,
     input: CUmoduleContent,
+    jit_options: &mut JitOptions,
 ) -> Result<Cow<'static, [u8]>, CUresult> {
     match input {
         CUmoduleContent::Elf(ptr) => Ok(Cow::Borrowed(hip_common::elf::as_slice(ptr))),
@@ -123,8 +365,14 @@ unsafe fn link_build_or_load_cuda_module(
             let ptx = CStr::from_ptr(ptr.cast())
                 .to_str()
                 .map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)?;
-            link_build_zluda_module(global_state, compilation_mode, isa, &[Cow::Borrowed(ptx)])
-                .map(Cow::Owned)
+            link_build_zluda_module(
+                global_state,
+                compilation_mode,
+                isa,
+                &[Cow::Borrowed(ptx)],
+                jit_options,
+            )
+            .map(Cow::Owned)
         }
         CUmoduleContent::File(file) => {
             let name = CStr::from_ptr(file)
@@ -132,21 +380,35 @@ unsafe fn link_build_or_load_cuda_module(
                 .map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)?;
             let ptx =
                 std::fs::read_to_string(name).map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)?;
-            link_build_zluda_module(global_state, compilation_mode, isa, &[Cow::Owned(ptx)])
-                .map(Cow::Owned)
+            link_build_zluda_module(
+                global_state,
+                compilation_mode,
+                isa,
+                &[Cow::Owned(ptx)],
+                jit_options,
+            )
+            .map(Cow::Owned)
         }
         CUmoduleContent::Fatbin(files) => match files {
-            zluda_dark_api::CudaFatbin::Version1(module) => {
-                link_build_or_load_fatbin_module(global_state, compilation_mode, isa, module)
-                    .map(Cow::Owned)
-            }
+            zluda_dark_api::CudaFatbin::Version1(module) => link_build_or_load_fatbin_module(
+                global_state,
+                compilation_mode,
+                isa,
+                module,
+                jit_options,
+            )
+            .map(Cow::Owned),
             zluda_dark_api::CudaFatbin::Version2 {
                 post_link,
                 pre_link,
             } => {
-                if let Ok(binary) =
-                    link_build_or_load_fatbin_module(global_state, compilation_mode, isa, post_link)
-                {
+                if let Ok(binary) = link_build_or_load_fatbin_module(
+                    global_state,
+                    compilation_mode,
+                    isa,
+                    post_link,
+                    jit_options,
+                ) {
                     return Ok(Cow::Owned(binary));
                 }
                 let ptx_files = pre_link
@@ -168,8 +430,14 @@ unsafe fn link_build_or_load_cuda_module(
                         }
                     })
                     .collect::<Result<Vec<_>, _>>()?;
-                link_build_zluda_module(global_state, compilation_mode, isa, &*ptx_files)
-                    .map(Cow::Owned)
+                link_build_zluda_module(
+                    global_state,
+                    compilation_mode,
+                    isa,
+                    &*ptx_files,
+                    jit_options,
+                )
+                .map(Cow::Owned)
             }
         },
     }
@@ -180,6 +448,7 @@ fn link_build_or_load_fatbin_module(
     compilation_mode: CompilationMode,
     isa: &CStr,
     module: zluda_dark_api::FatbinModuleHandle,
+    jit_options: &mut JitOptions,
 ) -> Result<Vec<u8>, CUresult> {
     let module = unsafe { module.get() }.map_err(|_| CUresult::CUDA_ERROR_NOT_SUPPORTED)?;
     match module {
@@ -187,11 +456,46 @@ fn link_build_or_load_fatbin_module(
             return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
         }
         zluda_dark_api::FatbinModule::Files(files) => {
-            let ptx_files = extract_ptx(files);
-            for (ptx, _) in ptx_files {
-                if let Ok(binary) =
-                    link_build_zluda_module(global_state, compilation_mode, isa, &[ptx])
-                {
+            // Usually the first candidate compiles, so we don't spend the
+            // threads on the others. If it does not, we don't want to wait for
+            // the fallbacks one by one. Logs are the same as if the candidates
+            // were compiled in order
+            let mut candidates = extract_ptx(files).into_iter();
+            let (preferred, _) = match candidates.next() {
+                Some(candidate) => candidate,
+                None => return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED),
+            };
+            if let Ok(binary) = link_build_zluda_module(
+                global_state,
+                compilation_mode,
+                isa,
+                &[preferred],
+                jit_options,
+            ) {
+                return Ok(binary);
+            }
+            let options = jit_options.compilation;
+            let collect_log = jit_options.wants_log();
+            let results = compiler::map_parallel(candidates.collect(), |(ptx, _)| {
+                let mut worker_options = JitOptions::from(options);
+                worker_options.collect_log = collect_log;
+                let result = link_build_zluda_module(
+                    global_state,
+                    compilation_mode,
+                    isa,
+                    &[ptx],
+                    &mut worker_options,
+                );
+                (
+                    result,
+                    worker_options.info_log_text,
+                    worker_options.error_log_text,
+                )
+            });
+            for (result, info_log, error_log) in results {
+                jit_options.info_log_text.push_str(&info_log);
+                jit_options.error_log_text.push_str(&error_log);
+                if let Ok(binary) = result {
                     return Ok(binary);
                 }
             }
@@ -209,13 +513,6 @@ fn extract_ptx(files: zluda_dark_api::FatbinModuleFiles) -> Vec<(Cow<'static, st
                         unsafe { file.get_or_decompress() }
                             .ok()
                             .map(|f| {
-                                // TODO: implement support for envreg
-                                // %envreg is currently used by global grid sync in PETSc on never CUDA architectures:
-                                //  auto g = cooperative_groups::this_grid();
-                                //  g.sync();
-                                if memchr::memmem::find(&*f, b"%envreg").is_some() {
-                                    return None;
-                                }
                                 let text = match f {
                                     Cow::Borrowed(slice) => {
                                         Cow::Borrowed(std::str::from_utf8(slice).ok()?)
@@ -241,13 +538,46 @@ pub(crate) unsafe fn load_data_any(
     compilation_mode: CompilationMode,
     isa: &CStr,
     input: CUmoduleContent,
+    jit_options: &mut JitOptions,
 ) -> Result<ModuleData, CUresult> {
     let global_state = GLOBAL_STATE.get()?;
-    let gpu_module = link_build_or_load_cuda_module(global_state, compilation_mode, isa, input)?;
+    let device_version = device::COMPUTE_CAPABILITY_MAJOR * 10 + device::COMPUTE_CAPABILITY_MINOR;
+    // Compilation logs are expected to be filled when the load returns
+    if lazy_loading() && !jit_options.wants_log() {
+        if let Some((lazy, sm_version)) = LazyModule::new(isa, &input, jit_options)? {
+            // Fatbins are registered by the CUDA runtime when the application
+            // starts, that's the best moment to start compiling them
+            if matches!(input, CUmoduleContent::Fatbin(_))
+                && env_flag("ZLUDA_PRECOMPILE")
+                && global_state.kernel_cache.is_some()
+                && lazy.compilation_options.is_default()
+            {
+                lazy.precompile(compilation_mode);
+            }
+            return Ok(ModuleData {
+                compilation_mode,
+                base: ptr::null_mut(),
+                owner,
+                device_version,
+                sm_version,
+                hipfix_max_group_sizes: FxHashMap::default(),
+                functions: Mutex::new(FxHashMap::default()),
+                kernel_names: lazy.kernel_names(),
+                lazy: Some(lazy),
+            });
+        }
+    }
+    let gpu_module =
+        link_build_or_load_cuda_module(global_state, compilation_mode, isa, input, jit_options)?;
     let (hipfix_max_group_sizes, sm_version) = load_kernel_metadata(&*gpu_module)?;
+    // Only used for enumeration, not worth failing the load over
+    let kernel_names = hip_common::elf::kernel_names(&*gpu_module)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|name| CString::new(name).ok())
+        .collect();
     let mut hip_module = ptr::null_mut();
     hip_call_cuda! { hipModuleLoadData(&mut hip_module, gpu_module.as_ptr() as _) };
-    let device_version = device::COMPUTE_CAPABILITY_MAJOR * 10 + device::COMPUTE_CAPABILITY_MINOR;
     Ok(ModuleData {
         compilation_mode,
         base: hip_module,
@@ -256,6 +586,192 @@ pub(crate) unsafe fn load_data_any(
         sm_version,
         hipfix_max_group_sizes,
         functions: Mutex::new(FxHashMap::default()),
+        kernel_names,
+        lazy: None,
+    })
+}
+
+impl LazyModule {
+    // Returns None if the module has to be loaded eagerly: it's not PTX, it
+    // does not parse (we want the usual diagnostics and fallbacks) or it has
+    // global variables, which must exist as soon as the module is loaded and
+    // can't be shared between separately compiled kernels
+    unsafe fn new(
+        isa: &CStr,
+        input: &CUmoduleContent,
+        jit_options: &JitOptions,
+    ) -> Result<Option<(Self, u32)>, CUresult> {
+        let texts = match lazy_candidates(input)? {
+            Some(texts) => texts,
+            None => return Ok(None),
+        };
+        let mut candidates = Vec::new();
+        let mut sm_version = None;
+        for text in texts {
+            let ast = match ptx::ModuleParser::parse_checked(&text) {
+                Ok(ast) => ast,
+                Err(_) => continue,
+            };
+            if !ast.has_independent_kernels() {
+                return Ok(None);
+            }
+            sm_version.get_or_insert(ast.sm_version);
+            let kernels = ast
+                .kernel_spans
+                .iter()
+                .enumerate()
+                .filter_map(|(index, (name, _))| Some((CString::new(*name).ok()?, index)))
+                .collect();
+            let split = ptx::KernelSplit::new(&text, &ast);
+            drop(ast);
+            candidates.push(LazyCandidate {
+                text: text.into_owned(),
+                split,
+                kernels,
+            });
+        }
+        let candidates = Arc::<[LazyCandidate]>::from(candidates);
+        let sm_version = match sm_version {
+            Some(sm_version) => sm_version,
+            None => return Ok(None),
+        };
+        Ok(Some((
+            LazyModule {
+                isa: isa.to_owned(),
+                compilation_options: jit_options.compilation,
+                candidates,
+            },
+            sm_version,
+        )))
+    }
+
+    // Returns HIP module with the kernel, its group size limits and the PTX
+    // version it was compiled from
+    unsafe fn load_kernel(
+        &self,
+        compilation_mode: CompilationMode,
+        name: &CStr,
+    ) -> Result<(hipModule_t, Option<(u32, u32)>, u32), CUresult> {
+        let global_state = GLOBAL_STATE.get()?;
+        let mut result = Err(CUresult::CUDA_ERROR_NOT_FOUND);
+        for candidate in self.candidates.iter() {
+            let kernel = match candidate.kernels.get(name) {
+                Some(kernel) => *kernel,
+                None => continue,
+            };
+            result = candidate.build_kernel(
+                global_state,
+                compilation_mode,
+                &self.isa,
+                kernel,
+                self.compilation_options,
+            );
+            if result.is_ok() {
+                break;
+            }
+        }
+        let binary = result?;
+        let (mut group_sizes, sm_version) = load_kernel_metadata(&binary)?;
+        let mut hip_module = ptr::null_mut();
+        hip_call_cuda! { hipModuleLoadData(&mut hip_module, binary.as_ptr() as _) };
+        Ok((hip_module, group_sizes.remove(name), sm_version))
+    }
+
+    // Candidates should all contain the same kernels, but we don't rely on it
+    fn kernel_names(&self) -> Vec<CString> {
+        let mut names = Vec::new();
+        for candidate in self.candidates.iter() {
+            let mut kernels = candidate.kernels.iter().collect::<Vec<_>>();
+            kernels.sort_unstable_by_key(|(_, index)| **index);
+            for (name, _) in kernels {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+        }
+        names
+    }
+
+    // Compiles every kernel of the preferred candidate into the kernel cache.
+    // If the application asks for a kernel that is still being compiled, it
+    // gets compiled twice, the second compilation saves the same binary
+    fn precompile(&self, compilation_mode: CompilationMode) {
+        let isa = self.isa.clone();
+        let candidates = self.candidates.clone();
+        let options = self.compilation_options;
+        compiler::run_in_background(move || {
+            let global_state = match GLOBAL_STATE.get() {
+                Ok(global_state) => global_state,
+                Err(_) => return,
+            };
+            let candidate = match candidates.first() {
+                Some(candidate) => candidate,
+                None => return,
+            };
+            compiler::map_parallel((0..candidate.split.kernel_count()).collect(), |kernel| {
+                candidate
+                    .build_kernel(global_state, compilation_mode, &isa, kernel, options)
+                    .ok();
+            });
+        });
+    }
+}
+
+impl LazyCandidate {
+    fn build_kernel(
+        &self,
+        global_state: &super::GlobalState,
+        compilation_mode: CompilationMode,
+        isa: &CStr,
+        kernel: usize,
+        options: CompilationOptions,
+    ) -> Result<Vec<u8>, CUresult> {
+        let text = self.split.kernel_text(&self.text, kernel);
+        link_build_zluda_module(
+            global_state,
+            compilation_mode,
+            isa,
+            &[Cow::Owned(text)],
+            &mut JitOptions::from(options),
+        )
+    }
+}
+
+// Texts are copied, the application is free to release the image after
+// cuModuleLoadData(...) returns
+unsafe fn lazy_candidates(
+    input: &CUmoduleContent,
+) -> Result<Option<Vec<Cow<'static, str>>>, CUresult> {
+    Ok(match input {
+        CUmoduleContent::RawText(ptr) => Some(vec![Cow::Owned(
+            CStr::from_ptr(ptr.cast())
+                .to_str()
+                .map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)?
+                .to_string(),
+        )]),
+        CUmoduleContent::File(file) => {
+            let name = CStr::from_ptr(*file)
+                .to_str()
+                .map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)?;
+            Some(vec![Cow::Owned(
+                std::fs::read_to_string(name).map_err(|_| CUresult::CUDA_ERROR_INVALID_VALUE)?,
+            )])
+        }
+        CUmoduleContent::Fatbin(zluda_dark_api::CudaFatbin::Version1(module))
+        | CUmoduleContent::Fatbin(zluda_dark_api::CudaFatbin::Version2 {
+            post_link: module, ..
+        }) => match module.get() {
+            Ok(zluda_dark_api::FatbinModule::Files(files)) => {
+                let ptx_files = extract_ptx(files);
+                if ptx_files.is_empty() {
+                    None
+                } else {
+                    Some(ptx_files.into_iter().map(|(ptx, _)| ptx).collect())
+                }
+            }
+            _ => None,
+        },
+        CUmoduleContent::Elf(_) | CUmoduleContent::Archive(_) => None,
     })
 }
 
@@ -292,62 +808,264 @@ pub(crate) fn link_build_zluda_module(
     compilation_mode: CompilationMode,
     isa: &CStr,
     ptx_text: &[Cow<'_, str>],
+    jit_options: &mut JitOptions,
 ) -> Result<Vec<u8>, CUresult> {
     if ptx_text.is_empty() {
         return Err(CUresult::CUDA_ERROR_UNKNOWN);
     }
-    if let Some(ref cache) = global_state.kernel_cache {
+    let options = jit_options.compilation;
+    let line_info = options.line_info || line_info_from_env();
+    let kernel_cache = if line_info || !options.is_default() {
+        None
+    } else {
+        global_state.kernel_cache.as_ref()
+    };
+    if let Some(cache) = kernel_cache {
         if let Some(binary) =
             cache.try_load_program(&global_state.comgr_version, isa, ptx_text, compilation_mode)
         {
             return Ok(binary);
         }
     }
+    let binary = match build_kernels_in_parallel(
+        global_state,
+        compilation_mode,
+        isa,
+        ptx_text,
+        line_info,
+        jit_options,
+    ) {
+        Some(binary) => binary,
+        None => build_zluda_module(
+            global_state,
+            compilation_mode,
+            isa,
+            ptx_text,
+            line_info,
+            jit_options,
+        )?,
+    };
+    if let Some(cache) = kernel_cache {
+        cache.save_program(
+            &global_state.comgr_version,
+            isa,
+            ptx_text,
+            compilation_mode,
+            &binary,
+        );
+    }
+    Ok(binary)
+}
+
+fn build_zluda_module(
+    global_state: &super::GlobalState,
+    compilation_mode: CompilationMode,
+    isa: &CStr,
+    ptx_text: &[Cow<'_, str>],
+    line_info: bool,
+    jit_options: &mut JitOptions,
+) -> Result<Vec<u8>, CUresult> {
     // Older CUDA applications have no notion of lazy loading
     // and will eager load everything even if the module is unused.
     // For this reason we fallback to empty module since that has potential
-    // to enable a few applications (but only in release mode)
+    // to enable a few applications (but only in release mode).
+    // ZLUDA_NO_EMPTY_MODULE_FALLBACK=1 turns it off, which is useful for
+    // finding out which instruction is blocking a kernel
+    let fallback = !cfg!(debug_assertions) && !env_flag("ZLUDA_NO_EMPTY_MODULE_FALLBACK");
+    let mut diagnostics = Vec::new();
     let asts = ptx_text
         .iter()
         .map(|ptx_mod| {
             let mut module = ptx::ModuleParser::parse_checked(&*ptx_mod);
-            if !cfg!(debug_assertions) {
+            if let Err(ref errors) = module {
+                diagnostics.extend(
+                    errors
+                        .iter()
+                        .map(|error| ptx::Diagnostic::from_parse_error(&*ptx_mod, error)),
+                );
+            }
+            if fallback {
                 module = module.or_else(|_| ptx::ModuleParser::parse_checked(EMPTY_MODULE))
             }
             module
         })
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|_| CUresult::CUDA_ERROR_INVALID_PTX)?;
-    let mut llvm_module = ptx::to_llvm_module(compilation_mode, asts);
-    if !cfg!(debug_assertions) {
-        llvm_module = llvm_module.or_else(|_| {
-            ptx::to_llvm_module(
-                compilation_mode,
-                vec![ptx::ModuleParser::parse_checked(EMPTY_MODULE)
-                    .map_err(|_| ptx::TranslateError::Todo)?],
-            )
-        });
+        .collect::<Result<Vec<_>, _>>();
+    let mut llvm_module = match asts {
+        Ok(asts) => {
+            let llvm_module = if line_info {
+                ptx::to_llvm_module_with_line_info(compilation_mode, asts)
+            } else {
+                ptx::to_llvm_module(compilation_mode, asts)
+            };
+            if let Err(ref error) = llvm_module {
+                diagnostics.push(ptx::Diagnostic::from_translate_error(ptx_text, error));
+            }
+            llvm_module.ok()
+        }
+        Err(_) => None,
+    };
+    for diagnostic in diagnostics.iter() {
+        writeln!(jit_options.error_log_text, "{}", diagnostic).ok();
     }
-    let llvm_module = llvm_module.map_err(|_| CUresult::CUDA_ERROR_INVALID_PTX)?;
-    let binary = global_state
-        .comgr
-        .compile(
-            compilation_mode,
-            isa,
-            ptx::Module::get_bitcode_multi(std::iter::once(&llvm_module)).into_iter(),
-            &llvm_module.metadata.to_elf_section(),
-        )
-        .map_err(comgr_error_to_cuda)?;
-    if let Some(ref cache) = global_state.kernel_cache {
-        cache.save_program(
-            &global_state.comgr_version,
-            isa,
-            ptx_text,
-            compilation_mode,
-            &binary,
-        );
+    if llvm_module.is_none() && fallback {
+        llvm_module = ptx::ModuleParser::parse_checked(EMPTY_MODULE)
+            .ok()
+            .and_then(|ast| ptx::to_llvm_module(compilation_mode, vec![ast]).ok());
+    }
+    let mut llvm_module = llvm_module.ok_or_else(|| diagnostics_to_cuda(&diagnostics))?;
+    let options = jit_options.compilation;
+    if options.max_registers.is_some() || options.threads_per_block.is_some() {
+        llvm_module.set_kernel_resource_limits(options.max_registers, options.threads_per_block);
+    }
+    let mut comgr_log = String::new();
+    let binary = global_state.comgr.compile_with_options(
+        compilation_mode,
+        isa,
+        ptx::Module::get_bitcode_multi(iter::once(&llvm_module)).into_iter(),
+        &llvm_module.metadata.to_elf_section(),
+        options.optimization_level.unwrap_or(3),
+        if jit_options.wants_log() {
+            Some(&mut comgr_log)
+        } else {
+            None
+        },
+    );
+    if binary.is_ok() {
+        jit_options.info_log_text.push_str(&comgr_log);
+    } else {
+        jit_options.error_log_text.push_str(&comgr_log);
+    }
+    binary.map_err(comgr_error_to_cuda)
+}
+
+// Kernels of a single module are translated and compiled on separate threads
+// and then linked together. Anything unusual (multiple modules, module-scope
+// variables) is left to build_zluda_module(...), which reports errors and
+// falls back to the empty module. If a kernel fails to build, the reason goes
+// to the info log and the whole module is built again by build_zluda_module(...)
+fn build_kernels_in_parallel(
+    global_state: &super::GlobalState,
+    compilation_mode: CompilationMode,
+    isa: &CStr,
+    ptx_text: &[Cow<'_, str>],
+    line_info: bool,
+    jit_options: &mut JitOptions,
+) -> Option<Vec<u8>> {
+    let ptx_text = match ptx_text {
+        [ptx_text] => &**ptx_text,
+        _ => return None,
+    };
+    if compiler::thread_count() < 2 {
+        return None;
+    }
+    // Module-scope variables of the PTX module would be duplicated in every
+    // kernel, so such modules are not split. Globals of zluda_ptx_impl (e.g.
+    // __zluda_ptx_impl__grid_workspace) are weak, the linker keeps one copy
+    let (names, split) = {
+        let ast = ptx::ModuleParser::parse_checked(ptx_text).ok()?;
+        if ast.kernel_spans.len() < 2 || !ast.has_independent_kernels() {
+            return None;
+        }
+        let names = ast
+            .kernel_spans
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>();
+        (names, ptx::KernelSplit::new(ptx_text, &ast))
+    };
+    let kernels = names
+        .iter()
+        .enumerate()
+        .map(|(kernel, name)| (*name, split.kernel_text(ptx_text, kernel)))
+        .collect::<Vec<_>>();
+    let options = jit_options.compilation;
+    let collect_log = jit_options.wants_log();
+    let comgr = &global_state.comgr;
+    let relocatables = compiler::map_parallel(
+        kernels.iter().collect(),
+        |(name, kernel): &(&str, String)| {
+            let ast = ptx::ModuleParser::parse_checked(kernel)
+                .map_err(|_| format!("kernel {} does not parse on its own", name))?;
+            let mut llvm_module = if line_info {
+                ptx::to_llvm_module_with_line_info(compilation_mode, vec![ast])
+            } else {
+                ptx::to_llvm_module(compilation_mode, vec![ast])
+            }
+            .map_err(|error| {
+                ptx::Diagnostic::from_translate_error(&[kernel], &error).to_string()
+            })?;
+            if options.max_registers.is_some() || options.threads_per_block.is_some() {
+                llvm_module
+                    .set_kernel_resource_limits(options.max_registers, options.threads_per_block);
+            }
+            let mut comgr_log = String::new();
+            let relocatable = comgr
+                .build_relocatable_with_options(
+                    compilation_mode,
+                    isa,
+                    ptx::Module::get_bitcode_multi(iter::once(&llvm_module)).into_iter(),
+                    options.optimization_level.unwrap_or(3),
+                    if collect_log {
+                        Some(&mut comgr_log)
+                    } else {
+                        None
+                    },
+                )
+                .map_err(|error| format!("could not compile kernel {}: {:?}", name, error))?;
+            Ok((relocatable, llvm_module.metadata, comgr_log))
+        },
+    )
+    .into_iter()
+    .collect::<Result<Vec<_>, String>>();
+    let relocatables = match relocatables {
+        Ok(relocatables) => relocatables,
+        Err(reason) => return log_parallel_build_fallback(jit_options, &reason),
+    };
+    let mut metadata = ptx::translate::Metadata::empty();
+    let mut comgr_log = String::new();
+    for (_, kernel_metadata, kernel_log) in relocatables.iter() {
+        metadata = metadata.join(kernel_metadata);
+        comgr_log.push_str(kernel_log);
+    }
+    let binary = comgr.link_relocatable_with_options(
+        isa,
+        relocatables.iter().map(|(relocatable, _, _)| relocatable),
+        &metadata.to_elf_section(),
+        if collect_log {
+            Some(&mut comgr_log)
+        } else {
+            None
+        },
+    );
+    jit_options.info_log_text.push_str(&comgr_log);
+    match binary {
+        Ok(binary) => Some(binary),
+        Err(error) => log_parallel_build_fallback(
+            jit_options,
+            &format!("could not link kernels: {:?}", error),
+        ),
+    }
+}
+
+fn log_parallel_build_fallback(jit_options: &mut JitOptions, reason: &str) -> Option<Vec<u8>> {
+    writeln!(
+        jit_options.info_log_text,
+        "Building kernels separately failed, building the whole module: {}",
+        reason
+    )
+    .ok();
+    None
+}
+
+// PTX that we don't support yet is not the application's fault, so it's
+// reported differently than malformed PTX
+fn diagnostics_to_cuda(diagnostics: &[ptx::Diagnostic]) -> CUresult {
+    match diagnostics.first() {
+        Some(diagnostic) if diagnostic.reason.is_unsupported() => {
+            CUresult::CUDA_ERROR_NOT_SUPPORTED
+        }
+        _ => CUresult::CUDA_ERROR_INVALID_PTX,
     }
-    Ok(binary)
 }
 
 pub(crate) unsafe fn unload(hmod: *mut Module) -> Result<(), CUresult> {
@@ -371,34 +1089,106 @@ pub(crate) unsafe fn get_function(
     }
     let module = LiveCheck::as_result(hmod)?;
     let name = CStr::from_ptr(name).to_owned();
+    let existing_function = |functions: &FxHashMap<CString, Box<function::Function>>| {
+        functions.get(&name).map(|function| {
+            let function: &function::Function = &*function;
+            function as *const function::Function as *mut _
+        })
+    };
+    {
+        let functions = module
+            .functions
+            .lock()
+            .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+        if let Some(function) = existing_function(&functions) {
+            *hfunc = function;
+            return Ok(());
+        }
+    }
+    // Lazily loaded kernels are compiled here, without holding the lock, so
+    // other threads can get already loaded functions of this module meanwhile
+    let (hip_module, group_size, ptx_version) = match module.lazy {
+        Some(ref lazy) => lazy.load_kernel(module.compilation_mode, &name)?,
+        None => (
+            module.base,
+            module.hipfix_max_group_sizes.get(&name).copied(),
+            module.sm_version,
+        ),
+    };
+    let mut hip_func = ptr::null_mut();
+    hip_call_cuda!(hipModuleGetFunction(
+        &mut hip_func,
+        hip_module,
+        name.as_ptr() as _
+    ));
+    // Optimized out of kernels that don't read %envreg
+    let mut grid_workspace_pointer = hipDeviceptr_t(ptr::null_mut());
+    if hipfix::module_get_global(
+        &mut grid_workspace_pointer,
+        ptr::null_mut(),
+        hip_module,
+        function::GRID_WORKSPACE_POINTER.as_ptr() as _,
+    ) != hipError_t::hipSuccess
+    {
+        grid_workspace_pointer = hipDeviceptr_t(ptr::null_mut());
+    }
     let mut functions = module
         .functions
         .lock()
         .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
-    let function = match functions.entry(name.to_owned()) {
-        hash_map::Entry::Occupied(entry) => {
-            let function: &function::Function = &*entry.get();
-            function as *const function::Function as *mut _
-        }
-        hash_map::Entry::Vacant(entry) => {
-            let mut hip_func = ptr::null_mut();
-            hip_call_cuda!(hipModuleGetFunction(
-                &mut hip_func,
-                module.base,
-                name.as_ptr() as _
-            ));
-            let function: &function::Function =
-                &*entry.insert(Box::new(LiveCheck::new(FunctionData {
-                    base: hip_func,
-                    binary_version: module.device_version,
-                    ptx_version: module.sm_version,
-                    group_size: module.hipfix_max_group_sizes.get(&name).copied(),
-                    compilation_mode: module.compilation_mode,
-                })));
-            function as *const function::Function as *mut _
-        }
-    };
-    *hfunc = function;
+    // If another thread loaded the same kernel in the meantime, we use its
+    // function. Our HIP module stays loaded, like every other HIP module (see
+    // drop_with_result(...))
+    if let Some(function) = existing_function(&functions) {
+        *hfunc = function;
+        return Ok(());
+    }
+    let function: &function::Function =
+        &*functions
+            .entry(name.clone())
+            .or_insert(Box::new(LiveCheck::new(FunctionData {
+                base: hip_func,
+                name,
+                binary_version: module.device_version,
+                ptx_version,
+                group_size,
+                compilation_mode: module.compilation_mode,
+                grid_workspace_pointer: grid_workspace_pointer.0,
+            })));
+    function::register(function);
+    *hfunc = function as *const function::Function as *mut _;
+    Ok(())
+}
+
+pub(crate) unsafe fn get_function_count(
+    count: *mut c_uint,
+    hmod: *mut Module,
+) -> Result<(), CUresult> {
+    if count == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let module = LiveCheck::as_result(hmod)?;
+    *count = module.kernel_names.len() as c_uint;
+    Ok(())
+}
+
+pub(crate) unsafe fn enumerate_functions(
+    functions: *mut *mut function::Function,
+    num_functions: c_uint,
+    hmod: *mut Module,
+) -> Result<(), CUresult> {
+    if functions == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let module = LiveCheck::as_result(hmod)?;
+    for (i, name) in module
+        .kernel_names
+        .iter()
+        .take(num_functions as usize)
+        .enumerate()
+    {
+        get_function(functions.add(i), hmod, name.as_ptr())?;
+    }
     Ok(())
 }
 
@@ -414,7 +1204,12 @@ pub(crate) unsafe fn get_global(
     if hmod == ptr::null_mut() {
         return Err(CUresult::CUDA_ERROR_INVALID_HANDLE);
     }
-    let hip_module = LiveCheck::as_result(hmod)?.base;
+    let module = LiveCheck::as_result(hmod)?;
+    // Lazily loaded modules have no global variables
+    if module.lazy.is_some() {
+        return Err(CUresult::CUDA_ERROR_NOT_FOUND);
+    }
+    let hip_module = module.base;
     hip_call_cuda!(hipfix::module_get_global(dptr, bytes, hip_module, name));
     Ok(())
 }
@@ -427,7 +1222,11 @@ pub(crate) unsafe fn get_tex_ref(
     if tex_ref == ptr::null_mut() || hmod == ptr::null_mut() || name == ptr::null_mut() {
         return Err(CUresult::CUDA_ERROR_INVALID_HANDLE);
     }
-    let hip_module = LiveCheck::as_result(hmod)?.base;
+    let module = LiveCheck::as_result(hmod)?;
+    if module.lazy.is_some() {
+        return Err(CUresult::CUDA_ERROR_NOT_FOUND);
+    }
+    let hip_module = module.base;
     hip_call_cuda!(hipModuleGetTexRef(tex_ref, hip_module, name));
     hip_call_cuda!(hipTexRefSetFormat(
         *tex_ref,
@@ -453,10 +1252,10 @@ pub(crate) unsafe fn get_loading_mode(result: *mut CUmoduleLoadingMode) -> CUres
     if result == ptr::null_mut() {
         CUresult::CUDA_ERROR_INVALID_VALUE
     } else {
-        let mode = if matches!(std::env::var("CUDA_MODULE_LOADING").as_deref(), Ok("EAGER")) {
-            CUmoduleLoadingMode::CU_MODULE_EAGER_LOADING
-        } else {
+        let mode = if lazy_loading() {
             CUmoduleLoadingMode::CU_MODULE_LAZY_LOADING
+        } else {
+            CUmoduleLoadingMode::CU_MODULE_EAGER_LOADING
         };
         *result = mode;
         CUresult::CUDA_SUCCESS
@@ -0,0 +1,782 @@
+use super::{context, memory, stream, LiveCheck};
+use crate::hip_call_cuda;
+use cuda_types::*;
+use hip_runtime_sys::*;
+use std::{collections::BTreeMap, sync::Mutex};
+
+// CUDA tracks peer access per context, HIP tracks it per device. We keep
+// track of context pairs here and call into HIP only when the first pair
+// between two devices enables access or the last one disables it
+static PEER_ACCESS: Mutex<PeerAccess> = Mutex::new(PeerAccess::new());
+
+trait PeerDevices {
+    unsafe fn can_access_peer(
+        &self,
+        device: hipDevice_t,
+        peer: hipDevice_t,
+    ) -> Result<bool, CUresult>;
+    unsafe fn enable_peer_access(
+        &self,
+        device: hipDevice_t,
+        peer: hipDevice_t,
+    ) -> Result<(), CUresult>;
+    unsafe fn disable_peer_access(
+        &self,
+        device: hipDevice_t,
+        peer: hipDevice_t,
+    ) -> Result<(), CUresult>;
+    unsafe fn p2p_attribute(
+        &self,
+        attribute: hipDeviceP2PAttr,
+        src_device: hipDevice_t,
+        dst_device: hipDevice_t,
+    ) -> Result<i32, CUresult>;
+    // Synchronous if `stream` is None
+    unsafe fn copy(
+        &self,
+        dst: (hipDeviceptr_t, hipDevice_t),
+        src: (hipDeviceptr_t, hipDevice_t),
+        byte_count: usize,
+        stream: Option<hipStream_t>,
+    ) -> Result<(), CUresult>;
+    unsafe fn copy3d(
+        &self,
+        copy: &CUDA_MEMCPY3D,
+        stream: Option<*mut stream::Stream>,
+    ) -> Result<(), CUresult>;
+}
+
+struct Hip;
+
+// Enable and disable act on the current HIP device, which is always the
+// device of the current context
+impl PeerDevices for Hip {
+    unsafe fn can_access_peer(
+        &self,
+        device: hipDevice_t,
+        peer: hipDevice_t,
+    ) -> Result<bool, CUresult> {
+        let mut can_access = 0;
+        hip_call_cuda!(hipDeviceCanAccessPeer(&mut can_access, device, peer));
+        Ok(can_access != 0)
+    }
+
+    unsafe fn enable_peer_access(
+        &self,
+        _device: hipDevice_t,
+        peer: hipDevice_t,
+    ) -> Result<(), CUresult> {
+        hip_call_cuda!(hipDeviceEnablePeerAccess(peer, 0));
+        Ok(())
+    }
+
+    unsafe fn disable_peer_access(
+        &self,
+        _device: hipDevice_t,
+        peer: hipDevice_t,
+    ) -> Result<(), CUresult> {
+        hip_call_cuda!(hipDeviceDisablePeerAccess(peer));
+        Ok(())
+    }
+
+    unsafe fn p2p_attribute(
+        &self,
+        attribute: hipDeviceP2PAttr,
+        src_device: hipDevice_t,
+        dst_device: hipDevice_t,
+    ) -> Result<i32, CUresult> {
+        let mut value = 0;
+        hip_call_cuda!(hipDeviceGetP2PAttribute(
+            &mut value, attribute, src_device, dst_device
+        ));
+        Ok(value)
+    }
+
+    unsafe fn copy(
+        &self,
+        (dst, dst_device): (hipDeviceptr_t, hipDevice_t),
+        (src, src_device): (hipDeviceptr_t, hipDevice_t),
+        byte_count: usize,
+        stream: Option<hipStream_t>,
+    ) -> Result<(), CUresult> {
+        match stream {
+            Some(stream) => {
+                hip_call_cuda!(hipMemcpyPeerAsync(
+                    dst.0, dst_device, src.0, src_device, byte_count, stream
+                ));
+            }
+            None => {
+                hip_call_cuda!(hipMemcpyPeer(
+                    dst.0, dst_device, src.0, src_device, byte_count
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    unsafe fn copy3d(
+        &self,
+        copy: &CUDA_MEMCPY3D,
+        stream: Option<*mut stream::Stream>,
+    ) -> Result<(), CUresult> {
+        match stream {
+            Some(stream) => memory::copy3d_async(copy, stream),
+            None => memory::copy3d(copy),
+        }
+    }
+}
+
+struct PeerAccess {
+    // (context, peer context) -> (device, peer device)
+    contexts: BTreeMap<(usize, usize), (hipDevice_t, hipDevice_t)>,
+    // (device, peer device) -> number of context pairs with enabled access
+    devices: BTreeMap<(hipDevice_t, hipDevice_t), usize>,
+}
+
+impl PeerAccess {
+    const fn new() -> Self {
+        Self {
+            contexts: BTreeMap::new(),
+            devices: BTreeMap::new(),
+        }
+    }
+
+    unsafe fn enable(
+        &mut self,
+        hip: &impl PeerDevices,
+        (context, device): (usize, hipDevice_t),
+        (peer_context, peer_device): (usize, hipDevice_t),
+        flags: u32,
+    ) -> Result<(), CUresult> {
+        if flags != 0 {
+            return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+        }
+        if self.contexts.contains_key(&(context, peer_context)) {
+            return Err(CUresult::CUDA_ERROR_PEER_ACCESS_ALREADY_ENABLED);
+        }
+        if device == peer_device || !hip.can_access_peer(device, peer_device)? {
+            return Err(CUresult::CUDA_ERROR_PEER_ACCESS_UNSUPPORTED);
+        }
+        // Access might have been already enabled by a HIP library running in
+        // the same process or left enabled by a destroyed context
+        if !self.devices.contains_key(&(device, peer_device)) {
+            match hip.enable_peer_access(device, peer_device) {
+                Ok(()) | Err(CUresult::CUDA_ERROR_PEER_ACCESS_ALREADY_ENABLED) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        *self.devices.entry((device, peer_device)).or_insert(0) += 1;
+        self.contexts
+            .insert((context, peer_context), (device, peer_device));
+        Ok(())
+    }
+
+    unsafe fn disable(
+        &mut self,
+        hip: &impl PeerDevices,
+        context: usize,
+        peer_context: usize,
+    ) -> Result<(), CUresult> {
+        let devices = self
+            .contexts
+            .get(&(context, peer_context))
+            .copied()
+            .ok_or(CUresult::CUDA_ERROR_PEER_ACCESS_NOT_ENABLED)?;
+        if self.devices.get(&devices) == Some(&1) {
+            match hip.disable_peer_access(devices.0, devices.1) {
+                Ok(()) | Err(CUresult::CUDA_ERROR_PEER_ACCESS_NOT_ENABLED) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        self.contexts.remove(&(context, peer_context));
+        self.release_pair(devices);
+        Ok(())
+    }
+
+    // HIP access is left enabled, we can't switch the current device during
+    // context destruction
+    fn remove_context(&mut self, context: usize) {
+        let removed = self
+            .contexts
+            .keys()
+            .copied()
+            .filter(|(ctx, peer_ctx)| *ctx == context || *peer_ctx == context)
+            .collect::<Vec<_>>();
+        for key in removed {
+            if let Some(devices) = self.contexts.remove(&key) {
+                self.release_pair(devices);
+            }
+        }
+    }
+
+    fn release_pair(&mut self, devices: (hipDevice_t, hipDevice_t)) {
+        if let Some(pairs) = self.devices.get_mut(&devices) {
+            *pairs -= 1;
+            if *pairs == 0 {
+                self.devices.remove(&devices);
+            }
+        }
+    }
+}
+
+unsafe fn current_context() -> Result<(usize, hipDevice_t), CUresult> {
+    let ctx = context::CONTEXT_STACK
+        .with(|stack| stack.borrow().last().map(|(ctx, _)| *ctx))
+        .ok_or(CUresult::CUDA_ERROR_INVALID_CONTEXT)?;
+    Ok((ctx as usize, LiveCheck::as_result(ctx)?.device))
+}
+
+pub(crate) unsafe fn enable_access(
+    peer_context: *mut context::Context,
+    flags: u32,
+) -> Result<(), CUresult> {
+    let current = current_context()?;
+    let peer_device = LiveCheck::as_result(peer_context)?.device;
+    let mut peer_access = PEER_ACCESS
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    peer_access.enable(&Hip, current, (peer_context as usize, peer_device), flags)
+}
+
+pub(crate) unsafe fn disable_access(peer_context: *mut context::Context) -> Result<(), CUresult> {
+    let (current, _) = current_context()?;
+    LiveCheck::as_result(peer_context)?;
+    let mut peer_access = PEER_ACCESS
+        .lock()
+        .map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    peer_access.disable(&Hip, current, peer_context as usize)
+}
+
+pub(crate) fn remove_context(context: *mut context::Context) {
+    if let Ok(mut peer_access) = PEER_ACCESS.lock() {
+        peer_access.remove_context(context as usize);
+    }
+}
+
+pub(crate) unsafe fn get_attribute(
+    value: *mut i32,
+    attribute: CUdevice_P2PAttribute,
+    src_device: hipDevice_t,
+    dst_device: hipDevice_t,
+) -> Result<(), CUresult> {
+    get_attribute_impl(&Hip, value, attribute, src_device, dst_device)
+}
+
+unsafe fn get_attribute_impl(
+    hip: &impl PeerDevices,
+    value: *mut i32,
+    attribute: CUdevice_P2PAttribute,
+    src_device: hipDevice_t,
+    dst_device: hipDevice_t,
+) -> Result<(), CUresult> {
+    if value == std::ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let attribute = attribute_from_cuda(attribute)?;
+    // Like CUDA, there are no attributes of a device with itself
+    if src_device == dst_device {
+        return Err(CUresult::CUDA_ERROR_INVALID_DEVICE);
+    }
+    *value = hip.p2p_attribute(attribute, src_device, dst_device)?;
+    Ok(())
+}
+
+fn attribute_from_cuda(attribute: CUdevice_P2PAttribute) -> Result<hipDeviceP2PAttr, CUresult> {
+    Ok(match attribute {
+        CUdevice_P2PAttribute::CU_DEVICE_P2P_ATTRIBUTE_PERFORMANCE_RANK => {
+            hipDeviceP2PAttr::hipDevP2PAttrPerformanceRank
+        }
+        CUdevice_P2PAttribute::CU_DEVICE_P2P_ATTRIBUTE_ACCESS_SUPPORTED => {
+            hipDeviceP2PAttr::hipDevP2PAttrAccessSupported
+        }
+        CUdevice_P2PAttribute::CU_DEVICE_P2P_ATTRIBUTE_NATIVE_ATOMIC_SUPPORTED => {
+            hipDeviceP2PAttr::hipDevP2PAttrNativeAtomicSupported
+        }
+        CUdevice_P2PAttribute::CU_DEVICE_P2P_ATTRIBUTE_CUDA_ARRAY_ACCESS_SUPPORTED => {
+            hipDeviceP2PAttr::hipDevP2PAttrHipArrayAccessSupported
+        }
+        _ => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    })
+}
+
+pub(crate) unsafe fn copy(
+    dst: hipDeviceptr_t,
+    dst_context: *mut context::Context,
+    src: hipDeviceptr_t,
+    src_context: *mut context::Context,
+    byte_count: usize,
+) -> Result<(), CUresult> {
+    copy_impl(
+        &Hip,
+        (dst, dst_context),
+        (src, src_context),
+        byte_count,
+        None,
+    )
+}
+
+pub(crate) unsafe fn copy_async(
+    dst: hipDeviceptr_t,
+    dst_context: *mut context::Context,
+    src: hipDeviceptr_t,
+    src_context: *mut context::Context,
+    byte_count: usize,
+    stream: *mut stream::Stream,
+) -> Result<(), CUresult> {
+    let hip_stream = stream::as_hip_stream(stream)?;
+    copy_impl(
+        &Hip,
+        (dst, dst_context),
+        (src, src_context),
+        byte_count,
+        Some(hip_stream),
+    )
+}
+
+unsafe fn copy_impl(
+    hip: &impl PeerDevices,
+    (dst, dst_context): (hipDeviceptr_t, *mut context::Context),
+    (src, src_context): (hipDeviceptr_t, *mut context::Context),
+    byte_count: usize,
+    stream: Option<hipStream_t>,
+) -> Result<(), CUresult> {
+    let dst_device = LiveCheck::as_result(dst_context)?.device;
+    let src_device = LiveCheck::as_result(src_context)?.device;
+    hip.copy((dst, dst_device), (src, src_device), byte_count, stream)
+}
+
+pub(crate) unsafe fn copy3d(copy: *const CUDA_MEMCPY3D_PEER) -> Result<(), CUresult> {
+    copy3d_impl(&Hip, copy, None)
+}
+
+pub(crate) unsafe fn copy3d_async(
+    copy: *const CUDA_MEMCPY3D_PEER,
+    stream: *mut stream::Stream,
+) -> Result<(), CUresult> {
+    copy3d_impl(&Hip, copy, Some(stream))
+}
+
+unsafe fn copy3d_impl(
+    hip: &impl PeerDevices,
+    copy: *const CUDA_MEMCPY3D_PEER,
+    stream: Option<*mut stream::Stream>,
+) -> Result<(), CUresult> {
+    let copy = copy3d_from_peer(copy)?;
+    hip.copy3d(&copy, stream)
+}
+
+// All allocations live in the unified address space, so contexts are not
+// needed to perform the copy
+unsafe fn copy3d_from_peer(copy: *const CUDA_MEMCPY3D_PEER) -> Result<CUDA_MEMCPY3D, CUresult> {
+    let copy = copy.as_ref().ok_or(CUresult::CUDA_ERROR_INVALID_VALUE)?;
+    Ok(CUDA_MEMCPY3D {
+        srcXInBytes: copy.srcXInBytes,
+        srcY: copy.srcY,
+        srcZ: copy.srcZ,
+        srcLOD: copy.srcLOD,
+        srcMemoryType: copy.srcMemoryType,
+        srcHost: copy.srcHost,
+        srcDevice: copy.srcDevice,
+        srcArray: copy.srcArray,
+        reserved0: std::ptr::null_mut(),
+        srcPitch: copy.srcPitch,
+        srcHeight: copy.srcHeight,
+        dstXInBytes: copy.dstXInBytes,
+        dstY: copy.dstY,
+        dstZ: copy.dstZ,
+        dstLOD: copy.dstLOD,
+        dstMemoryType: copy.dstMemoryType,
+        dstHost: copy.dstHost,
+        dstDevice: copy.dstDevice,
+        dstArray: copy.dstArray,
+        reserved1: std::ptr::null_mut(),
+        dstPitch: copy.dstPitch,
+        dstHeight: copy.dstHeight,
+        WidthInBytes: copy.WidthInBytes,
+        Height: copy.Height,
+        Depth: copy.Depth,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{copy3d_impl, copy_impl, get_attribute_impl, PeerAccess, PeerDevices};
+    use crate::r#impl::{context, stream, LiveCheck};
+    use cuda_types::*;
+    use hip_runtime_sys::*;
+    use std::{cell::RefCell, mem, ptr};
+
+    // Fully connected machine, except for device pairs in `no_peer`
+    struct MockDevices {
+        count: hipDevice_t,
+        no_peer: Vec<(hipDevice_t, hipDevice_t)>,
+        // Pairs that can access each other, but run out of peer resources
+        too_many_peers: Vec<(hipDevice_t, hipDevice_t)>,
+        enabled: RefCell<Vec<(hipDevice_t, hipDevice_t)>>,
+        hip_calls: RefCell<usize>,
+        // (dst, dst device, src, src device, byte count, stream)
+        copies: RefCell<Vec<(usize, hipDevice_t, usize, hipDevice_t, usize, Option<usize>)>>,
+        // (dst, src, width, height, depth, stream)
+        copies3d: RefCell<Vec<(usize, usize, usize, usize, usize, Option<usize>)>>,
+    }
+
+    impl MockDevices {
+        fn new(count: hipDevice_t) -> Self {
+            Self {
+                count,
+                no_peer: Vec::new(),
+                too_many_peers: Vec::new(),
+                enabled: RefCell::new(Vec::new()),
+                hip_calls: RefCell::new(0),
+                copies: RefCell::new(Vec::new()),
+                copies3d: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    fn context(device: hipDevice_t) -> *mut context::Context {
+        Box::into_raw(Box::new(LiveCheck::new(
+            context::ContextData::new_non_primary(0, device),
+        )))
+    }
+
+    impl PeerDevices for MockDevices {
+        unsafe fn can_access_peer(
+            &self,
+            device: hipDevice_t,
+            peer: hipDevice_t,
+        ) -> Result<bool, CUresult> {
+            if device >= self.count || peer >= self.count {
+                return Err(CUresult::CUDA_ERROR_INVALID_DEVICE);
+            }
+            Ok(device != peer && !self.no_peer.contains(&(device, peer)))
+        }
+
+        unsafe fn enable_peer_access(
+            &self,
+            device: hipDevice_t,
+            peer: hipDevice_t,
+        ) -> Result<(), CUresult> {
+            *self.hip_calls.borrow_mut() += 1;
+            if self.too_many_peers.contains(&(device, peer)) {
+                return Err(CUresult::CUDA_ERROR_TOO_MANY_PEERS);
+            }
+            let mut enabled = self.enabled.borrow_mut();
+            if enabled.contains(&(device, peer)) {
+                return Err(CUresult::CUDA_ERROR_PEER_ACCESS_ALREADY_ENABLED);
+            }
+            enabled.push((device, peer));
+            Ok(())
+        }
+
+        unsafe fn disable_peer_access(
+            &self,
+            device: hipDevice_t,
+            peer: hipDevice_t,
+        ) -> Result<(), CUresult> {
+            *self.hip_calls.borrow_mut() += 1;
+            let mut enabled = self.enabled.borrow_mut();
+            let index = enabled
+                .iter()
+                .position(|pair| *pair == (device, peer))
+                .ok_or(CUresult::CUDA_ERROR_PEER_ACCESS_NOT_ENABLED)?;
+            enabled.remove(index);
+            Ok(())
+        }
+
+        unsafe fn p2p_attribute(
+            &self,
+            attribute: hipDeviceP2PAttr,
+            src_device: hipDevice_t,
+            dst_device: hipDevice_t,
+        ) -> Result<i32, CUresult> {
+            let can_access = self.can_access_peer(src_device, dst_device)?;
+            if attribute == hipDeviceP2PAttr::hipDevP2PAttrPerformanceRank {
+                Ok(0)
+            } else {
+                Ok(can_access as i32)
+            }
+        }
+
+        unsafe fn copy(
+            &self,
+            (dst, dst_device): (hipDeviceptr_t, hipDevice_t),
+            (src, src_device): (hipDeviceptr_t, hipDevice_t),
+            byte_count: usize,
+            stream: Option<hipStream_t>,
+        ) -> Result<(), CUresult> {
+            if dst_device >= self.count || src_device >= self.count {
+                return Err(CUresult::CUDA_ERROR_INVALID_DEVICE);
+            }
+            self.copies.borrow_mut().push((
+                dst.0 as usize,
+                dst_device,
+                src.0 as usize,
+                src_device,
+                byte_count,
+                stream.map(|stream| stream as usize),
+            ));
+            Ok(())
+        }
+
+        unsafe fn copy3d(
+            &self,
+            copy: &CUDA_MEMCPY3D,
+            stream: Option<*mut stream::Stream>,
+        ) -> Result<(), CUresult> {
+            self.copies3d.borrow_mut().push((
+                copy.dstDevice.0 as usize,
+                copy.srcDevice.0 as usize,
+                copy.WidthInBytes,
+                copy.Height,
+                copy.Depth,
+                stream.map(|stream| stream as usize),
+            ));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn enable_and_disable() {
+        let hip = MockDevices::new(2);
+        let mut access = PeerAccess::new();
+        unsafe {
+            assert_eq!(access.enable(&hip, (1, 0), (2, 1), 0), Ok(()));
+            assert_eq!(
+                access.enable(&hip, (1, 0), (2, 1), 0),
+                Err(CUresult::CUDA_ERROR_PEER_ACCESS_ALREADY_ENABLED)
+            );
+            assert_eq!(*hip.enabled.borrow(), vec![(0, 1)]);
+            assert_eq!(access.disable(&hip, 1, 2), Ok(()));
+            assert_eq!(
+                access.disable(&hip, 1, 2),
+                Err(CUresult::CUDA_ERROR_PEER_ACCESS_NOT_ENABLED)
+            );
+        }
+        assert!(hip.enabled.borrow().is_empty());
+    }
+
+    #[test]
+    fn access_is_directional() {
+        let hip = MockDevices::new(2);
+        let mut access = PeerAccess::new();
+        unsafe {
+            assert_eq!(access.enable(&hip, (1, 0), (2, 1), 0), Ok(()));
+            assert_eq!(access.enable(&hip, (2, 1), (1, 0), 0), Ok(()));
+            assert_eq!(
+                access.disable(&hip, 1, 3),
+                Err(CUresult::CUDA_ERROR_PEER_ACCESS_NOT_ENABLED)
+            );
+        }
+        assert_eq!(*hip.enabled.borrow(), vec![(0, 1), (1, 0)]);
+    }
+
+    #[test]
+    fn contexts_on_same_device_share_hip_access() {
+        let hip = MockDevices::new(3);
+        let mut access = PeerAccess::new();
+        unsafe {
+            // Two contexts on device 0, both access the context on device 2
+            assert_eq!(access.enable(&hip, (1, 0), (3, 2), 0), Ok(()));
+            assert_eq!(access.enable(&hip, (2, 0), (3, 2), 0), Ok(()));
+            assert_eq!(*hip.hip_calls.borrow(), 1);
+            assert_eq!(access.disable(&hip, 1, 3), Ok(()));
+            assert_eq!(*hip.enabled.borrow(), vec![(0, 2)]);
+            assert_eq!(access.disable(&hip, 2, 3), Ok(()));
+        }
+        assert_eq!(*hip.hip_calls.borrow(), 2);
+        assert!(hip.enabled.borrow().is_empty());
+    }
+
+    #[test]
+    fn unsupported_peers() {
+        let mut hip = MockDevices::new(4);
+        hip.no_peer.push((0, 3));
+        let mut access = PeerAccess::new();
+        unsafe {
+            assert_eq!(
+                access.enable(&hip, (1, 0), (4, 3), 0),
+                Err(CUresult::CUDA_ERROR_PEER_ACCESS_UNSUPPORTED)
+            );
+            assert_eq!(
+                access.enable(&hip, (1, 0), (2, 0), 0),
+                Err(CUresult::CUDA_ERROR_PEER_ACCESS_UNSUPPORTED)
+            );
+            assert_eq!(
+                access.enable(&hip, (1, 0), (5, 4), 0),
+                Err(CUresult::CUDA_ERROR_INVALID_DEVICE)
+            );
+            assert_eq!(
+                access.enable(&hip, (1, 0), (3, 2), 1),
+                Err(CUresult::CUDA_ERROR_INVALID_VALUE)
+            );
+        }
+        assert_eq!(*hip.hip_calls.borrow(), 0);
+    }
+
+    #[test]
+    fn failed_enable_leaves_no_pair() {
+        let mut hip = MockDevices::new(2);
+        hip.too_many_peers.push((0, 1));
+        let mut access = PeerAccess::new();
+        unsafe {
+            assert_eq!(
+                access.enable(&hip, (1, 0), (2, 1), 0),
+                Err(CUresult::CUDA_ERROR_TOO_MANY_PEERS)
+            );
+            assert!(access.contexts.is_empty());
+            assert!(access.devices.is_empty());
+            hip.too_many_peers.clear();
+            // The next enable must still reach HIP
+            assert_eq!(access.enable(&hip, (1, 0), (2, 1), 0), Ok(()));
+        }
+        assert_eq!(*hip.hip_calls.borrow(), 2);
+        assert_eq!(*hip.enabled.borrow(), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn removed_context_releases_pairs() {
+        let hip = MockDevices::new(2);
+        let mut access = PeerAccess::new();
+        unsafe {
+            assert_eq!(access.enable(&hip, (1, 0), (2, 1), 0), Ok(()));
+            access.remove_context(2);
+            assert!(access.contexts.is_empty());
+            assert!(access.devices.is_empty());
+            assert_eq!(
+                access.disable(&hip, 1, 2),
+                Err(CUresult::CUDA_ERROR_PEER_ACCESS_NOT_ENABLED)
+            );
+            // HIP access is still enabled from the destroyed context
+            assert_eq!(access.enable(&hip, (1, 0), (3, 1), 0), Ok(()));
+            assert_eq!(access.disable(&hip, 1, 3), Ok(()));
+        }
+        assert!(hip.enabled.borrow().is_empty());
+    }
+
+    #[test]
+    fn copy_uses_devices_of_contexts() {
+        let hip = MockDevices::new(2);
+        let ctx0 = context(0);
+        let ctx1 = context(1);
+        unsafe {
+            assert_eq!(
+                copy_impl(
+                    &hip,
+                    (hipDeviceptr_t(0x1000 as _), ctx1),
+                    (hipDeviceptr_t(0x2000 as _), ctx0),
+                    64,
+                    None,
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                copy_impl(
+                    &hip,
+                    (hipDeviceptr_t(0x3000 as _), ctx0),
+                    (hipDeviceptr_t(0x4000 as _), ctx1),
+                    32,
+                    Some(0x10 as hipStream_t),
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                copy_impl(
+                    &hip,
+                    (hipDeviceptr_t(0x3000 as _), ptr::null_mut()),
+                    (hipDeviceptr_t(0x4000 as _), ctx1),
+                    32,
+                    None,
+                ),
+                Err(CUresult::CUDA_ERROR_INVALID_VALUE)
+            );
+        }
+        assert_eq!(
+            *hip.copies.borrow(),
+            vec![
+                (0x1000, 1, 0x2000, 0, 64, None),
+                (0x3000, 0, 0x4000, 1, 32, Some(0x10))
+            ]
+        );
+    }
+
+    #[test]
+    fn copy3d_keeps_copy_description() {
+        let hip = MockDevices::new(2);
+        let mut copy = unsafe { mem::zeroed::<CUDA_MEMCPY3D_PEER>() };
+        copy.srcMemoryType = CUmemorytype::CU_MEMORYTYPE_DEVICE;
+        copy.srcDevice = CUdeviceptr_v2(0x2000 as _);
+        copy.srcContext = context(0) as _;
+        copy.dstMemoryType = CUmemorytype::CU_MEMORYTYPE_DEVICE;
+        copy.dstDevice = CUdeviceptr_v2(0x1000 as _);
+        copy.dstContext = context(1) as _;
+        copy.WidthInBytes = 16;
+        copy.Height = 4;
+        copy.Depth = 2;
+        unsafe {
+            assert_eq!(copy3d_impl(&hip, &copy, None), Ok(()));
+            assert_eq!(
+                copy3d_impl(&hip, &copy, Some(0x20 as *mut stream::Stream)),
+                Ok(())
+            );
+            assert_eq!(
+                copy3d_impl(&hip, ptr::null(), None),
+                Err(CUresult::CUDA_ERROR_INVALID_VALUE)
+            );
+        }
+        assert_eq!(
+            *hip.copies3d.borrow(),
+            vec![
+                (0x1000, 0x2000, 16, 4, 2, None),
+                (0x1000, 0x2000, 16, 4, 2, Some(0x20))
+            ]
+        );
+    }
+
+    #[test]
+    fn get_attribute() {
+        let mut hip = MockDevices::new(3);
+        hip.no_peer.push((0, 2));
+        let access_supported = CUdevice_P2PAttribute::CU_DEVICE_P2P_ATTRIBUTE_ACCESS_SUPPORTED;
+        let mut value = -1;
+        unsafe {
+            assert_eq!(
+                get_attribute_impl(&hip, &mut value, access_supported, 0, 1),
+                Ok(())
+            );
+            assert_eq!(value, 1);
+            assert_eq!(
+                get_attribute_impl(&hip, &mut value, access_supported, 0, 2),
+                Ok(())
+            );
+            assert_eq!(value, 0);
+            assert_eq!(
+                get_attribute_impl(
+                    &hip,
+                    &mut value,
+                    CUdevice_P2PAttribute::CU_DEVICE_P2P_ATTRIBUTE_PERFORMANCE_RANK,
+                    2,
+                    1
+                ),
+                Ok(())
+            );
+            assert_eq!(value, 0);
+            assert_eq!(
+                get_attribute_impl(&hip, &mut value, access_supported, 1, 1),
+                Err(CUresult::CUDA_ERROR_INVALID_DEVICE)
+            );
+            assert_eq!(
+                get_attribute_impl(&hip, &mut value, access_supported, 0, 3),
+                Err(CUresult::CUDA_ERROR_INVALID_DEVICE)
+            );
+            assert_eq!(
+                get_attribute_impl(&hip, &mut value, CUdevice_P2PAttribute(100), 0, 1),
+                Err(CUresult::CUDA_ERROR_INVALID_VALUE)
+            );
+            assert_eq!(
+                get_attribute_impl(&hip, ptr::null_mut(), access_supported, 0, 1),
+                Err(CUresult::CUDA_ERROR_INVALID_VALUE)
+            );
+        }
+    }
+}
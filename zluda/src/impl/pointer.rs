@@ -9,7 +9,10 @@ use hip_runtime_sys::{
     hipPointer_attribute,
 };
 
-use crate::{hip_call_cuda, r#impl::IntoCuda};
+use crate::{
+    hip_call_cuda,
+    r#impl::{vmm, IntoCuda},
+};
 
 pub(crate) unsafe fn get_attribute(
     data: *mut c_void,
@@ -19,6 +22,10 @@ pub(crate) unsafe fn get_attribute(
     if data == ptr::null_mut() {
         return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
     }
+    // HIP does not know about ranges mapped with cuMemMap(...)
+    if let Some(result) = vmm::get_pointer_attribute(data, attribute, ptr) {
+        return result;
+    }
     let mut attribs = mem::zeroed();
     hip_call_cuda! { hipPointerGetAttributes(&mut attribs, ptr.0 as _) };
     // TODO: implement HIP_POINTER_ATTRIBUTE_CONTEXT
@@ -45,7 +52,7 @@ pub(crate) unsafe fn get_attribute(
             Ok(())
         }
         hipPointer_attribute::HIP_POINTER_ATTRIBUTE_IS_MANAGED => {
-            *(data as *mut _) = attribs.isManaged;
+            *(data as *mut bool) = attribs.isManaged != 0;
             Ok(())
         }
         hipPointer_attribute::HIP_POINTER_ATTRIBUTE_RANGE_START_ADDR => {
@@ -76,6 +83,8 @@ fn memory_type(cu: hipMemoryType) -> Result<CUmemorytype, hipError_t> {
         hipMemoryType::hipMemoryTypeDevice => Ok(CUmemorytype::CU_MEMORYTYPE_DEVICE),
         hipMemoryType::hipMemoryTypeArray => Ok(CUmemorytype::CU_MEMORYTYPE_ARRAY),
         hipMemoryType::hipMemoryTypeUnified => Ok(CUmemorytype::CU_MEMORYTYPE_UNIFIED),
+        // CUDA reports managed allocations as device memory
+        hipMemoryType::hipMemoryTypeManaged => Ok(CUmemorytype::CU_MEMORYTYPE_DEVICE),
         _ => Err(hipError_t::hipErrorInvalidValue),
     }
 }
@@ -1,13 +1,23 @@
-use super::{context, LiveCheck, ZludaObject};
+use super::{context, function, mempool, module, FromCuda, IntoCuda, LiveCheck, ZludaObject};
 use crate::{hip_call_cuda, r#impl::hipfix};
-use cuda_types::{CUhostFn, CUresult};
+use cuda_types::{CUhostFn, CUresult, CUstreamBatchMemOpParams, CUstreamBatchMemOpType};
 use hip_runtime_sys::*;
-use std::{ffi::c_void, ptr};
+use std::{ffi::c_void, ptr, slice};
 
 pub(crate) const CU_STREAM_NULL: *mut Stream = 0 as *mut _;
 pub(crate) const CU_STREAM_LEGACY: *mut Stream = 1 as *mut _;
 pub(crate) const CU_STREAM_PER_THREAD: *mut Stream = 2 as *mut _;
 
+const CU_STREAM_WAIT_VALUE_GEQ: u32 = 0;
+const CU_STREAM_WAIT_VALUE_EQ: u32 = 1;
+const CU_STREAM_WAIT_VALUE_AND: u32 = 2;
+const CU_STREAM_WAIT_VALUE_NOR: u32 = 3;
+const CU_STREAM_WAIT_VALUE_FLUSH: u32 = 1 << 30;
+const CU_STREAM_WRITE_VALUE_NO_MEMORY_BARRIER: u32 = 1;
+const CU_STREAM_MEM_OP_MAX_BATCH: u32 = 256;
+
+const MEM_OPS_MODULE: &'static str = concat!(include_str!("stream_mem_ops.ptx"), "\0");
+
 pub(crate) type Stream = LiveCheck<StreamData>;
 
 impl ZludaObject for StreamData {
@@ -105,7 +115,7 @@ pub(crate) unsafe fn synchronize(
 ) -> Result<(), CUresult> {
     let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
     hip_call_cuda!(hipStreamSynchronize(hip_stream));
-    Ok(())
+    mempool::release_unused()
 }
 
 pub(crate) unsafe fn thread_exchange_capture_mode(
@@ -220,3 +230,222 @@ pub(crate) unsafe fn is_capturing(
     hip_call_cuda! { hipStreamIsCapturing(hip_stream, capture_status) };
     Ok(())
 }
+
+#[derive(Clone, Copy)]
+pub(crate) enum MemOpValue {
+    U32(u32),
+    U64(u64),
+}
+
+pub(crate) unsafe fn wait_value(
+    stream: *mut Stream,
+    addr: hipDeviceptr_t,
+    value: MemOpValue,
+    flags: u32,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    // HIP has no way to flush remote writes, we report
+    // CU_DEVICE_ATTRIBUTE_CAN_FLUSH_REMOTE_WRITES as 0
+    if flags & CU_STREAM_WAIT_VALUE_FLUSH != 0 {
+        return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED);
+    }
+    // CUDA's GEQ is cyclic: it waits for (int32_t)(*addr - value) >= 0, HIP's
+    // hipStreamWaitValueGte compares unsigned values, so GEQ always goes
+    // through the fallback kernel
+    let (mut cuda_mode, hip_mode) = match flags {
+        CU_STREAM_WAIT_VALUE_GEQ => (CU_STREAM_WAIT_VALUE_GEQ, None),
+        CU_STREAM_WAIT_VALUE_EQ => (CU_STREAM_WAIT_VALUE_EQ, Some(hipStreamWaitValueEq)),
+        CU_STREAM_WAIT_VALUE_AND => (CU_STREAM_WAIT_VALUE_AND, Some(hipStreamWaitValueAnd)),
+        CU_STREAM_WAIT_VALUE_NOR => (CU_STREAM_WAIT_VALUE_NOR, Some(hipStreamWaitValueNor)),
+        _ => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    };
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    let mut addr = addr.0;
+    if let Some(hip_mode) = hip_mode {
+        let result = match value {
+            MemOpValue::U32(value) => {
+                hipStreamWaitValue32(hip_stream, addr, value, hip_mode, u32::MAX)
+            }
+            MemOpValue::U64(value) => {
+                hipStreamWaitValue64(hip_stream, addr, value, hip_mode, u64::MAX)
+            }
+        };
+        if result != hipError_t::hipErrorNotSupported {
+            return result.into_cuda().into();
+        }
+    }
+    match value {
+        MemOpValue::U32(mut value) => launch_mem_op_kernel(
+            b"zluda_wait_value32\0",
+            &mut [
+                &mut addr as *mut _ as _,
+                &mut value as *mut _ as _,
+                &mut cuda_mode as *mut _ as _,
+            ],
+            stream,
+            default_stream_per_thread,
+        ),
+        MemOpValue::U64(mut value) => launch_mem_op_kernel(
+            b"zluda_wait_value64\0",
+            &mut [
+                &mut addr as *mut _ as _,
+                &mut value as *mut _ as _,
+                &mut cuda_mode as *mut _ as _,
+            ],
+            stream,
+            default_stream_per_thread,
+        ),
+    }
+}
+
+pub(crate) unsafe fn attach_mem_async(
+    stream: *mut Stream,
+    dptr: hipDeviceptr_t,
+    length: usize,
+    flags: u32,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    // CU_MEM_ATTACH_* flags have the same values as hipMemAttach*
+    hip_call_cuda! { hipStreamAttachMemAsync(hip_stream, dptr.0, length, flags) };
+    Ok(())
+}
+
+pub(crate) unsafe fn write_value(
+    stream: *mut Stream,
+    addr: hipDeviceptr_t,
+    value: MemOpValue,
+    flags: u32,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    // CU_STREAM_WRITE_VALUE_NO_MEMORY_BARRIER only relaxes ordering, it's
+    // always fine to ignore it
+    if flags & !CU_STREAM_WRITE_VALUE_NO_MEMORY_BARRIER != 0 {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let hip_stream = hipfix::as_hip_stream_per_thread(stream, default_stream_per_thread)?;
+    let mut addr = addr.0;
+    let result = match value {
+        MemOpValue::U32(value) => hipStreamWriteValue32(hip_stream, addr, value, 0),
+        MemOpValue::U64(value) => hipStreamWriteValue64(hip_stream, addr, value, 0),
+    };
+    if result != hipError_t::hipErrorNotSupported {
+        return result.into_cuda().into();
+    }
+    match value {
+        MemOpValue::U32(mut value) => launch_mem_op_kernel(
+            b"zluda_write_value32\0",
+            &mut [&mut addr as *mut _ as _, &mut value as *mut _ as _],
+            stream,
+            default_stream_per_thread,
+        ),
+        MemOpValue::U64(mut value) => launch_mem_op_kernel(
+            b"zluda_write_value64\0",
+            &mut [&mut addr as *mut _ as _, &mut value as *mut _ as _],
+            stream,
+            default_stream_per_thread,
+        ),
+    }
+}
+
+pub(crate) unsafe fn batch_mem_op(
+    stream: *mut Stream,
+    count: u32,
+    params: *mut CUstreamBatchMemOpParams,
+    flags: u32,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    if flags != 0 || count > CU_STREAM_MEM_OP_MAX_BATCH {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    if count == 0 {
+        return Ok(());
+    }
+    if params == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    for param in slice::from_raw_parts(params, count as usize) {
+        match param.operation {
+            CUstreamBatchMemOpType::CU_STREAM_MEM_OP_WAIT_VALUE_32 => wait_value(
+                stream,
+                FromCuda::from_cuda(param.waitValue.address),
+                MemOpValue::U32(param.waitValue.__bindgen_anon_1.value),
+                param.waitValue.flags,
+                default_stream_per_thread,
+            )?,
+            CUstreamBatchMemOpType::CU_STREAM_MEM_OP_WAIT_VALUE_64 => wait_value(
+                stream,
+                FromCuda::from_cuda(param.waitValue.address),
+                MemOpValue::U64(param.waitValue.__bindgen_anon_1.value64),
+                param.waitValue.flags,
+                default_stream_per_thread,
+            )?,
+            CUstreamBatchMemOpType::CU_STREAM_MEM_OP_WRITE_VALUE_32 => write_value(
+                stream,
+                FromCuda::from_cuda(param.writeValue.address),
+                MemOpValue::U32(param.writeValue.__bindgen_anon_1.value),
+                param.writeValue.flags,
+                default_stream_per_thread,
+            )?,
+            CUstreamBatchMemOpType::CU_STREAM_MEM_OP_WRITE_VALUE_64 => write_value(
+                stream,
+                FromCuda::from_cuda(param.writeValue.address),
+                MemOpValue::U64(param.writeValue.__bindgen_anon_1.value64),
+                param.writeValue.flags,
+                default_stream_per_thread,
+            )?,
+            CUstreamBatchMemOpType::CU_STREAM_MEM_OP_BARRIER => launch_mem_op_kernel(
+                b"zluda_memory_barrier\0",
+                &mut [],
+                stream,
+                default_stream_per_thread,
+            )?,
+            // We report CU_DEVICE_ATTRIBUTE_CAN_FLUSH_REMOTE_WRITES as 0
+            CUstreamBatchMemOpType::CU_STREAM_MEM_OP_FLUSH_REMOTE_WRITES => {
+                return Err(CUresult::CUDA_ERROR_NOT_SUPPORTED)
+            }
+            _ => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+        }
+    }
+    Ok(())
+}
+
+// Used when HIP reports a stream memory operation as not supported. Kernels
+// are compiled from stream_mem_ops.ptx once per context
+unsafe fn launch_mem_op_kernel(
+    name: &[u8],
+    args: &mut [*mut c_void],
+    stream: *mut Stream,
+    default_stream_per_thread: bool,
+) -> Result<(), CUresult> {
+    let mem_ops_module =
+        context::with_current(|ctx| ctx.with_inner_mut(|mutable| mutable.mem_ops_module))??;
+    let mem_ops_module = match mem_ops_module {
+        Some(mem_ops_module) => mem_ops_module,
+        None => {
+            let mut new_module = ptr::null_mut();
+            module::load_data(&mut new_module, MEM_OPS_MODULE.as_ptr().cast())?;
+            // If we lost the race the other module stays with the context
+            // until it's destroyed, same as any other module
+            context::with_current(|ctx| {
+                ctx.with_inner_mut(|mutable| *mutable.mem_ops_module.get_or_insert(new_module))
+            })??
+        }
+    };
+    let mut func = ptr::null_mut();
+    module::get_function(&mut func, mem_ops_module, name.as_ptr().cast())?;
+    function::launch_kernel(
+        func,
+        1,
+        1,
+        1,
+        1,
+        1,
+        1,
+        0,
+        stream,
+        args.as_mut_ptr(),
+        ptr::null_mut(),
+        default_stream_per_thread,
+    )
+}
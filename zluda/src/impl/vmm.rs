@@ -0,0 +1,402 @@
+// Virtual memory management: physical allocations (cuMemCreate) are mapped
+// into reserved address ranges (cuMemAddressReserve). HIP knows nothing about
+// such ranges in hipPointerGetAttributes, so we keep track of the mappings
+// ourselves and answer pointer queries about them here
+use super::{LiveCheck, ZludaObject};
+use crate::hip_call_cuda;
+use cuda_types::*;
+use hip_runtime_sys::*;
+use std::{
+    collections::BTreeMap,
+    ffi::{c_uint, c_ulonglong, c_void},
+    ptr,
+    sync::Mutex,
+};
+
+pub(crate) type Allocation = LiveCheck<AllocationData>;
+
+impl ZludaObject for AllocationData {
+    #[cfg(target_pointer_width = "64")]
+    const LIVENESS_COOKIE: usize = 0x3a7e5b0d91c4f268;
+    #[cfg(target_pointer_width = "32")]
+    const LIVENESS_COOKIE: usize = 0x91c4f268;
+    const LIVENESS_FAIL: CUresult = CUresult::CUDA_ERROR_INVALID_VALUE;
+
+    // Physical memory stays alive as long as it's mapped somewhere
+    fn drop_with_result(&mut self, _by_owner: bool) -> Result<(), CUresult> {
+        hip_call_cuda!(hipMemRelease(self.base));
+        Ok(())
+    }
+}
+
+pub(crate) struct AllocationData {
+    base: hipMemGenericAllocationHandle_t,
+    size: usize,
+    prop: CUmemAllocationProp,
+}
+
+struct Mapping {
+    size: usize,
+    // hipMemGenericAllocationHandle_t
+    base: usize,
+    allocation_size: usize,
+    device: i32,
+    handle_types: CUmemAllocationHandleType,
+}
+
+// Keyed by the start address of the mapping
+static MAPPINGS: Mutex<BTreeMap<usize, Mapping>> = Mutex::new(BTreeMap::new());
+
+pub(crate) unsafe fn address_reserve(
+    ptr: *mut hipDeviceptr_t,
+    size: usize,
+    alignment: usize,
+    addr: hipDeviceptr_t,
+    flags: c_ulonglong,
+) -> Result<(), CUresult> {
+    if ptr == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    hip_call_cuda!(hipMemAddressReserve(
+        ptr.cast(),
+        size,
+        alignment,
+        addr.0,
+        flags
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn address_free(ptr: hipDeviceptr_t, size: usize) -> Result<(), CUresult> {
+    hip_call_cuda!(hipMemAddressFree(ptr.0, size));
+    Ok(())
+}
+
+pub(crate) unsafe fn create(
+    handle: *mut *mut Allocation,
+    size: usize,
+    prop: *const CUmemAllocationProp,
+    flags: c_ulonglong,
+) -> Result<(), CUresult> {
+    if handle == ptr::null_mut() || prop == ptr::null() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let prop = *prop;
+    let mut base = ptr::null_mut();
+    hip_call_cuda!(hipMemCreate(&mut base, size, &prop_from_cuda(&prop), flags));
+    *handle = Box::into_raw(Box::new(LiveCheck::new(AllocationData {
+        base,
+        size,
+        prop,
+    })));
+    Ok(())
+}
+
+pub(crate) unsafe fn release(handle: *mut Allocation) -> Result<(), CUresult> {
+    LiveCheck::as_result(handle)?;
+    LiveCheck::drop_box_with_result(handle, false)
+}
+
+pub(crate) unsafe fn map(
+    ptr: hipDeviceptr_t,
+    size: usize,
+    offset: usize,
+    handle: *mut Allocation,
+    flags: c_ulonglong,
+) -> Result<(), CUresult> {
+    let allocation = LiveCheck::as_result(handle)?;
+    if offset
+        .checked_add(size)
+        .map_or(true, |end| end > allocation.size)
+    {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let mut mappings = MAPPINGS.lock().map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    hip_call_cuda!(hipMemMap(ptr.0, size, offset, allocation.base, flags));
+    mappings.insert(
+        ptr.0 as usize,
+        Mapping {
+            size,
+            base: allocation.base as usize,
+            allocation_size: allocation.size,
+            device: allocation.prop.location.id,
+            handle_types: allocation.prop.requestedHandleTypes,
+        },
+    );
+    Ok(())
+}
+
+pub(crate) unsafe fn unmap(ptr: hipDeviceptr_t, size: usize) -> Result<(), CUresult> {
+    let mut mappings = MAPPINGS.lock().map_err(|_| CUresult::CUDA_ERROR_UNKNOWN)?;
+    hip_call_cuda!(hipMemUnmap(ptr.0, size));
+    // Unmapped range can cover multiple mappings
+    let start = ptr.0 as usize;
+    let unmapped = mappings
+        .range(start..start.saturating_add(size))
+        .map(|(start, _)| *start)
+        .collect::<Vec<_>>();
+    for start in unmapped {
+        mappings.remove(&start);
+    }
+    Ok(())
+}
+
+pub(crate) unsafe fn set_access(
+    ptr: hipDeviceptr_t,
+    size: usize,
+    desc: *const CUmemAccessDesc,
+    count: usize,
+) -> Result<(), CUresult> {
+    let desc = access_descs_from_cuda(desc, count)?;
+    hip_call_cuda!(hipMemSetAccess(ptr.0, size, desc.as_ptr(), desc.len()));
+    Ok(())
+}
+
+pub(crate) unsafe fn get_access(
+    flags: *mut c_ulonglong,
+    location: *const CUmemLocation,
+    ptr: hipDeviceptr_t,
+) -> Result<(), CUresult> {
+    let location = match location.as_ref() {
+        Some(location) => location_from_cuda(location),
+        None => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    };
+    hip_call_cuda!(hipMemGetAccess(flags, &location, ptr.0));
+    Ok(())
+}
+
+pub(crate) unsafe fn get_allocation_granularity(
+    granularity: *mut usize,
+    prop: *const CUmemAllocationProp,
+    option: CUmemAllocationGranularity_flags,
+) -> Result<(), CUresult> {
+    let prop = match prop.as_ref() {
+        Some(prop) => prop_from_cuda(prop),
+        None => return Err(CUresult::CUDA_ERROR_INVALID_VALUE),
+    };
+    hip_call_cuda!(hipMemGetAllocationGranularity(
+        granularity,
+        &prop,
+        hipMemAllocationGranularity_flags(option.0 as _)
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn get_allocation_properties_from_handle(
+    prop: *mut CUmemAllocationProp,
+    handle: *mut Allocation,
+) -> Result<(), CUresult> {
+    let allocation = LiveCheck::as_result(handle)?;
+    if prop == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    *prop = allocation.prop;
+    Ok(())
+}
+
+pub(crate) unsafe fn export_to_shareable_handle(
+    shareable_handle: *mut c_void,
+    handle: *mut Allocation,
+    handle_type: CUmemAllocationHandleType,
+    flags: c_ulonglong,
+) -> Result<(), CUresult> {
+    let allocation = LiveCheck::as_result(handle)?;
+    hip_call_cuda!(hipMemExportToShareableHandle(
+        shareable_handle,
+        allocation.base,
+        hipMemAllocationHandleType(handle_type.0 as _),
+        flags
+    ));
+    Ok(())
+}
+
+pub(crate) unsafe fn import_from_shareable_handle(
+    handle: *mut *mut Allocation,
+    os_handle: *mut c_void,
+    handle_type: CUmemAllocationHandleType,
+) -> Result<(), CUresult> {
+    if handle == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let mut base = ptr::null_mut();
+    hip_call_cuda!(hipMemImportFromShareableHandle(
+        &mut base,
+        os_handle,
+        hipMemAllocationHandleType(handle_type.0 as _)
+    ));
+    *handle = new_allocation_from_hip(base)?;
+    Ok(())
+}
+
+// Every call creates a new handle that has to be released separately
+pub(crate) unsafe fn retain_allocation_handle(
+    handle: *mut *mut Allocation,
+    addr: *mut c_void,
+) -> Result<(), CUresult> {
+    if handle == ptr::null_mut() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    let mut base = ptr::null_mut();
+    hip_call_cuda!(hipMemRetainAllocationHandle(&mut base, addr));
+    *handle = new_allocation_from_hip(base)?;
+    Ok(())
+}
+
+unsafe fn new_allocation_from_hip(
+    base: hipMemGenericAllocationHandle_t,
+) -> Result<*mut Allocation, CUresult> {
+    let mut hip_prop = std::mem::zeroed();
+    if hipMemGetAllocationPropertiesFromHandle(&mut hip_prop, base) != hipError_t::hipSuccess {
+        let _ = hipMemRelease(base);
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    // HIP does not tell us the size of the allocation, we know it only if it's
+    // mapped somewhere in this process. Otherwise HIP checks cuMemMap(...) bounds
+    let size = MAPPINGS
+        .lock()
+        .ok()
+        .and_then(|mappings| {
+            mappings
+                .values()
+                .find(|mapping| mapping.base == base as usize)
+                .map(|mapping| mapping.allocation_size)
+        })
+        .unwrap_or(usize::MAX);
+    let prop = prop_to_cuda(&hip_prop);
+    Ok(Box::into_raw(Box::new(LiveCheck::new(AllocationData {
+        base,
+        size,
+        prop,
+    }))))
+}
+
+// Called by cuPointerGetAttribute(...), returns None if the pointer is not
+// in any of the mapped ranges
+pub(crate) unsafe fn get_pointer_attribute(
+    data: *mut c_void,
+    attribute: hipPointer_attribute,
+    ptr: hipDeviceptr_t,
+) -> Option<Result<(), CUresult>> {
+    let mappings = MAPPINGS.lock().ok()?;
+    let (start, mapping) = find_mapping(&mappings, ptr.0 as usize)?;
+    Some(match attribute {
+        hipPointer_attribute::HIP_POINTER_ATTRIBUTE_MEMORY_TYPE => {
+            *(data as *mut _) = CUmemorytype::CU_MEMORYTYPE_DEVICE;
+            Ok(())
+        }
+        hipPointer_attribute::HIP_POINTER_ATTRIBUTE_DEVICE_POINTER => {
+            *(data as *mut _) = ptr;
+            Ok(())
+        }
+        hipPointer_attribute::HIP_POINTER_ATTRIBUTE_HOST_POINTER => {
+            Err(CUresult::CUDA_ERROR_INVALID_VALUE)
+        }
+        hipPointer_attribute::HIP_POINTER_ATTRIBUTE_IS_MANAGED
+        | hipPointer_attribute::HIP_POINTER_ATTRIBUTE_IS_LEGACY_HIP_IPC_CAPABLE => {
+            *(data as *mut bool) = false;
+            Ok(())
+        }
+        hipPointer_attribute::HIP_POINTER_ATTRIBUTE_MAPPED => {
+            *(data as *mut bool) = true;
+            Ok(())
+        }
+        hipPointer_attribute::HIP_POINTER_ATTRIBUTE_RANGE_START_ADDR => {
+            *(data as *mut _) = hipDeviceptr_t(start as _);
+            Ok(())
+        }
+        hipPointer_attribute::HIP_POINTER_ATTRIBUTE_RANGE_SIZE => {
+            *(data as *mut _) = mapping.size;
+            Ok(())
+        }
+        hipPointer_attribute::HIP_POINTER_ATTRIBUTE_DEVICE_ORDINAL => {
+            *(data as *mut _) = mapping.device;
+            Ok(())
+        }
+        hipPointer_attribute::HIP_POINTER_ATTRIBUTE_ALLOWED_HANDLE_TYPES => {
+            *(data as *mut _) = mapping.handle_types;
+            Ok(())
+        }
+        hipPointer_attribute::HIP_POINTER_ATTRIBUTE_ACCESS_FLAGS => {
+            let location = hipMemLocation {
+                type_: hipMemLocationType::hipMemLocationTypeDevice,
+                id: mapping.device,
+            };
+            let mut flags = 0;
+            let error = hipMemGetAccess(&mut flags, &location, ptr.0);
+            if error == hipError_t::hipSuccess {
+                *(data as *mut c_uint) = flags as c_uint;
+                Ok(())
+            } else {
+                Err(CUresult::CUDA_ERROR_INVALID_VALUE)
+            }
+        }
+        _ => Err(CUresult::CUDA_ERROR_NOT_SUPPORTED),
+    })
+}
+
+// Same as above, for cuMemGetAddressRange(...)
+pub(crate) fn get_address_range(ptr: hipDeviceptr_t) -> Option<(hipDeviceptr_t, usize)> {
+    let mappings = MAPPINGS.lock().ok()?;
+    let (start, mapping) = find_mapping(&mappings, ptr.0 as usize)?;
+    Some((hipDeviceptr_t(start as _), mapping.size))
+}
+
+fn find_mapping(mappings: &BTreeMap<usize, Mapping>, ptr: usize) -> Option<(usize, &Mapping)> {
+    let (start, mapping) = mappings.range(..=ptr).next_back()?;
+    if ptr < start + mapping.size {
+        Some((*start, mapping))
+    } else {
+        None
+    }
+}
+
+pub(crate) unsafe fn access_descs_from_cuda(
+    desc: *const CUmemAccessDesc,
+    count: usize,
+) -> Result<Vec<hipMemAccessDesc>, CUresult> {
+    if desc == ptr::null() {
+        return Err(CUresult::CUDA_ERROR_INVALID_VALUE);
+    }
+    Ok(std::slice::from_raw_parts(desc, count)
+        .iter()
+        .map(|desc| hipMemAccessDesc {
+            location: location_from_cuda(&desc.location),
+            flags: hipMemAccessFlags(desc.flags.0 as _),
+        })
+        .collect())
+}
+
+pub(crate) fn location_from_cuda(location: &CUmemLocation) -> hipMemLocation {
+    hipMemLocation {
+        type_: hipMemLocationType(location.type_.0 as _),
+        id: location.id,
+    }
+}
+
+// Values of all the enums are the same in CUDA and HIP
+fn prop_from_cuda(prop: &CUmemAllocationProp) -> hipMemAllocationProp {
+    let mut result = unsafe { std::mem::zeroed::<hipMemAllocationProp>() };
+    result.type_ = hipMemAllocationType(prop.type_.0 as _);
+    result.requestedHandleType = hipMemAllocationHandleType(prop.requestedHandleTypes.0 as _);
+    result.location = location_from_cuda(&prop.location);
+    result.win32HandleMetaData = prop.win32HandleMetaData;
+    result.allocFlags.compressionType = prop.allocFlags.compressionType;
+    result.allocFlags.gpuDirectRDMACapable = prop.allocFlags.gpuDirectRDMACapable;
+    result.allocFlags.usage = prop.allocFlags.usage;
+    result
+}
+
+fn prop_to_cuda(prop: &hipMemAllocationProp) -> CUmemAllocationProp {
+    let mut result = unsafe { std::mem::zeroed::<CUmemAllocationProp>() };
+    result.type_ = CUmemAllocationType(prop.type_.0 as _);
+    result.requestedHandleTypes = CUmemAllocationHandleType(prop.requestedHandleType.0 as _);
+    result.location = CUmemLocation {
+        type_: CUmemLocationType(prop.location.type_.0 as _),
+        id: prop.location.id,
+    };
+    result.win32HandleMetaData = prop.win32HandleMetaData;
+    result.allocFlags.compressionType = prop.allocFlags.compressionType;
+    result.allocFlags.gpuDirectRDMACapable = prop.allocFlags.gpuDirectRDMACapable;
+    result.allocFlags.usage = prop.allocFlags.usage;
+    result
+}
@@ -0,0 +1,112 @@
+#![cfg(not(windows))]
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{ffi::c_void, mem, ptr};
+
+mod common;
+
+cuda_driver_test!(external_memory);
+
+const VALUE: u32 = 0x01020304;
+
+// VMM allocations are exported as DMA-BUF file descriptors on ROCm, which
+// makes them a stand-in for a buffer exported by Vulkan. CUDA only accepts
+// file descriptors coming from Vulkan or OpenGL here
+unsafe fn external_memory<T: CudaDriverFns>(cuda: T) {
+    if T::is_nvidia() {
+        return;
+    }
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut prop = mem::zeroed::<CUmemAllocationProp>();
+    prop.type_ = CUmemAllocationType::CU_MEM_ALLOCATION_TYPE_PINNED;
+    prop.location.type_ = CUmemLocationType::CU_MEM_LOCATION_TYPE_DEVICE;
+    prop.location.id = 0;
+    prop.requestedHandleTypes = CUmemAllocationHandleType::CU_MEM_HANDLE_TYPE_POSIX_FILE_DESCRIPTOR;
+    let mut size = 0;
+    assert_eq!(
+        cuda.cuMemGetAllocationGranularity(
+            &mut size,
+            &prop,
+            CUmemAllocationGranularity_flags::CU_MEM_ALLOC_GRANULARITY_MINIMUM
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut handle = 0;
+    assert_eq!(
+        cuda.cuMemCreate(&mut handle, size, &prop, 0),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut base = CUdeviceptr_v2(ptr::null_mut());
+    assert_eq!(
+        cuda.cuMemAddressReserve(&mut base, size, 0, CUdeviceptr_v2(ptr::null_mut()), 0),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemMap(base, size, 0, handle, 0),
+        CUresult::CUDA_SUCCESS
+    );
+    let access = CUmemAccessDesc {
+        location: prop.location,
+        flags: CUmemAccess_flags::CU_MEM_ACCESS_FLAGS_PROT_READWRITE,
+    };
+    assert_eq!(
+        cuda.cuMemSetAccess(base, size, &access, 1),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemsetD32_v2(base, VALUE, size / 4),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut fd = -1i32;
+    assert_eq!(
+        cuda.cuMemExportToShareableHandle(
+            &mut fd as *mut i32 as *mut c_void,
+            handle,
+            CUmemAllocationHandleType::CU_MEM_HANDLE_TYPE_POSIX_FILE_DESCRIPTOR,
+            0
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert!(fd >= 0);
+    let mut desc = mem::zeroed::<CUDA_EXTERNAL_MEMORY_HANDLE_DESC>();
+    desc.type_ = CUexternalMemoryHandleType::CU_EXTERNAL_MEMORY_HANDLE_TYPE_NVSCIBUF;
+    desc.handle.fd = fd;
+    desc.size = size as u64;
+    let mut ext_mem = ptr::null_mut();
+    assert_eq!(
+        cuda.cuImportExternalMemory(&mut ext_mem, &desc),
+        CUresult::CUDA_ERROR_NOT_SUPPORTED
+    );
+    desc.type_ = CUexternalMemoryHandleType::CU_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD;
+    assert_eq!(
+        cuda.cuImportExternalMemory(&mut ext_mem, &desc),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut buffer_desc = mem::zeroed::<CUDA_EXTERNAL_MEMORY_BUFFER_DESC>();
+    buffer_desc.size = size as u64;
+    let mut mapped = CUdeviceptr_v2(ptr::null_mut());
+    assert_eq!(
+        cuda.cuExternalMemoryGetMappedBuffer(&mut mapped, ext_mem, &buffer_desc),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut result = vec![0u32; size / 4];
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(result.as_mut_ptr() as _, mapped, size),
+        CUresult::CUDA_SUCCESS
+    );
+    assert!(result.iter().all(|x| *x == VALUE));
+    assert_eq!(cuda.cuMemFree_v2(mapped), CUresult::CUDA_SUCCESS);
+    assert_eq!(
+        cuda.cuDestroyExternalMemory(ext_mem),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuMemUnmap(base, size), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemRelease(handle), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemAddressFree(base, size), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
@@ -0,0 +1,57 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{mem, ptr};
+
+mod common;
+
+cuda_driver_test!(external_semaphore);
+
+// Semaphores can only be exported by graphics APIs, this checks how import,
+// signal and wait handle their arguments
+unsafe fn external_semaphore<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut stream = ptr::null_mut();
+    assert_eq!(cuda.cuStreamCreate(&mut stream, 0), CUresult::CUDA_SUCCESS);
+    let mut desc = mem::zeroed::<CUDA_EXTERNAL_SEMAPHORE_HANDLE_DESC>();
+    let mut ext_sem = ptr::null_mut();
+    assert_eq!(
+        cuda.cuImportExternalSemaphore(&mut ext_sem, &desc),
+        CUresult::CUDA_ERROR_INVALID_VALUE
+    );
+    desc.type_ = CUexternalSemaphoreHandleType::CU_EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_FD;
+    assert_eq!(
+        cuda.cuImportExternalSemaphore(&mut ext_sem, ptr::null()),
+        CUresult::CUDA_ERROR_INVALID_VALUE
+    );
+    assert_eq!(
+        cuda.cuImportExternalSemaphore(ptr::null_mut(), &desc),
+        CUresult::CUDA_ERROR_INVALID_VALUE
+    );
+    // No semaphores to signal or wait on
+    assert_eq!(
+        cuda.cuSignalExternalSemaphoresAsync(ptr::null(), ptr::null(), 0, stream),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuWaitExternalSemaphoresAsync(ptr::null(), ptr::null(), 0, stream),
+        CUresult::CUDA_SUCCESS
+    );
+    let signal_params = mem::zeroed::<CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS>();
+    assert_eq!(
+        cuda.cuSignalExternalSemaphoresAsync(ptr::null(), &signal_params, 1, stream),
+        CUresult::CUDA_ERROR_INVALID_VALUE
+    );
+    let wait_params = mem::zeroed::<CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS>();
+    assert_eq!(
+        cuda.cuWaitExternalSemaphoresAsync(ptr::null(), &wait_params, 1, stream),
+        CUresult::CUDA_ERROR_INVALID_VALUE
+    );
+    assert_eq!(cuda.cuStreamSynchronize(stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuStreamDestroy_v2(stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
@@ -0,0 +1,252 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{
+    ffi::c_void,
+    mem, ptr,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+mod common;
+
+cuda_driver_test!(graph_exec_update);
+
+const ELEMENTS: usize = 1024;
+
+unsafe extern "system" fn increment(user_data: *mut c_void) {
+    (&*(user_data as *const AtomicU32)).fetch_add(1, Ordering::SeqCst);
+}
+
+unsafe fn memset_params(dst: CUdeviceptr, value: u32) -> CUDA_MEMSET_NODE_PARAMS {
+    CUDA_MEMSET_NODE_PARAMS {
+        dst,
+        pitch: 0,
+        value,
+        elementSize: 4,
+        width: ELEMENTS,
+        height: 1,
+    }
+}
+
+// 8 blocks of 32x2x2 threads, one per element. In Wave32OnWave64 mode
+// blockDimZ is doubled for HIP and halved back when read
+unsafe fn kernel_params(func: CUfunction, args: &mut [*mut c_void; 2]) -> CUDA_KERNEL_NODE_PARAMS {
+    CUDA_KERNEL_NODE_PARAMS {
+        func,
+        gridDimX: 8,
+        gridDimY: 1,
+        gridDimZ: 1,
+        blockDimX: 32,
+        blockDimY: 2,
+        blockDimZ: 2,
+        sharedMemBytes: 0,
+        kernelParams: args.as_mut_ptr(),
+        extra: ptr::null_mut(),
+        kern: ptr::null_mut(),
+        ctx: ptr::null_mut(),
+    }
+}
+
+unsafe fn read_back<T: CudaDriverFns>(cuda: &T, src: CUdeviceptr) -> Vec<u32> {
+    let mut result = vec![0u32; ELEMENTS];
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(result.as_mut_ptr() as _, src, ELEMENTS * 4),
+        CUresult::CUDA_SUCCESS
+    );
+    result
+}
+
+unsafe fn graph_exec_update<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut stream = ptr::null_mut();
+    assert_eq!(cuda.cuStreamCreate(&mut stream, 0), CUresult::CUDA_SUCCESS);
+    let kernel = include_str!("graph_exec_update.ptx");
+    let mut module = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleLoadData(&mut module, kernel.as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut func = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleGetFunction(&mut func, module, b"add_value\0".as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut src = CUdeviceptr_v2(ptr::null_mut());
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut src, ELEMENTS * 4),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut dst = CUdeviceptr_v2(ptr::null_mut());
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut dst, ELEMENTS * 4),
+        CUresult::CUDA_SUCCESS
+    );
+    let counter = AtomicU32::new(0);
+    let mut graph = ptr::null_mut();
+    assert_eq!(cuda.cuGraphCreate(&mut graph, 0), CUresult::CUDA_SUCCESS);
+    // memset(src) -> memcpy(src, dst) -> kernel(dst += value) -> host
+    let mut memset_node = ptr::null_mut();
+    assert_eq!(
+        cuda.cuGraphAddMemsetNode(
+            &mut memset_node,
+            graph,
+            ptr::null(),
+            0,
+            &memset_params(src, 0x11),
+            ctx
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut copy = mem::zeroed::<CUDA_MEMCPY3D>();
+    copy.srcMemoryType = CUmemorytype::CU_MEMORYTYPE_DEVICE;
+    copy.srcDevice = src;
+    copy.srcPitch = ELEMENTS * 4;
+    copy.dstMemoryType = CUmemorytype::CU_MEMORYTYPE_DEVICE;
+    copy.dstDevice = dst;
+    copy.dstPitch = ELEMENTS * 4;
+    copy.WidthInBytes = ELEMENTS * 4;
+    copy.Height = 1;
+    copy.Depth = 1;
+    let mut memcpy_node = ptr::null_mut();
+    assert_eq!(
+        cuda.cuGraphAddMemcpyNode(&mut memcpy_node, graph, &memset_node, 1, &copy, ctx),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut value = 1u32;
+    let mut args = [
+        &mut dst as *mut _ as *mut c_void,
+        &mut value as *mut _ as *mut c_void,
+    ];
+    let mut kernel_node = ptr::null_mut();
+    assert_eq!(
+        cuda.cuGraphAddKernelNode_v2(
+            &mut kernel_node,
+            graph,
+            &memcpy_node,
+            1,
+            &kernel_params(func, &mut args)
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    let host_params = CUDA_HOST_NODE_PARAMS {
+        fn_: Some(increment),
+        userData: &counter as *const _ as *mut _,
+    };
+    let mut host_node = ptr::null_mut();
+    assert_eq!(
+        cuda.cuGraphAddHostNode(&mut host_node, graph, &kernel_node, 1, &host_params),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut node_type = CUgraphNodeType::CU_GRAPH_NODE_TYPE_EMPTY;
+    assert_eq!(
+        cuda.cuGraphNodeGetType(memset_node, &mut node_type),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(node_type.0, CUgraphNodeType::CU_GRAPH_NODE_TYPE_MEMSET.0);
+    assert_eq!(
+        cuda.cuGraphNodeGetType(memcpy_node, &mut node_type),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(node_type.0, CUgraphNodeType::CU_GRAPH_NODE_TYPE_MEMCPY.0);
+    assert_eq!(
+        cuda.cuGraphNodeGetType(host_node, &mut node_type),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(node_type.0, CUgraphNodeType::CU_GRAPH_NODE_TYPE_HOST.0);
+    let mut memset_out = mem::zeroed::<CUDA_MEMSET_NODE_PARAMS>();
+    assert_eq!(
+        cuda.cuGraphMemsetNodeGetParams(memset_node, &mut memset_out),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(memset_out.dst.0, src.0);
+    assert_eq!(memset_out.value, 0x11);
+    assert_eq!(memset_out.width, ELEMENTS);
+    let mut kernel_out = mem::zeroed::<CUDA_KERNEL_NODE_PARAMS>();
+    assert_eq!(
+        cuda.cuGraphKernelNodeGetParams_v2(kernel_node, &mut kernel_out),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(kernel_out.func, func);
+    assert_eq!(
+        (
+            kernel_out.blockDimX,
+            kernel_out.blockDimY,
+            kernel_out.blockDimZ
+        ),
+        (32, 2, 2)
+    );
+    assert_eq!(kernel_out.extra, ptr::null_mut());
+    let mut exec = ptr::null_mut();
+    assert_eq!(
+        cuda.cuGraphInstantiateWithFlags(&mut exec, graph, 0),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuGraphLaunch(exec, stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuStreamSynchronize(stream), CUresult::CUDA_SUCCESS);
+    assert!(read_back(&cuda, dst).iter().all(|x| *x == 0x12));
+    assert_eq!(counter.load(Ordering::SeqCst), 1);
+    // Update a single node of the executable graph
+    assert_eq!(
+        cuda.cuGraphExecMemsetNodeSetParams(exec, memset_node, &memset_params(src, 0x22), ctx),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuGraphLaunch(exec, stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuStreamSynchronize(stream), CUresult::CUDA_SUCCESS);
+    assert!(read_back(&cuda, dst).iter().all(|x| *x == 0x23));
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+    let mut new_value = 0x10u32;
+    let mut new_args = [
+        &mut dst as *mut _ as *mut c_void,
+        &mut new_value as *mut _ as *mut c_void,
+    ];
+    assert_eq!(
+        cuda.cuGraphExecKernelNodeSetParams_v2(
+            exec,
+            kernel_node,
+            &kernel_params(func, &mut new_args)
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuGraphLaunch(exec, stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuStreamSynchronize(stream), CUresult::CUDA_SUCCESS);
+    assert!(read_back(&cuda, dst).iter().all(|x| *x == 0x32));
+    assert_eq!(counter.load(Ordering::SeqCst), 3);
+    // Update the whole executable graph from a modified clone
+    let mut clone = ptr::null_mut();
+    assert_eq!(cuda.cuGraphClone(&mut clone, graph), CUresult::CUDA_SUCCESS);
+    let mut cloned_memset_node = ptr::null_mut();
+    assert_eq!(
+        cuda.cuGraphNodeFindInClone(&mut cloned_memset_node, memset_node, clone),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuGraphMemsetNodeSetParams(cloned_memset_node, &memset_params(src, 0x33)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut result_info = mem::zeroed::<CUgraphExecUpdateResultInfo>();
+    assert_eq!(
+        cuda.cuGraphExecUpdate_v2(exec, clone, &mut result_info),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        result_info.result.0,
+        CUgraphExecUpdateResult::CU_GRAPH_EXEC_UPDATE_SUCCESS.0
+    );
+    assert_eq!(cuda.cuGraphLaunch(exec, stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuStreamSynchronize(stream), CUresult::CUDA_SUCCESS);
+    // The clone still has the original kernel arguments
+    assert!(read_back(&cuda, dst).iter().all(|x| *x == 0x34));
+    assert_eq!(counter.load(Ordering::SeqCst), 4);
+    assert_eq!(cuda.cuGraphExecDestroy(exec), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuGraphDestroy(clone), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuGraphDestroy(graph), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemFree_v2(src), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemFree_v2(dst), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuModuleUnload(module), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuStreamDestroy_v2(stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
@@ -0,0 +1,125 @@
+#![cfg(not(windows))]
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{mem, panic, ptr};
+
+mod common;
+
+cuda_driver_test!(ipc_mem_handle);
+
+const ELEMENTS: usize = 1024;
+const VALUE: u32 = 0x0a0b0c0d;
+// CU_EVENT_DISABLE_TIMING | CU_EVENT_INTERPROCESS
+const IPC_EVENT_FLAGS: u32 = 0x2 | 0x4;
+// CU_IPC_MEM_LAZY_ENABLE_PEER_ACCESS
+const IPC_MEM_FLAGS: u32 = 0x1;
+
+unsafe fn write_all<T>(fd: i32, value: &T) {
+    let size = mem::size_of::<T>();
+    assert_eq!(
+        libc::write(fd, value as *const T as *const _, size),
+        size as isize
+    );
+}
+
+unsafe fn read_exact<T>(fd: i32) -> T {
+    let mut value = mem::zeroed::<T>();
+    let size = mem::size_of::<T>();
+    assert_eq!(
+        libc::read(fd, &mut value as *mut T as *mut _, size),
+        size as isize
+    );
+    value
+}
+
+// Child process must not touch the driver before fork(...), so both sides
+// initialize only after the process is split
+unsafe fn ipc_mem_handle<T: CudaDriverFns>(cuda: T) {
+    let mut pipe = [0i32; 2];
+    assert_eq!(libc::pipe(pipe.as_mut_ptr()), 0);
+    let [read_fd, write_fd] = pipe;
+    let pid = libc::fork();
+    assert!(pid >= 0);
+    if pid == 0 {
+        libc::close(write_fd);
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| ipc_child(&cuda, read_fd)));
+        libc::_exit(if result.is_ok() { 0 } else { 1 });
+    }
+    libc::close(read_fd);
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut dptr = CUdeviceptr_v2(ptr::null_mut());
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut dptr, ELEMENTS * 4),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut event = ptr::null_mut();
+    assert_eq!(
+        cuda.cuEventCreate(&mut event, IPC_EVENT_FLAGS),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemsetD32_v2(dptr, VALUE, ELEMENTS),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuEventRecord(event, ptr::null_mut()),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut mem_handle = mem::zeroed::<CUipcMemHandle>();
+    assert_eq!(
+        cuda.cuIpcGetMemHandle(&mut mem_handle, dptr),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut event_handle = mem::zeroed::<CUipcEventHandle>();
+    assert_eq!(
+        cuda.cuIpcGetEventHandle(&mut event_handle, event),
+        CUresult::CUDA_SUCCESS
+    );
+    write_all(write_fd, &mem_handle);
+    write_all(write_fd, &event_handle);
+    libc::close(write_fd);
+    let mut status = 0;
+    assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+    assert!(libc::WIFEXITED(status));
+    assert_eq!(libc::WEXITSTATUS(status), 0);
+    assert_eq!(cuda.cuEventDestroy_v2(event), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemFree_v2(dptr), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
+
+unsafe fn ipc_child<T: CudaDriverFns>(cuda: &T, read_fd: i32) {
+    let mem_handle = read_exact::<CUipcMemHandle>(read_fd);
+    let event_handle = read_exact::<CUipcEventHandle>(read_fd);
+    libc::close(read_fd);
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut event = ptr::null_mut();
+    assert_eq!(
+        cuda.cuIpcOpenEventHandle(&mut event, event_handle),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuEventSynchronize(event), CUresult::CUDA_SUCCESS);
+    let mut dptr = CUdeviceptr_v2(ptr::null_mut());
+    assert_eq!(
+        cuda.cuIpcOpenMemHandle_v2(&mut dptr, mem_handle, IPC_MEM_FLAGS),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut result = vec![0u32; ELEMENTS];
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(result.as_mut_ptr() as _, dptr, ELEMENTS * 4),
+        CUresult::CUDA_SUCCESS
+    );
+    assert!(result.iter().all(|x| *x == VALUE));
+    assert_eq!(cuda.cuIpcCloseMemHandle(dptr), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuEventDestroy_v2(event), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
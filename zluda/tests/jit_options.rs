@@ -0,0 +1,129 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{ffi::c_void, mem, ptr};
+
+mod common;
+
+cuda_driver_test!(jit_threads_per_block);
+cuda_driver_test!(jit_log_and_wall_time);
+
+const THREADS: u32 = 64;
+
+unsafe fn jit_threads_per_block<T: CudaDriverFns>(cuda: T) {
+    let kernel = concat!(include_str!("jit_options.ptx"), "\0");
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut options = [CUjit_option::CU_JIT_THREADS_PER_BLOCK];
+    let mut values = [THREADS as usize as *mut c_void];
+    let mut module = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleLoadDataEx(
+            &mut module,
+            kernel.as_ptr() as _,
+            options.len() as u32,
+            options.as_mut_ptr(),
+            values.as_mut_ptr()
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    // Written back with the number of threads the kernels were compiled for
+    assert!(values[0] as usize >= 1 && values[0] as usize <= THREADS as usize);
+    let mut function = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleGetFunction(&mut function, module, b"add_one\0".as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut output = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut output, THREADS as usize * mem::size_of::<u32>()),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut args = [&mut output as *mut _ as *mut c_void];
+    // The whole requested block size must still be launchable, also when
+    // every CUDA thread takes two hardware lanes
+    assert_eq!(
+        cuda.cuLaunchKernel(
+            function,
+            1,
+            1,
+            1,
+            THREADS,
+            1,
+            1,
+            0,
+            ptr::null_mut(),
+            args.as_mut_ptr(),
+            ptr::null_mut()
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut result = vec![0u32; THREADS as usize];
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(
+            result.as_mut_ptr() as _,
+            output,
+            THREADS as usize * mem::size_of::<u32>()
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(result, (1..=THREADS).collect::<Vec<_>>());
+    assert_eq!(cuda.cuMemFree_v2(output), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuModuleUnload(module), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
+
+unsafe fn jit_log_and_wall_time<T: CudaDriverFns>(cuda: T) {
+    let kernel = concat!(include_str!("jit_options.ptx"), "\0");
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut info_log = vec![0xffu8; 1024];
+    let mut error_log = vec![0xffu8; 1024];
+    let mut options = [
+        CUjit_option::CU_JIT_INFO_LOG_BUFFER,
+        CUjit_option::CU_JIT_INFO_LOG_BUFFER_SIZE_BYTES,
+        CUjit_option::CU_JIT_ERROR_LOG_BUFFER,
+        CUjit_option::CU_JIT_ERROR_LOG_BUFFER_SIZE_BYTES,
+        CUjit_option::CU_JIT_WALL_TIME,
+        CUjit_option::CU_JIT_OPTIMIZATION_LEVEL,
+    ];
+    let mut values = [
+        info_log.as_mut_ptr() as *mut c_void,
+        info_log.len() as *mut c_void,
+        error_log.as_mut_ptr() as *mut c_void,
+        error_log.len() as *mut c_void,
+        ptr::null_mut(),
+        1 as *mut c_void,
+    ];
+    let mut module = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleLoadDataEx(
+            &mut module,
+            kernel.as_ptr() as _,
+            options.len() as u32,
+            options.as_mut_ptr(),
+            values.as_mut_ptr()
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    // Log sizes are overwritten with the number of bytes used, including the
+    // NUL terminator
+    for (log, size) in [(&info_log, values[1]), (&error_log, values[3])] {
+        let size = size as usize;
+        assert!(size <= log.len());
+        if size > 0 {
+            assert_eq!(log[size - 1], 0);
+        }
+    }
+    let wall_time = *(&values[4] as *const *mut c_void as *const f32);
+    assert!(wall_time >= 0.0);
+    assert_eq!(cuda.cuModuleUnload(module), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
@@ -0,0 +1,125 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{ffi::c_void, mem, ptr};
+
+mod common;
+
+cuda_driver_test!(kernel_grid_sync);
+cuda_driver_test!(kernel_envreg);
+
+const BLOCKS: u32 = 16;
+
+unsafe fn kernel_grid_sync<T: CudaDriverFns>(cuda: T) {
+    let kernel = concat!(include_str!("kernel_grid_sync.ptx"), "\0");
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut module = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleLoadData(&mut module, kernel.as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut function = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleGetFunction(&mut function, module, b"grid_sync_kernel\0".as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut stream = ptr::null_mut();
+    assert_eq!(cuda.cuStreamCreate(&mut stream, 0), CUresult::CUDA_SUCCESS);
+    let size = BLOCKS as usize * 3 * mem::size_of::<u32>();
+    let mut output = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut output, size),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut args = [&mut output as *mut _ as *mut c_void];
+    // Every launch gets a fresh workspace, so back-to-back launches must not
+    // see each other's barrier state
+    for _ in 0..2 {
+        assert_eq!(
+            cuda.cuMemsetD32Async(output, 0, BLOCKS as usize * 3, stream),
+            CUresult::CUDA_SUCCESS
+        );
+        assert_eq!(
+            cuda.cuLaunchCooperativeKernel(
+                function,
+                BLOCKS,
+                1,
+                1,
+                64,
+                1,
+                1,
+                0,
+                stream,
+                args.as_mut_ptr()
+            ),
+            CUresult::CUDA_SUCCESS
+        );
+        let mut result = vec![0u32; BLOCKS as usize * 3];
+        assert_eq!(
+            cuda.cuMemcpyDtoHAsync_v2(result.as_mut_ptr() as _, output, size, stream),
+            CUresult::CUDA_SUCCESS
+        );
+        assert_eq!(cuda.cuStreamSynchronize(stream), CUresult::CUDA_SUCCESS);
+        let expected = (0..BLOCKS)
+            .map(|i| i + 1)
+            .chain((0..BLOCKS).map(|i| (i + 1) % BLOCKS + 1))
+            .chain((0..BLOCKS).map(|i| (i + 3) % BLOCKS + 1))
+            .collect::<Vec<_>>();
+        assert_eq!(result, expected);
+    }
+    assert_eq!(cuda.cuMemFree_v2(output), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuStreamDestroy_v2(stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuModuleUnload(module), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
+
+unsafe fn kernel_envreg<T: CudaDriverFns>(cuda: T) {
+    let kernel = concat!(include_str!("kernel_grid_sync.ptx"), "\0");
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut module = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleLoadData(&mut module, kernel.as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut function = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleGetFunction(&mut function, module, b"envreg\0".as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut output = mem::zeroed();
+    assert_eq!(cuda.cuMemAlloc_v2(&mut output, 8), CUresult::CUDA_SUCCESS);
+    let mut args = [&mut output as *mut _ as *mut c_void];
+    assert_eq!(
+        cuda.cuLaunchCooperativeKernel(
+            function,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            0,
+            ptr::null_mut(),
+            args.as_mut_ptr()
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut workspace = 0u64;
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(&mut workspace as *mut _ as _, output, 8),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_ne!(workspace, 0);
+    assert_eq!(cuda.cuMemFree_v2(output), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuModuleUnload(module), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
@@ -0,0 +1,161 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{ffi::c_void, mem, ptr};
+
+mod common;
+
+cuda_driver_test!(kernel_launch_ex);
+
+const CU_LAUNCH_PARAM_BUFFER_POINTER: *mut c_void = 1 as *mut _;
+const CU_LAUNCH_PARAM_BUFFER_SIZE: *mut c_void = 2 as *mut _;
+const CU_LAUNCH_PARAM_END: *mut c_void = 0 as *mut _;
+
+unsafe fn attribute(
+    id: CUlaunchAttributeID,
+    set_value: impl FnOnce(&mut CUlaunchAttributeValue),
+) -> CUlaunchAttribute {
+    let mut attribute = mem::zeroed::<CUlaunchAttribute>();
+    attribute.id = id;
+    set_value(&mut attribute.value);
+    attribute
+}
+
+unsafe fn launch_config(attrs: &mut [CUlaunchAttribute]) -> CUlaunchConfig {
+    CUlaunchConfig {
+        gridDimX: 1,
+        gridDimY: 1,
+        gridDimZ: 1,
+        blockDimX: 1,
+        blockDimY: 1,
+        blockDimZ: 1,
+        sharedMemBytes: 0,
+        hStream: ptr::null_mut(),
+        attrs: attrs.as_mut_ptr(),
+        numAttrs: attrs.len() as u32,
+    }
+}
+
+unsafe fn kernel_launch_ex<T: CudaDriverFns>(cuda: T) {
+    let kernel = include_str!("kernel_extra.ptx");
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut module = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleLoadData(&mut module, kernel.as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut buffer_input = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut buffer_input, 8),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut buffer_output = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut buffer_output, 8),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut kernel = mem::zeroed();
+    assert_eq!(
+        cuda.cuModuleGetFunction(&mut kernel, module, b"add\0".as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let input = 41u64;
+    assert_eq!(
+        cuda.cuMemcpyHtoD_v2(buffer_input, &input as *const _ as _, 8),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut attrs = [
+        attribute(CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_PRIORITY, |value| {
+            value.priority = 0
+        }),
+        attribute(
+            CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_PROGRAMMATIC_STREAM_SERIALIZATION,
+            |value| value.programmaticStreamSerializationAllowed = 1,
+        ),
+        attribute(
+            CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_CLUSTER_DIMENSION,
+            |value| {
+                value.clusterDim.x = 1;
+                value.clusterDim.y = 1;
+                value.clusterDim.z = 1;
+            },
+        ),
+    ];
+    let config = launch_config(&mut attrs);
+    let mut args = [&mut buffer_input, &mut buffer_output];
+    assert_eq!(
+        cuda.cuLaunchKernelEx(&config, kernel, args.as_mut_ptr() as _, ptr::null_mut()),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut output = 0u64;
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(&mut output as *mut _ as _, buffer_output, 8),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(output, 42);
+    // Same launch, but with arguments passed through `extra`
+    let mut args = [buffer_output, buffer_input];
+    let mut size = mem::size_of_val(&args);
+    let mut extra = [
+        CU_LAUNCH_PARAM_BUFFER_POINTER,
+        args.as_mut_ptr() as *mut _ as _,
+        CU_LAUNCH_PARAM_BUFFER_SIZE,
+        &mut size as *mut _ as _,
+        CU_LAUNCH_PARAM_END,
+    ];
+    assert_eq!(
+        cuda.cuLaunchKernelEx(&config, kernel, ptr::null_mut(), extra.as_mut_ptr()),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(&mut output as *mut _ as _, buffer_input, 8),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(output, 43);
+    // Priority other than the priority of the stream
+    let mut least_priority = 0;
+    let mut greatest_priority = 0;
+    assert_eq!(
+        cuda.cuCtxGetStreamPriorityRange(&mut least_priority, &mut greatest_priority),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut attrs = [attribute(
+        CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_PRIORITY,
+        |value| value.priority = greatest_priority,
+    )];
+    let config = launch_config(&mut attrs);
+    let mut args = [&mut buffer_input, &mut buffer_output];
+    assert_eq!(
+        cuda.cuLaunchKernelEx(&config, kernel, args.as_mut_ptr() as _, ptr::null_mut()),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(&mut output as *mut _ as _, buffer_output, 8),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(output, 44);
+    if !T::is_nvidia() {
+        let mut attrs = [attribute(
+            CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_CLUSTER_DIMENSION,
+            |value| {
+                value.clusterDim.x = 2;
+                value.clusterDim.y = 1;
+                value.clusterDim.z = 1;
+            },
+        )];
+        let mut config = launch_config(&mut attrs);
+        config.gridDimX = 2;
+        assert_eq!(
+            cuda.cuLaunchKernelEx(&config, kernel, ptr::null_mut(), extra.as_mut_ptr()),
+            CUresult::CUDA_ERROR_NOT_SUPPORTED
+        );
+    }
+    assert_eq!(
+        cuda.cuStreamSynchronize(ptr::null_mut()),
+        CUresult::CUDA_SUCCESS
+    );
+}
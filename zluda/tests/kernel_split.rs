@@ -0,0 +1,117 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{ffi::c_void, mem, ptr};
+
+mod common;
+
+cuda_driver_test!(kernel_split);
+
+const THREADS: u32 = 32;
+
+// Kernels of this module are compiled separately and linked together. Both
+// kernels must work after linking, including the one that uses a global from
+// zluda_ptx_impl (grid workspace behind %envreg)
+unsafe fn kernel_split<T: CudaDriverFns>(cuda: T) {
+    let kernel = concat!(include_str!("kernel_split.ptx"), "\0");
+    std::env::set_var("ZLUDA_COMPILER_THREADS", "2");
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    // Non-default optimization level skips the kernel cache and the info log
+    // turns off lazy loading, so the module is built right here
+    let mut info_log = vec![0u8; 4096];
+    let mut options = [
+        CUjit_option::CU_JIT_INFO_LOG_BUFFER,
+        CUjit_option::CU_JIT_INFO_LOG_BUFFER_SIZE_BYTES,
+        CUjit_option::CU_JIT_OPTIMIZATION_LEVEL,
+    ];
+    let mut values = [
+        info_log.as_mut_ptr() as *mut c_void,
+        info_log.len() as *mut c_void,
+        2 as *mut c_void,
+    ];
+    let mut module = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleLoadDataEx(
+            &mut module,
+            kernel.as_ptr() as _,
+            options.len() as u32,
+            options.as_mut_ptr(),
+            values.as_mut_ptr()
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    let info_log_size = values[1] as usize;
+    let info_log = String::from_utf8_lossy(&info_log[..info_log_size]);
+    assert!(
+        !info_log.contains("Building kernels separately failed"),
+        "{}",
+        info_log
+    );
+    let mut add_one = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleGetFunction(&mut add_one, module, b"add_one\0".as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut envreg = ptr::null_mut();
+    assert_eq!(
+        cuda.cuModuleGetFunction(&mut envreg, module, b"envreg\0".as_ptr() as _),
+        CUresult::CUDA_SUCCESS
+    );
+    let size = THREADS as usize * mem::size_of::<u32>();
+    let mut output = mem::zeroed();
+    assert_eq!(
+        cuda.cuMemAlloc_v2(&mut output, size),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut args = [&mut output as *mut _ as *mut c_void];
+    assert_eq!(
+        cuda.cuLaunchKernel(
+            add_one,
+            1,
+            1,
+            1,
+            THREADS,
+            1,
+            1,
+            0,
+            ptr::null_mut(),
+            args.as_mut_ptr(),
+            ptr::null_mut()
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut result = vec![0u32; THREADS as usize];
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(result.as_mut_ptr() as _, output, size),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(result, (1..=THREADS).collect::<Vec<_>>());
+    assert_eq!(
+        cuda.cuLaunchCooperativeKernel(
+            envreg,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            0,
+            ptr::null_mut(),
+            args.as_mut_ptr()
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut workspace = 0u64;
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(&mut workspace as *mut _ as _, output, 8),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_ne!(workspace, 0);
+    assert_eq!(cuda.cuMemFree_v2(output), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuModuleUnload(module), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
@@ -0,0 +1,103 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{ffi::CStr, ptr};
+
+mod common;
+
+cuda_driver_test!(library_kernels);
+
+static KERNEL: &str = concat!(include_str!("library_kernels.ptx"), "\0");
+
+unsafe fn library_kernels<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ptr::null_mut(), 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut library = ptr::null_mut();
+    assert_eq!(
+        cuda.cuLibraryLoadData(
+            &mut library,
+            KERNEL.as_ptr() as _,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            0,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            0
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut kernel = ptr::null_mut();
+    assert_eq!(
+        cuda.cuLibraryGetKernel(&mut kernel, library, b"missing\0".as_ptr().cast()),
+        CUresult::CUDA_ERROR_NOT_FOUND
+    );
+    assert_eq!(
+        cuda.cuLibraryGetKernel(&mut kernel, library, b"second\0".as_ptr().cast()),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut func = ptr::null_mut();
+    assert_eq!(
+        cuda.cuKernelGetFunction(&mut func, kernel),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut name = ptr::null();
+    assert_eq!(cuda.cuFuncGetName(&mut name, func), CUresult::CUDA_SUCCESS);
+    assert_eq!(CStr::from_ptr(name).to_bytes(), b"second");
+    let mut ptx_version = 0;
+    assert_eq!(
+        cuda.cuKernelGetAttribute(
+            &mut ptx_version,
+            CUfunction_attribute::CU_FUNC_ATTRIBUTE_PTX_VERSION,
+            kernel,
+            CUdevice_v1(0)
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(ptx_version, 35);
+    assert_eq!(
+        cuda.cuKernelGetAttribute(
+            &mut ptx_version,
+            CUfunction_attribute::CU_FUNC_ATTRIBUTE_PTX_VERSION,
+            kernel,
+            CUdevice_v1(1000)
+        ),
+        CUresult::CUDA_ERROR_INVALID_DEVICE
+    );
+    if !T::is_nvidia() {
+        let mut dptr = CUdeviceptr_v2(ptr::null_mut());
+        let mut bytes = 0;
+        assert_eq!(
+            cuda.cuLibraryGetManaged(&mut dptr, &mut bytes, library, b"first\0".as_ptr().cast()),
+            CUresult::CUDA_ERROR_NOT_SUPPORTED
+        );
+    }
+    let mut module = ptr::null_mut();
+    assert_eq!(
+        cuda.cuLibraryGetModule(&mut module, library),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut count = 0;
+    assert_eq!(
+        cuda.cuModuleGetFunctionCount(&mut count, module),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(count, 2);
+    let mut functions = [ptr::null_mut(); 2];
+    assert_eq!(
+        cuda.cuModuleEnumerateFunctions(functions.as_mut_ptr(), count, module),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut names = functions
+        .iter()
+        .map(|func| {
+            let mut name = ptr::null();
+            assert_eq!(cuda.cuFuncGetName(&mut name, *func), CUresult::CUDA_SUCCESS);
+            CStr::from_ptr(name).to_bytes().to_vec()
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, [b"first".to_vec(), b"second".to_vec()]);
+    assert_eq!(cuda.cuLibraryUnload(library), CUresult::CUDA_SUCCESS);
+}
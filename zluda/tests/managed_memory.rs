@@ -0,0 +1,135 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{ffi::c_void, mem, ptr};
+
+mod common;
+
+cuda_driver_test!(managed_memory);
+
+const CU_MEM_ATTACH_GLOBAL: u32 = 1;
+const CU_MEM_ATTACH_SINGLE: u32 = 4;
+const SIZE: usize = 4096;
+
+unsafe fn managed_memory<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut managed_memory = 0;
+    assert_eq!(
+        cuda.cuDeviceGetAttribute(
+            &mut managed_memory,
+            CUdevice_attribute::CU_DEVICE_ATTRIBUTE_MANAGED_MEMORY,
+            CUdevice_v1(0)
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    if managed_memory == 0 {
+        assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+        return;
+    }
+    let mut memory = CUdeviceptr_v2(ptr::null_mut());
+    assert_eq!(
+        cuda.cuMemAllocManaged(&mut memory, SIZE, CU_MEM_ATTACH_GLOBAL),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut is_managed = false;
+    assert_eq!(
+        cuda.cuPointerGetAttribute(
+            &mut is_managed as *mut bool as _,
+            CUpointer_attribute::CU_POINTER_ATTRIBUTE_IS_MANAGED,
+            memory
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert!(is_managed);
+    let mut memory_type = CUmemorytype(0);
+    assert_eq!(
+        cuda.cuPointerGetAttribute(
+            &mut memory_type as *mut _ as _,
+            CUpointer_attribute::CU_POINTER_ATTRIBUTE_MEMORY_TYPE,
+            memory
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(memory_type.0, CUmemorytype::CU_MEMORYTYPE_DEVICE.0);
+    assert_eq!(
+        cuda.cuMemAdvise(
+            memory,
+            SIZE,
+            CUmem_advise::CU_MEM_ADVISE_SET_PREFERRED_LOCATION,
+            CUdevice_v1(0)
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut preferred_location = -2i32;
+    assert_eq!(
+        cuda.cuMemRangeGetAttribute(
+            &mut preferred_location as *mut i32 as _,
+            mem::size_of::<i32>(),
+            CUmem_range_attribute::CU_MEM_RANGE_ATTRIBUTE_PREFERRED_LOCATION,
+            memory,
+            SIZE
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(preferred_location, 0);
+    assert_eq!(
+        cuda.cuMemAdvise(
+            memory,
+            SIZE,
+            CUmem_advise::CU_MEM_ADVISE_SET_READ_MOSTLY,
+            CUdevice_v1(0)
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut read_mostly = 0i32;
+    let mut location_type = CUmemLocationType(0);
+    let mut location_id = -2i32;
+    let mut data = [
+        &mut read_mostly as *mut i32 as *mut c_void,
+        &mut location_type as *mut _ as _,
+        &mut location_id as *mut i32 as _,
+    ];
+    let mut data_sizes = [mem::size_of::<i32>(); 3];
+    let mut attributes = [
+        CUmem_range_attribute::CU_MEM_RANGE_ATTRIBUTE_READ_MOSTLY,
+        CUmem_range_attribute::CU_MEM_RANGE_ATTRIBUTE_PREFERRED_LOCATION_TYPE,
+        CUmem_range_attribute::CU_MEM_RANGE_ATTRIBUTE_PREFERRED_LOCATION_ID,
+    ];
+    assert_eq!(
+        cuda.cuMemRangeGetAttributes(
+            data.as_mut_ptr(),
+            data_sizes.as_mut_ptr(),
+            attributes.as_mut_ptr(),
+            attributes.len(),
+            memory,
+            SIZE
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(read_mostly, 1);
+    assert_eq!(
+        location_type.0,
+        CUmemLocationType::CU_MEM_LOCATION_TYPE_DEVICE.0
+    );
+    assert_eq!(location_id, 0);
+    let mut stream = ptr::null_mut();
+    assert_eq!(cuda.cuStreamCreate(&mut stream, 0), CUresult::CUDA_SUCCESS);
+    assert_eq!(
+        cuda.cuStreamAttachMemAsync(stream, memory, 0, CU_MEM_ATTACH_SINGLE),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemsetD32Async(memory, 0x01020304, SIZE / 4, stream),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuStreamSynchronize(stream), CUresult::CUDA_SUCCESS);
+    let host = std::slice::from_raw_parts(memory.0 as *const u32, SIZE / 4);
+    assert!(host.iter().all(|x| *x == 0x01020304));
+    assert_eq!(cuda.cuStreamDestroy_v2(stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemFree_v2(memory), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
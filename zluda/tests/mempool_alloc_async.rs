@@ -0,0 +1,145 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{ffi::c_void, mem, ptr};
+
+mod common;
+
+cuda_driver_test!(mempool_alloc_async);
+
+unsafe fn mempool_alloc_async<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut pools_supported = 0;
+    assert_eq!(
+        cuda.cuDeviceGetAttribute(
+            &mut pools_supported,
+            CUdevice_attribute::CU_DEVICE_ATTRIBUTE_MEMORY_POOLS_SUPPORTED,
+            CUdevice_v1(0)
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(pools_supported, 1);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut stream = ptr::null_mut();
+    assert_eq!(cuda.cuStreamCreate(&mut stream, 0), CUresult::CUDA_SUCCESS);
+    let mut props = mem::zeroed::<CUmemPoolProps>();
+    props.allocType = CUmemAllocationType::CU_MEM_ALLOCATION_TYPE_PINNED;
+    props.location.type_ = CUmemLocationType::CU_MEM_LOCATION_TYPE_DEVICE;
+    props.location.id = 0;
+    props.maxSize = 1 << 20;
+    let mut pool = ptr::null_mut();
+    assert_eq!(
+        cuda.cuMemPoolCreate(&mut pool, &props),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut dptr = CUdeviceptr_v2(ptr::null_mut());
+    assert_eq!(
+        cuda.cuMemAllocFromPoolAsync(&mut dptr, 4096, pool, stream),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemsetD32Async(dptr, 0x01020304, 1024, stream),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut result = vec![0u32; 1024];
+    assert_eq!(
+        cuda.cuMemcpyDtoHAsync_v2(result.as_mut_ptr() as _, dptr, 4096, stream),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut used = 0u64;
+    assert_eq!(
+        cuda.cuMemPoolGetAttribute(
+            pool,
+            CUmemPool_attribute::CU_MEMPOOL_ATTR_USED_MEM_CURRENT,
+            &mut used as *mut _ as *mut c_void
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert!(used >= 4096);
+    // Larger than the maximum size of the pool
+    let mut too_large = CUdeviceptr_v2(ptr::null_mut());
+    assert_eq!(
+        cuda.cuMemAllocFromPoolAsync(&mut too_large, 2 << 20, pool, stream),
+        CUresult::CUDA_ERROR_OUT_OF_MEMORY
+    );
+    assert_eq!(cuda.cuMemFreeAsync(dptr, stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuStreamSynchronize(stream), CUresult::CUDA_SUCCESS);
+    assert!(result.iter().all(|x| *x == 0x01020304));
+    assert_eq!(
+        cuda.cuMemPoolGetAttribute(
+            pool,
+            CUmemPool_attribute::CU_MEMPOOL_ATTR_USED_MEM_CURRENT,
+            &mut used as *mut _ as *mut c_void
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(used, 0);
+    assert_eq!(cuda.cuMemPoolTrimTo(pool, 0), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemPoolDestroy(pool), CUresult::CUDA_SUCCESS);
+    // Default pool of the device
+    assert_eq!(
+        cuda.cuMemAllocAsync(&mut dptr, 4096, stream),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuMemFreeAsync(dptr, stream), CUresult::CUDA_SUCCESS);
+    let mut default_pool = ptr::null_mut();
+    assert_eq!(
+        cuda.cuDeviceGetDefaultMemPool(&mut default_pool, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemPoolDestroy(default_pool),
+        CUresult::CUDA_ERROR_INVALID_VALUE
+    );
+    assert_eq!(cuda.cuStreamSynchronize(stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuStreamDestroy_v2(stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
+
+cuda_driver_test!(mempool_destroy_before_free);
+
+// Pool destroyed with an outstanding allocation stays usable until the
+// allocation is freed, here with the synchronous cuMemFree(...)
+unsafe fn mempool_destroy_before_free<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut stream = ptr::null_mut();
+    assert_eq!(cuda.cuStreamCreate(&mut stream, 0), CUresult::CUDA_SUCCESS);
+    let mut props = mem::zeroed::<CUmemPoolProps>();
+    props.allocType = CUmemAllocationType::CU_MEM_ALLOCATION_TYPE_PINNED;
+    props.location.type_ = CUmemLocationType::CU_MEM_LOCATION_TYPE_DEVICE;
+    props.location.id = 0;
+    props.maxSize = 1 << 20;
+    let mut pool = ptr::null_mut();
+    assert_eq!(
+        cuda.cuMemPoolCreate(&mut pool, &props),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut dptr = CUdeviceptr_v2(ptr::null_mut());
+    assert_eq!(
+        cuda.cuMemAllocFromPoolAsync(&mut dptr, 4096, pool, stream),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuMemPoolDestroy(pool), CUresult::CUDA_SUCCESS);
+    assert_eq!(
+        cuda.cuMemsetD32Async(dptr, 0x01020304, 1024, stream),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut result = vec![0u32; 1024];
+    assert_eq!(
+        cuda.cuMemcpyDtoHAsync_v2(result.as_mut_ptr() as _, dptr, 4096, stream),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuStreamSynchronize(stream), CUresult::CUDA_SUCCESS);
+    assert!(result.iter().all(|x| *x == 0x01020304));
+    assert_eq!(cuda.cuMemFree_v2(dptr), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuStreamDestroy_v2(stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
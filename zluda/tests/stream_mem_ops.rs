@@ -0,0 +1,105 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{mem, ptr};
+
+mod common;
+
+cuda_driver_test!(stream_mem_ops);
+
+const CU_STREAM_WAIT_VALUE_GEQ: u32 = 0;
+const CU_STREAM_WAIT_VALUE_EQ: u32 = 1;
+const CU_STREAM_WAIT_VALUE_AND: u32 = 2;
+const CU_STREAM_WAIT_VALUE_NOR: u32 = 3;
+const CU_STREAM_WAIT_VALUE_FLUSH: u32 = 1 << 30;
+
+unsafe fn stream_mem_ops<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut stream = ptr::null_mut();
+    assert_eq!(cuda.cuStreamCreate(&mut stream, 0), CUresult::CUDA_SUCCESS);
+    let mut memory = CUdeviceptr_v2(ptr::null_mut());
+    assert_eq!(cuda.cuMemAlloc_v2(&mut memory, 16), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemsetD8_v2(memory, 0, 16), CUresult::CUDA_SUCCESS);
+    let flag = memory;
+    let value64 = CUdeviceptr_v2(memory.0.cast::<u8>().add(8).cast());
+    assert_eq!(
+        cuda.cuStreamWriteValue32_v2(stream, flag, 5, 0),
+        CUresult::CUDA_SUCCESS
+    );
+    for mode in [
+        CU_STREAM_WAIT_VALUE_GEQ,
+        CU_STREAM_WAIT_VALUE_EQ,
+        CU_STREAM_WAIT_VALUE_AND,
+    ] {
+        assert_eq!(
+            cuda.cuStreamWaitValue32_v2(stream, flag, 5, mode),
+            CUresult::CUDA_SUCCESS
+        );
+    }
+    // ~(5 | 2) != 0
+    assert_eq!(
+        cuda.cuStreamWaitValue32_v2(stream, flag, 2, CU_STREAM_WAIT_VALUE_NOR),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuStreamWriteValue64_v2(stream, value64, u64::MAX - 1, 0),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuStreamWaitValue64_v2(stream, value64, u64::MAX - 1, CU_STREAM_WAIT_VALUE_EQ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuStreamWaitValue32_v2(stream, flag, 5, 0xff),
+        CUresult::CUDA_ERROR_INVALID_VALUE
+    );
+    // GEQ compares cyclically: (int32_t)(5 - 0xfffffffe) >= 0
+    assert_eq!(
+        cuda.cuStreamWaitValue32_v2(stream, flag, 0xfffffffe, CU_STREAM_WAIT_VALUE_GEQ),
+        CUresult::CUDA_SUCCESS
+    );
+    if !T::is_nvidia() {
+        assert_eq!(
+            cuda.cuStreamWaitValue32_v2(
+                stream,
+                flag,
+                5,
+                CU_STREAM_WAIT_VALUE_EQ | CU_STREAM_WAIT_VALUE_FLUSH
+            ),
+            CUresult::CUDA_ERROR_NOT_SUPPORTED
+        );
+    }
+    let mut ops = [mem::zeroed::<CUstreamBatchMemOpParams>(); 3];
+    ops[0].writeValue.operation = CUstreamBatchMemOpType::CU_STREAM_MEM_OP_WRITE_VALUE_32;
+    ops[0].writeValue.address = flag;
+    ops[0].writeValue.__bindgen_anon_1.value = 7;
+    ops[1].waitValue.operation = CUstreamBatchMemOpType::CU_STREAM_MEM_OP_WAIT_VALUE_32;
+    ops[1].waitValue.address = flag;
+    ops[1].waitValue.__bindgen_anon_1.value = 6;
+    ops[1].waitValue.flags = CU_STREAM_WAIT_VALUE_GEQ;
+    ops[2].writeValue.operation = CUstreamBatchMemOpType::CU_STREAM_MEM_OP_WRITE_VALUE_64;
+    ops[2].writeValue.address = value64;
+    ops[2].writeValue.__bindgen_anon_1.value64 = 0x0102030405060708;
+    assert_eq!(
+        cuda.cuStreamBatchMemOp_v2(stream, ops.len() as u32, ops.as_mut_ptr(), 0),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(cuda.cuStreamSynchronize(stream), CUresult::CUDA_SUCCESS);
+    let mut result = [0u32; 4];
+    assert_eq!(
+        cuda.cuMemcpyDtoH_v2(result.as_mut_ptr() as _, memory, 16),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(result[0], 7);
+    assert_eq!(
+        result[2] as u64 | ((result[3] as u64) << 32),
+        0x0102030405060708
+    );
+    assert_eq!(cuda.cuMemFree_v2(memory), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuStreamDestroy_v2(stream), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
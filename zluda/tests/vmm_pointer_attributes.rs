@@ -0,0 +1,88 @@
+use crate::common::CudaDriverFns;
+use cuda_types::*;
+use std::{mem, ptr};
+
+mod common;
+
+cuda_driver_test!(vmm_pointer_attributes);
+
+unsafe fn vmm_pointer_attributes<T: CudaDriverFns>(cuda: T) {
+    assert_eq!(cuda.cuInit(0), CUresult::CUDA_SUCCESS);
+    let mut ctx = ptr::null_mut();
+    assert_eq!(
+        cuda.cuCtxCreate_v2(&mut ctx, 0, CUdevice_v1(0)),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut prop = mem::zeroed::<CUmemAllocationProp>();
+    prop.type_ = CUmemAllocationType::CU_MEM_ALLOCATION_TYPE_PINNED;
+    prop.location.type_ = CUmemLocationType::CU_MEM_LOCATION_TYPE_DEVICE;
+    prop.location.id = 0;
+    let mut granularity = 0;
+    assert_eq!(
+        cuda.cuMemGetAllocationGranularity(
+            &mut granularity,
+            &prop,
+            CUmemAllocationGranularity_flags::CU_MEM_ALLOC_GRANULARITY_MINIMUM
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_ne!(granularity, 0);
+    let size = granularity * 2;
+    let mut handle = 0;
+    assert_eq!(
+        cuda.cuMemCreate(&mut handle, size, &prop, 0),
+        CUresult::CUDA_SUCCESS
+    );
+    let mut base = CUdeviceptr_v2(ptr::null_mut());
+    assert_eq!(
+        cuda.cuMemAddressReserve(&mut base, size, 0, CUdeviceptr_v2(ptr::null_mut()), 0),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(
+        cuda.cuMemMap(base, size, 0, handle, 0),
+        CUresult::CUDA_SUCCESS
+    );
+    let access = CUmemAccessDesc {
+        location: prop.location,
+        flags: CUmemAccess_flags::CU_MEM_ACCESS_FLAGS_PROT_READWRITE,
+    };
+    assert_eq!(
+        cuda.cuMemSetAccess(base, size, &access, 1),
+        CUresult::CUDA_SUCCESS
+    );
+    let inner = CUdeviceptr_v2(base.0.cast::<u8>().add(granularity).cast());
+    let mut range_start = CUdeviceptr_v2(ptr::null_mut());
+    assert_eq!(
+        cuda.cuPointerGetAttribute(
+            &mut range_start as *mut _ as *mut _,
+            CUpointer_attribute::CU_POINTER_ATTRIBUTE_RANGE_START_ADDR,
+            inner
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(range_start.0, base.0);
+    let mut range_size = 0usize;
+    assert_eq!(
+        cuda.cuPointerGetAttribute(
+            &mut range_size as *mut _ as *mut _,
+            CUpointer_attribute::CU_POINTER_ATTRIBUTE_RANGE_SIZE,
+            inner
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert_eq!(range_size, size);
+    let mut mapped = false;
+    assert_eq!(
+        cuda.cuPointerGetAttribute(
+            &mut mapped as *mut _ as *mut _,
+            CUpointer_attribute::CU_POINTER_ATTRIBUTE_MAPPED,
+            inner
+        ),
+        CUresult::CUDA_SUCCESS
+    );
+    assert!(mapped);
+    assert_eq!(cuda.cuMemUnmap(base, size), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemRelease(handle), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuMemAddressFree(base, size), CUresult::CUDA_SUCCESS);
+    assert_eq!(cuda.cuCtxDestroy_v2(ctx), CUresult::CUDA_SUCCESS);
+}
@@ -0,0 +1,630 @@
+use argh::FromArgs;
+use hip_common::cache::{self, CacheSettings};
+use rusqlite::{Connection, OpenFlags};
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(FromArgs)]
+/// Inspect and manage ZLUDA kernel cache
+struct Arguments {
+    /// path to the cache file, defaults to the cache used by ZLUDA
+    #[argh(option, short = 'f')]
+    file: Option<PathBuf>,
+    #[argh(subcommand)]
+    command: Command,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    List(ListCommand),
+    Stat(StatCommand),
+    Export(ExportCommand),
+    Import(ImportCommand),
+    Purge(PurgeCommand),
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "list")]
+/// list cached kernels
+struct ListCommand {
+    /// only kernels compiled for this device, e.g. gfx1030
+    #[argh(option)]
+    device: Option<String>,
+    /// only kernels compiled with this compiler version
+    #[argh(option)]
+    compiler_version: Option<String>,
+    /// only kernels compiled by ZLUDA built from this git commit
+    #[argh(option)]
+    git_hash: Option<String>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "stat")]
+/// print number and size of cached kernels per device and compiler version
+struct StatCommand {}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "export")]
+/// copy cached kernels to a new cache file
+struct ExportCommand {
+    /// path to the new cache file
+    #[argh(positional)]
+    output: PathBuf,
+    /// only kernels compiled for this device, e.g. gfx1030
+    #[argh(option)]
+    device: Option<String>,
+    /// only kernels compiled with this compiler version
+    #[argh(option)]
+    compiler_version: Option<String>,
+    /// only kernels compiled by ZLUDA built from this git commit
+    #[argh(option)]
+    git_hash: Option<String>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "import")]
+/// copy kernels from another cache file, e.g. one created with export
+struct ImportCommand {
+    /// path to the imported cache file
+    #[argh(positional)]
+    input: PathBuf,
+    /// only kernels compiled for this device, e.g. gfx1030
+    #[argh(option)]
+    device: Option<String>,
+    /// only kernels compiled with this compiler version
+    #[argh(option)]
+    compiler_version: Option<String>,
+    /// only kernels compiled by ZLUDA built from this git commit
+    #[argh(option)]
+    git_hash: Option<String>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "purge")]
+/// remove cached kernels
+struct PurgeCommand {
+    /// remove kernels compiled for this device, e.g. gfx1030
+    #[argh(option)]
+    device: Option<String>,
+    /// remove kernels compiled with this compiler version
+    #[argh(option)]
+    compiler_version: Option<String>,
+    /// remove kernels compiled by ZLUDA built from this git commit
+    #[argh(option)]
+    git_hash: Option<String>,
+    /// remove least recently used kernels until the cache is not larger than
+    /// this size, e.g. 512M or 10G
+    #[argh(option, from_str_fn(parse_size))]
+    max_size: Option<u64>,
+    /// remove all kernels
+    #[argh(switch)]
+    all: bool,
+}
+
+fn parse_size(value: &str) -> Result<u64, String> {
+    cache::parse_size(value).ok_or_else(|| format!("Invalid size: {}", value))
+}
+
+// Columns shared by kernels tables of all ZLUDA libraries
+struct Filter {
+    device: Option<String>,
+    compiler_version: Option<String>,
+    git_hash: Option<String>,
+}
+
+impl Filter {
+    fn new(
+        device: &Option<String>,
+        compiler_version: &Option<String>,
+        git_hash: &Option<String>,
+    ) -> Self {
+        Filter {
+            device: device.clone(),
+            compiler_version: compiler_version.clone(),
+            git_hash: git_hash.clone(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.device.is_none() && self.compiler_version.is_none() && self.git_hash.is_none()
+    }
+
+    // Always returns a valid WHERE condition, `true` if there is nothing to filter
+    fn condition(&self, table: &str) -> String {
+        let mut condition = "true".to_string();
+        for (column, value) in [
+            ("device", &self.device),
+            ("compiler_version", &self.compiler_version),
+            ("git_hash", &self.git_hash),
+        ] {
+            if value.is_some() {
+                condition.push_str(&format!(" AND {table}.{column} = :{column}"));
+            }
+        }
+        condition
+    }
+
+    fn params(&self) -> Vec<(&'static str, &dyn rusqlite::ToSql)> {
+        IntoIterator::into_iter([
+            (":device", &self.device),
+            (":compiler_version", &self.compiler_version),
+            (":git_hash", &self.git_hash),
+        ])
+        .filter_map(|(name, value)| Some((name, value.as_ref()? as &dyn rusqlite::ToSql)))
+        .collect()
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Arguments = argh::from_env();
+    let settings = CacheSettings::load();
+    let file = match args.file.or_else(|| settings.cache_file()) {
+        Some(file) => file,
+        None => return Err("Could not find cache directory, use --file".into()),
+    };
+    run(
+        args.command,
+        &file,
+        settings.max_size,
+        &mut io::stdout().lock(),
+    )
+}
+
+// `max_size` is the cache size limit from the settings, applied on import
+fn run(
+    command: Command,
+    file: &Path,
+    max_size: Option<u64>,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::List(list) => {
+            let filter = Filter::new(&list.device, &list.compiler_version, &list.git_hash);
+            list_kernels(&open(file, false)?, &filter, out)
+        }
+        Command::Stat(_) => print_stats(&open(file, false)?, out),
+        Command::Export(export) => {
+            let filter = Filter::new(&export.device, &export.compiler_version, &export.git_hash);
+            export_kernels(file, &export.output, &filter, out)
+        }
+        Command::Import(import) => {
+            let filter = Filter::new(&import.device, &import.compiler_version, &import.git_hash);
+            import_kernels(file, &import.input, &filter, max_size, out)
+        }
+        Command::Purge(purge) => {
+            let filter = Filter::new(&purge.device, &purge.compiler_version, &purge.git_hash);
+            if !purge.all && purge.max_size.is_none() && filter.is_empty() {
+                return Err(
+                    "Nothing to purge, use --all, --max-size or select kernels to remove".into(),
+                );
+            }
+            purge_kernels(&open(file, true)?, &filter, purge.all, purge.max_size, out)
+        }
+    }
+}
+
+fn open(file: &Path, writable: bool) -> Result<Connection, Box<dyn Error>> {
+    let flags = if writable {
+        OpenFlags::SQLITE_OPEN_READ_WRITE
+    } else {
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+    };
+    let connection = Connection::open_with_flags(file, flags | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+        .map_err(|err| format!("Could not open {}: {}", file.display(), err))?;
+    // ZLUDA might be using the cache at the same time
+    connection.busy_timeout(Duration::from_secs(10))?;
+    Ok(connection)
+}
+
+fn list_kernels(
+    connection: &Connection,
+    filter: &Filter,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let mut statement = connection.prepare(&format!(
+        "SELECT id, hash, device, compiler_version, git_hash, length(binary), last_used
+        FROM kernels WHERE {} ORDER BY last_used DESC",
+        filter.condition("kernels")
+    ))?;
+    let mut rows = statement.query(&*filter.params())?;
+    writeln!(
+        out,
+        "id\thash\tdevice\tcompiler_version\tgit_hash\tsize\tlast_used"
+    )?;
+    while let Some(row) = rows.next()? {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, String>(4)?,
+            row.get::<_, i64>(5)?,
+            format_age(row.get::<_, i64>(6)?),
+        )?;
+    }
+    Ok(())
+}
+
+fn print_stats(connection: &Connection, out: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    let mut statement = connection.prepare(
+        "SELECT device, compiler_version, count(*), sum(length(binary))
+        FROM kernels GROUP BY device, compiler_version ORDER BY device, compiler_version",
+    )?;
+    let mut rows = statement.query([])?;
+    let (mut total_count, mut total_size) = (0, 0);
+    writeln!(out, "device\tcompiler_version\tkernels\tsize")?;
+    while let Some(row) = rows.next()? {
+        let count = row.get::<_, i64>(2)?;
+        let size = row.get::<_, i64>(3)?;
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            count,
+            format_size(size as u64)
+        )?;
+        total_count += count;
+        total_size += size;
+    }
+    writeln!(
+        out,
+        "total\t\t{}\t{}",
+        total_count,
+        format_size(total_size as u64)
+    )?;
+    Ok(())
+}
+
+fn export_kernels(
+    file: &Path,
+    output: &Path,
+    filter: &Filter,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    if !file.exists() {
+        return Err(format!("{} does not exist", file.display()).into());
+    }
+    if output.exists() {
+        return Err(format!("{} already exists", output.display()).into());
+    }
+    let connection = Connection::open(output)?;
+    connection.execute(
+        "ATTACH DATABASE ?1 AS source",
+        [file.to_string_lossy().as_ref()],
+    )?;
+    let tx = connection.unchecked_transaction()?;
+    copy_schema(&tx)?;
+    let copied = copy_kernels(&tx, filter, "")?;
+    tx.commit()?;
+    connection.execute("DETACH DATABASE source", [])?;
+    writeln!(out, "Exported {} kernels to {}", copied, output.display())?;
+    Ok(())
+}
+
+fn import_kernels(
+    file: &Path,
+    input: &Path,
+    filter: &Filter,
+    max_size: Option<u64>,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    // ATTACH silently creates missing database files
+    if !input.exists() {
+        return Err(format!("{} does not exist", input.display()).into());
+    }
+    let mut connection = Connection::open(file)?;
+    connection.busy_timeout(Duration::from_secs(10))?;
+    connection.execute(
+        "ATTACH DATABASE ?1 AS source",
+        [input.to_string_lossy().as_ref()],
+    )?;
+    let tx = connection.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+    let has_kernels = tx.query_row(
+        "SELECT count(*) FROM main.sqlite_master WHERE type = 'table' AND name = 'kernels'",
+        [],
+        |row| row.get::<_, i64>(0),
+    )? > 0;
+    if has_kernels {
+        if columns(&tx, "main")? != columns(&tx, "source")? {
+            return Err(format!(
+                "{} and {} come from different ZLUDA libraries",
+                file.display(),
+                input.display()
+            )
+            .into());
+        }
+    } else {
+        copy_schema(&tx)?;
+    }
+    // Kernel already in the cache is kept, but if it was used more recently
+    // in the imported cache we take that into account
+    let copied = copy_kernels(
+        &tx,
+        filter,
+        "ON CONFLICT DO UPDATE SET last_used = max(last_used, excluded.last_used)",
+    )?;
+    let evicted = match max_size {
+        Some(max_size) => cache::evict_least_recently_used(&tx, max_size)?,
+        None => 0,
+    };
+    tx.commit()?;
+    connection.execute("DETACH DATABASE source", [])?;
+    writeln!(out, "Imported {} kernels from {}", copied, input.display())?;
+    if evicted > 0 {
+        writeln!(out, "Removed {} least recently used kernels", evicted)?;
+    }
+    Ok(())
+}
+
+fn purge_kernels(
+    connection: &Connection,
+    filter: &Filter,
+    all: bool,
+    max_size: Option<u64>,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let tx = connection.unchecked_transaction()?;
+    let mut removed = 0;
+    if all || !filter.is_empty() {
+        removed += tx.execute(
+            &format!("DELETE FROM kernels WHERE {}", filter.condition("kernels")),
+            &*filter.params(),
+        )?;
+    }
+    if let Some(max_size) = max_size {
+        removed += cache::evict_least_recently_used(&tx, max_size)?;
+    }
+    tx.commit()?;
+    connection.execute("VACUUM", [])?;
+    writeln!(out, "Removed {} kernels", removed)?;
+    Ok(())
+}
+
+// Creates tables, indices and triggers of the source database in the main
+// database
+fn copy_schema(connection: &Connection) -> rusqlite::Result<()> {
+    let mut statement = connection.prepare(
+        "SELECT sql FROM source.sqlite_master
+        WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%'
+        ORDER BY CASE type WHEN 'table' THEN 0 WHEN 'index' THEN 1 ELSE 2 END",
+    )?;
+    let schema = statement
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for sql in schema {
+        connection.execute_batch(&sql)?;
+    }
+    connection.execute(
+        "INSERT OR IGNORE INTO main.globals (key, value) VALUES ('total_binary_size', 0)",
+        [],
+    )?;
+    Ok(())
+}
+
+// Copies kernels from the source database to the main database, ids are
+// assigned anew
+fn copy_kernels(
+    connection: &Connection,
+    filter: &Filter,
+    on_conflict: &str,
+) -> rusqlite::Result<usize> {
+    let columns = columns(connection, "source")?
+        .into_iter()
+        .filter(|column| column != "id")
+        .collect::<Vec<_>>()
+        .join(", ");
+    // WHERE is required to disambiguate ON CONFLICT in INSERT ... SELECT
+    connection.execute(
+        &format!(
+            "INSERT INTO main.kernels ({columns}) SELECT {columns} FROM source.kernels
+            WHERE {} {on_conflict}",
+            filter.condition("source.kernels")
+        ),
+        &*filter.params(),
+    )
+}
+
+fn columns(connection: &Connection, schema: &str) -> rusqlite::Result<Vec<String>> {
+    let mut statement = connection.prepare(&format!("PRAGMA {schema}.table_info(kernels)"))?;
+    let mut columns = statement
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    columns.sort_unstable();
+    Ok(columns)
+}
+
+fn format_size(size: u64) -> String {
+    let mut size = size as f64;
+    for unit in ["B", "KiB", "MiB", "GiB"] {
+        if size < 1024.0 {
+            return format!("{:.1} {}", size, unit);
+        }
+        size /= 1024.0;
+    }
+    format!("{:.1} TiB", size)
+}
+
+// last_used is stored as milliseconds since UNIX epoch
+fn format_age(last_used: i64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_millis() as i64);
+    let seconds = (now - last_used).max(0) / 1000;
+    match seconds {
+        0..=59 => format!("{}s ago", seconds),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, Arguments};
+    use argh::FromArgs;
+    use hip_common::cache::{KernelExtendedData, KernelKey, KernelRepository};
+    use rusqlite::Connection;
+    use std::error::Error;
+    use std::ffi::CString;
+    use std::path::Path;
+
+    struct NoExtendedData;
+
+    impl KernelExtendedData for NoExtendedData {
+        const INPUT_COLUMNS: &'static [[&'static str; 2]] = &[];
+    }
+
+    struct OtherExtendedData;
+
+    impl KernelExtendedData for OtherExtendedData {
+        const INPUT_COLUMNS: &'static [[&'static str; 2]] = &[["program_name", "TEXT NOT NULL"]];
+    }
+
+    // (last_used, hash, device, compiler_version, binary size)
+    const KERNELS: [(i64, &str, &str, &str, usize); 3] = [
+        (1, "A", "gfx1030", "Clang 15", 4),
+        (2, "B", "gfx1030", "Clang 16", 8),
+        (3, "C", "gfx90a", "Clang 15", 16),
+    ];
+
+    fn create_cache(file: &Path) {
+        let repository = KernelRepository::<NoExtendedData>::new(Some(file.to_path_buf())).unwrap();
+        for (now, hash, device, compiler_version, size) in KERNELS {
+            let device = CString::new(device).unwrap();
+            let key = KernelKey {
+                hash,
+                compiler_version,
+                git_hash: "EEEE",
+                device: &device,
+            };
+            repository
+                .save_program(now, &key, &vec![0; size], &[])
+                .unwrap();
+        }
+    }
+
+    fn zluda_cache(
+        file: &Path,
+        args: &[&str],
+        max_size: Option<u64>,
+    ) -> Result<String, Box<dyn Error>> {
+        let args = [&["-f", file.to_str().unwrap()][..], args].concat();
+        let arguments = Arguments::from_args(&["zluda-cache"], &args).unwrap();
+        let mut out = Vec::new();
+        run(
+            arguments.command,
+            &arguments.file.unwrap(),
+            max_size,
+            &mut out,
+        )?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    fn hashes(file: &Path) -> Vec<String> {
+        let connection = Connection::open(file).unwrap();
+        let mut statement = connection
+            .prepare("SELECT hash FROM kernels ORDER BY hash")
+            .unwrap();
+        let hashes = statement
+            .query_map([], |row| row.get::<_, String>(0))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap();
+        hashes
+    }
+
+    #[test]
+    fn list_and_stat() {
+        let directory = tempfile::tempdir().unwrap();
+        let file = directory.path().join("zluda.db");
+        create_cache(&file);
+        let list = zluda_cache(&file, &["list", "--device", "gfx1030"], None).unwrap();
+        let rows = list
+            .lines()
+            .skip(1)
+            .map(|line| line.split('\t').take(4).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                ["2", "B", "gfx1030", "Clang 16"],
+                ["1", "A", "gfx1030", "Clang 15"]
+            ]
+        );
+        let stat = zluda_cache(&file, &["stat"], None).unwrap();
+        assert_eq!(
+            stat.lines().collect::<Vec<_>>(),
+            [
+                "device\tcompiler_version\tkernels\tsize",
+                "gfx1030\tClang 15\t1\t4.0 B",
+                "gfx1030\tClang 16\t1\t8.0 B",
+                "gfx90a\tClang 15\t1\t16.0 B",
+                "total\t\t3\t28.0 B"
+            ]
+        );
+    }
+
+    #[test]
+    fn export_and_import() {
+        let directory = tempfile::tempdir().unwrap();
+        let file = directory.path().join("zluda.db");
+        let exported = directory.path().join("exported.db");
+        let imported = directory.path().join("imported.db");
+        create_cache(&file);
+        let export = zluda_cache(
+            &file,
+            &["export", exported.to_str().unwrap(), "--device", "gfx90a"],
+            None,
+        )
+        .unwrap();
+        assert!(export.starts_with("Exported 1 kernels"));
+        assert_eq!(hashes(&exported), ["C"]);
+        assert!(zluda_cache(&file, &["export", exported.to_str().unwrap()], None).is_err());
+        // Import into a file without tables copies the schema too
+        zluda_cache(&imported, &["import", exported.to_str().unwrap()], None).unwrap();
+        assert_eq!(hashes(&imported), ["C"]);
+        // C is already there, the cache size limit is applied afterwards
+        let import = zluda_cache(&imported, &["import", file.to_str().unwrap()], Some(24)).unwrap();
+        assert_eq!(
+            import.lines().collect::<Vec<_>>(),
+            [
+                format!("Imported 3 kernels from {}", file.display()),
+                "Removed 1 least recently used kernels".to_string()
+            ]
+        );
+        assert_eq!(hashes(&imported), ["B", "C"]);
+    }
+
+    #[test]
+    fn import_rejects_other_libraries() {
+        let directory = tempfile::tempdir().unwrap();
+        let file = directory.path().join("zluda.db");
+        let other = directory.path().join("zluda_optix6.db");
+        create_cache(&file);
+        KernelRepository::<OtherExtendedData>::new(Some(other.clone())).unwrap();
+        assert!(zluda_cache(&file, &["import", other.to_str().unwrap()], None).is_err());
+        assert_eq!(hashes(&file), ["A", "B", "C"]);
+    }
+
+    #[test]
+    fn purge() {
+        let directory = tempfile::tempdir().unwrap();
+        let file = directory.path().join("zluda.db");
+        create_cache(&file);
+        assert!(zluda_cache(&file, &["purge"], None).is_err());
+        let purge = zluda_cache(&file, &["purge", "--compiler-version", "Clang 16"], None).unwrap();
+        assert_eq!(purge, "Removed 1 kernels\n");
+        assert_eq!(hashes(&file), ["A", "C"]);
+        // Only the most recently used kernel fits
+        zluda_cache(&file, &["purge", "--max-size", "16"], None).unwrap();
+        assert_eq!(hashes(&file), ["C"]);
+        zluda_cache(&file, &["purge", "--all"], None).unwrap();
+        assert!(hashes(&file).is_empty());
+    }
+}
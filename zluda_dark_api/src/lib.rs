@@ -594,6 +594,25 @@ impl CudaFatbin {
     pub unsafe fn from_header(fatbin_header: *const FatbinHeader) -> Self {
         CudaFatbin::Version1(FatbinModuleHandle(fatbin_header))
     }
+
+    // .nv_fatbin section of an executable or a shared library is a sequence
+    // of fatbins, each one aligned to 8 bytes. Section data must be aligned
+    // to 8 bytes and outlive the returned fatbins
+    pub unsafe fn from_elf_section(section: &[u8]) -> Vec<Self> {
+        let mut result = Vec::new();
+        let mut offset = 0;
+        while offset + mem::size_of::<FatbinHeader>() <= section.len() {
+            let header = section.as_ptr().add(offset) as *const FatbinHeader;
+            if (*header).magic != FATBIN_MAGIC {
+                break;
+            }
+            result.push(Self::from_header(header));
+            offset += (*header).header_size as usize + (*header).files_size as usize;
+            offset = (offset + mem::align_of::<FatbinHeader>() - 1)
+                & !(mem::align_of::<FatbinHeader>() - 1);
+        }
+        result
+    }
 }
 
 pub enum CUmoduleContent {
@@ -7,6 +7,7 @@ use std::hash::Hash;
 use std::{
     collections::{hash_map, HashMap},
     ffi::c_void,
+    io::Write,
     mem,
     os::raw::c_uint,
     slice,
@@ -165,14 +166,12 @@ impl CudaDarkApiDump for CudaDarkApiDumpFns {
         module: *mut CUmodule,
         fatbinc_wrapper: *const zluda_dark_api::FatbincWrapper,
     ) -> CUresult {
-        let arguments_writer = Box::new(move |writer: &mut dyn std::io::Write| {
-            writer.write_all(b"(")?;
-            writer.write_all(stringify!(result).as_bytes())?;
-            writer.write_all(b": ")?;
+        let arguments_writer = Box::new(move |writer: &mut format::ArgumentsWriter| {
+            writer.begin_argument(stringify!(result))?;
             format::CudaDisplay::write(&module, "", 0, writer)?;
-            writer.write_all(b", ")?;
-            writer.write_all(stringify!(fatbinc_wrapper).as_bytes())?;
-            write!(writer, ": {:p})", fatbinc_wrapper)
+            writer.begin_argument(stringify!(fatbinc_wrapper))?;
+            write!(writer, "{:p}", fatbinc_wrapper)?;
+            writer.finish()
         });
         let global_state = &mut *super::GLOBAL_STATE.lock().unwrap();
         let mut fn_logger = global_state.log_factory.get_logger_dark_api(
@@ -224,20 +223,18 @@ impl CudaDarkApiDump for CudaDarkApiDumpFns {
         arg4: *mut c_void,
         arg5: usize,
     ) -> CUresult {
-        let arguments_writer = Box::new(move |writer: &mut dyn std::io::Write| {
-            writer.write_all(b"(")?;
-            writer.write_all(stringify!(module).as_bytes())?;
-            writer.write_all(b": ")?;
+        let arguments_writer = Box::new(move |writer: &mut format::ArgumentsWriter| {
+            writer.begin_argument(stringify!(module))?;
             format::CudaDisplay::write(&module, "", 0, writer)?;
-            writer.write_all(b", ")?;
-            writer.write_all(stringify!(fatbinc_wrapper).as_bytes())?;
-            write!(writer, ": {:p}, ", fatbinc_wrapper)?;
-            writer.write_all(stringify!(arg3).as_bytes())?;
-            write!(writer, ": {:p}, ", arg3)?;
-            writer.write_all(stringify!(arg4).as_bytes())?;
-            write!(writer, ": {:p}, ", arg4)?;
-            writer.write_all(stringify!(arg5).as_bytes())?;
-            write!(writer, ": {})", arg5)
+            writer.begin_argument(stringify!(fatbinc_wrapper))?;
+            write!(writer, "{:p}", fatbinc_wrapper)?;
+            writer.begin_argument(stringify!(arg3))?;
+            write!(writer, "{:p}", arg3)?;
+            writer.begin_argument(stringify!(arg4))?;
+            write!(writer, "{:p}", arg4)?;
+            writer.begin_argument(stringify!(arg5))?;
+            write!(writer, "{}", arg5)?;
+            writer.finish()
         });
         let global_state = &mut *super::GLOBAL_STATE.lock().unwrap();
         let mut fn_logger = global_state.log_factory.get_logger_dark_api(
@@ -292,20 +289,18 @@ impl CudaDarkApiDump for CudaDarkApiDumpFns {
         arg4: *mut c_void,
         arg5: c_uint,
     ) -> CUresult {
-        let arguments_writer = Box::new(move |writer: &mut dyn std::io::Write| {
-            writer.write_all(b"(")?;
-            writer.write_all(stringify!(fatbin_header).as_bytes())?;
-            write!(writer, ": {:p}, ", fatbin_header)?;
-            writer.write_all(stringify!(module).as_bytes())?;
-            writer.write_all(b": ")?;
+        let arguments_writer = Box::new(move |writer: &mut format::ArgumentsWriter| {
+            writer.begin_argument(stringify!(fatbin_header))?;
+            write!(writer, "{:p}", fatbin_header)?;
+            writer.begin_argument(stringify!(module))?;
             format::CudaDisplay::write(&module, "", 0, writer)?;
-            writer.write_all(b", ")?;
-            writer.write_all(stringify!(arg3).as_bytes())?;
-            write!(writer, ": {:p}, ", arg3)?;
-            writer.write_all(stringify!(arg4).as_bytes())?;
-            write!(writer, ": {:p}, ", arg4)?;
-            writer.write_all(stringify!(arg5).as_bytes())?;
-            write!(writer, ": {})", arg5)
+            writer.begin_argument(stringify!(arg3))?;
+            write!(writer, "{:p}", arg3)?;
+            writer.begin_argument(stringify!(arg4))?;
+            write!(writer, "{:p}", arg4)?;
+            writer.begin_argument(stringify!(arg5))?;
+            write!(writer, "{}", arg5)?;
+            writer.finish()
         });
         let global_state = &mut *super::GLOBAL_STATE.lock().unwrap();
         let mut fn_logger = global_state.log_factory.get_logger_dark_api(
@@ -361,34 +356,28 @@ impl CudaDarkApiDump for CudaDarkApiDumpFns {
         hStream: CUstream,
         extra: *mut *mut ::std::os::raw::c_void,
     ) -> CUresult {
-        let arguments_writer = Box::new(move |writer: &mut dyn std::io::Write| {
-            writer.write_all(b"(")?;
-            writer.write_all(stringify!(f).as_bytes())?;
-            writer.write_all(b": ")?;
+        let arguments_writer = Box::new(move |writer: &mut format::ArgumentsWriter| {
+            writer.begin_argument(stringify!(f))?;
             format::CudaDisplay::write(&f, "", 0, writer)?;
-            writer.write_all(b", ")?;
-            writer.write_all(stringify!(gridDimX).as_bytes())?;
-            write!(writer, ": {}, ", gridDimX)?;
-            writer.write_all(stringify!(gridDimY).as_bytes())?;
-            write!(writer, ": {}, ", gridDimY)?;
-            writer.write_all(stringify!(gridDimZ).as_bytes())?;
-            write!(writer, ": {}, ", gridDimZ)?;
-            writer.write_all(stringify!(blockDimX).as_bytes())?;
-            write!(writer, ": {}, ", blockDimX)?;
-            writer.write_all(stringify!(blockDimY).as_bytes())?;
-            write!(writer, ": {}, ", blockDimY)?;
-            writer.write_all(stringify!(blockDimZ).as_bytes())?;
-            write!(writer, ": {}, ", blockDimZ)?;
-            writer.write_all(stringify!(sharedMemBytes).as_bytes())?;
-            write!(writer, ": {}, ", sharedMemBytes)?;
-            writer.write_all(stringify!(hStream).as_bytes())?;
-            writer.write_all(b": ")?;
+            writer.begin_argument(stringify!(gridDimX))?;
+            write!(writer, "{}", gridDimX)?;
+            writer.begin_argument(stringify!(gridDimY))?;
+            write!(writer, "{}", gridDimY)?;
+            writer.begin_argument(stringify!(gridDimZ))?;
+            write!(writer, "{}", gridDimZ)?;
+            writer.begin_argument(stringify!(blockDimX))?;
+            write!(writer, "{}", blockDimX)?;
+            writer.begin_argument(stringify!(blockDimY))?;
+            write!(writer, "{}", blockDimY)?;
+            writer.begin_argument(stringify!(blockDimZ))?;
+            write!(writer, "{}", blockDimZ)?;
+            writer.begin_argument(stringify!(sharedMemBytes))?;
+            write!(writer, "{}", sharedMemBytes)?;
+            writer.begin_argument(stringify!(hStream))?;
             format::CudaDisplay::write(&hStream, "", 0, writer)?;
-            writer.write_all(b", ")?;
-            writer.write_all(stringify!(extra).as_bytes())?;
-            writer.write_all(b": ")?;
+            writer.begin_argument(stringify!(extra))?;
             format::CudaDisplay::write(&extra, "cuLaunchKernel", 9, writer)?;
-            writer.write_all(b")")
+            writer.finish()
         });
         let global_state = &mut *super::GLOBAL_STATE.lock().unwrap();
         let mut fn_logger = global_state.log_factory.get_logger_dark_api(
@@ -448,14 +437,14 @@ impl CudaDarkApiDump for CudaDarkApiDumpFns {
         handle: *mut c_void,
         retval2: *mut *mut c_void,
     ) -> CUresult {
-        let arguments_writer = Box::new(move |writer: &mut dyn std::io::Write| {
-            writer.write_all(b"(")?;
-            writer.write_all(stringify!(retval1).as_bytes())?;
-            write!(writer, ": {:p}, ", deref_not_null(retval1))?;
-            writer.write_all(stringify!(handle).as_bytes())?;
-            write!(writer, ": {:p}, ", handle)?;
-            writer.write_all(stringify!(retval2).as_bytes())?;
-            write!(writer, ": {:p})", deref_not_null(retval2))
+        let arguments_writer = Box::new(move |writer: &mut format::ArgumentsWriter| {
+            writer.begin_argument(stringify!(retval1))?;
+            write!(writer, "{:p}", deref_not_null(retval1))?;
+            writer.begin_argument(stringify!(handle))?;
+            write!(writer, "{:p}", handle)?;
+            writer.begin_argument(stringify!(retval2))?;
+            write!(writer, "{:p}", deref_not_null(retval2))?;
+            writer.finish()
         });
         let global_state = &mut *super::GLOBAL_STATE.lock().unwrap();
         let mut fn_logger = global_state.log_factory.get_logger_dark_api(
@@ -478,7 +467,7 @@ impl CudaDarkApiDump for CudaDarkApiDumpFns {
 
     unsafe fn dlss_cuInit_impl(guid: &[u8; 16], idx: usize) -> CUresult {
         let arguments_writer =
-            Box::new(move |writer: &mut dyn std::io::Write| writer.write_all(b"()"));
+            Box::new(move |writer: &mut format::ArgumentsWriter| writer.finish());
         let global_state = &mut *super::GLOBAL_STATE.lock().unwrap();
         let mut fn_logger = global_state.log_factory.get_logger_dark_api(
             CUuuid {
@@ -505,18 +494,18 @@ impl CudaDarkApiDump for CudaDarkApiDumpFns {
         arg4: *mut c_void,
         arg5: *mut c_void,
     ) -> CUresult {
-        let arguments_writer = Box::new(move |writer: &mut dyn std::io::Write| {
-            writer.write_all(b"(")?;
-            writer.write_all(stringify!(retval1).as_bytes())?;
-            write!(writer, ": {:p}, ", deref_not_null(retval1))?;
-            writer.write_all(stringify!(arg2).as_bytes())?;
-            write!(writer, ": {:p}, ", arg2)?;
-            writer.write_all(stringify!(arg3).as_bytes())?;
-            write!(writer, ": {:p}, ", arg3)?;
-            writer.write_all(stringify!(arg4).as_bytes())?;
-            write!(writer, ": {:p}, ", arg4)?;
-            writer.write_all(stringify!(arg5).as_bytes())?;
-            write!(writer, ": {:p})", arg5)
+        let arguments_writer = Box::new(move |writer: &mut format::ArgumentsWriter| {
+            writer.begin_argument(stringify!(retval1))?;
+            write!(writer, "{:p}", deref_not_null(retval1))?;
+            writer.begin_argument(stringify!(arg2))?;
+            write!(writer, "{:p}", arg2)?;
+            writer.begin_argument(stringify!(arg3))?;
+            write!(writer, "{:p}", arg3)?;
+            writer.begin_argument(stringify!(arg4))?;
+            write!(writer, "{:p}", arg4)?;
+            writer.begin_argument(stringify!(arg5))?;
+            write!(writer, "{:p}", arg5)?;
+            writer.finish()
         });
         let global_state = &mut *super::GLOBAL_STATE.lock().unwrap();
         let mut fn_logger = global_state.log_factory.get_logger_dark_api(
@@ -550,16 +539,16 @@ impl CudaDarkApiDump for CudaDarkApiDumpFns {
         handle: *mut c_void,
         retval: *mut u32,
     ) -> CUresult {
-        let arguments_writer = Box::new(move |writer: &mut dyn std::io::Write| {
-            writer.write_all(b"(")?;
-            writer.write_all(stringify!(handle).as_bytes())?;
-            write!(writer, ": {:p}, ", handle)?;
-            writer.write_all(stringify!(retval).as_bytes())?;
+        let arguments_writer = Box::new(move |writer: &mut format::ArgumentsWriter| {
+            writer.begin_argument(stringify!(handle))?;
+            write!(writer, "{:p}", handle)?;
+            writer.begin_argument(stringify!(retval))?;
             if retval == ptr::null_mut() {
-                write!(writer, ": {:p})", retval)
+                write!(writer, "{:p}", retval)?;
             } else {
-                write!(writer, ": {})", *retval)
+                write!(writer, "{}", *retval)?;
             }
+            writer.finish()
         });
         let global_state = &mut *super::GLOBAL_STATE.lock().unwrap();
         let mut fn_logger = global_state.log_factory.get_logger_dark_api(
@@ -589,22 +578,16 @@ impl CudaDarkApiDump for CudaDarkApiDumpFns {
         retval3: *mut u32,
         handle: *mut c_void,
     ) -> CUresult {
-        let arguments_writer = Box::new(move |writer: &mut dyn std::io::Write| {
-            writer.write_all(b"(")?;
-            writer.write_all(stringify!(retval1).as_bytes())?;
-            writer.write_all(b": ")?;
+        let arguments_writer = Box::new(move |writer: &mut format::ArgumentsWriter| {
+            writer.begin_argument(stringify!(retval1))?;
             write_deref(writer, retval1)?;
-            writer.write_all(b", ")?;
-            writer.write_all(stringify!(retval2).as_bytes())?;
-            writer.write_all(b": ")?;
+            writer.begin_argument(stringify!(retval2))?;
             write_deref(writer, retval2)?;
-            writer.write_all(b", ")?;
-            writer.write_all(stringify!(retval3).as_bytes())?;
-            writer.write_all(b": ")?;
+            writer.begin_argument(stringify!(retval3))?;
             write_deref(writer, retval3)?;
-            writer.write_all(b", ")?;
-            writer.write_all(stringify!(handle).as_bytes())?;
-            write!(writer, ": {:p})", handle)
+            writer.begin_argument(stringify!(handle))?;
+            write!(writer, "{:p}", handle)?;
+            writer.finish()
         });
         let global_state = &mut *super::GLOBAL_STATE.lock().unwrap();
         let mut fn_logger = global_state.log_factory.get_logger_dark_api(
@@ -636,20 +619,20 @@ impl CudaDarkApiDump for CudaDarkApiDumpFns {
         handle5: *mut c_void,
         arg6: u32,
     ) -> CUresult {
-        let arguments_writer = Box::new(move |writer: &mut dyn std::io::Write| {
-            writer.write_all(b"(")?;
-            writer.write_all(stringify!(handle1).as_bytes())?;
-            write!(writer, ": {:p}, ", handle1)?;
-            writer.write_all(stringify!(handle2).as_bytes())?;
-            write!(writer, ": {:p}, ", handle2)?;
-            writer.write_all(stringify!(handle3).as_bytes())?;
-            write!(writer, ": {:p}, ", handle3)?;
-            writer.write_all(stringify!(arg4).as_bytes())?;
-            write!(writer, ": {}, ", arg4)?;
-            writer.write_all(stringify!(handle5).as_bytes())?;
-            write!(writer, ": {:p}, ", handle5)?;
-            writer.write_all(stringify!(arg6).as_bytes())?;
-            write!(writer, ": {})", arg6)
+        let arguments_writer = Box::new(move |writer: &mut format::ArgumentsWriter| {
+            writer.begin_argument(stringify!(handle1))?;
+            write!(writer, "{:p}", handle1)?;
+            writer.begin_argument(stringify!(handle2))?;
+            write!(writer, "{:p}", handle2)?;
+            writer.begin_argument(stringify!(handle3))?;
+            write!(writer, "{:p}", handle3)?;
+            writer.begin_argument(stringify!(arg4))?;
+            write!(writer, "{}", arg4)?;
+            writer.begin_argument(stringify!(handle5))?;
+            write!(writer, "{:p}", handle5)?;
+            writer.begin_argument(stringify!(arg6))?;
+            write!(writer, "{}", arg6)?;
+            writer.finish()
         });
         let global_state = &mut *super::GLOBAL_STATE.lock().unwrap();
         let mut fn_logger = global_state.log_factory.get_logger_dark_api(
@@ -687,20 +670,20 @@ impl CudaDarkApiDump for CudaDarkApiDumpFns {
         arg5: *mut c_void,
         arg6: *mut c_void,
     ) -> CUresult {
-        let arguments_writer = Box::new(move |writer: &mut dyn std::io::Write| {
-            writer.write_all(b"(")?;
-            writer.write_all(stringify!(context).as_bytes())?;
-            write!(writer, ": {:p}, ", context)?;
-            writer.write_all(stringify!(result).as_bytes())?;
-            write!(writer, ": {:p}, ", deref_not_null(result))?;
-            writer.write_all(stringify!(fatbin).as_bytes())?;
-            write!(writer, ": {:p}, ", fatbin)?;
-            writer.write_all(stringify!(arg4).as_bytes())?;
-            write!(writer, ": {}, ", arg4)?;
-            writer.write_all(stringify!(arg5).as_bytes())?;
-            write!(writer, ": {:p}, ", arg5)?;
-            writer.write_all(stringify!(arg6).as_bytes())?;
-            write!(writer, ": {:p})", arg6)
+        let arguments_writer = Box::new(move |writer: &mut format::ArgumentsWriter| {
+            writer.begin_argument(stringify!(context))?;
+            write!(writer, "{:p}", context)?;
+            writer.begin_argument(stringify!(result))?;
+            write!(writer, "{:p}", deref_not_null(result))?;
+            writer.begin_argument(stringify!(fatbin))?;
+            write!(writer, "{:p}", fatbin)?;
+            writer.begin_argument(stringify!(arg4))?;
+            write!(writer, "{}", arg4)?;
+            writer.begin_argument(stringify!(arg5))?;
+            write!(writer, "{:p}", arg5)?;
+            writer.begin_argument(stringify!(arg6))?;
+            write!(writer, "{:p}", arg6)?;
+            writer.finish()
         });
         let global_state = &mut *super::GLOBAL_STATE.lock().unwrap();
         let mut fn_logger = global_state.log_factory.get_logger_dark_api(
@@ -749,16 +732,14 @@ impl CudaDarkApiDump for CudaDarkApiDumpFns {
         module: CUmodule,
         name: *const i8,
     ) -> CUresult {
-        let arguments_writer = Box::new(move |writer: &mut dyn std::io::Write| {
-            writer.write_all(b"(")?;
-            writer.write_all(stringify!(result).as_bytes())?;
-            write!(writer, ": {:p}, ", deref_not_null(result))?;
-            writer.write_all(stringify!(module).as_bytes())?;
-            write!(writer, ": {:p}, ", module)?;
-            writer.write_all(stringify!(name).as_bytes())?;
-            write!(writer, ": ")?;
+        let arguments_writer = Box::new(move |writer: &mut format::ArgumentsWriter| {
+            writer.begin_argument(stringify!(result))?;
+            write!(writer, "{:p}", deref_not_null(result))?;
+            writer.begin_argument(stringify!(module))?;
+            write!(writer, "{:p}", module)?;
+            writer.begin_argument(stringify!(name))?;
             format::CudaDisplay::write(&name, "", 0, writer)?;
-            write!(writer, ")")
+            writer.finish()
         });
         let global_state = &mut *super::GLOBAL_STATE.lock().unwrap();
         let mut fn_logger = global_state.log_factory.get_logger_dark_api(
@@ -787,20 +768,14 @@ impl CudaDarkApiDump for CudaDarkApiDumpFns {
         timestamp: u64,
         result: *mut u128,
     ) -> CUresult {
-        let arguments_writer = Box::new(move |writer: &mut dyn std::io::Write| {
-            writer.write_all(b"(")?;
-            writer.write_all(stringify!(rt_version).as_bytes())?;
-            writer.write_all(b": ")?;
+        let arguments_writer = Box::new(move |writer: &mut format::ArgumentsWriter| {
+            writer.begin_argument(stringify!(rt_version))?;
             format::CudaDisplay::write(&rt_version, "", 0, writer)?;
-            writer.write_all(b", ")?;
-            writer.write_all(stringify!(timestamp).as_bytes())?;
-            writer.write_all(b": ")?;
+            writer.begin_argument(stringify!(timestamp))?;
             format::CudaDisplay::write(&timestamp, "", 0, writer)?;
-            writer.write_all(b", ")?;
-            writer.write_all(stringify!(result).as_bytes())?;
-            writer.write_all(b": ")?;
+            writer.begin_argument(stringify!(result))?;
             write_deref(writer, result)?;
-            write!(writer, ")")
+            writer.finish()
         });
         let global_state = &mut *super::GLOBAL_STATE.lock().unwrap();
         let mut fn_logger = global_state.log_factory.get_logger_dark_api(
@@ -882,14 +857,14 @@ impl CudaDarkApiDump for CudaDarkApiDumpFns {
         is_wrapped: *mut u32,
         unwrapped_ctx: *mut CUcontext,
     ) -> CUresult {
-        let arguments_writer = Box::new(move |writer: &mut dyn std::io::Write| {
-            writer.write_all(b"(ctx: ")?;
+        let arguments_writer = Box::new(move |writer: &mut format::ArgumentsWriter| {
+            writer.begin_argument("ctx")?;
             format::CudaDisplay::write(&ctx, "", 0, writer)?;
-            writer.write_all(b", is_wrapped: ")?;
+            writer.begin_argument("is_wrapped")?;
             format::CudaDisplay::write(&is_wrapped, "", 0, writer)?;
-            writer.write_all(b", unwrapped_ctx: ")?;
+            writer.begin_argument("unwrapped_ctx")?;
             format::CudaDisplay::write(&unwrapped_ctx, "", 0, writer)?;
-            write!(writer, ")")
+            writer.finish()
         });
         let global_state = &mut *super::GLOBAL_STATE.lock().unwrap();
         let mut fn_logger = global_state.log_factory.get_logger_dark_api(
@@ -0,0 +1,271 @@
+use crate::log::LogEntry;
+use crate::parse_env_var;
+use regex::Regex;
+use serde::Deserialize;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::str::FromStr;
+
+// Limits what zluda_dump does for applications that make millions of calls.
+// Every setting can be passed either as an environment variable or as a field
+// in JSON file pointed to by ZLUDA_DUMP_CONFIG, e.g.:
+//   { "log_functions": "^cuLaunch", "dump_kernels": "gemm", "dump_launches": "100..200" }
+// Environment variables take precedence over the file
+pub(crate) struct FilterConfig {
+    // Calls to functions that don't match are logged only if they reported
+    // errors or warnings
+    pub(crate) log_functions: Option<Regex>,
+    // Kernel launch selection applies to side-by-side comparison and dumps
+    pub(crate) dump_kernels: Option<Regex>,
+    // Range of indices of kernel launches matching `dump_kernels`, starting
+    // from 0
+    pub(crate) dump_launches: Option<LaunchRange>,
+    pub(crate) dump_every_nth_launch: Option<u64>,
+    // Total size of modules and kernel launches saved to the dump directory.
+    // Logs and profiler output are not counted
+    pub(crate) max_dump_size: Option<ByteSize>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    log_functions: Option<String>,
+    dump_kernels: Option<String>,
+    dump_launches: Option<String>,
+    dump_every_nth_launch: Option<u64>,
+    max_dump_size: Option<String>,
+}
+
+impl FilterConfig {
+    pub(crate) fn read(report_err: &mut impl FnMut(LogEntry)) -> Self {
+        let file = Self::read_file().unwrap_or_else(|err| {
+            report_err(LogEntry::ErrorBox(err));
+            ConfigFile::default()
+        });
+        let dump_every_nth_launch =
+            parse_env_var::<u64, _>("ZLUDA_DUMP_EVERY_NTH_LAUNCH", report_err)
+                .or(file.dump_every_nth_launch)
+                .filter(|n| *n > 1);
+        FilterConfig {
+            log_functions: Self::read_setting(
+                "ZLUDA_LOG_FUNCTIONS",
+                file.log_functions,
+                report_err,
+            ),
+            dump_kernels: Self::read_setting("ZLUDA_DUMP_KERNELS", file.dump_kernels, report_err),
+            dump_launches: Self::read_setting(
+                "ZLUDA_DUMP_LAUNCHES",
+                file.dump_launches,
+                report_err,
+            ),
+            dump_every_nth_launch,
+            max_dump_size: Self::read_setting(
+                "ZLUDA_DUMP_MAX_SIZE",
+                file.max_dump_size,
+                report_err,
+            ),
+        }
+    }
+
+    fn read_file() -> Result<ConfigFile, Box<dyn std::error::Error>> {
+        let path = match env::var("ZLUDA_DUMP_CONFIG") {
+            Ok(path) => path,
+            Err(env::VarError::NotPresent) => return Ok(ConfigFile::default()),
+            Err(err) => return Err(Box::new(err)),
+        };
+        let text = fs::read_to_string(&path)
+            .map_err(|err| format!("Could not read config file {}: {}", path, err))?;
+        Ok(serde_json::from_str(&text)
+            .map_err(|err| format!("Could not parse config file {}: {}", path, err))?)
+    }
+
+    fn read_setting<T: FromStr>(
+        key: &'static str,
+        from_file: Option<String>,
+        report_err: &mut impl FnMut(LogEntry),
+    ) -> Option<T>
+    where
+        T::Err: Display + 'static,
+    {
+        if let Some(value) = parse_env_var::<T, _>(key, report_err) {
+            return Some(value);
+        }
+        match from_file?.parse::<T>() {
+            Ok(value) => Some(value),
+            Err(err) => {
+                report_err(LogEntry::ErrorBox(
+                    format!("Error parsing {} from the config file: {}", key, err).into(),
+                ));
+                None
+            }
+        }
+    }
+}
+
+// Half-open range of launch indices, written as "start..end", "start.." or
+// "..end"
+#[derive(Clone, Copy)]
+pub(crate) struct LaunchRange {
+    start: u64,
+    end: Option<u64>,
+}
+
+impl FromStr for LaunchRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Invalid launch range {}, expected start..end", s);
+        let (start, end) = s.split_once("..").ok_or_else(err)?;
+        let start = if start.is_empty() {
+            0
+        } else {
+            start.parse().map_err(|_| err())?
+        };
+        let end = if end.is_empty() {
+            None
+        } else {
+            Some(end.parse().map_err(|_| err())?)
+        };
+        Ok(LaunchRange { start, end })
+    }
+}
+
+// Size in bytes, optionally with a K, M or G suffix
+#[derive(Clone, Copy)]
+pub(crate) struct ByteSize(pub(crate) u64);
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, multiplier) = match s.as_bytes().last() {
+            Some(b'K') | Some(b'k') => (&s[..s.len() - 1], 1 << 10),
+            Some(b'M') | Some(b'm') => (&s[..s.len() - 1], 1 << 20),
+            Some(b'G') | Some(b'g') => (&s[..s.len() - 1], 1 << 30),
+            _ => (s, 1),
+        };
+        let number = number
+            .parse::<u64>()
+            .map_err(|_| format!("Invalid size {}, expected a number of bytes", s))?;
+        Ok(ByteSize(number.saturating_mul(multiplier)))
+    }
+}
+
+pub(crate) struct Filter {
+    log_functions: Option<Regex>,
+    dump_kernels: Option<Regex>,
+    dump_launches: Option<LaunchRange>,
+    dump_every_nth_launch: Option<u64>,
+    // Number of kernel launches that matched `dump_kernels` so far
+    matching_launches: u64,
+}
+
+impl Filter {
+    pub(crate) fn new(config: &FilterConfig) -> Self {
+        Filter {
+            log_functions: config.log_functions.clone(),
+            dump_kernels: config.dump_kernels.clone(),
+            dump_launches: config.dump_launches,
+            dump_every_nth_launch: config.dump_every_nth_launch,
+            matching_launches: 0,
+        }
+    }
+
+    pub(crate) fn should_log(&self, func: &str) -> bool {
+        match self.log_functions {
+            Some(ref log_functions) => log_functions.is_match(func),
+            None => true,
+        }
+    }
+
+    // Must be called once for every kernel launch, `name` is None if the
+    // kernel is unknown
+    pub(crate) fn should_dump_launch(&mut self, name: Option<&str>) -> bool {
+        if let Some(ref dump_kernels) = self.dump_kernels {
+            match name {
+                Some(name) if dump_kernels.is_match(name) => {}
+                _ => return false,
+            }
+        }
+        let index = self.matching_launches;
+        self.matching_launches += 1;
+        let start = match self.dump_launches {
+            Some(LaunchRange { start, end }) => {
+                if index < start || end.map_or(false, |end| index >= end) {
+                    return false;
+                }
+                start
+            }
+            None => 0,
+        };
+        match self.dump_every_nth_launch {
+            Some(n) => (index - start) % n == 0,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ByteSize, Filter, FilterConfig};
+    use regex::Regex;
+
+    fn filter(kernels: Option<&str>, launches: Option<&str>, every_nth: Option<u64>) -> Filter {
+        Filter::new(&FilterConfig {
+            log_functions: None,
+            dump_kernels: kernels.map(|kernels| Regex::new(kernels).unwrap()),
+            dump_launches: launches.map(|launches| launches.parse().unwrap()),
+            dump_every_nth_launch: every_nth,
+            max_dump_size: None,
+        })
+    }
+
+    fn selected(filter: &mut Filter, names: &[&str]) -> Vec<usize> {
+        names
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| filter.should_dump_launch(Some(name)))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    #[test]
+    fn launch_range_and_sampling() {
+        let names = ["k"; 10];
+        assert_eq!(selected(&mut filter(None, None, None), &names).len(), 10);
+        assert_eq!(
+            selected(&mut filter(None, Some("2..6"), None), &names),
+            vec![2, 3, 4, 5]
+        );
+        assert_eq!(
+            selected(&mut filter(None, Some("3.."), Some(3)), &names),
+            vec![3, 6, 9]
+        );
+        assert_eq!(
+            selected(&mut filter(None, Some("..4"), Some(2)), &names),
+            vec![0, 2]
+        );
+    }
+
+    #[test]
+    fn launch_indices_count_only_matching_kernels() {
+        let names = ["gemm", "copy", "gemm", "copy", "gemm", "gemm"];
+        assert_eq!(
+            selected(&mut filter(Some("^gemm$"), Some("1..3"), None), &names),
+            vec![2, 4]
+        );
+        let mut unknown = filter(Some("gemm"), None, None);
+        assert!(!unknown.should_dump_launch(None));
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!("512".parse::<ByteSize>().unwrap().0, 512);
+        assert_eq!("2K".parse::<ByteSize>().unwrap().0, 2048);
+        assert_eq!("3G".parse::<ByteSize>().unwrap().0, 3 << 30);
+        assert!("G".parse::<ByteSize>().is_err());
+        assert!("1..".parse::<super::LaunchRange>().is_ok());
+        assert!("1-2".parse::<super::LaunchRange>().is_err());
+    }
+}
@@ -2,6 +2,7 @@ use cuda_types::*;
 use std::{
     ffi::{c_void, CStr},
     fmt::LowerHex,
+    io::{self, Write},
     mem, ptr, slice,
 };
 
@@ -16,6 +17,61 @@ pub(crate) trait CudaDisplay {
     ) -> std::io::Result<()>;
 }
 
+// Writes arguments of a call as "(name: value, ...)". If `values` is set,
+// every argument value is also collected on its own for the structured log
+pub(crate) struct ArgumentsWriter<'a> {
+    text: &'a mut dyn Write,
+    values: Option<&'a mut Vec<(&'static str, Vec<u8>)>>,
+    started: bool,
+}
+
+impl<'a> ArgumentsWriter<'a> {
+    pub(crate) fn new(
+        text: &'a mut dyn Write,
+        values: Option<&'a mut Vec<(&'static str, Vec<u8>)>>,
+    ) -> Self {
+        Self {
+            text,
+            values,
+            started: false,
+        }
+    }
+
+    // Everything written until the next argument is the value of this one
+    pub(crate) fn begin_argument(&mut self, name: &'static str) -> io::Result<()> {
+        self.text
+            .write_all(if self.started { b", " } else { b"(" })?;
+        self.text.write_all(name.as_bytes())?;
+        self.text.write_all(b": ")?;
+        self.started = true;
+        if let Some(values) = &mut self.values {
+            values.push((name, Vec::new()));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(&mut self) -> io::Result<()> {
+        if !self.started {
+            self.text.write_all(b"(")?;
+        }
+        self.text.write_all(b")")
+    }
+}
+
+impl<'a> Write for ArgumentsWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.text.write(buf)?;
+        if let Some((_, value)) = self.values.as_mut().and_then(|values| values.last_mut()) {
+            value.extend_from_slice(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.text.flush()
+    }
+}
+
 impl CudaDisplay for cuda_types::CUuuid {
     fn write(
         &self,
@@ -632,18 +688,18 @@ impl CudaDisplay for cuda_types::CUlaunchAttribute_st {
 
 #[allow(non_snake_case)]
 pub fn write_cuStreamBatchMemOp(
-    writer: &mut (impl std::io::Write + ?Sized),
+    writer: &mut ArgumentsWriter,
     stream: cuda_types::CUstream,
     count: ::std::os::raw::c_uint,
     paramArray: *mut cuda_types::CUstreamBatchMemOpParams,
     flags: ::std::os::raw::c_uint,
 ) -> std::io::Result<()> {
-    writer.write_all(b"(stream: ")?;
+    writer.begin_argument("stream")?;
     CudaDisplay::write(&stream, "cuStreamBatchMemOp", 0, writer)?;
-    writer.write_all(b", ")?;
-    writer.write_all(b"count: ")?;
+    writer.begin_argument("count")?;
     CudaDisplay::write(&count, "cuStreamBatchMemOp", 1, writer)?;
-    writer.write_all(b", paramArray: [")?;
+    writer.begin_argument("paramArray")?;
+    writer.write_all(b"[")?;
     for i in 0..count {
         if i != 0 {
             writer.write_all(b", ")?;
@@ -655,25 +711,26 @@ pub fn write_cuStreamBatchMemOp(
             writer,
         )?;
     }
-    writer.write_all(b"], flags: ")?;
+    writer.write_all(b"]")?;
+    writer.begin_argument("flags")?;
     CudaDisplay::write(&flags, "cuStreamBatchMemOp", 3, writer)?;
-    writer.write_all(b")")
+    writer.finish()
 }
 
 #[allow(non_snake_case)]
 pub fn write_cuGraphKernelNodeGetAttribute(
-    writer: &mut (impl std::io::Write + ?Sized),
+    writer: &mut ArgumentsWriter,
     hNode: cuda_types::CUgraphNode,
     attr: cuda_types::CUlaunchAttributeID,
     value_out: *mut cuda_types::CUkernelNodeAttrValue,
 ) -> std::io::Result<()> {
-    writer.write_all(b"(hNode: ")?;
+    writer.begin_argument("hNode")?;
     CudaDisplay::write(&hNode, "cuGraphKernelNodeGetAttribute", 0, writer)?;
-    writer.write_all(b", attr: ")?;
+    writer.begin_argument("attr")?;
     CudaDisplay::write(&attr, "cuGraphKernelNodeGetAttribute", 1, writer)?;
+    writer.begin_argument("value_out")?;
     match attr {
         cuda_types::CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_ACCESS_POLICY_WINDOW => {
-            writer.write_all(b", value_out: ")?;
             CudaDisplay::write(
                 unsafe { &(*value_out).accessPolicyWindow },
                 "cuGraphKernelNodeGetAttribute",
@@ -682,7 +739,6 @@ pub fn write_cuGraphKernelNodeGetAttribute(
             )?;
         }
         cuda_types::CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_COOPERATIVE => {
-            writer.write_all(b", value_out: ")?;
             CudaDisplay::write(
                 unsafe { &(*value_out).cooperative },
                 "cuGraphKernelNodeGetAttribute",
@@ -690,14 +746,14 @@ pub fn write_cuGraphKernelNodeGetAttribute(
                 writer,
             )?;
         }
-        _ => return writer.write_all(b", ...) "),
+        _ => writer.write_all(b"...")?,
     }
-    writer.write_all(b")")
+    writer.finish()
 }
 
 #[allow(non_snake_case)]
 pub fn write_cuGraphKernelNodeSetAttribute(
-    writer: &mut (impl std::io::Write + ?Sized),
+    writer: &mut ArgumentsWriter,
     hNode: cuda_types::CUgraphNode,
     attr: cuda_types::CUlaunchAttributeID,
     value_out: *const cuda_types::CUkernelNodeAttrValue,
@@ -707,18 +763,18 @@ pub fn write_cuGraphKernelNodeSetAttribute(
 
 #[allow(non_snake_case)]
 pub fn write_cuStreamGetAttribute(
-    writer: &mut (impl std::io::Write + ?Sized),
+    writer: &mut ArgumentsWriter,
     hStream: cuda_types::CUstream,
     attr: cuda_types::CUstreamAttrID,
     value_out: *mut cuda_types::CUstreamAttrValue,
 ) -> std::io::Result<()> {
-    writer.write_all(b"(hStream: ")?;
+    writer.begin_argument("hStream")?;
     CudaDisplay::write(&hStream, "cuStreamGetAttribute", 0, writer)?;
-    writer.write_all(b", attr: ")?;
+    writer.begin_argument("attr")?;
     CudaDisplay::write(&attr, "cuStreamGetAttribute", 1, writer)?;
+    writer.begin_argument("value_out")?;
     match attr {
         cuda_types::CUstreamAttrID::CU_LAUNCH_ATTRIBUTE_ACCESS_POLICY_WINDOW => {
-            writer.write_all(b", value_out: ")?;
             CudaDisplay::write(
                 unsafe { &(*value_out).accessPolicyWindow },
                 "cuStreamGetAttribute",
@@ -727,7 +783,6 @@ pub fn write_cuStreamGetAttribute(
             )?;
         }
         cuda_types::CUstreamAttrID::CU_LAUNCH_ATTRIBUTE_SYNCHRONIZATION_POLICY => {
-            writer.write_all(b", value_out: ")?;
             CudaDisplay::write(
                 unsafe { &(*value_out).syncPolicy },
                 "cuStreamGetAttribute",
@@ -735,14 +790,14 @@ pub fn write_cuStreamGetAttribute(
                 writer,
             )?;
         }
-        _ => return writer.write_all(b", ...) "),
+        _ => writer.write_all(b"...")?,
     }
-    writer.write_all(b")")
+    writer.finish()
 }
 
 #[allow(non_snake_case)]
 pub fn write_cuStreamGetAttribute_ptsz(
-    writer: &mut (impl std::io::Write + ?Sized),
+    writer: &mut ArgumentsWriter,
     hStream: cuda_types::CUstream,
     attr: cuda_types::CUstreamAttrID,
     value_out: *mut cuda_types::CUstreamAttrValue,
@@ -752,7 +807,7 @@ pub fn write_cuStreamGetAttribute_ptsz(
 
 #[allow(non_snake_case)]
 pub fn write_cuStreamSetAttribute(
-    writer: &mut (impl std::io::Write + ?Sized),
+    writer: &mut ArgumentsWriter,
     hStream: cuda_types::CUstream,
     attr: cuda_types::CUstreamAttrID,
     value_out: *const cuda_types::CUstreamAttrValue,
@@ -762,7 +817,7 @@ pub fn write_cuStreamSetAttribute(
 
 #[allow(non_snake_case)]
 pub fn write_cuStreamSetAttribute_ptsz(
-    writer: &mut (impl std::io::Write + ?Sized),
+    writer: &mut ArgumentsWriter,
     hStream: cuda_types::CUstream,
     attr: cuda_types::CUstreamAttrID,
     value_out: *const cuda_types::CUstreamAttrValue,
@@ -772,7 +827,7 @@ pub fn write_cuStreamSetAttribute_ptsz(
 
 #[allow(non_snake_case)]
 pub fn write_cuCtxCreate_v3(
-    _writer: &mut (impl std::io::Write + ?Sized),
+    _writer: &mut ArgumentsWriter,
     _pctx: *mut cuda_types::CUcontext,
     _paramsArray: *mut cuda_types::CUexecAffinityParam,
     _numParams: ::std::os::raw::c_int,
@@ -784,7 +839,7 @@ pub fn write_cuCtxCreate_v3(
 
 #[allow(non_snake_case)]
 pub fn write_cuCtxGetExecAffinity(
-    _writer: &mut (impl std::io::Write + ?Sized),
+    _writer: &mut ArgumentsWriter,
     _pExecAffinity: *mut cuda_types::CUexecAffinityParam,
     _type_: cuda_types::CUexecAffinityType,
 ) -> std::io::Result<()> {
@@ -793,7 +848,7 @@ pub fn write_cuCtxGetExecAffinity(
 
 #[allow(non_snake_case)]
 pub fn write_cuMemMapArrayAsync(
-    _writer: &mut (impl std::io::Write + ?Sized),
+    _writer: &mut ArgumentsWriter,
     _mapInfoList: *mut cuda_types::CUarrayMapInfo,
     _count: ::std::os::raw::c_uint,
     _hStream: cuda_types::CUstream,
@@ -803,7 +858,7 @@ pub fn write_cuMemMapArrayAsync(
 
 #[allow(non_snake_case)]
 pub fn write_cuMemMapArrayAsync_ptsz(
-    writer: &mut (impl std::io::Write + ?Sized),
+    writer: &mut ArgumentsWriter,
     mapInfoList: *mut cuda_types::CUarrayMapInfo,
     count: ::std::os::raw::c_uint,
     hStream: cuda_types::CUstream,
@@ -813,26 +868,26 @@ pub fn write_cuMemMapArrayAsync_ptsz(
 
 #[allow(non_snake_case)]
 pub fn write_cuLinkCreate_v2(
-    writer: &mut (impl std::io::Write + ?Sized),
+    writer: &mut ArgumentsWriter,
     numOptions: ::std::os::raw::c_uint,
     options: *mut CUjit_option,
     optionValues: *mut *mut ::std::os::raw::c_void,
     stateOut: *mut CUlinkState,
 ) -> std::io::Result<()> {
-    writer.write_all(b"(numOptions: ")?;
+    writer.begin_argument("numOptions")?;
     CudaDisplay::write(&numOptions, "cuLinkCreate_v2", 0, writer)?;
-    writer.write_all(b", options: ")?;
+    writer.begin_argument("options")?;
     write_array("cuLinkCreate_v2", 1, writer, numOptions, options)?;
-    writer.write_all(b", optionValues: ")?;
+    writer.begin_argument("optionValues")?;
     write_array("cuLinkCreate_v2", 2, writer, numOptions, optionValues)?;
-    writer.write_all(b", stateOut: ")?;
+    writer.begin_argument("stateOut")?;
     CudaDisplay::write(&stateOut, "cuLinkCreate_v2", 3, writer)?;
-    writer.write_all(b")")
+    writer.finish()
 }
 
 #[allow(non_snake_case)]
 pub fn write_cuLinkAddData_v2(
-    writer: &mut (impl std::io::Write + ?Sized),
+    writer: &mut ArgumentsWriter,
     state: CUlinkState,
     type_: CUjitInputType,
     data: *mut ::std::os::raw::c_void,
@@ -842,45 +897,45 @@ pub fn write_cuLinkAddData_v2(
     options: *mut CUjit_option,
     optionValues: *mut *mut ::std::os::raw::c_void,
 ) -> std::io::Result<()> {
-    writer.write_all(b"(state: ")?;
+    writer.begin_argument("state")?;
     CudaDisplay::write(&state, "cuLinkAddData_v2", 0, writer)?;
-    writer.write_all(b", type: ")?;
+    writer.begin_argument("type")?;
     CudaDisplay::write(&type_, "cuLinkAddData_v2", 1, writer)?;
-    writer.write_all(b", data: ")?;
+    writer.begin_argument("data")?;
     CudaDisplay::write(&data, "cuLinkAddData_v2", 2, writer)?;
-    writer.write_all(b", size: ")?;
+    writer.begin_argument("size")?;
     CudaDisplay::write(&size, "cuLinkAddData_v2", 3, writer)?;
-    writer.write_all(b", name: ")?;
+    writer.begin_argument("name")?;
     CudaDisplay::write(&name, "cuLinkAddData_v2", 4, writer)?;
-    writer.write_all(b", numOptions: ")?;
+    writer.begin_argument("numOptions")?;
     CudaDisplay::write(&numOptions, "cuLinkAddData_v2", 5, writer)?;
-    writer.write_all(b", options: ")?;
+    writer.begin_argument("options")?;
     write_array("cuLinkAddData_v2", 6, writer, numOptions, options)?;
-    writer.write_all(b", optionValues: ")?;
+    writer.begin_argument("optionValues")?;
     write_array("cuLinkAddData_v2", 7, writer, numOptions, optionValues)?;
-    writer.write_all(b")")
+    writer.finish()
 }
 
 #[allow(non_snake_case)]
 pub fn write_cuModuleLoadDataEx(
-    writer: &mut (impl std::io::Write + ?Sized),
+    writer: &mut ArgumentsWriter,
     module: *mut CUmodule,
     image: *const ::std::os::raw::c_void,
     numOptions: ::std::os::raw::c_uint,
     options: *mut CUjit_option,
     optionValues: *mut *mut ::std::os::raw::c_void,
 ) -> std::io::Result<()> {
-    writer.write_all(b"(module: ")?;
+    writer.begin_argument("module")?;
     CudaDisplay::write(&module, "cuModuleLoadDataEx", 0, writer)?;
-    writer.write_all(b", image: ")?;
+    writer.begin_argument("image")?;
     CudaDisplay::write(&image, "cuModuleLoadDataEx", 1, writer)?;
-    writer.write_all(b", numOptions: ")?;
+    writer.begin_argument("numOptions")?;
     CudaDisplay::write(&numOptions, "cuModuleLoadDataEx", 2, writer)?;
-    writer.write_all(b", options: ")?;
+    writer.begin_argument("options")?;
     write_array("cuModuleLoadDataEx", 3, writer, numOptions, options)?;
-    writer.write_all(b", optionValues: ")?;
+    writer.begin_argument("optionValues")?;
     write_array("cuModuleLoadDataEx", 4, writer, numOptions, optionValues)?;
-    writer.write_all(b")")
+    writer.finish()
 }
 
 fn write_array<T: CudaDisplay>(
@@ -23,7 +23,7 @@ macro_rules! extern_redirect {
                 let original_fn = |dynamic_fns: &mut crate::CudaDynamicFns| {
                     dynamic_fns.$fn_name($( $arg_id ),*)
                 };
-                let get_formatted_args = Box::new(move |writer: &mut dyn std::io::Write| {
+                let get_formatted_args = Box::new(move |writer: &mut format::ArgumentsWriter| {
                     (paste! { format :: [<write_ $fn_name>] }) (
                         writer
                         $(,$arg_id)*
@@ -43,7 +43,7 @@ macro_rules! extern_redirect_with_post {
                 let original_fn = |dynamic_fns: &mut crate::CudaDynamicFns| {
                     dynamic_fns.$fn_name($( $arg_id ),*)
                 };
-                let get_formatted_args = Box::new(move |writer: &mut dyn std::io::Write| {
+                let get_formatted_args = Box::new(move |writer: &mut format::ArgumentsWriter| {
                     (paste! { format :: [<write_ $fn_name>] }) (
                         writer
                         $(,$arg_id)*
@@ -86,11 +86,107 @@ cuda_function_declarations!(
         cuGetProcAddress_v2,
         cuLinkAddData_v2,
         cuLibraryLoadData,
-        cuLibraryGetModule
+        cuLibraryGetModule,
+        cuLaunchKernel_ptsz,
+        cuLaunchCooperativeKernel,
+        cuLaunchCooperativeKernel_ptsz,
+        cuLaunchKernelEx,
+        cuLaunchKernelEx_ptsz,
+        cuGraphLaunch,
+        cuGraphLaunch_ptsz,
+        cuGraphInstantiate,
+        cuGraphInstantiate_v2,
+        cuGraphInstantiateWithFlags,
+        cuGraphInstantiateWithParams,
+        cuGraphInstantiateWithParams_ptsz,
+        cuGraphExecDestroy,
+        cuGraphExecKernelNodeSetParams,
+        cuGraphExecKernelNodeSetParams_v2,
+        cuGraphExecMemsetNodeSetParams,
+        cuGraphExecMemcpyNodeSetParams,
+        cuGraphExecUpdate,
+        cuGraphExecUpdate_v2,
+        cuTexObjectCreate,
+        cuTexObjectDestroy,
+        cuSurfObjectCreate,
+        cuSurfObjectDestroy,
+        cuMemcpy,
+        cuMemcpy_ptds,
+        cuMemcpyPeer,
+        cuMemcpyPeer_ptds,
+        cuMemcpyHtoD_v2,
+        cuMemcpyHtoD_v2_ptds,
+        cuMemcpyDtoH_v2,
+        cuMemcpyDtoH_v2_ptds,
+        cuMemcpyDtoD_v2,
+        cuMemcpyDtoD_v2_ptds,
+        cuMemcpyDtoA_v2,
+        cuMemcpyDtoA_v2_ptds,
+        cuMemcpyAtoD_v2,
+        cuMemcpyAtoD_v2_ptds,
+        cuMemcpyHtoA_v2,
+        cuMemcpyHtoA_v2_ptds,
+        cuMemcpyAtoH_v2,
+        cuMemcpyAtoH_v2_ptds,
+        cuMemcpyAtoA_v2,
+        cuMemcpyAtoA_v2_ptds,
+        cuMemcpy2D_v2,
+        cuMemcpy2D_v2_ptds,
+        cuMemcpy2DUnaligned_v2,
+        cuMemcpy2DUnaligned_v2_ptds,
+        cuMemcpy3D_v2,
+        cuMemcpy3D_v2_ptds,
+        cuMemcpy3DPeer,
+        cuMemcpy3DPeer_ptds,
+        cuMemcpyAsync,
+        cuMemcpyAsync_ptsz,
+        cuMemcpyPeerAsync,
+        cuMemcpyPeerAsync_ptsz,
+        cuMemcpyHtoDAsync_v2,
+        cuMemcpyHtoDAsync_v2_ptsz,
+        cuMemcpyDtoHAsync_v2,
+        cuMemcpyDtoHAsync_v2_ptsz,
+        cuMemcpyDtoDAsync_v2,
+        cuMemcpyDtoDAsync_v2_ptsz,
+        cuMemcpyHtoAAsync_v2,
+        cuMemcpyHtoAAsync_v2_ptsz,
+        cuMemcpyAtoHAsync_v2,
+        cuMemcpyAtoHAsync_v2_ptsz,
+        cuMemcpy2DAsync_v2,
+        cuMemcpy2DAsync_v2_ptsz,
+        cuMemcpy3DAsync_v2,
+        cuMemcpy3DAsync_v2_ptsz,
+        cuMemcpy3DPeerAsync,
+        cuMemcpy3DPeerAsync_ptsz,
+        cuMemsetD8_v2,
+        cuMemsetD8_v2_ptds,
+        cuMemsetD16_v2,
+        cuMemsetD16_v2_ptds,
+        cuMemsetD32_v2,
+        cuMemsetD32_v2_ptds,
+        cuMemsetD2D8_v2,
+        cuMemsetD2D8_v2_ptds,
+        cuMemsetD2D16_v2,
+        cuMemsetD2D16_v2_ptds,
+        cuMemsetD2D32_v2,
+        cuMemsetD2D32_v2_ptds,
+        cuMemsetD8Async,
+        cuMemsetD8Async_ptsz,
+        cuMemsetD16Async,
+        cuMemsetD16Async_ptsz,
+        cuMemsetD32Async,
+        cuMemsetD32Async_ptsz,
+        cuMemsetD2D8Async,
+        cuMemsetD2D8Async_ptsz,
+        cuMemsetD2D16Async,
+        cuMemsetD2D16Async_ptsz,
+        cuMemsetD2D32Async,
+        cuMemsetD2D32Async_ptsz
     ]
 );
 
 mod dark_api;
+mod filter;
 mod format;
 mod log;
 #[cfg_attr(windows, path = "os_win.rs")]
@@ -98,8 +194,11 @@ mod log;
 mod os;
 mod profiler;
 mod side_by_side;
+pub mod structured_log;
 mod trace;
 
+pub use os::LIBCUDA_DEFAULT_PATH;
+
 lazy_static! {
     static ref GLOBAL_STATE: Mutex<GlobalState> = Mutex::new(GlobalState::new());
 }
@@ -152,12 +251,13 @@ struct GlobalDelayedState {
     cuda_state: trace::StateTracker,
     pub(crate) side_by_side: Option<side_by_side::SideBySide>,
     pub(crate) profiler: Option<profiler::Profiler>,
+    filter: filter::Filter,
 }
 
 impl GlobalDelayedState {
     fn new<'a>(
         func: &'static str,
-        arguments_writer: Box<dyn FnMut(&mut dyn std::io::Write) -> std::io::Result<()>>,
+        arguments_writer: Box<dyn FnMut(&mut format::ArgumentsWriter) -> std::io::Result<()>>,
         factory: &'a mut log::Factory,
     ) -> (LateInit<Self>, log::FunctionLogger<'a>) {
         let (mut fn_logger, settings) =
@@ -201,12 +301,14 @@ impl GlobalDelayedState {
             })
             .flatten();
         let profiler = profiler::Profiler::new(&settings, &mut libcuda, &mut fn_logger);
+        let filter = filter::Filter::new(&settings.filter);
         let delayed_state = GlobalDelayedState {
             _settings: settings,
             libcuda,
             cuda_state,
             side_by_side,
             profiler,
+            filter,
         };
         (LateInit::Success(delayed_state), fn_logger)
     }
@@ -221,6 +323,8 @@ struct Settings {
     side_by_side_skip_kernel: Option<String>,
     side_by_side_dump_threshold: Option<f32>,
     profiler_output: Option<String>,
+    log_format: structured_log::LogFormat,
+    filter: filter::FilterConfig,
 }
 
 impl Settings {
@@ -249,6 +353,10 @@ impl Settings {
         let side_by_side_dump_threshold =
             parse_env_var::<f32, _>("ZLUDA_SIDE_BY_DUMP_THRESHOLD", &mut report_err);
         let profiler_output = parse_env_var::<String, _>("ZLUDA_PROFILER_OUTPUT", &mut report_err);
+        let log_format =
+            parse_env_var::<structured_log::LogFormat, _>("ZLUDA_LOG_FORMAT", &mut report_err)
+                .unwrap_or(structured_log::LogFormat::Text);
+        let filter = filter::FilterConfig::read(&mut report_err);
         Settings {
             dump_dir,
             log_enabled,
@@ -258,6 +366,8 @@ impl Settings {
             side_by_side_skip_kernel,
             side_by_side_dump_threshold,
             profiler_output,
+            log_format,
+            filter,
         }
     }
 
@@ -313,7 +423,7 @@ where
 fn handle_cuda_function_call(
     func: &'static str,
     original_cuda_fn: impl FnOnce(&mut CudaDynamicFns) -> Option<CUresult>,
-    arguments_writer: Box<dyn FnMut(&mut dyn std::io::Write) -> std::io::Result<()>>,
+    arguments_writer: Box<dyn FnMut(&mut format::ArgumentsWriter) -> std::io::Result<()>>,
 ) -> CUresult {
     handle_cuda_function_call_with_probes(
         func,
@@ -328,7 +438,7 @@ fn handle_cuda_function_call_with_probes<T, PreFn, PostFn>(
     func: &'static str,
     pre_probe: PreFn,
     original_cuda_fn: impl FnOnce(&mut CudaDynamicFns) -> Option<CUresult>,
-    arguments_writer: Box<dyn FnMut(&mut dyn std::io::Write) -> std::io::Result<()>>,
+    arguments_writer: Box<dyn FnMut(&mut format::ArgumentsWriter) -> std::io::Result<()>>,
     post_probe: PostFn,
 ) -> CUresult
 where
@@ -355,6 +465,9 @@ where
             (logger, global_state.delayed_state.as_mut().unwrap())
         }
     };
+    if !delayed_state.filter.should_log(func) {
+        logger.set_quiet();
+    }
     let pre_result = pre_probe(&mut logger, delayed_state);
     let maybe_cu_result = {
         let task = delayed_state.profiler.as_ref().map(|p| p.record_task(func));
@@ -613,36 +726,211 @@ pub(crate) fn cuLaunchKernel_Pre(
     fn_logger: &mut log::FunctionLogger,
     state: &mut GlobalDelayedState,
 ) -> (Option<side_by_side::HostArguments>, Option<CUevent>) {
-    let side_by_side_args = unsafe {
-        side_by_side::pre_kernel_launch(
-            &mut state.libcuda,
-            &mut state.cuda_state,
-            &mut state.side_by_side,
-            fn_logger,
-            f,
-            stream,
-            kernelParams,
-            extra,
-        )
+    kernel_launch_pre(f, stream, kernelParams, extra, fn_logger, state)
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuLaunchKernel_Post(
+    f: CUfunction,
+    gridDimX: ::std::os::raw::c_uint,
+    gridDimY: ::std::os::raw::c_uint,
+    gridDimZ: ::std::os::raw::c_uint,
+    blockDimX: ::std::os::raw::c_uint,
+    blockDimY: ::std::os::raw::c_uint,
+    blockDimZ: ::std::os::raw::c_uint,
+    sharedMemBytes: ::std::os::raw::c_uint,
+    stream: CUstream,
+    kernelParams: *mut *mut ::std::os::raw::c_void,
+    extra: *mut *mut ::std::os::raw::c_void,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    pre_result: (Option<side_by_side::HostArguments>, Option<CUevent>),
+    result: CUresult,
+) {
+    let launch_params = trace::KernelLaunchParams {
+        gridDimX,
+        gridDimY,
+        gridDimZ,
+        blockDimX,
+        blockDimY,
+        blockDimZ,
+        sharedMemBytes,
     };
-    let start_event = if state.profiler.is_some() {
-        fn_logger.log_unwrap(record_event(stream, &mut state.libcuda))
-    } else {
-        None
+    kernel_launch_post(
+        f,
+        launch_params,
+        false,
+        stream,
+        kernelParams,
+        extra,
+        fn_logger,
+        state,
+        pre_result,
+        result,
+    )
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuLaunchKernel_ptsz_Pre(
+    f: CUfunction,
+    gridDimX: ::std::os::raw::c_uint,
+    gridDimY: ::std::os::raw::c_uint,
+    gridDimZ: ::std::os::raw::c_uint,
+    blockDimX: ::std::os::raw::c_uint,
+    blockDimY: ::std::os::raw::c_uint,
+    blockDimZ: ::std::os::raw::c_uint,
+    sharedMemBytes: ::std::os::raw::c_uint,
+    stream: CUstream,
+    kernelParams: *mut *mut ::std::os::raw::c_void,
+    extra: *mut *mut ::std::os::raw::c_void,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+) -> (Option<side_by_side::HostArguments>, Option<CUevent>) {
+    cuLaunchKernel_Pre(
+        f,
+        gridDimX,
+        gridDimY,
+        gridDimZ,
+        blockDimX,
+        blockDimY,
+        blockDimZ,
+        sharedMemBytes,
+        stream,
+        kernelParams,
+        extra,
+        fn_logger,
+        state,
+    )
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuLaunchKernel_ptsz_Post(
+    f: CUfunction,
+    gridDimX: ::std::os::raw::c_uint,
+    gridDimY: ::std::os::raw::c_uint,
+    gridDimZ: ::std::os::raw::c_uint,
+    blockDimX: ::std::os::raw::c_uint,
+    blockDimY: ::std::os::raw::c_uint,
+    blockDimZ: ::std::os::raw::c_uint,
+    sharedMemBytes: ::std::os::raw::c_uint,
+    stream: CUstream,
+    kernelParams: *mut *mut ::std::os::raw::c_void,
+    extra: *mut *mut ::std::os::raw::c_void,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    pre_result: (Option<side_by_side::HostArguments>, Option<CUevent>),
+    result: CUresult,
+) {
+    cuLaunchKernel_Post(
+        f,
+        gridDimX,
+        gridDimY,
+        gridDimZ,
+        blockDimX,
+        blockDimY,
+        blockDimZ,
+        sharedMemBytes,
+        stream,
+        kernelParams,
+        extra,
+        fn_logger,
+        state,
+        pre_result,
+        result,
+    )
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuLaunchCooperativeKernel_Pre(
+    f: CUfunction,
+    _gridDimX: ::std::os::raw::c_uint,
+    _gridDimY: ::std::os::raw::c_uint,
+    _gridDimZ: ::std::os::raw::c_uint,
+    _blockDimX: ::std::os::raw::c_uint,
+    _blockDimY: ::std::os::raw::c_uint,
+    _blockDimZ: ::std::os::raw::c_uint,
+    _sharedMemBytes: ::std::os::raw::c_uint,
+    stream: CUstream,
+    kernelParams: *mut *mut ::std::os::raw::c_void,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+) -> (Option<side_by_side::HostArguments>, Option<CUevent>) {
+    kernel_launch_pre(f, stream, kernelParams, ptr::null_mut(), fn_logger, state)
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuLaunchCooperativeKernel_Post(
+    f: CUfunction,
+    gridDimX: ::std::os::raw::c_uint,
+    gridDimY: ::std::os::raw::c_uint,
+    gridDimZ: ::std::os::raw::c_uint,
+    blockDimX: ::std::os::raw::c_uint,
+    blockDimY: ::std::os::raw::c_uint,
+    blockDimZ: ::std::os::raw::c_uint,
+    sharedMemBytes: ::std::os::raw::c_uint,
+    stream: CUstream,
+    kernelParams: *mut *mut ::std::os::raw::c_void,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    pre_result: (Option<side_by_side::HostArguments>, Option<CUevent>),
+    result: CUresult,
+) {
+    let launch_params = trace::KernelLaunchParams {
+        gridDimX,
+        gridDimY,
+        gridDimZ,
+        blockDimX,
+        blockDimY,
+        blockDimZ,
+        sharedMemBytes,
     };
-    (side_by_side_args, start_event)
+    kernel_launch_post(
+        f,
+        launch_params,
+        true,
+        stream,
+        kernelParams,
+        ptr::null_mut(),
+        fn_logger,
+        state,
+        pre_result,
+        result,
+    )
 }
 
-// TODO: stop leaking CUevent on failure
-fn record_event(stream: CUstream, libcuda: &mut CudaDynamicFns) -> Result<CUevent, LogEntry> {
-    let mut event = ptr::null_mut();
-    cuda_call!(libcuda.cuEventCreate(&mut event, 0));
-    cuda_call!(libcuda.cuEventRecord(event, stream));
-    Ok(event)
+#[allow(non_snake_case)]
+pub(crate) fn cuLaunchCooperativeKernel_ptsz_Pre(
+    f: CUfunction,
+    gridDimX: ::std::os::raw::c_uint,
+    gridDimY: ::std::os::raw::c_uint,
+    gridDimZ: ::std::os::raw::c_uint,
+    blockDimX: ::std::os::raw::c_uint,
+    blockDimY: ::std::os::raw::c_uint,
+    blockDimZ: ::std::os::raw::c_uint,
+    sharedMemBytes: ::std::os::raw::c_uint,
+    stream: CUstream,
+    kernelParams: *mut *mut ::std::os::raw::c_void,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+) -> (Option<side_by_side::HostArguments>, Option<CUevent>) {
+    cuLaunchCooperativeKernel_Pre(
+        f,
+        gridDimX,
+        gridDimY,
+        gridDimZ,
+        blockDimX,
+        blockDimY,
+        blockDimZ,
+        sharedMemBytes,
+        stream,
+        kernelParams,
+        fn_logger,
+        state,
+    )
 }
 
 #[allow(non_snake_case)]
-pub(crate) fn cuLaunchKernel_Post(
+pub(crate) fn cuLaunchCooperativeKernel_ptsz_Post(
     f: CUfunction,
     gridDimX: ::std::os::raw::c_uint,
     gridDimY: ::std::os::raw::c_uint,
@@ -653,51 +941,1271 @@ pub(crate) fn cuLaunchKernel_Post(
     sharedMemBytes: ::std::os::raw::c_uint,
     stream: CUstream,
     kernelParams: *mut *mut ::std::os::raw::c_void,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    pre_result: (Option<side_by_side::HostArguments>, Option<CUevent>),
+    result: CUresult,
+) {
+    cuLaunchCooperativeKernel_Post(
+        f,
+        gridDimX,
+        gridDimY,
+        gridDimZ,
+        blockDimX,
+        blockDimY,
+        blockDimZ,
+        sharedMemBytes,
+        stream,
+        kernelParams,
+        fn_logger,
+        state,
+        pre_result,
+        result,
+    )
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuLaunchKernelEx_Pre(
+    config: *const CUlaunchConfig,
+    f: CUfunction,
+    kernelParams: *mut *mut ::std::os::raw::c_void,
+    extra: *mut *mut ::std::os::raw::c_void,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+) -> (Option<side_by_side::HostArguments>, Option<CUevent>) {
+    if config == ptr::null() {
+        return (None, None);
+    }
+    let stream = unsafe { *config }.hStream;
+    kernel_launch_pre(f, stream, kernelParams, extra, fn_logger, state)
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuLaunchKernelEx_Post(
+    config: *const CUlaunchConfig,
+    f: CUfunction,
+    kernelParams: *mut *mut ::std::os::raw::c_void,
     extra: *mut *mut ::std::os::raw::c_void,
     fn_logger: &mut log::FunctionLogger,
     state: &mut GlobalDelayedState,
     pre_result: (Option<side_by_side::HostArguments>, Option<CUevent>),
     result: CUresult,
 ) {
-    if result != CUresult::CUDA_SUCCESS {
+    if config == ptr::null() {
         return;
     }
-    let (side_by_side_args, start_event) = pre_result;
-    if let Some(start_event) = start_event {
-        if let Some(end_event) = fn_logger.log_unwrap(record_event(stream, &mut state.libcuda)) {
-            let func_name = match state.cuda_state.functions.get(&f) {
-                Some(recorded_func) => profiler::FunctionName::Resolved(recorded_func.name.clone()),
-                None => profiler::FunctionName::Unresolved(f),
-            };
-            state.profiler.as_ref().unwrap().record_kernel(
+    let config = unsafe { *config };
+    let launch_params = trace::KernelLaunchParams {
+        gridDimX: config.gridDimX,
+        gridDimY: config.gridDimY,
+        gridDimZ: config.gridDimZ,
+        blockDimX: config.blockDimX,
+        blockDimY: config.blockDimY,
+        blockDimZ: config.blockDimZ,
+        sharedMemBytes: config.sharedMemBytes,
+    };
+    let attributes = if config.attrs == ptr::null_mut() {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(config.attrs, config.numAttrs as usize) }
+    };
+    let cooperative = attributes.iter().any(|attribute| {
+        attribute.id == CUlaunchAttributeID::CU_LAUNCH_ATTRIBUTE_COOPERATIVE
+            && unsafe { attribute.value.cooperative } != 0
+    });
+    kernel_launch_post(
+        f,
+        launch_params,
+        cooperative,
+        config.hStream,
+        kernelParams,
+        extra,
+        fn_logger,
+        state,
+        pre_result,
+        result,
+    )
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuLaunchKernelEx_ptsz_Pre(
+    config: *const CUlaunchConfig,
+    f: CUfunction,
+    kernelParams: *mut *mut ::std::os::raw::c_void,
+    extra: *mut *mut ::std::os::raw::c_void,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+) -> (Option<side_by_side::HostArguments>, Option<CUevent>) {
+    cuLaunchKernelEx_Pre(config, f, kernelParams, extra, fn_logger, state)
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuLaunchKernelEx_ptsz_Post(
+    config: *const CUlaunchConfig,
+    f: CUfunction,
+    kernelParams: *mut *mut ::std::os::raw::c_void,
+    extra: *mut *mut ::std::os::raw::c_void,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    pre_result: (Option<side_by_side::HostArguments>, Option<CUevent>),
+    result: CUresult,
+) {
+    cuLaunchKernelEx_Post(
+        config,
+        f,
+        kernelParams,
+        extra,
+        fn_logger,
+        state,
+        pre_result,
+        result,
+    )
+}
+
+fn kernel_launch_pre(
+    f: CUfunction,
+    stream: CUstream,
+    kernel_params: *mut *mut ::std::os::raw::c_void,
+    extra: *mut *mut ::std::os::raw::c_void,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+) -> (Option<side_by_side::HostArguments>, Option<CUevent>) {
+    let kernel_name = state
+        .cuda_state
+        .functions
+        .get(&f)
+        .map(|recorded_func| recorded_func.name.as_str());
+    let side_by_side_args = if state.filter.should_dump_launch(kernel_name) {
+        unsafe {
+            side_by_side::pre_kernel_launch(
+                &mut state.libcuda,
+                &mut state.cuda_state,
+                &mut state.side_by_side,
+                fn_logger,
+                f,
                 stream,
-                func_name,
-                start_event,
-                end_event,
-            );
+                kernel_params,
+                extra,
+            )
         }
+    } else {
+        None
+    };
+    let start_event = profiler_start_event(stream, fn_logger, state);
+    (side_by_side_args, start_event)
+}
+
+// Device operations are timed by the profiler with a pair of events recorded
+// around them. We skip streams that are being captured, recording an event
+// there would become a part of the graph
+fn profiler_start_event(
+    stream: CUstream,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+) -> Option<CUevent> {
+    if state.profiler.is_none() {
+        return None;
     }
-    unsafe {
-        side_by_side::post_kernel_launch(
-            &mut state.libcuda,
-            &mut state.cuda_state,
-            &mut state.side_by_side,
-            fn_logger,
-            side_by_side_args,
-            f,
-            gridDimX,
-            gridDimY,
-            gridDimZ,
-            blockDimX,
-            blockDimY,
-            blockDimZ,
-            sharedMemBytes,
-            stream,
-            kernelParams,
-            extra,
-        )
+    match side_by_side::is_capturing(&mut state.libcuda, stream) {
+        Ok(false) => fn_logger.log_unwrap(record_event(stream, &mut state.libcuda)),
+        _ => None,
+    }
+}
+
+fn profiler_end_event(
+    stream: CUstream,
+    name: profiler::OperationName,
+    category: profiler::Category,
+    start_event: CUevent,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    result: CUresult,
+) {
+    if result != CUresult::CUDA_SUCCESS {
+        state.libcuda.cuEventDestroy_v2(start_event);
+        return;
+    }
+    let end_event = match fn_logger.log_unwrap(record_event(stream, &mut state.libcuda)) {
+        Some(end_event) => end_event,
+        None => {
+            state.libcuda.cuEventDestroy_v2(start_event);
+            return;
+        }
+    };
+    let mut context = ptr::null_mut();
+    state.libcuda.cuStreamGetCtx(stream, &mut context);
+    state.profiler.as_ref().unwrap().record_device_operation(
+        context,
+        stream,
+        name,
+        category,
+        start_event,
+        end_event,
+    );
+}
+
+fn record_event(stream: CUstream, libcuda: &mut CudaDynamicFns) -> Result<CUevent, LogEntry> {
+    let mut event = ptr::null_mut();
+    cuda_call!(libcuda.cuEventCreate(&mut event, 0));
+    match libcuda.cuEventRecord(event, stream) {
+        Some(CUresult::CUDA_SUCCESS) => Ok(event),
+        result => {
+            libcuda.cuEventDestroy_v2(event);
+            Err(match result {
+                Some(error) => LogEntry::CudaError(error),
+                None => LogEntry::NoCudaFunction(std::borrow::Cow::Borrowed("cuEventRecord")),
+            })
+        }
+    }
+}
+
+// CU_STREAM_PER_THREAD, synchronous functions with _ptds suffix use it
+const CU_STREAM_PER_THREAD: CUstream = 2 as CUstream;
+
+// Memory copies and memsets are only timed by the profiler, so all their
+// probes are the same
+macro_rules! memory_operation_probes {
+    ($(
+        $category:ident $fn_name:ident ( $($arg_id:ident : $arg_type:ty),* $(,)? ) => $stream:expr;
+    )*) => {
+        $(
+            paste! {
+                #[allow(non_snake_case, unused_variables)]
+                pub(crate) fn [<$fn_name _Pre>](
+                    $($arg_id: $arg_type,)*
+                    fn_logger: &mut log::FunctionLogger,
+                    state: &mut GlobalDelayedState,
+                ) -> Option<CUevent> {
+                    profiler_start_event($stream, fn_logger, state)
+                }
+
+                #[allow(non_snake_case, unused_variables)]
+                pub(crate) fn [<$fn_name _Post>](
+                    $($arg_id: $arg_type,)*
+                    fn_logger: &mut log::FunctionLogger,
+                    state: &mut GlobalDelayedState,
+                    start_event: Option<CUevent>,
+                    result: CUresult,
+                ) {
+                    if let Some(start_event) = start_event {
+                        profiler_end_event(
+                            $stream,
+                            profiler::OperationName::Function(stringify!($fn_name)),
+                            profiler::Category::$category,
+                            start_event,
+                            fn_logger,
+                            state,
+                            result,
+                        )
+                    }
+                }
+            }
+        )*
+    };
+}
+
+memory_operation_probes! {
+    Memcpy cuMemcpy(dst: CUdeviceptr, src: CUdeviceptr, ByteCount: usize) => ptr::null_mut();
+    Memcpy cuMemcpy_ptds(
+        dst: CUdeviceptr,
+        src: CUdeviceptr,
+        ByteCount: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memcpy cuMemcpyPeer(
+        dstDevice: CUdeviceptr,
+        dstContext: CUcontext,
+        srcDevice: CUdeviceptr,
+        srcContext: CUcontext,
+        ByteCount: usize,
+    ) => ptr::null_mut();
+    Memcpy cuMemcpyPeer_ptds(
+        dstDevice: CUdeviceptr,
+        dstContext: CUcontext,
+        srcDevice: CUdeviceptr,
+        srcContext: CUcontext,
+        ByteCount: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memcpy cuMemcpyHtoD_v2(
+        dstDevice: CUdeviceptr,
+        srcHost: *const ::std::os::raw::c_void,
+        ByteCount: usize,
+    ) => ptr::null_mut();
+    Memcpy cuMemcpyHtoD_v2_ptds(
+        dstDevice: CUdeviceptr,
+        srcHost: *const ::std::os::raw::c_void,
+        ByteCount: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memcpy cuMemcpyDtoH_v2(
+        dstHost: *mut ::std::os::raw::c_void,
+        srcDevice: CUdeviceptr,
+        ByteCount: usize,
+    ) => ptr::null_mut();
+    Memcpy cuMemcpyDtoH_v2_ptds(
+        dstHost: *mut ::std::os::raw::c_void,
+        srcDevice: CUdeviceptr,
+        ByteCount: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memcpy cuMemcpyDtoD_v2(
+        dstDevice: CUdeviceptr,
+        srcDevice: CUdeviceptr,
+        ByteCount: usize,
+    ) => ptr::null_mut();
+    Memcpy cuMemcpyDtoD_v2_ptds(
+        dstDevice: CUdeviceptr,
+        srcDevice: CUdeviceptr,
+        ByteCount: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memcpy cuMemcpyDtoA_v2(
+        dstArray: CUarray,
+        dstOffset: usize,
+        srcDevice: CUdeviceptr,
+        ByteCount: usize,
+    ) => ptr::null_mut();
+    Memcpy cuMemcpyDtoA_v2_ptds(
+        dstArray: CUarray,
+        dstOffset: usize,
+        srcDevice: CUdeviceptr,
+        ByteCount: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memcpy cuMemcpyAtoD_v2(
+        dstDevice: CUdeviceptr,
+        srcArray: CUarray,
+        srcOffset: usize,
+        ByteCount: usize,
+    ) => ptr::null_mut();
+    Memcpy cuMemcpyAtoD_v2_ptds(
+        dstDevice: CUdeviceptr,
+        srcArray: CUarray,
+        srcOffset: usize,
+        ByteCount: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memcpy cuMemcpyHtoA_v2(
+        dstArray: CUarray,
+        dstOffset: usize,
+        srcHost: *const ::std::os::raw::c_void,
+        ByteCount: usize,
+    ) => ptr::null_mut();
+    Memcpy cuMemcpyHtoA_v2_ptds(
+        dstArray: CUarray,
+        dstOffset: usize,
+        srcHost: *const ::std::os::raw::c_void,
+        ByteCount: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memcpy cuMemcpyAtoH_v2(
+        dstHost: *mut ::std::os::raw::c_void,
+        srcArray: CUarray,
+        srcOffset: usize,
+        ByteCount: usize,
+    ) => ptr::null_mut();
+    Memcpy cuMemcpyAtoH_v2_ptds(
+        dstHost: *mut ::std::os::raw::c_void,
+        srcArray: CUarray,
+        srcOffset: usize,
+        ByteCount: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memcpy cuMemcpyAtoA_v2(
+        dstArray: CUarray,
+        dstOffset: usize,
+        srcArray: CUarray,
+        srcOffset: usize,
+        ByteCount: usize,
+    ) => ptr::null_mut();
+    Memcpy cuMemcpyAtoA_v2_ptds(
+        dstArray: CUarray,
+        dstOffset: usize,
+        srcArray: CUarray,
+        srcOffset: usize,
+        ByteCount: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memcpy cuMemcpy2D_v2(pCopy: *const CUDA_MEMCPY2D) => ptr::null_mut();
+    Memcpy cuMemcpy2D_v2_ptds(pCopy: *const CUDA_MEMCPY2D) => CU_STREAM_PER_THREAD;
+    Memcpy cuMemcpy2DUnaligned_v2(pCopy: *const CUDA_MEMCPY2D) => ptr::null_mut();
+    Memcpy cuMemcpy2DUnaligned_v2_ptds(pCopy: *const CUDA_MEMCPY2D) => CU_STREAM_PER_THREAD;
+    Memcpy cuMemcpy3D_v2(pCopy: *const CUDA_MEMCPY3D) => ptr::null_mut();
+    Memcpy cuMemcpy3D_v2_ptds(pCopy: *const CUDA_MEMCPY3D) => CU_STREAM_PER_THREAD;
+    Memcpy cuMemcpy3DPeer(pCopy: *const CUDA_MEMCPY3D_PEER) => ptr::null_mut();
+    Memcpy cuMemcpy3DPeer_ptds(pCopy: *const CUDA_MEMCPY3D_PEER) => CU_STREAM_PER_THREAD;
+    Memcpy cuMemcpyAsync(
+        dst: CUdeviceptr,
+        src: CUdeviceptr,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memcpy cuMemcpyAsync_ptsz(
+        dst: CUdeviceptr,
+        src: CUdeviceptr,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memcpy cuMemcpyPeerAsync(
+        dstDevice: CUdeviceptr,
+        dstContext: CUcontext,
+        srcDevice: CUdeviceptr,
+        srcContext: CUcontext,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memcpy cuMemcpyPeerAsync_ptsz(
+        dstDevice: CUdeviceptr,
+        dstContext: CUcontext,
+        srcDevice: CUdeviceptr,
+        srcContext: CUcontext,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memcpy cuMemcpyHtoDAsync_v2(
+        dstDevice: CUdeviceptr,
+        srcHost: *const ::std::os::raw::c_void,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memcpy cuMemcpyHtoDAsync_v2_ptsz(
+        dstDevice: CUdeviceptr,
+        srcHost: *const ::std::os::raw::c_void,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memcpy cuMemcpyDtoHAsync_v2(
+        dstHost: *mut ::std::os::raw::c_void,
+        srcDevice: CUdeviceptr,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memcpy cuMemcpyDtoHAsync_v2_ptsz(
+        dstHost: *mut ::std::os::raw::c_void,
+        srcDevice: CUdeviceptr,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memcpy cuMemcpyDtoDAsync_v2(
+        dstDevice: CUdeviceptr,
+        srcDevice: CUdeviceptr,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memcpy cuMemcpyDtoDAsync_v2_ptsz(
+        dstDevice: CUdeviceptr,
+        srcDevice: CUdeviceptr,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memcpy cuMemcpyHtoAAsync_v2(
+        dstArray: CUarray,
+        dstOffset: usize,
+        srcHost: *const ::std::os::raw::c_void,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memcpy cuMemcpyHtoAAsync_v2_ptsz(
+        dstArray: CUarray,
+        dstOffset: usize,
+        srcHost: *const ::std::os::raw::c_void,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memcpy cuMemcpyAtoHAsync_v2(
+        dstHost: *mut ::std::os::raw::c_void,
+        srcArray: CUarray,
+        srcOffset: usize,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memcpy cuMemcpyAtoHAsync_v2_ptsz(
+        dstHost: *mut ::std::os::raw::c_void,
+        srcArray: CUarray,
+        srcOffset: usize,
+        ByteCount: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memcpy cuMemcpy2DAsync_v2(pCopy: *const CUDA_MEMCPY2D, hStream: CUstream) => hStream;
+    Memcpy cuMemcpy2DAsync_v2_ptsz(pCopy: *const CUDA_MEMCPY2D, hStream: CUstream) => hStream;
+    Memcpy cuMemcpy3DAsync_v2(pCopy: *const CUDA_MEMCPY3D, hStream: CUstream) => hStream;
+    Memcpy cuMemcpy3DAsync_v2_ptsz(pCopy: *const CUDA_MEMCPY3D, hStream: CUstream) => hStream;
+    Memcpy cuMemcpy3DPeerAsync(pCopy: *const CUDA_MEMCPY3D_PEER, hStream: CUstream) => hStream;
+    Memcpy cuMemcpy3DPeerAsync_ptsz(pCopy: *const CUDA_MEMCPY3D_PEER, hStream: CUstream) => hStream;
+    Memset cuMemsetD8_v2(
+        dstDevice: CUdeviceptr,
+        uc: ::std::os::raw::c_uchar,
+        N: usize,
+    ) => ptr::null_mut();
+    Memset cuMemsetD8_v2_ptds(
+        dstDevice: CUdeviceptr,
+        uc: ::std::os::raw::c_uchar,
+        N: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memset cuMemsetD16_v2(
+        dstDevice: CUdeviceptr,
+        us: ::std::os::raw::c_ushort,
+        N: usize,
+    ) => ptr::null_mut();
+    Memset cuMemsetD16_v2_ptds(
+        dstDevice: CUdeviceptr,
+        us: ::std::os::raw::c_ushort,
+        N: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memset cuMemsetD32_v2(
+        dstDevice: CUdeviceptr,
+        ui: ::std::os::raw::c_uint,
+        N: usize,
+    ) => ptr::null_mut();
+    Memset cuMemsetD32_v2_ptds(
+        dstDevice: CUdeviceptr,
+        ui: ::std::os::raw::c_uint,
+        N: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memset cuMemsetD2D8_v2(
+        dstDevice: CUdeviceptr,
+        dstPitch: usize,
+        uc: ::std::os::raw::c_uchar,
+        Width: usize,
+        Height: usize,
+    ) => ptr::null_mut();
+    Memset cuMemsetD2D8_v2_ptds(
+        dstDevice: CUdeviceptr,
+        dstPitch: usize,
+        uc: ::std::os::raw::c_uchar,
+        Width: usize,
+        Height: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memset cuMemsetD2D16_v2(
+        dstDevice: CUdeviceptr,
+        dstPitch: usize,
+        us: ::std::os::raw::c_ushort,
+        Width: usize,
+        Height: usize,
+    ) => ptr::null_mut();
+    Memset cuMemsetD2D16_v2_ptds(
+        dstDevice: CUdeviceptr,
+        dstPitch: usize,
+        us: ::std::os::raw::c_ushort,
+        Width: usize,
+        Height: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memset cuMemsetD2D32_v2(
+        dstDevice: CUdeviceptr,
+        dstPitch: usize,
+        ui: ::std::os::raw::c_uint,
+        Width: usize,
+        Height: usize,
+    ) => ptr::null_mut();
+    Memset cuMemsetD2D32_v2_ptds(
+        dstDevice: CUdeviceptr,
+        dstPitch: usize,
+        ui: ::std::os::raw::c_uint,
+        Width: usize,
+        Height: usize,
+    ) => CU_STREAM_PER_THREAD;
+    Memset cuMemsetD8Async(
+        dstDevice: CUdeviceptr,
+        uc: ::std::os::raw::c_uchar,
+        N: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memset cuMemsetD8Async_ptsz(
+        dstDevice: CUdeviceptr,
+        uc: ::std::os::raw::c_uchar,
+        N: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memset cuMemsetD16Async(
+        dstDevice: CUdeviceptr,
+        us: ::std::os::raw::c_ushort,
+        N: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memset cuMemsetD16Async_ptsz(
+        dstDevice: CUdeviceptr,
+        us: ::std::os::raw::c_ushort,
+        N: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memset cuMemsetD32Async(
+        dstDevice: CUdeviceptr,
+        ui: ::std::os::raw::c_uint,
+        N: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memset cuMemsetD32Async_ptsz(
+        dstDevice: CUdeviceptr,
+        ui: ::std::os::raw::c_uint,
+        N: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memset cuMemsetD2D8Async(
+        dstDevice: CUdeviceptr,
+        dstPitch: usize,
+        uc: ::std::os::raw::c_uchar,
+        Width: usize,
+        Height: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memset cuMemsetD2D8Async_ptsz(
+        dstDevice: CUdeviceptr,
+        dstPitch: usize,
+        uc: ::std::os::raw::c_uchar,
+        Width: usize,
+        Height: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memset cuMemsetD2D16Async(
+        dstDevice: CUdeviceptr,
+        dstPitch: usize,
+        us: ::std::os::raw::c_ushort,
+        Width: usize,
+        Height: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memset cuMemsetD2D16Async_ptsz(
+        dstDevice: CUdeviceptr,
+        dstPitch: usize,
+        us: ::std::os::raw::c_ushort,
+        Width: usize,
+        Height: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memset cuMemsetD2D32Async(
+        dstDevice: CUdeviceptr,
+        dstPitch: usize,
+        ui: ::std::os::raw::c_uint,
+        Width: usize,
+        Height: usize,
+        hStream: CUstream,
+    ) => hStream;
+    Memset cuMemsetD2D32Async_ptsz(
+        dstDevice: CUdeviceptr,
+        dstPitch: usize,
+        ui: ::std::os::raw::c_uint,
+        Width: usize,
+        Height: usize,
+        hStream: CUstream,
+    ) => hStream;
+}
+
+fn kernel_launch_post(
+    f: CUfunction,
+    launch_params: trace::KernelLaunchParams,
+    cooperative: bool,
+    stream: CUstream,
+    kernel_params: *mut *mut ::std::os::raw::c_void,
+    extra: *mut *mut ::std::os::raw::c_void,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    pre_result: (Option<side_by_side::HostArguments>, Option<CUevent>),
+    result: CUresult,
+) {
+    let (side_by_side_args, start_event) = pre_result;
+    if let Some(start_event) = start_event {
+        let func_name = match state.cuda_state.functions.get(&f) {
+            Some(recorded_func) => profiler::OperationName::Resolved(recorded_func.name.clone()),
+            None => profiler::OperationName::Unresolved(f),
+        };
+        profiler_end_event(
+            stream,
+            func_name,
+            profiler::Category::Kernel,
+            start_event,
+            fn_logger,
+            state,
+            result,
+        );
+    }
+    if result != CUresult::CUDA_SUCCESS {
+        return;
+    }
+    unsafe {
+        side_by_side::post_kernel_launch(
+            &mut state.libcuda,
+            &mut state.cuda_state,
+            &mut state.side_by_side,
+            fn_logger,
+            side_by_side_args,
+            f,
+            launch_params,
+            cooperative,
+            stream,
+            kernel_params,
+            extra,
+        )
+    }
+    .unwrap_or_default()
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphLaunch_Pre(
+    hGraphExec: CUgraphExec,
+    hStream: CUstream,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+) -> Option<side_by_side::GraphArguments> {
+    unsafe {
+        side_by_side::pre_graph_launch(
+            &mut state.libcuda,
+            &mut state.cuda_state,
+            &mut state.side_by_side,
+            fn_logger,
+            hGraphExec,
+            hStream,
+        )
+    }
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphLaunch_Post(
+    _hGraphExec: CUgraphExec,
+    hStream: CUstream,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    pre_result: Option<side_by_side::GraphArguments>,
+    result: CUresult,
+) {
+    if result != CUresult::CUDA_SUCCESS {
+        return;
+    }
+    unsafe {
+        side_by_side::post_graph_launch(
+            &mut state.libcuda,
+            &mut state.cuda_state,
+            &mut state.side_by_side,
+            fn_logger,
+            pre_result,
+            hStream,
+        )
+    }
+    .unwrap_or_default()
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphLaunch_ptsz_Pre(
+    hGraphExec: CUgraphExec,
+    hStream: CUstream,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+) -> Option<side_by_side::GraphArguments> {
+    cuGraphLaunch_Pre(hGraphExec, hStream, fn_logger, state)
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphLaunch_ptsz_Post(
+    hGraphExec: CUgraphExec,
+    hStream: CUstream,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    pre_result: Option<side_by_side::GraphArguments>,
+    result: CUresult,
+) {
+    cuGraphLaunch_Post(hGraphExec, hStream, fn_logger, state, pre_result, result)
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphInstantiate_Pre(
+    _phGraphExec: *mut CUgraphExec,
+    _hGraph: CUgraph,
+    _phErrorNode: *mut CUgraphNode,
+    _logBuffer: *mut ::std::os::raw::c_char,
+    _bufferSize: usize,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphInstantiate_Post(
+    phGraphExec: *mut CUgraphExec,
+    hGraph: CUgraph,
+    _phErrorNode: *mut CUgraphNode,
+    _logBuffer: *mut ::std::os::raw::c_char,
+    _bufferSize: usize,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    _pre_result: (),
+    result: CUresult,
+) {
+    if result == CUresult::CUDA_SUCCESS {
+        unsafe {
+            side_by_side::record_graph_exec(
+                &mut state.libcuda,
+                &mut state.cuda_state,
+                &state.side_by_side,
+                fn_logger,
+                *phGraphExec,
+                hGraph,
+            )
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphInstantiate_v2_Pre(
+    _phGraphExec: *mut CUgraphExec,
+    _hGraph: CUgraph,
+    _phErrorNode: *mut CUgraphNode,
+    _logBuffer: *mut ::std::os::raw::c_char,
+    _bufferSize: usize,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphInstantiate_v2_Post(
+    phGraphExec: *mut CUgraphExec,
+    hGraph: CUgraph,
+    phErrorNode: *mut CUgraphNode,
+    logBuffer: *mut ::std::os::raw::c_char,
+    bufferSize: usize,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    pre_result: (),
+    result: CUresult,
+) {
+    cuGraphInstantiate_Post(
+        phGraphExec,
+        hGraph,
+        phErrorNode,
+        logBuffer,
+        bufferSize,
+        fn_logger,
+        state,
+        pre_result,
+        result,
+    )
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphInstantiateWithFlags_Pre(
+    _phGraphExec: *mut CUgraphExec,
+    _hGraph: CUgraph,
+    _flags: ::std::os::raw::c_ulonglong,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphInstantiateWithFlags_Post(
+    phGraphExec: *mut CUgraphExec,
+    hGraph: CUgraph,
+    _flags: ::std::os::raw::c_ulonglong,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    _pre_result: (),
+    result: CUresult,
+) {
+    if result == CUresult::CUDA_SUCCESS {
+        unsafe {
+            side_by_side::record_graph_exec(
+                &mut state.libcuda,
+                &mut state.cuda_state,
+                &state.side_by_side,
+                fn_logger,
+                *phGraphExec,
+                hGraph,
+            )
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphInstantiateWithParams_Pre(
+    _phGraphExec: *mut CUgraphExec,
+    _hGraph: CUgraph,
+    _instantiateParams: *mut CUDA_GRAPH_INSTANTIATE_PARAMS,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphInstantiateWithParams_Post(
+    phGraphExec: *mut CUgraphExec,
+    hGraph: CUgraph,
+    _instantiateParams: *mut CUDA_GRAPH_INSTANTIATE_PARAMS,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    _pre_result: (),
+    result: CUresult,
+) {
+    if result == CUresult::CUDA_SUCCESS {
+        unsafe {
+            side_by_side::record_graph_exec(
+                &mut state.libcuda,
+                &mut state.cuda_state,
+                &state.side_by_side,
+                fn_logger,
+                *phGraphExec,
+                hGraph,
+            )
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphInstantiateWithParams_ptsz_Pre(
+    _phGraphExec: *mut CUgraphExec,
+    _hGraph: CUgraph,
+    _instantiateParams: *mut CUDA_GRAPH_INSTANTIATE_PARAMS,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphInstantiateWithParams_ptsz_Post(
+    phGraphExec: *mut CUgraphExec,
+    hGraph: CUgraph,
+    instantiateParams: *mut CUDA_GRAPH_INSTANTIATE_PARAMS,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    pre_result: (),
+    result: CUresult,
+) {
+    cuGraphInstantiateWithParams_Post(
+        phGraphExec,
+        hGraph,
+        instantiateParams,
+        fn_logger,
+        state,
+        pre_result,
+        result,
+    )
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphExecDestroy_Pre(
+    _hGraphExec: CUgraphExec,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphExecDestroy_Post(
+    hGraphExec: CUgraphExec,
+    _fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    _pre_result: (),
+    result: CUresult,
+) {
+    if result == CUresult::CUDA_SUCCESS {
+        state.cuda_state.remove_graph_exec(hGraphExec);
+    }
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphExecKernelNodeSetParams_Pre(
+    _hGraphExec: CUgraphExec,
+    _hNode: CUgraphNode,
+    _nodeParams: *const CUDA_KERNEL_NODE_PARAMS_v1,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphExecKernelNodeSetParams_Post(
+    hGraphExec: CUgraphExec,
+    hNode: CUgraphNode,
+    nodeParams: *const CUDA_KERNEL_NODE_PARAMS_v1,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    pre_result: (),
+    result: CUresult,
+) {
+    if result != CUresult::CUDA_SUCCESS {
+        return;
+    }
+    let node_params = unsafe { *nodeParams };
+    let node_params = CUDA_KERNEL_NODE_PARAMS {
+        func: node_params.func,
+        gridDimX: node_params.gridDimX,
+        gridDimY: node_params.gridDimY,
+        gridDimZ: node_params.gridDimZ,
+        blockDimX: node_params.blockDimX,
+        blockDimY: node_params.blockDimY,
+        blockDimZ: node_params.blockDimZ,
+        sharedMemBytes: node_params.sharedMemBytes,
+        kernelParams: node_params.kernelParams,
+        extra: node_params.extra,
+        kern: ptr::null_mut(),
+        ctx: ptr::null_mut(),
+    };
+    cuGraphExecKernelNodeSetParams_v2_Post(
+        hGraphExec,
+        hNode,
+        &node_params,
+        fn_logger,
+        state,
+        pre_result,
+        result,
+    )
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphExecKernelNodeSetParams_v2_Pre(
+    _hGraphExec: CUgraphExec,
+    _hNode: CUgraphNode,
+    _nodeParams: *const CUDA_KERNEL_NODE_PARAMS,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphExecKernelNodeSetParams_v2_Post(
+    hGraphExec: CUgraphExec,
+    hNode: CUgraphNode,
+    nodeParams: *const CUDA_KERNEL_NODE_PARAMS,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    _pre_result: (),
+    result: CUresult,
+) {
+    if result == CUresult::CUDA_SUCCESS {
+        unsafe {
+            side_by_side::set_graph_exec_kernel_node_params(
+                &mut state.cuda_state,
+                &state.side_by_side,
+                fn_logger,
+                hGraphExec,
+                hNode,
+                &*nodeParams,
+            )
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphExecMemsetNodeSetParams_Pre(
+    _hGraphExec: CUgraphExec,
+    _hNode: CUgraphNode,
+    _memsetParams: *const CUDA_MEMSET_NODE_PARAMS,
+    _ctx: CUcontext,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphExecMemsetNodeSetParams_Post(
+    hGraphExec: CUgraphExec,
+    hNode: CUgraphNode,
+    memsetParams: *const CUDA_MEMSET_NODE_PARAMS,
+    _ctx: CUcontext,
+    _fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    _pre_result: (),
+    result: CUresult,
+) {
+    if result == CUresult::CUDA_SUCCESS {
+        side_by_side::set_graph_exec_memset_node_params(
+            &mut state.cuda_state,
+            &state.side_by_side,
+            hGraphExec,
+            hNode,
+            unsafe { &*memsetParams },
+        )
+    }
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphExecMemcpyNodeSetParams_Pre(
+    _hGraphExec: CUgraphExec,
+    _hNode: CUgraphNode,
+    _copyParams: *const CUDA_MEMCPY3D,
+    _ctx: CUcontext,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphExecMemcpyNodeSetParams_Post(
+    hGraphExec: CUgraphExec,
+    hNode: CUgraphNode,
+    copyParams: *const CUDA_MEMCPY3D,
+    _ctx: CUcontext,
+    _fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    _pre_result: (),
+    result: CUresult,
+) {
+    if result == CUresult::CUDA_SUCCESS {
+        side_by_side::set_graph_exec_memcpy_node_params(
+            &mut state.cuda_state,
+            &state.side_by_side,
+            hGraphExec,
+            hNode,
+            unsafe { &*copyParams },
+        )
+    }
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphExecUpdate_Pre(
+    _hGraphExec: CUgraphExec,
+    _hGraph: CUgraph,
+    _hErrorNode_out: *mut CUgraphNode,
+    _updateResult_out: *mut CUgraphExecUpdateResult,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphExecUpdate_Post(
+    hGraphExec: CUgraphExec,
+    hGraph: CUgraph,
+    _hErrorNode_out: *mut CUgraphNode,
+    _updateResult_out: *mut CUgraphExecUpdateResult,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    _pre_result: (),
+    result: CUresult,
+) {
+    if result == CUresult::CUDA_SUCCESS {
+        unsafe {
+            side_by_side::update_graph_exec(
+                &mut state.libcuda,
+                &mut state.cuda_state,
+                &state.side_by_side,
+                fn_logger,
+                hGraphExec,
+                hGraph,
+            )
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphExecUpdate_v2_Pre(
+    _hGraphExec: CUgraphExec,
+    _hGraph: CUgraph,
+    _resultInfo: *mut CUgraphExecUpdateResultInfo,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuGraphExecUpdate_v2_Post(
+    hGraphExec: CUgraphExec,
+    hGraph: CUgraph,
+    _resultInfo: *mut CUgraphExecUpdateResultInfo,
+    fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    pre_result: (),
+    result: CUresult,
+) {
+    cuGraphExecUpdate_Post(
+        hGraphExec,
+        hGraph,
+        ptr::null_mut(),
+        ptr::null_mut(),
+        fn_logger,
+        state,
+        pre_result,
+        result,
+    )
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuTexObjectCreate_Pre(
+    _pTexObject: *mut CUtexObject,
+    _pResDesc: *const CUDA_RESOURCE_DESC,
+    _pTexDesc: *const CUDA_TEXTURE_DESC,
+    _pResViewDesc: *const CUDA_RESOURCE_VIEW_DESC,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuTexObjectCreate_Post(
+    pTexObject: *mut CUtexObject,
+    pResDesc: *const CUDA_RESOURCE_DESC,
+    pTexDesc: *const CUDA_TEXTURE_DESC,
+    pResViewDesc: *const CUDA_RESOURCE_VIEW_DESC,
+    _fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    _pre_result: (),
+    result: CUresult,
+) {
+    if result == CUresult::CUDA_SUCCESS {
+        unsafe {
+            state
+                .cuda_state
+                .record_texture_object(*pTexObject, pResDesc, pTexDesc, pResViewDesc)
+        };
+    }
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuTexObjectDestroy_Pre(
+    _texObject: CUtexObject,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuTexObjectDestroy_Post(
+    texObject: CUtexObject,
+    _fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    _pre_result: (),
+    result: CUresult,
+) {
+    if result == CUresult::CUDA_SUCCESS {
+        state.cuda_state.remove_texture_object(texObject);
+    }
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuSurfObjectCreate_Pre(
+    _pSurfObject: *mut CUsurfObject,
+    _pResDesc: *const CUDA_RESOURCE_DESC,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuSurfObjectCreate_Post(
+    pSurfObject: *mut CUsurfObject,
+    pResDesc: *const CUDA_RESOURCE_DESC,
+    _fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    _pre_result: (),
+    result: CUresult,
+) {
+    if result == CUresult::CUDA_SUCCESS {
+        unsafe {
+            state
+                .cuda_state
+                .record_surface_object(*pSurfObject, pResDesc)
+        };
+    }
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuSurfObjectDestroy_Pre(
+    _surfObject: CUsurfObject,
+    _fn_logger: &mut log::FunctionLogger,
+    _state: &mut GlobalDelayedState,
+) {
+}
+
+#[allow(non_snake_case)]
+pub(crate) fn cuSurfObjectDestroy_Post(
+    surfObject: CUsurfObject,
+    _fn_logger: &mut log::FunctionLogger,
+    state: &mut GlobalDelayedState,
+    _pre_result: (),
+    result: CUresult,
+) {
+    if result == CUresult::CUDA_SUCCESS {
+        state.cuda_state.remove_surface_object(surfObject);
     }
-    .unwrap_or_default()
 }
 
 #[allow(non_snake_case)]
@@ -1119,7 +2627,7 @@ impl<T> DynamicFn<T> {
     }
 }
 
-pub(crate) struct CudaDynamicFns {
+pub struct CudaDynamicFns {
     pub(crate) lib_handle: NonNull<::std::ffi::c_void>,
     pub(crate) fn_table: CudaFnTable,
 }
@@ -1144,7 +2652,7 @@ macro_rules! try_get_cuda_function {
 }
 
 impl CudaDynamicFns {
-    pub(crate) unsafe fn load_library(path: &str) -> Option<Self> {
+    pub unsafe fn load_library(path: &str) -> Option<Self> {
         let lib_handle = NonNull::new(os::load_library(path));
         lib_handle.map(|lib_handle| CudaDynamicFns {
             lib_handle,
@@ -1163,7 +2671,7 @@ macro_rules! emit_cuda_fn_table {
         impl CudaDynamicFns {
             $(
                 #[allow(dead_code)]
-                pub(crate) fn $fn_name(&mut self, $($arg_id : $arg_type),*) -> Option<$ret_type> {
+                pub fn $fn_name(&mut self, $($arg_id : $arg_type),*) -> Option<$ret_type> {
                     let func = unsafe { self.fn_table.$fn_name.get(self.lib_handle.as_ptr(), concat!(stringify!($fn_name), "\0").as_bytes()) };
                     func.map(|f| f($($arg_id),*) )
                 }
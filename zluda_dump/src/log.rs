@@ -3,6 +3,8 @@ use super::Settings;
 use crate::format;
 use crate::format::CudaDisplay;
 use crate::parse_env_var;
+use crate::structured_log;
+use crate::structured_log::LogFormat;
 use cuda_types::*;
 use std::borrow::Cow;
 use std::env;
@@ -17,9 +19,10 @@ use std::io::Stderr;
 use std::io::Write;
 use std::path::PathBuf;
 use std::str::Utf8Error;
+use std::time::SystemTime;
 use zluda_dark_api::AnyUInt;
-use zluda_dark_api::FatbinFileKind;
 use zluda_dark_api::DecompressionFailure;
+use zluda_dark_api::FatbinFileKind;
 use zluda_dark_api::UnexpectedFieldError;
 
 const LOG_PREFIX: &[u8] = b"[ZLUDA_DUMP] ";
@@ -33,6 +36,9 @@ pub(crate) struct Factory {
     // This is emitter that "always works" (and if it does not, then we don't
     // care). In addition of normal logs it emits errors from fallible emitter
     infallible_emitter: Box<dyn WriteTrailingZeroAware>,
+    // Replaces file emitter if ZLUDA_LOG_FORMAT selects a machine-readable
+    // format. Infallible emitter keeps writing text
+    structured_emitter: Option<structured_log::Emitter>,
     write_buffer: WriteBuffer,
     // another shared buffer, so we dont't reallocate on every function call
     log_queue: Vec<LogEntry>,
@@ -165,6 +171,7 @@ impl Factory {
         Factory {
             infallible_emitter,
             fallible_emitter: None,
+            structured_emitter: None,
             write_buffer: WriteBuffer::new(),
             log_queue: Vec::new(),
             log_enable,
@@ -174,15 +181,34 @@ impl Factory {
     fn initalize_fallible_emitter(
         settings: &Settings,
     ) -> std::io::Result<Option<Box<dyn WriteTrailingZeroAware>>> {
-        if !settings.log_enabled {
+        if !settings.log_enabled || settings.log_format != LogFormat::Text {
             return Ok(None);
         }
         settings
             .dump_dir
             .as_ref()
             .map(|path| {
-                Ok::<_, std::io::Error>(Box::new(File::create(path.to_path_buf().join("log.txt"))?)
-                    as Box<dyn WriteTrailingZeroAware>)
+                Ok::<_, std::io::Error>(Box::new(File::create(
+                    path.to_path_buf().join(settings.log_format.file_name()),
+                )?) as Box<dyn WriteTrailingZeroAware>)
+            })
+            .transpose()
+    }
+
+    fn initialize_structured_emitter(
+        settings: &Settings,
+    ) -> std::io::Result<Option<structured_log::Emitter>> {
+        if !settings.log_enabled || settings.log_format == LogFormat::Text {
+            return Ok(None);
+        }
+        settings
+            .dump_dir
+            .as_ref()
+            .map(|path| {
+                structured_log::Emitter::new(
+                    &path.join(settings.log_format.file_name()),
+                    settings.log_format,
+                )
             })
             .transpose()
     }
@@ -199,7 +225,7 @@ impl Factory {
     pub(crate) fn get_first_logger_and_init_settings(
         &mut self,
         func: &'static str,
-        arguments_writer: Box<dyn FnMut(&mut dyn std::io::Write) -> std::io::Result<()>>,
+        arguments_writer: Box<dyn FnMut(&mut format::ArgumentsWriter) -> std::io::Result<()>>,
     ) -> (FunctionLogger, Settings) {
         let log_enabled = self.log_enable;
         let mut first_logger = self.get_logger(func, arguments_writer);
@@ -210,6 +236,12 @@ impl Factory {
             }
             Err(err) => first_logger.log(LogEntry::IoError(err)),
         }
+        match Self::initialize_structured_emitter(&settings) {
+            Ok(structured_emitter) => {
+                *first_logger.structured_emitter = structured_emitter;
+            }
+            Err(err) => first_logger.log(LogEntry::IoError(err)),
+        }
         first_logger.write_buffer.init(
             first_logger.fallible_emitter,
             first_logger.infallible_emitter,
@@ -220,16 +252,19 @@ impl Factory {
     pub(crate) fn get_logger(
         &mut self,
         func: &'static str,
-        arguments_writer: Box<dyn FnMut(&mut dyn std::io::Write) -> std::io::Result<()>>,
+        arguments_writer: Box<dyn FnMut(&mut format::ArgumentsWriter) -> std::io::Result<()>>,
     ) -> FunctionLogger {
         FunctionLogger {
             result: None,
             name: CudaFunctionName::Normal(func),
             fallible_emitter: &mut self.fallible_emitter,
             infallible_emitter: &mut self.infallible_emitter,
+            structured_emitter: &mut self.structured_emitter,
+            quiet: false,
             write_buffer: &mut self.write_buffer,
             log_queue: &mut self.log_queue,
             arguments_writer: Some(arguments_writer),
+            start: SystemTime::now(),
         }
     }
 
@@ -237,16 +272,21 @@ impl Factory {
         &mut self,
         guid: CUuuid,
         index: usize,
-        arguments_writer: Option<Box<dyn FnMut(&mut dyn std::io::Write) -> std::io::Result<()>>>,
+        arguments_writer: Option<
+            Box<dyn FnMut(&mut format::ArgumentsWriter) -> std::io::Result<()>>,
+        >,
     ) -> FunctionLogger {
         FunctionLogger {
             result: None,
             name: CudaFunctionName::Dark { guid, index },
             fallible_emitter: &mut self.fallible_emitter,
             infallible_emitter: &mut self.infallible_emitter,
+            structured_emitter: &mut self.structured_emitter,
+            quiet: false,
             write_buffer: &mut self.write_buffer,
             log_queue: &mut self.log_queue,
             arguments_writer,
+            start: SystemTime::now(),
         }
     }
 }
@@ -266,9 +306,14 @@ pub(crate) struct FunctionLogger<'a> {
     name: CudaFunctionName,
     infallible_emitter: &'a mut Box<dyn WriteTrailingZeroAware>,
     fallible_emitter: &'a mut Option<Box<dyn WriteTrailingZeroAware>>,
-    arguments_writer: Option<Box<dyn FnMut(&mut dyn std::io::Write) -> std::io::Result<()>>>,
+    structured_emitter: &'a mut Option<structured_log::Emitter>,
+    // Set for functions filtered out by ZLUDA_LOG_FUNCTIONS, such calls are
+    // logged only if they fail or there's an error or a warning to report
+    quiet: bool,
+    arguments_writer: Option<Box<dyn FnMut(&mut format::ArgumentsWriter) -> std::io::Result<()>>>,
     write_buffer: &'a mut WriteBuffer,
     log_queue: &'a mut Vec<LogEntry>,
+    start: SystemTime,
 }
 
 impl<'a> FunctionLogger<'a> {
@@ -276,6 +321,10 @@ impl<'a> FunctionLogger<'a> {
         self.log_queue.push(l);
     }
 
+    pub(crate) fn set_quiet(&mut self) {
+        self.quiet = true;
+    }
+
     pub(crate) fn log_io_error(&mut self, error: io::Result<()>) {
         if let Err(e) = error {
             self.log_queue.push(LogEntry::IoError(e));
@@ -299,7 +348,9 @@ impl<'a> FunctionLogger<'a> {
         }
     }
 
-    fn flush_log_queue_to_write_buffer(&mut self) {
+    // Returns values of arguments if they are needed by the structured emitter
+    fn flush_log_queue_to_write_buffer(&mut self) -> Option<Vec<(&'static str, Vec<u8>)>> {
+        let mut structured_arguments = None;
         self.write_buffer.start_line();
         match self.name {
             CudaFunctionName::Normal(fn_name) => self.write_buffer.write(fn_name),
@@ -310,7 +361,17 @@ impl<'a> FunctionLogger<'a> {
         }
         match &mut self.arguments_writer {
             Some(arg_writer) => {
-                arg_writer(&mut self.write_buffer).ok();
+                if self.structured_emitter.is_some() {
+                    let mut values = Vec::new();
+                    arg_writer(&mut format::ArgumentsWriter::new(
+                        self.write_buffer,
+                        Some(&mut values),
+                    ))
+                    .ok();
+                    structured_arguments = Some(values);
+                } else {
+                    arg_writer(&mut format::ArgumentsWriter::new(self.write_buffer, None)).ok();
+                }
             }
             None => {
                 self.write_buffer.write_all(b"(...)").ok();
@@ -328,6 +389,47 @@ impl<'a> FunctionLogger<'a> {
             self.write_buffer.end_line();
         }
         self.write_buffer.finish();
+        structured_arguments
+    }
+
+    fn emit_structured(
+        &mut self,
+        arguments: Option<Vec<(&'static str, Vec<u8>)>>,
+    ) -> io::Result<()> {
+        let emitter = match self.structured_emitter {
+            Some(emitter) => emitter,
+            None => return Ok(()),
+        };
+        let function = match self.name {
+            CudaFunctionName::Normal(fn_name) => fn_name.to_string(),
+            CudaFunctionName::Dark { guid, index } => {
+                let mut fn_name = Vec::new();
+                format::CudaDisplay::write(&guid, "", 0, &mut fn_name)?;
+                write!(&mut fn_name, "::{}", index)?;
+                String::from_utf8_lossy(&fn_name).into_owned()
+            }
+        };
+        let arguments = arguments
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, value)| structured_log::Argument {
+                name: name.to_owned(),
+                value: String::from_utf8_lossy(&value).into_owned(),
+            })
+            .collect();
+        let result = match self.result {
+            Some(result) => {
+                let mut name = Vec::new();
+                format::CudaDisplay::write(&result, "", 0, &mut name)?;
+                Some(structured_log::CallResult {
+                    code: result.0,
+                    name: String::from_utf8_lossy(&name).into_owned(),
+                })
+            }
+            None => None,
+        };
+        let messages = self.log_queue.iter().map(ToString::to_string).collect();
+        emitter.emit(self.start, function, arguments, result, messages)
     }
 
     // This is a dirty hack: we call it at the point where our write buffer is
@@ -342,7 +444,10 @@ impl<'a> FunctionLogger<'a> {
 
 impl<'a> Drop for FunctionLogger<'a> {
     fn drop(&mut self) {
-        self.flush_log_queue_to_write_buffer();
+        if self.quiet && self.log_queue.is_empty() && self.result == Some(CUresult::CUDA_SUCCESS) {
+            return;
+        }
+        let structured_arguments = self.flush_log_queue_to_write_buffer();
         let error_from_writing_to_fallible_emitter = match self.fallible_emitter {
             Some(emitter) => self.write_buffer.send_to(emitter),
             None => Ok(()),
@@ -350,6 +455,9 @@ impl<'a> Drop for FunctionLogger<'a> {
         if let Err(e) = error_from_writing_to_fallible_emitter {
             self.hack_squeeze_in_additional_error(LogEntry::IoError(e))
         }
+        if let Err(e) = self.emit_structured(structured_arguments) {
+            self.hack_squeeze_in_additional_error(LogEntry::IoError(e))
+        }
         self.write_buffer.send_to(self.infallible_emitter).ok();
         self.write_buffer.reset();
         self.log_queue.truncate(0);
@@ -370,6 +478,7 @@ pub(crate) enum LogEntry {
     MalformedModulePath(Utf8Error),
     NonUtf8ModuleText(Utf8Error),
     ModuleParsingError(String),
+    ModuleDiagnostic(ptx::Diagnostic),
     Lz4DecompressionFailure,
     UnknownExportTableFn,
     UnexpectedBinaryField {
@@ -388,6 +497,10 @@ pub(crate) enum LogEntry {
         total_count: usize,
     },
     UnknownTexref(CUtexref),
+    UnknownGraphExec(CUgraphExec),
+    UnsupportedGraphNode(CUgraphNode, CUgraphNodeType),
+    UnsupportedResourceType(CUresourcetype),
+    UnknownAllocation(CUdeviceptr),
     EnvVarError(env::VarError),
     MalformedEnvVar {
         key: &'static str,
@@ -447,6 +560,7 @@ impl Display for LogEntry {
                     file_name
                 )
             }
+            LogEntry::ModuleDiagnostic(diagnostic) => write!(f, "PTX {}", diagnostic),
             LogEntry::Lz4DecompressionFailure => write!(f, "Decompression failure"),
             LogEntry::UnknownExportTableFn => write!(f, "Unknown export table function"),
             LogEntry::UnexpectedBinaryField {
@@ -501,6 +615,24 @@ impl Display for LogEntry {
                 f.write_all(b"Unknown texture references ")?;
                 CudaDisplay::write(texref, "", 0, f)
             }),
+            LogEntry::UnknownGraphExec(graph_exec) => as_io_write(f, |f| {
+                f.write_all(b"Unknown executable graph ")?;
+                CudaDisplay::write(graph_exec, "", 0, f)
+            }),
+            LogEntry::UnsupportedGraphNode(node, node_type) => as_io_write(f, |f| {
+                f.write_all(b"Unsupported graph node ")?;
+                CudaDisplay::write(node, "", 0, f)?;
+                f.write_all(b" of type ")?;
+                CudaDisplay::write(node_type, "", 0, f)
+            }),
+            LogEntry::UnsupportedResourceType(resource_type) => as_io_write(f, |f| {
+                f.write_all(b"Unsupported resource type ")?;
+                CudaDisplay::write(resource_type, "", 0, f)
+            }),
+            LogEntry::UnknownAllocation(devptr) => as_io_write(f, |f| {
+                f.write_all(b"Unknown allocation ")?;
+                CudaDisplay::write(devptr, "", 0, f)
+            }),
             LogEntry::EnvVarError(err) => {
                 write!(f, "Error reading environment variable: {}", err)
             }
@@ -704,9 +836,10 @@ mod os {
 
 #[cfg(test)]
 mod tests {
-    use std::{cell::RefCell, io, rc::Rc, str};
+    use std::{cell::RefCell, fs, io, process, ptr, rc::Rc, str, time::SystemTime};
 
     use super::{FunctionLogger, LogEntry, WriteTrailingZeroAware};
+    use crate::{format, structured_log};
     use crate::{log::CudaFunctionName, log::WriteBuffer, CUresult};
 
     struct FailOnNthWrite {
@@ -769,9 +902,12 @@ mod tests {
             name: CudaFunctionName::Normal("cuInit"),
             infallible_emitter: &mut infallible_emitter,
             fallible_emitter: &mut fallible_emitter,
+            structured_emitter: &mut None,
+            quiet: false,
             arguments_writer: None,
             write_buffer: &mut write_buffer,
             log_queue: &mut log_queue,
+            start: SystemTime::now(),
         };
 
         func_logger.log(LogEntry::IoError(io::Error::from_raw_os_error(1)));
@@ -790,4 +926,101 @@ mod tests {
         assert!(result_lines[3].starts_with("    "));
         assert!(result_lines[4].starts_with("    "));
     }
+
+    #[test]
+    fn structured_log_records_argument_values() {
+        let path =
+            std::env::temp_dir().join(format!("zluda_dump_structured_log_{}.jsonl", process::id()));
+        let mut structured_emitter =
+            Some(structured_log::Emitter::new(&path, structured_log::LogFormat::Jsonl).unwrap());
+        let text = RcVec(Rc::new(RefCell::new(Vec::<u8>::new())));
+        let mut infallible_emitter = Box::new(text.clone()) as Box<dyn WriteTrailingZeroAware>;
+        let mut write_buffer = WriteBuffer::new();
+        write_buffer.unprefixed_buffer = Some(Vec::new());
+        let mut log_queue = Vec::new();
+        let module = 0x1234usize as cuda_types::CUmodule;
+        // Separators inside of a value must not split it
+        let name = b"kernel, (1)\0".as_ptr() as *const i8;
+        let func_logger = FunctionLogger {
+            result: Some(CUresult::CUDA_ERROR_NOT_FOUND),
+            name: CudaFunctionName::Normal("cuModuleGetFunction"),
+            infallible_emitter: &mut infallible_emitter,
+            fallible_emitter: &mut None,
+            structured_emitter: &mut structured_emitter,
+            quiet: false,
+            arguments_writer: Some(Box::new(move |writer: &mut format::ArgumentsWriter| {
+                format::write_cuModuleGetFunction(writer, ptr::null_mut(), module, name)
+            })),
+            write_buffer: &mut write_buffer,
+            log_queue: &mut log_queue,
+            start: SystemTime::now(),
+        };
+        drop(func_logger);
+        drop(structured_emitter);
+        let records = structured_log::Reader::open(&path)
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.function, "cuModuleGetFunction");
+        let arguments = record
+            .arguments
+            .iter()
+            .map(|arg| (arg.name.as_str(), arg.value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            arguments,
+            vec![
+                ("hfunc", "NULL"),
+                ("hmod", "0x1234"),
+                ("name", "\"kernel, (1)\"")
+            ]
+        );
+        assert_eq!(record.objects, vec![0x1234]);
+        assert_eq!(record.result.as_ref().unwrap().code, 500);
+        let text = text.0.borrow();
+        assert_eq!(
+            str::from_utf8(&*text).unwrap().lines().next().unwrap(),
+            "cuModuleGetFunction(hfunc: NULL, hmod: 0x1234, name: \"kernel, (1)\") -> CUDA_ERROR_NOT_FOUND"
+        );
+    }
+
+    fn log_quiet_call(result: CUresult) -> String {
+        let text = RcVec(Rc::new(RefCell::new(Vec::<u8>::new())));
+        let mut infallible_emitter = Box::new(text.clone()) as Box<dyn WriteTrailingZeroAware>;
+        let mut write_buffer = WriteBuffer::new();
+        write_buffer.unprefixed_buffer = Some(Vec::new());
+        let mut log_queue = Vec::new();
+        let mut func_logger = FunctionLogger {
+            result: None,
+            name: CudaFunctionName::Normal("cuCtxSynchronize"),
+            infallible_emitter: &mut infallible_emitter,
+            fallible_emitter: &mut None,
+            structured_emitter: &mut None,
+            quiet: false,
+            arguments_writer: None,
+            write_buffer: &mut write_buffer,
+            log_queue: &mut log_queue,
+            start: SystemTime::now(),
+        };
+        func_logger.set_quiet();
+        func_logger.result = Some(result);
+        drop(func_logger);
+        drop(infallible_emitter);
+        let text = text.0.borrow();
+        String::from_utf8(text.clone()).unwrap()
+    }
+
+    #[test]
+    fn quiet_call_is_logged_only_on_failure() {
+        assert_eq!(log_quiet_call(CUresult::CUDA_SUCCESS), "");
+        assert_eq!(
+            log_quiet_call(CUresult::CUDA_ERROR_ILLEGAL_ADDRESS)
+                .lines()
+                .collect::<Vec<_>>(),
+            vec!["cuCtxSynchronize(...) -> CUDA_ERROR_ILLEGAL_ADDRESS"]
+        );
+    }
 }
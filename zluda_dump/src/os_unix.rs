@@ -1,7 +1,7 @@
 use std::ffi::{c_void, CStr, CString};
 use std::mem;
 
-pub(crate) const LIBCUDA_DEFAULT_PATH: &'static str = "/usr/lib/x86_64-linux-gnu/libcuda.so.1";
+pub const LIBCUDA_DEFAULT_PATH: &'static str = "/usr/lib/x86_64-linux-gnu/libcuda.so.1";
 
 pub unsafe fn load_library(libcuda_path: &str) -> *mut c_void {
     let libcuda_path = CString::new(libcuda_path).unwrap();
@@ -5,32 +5,38 @@ use crate::{
     log::{FunctionLogger, LogEntry},
     Settings,
 };
-use crossbeam_channel::{Receiver, Sender};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 use cuda_types::*;
-use rustc_hash::FxHashSet;
+use rustc_hash::FxHashMap;
 use serde::Serialize;
 use std::borrow::Cow;
 use std::env;
 use std::ffi::CStr;
 use std::fmt::Display;
 use std::io::BufWriter;
-use std::io::Write;
+use std::io::{self, Write};
+use std::os::raw::c_int;
+use std::path::Path;
 use std::process;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, TryLockError};
 use std::time::Instant;
-use std::{
-    collections::VecDeque,
-    fs::File,
-    path::PathBuf,
-    ptr,
-    thread::{self, JoinHandle},
-    time::Duration,
-};
+use std::{collections::VecDeque, fs::File, path::PathBuf, ptr, thread, time::Duration};
+
+lazy_static! {
+    // Profiler lives in the global state, which is never dropped. We keep
+    // another reference here so we can write out the end of the trace when
+    // the process exits
+    static ref PROFILER_STATE: Mutex<Option<Arc<Mutex<ProfilerState>>>> = Mutex::new(None);
+}
+
+extern "C" {
+    fn atexit(callback: extern "C" fn()) -> c_int;
+}
 
 pub(crate) struct Profiler {
     cu_event_destroy_v2: extern "system" fn(CUevent) -> CUresult,
     sender: Sender<ProfilerPacket>,
-    _thread: JoinHandle<()>,
+    state: Arc<Mutex<ProfilerState>>,
 }
 
 impl Profiler {
@@ -44,17 +50,21 @@ impl Profiler {
             .flatten()
     }
 
-    pub(crate) fn record_kernel(
+    pub(crate) fn record_device_operation(
         &self,
+        context: CUcontext,
         stream: CUstream,
-        function: FunctionName,
+        name: OperationName,
+        category: Category,
         start: CUevent,
         end: CUevent,
     ) {
         self.sender
-            .send(ProfilerPacket::RecordKernel(KernelEnqueue {
+            .send(ProfilerPacket::RecordDeviceOperation(DeviceOperation {
+                context,
                 stream,
-                function,
+                name,
+                category,
                 start,
                 end,
                 cu_event_destroy_v2: self.cu_event_destroy_v2,
@@ -92,10 +102,12 @@ impl Profiler {
             }
             None => return Ok(None),
         };
+        let is_perfetto = Self::is_perfetto(&path);
         let file = File::create(path)?;
         let cu_event_create = try_get_cuda_function!(fn_table, cuEventCreate)?;
         let cu_event_destroy_v2 = try_get_cuda_function!(fn_table, cuEventDestroy_v2)?;
         let cu_event_query = try_get_cuda_function!(fn_table, cuEventQuery)?;
+        let cu_event_synchronize = try_get_cuda_function!(fn_table, cuEventSynchronize)?;
         let cu_event_elapsed_time = try_get_cuda_function!(fn_table, cuEventElapsedTime)?;
         let mut profiling_start = ptr::null_mut();
         cuda_call!(fn_table.cuInit(0));
@@ -109,26 +121,41 @@ impl Profiler {
         cuda_call!(fn_table.cuEventSynchronize(profiling_start));
         cuda_call!(fn_table.cuCtxPopCurrent_v2(&mut ctx));
         // Don't release the primary context, otherwise the event will get wiped out
-        let (sender, receiver) = crossbeam_channel::bounded(1);
-        let kernel_start = ForceSend(profiling_start);
-        let thread = thread::spawn(move || {
-            Self::run(
-                file,
-                device_name,
-                cu_event_query,
-                cu_event_elapsed_time,
-                host_start,
-                kernel_start,
-                receiver,
-            )
-        });
+        let writer: Box<dyn TraceWriter> = if is_perfetto {
+            Box::new(PerfettoWriter::new(Self::current_exe(), file, device_name)?)
+        } else {
+            Box::new(JsonWriter::new(Self::current_exe(), file, device_name)?)
+        };
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let state = Arc::new(Mutex::new(ProfilerState {
+            writer,
+            receiver,
+            queue: VecDeque::new(),
+            host_start,
+            profiling_start,
+            cu_event_query,
+            cu_event_synchronize,
+            cu_event_elapsed_time,
+            finished: false,
+        }));
+        let thread_state = state.clone();
+        thread::spawn(move || Self::run(thread_state));
+        *PROFILER_STATE.lock().unwrap() = Some(state.clone());
+        unsafe { atexit(Self::finish_at_exit) };
         Ok(Some(Self {
             cu_event_destroy_v2,
             sender,
-            _thread: thread,
+            state,
         }))
     }
 
+    fn is_perfetto(path: &Path) -> bool {
+        match path.extension() {
+            Some(ext) => ext == "pftrace" || ext == "perfetto-trace",
+            None => false,
+        }
+    }
+
     fn get_device_name(fn_table: &mut CudaDynamicFns) -> Result<String, LogEntry> {
         let mut name = vec![0i8; 256];
         cuda_call!(fn_table.cuDeviceGetName(name.as_mut_ptr(), name.len() as i32, CUdevice_v1(0)));
@@ -138,50 +165,47 @@ impl Profiler {
             .to_string())
     }
 
-    fn run(
-        file: File,
-        device_name: String,
-        cu_event_query: extern "system" fn(CUevent) -> CUresult,
-        cu_event_elapsed_time: extern "system" fn(*mut f32, CUevent, CUevent) -> CUresult,
-        host_start: Instant,
-        kernel_start: ForceSend<CUevent>,
-        receiver: Receiver<ProfilerPacket>,
-    ) {
-        let mut writer = ProfilingWriter::new(Self::current_exe(), file, device_name);
-        let profiling_start = kernel_start.0;
-        let mut queue = VecDeque::new();
+    fn run(state: Arc<Mutex<ProfilerState>>) {
+        let receiver = state.lock().unwrap().receiver.clone();
         let mut timeout = 1;
-        'thread_loop: loop {
-            match receiver.recv_timeout(Duration::from_millis(timeout)) {
-                Ok(ProfilerPacket::Finish) => return,
-                Ok(ProfilerPacket::RecordKernel(packet)) => {
-                    timeout = 1;
-                    queue.push_back(packet);
-                    loop {
-                        match receiver.try_recv() {
-                            Ok(ProfilerPacket::Finish) => return,
-                            Ok(ProfilerPacket::RecordTask(task)) => {
-                                Self::process_task(&mut writer, host_start, task)
-                            }
-                            Ok(ProfilerPacket::RecordKernel(packet)) => queue.push_back(packet),
-                            Err(_) => continue 'thread_loop,
-                        }
-                    }
-                }
-                Ok(ProfilerPacket::RecordTask(task)) => {
+        loop {
+            let packet = receiver.recv_timeout(Duration::from_millis(timeout));
+            let mut state = state.lock().unwrap();
+            if state.finished {
+                return;
+            }
+            match packet {
+                Ok(packet) => {
                     timeout = 1;
-                    Self::process_task(&mut writer, host_start, task)
+                    state.process_packet(packet);
                 }
-                Err(_) => {
-                    timeout = u64::max(100, timeout * 2);
-                    Self::process_queue(
-                        &mut writer,
-                        profiling_start,
-                        cu_event_query,
-                        cu_event_elapsed_time,
-                        &mut queue,
-                    );
+                Err(RecvTimeoutError::Timeout) => {
+                    timeout = u64::min(100, timeout * 2);
+                    state.process_queue(false);
                 }
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+
+    extern "C" fn finish_at_exit() {
+        let state = match PROFILER_STATE.lock() {
+            Ok(mut state) => state.take(),
+            Err(_) => return,
+        };
+        if let Some(state) = state {
+            Self::finish(&state);
+        }
+    }
+
+    fn finish(state: &Mutex<ProfilerState>) {
+        // On Windows other threads are already killed when we get here, if
+        // the profiler thread was holding the lock it's never coming back
+        for _ in 0..100 {
+            match state.try_lock() {
+                Ok(mut state) => return state.finish(),
+                Err(TryLockError::Poisoned(_)) => return,
+                Err(TryLockError::WouldBlock) => thread::sleep(Duration::from_millis(10)),
             }
         }
     }
@@ -194,52 +218,105 @@ impl Profiler {
             })
             .unwrap_or(None)
     }
+}
 
-    fn process_task(writer: &mut ProfilingWriter, host_start: Instant, task: TaskMeasurement) {
-        let time_from_start = task.start.saturating_duration_since(host_start);
-        let duration = task.end.saturating_duration_since(task.start);
-        writer.write_host(task.thread_id, task.function, time_from_start, duration)
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        Self::finish(&self.state);
     }
+}
 
-    fn process_queue(
-        writer: &mut ProfilingWriter,
-        first_event: CUevent,
-        cu_event_query: extern "system" fn(CUevent) -> CUresult,
-        cu_event_elapsed_time: extern "system" fn(*mut f32, CUevent, CUevent) -> CUresult,
-        queue: &mut VecDeque<KernelEnqueue>,
-    ) {
-        loop {
-            let should_dequeue = match queue.front() {
-                Some(packet) => cu_event_query(packet.end) == CUresult::CUDA_SUCCESS,
-                None => false,
+struct ProfilerState {
+    writer: Box<dyn TraceWriter>,
+    receiver: Receiver<ProfilerPacket>,
+    queue: VecDeque<DeviceOperation>,
+    host_start: Instant,
+    profiling_start: CUevent,
+    cu_event_query: extern "system" fn(CUevent) -> CUresult,
+    cu_event_synchronize: extern "system" fn(CUevent) -> CUresult,
+    cu_event_elapsed_time: extern "system" fn(*mut f32, CUevent, CUevent) -> CUresult,
+    finished: bool,
+}
+
+unsafe impl Send for ProfilerState {}
+
+impl ProfilerState {
+    fn process_packet(&mut self, packet: ProfilerPacket) {
+        match packet {
+            ProfilerPacket::RecordDeviceOperation(operation) => self.queue.push_back(operation),
+            ProfilerPacket::RecordTask(task) => {
+                let time_from_start = task.start.saturating_duration_since(self.host_start);
+                let duration = task.end.saturating_duration_since(task.start);
+                self.writer
+                    .write_host(
+                        task.thread_id,
+                        task.function,
+                        host_category(task.function),
+                        time_from_start,
+                        duration,
+                    )
+                    .ok();
+            }
+        }
+    }
+
+    // If `wait` is set we block until all the queued operations are complete
+    fn process_queue(&mut self, wait: bool) {
+        while let Some(operation) = self.queue.front() {
+            let is_complete = if wait {
+                (self.cu_event_synchronize)(operation.end) == CUresult::CUDA_SUCCESS
+            } else {
+                (self.cu_event_query)(operation.end) == CUresult::CUDA_SUCCESS
             };
-            if !should_dequeue {
+            if !is_complete {
+                if wait {
+                    self.queue.pop_front();
+                    continue;
+                }
                 return;
             }
-            let kernel = queue.pop_front().unwrap();
+            let operation = self.queue.pop_front().unwrap();
             let mut time_from_start = 0f32;
-            let cu_result = cu_event_elapsed_time(&mut time_from_start, first_event, kernel.start);
+            let cu_result = (self.cu_event_elapsed_time)(
+                &mut time_from_start,
+                self.profiling_start,
+                operation.start,
+            );
             if cu_result != CUresult::CUDA_SUCCESS {
-                panic!("{}", cu_result.0);
+                continue;
             }
             let mut time_of_execution = 0f32;
-            let cu_result = cu_event_elapsed_time(&mut time_of_execution, kernel.start, kernel.end);
+            let cu_result = (self.cu_event_elapsed_time)(
+                &mut time_of_execution,
+                operation.start,
+                operation.end,
+            );
             if cu_result != CUresult::CUDA_SUCCESS {
-                panic!("{}", cu_result.0);
+                continue;
             }
-            writer.write_kernel(
-                kernel.stream,
-                &kernel.function,
-                time_from_start,
-                time_of_execution,
-            );
+            self.writer
+                .write_device(
+                    operation.context,
+                    operation.stream,
+                    &operation.name,
+                    operation.category,
+                    time_from_start,
+                    time_of_execution,
+                )
+                .ok();
         }
     }
-}
 
-impl Drop for Profiler {
-    fn drop(&mut self) {
-        self.sender.send(ProfilerPacket::Finish).ok();
+    fn finish(&mut self) {
+        if self.finished {
+            return;
+        }
+        while let Ok(packet) = self.receiver.try_recv() {
+            self.process_packet(packet);
+        }
+        self.process_queue(true);
+        self.writer.finish().ok();
+        self.finished = true;
     }
 }
 
@@ -266,21 +343,22 @@ impl<'a> Drop for TimedTask<'a> {
 }
 
 enum ProfilerPacket {
-    RecordKernel(KernelEnqueue),
+    RecordDeviceOperation(DeviceOperation),
     RecordTask(TaskMeasurement),
-    Finish,
 }
 
-struct KernelEnqueue {
+struct DeviceOperation {
+    context: CUcontext,
     stream: CUstream,
-    function: FunctionName,
+    name: OperationName,
+    category: Category,
     start: CUevent,
     end: CUevent,
     cu_event_destroy_v2: extern "system" fn(CUevent) -> CUresult,
 }
 
 #[allow(unused_must_use)]
-impl Drop for KernelEnqueue {
+impl Drop for DeviceOperation {
     fn drop(&mut self) {
         (self.cu_event_destroy_v2)(self.start);
         (self.cu_event_destroy_v2)(self.end);
@@ -294,37 +372,97 @@ struct TaskMeasurement {
     thread_id: usize,
 }
 
-unsafe impl Send for KernelEnqueue {}
+unsafe impl Send for DeviceOperation {}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Category {
+    Host,
+    Module,
+    Kernel,
+    Memcpy,
+    Memset,
+}
 
-struct ForceSend<T>(T);
+impl Category {
+    fn name(self) -> &'static str {
+        match self {
+            Category::Host => "host",
+            Category::Module => "module",
+            Category::Kernel => "kernel",
+            Category::Memcpy => "memcpy",
+            Category::Memset => "memset",
+        }
+    }
+}
 
-unsafe impl<T> Send for ForceSend<T> {}
+// With ZLUDA, module loading is where the JIT compilation happens. With lazy
+// loading kernels are compiled on the first cuModuleGetFunction(...)
+fn host_category(function: &str) -> Category {
+    if function.starts_with("cuModuleLoad")
+        || function.starts_with("cuLibraryLoad")
+        || function.starts_with("cuLink")
+        || function == "cuModuleGetFunction"
+        || function == "cuLibraryGetKernel"
+        || function == "cuKernelGetFunction"
+    {
+        Category::Module
+    } else {
+        Category::Host
+    }
+}
 
-pub(crate) enum FunctionName {
+pub(crate) enum OperationName {
     Resolved(Arc<String>),
     Unresolved(CUfunction),
+    Function(&'static str),
 }
 
-impl Display for FunctionName {
+impl Display for OperationName {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            FunctionName::Resolved(name) => Display::fmt(name, f),
-            FunctionName::Unresolved(cu_func) => write!(f, "{:p}", cu_func),
+            OperationName::Resolved(name) => Display::fmt(name, f),
+            OperationName::Unresolved(cu_func) => write!(f, "{:p}", cu_func),
+            OperationName::Function(name) => f.write_str(name),
         }
     }
 }
 
-struct ProfilingWriter {
+trait TraceWriter {
+    fn write_host(
+        &mut self,
+        tid: usize,
+        func: &'static str,
+        category: Category,
+        time_from_start: Duration,
+        duration: Duration,
+    ) -> io::Result<()>;
+
+    fn write_device(
+        &mut self,
+        context: CUcontext,
+        stream: CUstream,
+        name: &OperationName,
+        category: Category,
+        millis_from_start: f32,
+        millis_duration: f32,
+    ) -> io::Result<()>;
+
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+// Device operations are all in the process 0, every stream of every context
+// gets a separate thread
+struct JsonWriter {
     file: BufWriter<File>,
-    known_streams: FxHashSet<CUstream>,
+    known_streams: FxHashMap<(CUcontext, CUstream), usize>,
     pid: u32,
 }
 
-impl ProfilingWriter {
-    fn new(process: Option<String>, mut file: File, device_name: String) -> Self {
+impl JsonWriter {
+    fn new(process: Option<String>, file: File, device_name: String) -> io::Result<Self> {
         let pid = process::id();
-        file.write_all(b"[\n").unwrap();
-        let mut serializer = serde_json::Serializer::new(&mut file);
+        let mut file = BufWriter::new(file);
+        file.write_all(b"[\n")?;
         let entry = ProfilingEvent::Metadata {
             name: "process_name",
             ph: "M",
@@ -332,11 +470,9 @@ impl ProfilingWriter {
             tid: 0,
             args: NameArg { name: device_name },
         };
-        entry.serialize(&mut serializer).unwrap();
-        drop(serializer);
+        serde_json::to_writer(&mut file, &entry)?;
         if let Some(exe) = process {
-            file.write_all(b",\n").unwrap();
-            let mut serializer = serde_json::Serializer::new(&mut file);
+            file.write_all(b",\n")?;
             let entry = ProfilingEvent::Metadata {
                 name: "process_name",
                 ph: "M",
@@ -344,78 +480,81 @@ impl ProfilingWriter {
                 tid: 0,
                 args: NameArg { name: exe },
             };
-            entry.serialize(&mut serializer).unwrap();
+            serde_json::to_writer(&mut file, &entry)?;
         }
-        let file = BufWriter::new(file);
-        Self {
+        Ok(Self {
             file,
-            known_streams: FxHashSet::default(),
+            known_streams: FxHashMap::default(),
             pid,
-        }
+        })
+    }
+
+    fn write_event(&mut self, entry: &ProfilingEvent) -> io::Result<()> {
+        self.file.write_all(b",\n")?;
+        serde_json::to_writer(&mut self.file, entry)?;
+        Ok(())
     }
+}
 
+impl TraceWriter for JsonWriter {
     fn write_host(
         &mut self,
         tid: usize,
         func: &'static str,
+        category: Category,
         time_from_start: Duration,
         duration: Duration,
-    ) {
-        self.file.write_all(b",\n").unwrap();
-        let mut serializer = serde_json::Serializer::new(&mut self.file);
-        let entry = ProfilingEvent::Complete {
+    ) -> io::Result<()> {
+        self.write_event(&ProfilingEvent::Complete {
             name: Cow::Borrowed(func),
-            cat: "host",
+            cat: category.name(),
             ph: "X",
-            ts: time_from_start.as_micros() as f32,
-            dur: duration.as_micros() as f32,
+            ts: time_from_start.as_nanos() as f64 / 1000f64,
+            dur: duration.as_nanos() as f64 / 1000f64,
             pid: self.pid,
             tid,
-        };
-        entry.serialize(&mut serializer).unwrap();
+        })
     }
 
-    fn write_kernel(
+    fn write_device(
         &mut self,
+        context: CUcontext,
         stream: CUstream,
-        function: &FunctionName,
+        name: &OperationName,
+        category: Category,
         millis_from_start: f32,
         millis_duration: f32,
-    ) {
-        if self.known_streams.insert(stream) {
-            self.file.write_all(b",\n").unwrap();
-            let mut serializer = serde_json::Serializer::new(&mut self.file);
-            let entry = ProfilingEvent::Metadata {
+    ) -> io::Result<()> {
+        let next_tid = self.known_streams.len() + 1;
+        let tid = *self
+            .known_streams
+            .entry((context, stream))
+            .or_insert(next_tid);
+        if tid == next_tid {
+            self.write_event(&ProfilingEvent::Metadata {
                 name: "thread_name",
                 ph: "M",
                 pid: 0,
-                tid: stream as usize,
+                tid,
                 args: NameArg {
-                    name: format!("Stream {:p}", stream),
+                    name: format!("Context {:p} stream {:p}", context, stream),
                 },
-            };
-            entry.serialize(&mut serializer).unwrap();
+            })?;
         }
-        self.file.write_all(b",\n").unwrap();
-        let mut serializer = serde_json::Serializer::new(&mut self.file);
-        let ts = millis_from_start * 1000f32;
-        let dur = millis_duration * 1000f32;
-        let entry = ProfilingEvent::Complete {
-            name: Cow::Owned(format!("{}", function)),
-            cat: "kernel",
+        self.write_event(&ProfilingEvent::Complete {
+            name: Cow::Owned(format!("{}", name)),
+            cat: category.name(),
             ph: "X",
-            ts: ts,
-            dur: dur,
+            ts: millis_from_start as f64 * 1000f64,
+            dur: millis_duration as f64 * 1000f64,
             pid: 0,
-            tid: stream as usize,
-        };
-        entry.serialize(&mut serializer).unwrap();
+            tid,
+        })
     }
-}
 
-impl Drop for ProfilingWriter {
-    fn drop(&mut self) {
-        self.file.write_all(b"\n]").ok();
+    fn finish(&mut self) -> io::Result<()> {
+        self.file.write_all(b"\n]\n")?;
+        self.file.flush()
     }
 }
 
@@ -427,8 +566,8 @@ enum ProfilingEvent {
         name: Cow<'static, str>,
         cat: &'static str,
         ph: &'static str,
-        ts: f32,
-        dur: f32,
+        ts: f64,
+        dur: f64,
         pid: u32,
         tid: usize,
     },
@@ -445,3 +584,484 @@ enum ProfilingEvent {
 struct NameArg {
     name: String,
 }
+
+// Writes Perfetto protobuf trace: https://perfetto.dev/docs/reference/trace-packet-proto
+// We only need a handful of messages, so we encode them by hand instead of
+// pulling in a protobuf library. Device has a track per context and every
+// context has a child track per stream
+struct PerfettoWriter {
+    file: BufWriter<File>,
+    pid: u32,
+    process_track: u64,
+    device_track: u64,
+    tracks: FxHashMap<PerfettoTrack, u64>,
+    first_packet: bool,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum PerfettoTrack {
+    Thread(usize),
+    Context(CUcontext),
+    Stream(CUcontext, CUstream),
+}
+
+impl PerfettoWriter {
+    const SEQUENCE_ID: u64 = 1;
+    // TracePacket
+    const TIMESTAMP: u32 = 8;
+    const TRUSTED_PACKET_SEQUENCE_ID: u32 = 10;
+    const TRACK_EVENT: u32 = 11;
+    const SEQUENCE_FLAGS: u32 = 13;
+    const TRACK_DESCRIPTOR: u32 = 60;
+    const SEQ_INCREMENTAL_STATE_CLEARED: u64 = 1;
+    // TrackDescriptor
+    const UUID: u32 = 1;
+    const NAME: u32 = 2;
+    const PROCESS: u32 = 3;
+    const THREAD: u32 = 4;
+    const PARENT_UUID: u32 = 5;
+    // ProcessDescriptor and ThreadDescriptor
+    const PID: u32 = 1;
+    const TID: u32 = 2;
+    const PROCESS_NAME: u32 = 6;
+    const THREAD_NAME: u32 = 5;
+    // TrackEvent
+    const TYPE: u32 = 9;
+    const TRACK_UUID: u32 = 11;
+    const CATEGORIES: u32 = 22;
+    const EVENT_NAME: u32 = 23;
+    const TYPE_SLICE_BEGIN: u64 = 1;
+    const TYPE_SLICE_END: u64 = 2;
+
+    fn new(process: Option<String>, file: File, device_name: String) -> io::Result<Self> {
+        let pid = process::id();
+        let mut writer = Self {
+            file: BufWriter::new(file),
+            pid,
+            process_track: 1,
+            device_track: 2,
+            tracks: FxHashMap::default(),
+            first_packet: true,
+        };
+        let mut process_descriptor = ProtoMessage::new();
+        process_descriptor.varint(Self::PID, pid as u64);
+        if let Some(exe) = process {
+            process_descriptor.string(Self::PROCESS_NAME, &exe);
+        }
+        let mut descriptor = ProtoMessage::new();
+        descriptor.varint(Self::UUID, writer.process_track);
+        descriptor.message(Self::PROCESS, &process_descriptor);
+        writer.write_track_descriptor(&descriptor)?;
+        let mut descriptor = ProtoMessage::new();
+        descriptor.varint(Self::UUID, writer.device_track);
+        descriptor.string(Self::NAME, &device_name);
+        writer.write_track_descriptor(&descriptor)?;
+        Ok(writer)
+    }
+
+    fn next_uuid(&self) -> u64 {
+        self.tracks.len() as u64 + 3
+    }
+
+    fn thread_track(&mut self, tid: usize) -> io::Result<u64> {
+        if let Some(uuid) = self.tracks.get(&PerfettoTrack::Thread(tid)) {
+            return Ok(*uuid);
+        }
+        let uuid = self.next_uuid();
+        self.tracks.insert(PerfettoTrack::Thread(tid), uuid);
+        let mut thread_descriptor = ProtoMessage::new();
+        thread_descriptor.varint(Self::PID, self.pid as u64);
+        thread_descriptor.varint(Self::TID, tid as u64);
+        thread_descriptor.string(Self::THREAD_NAME, &format!("Thread {}", tid));
+        let mut descriptor = ProtoMessage::new();
+        descriptor.varint(Self::UUID, uuid);
+        descriptor.varint(Self::PARENT_UUID, self.process_track);
+        descriptor.message(Self::THREAD, &thread_descriptor);
+        self.write_track_descriptor(&descriptor)?;
+        Ok(uuid)
+    }
+
+    fn stream_track(&mut self, context: CUcontext, stream: CUstream) -> io::Result<u64> {
+        if let Some(uuid) = self.tracks.get(&PerfettoTrack::Stream(context, stream)) {
+            return Ok(*uuid);
+        }
+        let context_uuid = match self.tracks.get(&PerfettoTrack::Context(context)) {
+            Some(uuid) => *uuid,
+            None => {
+                let uuid = self.next_uuid();
+                self.tracks.insert(PerfettoTrack::Context(context), uuid);
+                let mut descriptor = ProtoMessage::new();
+                descriptor.varint(Self::UUID, uuid);
+                descriptor.varint(Self::PARENT_UUID, self.device_track);
+                descriptor.string(Self::NAME, &format!("Context {:p}", context));
+                self.write_track_descriptor(&descriptor)?;
+                uuid
+            }
+        };
+        let uuid = self.next_uuid();
+        self.tracks
+            .insert(PerfettoTrack::Stream(context, stream), uuid);
+        let mut descriptor = ProtoMessage::new();
+        descriptor.varint(Self::UUID, uuid);
+        descriptor.varint(Self::PARENT_UUID, context_uuid);
+        descriptor.string(Self::NAME, &format!("Stream {:p}", stream));
+        self.write_track_descriptor(&descriptor)?;
+        Ok(uuid)
+    }
+
+    fn write_track_descriptor(&mut self, descriptor: &ProtoMessage) -> io::Result<()> {
+        let mut packet = ProtoMessage::new();
+        packet.message(Self::TRACK_DESCRIPTOR, descriptor);
+        self.write_packet(packet)
+    }
+
+    fn write_slice(
+        &mut self,
+        track: u64,
+        name: &str,
+        category: Category,
+        start_ns: u64,
+        duration_ns: u64,
+    ) -> io::Result<()> {
+        let mut event = ProtoMessage::new();
+        event.varint(Self::TYPE, Self::TYPE_SLICE_BEGIN);
+        event.varint(Self::TRACK_UUID, track);
+        event.string(Self::CATEGORIES, category.name());
+        event.string(Self::EVENT_NAME, name);
+        let mut packet = ProtoMessage::new();
+        packet.varint(Self::TIMESTAMP, start_ns);
+        packet.message(Self::TRACK_EVENT, &event);
+        self.write_packet(packet)?;
+        let mut event = ProtoMessage::new();
+        event.varint(Self::TYPE, Self::TYPE_SLICE_END);
+        event.varint(Self::TRACK_UUID, track);
+        let mut packet = ProtoMessage::new();
+        packet.varint(Self::TIMESTAMP, start_ns + duration_ns);
+        packet.message(Self::TRACK_EVENT, &event);
+        self.write_packet(packet)
+    }
+
+    fn write_packet(&mut self, mut packet: ProtoMessage) -> io::Result<()> {
+        packet.varint(Self::TRUSTED_PACKET_SEQUENCE_ID, Self::SEQUENCE_ID);
+        if self.first_packet {
+            packet.varint(Self::SEQUENCE_FLAGS, Self::SEQ_INCREMENTAL_STATE_CLEARED);
+            self.first_packet = false;
+        }
+        // Trace is a sequence of `repeated TracePacket packet = 1`
+        let mut trace = ProtoMessage::new();
+        trace.message(1, &packet);
+        self.file.write_all(&trace.0)
+    }
+}
+
+impl TraceWriter for PerfettoWriter {
+    fn write_host(
+        &mut self,
+        tid: usize,
+        func: &'static str,
+        category: Category,
+        time_from_start: Duration,
+        duration: Duration,
+    ) -> io::Result<()> {
+        let track = self.thread_track(tid)?;
+        self.write_slice(
+            track,
+            func,
+            category,
+            time_from_start.as_nanos() as u64,
+            duration.as_nanos() as u64,
+        )
+    }
+
+    fn write_device(
+        &mut self,
+        context: CUcontext,
+        stream: CUstream,
+        name: &OperationName,
+        category: Category,
+        millis_from_start: f32,
+        millis_duration: f32,
+    ) -> io::Result<()> {
+        let track = self.stream_track(context, stream)?;
+        self.write_slice(
+            track,
+            &name.to_string(),
+            category,
+            (millis_from_start as f64 * 1_000_000f64) as u64,
+            (millis_duration as f64 * 1_000_000f64) as u64,
+        )
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+struct ProtoMessage(Vec<u8>);
+
+impl ProtoMessage {
+    const WIRE_VARINT: u32 = 0;
+    const WIRE_LENGTH_DELIMITED: u32 = 2;
+
+    fn new() -> Self {
+        ProtoMessage(Vec::new())
+    }
+
+    fn raw_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn varint(&mut self, field: u32, value: u64) {
+        self.raw_varint(((field << 3) | Self::WIRE_VARINT) as u64);
+        self.raw_varint(value);
+    }
+
+    fn bytes(&mut self, field: u32, value: &[u8]) {
+        self.raw_varint(((field << 3) | Self::WIRE_LENGTH_DELIMITED) as u64);
+        self.raw_varint(value.len() as u64);
+        self.0.extend_from_slice(value);
+    }
+
+    fn string(&mut self, field: u32, value: &str) {
+        self.bytes(field, value.as_bytes())
+    }
+
+    fn message(&mut self, field: u32, value: &ProtoMessage) {
+        self.bytes(field, &value.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Category, JsonWriter, OperationName, PerfettoWriter, ProtoMessage, TraceWriter};
+    use std::fs::{self, File};
+    use std::path::PathBuf;
+    use std::{process, ptr, time::Duration};
+
+    #[derive(Debug, PartialEq)]
+    enum Field {
+        Varint(u32, u64),
+        Bytes(u32, Vec<u8>),
+    }
+
+    fn read_varint(buffer: &mut &[u8]) -> u64 {
+        let mut result = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = buffer[0];
+            *buffer = &buffer[1..];
+            result |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return result;
+            }
+            shift += 7;
+        }
+    }
+
+    fn decode(mut buffer: &[u8]) -> Vec<Field> {
+        let mut fields = Vec::new();
+        while !buffer.is_empty() {
+            let key = read_varint(&mut buffer);
+            let field = (key >> 3) as u32;
+            match key & 7 {
+                0 => fields.push(Field::Varint(field, read_varint(&mut buffer))),
+                2 => {
+                    let len = read_varint(&mut buffer) as usize;
+                    fields.push(Field::Bytes(field, buffer[..len].to_vec()));
+                    buffer = &buffer[len..];
+                }
+                wire_type => panic!("Unexpected wire type {}", wire_type),
+            }
+        }
+        fields
+    }
+
+    fn bytes_field(fields: &[Field], field: u32) -> &[u8] {
+        fields
+            .iter()
+            .find_map(|f| match f {
+                Field::Bytes(id, value) if *id == field => Some(&value[..]),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("zluda_dump_{}_{}", name, process::id()))
+    }
+
+    #[test]
+    fn proto_message_encodes_varints_and_fields() {
+        let mut nested = ProtoMessage::new();
+        nested.varint(1, 300);
+        let mut message = ProtoMessage::new();
+        message.varint(2, 0);
+        message.varint(16, u64::MAX);
+        message.string(3, "ab");
+        message.message(4, &nested);
+        assert_eq!(
+            message.0,
+            vec![
+                0x10, 0x00, // field 2, varint 0
+                0x80, 0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+                0x01, // field 16, varint u64::MAX
+                0x1a, 0x02, b'a', b'b', // field 3, "ab"
+                0x22, 0x03, 0x08, 0xac, 0x02, // field 4, { field 1, varint 300 }
+            ]
+        );
+        assert_eq!(
+            decode(&message.0),
+            vec![
+                Field::Varint(2, 0),
+                Field::Varint(16, u64::MAX),
+                Field::Bytes(3, b"ab".to_vec()),
+                Field::Bytes(4, nested.0.clone()),
+            ]
+        );
+    }
+
+    #[test]
+    fn perfetto_writer_writes_decodable_trace() {
+        let path = temp_path("perfetto_trace");
+        let mut writer = PerfettoWriter::new(
+            Some("app".to_string()),
+            File::create(&path).unwrap(),
+            "device".to_string(),
+        )
+        .unwrap();
+        writer
+            .write_host(
+                5,
+                "cuInit",
+                Category::Host,
+                Duration::from_nanos(1000),
+                Duration::from_nanos(200),
+            )
+            .unwrap();
+        writer.finish().unwrap();
+        let trace = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let packets = decode(&trace)
+            .into_iter()
+            .map(|packet| match packet {
+                Field::Bytes(1, packet) => decode(&packet),
+                field => panic!("Unexpected field {:?}", field),
+            })
+            .collect::<Vec<_>>();
+        // Process track, device track, thread track, slice begin and slice end
+        assert_eq!(packets.len(), 5);
+        for (index, packet) in packets.iter().enumerate() {
+            assert!(packet.contains(&Field::Varint(
+                PerfettoWriter::TRUSTED_PACKET_SEQUENCE_ID,
+                PerfettoWriter::SEQUENCE_ID
+            )));
+            assert_eq!(
+                packet.contains(&Field::Varint(
+                    PerfettoWriter::SEQUENCE_FLAGS,
+                    PerfettoWriter::SEQ_INCREMENTAL_STATE_CLEARED
+                )),
+                index == 0
+            );
+        }
+        let process_track = decode(bytes_field(&packets[0], PerfettoWriter::TRACK_DESCRIPTOR));
+        assert_eq!(process_track[0], Field::Varint(PerfettoWriter::UUID, 1));
+        let process_descriptor = decode(bytes_field(&process_track, PerfettoWriter::PROCESS));
+        assert_eq!(
+            process_descriptor,
+            vec![
+                Field::Varint(PerfettoWriter::PID, process::id() as u64),
+                Field::Bytes(PerfettoWriter::PROCESS_NAME, b"app".to_vec()),
+            ]
+        );
+        let device_track = decode(bytes_field(&packets[1], PerfettoWriter::TRACK_DESCRIPTOR));
+        assert_eq!(
+            device_track,
+            vec![
+                Field::Varint(PerfettoWriter::UUID, 2),
+                Field::Bytes(PerfettoWriter::NAME, b"device".to_vec()),
+            ]
+        );
+        let thread_track = decode(bytes_field(&packets[2], PerfettoWriter::TRACK_DESCRIPTOR));
+        assert_eq!(thread_track[0], Field::Varint(PerfettoWriter::UUID, 3));
+        assert_eq!(
+            thread_track[1],
+            Field::Varint(PerfettoWriter::PARENT_UUID, 1)
+        );
+        let thread_descriptor = decode(bytes_field(&thread_track, PerfettoWriter::THREAD));
+        assert!(thread_descriptor.contains(&Field::Varint(PerfettoWriter::TID, 5)));
+        assert_eq!(
+            packets[3][0],
+            Field::Varint(PerfettoWriter::TIMESTAMP, 1000)
+        );
+        assert_eq!(
+            decode(bytes_field(&packets[3], PerfettoWriter::TRACK_EVENT)),
+            vec![
+                Field::Varint(PerfettoWriter::TYPE, PerfettoWriter::TYPE_SLICE_BEGIN),
+                Field::Varint(PerfettoWriter::TRACK_UUID, 3),
+                Field::Bytes(PerfettoWriter::CATEGORIES, b"host".to_vec()),
+                Field::Bytes(PerfettoWriter::EVENT_NAME, b"cuInit".to_vec()),
+            ]
+        );
+        assert_eq!(
+            packets[4][0],
+            Field::Varint(PerfettoWriter::TIMESTAMP, 1200)
+        );
+        assert_eq!(
+            decode(bytes_field(&packets[4], PerfettoWriter::TRACK_EVENT)),
+            vec![
+                Field::Varint(PerfettoWriter::TYPE, PerfettoWriter::TYPE_SLICE_END),
+                Field::Varint(PerfettoWriter::TRACK_UUID, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_writer_finish_closes_array() {
+        let path = temp_path("json_trace.json");
+        let mut writer = JsonWriter::new(
+            Some("app".to_string()),
+            File::create(&path).unwrap(),
+            "device".to_string(),
+        )
+        .unwrap();
+        writer
+            .write_host(
+                5,
+                "cuInit",
+                Category::Host,
+                Duration::from_micros(1),
+                Duration::from_micros(2),
+            )
+            .unwrap();
+        writer
+            .write_device(
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &OperationName::Function("cuMemcpyHtoD_v2"),
+                Category::Memcpy,
+                1.0,
+                0.5,
+            )
+            .unwrap();
+        writer.finish().unwrap();
+        let trace = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let events = serde_json::from_str::<serde_json::Value>(&trace).unwrap();
+        let events = events.as_array().unwrap();
+        // Device name, process name, host call, stream name, device operation
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[0]["args"]["name"], "device");
+        assert_eq!(events[1]["args"]["name"], "app");
+        assert_eq!(events[2]["name"], "cuInit");
+        assert_eq!(events[2]["tid"], 5);
+        assert_eq!(events[3]["name"], "thread_name");
+        assert_eq!(events[4]["name"], "cuMemcpyHtoD_v2");
+        assert_eq!(events[4]["cat"], "memcpy");
+        assert_eq!(events[4]["ts"], 1000.0);
+        assert_eq!(events[4]["dur"], 500.0);
+    }
+}
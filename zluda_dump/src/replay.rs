@@ -0,0 +1,941 @@
+use argh::FromArgs;
+use cuda_types::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::error::Error;
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::{cmp, fs, mem, process, ptr};
+use zluda_dump::CudaDynamicFns;
+
+#[derive(FromArgs)]
+/// Replay kernel launches saved by ZLUDA_DUMP and compare their results with
+/// the recorded ones
+struct Arguments {
+    /// directory with a single kernel launch (the one with kernel_launch.json)
+    /// or a directory with many of them
+    #[argh(positional)]
+    input: PathBuf,
+    /// CUDA library used for the replay, either ZLUDA or NVIDIA CUDA,
+    /// defaults to the system CUDA library
+    #[argh(option)]
+    cuda_lib: Option<String>,
+    /// ordinal of the device used for the replay, defaults to 0
+    #[argh(option, default = "0")]
+    device: i32,
+    /// how buffers are compared: bytes, f32 or f64, defaults to bytes
+    #[argh(option, default = "ElementType::Bytes")]
+    element_type: ElementType,
+    /// largest allowed absolute difference between f32 or f64 elements
+    #[argh(option, default = "0.0")]
+    abs_tolerance: f64,
+    /// largest allowed difference between f32 or f64 elements, relative to
+    /// the recorded value
+    #[argh(option, default = "0.0")]
+    rel_tolerance: f64,
+    /// number of mismatched elements printed for every buffer, defaults to 10
+    #[argh(option, default = "10")]
+    max_mismatches: usize,
+}
+
+#[derive(Clone, Copy)]
+enum ElementType {
+    Bytes,
+    F32,
+    F64,
+}
+
+impl FromStr for ElementType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bytes" => Ok(ElementType::Bytes),
+            "f32" => Ok(ElementType::F32),
+            "f64" => Ok(ElementType::F64),
+            _ => Err(format!("Unknown element type: {}", s)),
+        }
+    }
+}
+
+struct Tolerance {
+    element_type: ElementType,
+    absolute: f64,
+    relative: f64,
+    max_mismatches: usize,
+}
+
+impl Tolerance {
+    fn accepts(&self, expected: f64, result: f64) -> bool {
+        if expected.is_nan() || result.is_nan() {
+            return expected.is_nan() && result.is_nan();
+        }
+        if expected == result {
+            return true;
+        }
+        let difference = (expected - result).abs();
+        difference <= self.absolute || difference <= self.relative * expected.abs()
+    }
+}
+
+// Mirrors what is written by DumpWriter::save_kernel_launch
+#[derive(Deserialize)]
+struct KernelLaunch {
+    name: String,
+    parameters: KernelLaunchParams,
+    explicit_arguments: Vec<ExplicitArgument>,
+    texrefs: HashMap<String, TexrefDetails>,
+    globals: HashMap<String, BufferRef>,
+    #[serde(default)]
+    texture_objects: HashMap<u64, TextureObjectDetails>,
+    #[serde(default)]
+    surface_objects: HashMap<u64, SurfaceObjectDetails>,
+    allocations: HashMap<usize, Array3dDescriptor>,
+}
+
+#[derive(Deserialize)]
+#[allow(non_snake_case)]
+struct KernelLaunchParams {
+    gridDimX: u32,
+    gridDimY: u32,
+    gridDimZ: u32,
+    blockDimX: u32,
+    blockDimY: u32,
+    blockDimZ: u32,
+    sharedMemBytes: u32,
+}
+
+#[derive(Deserialize)]
+struct ExplicitArgument {
+    buffers: Vec<ExplicitArgumentBuffer>,
+    #[serde(default)]
+    objects: Vec<ExplicitArgumentObject>,
+    data: AlignedBuffer,
+}
+
+#[derive(Deserialize)]
+struct AlignedBuffer {
+    layout: Layout,
+    ptr: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct Layout {
+    size: usize,
+}
+
+#[derive(Deserialize)]
+struct ExplicitArgumentBuffer {
+    offset_into_argument: usize,
+    buffer: BufferRef,
+}
+
+#[derive(Deserialize)]
+struct ExplicitArgumentObject {
+    offset_into_argument: usize,
+    handle: u64,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct BufferRef {
+    offset_into_buffer: usize,
+    buffer_key: usize,
+}
+
+#[derive(Deserialize)]
+struct TexrefDetails {
+    address: TexrefAddress,
+    allocation: BufferRef,
+    address_mode: [i32; 3],
+    filter_mode: i32,
+    flags: u32,
+    mipmap_filter_mode: i32,
+    mipmap_level_bias: f32,
+    min_mipmap_level_clamp: f32,
+    max_mipmap_level_clamp: f32,
+    max_anisotropy: i32,
+    format: i32,
+    num_channels: i32,
+}
+
+#[derive(Deserialize)]
+enum TexrefAddress {
+    OneD {
+        bytes: usize,
+    },
+    TwoD {
+        width: usize,
+        height: usize,
+        format: i32,
+        channels: u32,
+        pitch: usize,
+    },
+    Array {
+        flags: u32,
+    },
+}
+
+#[derive(Deserialize)]
+enum ResourceDetails {
+    Array {
+        allocation: BufferRef,
+    },
+    Linear {
+        allocation: BufferRef,
+        format: i32,
+        num_channels: u32,
+        size_in_bytes: usize,
+    },
+    Pitch2D {
+        allocation: BufferRef,
+        format: i32,
+        num_channels: u32,
+        width: usize,
+        height: usize,
+        pitch_in_bytes: usize,
+    },
+}
+
+#[derive(Deserialize)]
+struct TextureObjectDetails {
+    resource: ResourceDetails,
+    address_mode: [i32; 3],
+    filter_mode: i32,
+    flags: u32,
+    max_anisotropy: u32,
+    mipmap_filter_mode: i32,
+    mipmap_level_bias: f32,
+    min_mipmap_level_clamp: f32,
+    max_mipmap_level_clamp: f32,
+    border_color: [f32; 4],
+}
+
+impl TextureObjectDetails {
+    fn to_cuda(&self) -> CUDA_TEXTURE_DESC {
+        let mut result = unsafe { mem::zeroed::<CUDA_TEXTURE_DESC>() };
+        for (mode, recorded) in result.addressMode.iter_mut().zip(self.address_mode.iter()) {
+            *mode = CUaddress_mode(*recorded);
+        }
+        result.filterMode = CUfilter_mode(self.filter_mode);
+        result.flags = self.flags;
+        result.maxAnisotropy = self.max_anisotropy;
+        result.mipmapFilterMode = CUfilter_mode(self.mipmap_filter_mode);
+        result.mipmapLevelBias = self.mipmap_level_bias;
+        result.minMipmapLevelClamp = self.min_mipmap_level_clamp;
+        result.maxMipmapLevelClamp = self.max_mipmap_level_clamp;
+        result.borderColor = self.border_color;
+        result
+    }
+}
+
+#[derive(Deserialize)]
+struct SurfaceObjectDetails {
+    resource: ResourceDetails,
+}
+
+#[derive(Deserialize)]
+#[allow(non_snake_case)]
+struct Array3dDescriptor {
+    Width: usize,
+    Height: usize,
+    Depth: usize,
+    Format: i32,
+    NumChannels: u32,
+    Flags: u32,
+}
+
+impl Array3dDescriptor {
+    fn to_cuda(&self) -> CUDA_ARRAY3D_DESCRIPTOR {
+        CUDA_ARRAY3D_DESCRIPTOR {
+            Width: self.Width,
+            Height: self.Height,
+            Depth: self.Depth,
+            Format: CUarray_format(self.Format),
+            NumChannels: self.NumChannels,
+            Flags: self.Flags,
+        }
+    }
+}
+
+macro_rules! cuda_call {
+    ($libcuda:ident . $fn_:ident ( $($arg:expr),* ) ) => {
+        match $libcuda . $fn_ ( $($arg),* ) {
+            Some(CUresult::CUDA_SUCCESS) => (),
+            Some(err) => {
+                return Err(format!("{} failed with error {}", stringify!($fn_), err.0).into())
+            }
+            None => {
+                return Err(format!("{} is missing from the CUDA library", stringify!($fn_)).into())
+            }
+        }
+    };
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Arguments = argh::from_env();
+    let tolerance = Tolerance {
+        element_type: args.element_type,
+        absolute: args.abs_tolerance,
+        relative: args.rel_tolerance,
+        max_mismatches: args.max_mismatches,
+    };
+    let libcuda_path = args
+        .cuda_lib
+        .as_deref()
+        .unwrap_or(zluda_dump::LIBCUDA_DEFAULT_PATH);
+    let mut libcuda = unsafe { CudaDynamicFns::load_library(libcuda_path) }
+        .ok_or_else(|| format!("Invalid CUDA library at path {}", libcuda_path))?;
+    let ctx = create_context(&mut libcuda, args.device)?;
+    let dumps = if args.input.join("kernel_launch.json").exists() {
+        vec![args.input]
+    } else {
+        let mut dumps = fs::read_dir(&args.input)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>, std::io::Error>>()?;
+        dumps.retain(|dump| dump.join("kernel_launch.json").exists());
+        dumps.sort();
+        dumps
+    };
+    let mut failed = 0;
+    for dump in dumps.iter() {
+        println!("{}", dump.display());
+        match replay(&mut libcuda, dump, &tolerance) {
+            Ok(true) => {}
+            Ok(false) => failed += 1,
+            Err(err) => {
+                println!("    error: {}", err);
+                failed += 1;
+            }
+        }
+    }
+    libcuda.cuCtxDestroy_v2(ctx);
+    println!(
+        "{} of {} kernel launches passed",
+        dumps.len() - failed,
+        dumps.len()
+    );
+    if failed != 0 {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn create_context(libcuda: &mut CudaDynamicFns, device: i32) -> Result<CUcontext, Box<dyn Error>> {
+    cuda_call!(libcuda.cuInit(0));
+    let mut cu_device = CUdevice_v1(0);
+    cuda_call!(libcuda.cuDeviceGet(&mut cu_device, device));
+    let mut ctx = ptr::null_mut();
+    cuda_call!(libcuda.cuCtxCreate_v2(&mut ctx, 0, cu_device));
+    Ok(ctx)
+}
+
+enum DeviceBuffer {
+    Linear {
+        ptr: CUdeviceptr,
+        size: usize,
+        // Globals are owned by the module
+        owned: bool,
+    },
+    Array {
+        array: CUarray,
+        descriptor: CUDA_ARRAY3D_DESCRIPTOR,
+    },
+}
+
+impl DeviceBuffer {
+    fn pointer(&self, offset: usize) -> Result<CUdeviceptr, Box<dyn Error>> {
+        match self {
+            DeviceBuffer::Linear { ptr, .. } => Ok(CUdeviceptr_v2((ptr.0 as usize + offset) as _)),
+            DeviceBuffer::Array { .. } => Err("Array used as a linear buffer".into()),
+        }
+    }
+}
+
+enum DeviceObject {
+    Texture(CUtexObject),
+    Surface(CUsurfObject),
+}
+
+impl DeviceObject {
+    fn handle(&self) -> u64 {
+        match self {
+            DeviceObject::Texture(handle) | DeviceObject::Surface(handle) => *handle,
+        }
+    }
+}
+
+// Resources created for a single kernel launch, released when the replay is
+// finished, even if it failed
+struct Replay<'a> {
+    libcuda: &'a mut CudaDynamicFns,
+    module: CUmodule,
+    buffers: HashMap<usize, DeviceBuffer>,
+    // Keyed by the recorded handles
+    objects: HashMap<u64, DeviceObject>,
+}
+
+impl<'a> Drop for Replay<'a> {
+    fn drop(&mut self) {
+        for (_, object) in self.objects.drain() {
+            match object {
+                DeviceObject::Texture(texture) => {
+                    self.libcuda.cuTexObjectDestroy(texture);
+                }
+                DeviceObject::Surface(surface) => {
+                    self.libcuda.cuSurfObjectDestroy(surface);
+                }
+            }
+        }
+        for (_, buffer) in self.buffers.drain() {
+            match buffer {
+                DeviceBuffer::Linear {
+                    ptr, owned: true, ..
+                } => {
+                    self.libcuda.cuMemFree_v2(ptr);
+                }
+                DeviceBuffer::Linear { owned: false, .. } => {}
+                DeviceBuffer::Array { array, .. } => {
+                    self.libcuda.cuArrayDestroy(array);
+                }
+            }
+        }
+        if self.module != ptr::null_mut() {
+            self.libcuda.cuModuleUnload(self.module);
+        }
+    }
+}
+
+// Returns false if the results differ from the recorded ones
+fn replay(
+    libcuda: &mut CudaDynamicFns,
+    dump: &Path,
+    tolerance: &Tolerance,
+) -> Result<bool, Box<dyn Error>> {
+    let kernel_launch: KernelLaunch =
+        serde_json::from_reader(fs::File::open(dump.join("kernel_launch.json"))?)?;
+    let mut module_text = fs::read(dump.join("module.ptx"))?;
+    module_text.push(0);
+    let pre = load_buffers(&dump.join("pre"))?;
+    let post = load_buffers(&dump.join("post"))?;
+    let mut replay = Replay {
+        libcuda,
+        module: ptr::null_mut(),
+        buffers: HashMap::new(),
+        objects: HashMap::new(),
+    };
+    let libcuda = &mut *replay.libcuda;
+    cuda_call!(libcuda.cuModuleLoadData(&mut replay.module, module_text.as_ptr() as _));
+    for (name, global) in kernel_launch.globals.iter() {
+        let name = CString::new(name.as_str())?;
+        let mut ptr = CUdeviceptr_v2(ptr::null_mut());
+        let mut size = 0;
+        cuda_call!(libcuda.cuModuleGetGlobal_v2(&mut ptr, &mut size, replay.module, name.as_ptr()));
+        replay.buffers.insert(
+            global.buffer_key,
+            DeviceBuffer::Linear {
+                ptr,
+                size,
+                owned: false,
+            },
+        );
+    }
+    for (key, data) in pre.iter() {
+        if !replay.buffers.contains_key(key) {
+            let buffer = match kernel_launch.allocations.get(key) {
+                Some(descriptor) => {
+                    let descriptor = descriptor.to_cuda();
+                    let mut array = ptr::null_mut();
+                    cuda_call!(libcuda.cuArray3DCreate_v2(&mut array, &descriptor));
+                    DeviceBuffer::Array { array, descriptor }
+                }
+                None => {
+                    let mut ptr = CUdeviceptr_v2(ptr::null_mut());
+                    cuda_call!(libcuda.cuMemAlloc_v2(&mut ptr, data.len()));
+                    DeviceBuffer::Linear {
+                        ptr,
+                        size: data.len(),
+                        owned: true,
+                    }
+                }
+            };
+            replay.buffers.insert(*key, buffer);
+        }
+        copy_to_device(libcuda, &replay.buffers[key], data)?;
+    }
+    for (name, texref_details) in kernel_launch.texrefs.iter() {
+        set_texref(
+            libcuda,
+            replay.module,
+            &replay.buffers,
+            name,
+            texref_details,
+        )?;
+    }
+    for (handle, texture) in kernel_launch.texture_objects.iter() {
+        let resource = resource_desc(&replay.buffers, &texture.resource)?;
+        let texture_desc = texture.to_cuda();
+        let mut object = 0;
+        cuda_call!(libcuda.cuTexObjectCreate(&mut object, &resource, &texture_desc, ptr::null()));
+        replay
+            .objects
+            .insert(*handle, DeviceObject::Texture(object));
+    }
+    for (handle, surface) in kernel_launch.surface_objects.iter() {
+        let resource = resource_desc(&replay.buffers, &surface.resource)?;
+        let mut object = 0;
+        cuda_call!(libcuda.cuSurfObjectCreate(&mut object, &resource));
+        replay
+            .objects
+            .insert(*handle, DeviceObject::Surface(object));
+    }
+    let buffers = &replay.buffers;
+    let objects = &replay.objects;
+    let mut arguments = kernel_launch
+        .explicit_arguments
+        .iter()
+        .map(|argument| patch_argument(buffers, objects, argument))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut argument_pointers = arguments
+        .iter_mut()
+        .map(|argument| argument.as_mut_ptr() as _)
+        .collect::<Vec<_>>();
+    let name = CString::new(kernel_launch.name)?;
+    let mut function = ptr::null_mut();
+    cuda_call!(libcuda.cuModuleGetFunction(&mut function, replay.module, name.as_ptr()));
+    let params = &kernel_launch.parameters;
+    cuda_call!(libcuda.cuLaunchKernel(
+        function,
+        params.gridDimX,
+        params.gridDimY,
+        params.gridDimZ,
+        params.blockDimX,
+        params.blockDimY,
+        params.blockDimZ,
+        params.sharedMemBytes,
+        ptr::null_mut(),
+        argument_pointers.as_mut_ptr(),
+        ptr::null_mut()
+    ));
+    cuda_call!(libcuda.cuCtxSynchronize());
+    let mut keys = post.keys().copied().collect::<Vec<_>>();
+    keys.sort();
+    let mut passed = true;
+    for key in keys {
+        let buffer = replay
+            .buffers
+            .get(&key)
+            .ok_or_else(|| format!("Buffer {} has no initial content", key))?;
+        let result = copy_from_device(libcuda, buffer)?;
+        passed &= compare(key, &post[&key], &result, tolerance);
+    }
+    Ok(passed)
+}
+
+fn load_buffers(dir: &Path) -> Result<HashMap<usize, Vec<u8>>, Box<dyn Error>> {
+    let mut result = HashMap::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let key = entry
+            .file_name()
+            .to_string_lossy()
+            .parse::<usize>()
+            .map_err(|_| format!("Unexpected file {}", entry.path().display()))?;
+        result.insert(key, fs::read(entry.path())?);
+    }
+    Ok(result)
+}
+
+fn set_texref(
+    libcuda: &mut CudaDynamicFns,
+    module: CUmodule,
+    buffers: &HashMap<usize, DeviceBuffer>,
+    name: &str,
+    details: &TexrefDetails,
+) -> Result<(), Box<dyn Error>> {
+    let name = CString::new(name)?;
+    let mut texref = ptr::null_mut();
+    cuda_call!(libcuda.cuModuleGetTexRef(&mut texref, module, name.as_ptr()));
+    cuda_call!(libcuda.cuTexRefSetFormat(
+        texref,
+        CUarray_format(details.format),
+        details.num_channels
+    ));
+    // Only the first two address modes are recorded
+    cuda_call!(libcuda.cuTexRefSetAddressMode(texref, 0, CUaddress_mode(details.address_mode[0])));
+    cuda_call!(libcuda.cuTexRefSetAddressMode(texref, 1, CUaddress_mode(details.address_mode[1])));
+    cuda_call!(libcuda.cuTexRefSetFilterMode(texref, CUfilter_mode(details.filter_mode)));
+    cuda_call!(libcuda.cuTexRefSetFlags(texref, details.flags));
+    cuda_call!(
+        libcuda.cuTexRefSetMipmapFilterMode(texref, CUfilter_mode(details.mipmap_filter_mode))
+    );
+    cuda_call!(libcuda.cuTexRefSetMipmapLevelBias(texref, details.mipmap_level_bias));
+    cuda_call!(libcuda.cuTexRefSetMipmapLevelClamp(
+        texref,
+        details.min_mipmap_level_clamp,
+        details.max_mipmap_level_clamp
+    ));
+    cuda_call!(libcuda.cuTexRefSetMaxAnisotropy(texref, details.max_anisotropy as u32));
+    let buffer = buffers
+        .get(&details.allocation.buffer_key)
+        .ok_or_else(|| format!("Texture {:?} has no allocation", name))?;
+    match (&details.address, buffer) {
+        (TexrefAddress::Array { flags }, DeviceBuffer::Array { array, .. }) => {
+            cuda_call!(libcuda.cuTexRefSetArray(texref, *array, *flags));
+        }
+        (TexrefAddress::OneD { bytes }, DeviceBuffer::Linear { .. }) => {
+            let mut offset = 0;
+            cuda_call!(libcuda.cuTexRefSetAddress_v2(
+                &mut offset,
+                texref,
+                buffer.pointer(details.allocation.offset_into_buffer)?,
+                *bytes
+            ));
+            if offset != 0 {
+                return Err(format!("Texture {:?} requires an offset of {}", name, offset).into());
+            }
+        }
+        (
+            TexrefAddress::TwoD {
+                width,
+                height,
+                format,
+                channels,
+                pitch,
+            },
+            DeviceBuffer::Linear { .. },
+        ) => {
+            let descriptor = CUDA_ARRAY_DESCRIPTOR {
+                Width: *width,
+                Height: *height,
+                Format: CUarray_format(*format),
+                NumChannels: *channels,
+            };
+            cuda_call!(libcuda.cuTexRefSetAddress2D_v3(
+                texref,
+                &descriptor,
+                buffer.pointer(details.allocation.offset_into_buffer)?,
+                *pitch
+            ));
+        }
+        _ => return Err(format!("Texture {:?} does not match its allocation", name).into()),
+    }
+    Ok(())
+}
+
+fn resource_desc(
+    buffers: &HashMap<usize, DeviceBuffer>,
+    resource: &ResourceDetails,
+) -> Result<CUDA_RESOURCE_DESC, Box<dyn Error>> {
+    let get_buffer = |allocation: &BufferRef| {
+        buffers
+            .get(&allocation.buffer_key)
+            .ok_or_else(|| format!("Unknown buffer {}", allocation.buffer_key))
+    };
+    let mut result = unsafe { mem::zeroed::<CUDA_RESOURCE_DESC>() };
+    match resource {
+        ResourceDetails::Array { allocation } => match get_buffer(allocation)? {
+            DeviceBuffer::Array { array, .. } => {
+                result.resType = CUresourcetype::CU_RESOURCE_TYPE_ARRAY;
+                result.res.array.hArray = *array;
+            }
+            DeviceBuffer::Linear { .. } => return Err("Linear buffer used as an array".into()),
+        },
+        ResourceDetails::Linear {
+            allocation,
+            format,
+            num_channels,
+            size_in_bytes,
+        } => {
+            result.resType = CUresourcetype::CU_RESOURCE_TYPE_LINEAR;
+            result.res.linear.devPtr =
+                get_buffer(allocation)?.pointer(allocation.offset_into_buffer)?;
+            result.res.linear.format = CUarray_format(*format);
+            result.res.linear.numChannels = *num_channels;
+            result.res.linear.sizeInBytes = *size_in_bytes;
+        }
+        ResourceDetails::Pitch2D {
+            allocation,
+            format,
+            num_channels,
+            width,
+            height,
+            pitch_in_bytes,
+        } => {
+            result.resType = CUresourcetype::CU_RESOURCE_TYPE_PITCH2D;
+            result.res.pitch2D.devPtr =
+                get_buffer(allocation)?.pointer(allocation.offset_into_buffer)?;
+            result.res.pitch2D.format = CUarray_format(*format);
+            result.res.pitch2D.numChannels = *num_channels;
+            result.res.pitch2D.width = *width;
+            result.res.pitch2D.height = *height;
+            result.res.pitch2D.pitchInBytes = *pitch_in_bytes;
+        }
+    }
+    Ok(result)
+}
+
+// Replaces recorded device pointers and texture or surface object handles
+// with the ones created for the replay
+fn patch_argument(
+    buffers: &HashMap<usize, DeviceBuffer>,
+    objects: &HashMap<u64, DeviceObject>,
+    argument: &ExplicitArgument,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut data = argument.data.ptr.clone();
+    if data.len() != argument.data.layout.size {
+        return Err("Argument size does not match its layout".into());
+    }
+    for argument_buffer in argument.buffers.iter() {
+        let buffer = buffers
+            .get(&argument_buffer.buffer.buffer_key)
+            .ok_or_else(|| format!("Unknown buffer {}", argument_buffer.buffer.buffer_key))?;
+        let pointer = buffer.pointer(argument_buffer.buffer.offset_into_buffer)?;
+        let offset = argument_buffer.offset_into_argument;
+        data[offset..offset + mem::size_of::<usize>()]
+            .copy_from_slice(&(pointer.0 as usize).to_ne_bytes());
+    }
+    for argument_object in argument.objects.iter() {
+        let object = objects
+            .get(&argument_object.handle)
+            .ok_or_else(|| format!("Unknown object {}", argument_object.handle))?;
+        let offset = argument_object.offset_into_argument;
+        data[offset..offset + mem::size_of::<u64>()]
+            .copy_from_slice(&object.handle().to_ne_bytes());
+    }
+    Ok(data)
+}
+
+fn copy_to_device(
+    libcuda: &mut CudaDynamicFns,
+    buffer: &DeviceBuffer,
+    data: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    match buffer {
+        DeviceBuffer::Linear { ptr, size, .. } => {
+            let size = cmp::min(*size, data.len());
+            cuda_call!(libcuda.cuMemcpyHtoD_v2(*ptr, data.as_ptr() as _, size));
+        }
+        DeviceBuffer::Array { array, descriptor } => {
+            if data.len() != array_buffer_size(descriptor)? {
+                return Err("Array size does not match its descriptor".into());
+            }
+            if descriptor.Height == 0 && descriptor.Depth == 0 {
+                cuda_call!(libcuda.cuMemcpyHtoA_v2(*array, 0, data.as_ptr() as _, data.len()));
+            } else {
+                let mut memcpy_descriptor = unsafe { mem::zeroed::<CUDA_MEMCPY3D>() };
+                set_memcpy(&mut memcpy_descriptor, descriptor)?;
+                memcpy_descriptor.srcMemoryType = CUmemorytype::CU_MEMORYTYPE_HOST;
+                memcpy_descriptor.srcHost = data.as_ptr() as _;
+                memcpy_descriptor.srcPitch = memcpy_descriptor.WidthInBytes;
+                memcpy_descriptor.srcHeight = memcpy_descriptor.Height;
+                memcpy_descriptor.dstMemoryType = CUmemorytype::CU_MEMORYTYPE_ARRAY;
+                memcpy_descriptor.dstArray = *array;
+                cuda_call!(libcuda.cuMemcpy3D_v2(&memcpy_descriptor));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn copy_from_device(
+    libcuda: &mut CudaDynamicFns,
+    buffer: &DeviceBuffer,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    match buffer {
+        DeviceBuffer::Linear { ptr, size, .. } => {
+            let mut result = vec![0u8; *size];
+            cuda_call!(libcuda.cuMemcpyDtoH_v2(result.as_mut_ptr() as _, *ptr, *size));
+            Ok(result)
+        }
+        DeviceBuffer::Array { array, descriptor } => {
+            let mut result = vec![0u8; array_buffer_size(descriptor)?];
+            if descriptor.Height == 0 && descriptor.Depth == 0 {
+                cuda_call!(libcuda.cuMemcpyAtoH_v2(
+                    result.as_mut_ptr() as _,
+                    *array,
+                    0,
+                    result.len()
+                ));
+            } else {
+                let mut memcpy_descriptor = unsafe { mem::zeroed::<CUDA_MEMCPY3D>() };
+                set_memcpy(&mut memcpy_descriptor, descriptor)?;
+                memcpy_descriptor.srcMemoryType = CUmemorytype::CU_MEMORYTYPE_ARRAY;
+                memcpy_descriptor.srcArray = *array;
+                memcpy_descriptor.dstMemoryType = CUmemorytype::CU_MEMORYTYPE_HOST;
+                memcpy_descriptor.dstHost = result.as_mut_ptr() as _;
+                memcpy_descriptor.dstPitch = memcpy_descriptor.WidthInBytes;
+                memcpy_descriptor.dstHeight = memcpy_descriptor.Height;
+                cuda_call!(libcuda.cuMemcpy3D_v2(&memcpy_descriptor));
+            }
+            Ok(result)
+        }
+    }
+}
+
+fn set_memcpy(
+    memcpy_descriptor: &mut CUDA_MEMCPY3D,
+    descriptor: &CUDA_ARRAY3D_DESCRIPTOR,
+) -> Result<(), Box<dyn Error>> {
+    memcpy_descriptor.WidthInBytes =
+        channel_size(descriptor.Format)? * descriptor.NumChannels as usize * descriptor.Width;
+    memcpy_descriptor.Height = cmp::max(descriptor.Height, 1);
+    memcpy_descriptor.Depth = cmp::max(descriptor.Depth, 1);
+    Ok(())
+}
+
+fn array_buffer_size(descriptor: &CUDA_ARRAY3D_DESCRIPTOR) -> Result<usize, Box<dyn Error>> {
+    Ok(descriptor.Width
+        * cmp::max(descriptor.Height, 1)
+        * cmp::max(descriptor.Depth, 1)
+        * descriptor.NumChannels as usize
+        * channel_size(descriptor.Format)?)
+}
+
+fn channel_size(format: CUarray_format) -> Result<usize, Box<dyn Error>> {
+    Ok(match format {
+        CUarray_format::CU_AD_FORMAT_UNSIGNED_INT8 | CUarray_format::CU_AD_FORMAT_SIGNED_INT8 => 1,
+        CUarray_format::CU_AD_FORMAT_UNSIGNED_INT16
+        | CUarray_format::CU_AD_FORMAT_SIGNED_INT16
+        | CUarray_format::CU_AD_FORMAT_HALF => 2,
+        CUarray_format::CU_AD_FORMAT_UNSIGNED_INT32
+        | CUarray_format::CU_AD_FORMAT_SIGNED_INT32
+        | CUarray_format::CU_AD_FORMAT_FLOAT => 4,
+        _ => return Err(format!("Unsupported array format {}", format.0).into()),
+    })
+}
+
+// Prints mismatched elements and returns true if the buffers are equal within
+// the tolerance
+fn compare(key: usize, expected: &[u8], result: &[u8], tolerance: &Tolerance) -> bool {
+    if expected.len() != result.len() {
+        println!(
+            "    {}: expected {} bytes, got {}",
+            key,
+            expected.len(),
+            result.len()
+        );
+        return false;
+    }
+    let element_size = match tolerance.element_type {
+        ElementType::Bytes => 1,
+        ElementType::F32 => mem::size_of::<f32>(),
+        ElementType::F64 => mem::size_of::<f64>(),
+    };
+    let mut mismatches = 0usize;
+    for (index, (expected, result)) in expected
+        .chunks(element_size)
+        .zip(result.chunks(element_size))
+        .enumerate()
+    {
+        // Trailing bytes that do not form a whole element are compared exactly
+        let equal = match (tolerance.element_type, expected.len() == element_size) {
+            (ElementType::F32, true) => tolerance.accepts(
+                f32::from_ne_bytes(expected.try_into().unwrap()) as f64,
+                f32::from_ne_bytes(result.try_into().unwrap()) as f64,
+            ),
+            (ElementType::F64, true) => tolerance.accepts(
+                f64::from_ne_bytes(expected.try_into().unwrap()),
+                f64::from_ne_bytes(result.try_into().unwrap()),
+            ),
+            _ => expected == result,
+        };
+        if !equal {
+            if mismatches < tolerance.max_mismatches {
+                println!(
+                    "    {}[{}]: expected {}, got {}",
+                    key,
+                    index * element_size,
+                    format_element(tolerance.element_type, expected),
+                    format_element(tolerance.element_type, result)
+                );
+            }
+            mismatches += 1;
+        }
+    }
+    if mismatches != 0 {
+        println!("    {}: {} mismatched elements", key, mismatches);
+    }
+    mismatches == 0
+}
+
+fn format_element(element_type: ElementType, bytes: &[u8]) -> String {
+    match (element_type, bytes.len()) {
+        (ElementType::F32, 4) => f32::from_ne_bytes(bytes.try_into().unwrap()).to_string(),
+        (ElementType::F64, 8) => f64::from_ne_bytes(bytes.try_into().unwrap()).to_string(),
+        _ => format!("{:02x?}", bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add_one_dump() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/replay/add_one")
+    }
+
+    #[test]
+    fn patches_recorded_pointers() {
+        let dump = add_one_dump();
+        let kernel_launch: KernelLaunch =
+            serde_json::from_reader(fs::File::open(dump.join("kernel_launch.json")).unwrap())
+                .unwrap();
+        let pre = load_buffers(&dump.join("pre")).unwrap();
+        let mut keys = pre.keys().copied().collect::<Vec<_>>();
+        keys.sort();
+        let buffers = keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| {
+                let buffer = DeviceBuffer::Linear {
+                    ptr: CUdeviceptr_v2((0x1000 * (index + 1)) as _),
+                    size: pre[key].len(),
+                    owned: false,
+                };
+                (*key, buffer)
+            })
+            .collect::<HashMap<_, _>>();
+        let arguments = kernel_launch
+            .explicit_arguments
+            .iter()
+            .map(|argument| patch_argument(&buffers, &HashMap::new(), argument).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            arguments,
+            vec![
+                0x1000usize.to_ne_bytes().to_vec(),
+                0x2000usize.to_ne_bytes().to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn compares_with_tolerance() {
+        let tolerance = Tolerance {
+            element_type: ElementType::F32,
+            absolute: 0.5,
+            relative: 0.0,
+            max_mismatches: 10,
+        };
+        let expected = [1.0f32.to_ne_bytes(), 2.0f32.to_ne_bytes()].concat();
+        let close = [1.25f32.to_ne_bytes(), 2.0f32.to_ne_bytes()].concat();
+        let far = [1.0f32.to_ne_bytes(), 3.0f32.to_ne_bytes()].concat();
+        assert!(compare(0, &expected, &close, &tolerance));
+        assert!(!compare(0, &expected, &far, &tolerance));
+    }
+
+    #[test]
+    fn rejects_unsupported_array_format() {
+        let descriptor = CUDA_ARRAY3D_DESCRIPTOR {
+            Width: 1,
+            Height: 0,
+            Depth: 0,
+            Format: CUarray_format(0x1234),
+            NumChannels: 1,
+            Flags: 0,
+        };
+        assert!(array_buffer_size(&descriptor).is_err());
+    }
+}
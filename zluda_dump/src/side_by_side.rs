@@ -1,7 +1,10 @@
 // Side-by-side really does three complex operations:
 // * Copy unstructured CUDA arguments to structured host representation. In
 //   practice, it means inspecting arguments to cuKernelLaunch(...) - firstly
-//   copying all explicit arguments to the kernel, secondly copying all texrefs
+//   copying all explicit arguments to the kernel (together with texture and
+//   surface objects they refer to), secondly copying all texrefs. Graph
+//   launches are handled the same way, kernel node by kernel node, memset and
+//   memcpy nodes are replayed on the same memory
 // * Copy structured host representation to structured device representation.
 //   This device representation is in the "space" of a different CUDA driver
 // * Copy explicit device representation to structured host representation.
@@ -10,9 +13,9 @@
 use crate::cuda_call;
 use crate::log::LogEntry;
 use crate::trace::serialize_array_format;
-use crate::trace::DumpWriter;
 use crate::trace::KernelLaunchParams;
 use crate::trace::RecordedFunction;
+use crate::trace::RecordedTextureObject;
 use crate::trace::TexrefAddress;
 use crate::try_get_cuda_function;
 use crate::CudaDynamicFns;
@@ -29,6 +32,7 @@ use std::borrow::Cow;
 use std::cmp;
 use std::collections::hash_map;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::ffi::CStr;
 use std::ffi::CString;
 use std::mem;
@@ -167,6 +171,37 @@ pub(crate) unsafe fn pre_kernel_launch(
     extra: *mut *mut ::std::os::raw::c_void,
 ) -> Option<HostArguments> {
     let side_by_side = side_by_side.as_ref()?;
+    // Kernels launched during stream capture are compared when the graph is
+    // launched
+    if fn_logger.log_unwrap(is_capturing(libcuda, stream))? {
+        return None;
+    }
+    let recorded_fn = get_recorded_function(state, side_by_side, fn_logger, f)?;
+    fn_logger.log_unwrap(synchronize(libcuda))?;
+    let mut memory_allocations = HostMemoryAllocations::new();
+    let arguments = save_kernel_arguments(
+        libcuda,
+        state,
+        fn_logger,
+        stream,
+        recorded_fn,
+        kernel_params,
+        extra,
+        &mut memory_allocations,
+    )?;
+    Some(HostArguments {
+        arguments,
+        memory_allocations,
+    })
+}
+
+// Returns None if the function should not be compared
+fn get_recorded_function<'a>(
+    state: &'a trace::StateTracker,
+    side_by_side: &SideBySide,
+    fn_logger: &mut log::FunctionLogger,
+    f: CUfunction,
+) -> Option<&'a RecordedFunction> {
     let recorded_fn = if let Some(parsed_fn) = state.functions.get(&f) {
         parsed_fn
     } else {
@@ -178,35 +213,24 @@ pub(crate) unsafe fn pre_kernel_launch(
             return None;
         }
     }
-    let parsed_fn = recorded_fn.parsed.as_ref()?;
-    let texrefs = fn_logger.log_unwrap(state.get_texrefs(recorded_fn.module))?;
-    let globals = fn_logger.log_unwrap(state.get_globals(recorded_fn.module))?;
-    fn_logger.log_unwrap(synchronize(libcuda))?;
-    save_kernel_arguments(
-        libcuda,
-        fn_logger,
-        stream,
-        recorded_fn.module,
-        texrefs,
-        globals,
-        &parsed_fn.explicit_arguments,
-        kernel_params,
-        extra,
-    )
+    recorded_fn.parsed.as_ref()?;
+    Some(recorded_fn)
 }
 
-unsafe fn save_kernel_arguments<'a>(
+unsafe fn save_kernel_arguments(
     libcuda: &mut CudaDynamicFns,
+    state: &trace::StateTracker,
     fn_logger: &mut log::FunctionLogger,
     stream: CUstream,
-    module: CUmodule,
-    get_texrefs: impl Iterator<Item = (&'a CStr, CUtexref, TexrefAddress)>,
-    get_globals: impl Iterator<Item = (&'a CStr, CUdeviceptr)>,
-    args_layout: &[Layout],
+    recorded_fn: &RecordedFunction,
     kernel_params: *mut *mut ::std::os::raw::c_void,
     extra: *mut *mut ::std::os::raw::c_void,
-) -> Option<HostArguments> {
-    let mut memory_allocations = HostMemoryAllocations::new();
+    memory_allocations: &mut HostMemoryAllocations,
+) -> Option<KernelArguments> {
+    let args_layout = &recorded_fn.parsed.as_ref()?.explicit_arguments;
+    let get_texrefs = fn_logger.log_unwrap(state.get_texrefs(recorded_fn.module))?;
+    let get_globals = fn_logger.log_unwrap(state.get_globals(recorded_fn.module))?;
+    let mut objects = HostObjects::new(state);
     if extra == ptr::null_mut() {
         let explicit_arguments = if kernel_params == ptr::null_mut() {
             Vec::new()
@@ -220,7 +244,8 @@ unsafe fn save_kernel_arguments<'a>(
                         ExplicitArgument::new(
                             libcuda,
                             stream,
-                            &mut memory_allocations,
+                            memory_allocations,
+                            &mut objects,
                             *arg_layout,
                             raw_argument,
                         )
@@ -237,7 +262,7 @@ unsafe fn save_kernel_arguments<'a>(
                         texref_name,
                         texref,
                         address,
-                        &mut memory_allocations,
+                        memory_allocations,
                     )
                 })
                 .collect::<Result<HashMap<_, _>, _>>(),
@@ -247,22 +272,424 @@ unsafe fn save_kernel_arguments<'a>(
                 .map(|(name, devptr)| {
                     Ok((
                         name.to_owned(),
-                        memory_allocations.alloc_global(libcuda, stream, module, name, devptr)?,
+                        memory_allocations.alloc_global(
+                            libcuda,
+                            stream,
+                            recorded_fn.module,
+                            name,
+                            devptr,
+                        )?,
                     ))
                 })
                 .collect::<Result<HashMap<_, _>, _>>(),
         )?;
-        Some(HostArguments {
+        Some(KernelArguments {
             explicit_arguments,
             texrefs: images,
             globals,
-            memory_allocations,
+            texture_objects: objects.texture_objects,
+            surface_objects: objects.surface_objects,
         })
     } else {
         unimplemented!()
     }
 }
 
+pub(crate) unsafe fn pre_graph_launch(
+    libcuda: &mut CudaDynamicFns,
+    state: &mut trace::StateTracker,
+    side_by_side: &mut Option<SideBySide>,
+    fn_logger: &mut log::FunctionLogger,
+    graph_exec: CUgraphExec,
+    stream: CUstream,
+) -> Option<GraphArguments> {
+    let side_by_side = side_by_side.as_ref()?;
+    let graph = if let Some(graph) = state.graphs.get(&graph_exec) {
+        graph
+    } else {
+        fn_logger.log(LogEntry::UnknownGraphExec(graph_exec));
+        return None;
+    };
+    // Graph is compared as a whole, we can't skip just some of its kernels
+    for node in graph.nodes.iter() {
+        if let GraphNodeParams::Kernel(kernel) = &node.params {
+            get_recorded_function(state, side_by_side, fn_logger, kernel.function)?;
+        }
+    }
+    fn_logger.log_unwrap(synchronize(libcuda))?;
+    let mut memory_allocations = HostMemoryAllocations::new();
+    let mut operations = Vec::new();
+    for node in graph.nodes.iter() {
+        match &node.params {
+            GraphNodeParams::Kernel(kernel) => {
+                let mut kernel_params = kernel
+                    .arguments
+                    .iter()
+                    .map(|argument| argument.ptr.as_ptr() as *mut c_void)
+                    .collect::<Vec<_>>();
+                let arguments = save_kernel_arguments(
+                    libcuda,
+                    state,
+                    fn_logger,
+                    stream,
+                    &state.functions[&kernel.function],
+                    kernel_params.as_mut_ptr(),
+                    ptr::null_mut(),
+                    &mut memory_allocations,
+                )?;
+                operations.push(GraphOperation::Kernel(GraphKernel {
+                    function: kernel.function,
+                    launch_params: kernel.launch_params,
+                    arguments,
+                }));
+            }
+            GraphNodeParams::Memset(params) => {
+                let dst = fn_logger
+                    .log_unwrap(memory_allocations.alloc_device(libcuda, stream, params.dst))?;
+                operations.push(GraphOperation::Memset {
+                    dst,
+                    params: *params,
+                });
+            }
+            GraphNodeParams::Memcpy(params) => {
+                let src = fn_logger.log_unwrap(memory_allocations.alloc_copy_side(
+                    libcuda,
+                    stream,
+                    params.srcMemoryType,
+                    params.srcDevice,
+                ))?;
+                let dst = fn_logger.log_unwrap(memory_allocations.alloc_copy_side(
+                    libcuda,
+                    stream,
+                    params.dstMemoryType,
+                    params.dstDevice,
+                ))?;
+                // Host memory is not compared and belongs to the application,
+                // so copies to host are not replayed
+                if let Some(dst) = dst {
+                    operations.push(GraphOperation::Memcpy {
+                        src,
+                        dst,
+                        params: *params,
+                    });
+                }
+            }
+        }
+    }
+    Some(GraphArguments {
+        operations,
+        memory_allocations,
+    })
+}
+
+pub(crate) unsafe fn record_graph_exec(
+    libcuda: &mut CudaDynamicFns,
+    state: &mut trace::StateTracker,
+    side_by_side: &Option<SideBySide>,
+    fn_logger: &mut log::FunctionLogger,
+    graph_exec: CUgraphExec,
+    graph: CUgraph,
+) {
+    if side_by_side.is_none() {
+        return;
+    }
+    if let Some(graph) = fn_logger.log_unwrap(copy_graph(libcuda, state, graph)) {
+        state.record_graph_exec(graph_exec, graph);
+    }
+}
+
+// Update copies parameters of nodes of the new graph into the instantiated
+// graph, but nodes keep being identified by handles from the graph it was
+// instantiated from
+pub(crate) unsafe fn update_graph_exec(
+    libcuda: &mut CudaDynamicFns,
+    state: &mut trace::StateTracker,
+    side_by_side: &Option<SideBySide>,
+    fn_logger: &mut log::FunctionLogger,
+    graph_exec: CUgraphExec,
+    graph: CUgraph,
+) {
+    if side_by_side.is_none() {
+        return;
+    }
+    let graph = match fn_logger.log_unwrap(copy_graph(libcuda, state, graph)) {
+        Some(graph) => graph,
+        None => return state.remove_graph_exec(graph_exec),
+    };
+    let previous_graph = match state.graphs.get(&graph_exec) {
+        Some(previous_graph) => previous_graph,
+        None => return,
+    };
+    match pair_graph_nodes(&previous_graph.dependencies, &graph.dependencies) {
+        Some(pairs) => {
+            let nodes = graph
+                .nodes
+                .into_iter()
+                .map(|node| GraphNode {
+                    node: pairs[&node.node],
+                    params: node.params,
+                })
+                .collect();
+            let dependencies = previous_graph.dependencies.clone();
+            state.record_graph_exec(
+                graph_exec,
+                RecordedGraph {
+                    nodes,
+                    dependencies,
+                },
+            );
+        }
+        // We don't know what the graph launches anymore
+        None => state.remove_graph_exec(graph_exec),
+    }
+}
+
+pub(crate) unsafe fn set_graph_exec_kernel_node_params(
+    state: &mut trace::StateTracker,
+    side_by_side: &Option<SideBySide>,
+    fn_logger: &mut log::FunctionLogger,
+    graph_exec: CUgraphExec,
+    node: CUgraphNode,
+    params: &CUDA_KERNEL_NODE_PARAMS,
+) {
+    if side_by_side.is_none() {
+        return;
+    }
+    let params = fn_logger
+        .log_unwrap(GraphKernelNode::new(state, params))
+        .map(GraphNodeParams::Kernel);
+    set_graph_exec_node_params(state, graph_exec, node, params);
+}
+
+pub(crate) fn set_graph_exec_memset_node_params(
+    state: &mut trace::StateTracker,
+    side_by_side: &Option<SideBySide>,
+    graph_exec: CUgraphExec,
+    node: CUgraphNode,
+    params: &CUDA_MEMSET_NODE_PARAMS,
+) {
+    if side_by_side.is_none() {
+        return;
+    }
+    set_graph_exec_node_params(
+        state,
+        graph_exec,
+        node,
+        Some(GraphNodeParams::Memset(*params)),
+    );
+}
+
+pub(crate) fn set_graph_exec_memcpy_node_params(
+    state: &mut trace::StateTracker,
+    side_by_side: &Option<SideBySide>,
+    graph_exec: CUgraphExec,
+    node: CUgraphNode,
+    params: &CUDA_MEMCPY3D,
+) {
+    if side_by_side.is_none() {
+        return;
+    }
+    set_graph_exec_node_params(
+        state,
+        graph_exec,
+        node,
+        Some(GraphNodeParams::Memcpy(*params)),
+    );
+}
+
+fn set_graph_exec_node_params(
+    state: &mut trace::StateTracker,
+    graph_exec: CUgraphExec,
+    node: CUgraphNode,
+    params: Option<GraphNodeParams>,
+) {
+    let graph = match state.graphs.get_mut(&graph_exec) {
+        Some(graph) => graph,
+        None => return,
+    };
+    match (
+        params,
+        graph
+            .nodes
+            .iter_mut()
+            .find(|old_node| old_node.node == node),
+    ) {
+        (Some(params), Some(old_node)) => old_node.params = params,
+        // We don't know what the graph launches anymore
+        _ => state.remove_graph_exec(graph_exec),
+    }
+}
+
+// Copies nodes that are replayed side-by-side in the order they can be
+// executed. Host nodes are skipped, host functions can't write device memory.
+// Event and semaphore nodes are skipped too, they only order work and side-by-
+// side replays all nodes in order anyway. Other nodes (child graphs, memory
+// allocations, ...) change what the kernels see and are not supported
+unsafe fn copy_graph(
+    libcuda: &mut CudaDynamicFns,
+    state: &trace::StateTracker,
+    graph: CUgraph,
+) -> Result<RecordedGraph, LogEntry> {
+    let dependencies = get_dependencies(libcuda, graph)?;
+    let mut nodes = Vec::new();
+    for node in topological_order(&dependencies) {
+        let mut node_type = CUgraphNodeType(0);
+        cuda_call!(libcuda.cuGraphNodeGetType(node, &mut node_type));
+        let params = match node_type {
+            CUgraphNodeType::CU_GRAPH_NODE_TYPE_KERNEL => {
+                let mut params = mem::zeroed::<CUDA_KERNEL_NODE_PARAMS>();
+                cuda_call!(libcuda.cuGraphKernelNodeGetParams_v2(node, &mut params));
+                GraphNodeParams::Kernel(GraphKernelNode::new(state, &params)?)
+            }
+            CUgraphNodeType::CU_GRAPH_NODE_TYPE_MEMSET => {
+                let mut params = mem::zeroed::<CUDA_MEMSET_NODE_PARAMS>();
+                cuda_call!(libcuda.cuGraphMemsetNodeGetParams(node, &mut params));
+                GraphNodeParams::Memset(params)
+            }
+            CUgraphNodeType::CU_GRAPH_NODE_TYPE_MEMCPY => {
+                let mut params = mem::zeroed::<CUDA_MEMCPY3D>();
+                cuda_call!(libcuda.cuGraphMemcpyNodeGetParams(node, &mut params));
+                GraphNodeParams::Memcpy(params)
+            }
+            CUgraphNodeType::CU_GRAPH_NODE_TYPE_EMPTY
+            | CUgraphNodeType::CU_GRAPH_NODE_TYPE_HOST
+            | CUgraphNodeType::CU_GRAPH_NODE_TYPE_EVENT_RECORD
+            | CUgraphNodeType::CU_GRAPH_NODE_TYPE_WAIT_EVENT
+            | CUgraphNodeType::CU_GRAPH_NODE_TYPE_EXT_SEMAS_SIGNAL
+            | CUgraphNodeType::CU_GRAPH_NODE_TYPE_EXT_SEMAS_WAIT => continue,
+            _ => return Err(LogEntry::UnsupportedGraphNode(node, node_type)),
+        };
+        nodes.push(GraphNode { node, params });
+    }
+    Ok(RecordedGraph {
+        nodes,
+        dependencies,
+    })
+}
+
+// Returns all nodes in creation order together with their dependencies
+unsafe fn get_dependencies(
+    libcuda: &mut CudaDynamicFns,
+    graph: CUgraph,
+) -> Result<Vec<(CUgraphNode, Vec<CUgraphNode>)>, LogEntry> {
+    let mut node_count = 0;
+    cuda_call!(libcuda.cuGraphGetNodes(graph, ptr::null_mut(), &mut node_count));
+    let mut nodes = vec![ptr::null_mut(); node_count];
+    cuda_call!(libcuda.cuGraphGetNodes(graph, nodes.as_mut_ptr(), &mut node_count));
+    nodes
+        .into_iter()
+        .map(|node| {
+            let mut dependency_count = 0;
+            cuda_call!(libcuda.cuGraphNodeGetDependencies(
+                node,
+                ptr::null_mut(),
+                &mut dependency_count
+            ));
+            let mut dependencies = vec![ptr::null_mut(); dependency_count];
+            cuda_call!(libcuda.cuGraphNodeGetDependencies(
+                node,
+                dependencies.as_mut_ptr(),
+                &mut dependency_count
+            ));
+            Ok((node, dependencies))
+        })
+        .collect()
+}
+
+fn topological_order(dependencies: &[(CUgraphNode, Vec<CUgraphNode>)]) -> Vec<CUgraphNode> {
+    let mut dependency_count = dependencies
+        .iter()
+        .map(|(node, node_dependencies)| (*node, node_dependencies.len()))
+        .collect::<HashMap<_, _>>();
+    let mut dependents = HashMap::<_, Vec<_>>::new();
+    for (node, node_dependencies) in dependencies.iter() {
+        for dependency in node_dependencies.iter() {
+            dependents.entry(*dependency).or_default().push(*node);
+        }
+    }
+    let mut ready = dependencies
+        .iter()
+        .filter(|(_, node_dependencies)| node_dependencies.is_empty())
+        .map(|(node, _)| *node)
+        .collect::<Vec<_>>();
+    let mut result = Vec::new();
+    while let Some(node) = ready.pop() {
+        result.push(node);
+        for dependent in dependents.get(&node).into_iter().flatten() {
+            let count = dependency_count.get_mut(dependent).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                ready.push(*dependent);
+            }
+        }
+    }
+    result
+}
+
+// Pairs nodes of the graph passed to cuGraphExecUpdate(...) (keys) with nodes
+// of the graph the executable graph was instantiated from (values) the same
+// way CUDA does: nodes with paired dependencies are paired in creation order.
+// Returns None if the graphs don't pair up
+fn pair_graph_nodes(
+    previous: &[(CUgraphNode, Vec<CUgraphNode>)],
+    current: &[(CUgraphNode, Vec<CUgraphNode>)],
+) -> Option<HashMap<CUgraphNode, CUgraphNode>> {
+    if previous.len() != current.len() {
+        return None;
+    }
+    let previous = sibling_indices(previous)
+        .into_iter()
+        .map(|(node, dependencies, index)| ((dependencies, index), node))
+        .collect::<HashMap<_, _>>();
+    let mut unpaired = sibling_indices(current);
+    let mut pairs = HashMap::new();
+    while !unpaired.is_empty() {
+        let (ready, waiting): (Vec<_>, Vec<_>) =
+            unpaired.into_iter().partition(|(_, dependencies, _)| {
+                dependencies
+                    .iter()
+                    .all(|dependency| pairs.contains_key(dependency))
+            });
+        if ready.is_empty() {
+            return None;
+        }
+        for (node, dependencies, index) in ready {
+            let dependencies = dependencies
+                .iter()
+                .map(|dependency| pairs[dependency])
+                .collect::<Vec<_>>();
+            pairs.insert(node, *previous.get(&(&dependencies[..], index))?);
+        }
+        unpaired = waiting;
+    }
+    Some(pairs)
+}
+
+// Index of every node among the nodes with the same dependencies
+fn sibling_indices(
+    nodes: &[(CUgraphNode, Vec<CUgraphNode>)],
+) -> Vec<(CUgraphNode, &[CUgraphNode], usize)> {
+    let mut counts = HashMap::new();
+    nodes
+        .iter()
+        .map(|(node, dependencies)| {
+            let count = counts.entry(&dependencies[..]).or_insert(0);
+            let index = *count;
+            *count += 1;
+            (*node, &dependencies[..], index)
+        })
+        .collect()
+}
+
+pub(crate) fn is_capturing(
+    libcuda: &mut CudaDynamicFns,
+    stream: CUstream,
+) -> Result<bool, LogEntry> {
+    let mut status = CUstreamCaptureStatus::CU_STREAM_CAPTURE_STATUS_NONE;
+    cuda_call!(libcuda.cuStreamIsCapturing(stream, &mut status));
+    Ok(status != CUstreamCaptureStatus::CU_STREAM_CAPTURE_STATUS_NONE)
+}
+
 fn synchronize(libcuda: &mut CudaDynamicFns) -> Result<(), LogEntry> {
     cuda_call!(libcuda.cuStreamSynchronize(ptr::null_mut()));
     Ok(())
@@ -329,6 +756,70 @@ impl HostMemoryAllocations {
             .transpose()?)
     }
 
+    fn alloc_device(
+        &mut self,
+        libcuda: &mut CudaDynamicFns,
+        stream: CUstream,
+        devptr: CUdeviceptr,
+    ) -> Result<BufferRef, LogEntry> {
+        self.try_alloc(libcuda, stream, devptr)?
+            .ok_or(LogEntry::UnknownAllocation(devptr))
+    }
+
+    // Returns None if the source or destination of a copy is in host memory
+    fn alloc_copy_side(
+        &mut self,
+        libcuda: &mut CudaDynamicFns,
+        stream: CUstream,
+        memory_type: CUmemorytype,
+        devptr: CUdeviceptr,
+    ) -> Result<Option<BufferRef>, LogEntry> {
+        match memory_type {
+            CUmemorytype::CU_MEMORYTYPE_HOST => Ok(None),
+            CUmemorytype::CU_MEMORYTYPE_DEVICE => {
+                Ok(Some(self.alloc_device(libcuda, stream, devptr)?))
+            }
+            CUmemorytype::CU_MEMORYTYPE_UNIFIED => self.try_alloc(libcuda, stream, devptr),
+            _ => Err(LogEntry::ErrorBox(
+                "Memcpy nodes with CUDA arrays are not supported".into(),
+            )),
+        }
+    }
+
+    // Copies current contents of the same allocations
+    fn copy_again(&self, libcuda: &mut CudaDynamicFns, stream: CUstream) -> Result<Self, LogEntry> {
+        self.0
+            .iter()
+            .map(|(key, buffer)| {
+                let copy = match buffer {
+                    HostBuffer::Linear { data } => {
+                        let fake_buffer = DeviceBuffer::Linear {
+                            data: CUdeviceptr_v2(*key),
+                            size: data.len(),
+                            cuMemFree_v2: dont_drop_device_memory,
+                        };
+                        HostBuffer::Linear {
+                            data: BufferRef::copy(libcuda, stream, &fake_buffer)?,
+                        }
+                    }
+                    HostBuffer::Array { descriptor, .. } => {
+                        let fake_array = DeviceBuffer::Array {
+                            array: *key as _,
+                            cuArrayDestroy: dont_drop_array,
+                            descriptor: *descriptor,
+                        };
+                        HostBuffer::Array {
+                            data: BufferRef::copy(libcuda, stream, &fake_array)?,
+                            descriptor: *descriptor,
+                        }
+                    }
+                };
+                Ok((*key, copy))
+            })
+            .collect::<Result<_, _>>()
+            .map(HostMemoryAllocations)
+    }
+
     fn alloc_texref(
         &mut self,
         libcuda: &mut CudaDynamicFns,
@@ -340,31 +831,37 @@ impl HostMemoryAllocations {
                 .try_alloc(libcuda, stream, *pointer)
                 .transpose()
                 .unwrap(),
-            TexrefAddress::Array { array, .. } => {
-                let array: CUarray = *array;
-                match self.0.entry(array as _) {
-                    hash_map::Entry::Occupied(_) => {}
-                    hash_map::Entry::Vacant(entry) => {
-                        let mut descriptor = unsafe { mem::zeroed::<CUDA_ARRAY3D_DESCRIPTOR>() };
-                        cuda_call!(libcuda.cuArray3DGetDescriptor_v2(&mut descriptor, array));
-                        let fake_array = DeviceBuffer::Array {
-                            array,
-                            cuArrayDestroy: dont_drop_array,
-                            descriptor,
-                        };
-                        let buffer = BufferRef::copy(libcuda, stream, &fake_array)?;
-                        entry.insert(HostBuffer::Array {
-                            data: buffer,
-                            descriptor: descriptor,
-                        });
-                    }
-                }
-                Ok(BufferRef {
-                    buffer_key: array as _,
-                    offset_into_buffer: 0,
-                })
+            TexrefAddress::Array { array, .. } => self.alloc_array(libcuda, stream, *array),
+        }
+    }
+
+    fn alloc_array(
+        &mut self,
+        libcuda: &mut CudaDynamicFns,
+        stream: CUstream,
+        array: CUarray,
+    ) -> Result<BufferRef, LogEntry> {
+        match self.0.entry(array as _) {
+            hash_map::Entry::Occupied(_) => {}
+            hash_map::Entry::Vacant(entry) => {
+                let mut descriptor = unsafe { mem::zeroed::<CUDA_ARRAY3D_DESCRIPTOR>() };
+                cuda_call!(libcuda.cuArray3DGetDescriptor_v2(&mut descriptor, array));
+                let fake_array = DeviceBuffer::Array {
+                    array,
+                    cuArrayDestroy: dont_drop_array,
+                    descriptor,
+                };
+                let buffer = BufferRef::copy(libcuda, stream, &fake_array)?;
+                entry.insert(HostBuffer::Array {
+                    data: buffer,
+                    descriptor: descriptor,
+                });
             }
         }
+        Ok(BufferRef {
+            buffer_key: array as _,
+            offset_into_buffer: 0,
+        })
     }
 
     fn alloc_global(
@@ -405,13 +902,116 @@ extern "system" fn dont_drop_array(_array: CUarray) -> CUresult {
 
 #[derive(Serialize)]
 pub(crate) struct HostArguments {
+    #[serde(flatten)]
+    arguments: KernelArguments,
+    #[serde(skip)]
+    pub(crate) memory_allocations: HostMemoryAllocations,
+}
+
+// Memory allocations are not part of kernel arguments, because kernels
+// launched together in a graph share them
+#[derive(Serialize)]
+pub(crate) struct KernelArguments {
     explicit_arguments: Vec<ExplicitArgument>,
     #[serde(serialize_with = "serialize_hashmap_cstring")]
     texrefs: HashMap<CString, TexrefDetails>,
     #[serde(serialize_with = "serialize_hashmap_cstring")]
     globals: HashMap<CString, BufferRef>,
-    #[serde(skip)]
-    pub(crate) memory_allocations: HostMemoryAllocations,
+    texture_objects: HashMap<CUtexObject, TextureObjectDetails>,
+    surface_objects: HashMap<CUsurfObject, SurfaceObjectDetails>,
+}
+
+pub(crate) struct GraphArguments {
+    operations: Vec<GraphOperation>,
+    memory_allocations: HostMemoryAllocations,
+}
+
+enum GraphOperation {
+    Kernel(GraphKernel),
+    Memset {
+        dst: BufferRef,
+        params: CUDA_MEMSET_NODE_PARAMS,
+    },
+    // Source is None for copies from host memory
+    Memcpy {
+        src: Option<BufferRef>,
+        dst: BufferRef,
+        params: CUDA_MEMCPY3D,
+    },
+}
+
+struct GraphKernel {
+    function: CUfunction,
+    launch_params: KernelLaunchParams,
+    arguments: KernelArguments,
+}
+
+// Instantiated graph. Nodes are copied, because the source graph can be
+// changed or destroyed before the graph is launched
+pub(crate) struct RecordedGraph {
+    // Nodes replayed side-by-side, in the order they can be executed
+    nodes: Vec<GraphNode>,
+    // All nodes of the source graph in creation order with their
+    // dependencies, used to pair nodes when the graph is updated
+    dependencies: Vec<(CUgraphNode, Vec<CUgraphNode>)>,
+}
+
+struct GraphNode {
+    node: CUgraphNode,
+    params: GraphNodeParams,
+}
+
+enum GraphNodeParams {
+    Kernel(GraphKernelNode),
+    Memset(CUDA_MEMSET_NODE_PARAMS),
+    Memcpy(CUDA_MEMCPY3D),
+}
+
+struct GraphKernelNode {
+    function: CUfunction,
+    launch_params: KernelLaunchParams,
+    // Empty if the function was not parsed, such functions are not compared
+    arguments: Vec<AlignedBuffer>,
+}
+
+impl GraphKernelNode {
+    unsafe fn new(
+        state: &trace::StateTracker,
+        params: &CUDA_KERNEL_NODE_PARAMS,
+    ) -> Result<Self, LogEntry> {
+        if params.extra != ptr::null_mut() {
+            return Err(LogEntry::ErrorBox(
+                "Kernel nodes with arguments passed in `extra` are not supported".into(),
+            ));
+        }
+        let args_layout = match state.functions.get(&params.func) {
+            Some(RecordedFunction {
+                parsed: Some(parsed_fn),
+                ..
+            }) if params.kernelParams != ptr::null_mut() => &parsed_fn.explicit_arguments[..],
+            _ => &[],
+        };
+        let arguments = args_layout
+            .iter()
+            .enumerate()
+            .map(|(index, arg_layout)| {
+                AlignedBuffer::new(*arg_layout, *params.kernelParams.add(index))
+            })
+            .collect();
+        Ok(Self {
+            function: params.func,
+            launch_params: KernelLaunchParams {
+                gridDimX: params.gridDimX,
+                gridDimY: params.gridDimY,
+                gridDimZ: params.gridDimZ,
+                blockDimX: params.blockDimX,
+                blockDimY: params.blockDimY,
+                blockDimZ: params.blockDimZ,
+                sharedMemBytes: params.sharedMemBytes,
+            },
+            arguments,
+        })
+    }
 }
 
 fn serialize_hashmap_cstring<V, S>(
@@ -433,9 +1033,12 @@ where
 // Every argument is a byte array with certain layout requirements.
 // The byte array can contain pointers to buffers, which we try to identify and
 // extract
+// Texture and surface objects are identified the same way: every value that
+// is not a pointer, but is equal to a live object handle is assumed to be one
 #[derive(Clone, Serialize)]
 pub(crate) struct ExplicitArgument {
     buffers: Vec<ExplicitArgumentBuffer>,
+    objects: Vec<ExplicitArgumentObject>,
     data: AlignedBuffer,
 }
 
@@ -444,24 +1047,40 @@ impl ExplicitArgument {
         libcuda: &mut CudaDynamicFns,
         stream: CUstream,
         unique_buffers: &mut HostMemoryAllocations,
+        unique_objects: &mut HostObjects,
         layout: Layout,
         raw_data: *mut c_void,
     ) -> Result<Self, LogEntry> {
         let data = AlignedBuffer::new(layout, raw_data);
-        let buffers = std::iter::successors(Some(0), |x| Some(x + mem::size_of::<usize>()))
+        let mut buffers = Vec::new();
+        let mut objects = Vec::new();
+        for offset in std::iter::successors(Some(0), |x| Some(x + mem::size_of::<usize>()))
             .take_while(|offset| offset + mem::size_of::<usize>() <= layout.size())
-            .filter_map(|potential_pointer_offset| {
-                ExplicitArgumentBuffer::try_new(
-                    libcuda,
-                    stream,
-                    unique_buffers,
-                    raw_data,
-                    potential_pointer_offset,
-                )
-                .transpose()
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-        Ok(Self { data, buffers })
+        {
+            match ExplicitArgumentBuffer::try_new(
+                libcuda,
+                stream,
+                unique_buffers,
+                raw_data,
+                offset,
+            )? {
+                Some(buffer) => buffers.push(buffer),
+                None => {
+                    let handle = ptr::read_unaligned(raw_data.add(offset) as *const u64);
+                    if unique_objects.try_alloc(libcuda, stream, unique_buffers, handle)? {
+                        objects.push(ExplicitArgumentObject {
+                            offset_into_argument: offset,
+                            handle,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(Self {
+            data,
+            buffers,
+            objects,
+        })
     }
 
     fn as_ptr(&self) -> *mut c_void {
@@ -475,6 +1094,12 @@ struct ExplicitArgumentBuffer {
     buffer: BufferRef,
 }
 
+#[derive(Clone, Serialize)]
+struct ExplicitArgumentObject {
+    offset_into_argument: usize,
+    handle: u64,
+}
+
 impl ExplicitArgumentBuffer {
     unsafe fn try_new(
         libcuda: &mut CudaDynamicFns,
@@ -493,6 +1118,231 @@ impl ExplicitArgumentBuffer {
     }
 }
 
+// Texture and surface objects used by a single kernel launch, keyed by their
+// handles in the original driver
+struct HostObjects<'a> {
+    recorded_textures: &'a HashMap<CUtexObject, RecordedTextureObject>,
+    recorded_surfaces: &'a HashMap<CUsurfObject, CUDA_RESOURCE_DESC>,
+    texture_objects: HashMap<CUtexObject, TextureObjectDetails>,
+    surface_objects: HashMap<CUsurfObject, SurfaceObjectDetails>,
+}
+
+impl<'a> HostObjects<'a> {
+    fn new(state: &'a trace::StateTracker) -> Self {
+        Self {
+            recorded_textures: &state.texture_objects,
+            recorded_surfaces: &state.surface_objects,
+            texture_objects: HashMap::new(),
+            surface_objects: HashMap::new(),
+        }
+    }
+
+    // Returns false if the handle is neither a texture nor a surface object
+    unsafe fn try_alloc(
+        &mut self,
+        libcuda: &mut CudaDynamicFns,
+        stream: CUstream,
+        unique_buffers: &mut HostMemoryAllocations,
+        handle: u64,
+    ) -> Result<bool, LogEntry> {
+        if handle == 0 {
+            return Ok(false);
+        }
+        if self.texture_objects.contains_key(&handle) || self.surface_objects.contains_key(&handle)
+        {
+            return Ok(true);
+        }
+        if let Some(texture) = self.recorded_textures.get(&handle) {
+            let details = TextureObjectDetails::new(libcuda, stream, unique_buffers, texture)?;
+            self.texture_objects.insert(handle, details);
+            Ok(true)
+        } else if let Some(resource) = self.recorded_surfaces.get(&handle) {
+            let resource = ResourceDetails::new(libcuda, stream, unique_buffers, resource)?;
+            self.surface_objects
+                .insert(handle, SurfaceObjectDetails { resource });
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+enum ResourceDetails {
+    Array {
+        allocation: BufferRef,
+    },
+    Linear {
+        allocation: BufferRef,
+        #[serde(serialize_with = "serialize_array_format")]
+        format: CUarray_format,
+        num_channels: u32,
+        size_in_bytes: usize,
+    },
+    Pitch2D {
+        allocation: BufferRef,
+        #[serde(serialize_with = "serialize_array_format")]
+        format: CUarray_format,
+        num_channels: u32,
+        width: usize,
+        height: usize,
+        pitch_in_bytes: usize,
+    },
+}
+
+impl ResourceDetails {
+    unsafe fn new(
+        libcuda: &mut CudaDynamicFns,
+        stream: CUstream,
+        unique_buffers: &mut HostMemoryAllocations,
+        resource: &CUDA_RESOURCE_DESC,
+    ) -> Result<Self, LogEntry> {
+        Ok(match resource.resType {
+            CUresourcetype::CU_RESOURCE_TYPE_ARRAY => ResourceDetails::Array {
+                allocation: unique_buffers.alloc_array(
+                    libcuda,
+                    stream,
+                    resource.res.array.hArray,
+                )?,
+            },
+            CUresourcetype::CU_RESOURCE_TYPE_LINEAR => {
+                let linear = resource.res.linear;
+                ResourceDetails::Linear {
+                    allocation: unique_buffers
+                        .try_alloc(libcuda, stream, linear.devPtr)?
+                        .ok_or(LogEntry::UnknownAllocation(linear.devPtr))?,
+                    format: linear.format,
+                    num_channels: linear.numChannels,
+                    size_in_bytes: linear.sizeInBytes,
+                }
+            }
+            CUresourcetype::CU_RESOURCE_TYPE_PITCH2D => {
+                let pitch2d = resource.res.pitch2D;
+                ResourceDetails::Pitch2D {
+                    allocation: unique_buffers
+                        .try_alloc(libcuda, stream, pitch2d.devPtr)?
+                        .ok_or(LogEntry::UnknownAllocation(pitch2d.devPtr))?,
+                    format: pitch2d.format,
+                    num_channels: pitch2d.numChannels,
+                    width: pitch2d.width,
+                    height: pitch2d.height,
+                    pitch_in_bytes: pitch2d.pitchInBytes,
+                }
+            }
+            resource_type => return Err(LogEntry::UnsupportedResourceType(resource_type)),
+        })
+    }
+
+    fn to_device(
+        &self,
+        device_allocations: &HashMap<*mut c_void, DeviceBuffer>,
+    ) -> CUDA_RESOURCE_DESC {
+        let mut result = unsafe { mem::zeroed::<CUDA_RESOURCE_DESC>() };
+        match self {
+            ResourceDetails::Array { allocation } => {
+                result.resType = CUresourcetype::CU_RESOURCE_TYPE_ARRAY;
+                match &device_allocations[&allocation.buffer_key] {
+                    DeviceBuffer::Array { array, .. } => result.res.array.hArray = *array,
+                    DeviceBuffer::Linear { .. } => unreachable!(),
+                }
+            }
+            ResourceDetails::Linear {
+                allocation,
+                format,
+                num_channels,
+                size_in_bytes,
+            } => {
+                result.resType = CUresourcetype::CU_RESOURCE_TYPE_LINEAR;
+                result.res.linear.devPtr = allocation.device_ptr(device_allocations);
+                result.res.linear.format = *format;
+                result.res.linear.numChannels = *num_channels;
+                result.res.linear.sizeInBytes = *size_in_bytes;
+            }
+            ResourceDetails::Pitch2D {
+                allocation,
+                format,
+                num_channels,
+                width,
+                height,
+                pitch_in_bytes,
+            } => {
+                result.resType = CUresourcetype::CU_RESOURCE_TYPE_PITCH2D;
+                result.res.pitch2D.devPtr = allocation.device_ptr(device_allocations);
+                result.res.pitch2D.format = *format;
+                result.res.pitch2D.numChannels = *num_channels;
+                result.res.pitch2D.width = *width;
+                result.res.pitch2D.height = *height;
+                result.res.pitch2D.pitchInBytes = *pitch_in_bytes;
+            }
+        }
+        result
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct TextureObjectDetails {
+    resource: ResourceDetails,
+    #[serde(serialize_with = "serialize_address_mode")]
+    address_mode: [CUaddress_mode; 3usize],
+    #[serde(serialize_with = "serialize_filter_mode")]
+    filter_mode: CUfilter_mode,
+    flags: ::std::os::raw::c_uint,
+    max_anisotropy: ::std::os::raw::c_uint,
+    #[serde(serialize_with = "serialize_filter_mode")]
+    mipmap_filter_mode: CUfilter_mode,
+    mipmap_level_bias: f32,
+    min_mipmap_level_clamp: f32,
+    max_mipmap_level_clamp: f32,
+    border_color: [f32; 4usize],
+    // Passed as-is to the side-by-side driver, but not dumped
+    #[serde(skip)]
+    view: Option<CUDA_RESOURCE_VIEW_DESC>,
+}
+
+impl TextureObjectDetails {
+    unsafe fn new(
+        libcuda: &mut CudaDynamicFns,
+        stream: CUstream,
+        unique_buffers: &mut HostMemoryAllocations,
+        recorded: &RecordedTextureObject,
+    ) -> Result<Self, LogEntry> {
+        let resource = ResourceDetails::new(libcuda, stream, unique_buffers, &recorded.resource)?;
+        let texture = &recorded.texture;
+        Ok(Self {
+            resource,
+            address_mode: texture.addressMode,
+            filter_mode: texture.filterMode,
+            flags: texture.flags,
+            max_anisotropy: texture.maxAnisotropy,
+            mipmap_filter_mode: texture.mipmapFilterMode,
+            mipmap_level_bias: texture.mipmapLevelBias,
+            min_mipmap_level_clamp: texture.minMipmapLevelClamp,
+            max_mipmap_level_clamp: texture.maxMipmapLevelClamp,
+            border_color: texture.borderColor,
+            view: recorded.view,
+        })
+    }
+
+    fn texture_desc(&self) -> CUDA_TEXTURE_DESC {
+        let mut result = unsafe { mem::zeroed::<CUDA_TEXTURE_DESC>() };
+        result.addressMode = self.address_mode;
+        result.filterMode = self.filter_mode;
+        result.flags = self.flags;
+        result.maxAnisotropy = self.max_anisotropy;
+        result.mipmapFilterMode = self.mipmap_filter_mode;
+        result.mipmapLevelBias = self.mipmap_level_bias;
+        result.minMipmapLevelClamp = self.min_mipmap_level_clamp;
+        result.maxMipmapLevelClamp = self.max_mipmap_level_clamp;
+        result.borderColor = self.border_color;
+        result
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct SurfaceObjectDetails {
+    resource: ResourceDetails,
+}
+
 #[derive(Clone, Serialize)]
 struct BufferRef {
     offset_into_buffer: usize,
@@ -508,6 +1358,15 @@ where
 }
 
 impl BufferRef {
+    fn device_ptr(&self, device_allocations: &HashMap<*mut c_void, DeviceBuffer>) -> CUdeviceptr {
+        match &device_allocations[&self.buffer_key] {
+            DeviceBuffer::Linear { data, .. } => {
+                CUdeviceptr_v2(unsafe { data.0.add(self.offset_into_buffer) })
+            }
+            DeviceBuffer::Array { .. } => unreachable!(),
+        }
+    }
+
     fn copy(
         libcuda: &mut CudaDynamicFns,
         stream: CUstream,
@@ -757,13 +1616,8 @@ pub(crate) unsafe fn post_kernel_launch(
     fn_logger: &mut log::FunctionLogger,
     pre_kernel_args: Option<HostArguments>,
     f: CUfunction,
-    grid_dim_x: ::std::os::raw::c_uint,
-    grid_dim_y: ::std::os::raw::c_uint,
-    grid_dim_z: ::std::os::raw::c_uint,
-    block_dim_x: ::std::os::raw::c_uint,
-    block_dim_y: ::std::os::raw::c_uint,
-    block_dim_z: ::std::os::raw::c_uint,
-    shared_mem_bytes: ::std::os::raw::c_uint,
+    launch_params: KernelLaunchParams,
+    cooperative: bool,
     stream: CUstream,
     kernel_params: *mut *mut ::std::os::raw::c_void,
     extra: *mut *mut ::std::os::raw::c_void,
@@ -777,135 +1631,275 @@ pub(crate) unsafe fn post_kernel_launch(
         return None;
     };
     let parsed_fn = recorded_fn.parsed.as_ref()?;
-    let texrefs = fn_logger.log_unwrap(state.get_texrefs(recorded_fn.module))?;
-    let globals = fn_logger.log_unwrap(state.get_globals(recorded_fn.module))?;
     fn_logger.log_unwrap(synchronize(libcuda))?;
-    let post_kernel_args_original = save_kernel_arguments(
+    let mut memory_allocations = HostMemoryAllocations::new();
+    let arguments = save_kernel_arguments(
         libcuda,
+        state,
         fn_logger,
         stream,
-        recorded_fn.module,
-        texrefs,
-        globals,
-        &parsed_fn.explicit_arguments,
+        recorded_fn,
         kernel_params,
         extra,
+        &mut memory_allocations,
     )?;
+    let post_kernel_args_original = HostArguments {
+        arguments,
+        memory_allocations,
+    };
     let _ctx = fn_logger.log_unwrap(side_by_side.activate_context())?;
-    let post_kernel_args_side_by_side = launch_kernel_and_get_results(
+    let post_kernel_args_side_by_side = replay_and_get_results(
         side_by_side,
-        recorded_fn,
         fn_logger,
-        pre_args,
-        f,
-        grid_dim_x,
-        grid_dim_y,
-        grid_dim_z,
-        block_dim_x,
-        block_dim_y,
-        block_dim_z,
-        shared_mem_bytes,
+        &pre_args.memory_allocations,
+        &[SideBySideOperation::Kernel(SideBySideKernel {
+            recorded_fn,
+            function: f,
+            arguments: &pre_args.arguments,
+            launch_params,
+            cooperative,
+        })],
     )?;
-    let launch_params = KernelLaunchParams {
-        gridDimX: grid_dim_x,
-        gridDimY: grid_dim_y,
-        gridDimZ: grid_dim_z,
-        blockDimX: block_dim_x,
-        blockDimY: block_dim_y,
-        blockDimZ: block_dim_z,
-        sharedMemBytes: shared_mem_bytes,
-    };
     if parsed_fn.text.len() != 1 {
         todo!("Linking not implemented");
     }
-    compare_and_notify_if_mismatch(
+    let should_dump = compare_and_notify_if_mismatch(
         fn_logger,
-        &recorded_fn.name,
-        &state.writer,
-        &post_kernel_args_original,
+        &post_kernel_args_original.memory_allocations,
         &post_kernel_args_side_by_side,
         side_by_side.dump_threshold,
-        &parsed_fn.text[0],
-        pre_args,
-        launch_params,
     );
+    if should_dump {
+        state.writer.save_kernel_launch(
+            fn_logger,
+            &recorded_fn.name,
+            &parsed_fn.text[0],
+            launch_params,
+            pre_args,
+            &post_kernel_args_original,
+        )
+    }
     Some(())
 }
 
-fn launch_kernel_and_get_results(
+// Kernels, memsets and memcpys in a graph are replayed one after another in
+// the side-by-side driver. Graph launches are compared, but never dumped
+pub(crate) unsafe fn post_graph_launch(
+    libcuda: &mut CudaDynamicFns,
+    state: &mut trace::StateTracker,
+    side_by_side: &mut Option<SideBySide>,
+    fn_logger: &mut log::FunctionLogger,
+    pre_graph_args: Option<GraphArguments>,
+    stream: CUstream,
+) -> Option<()> {
+    let pre_args = &pre_graph_args?;
+    let side_by_side = side_by_side.as_mut()?;
+    fn_logger.log_unwrap(synchronize(libcuda))?;
+    let post_memory_original =
+        fn_logger.log_unwrap(pre_args.memory_allocations.copy_again(libcuda, stream))?;
+    let operations = pre_args
+        .operations
+        .iter()
+        .map(|operation| {
+            Some(match operation {
+                GraphOperation::Kernel(kernel) => SideBySideOperation::Kernel(SideBySideKernel {
+                    recorded_fn: state.functions.get(&kernel.function)?,
+                    function: kernel.function,
+                    arguments: &kernel.arguments,
+                    launch_params: kernel.launch_params,
+                    cooperative: false,
+                }),
+                GraphOperation::Memset { dst, params } => {
+                    SideBySideOperation::Memset { dst, params }
+                }
+                GraphOperation::Memcpy { src, dst, params } => SideBySideOperation::Memcpy {
+                    src: src.as_ref(),
+                    dst,
+                    params,
+                },
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let _ctx = fn_logger.log_unwrap(side_by_side.activate_context())?;
+    let post_memory_side_by_side = replay_and_get_results(
+        side_by_side,
+        fn_logger,
+        &pre_args.memory_allocations,
+        &operations,
+    )?;
+    compare_and_notify_if_mismatch(
+        fn_logger,
+        &post_memory_original,
+        &post_memory_side_by_side,
+        None,
+    );
+    Some(())
+}
+
+struct SideBySideKernel<'a> {
+    recorded_fn: &'a RecordedFunction,
+    function: CUfunction,
+    arguments: &'a KernelArguments,
+    launch_params: KernelLaunchParams,
+    cooperative: bool,
+}
+
+enum SideBySideOperation<'a> {
+    Kernel(SideBySideKernel<'a>),
+    Memset {
+        dst: &'a BufferRef,
+        params: &'a CUDA_MEMSET_NODE_PARAMS,
+    },
+    Memcpy {
+        src: Option<&'a BufferRef>,
+        dst: &'a BufferRef,
+        params: &'a CUDA_MEMCPY3D,
+    },
+}
+
+// All operations share the same memory allocations and are replayed in order
+fn replay_and_get_results(
     side_by_side: &mut SideBySide,
-    recorded_fn: &RecordedFunction,
     fn_logger: &mut log::FunctionLogger,
-    host_args: &HostArguments,
-    original_func: CUfunction,
-    grid_dim_x: ::std::os::raw::c_uint,
-    grid_dim_y: ::std::os::raw::c_uint,
-    grid_dim_z: ::std::os::raw::c_uint,
-    block_dim_x: ::std::os::raw::c_uint,
-    block_dim_y: ::std::os::raw::c_uint,
-    block_dim_z: ::std::os::raw::c_uint,
-    shared_mem_bytes: ::std::os::raw::c_uint,
+    host_memory: &HostMemoryAllocations,
+    operations: &[SideBySideOperation],
 ) -> Option<HashMap<*mut c_void, Vec<u8>>> {
-    let parsed_fn = recorded_fn.parsed.as_ref()?;
-    if parsed_fn.text.len() != 1 {
-        todo!("Linking not implemented");
-    }
-    let (side_by_side_module, side_by_side_func) =
-        fn_logger.log_unwrap(side_by_side.get_module_and_function(
-            recorded_fn.module,
-            original_func,
-            &recorded_fn.name,
-            &parsed_fn.text[0],
+    let memory_allocations =
+        fn_logger.log_unwrap(alloc_device_memory(&mut side_by_side.fn_table, host_memory))?;
+    let mut modules_with_globals = HashSet::new();
+    for operation in operations {
+        let kernel = match operation {
+            SideBySideOperation::Kernel(kernel) => kernel,
+            SideBySideOperation::Memset { dst, params } => {
+                let dst = dst.device_ptr(&memory_allocations);
+                fn_logger.log_unwrap(memset(&mut side_by_side.fn_table, dst, params))?;
+                continue;
+            }
+            SideBySideOperation::Memcpy { src, dst, params } => {
+                let mut params = **params;
+                match src {
+                    Some(src) => {
+                        params.srcMemoryType = CUmemorytype::CU_MEMORYTYPE_DEVICE;
+                        params.srcDevice = src.device_ptr(&memory_allocations);
+                    }
+                    None => {
+                        // Unified memory that is not device memory is host memory
+                        if params.srcMemoryType == CUmemorytype::CU_MEMORYTYPE_UNIFIED {
+                            params.srcMemoryType = CUmemorytype::CU_MEMORYTYPE_HOST;
+                            params.srcHost = params.srcDevice.0;
+                        }
+                    }
+                }
+                params.dstMemoryType = CUmemorytype::CU_MEMORYTYPE_DEVICE;
+                params.dstDevice = dst.device_ptr(&memory_allocations);
+                fn_logger.log_unwrap(memcpy(&mut side_by_side.fn_table, &params))?;
+                continue;
+            }
+        };
+        let recorded_fn = kernel.recorded_fn;
+        let parsed_fn = recorded_fn.parsed.as_ref()?;
+        if parsed_fn.text.len() != 1 {
+            fn_logger.log(LogEntry::ErrorBox(
+                format!(
+                    "Side-by-side comparison of function {} from a linked module is not supported",
+                    recorded_fn.name
+                )
+                .into(),
+            ));
+            return None;
+        }
+        let (side_by_side_module, side_by_side_func) =
+            fn_logger.log_unwrap(side_by_side.get_module_and_function(
+                recorded_fn.module,
+                kernel.function,
+                &recorded_fn.name,
+                &parsed_fn.text[0],
+            ))?;
+        let side_by_side_fn_table = &mut side_by_side.fn_table;
+        let device_arguments = fn_logger.log_unwrap(DeviceArguments::new(
+            side_by_side_fn_table,
+            kernel.arguments,
+            &memory_allocations,
         ))?;
-    let side_by_side_fn_table = &mut side_by_side.fn_table;
-    let device_arguments =
-        fn_logger.log_unwrap(DeviceArguments::new(side_by_side_fn_table, &host_args))?;
-    fn_logger
-        .log_unwrap(device_arguments.bind_globals(side_by_side_fn_table, side_by_side_module))?;
-    let mut kernel_params = fn_logger.log_unwrap(
-        device_arguments.bind_texrefs_get_arguments(side_by_side_fn_table, side_by_side_module),
-    )?;
-    fn_logger.log_unwrap(launch_kernel(
-        side_by_side_fn_table,
-        side_by_side_func,
-        grid_dim_x,
-        grid_dim_y,
-        grid_dim_z,
-        block_dim_x,
-        block_dim_y,
-        block_dim_z,
-        shared_mem_bytes,
-        &mut kernel_params,
-    ))?;
-    fn_logger.log_unwrap(
-        device_arguments.copy_allocations_to_host(side_by_side_fn_table, ptr::null_mut()),
-    )
-}
-
-struct DeviceArguments {
+        // Later kernels from the same module must see globals written by
+        // earlier ones
+        if modules_with_globals.insert(side_by_side_module) {
+            fn_logger.log_unwrap(
+                device_arguments.bind_globals(side_by_side_fn_table, side_by_side_module),
+            )?;
+        }
+        let mut kernel_params = fn_logger.log_unwrap(
+            device_arguments.bind_texrefs_get_arguments(side_by_side_fn_table, side_by_side_module),
+        )?;
+        fn_logger.log_unwrap(launch_kernel(
+            side_by_side_fn_table,
+            side_by_side_func,
+            &kernel.launch_params,
+            kernel.cooperative,
+            &mut kernel_params,
+        ))?;
+    }
+    fn_logger.log_unwrap(copy_allocations_to_host(
+        &mut side_by_side.fn_table,
+        &memory_allocations,
+        ptr::null_mut(),
+    ))
+}
+
+fn alloc_device_memory(
+    libcuda: &mut CudaDynamicFns,
+    host: &HostMemoryAllocations,
+) -> Result<HashMap<*mut c_void, DeviceBuffer>, LogEntry> {
+    host.0
+        .iter()
+        .map(|(key, buff)| Ok((key.clone(), DeviceBuffer::new(libcuda, buff)?)))
+        .collect::<Result<_, LogEntry>>()
+}
+
+fn copy_allocations_to_host(
+    libcuda: &mut CudaDynamicFns,
+    memory_allocations: &HashMap<*mut c_void, DeviceBuffer>,
+    stream: CUstream,
+) -> Result<HashMap<*mut c_void, Vec<u8>>, LogEntry> {
+    memory_allocations
+        .iter()
+        .map(|(key, buffer)| Ok((*key, BufferRef::copy(libcuda, stream, buffer)?)))
+        .collect::<Result<HashMap<_, _>, LogEntry>>()
+}
+
+struct DeviceArguments<'a> {
     explicit_arguments: Vec<ExplicitArgument>,
-    texrefs: HashMap<CString, TexrefDetails>,
-    globals: HashMap<CString, BufferRef>,
-    memory_allocations: HashMap<*mut c_void, DeviceBuffer>,
+    texrefs: &'a HashMap<CString, TexrefDetails>,
+    globals: &'a HashMap<CString, BufferRef>,
+    memory_allocations: &'a HashMap<*mut c_void, DeviceBuffer>,
+    // Kept alive until the kernel has finished
+    _objects: HashMap<u64, DeviceObject>,
 }
 
-impl DeviceArguments {
-    fn new(libcuda: &mut CudaDynamicFns, host: &HostArguments) -> Result<Self, LogEntry> {
-        let memory_allocations = host
-            .memory_allocations
-            .0
-            .iter()
-            .map(|(key, buff)| Ok((key.clone(), DeviceBuffer::new(libcuda, buff)?)))
-            .collect::<Result<_, LogEntry>>()?;
+impl<'a> DeviceArguments<'a> {
+    fn new(
+        libcuda: &mut CudaDynamicFns,
+        host: &'a KernelArguments,
+        memory_allocations: &'a HashMap<*mut c_void, DeviceBuffer>,
+    ) -> Result<Self, LogEntry> {
         let mut explicit_arguments = host.explicit_arguments.clone();
-        Self::adjust_device_pointers(&mut explicit_arguments, &memory_allocations);
-        let texrefs = host.texrefs.clone();
-        let globals = host.globals.clone();
+        Self::adjust_device_pointers(&mut explicit_arguments, memory_allocations);
+        let mut objects = HashMap::new();
+        for (handle, texture) in host.texture_objects.iter() {
+            let object = DeviceObject::new_texture(libcuda, texture, memory_allocations)?;
+            objects.insert(*handle, object);
+        }
+        for (handle, surface) in host.surface_objects.iter() {
+            let object = DeviceObject::new_surface(libcuda, surface, memory_allocations)?;
+            objects.insert(*handle, object);
+        }
+        Self::adjust_object_handles(&mut explicit_arguments, &objects);
         Ok(DeviceArguments {
             explicit_arguments,
-            texrefs,
-            globals,
+            texrefs: &host.texrefs,
+            globals: &host.globals,
             memory_allocations,
+            _objects: objects,
         })
     }
 
@@ -934,6 +1928,22 @@ impl DeviceArguments {
         }
     }
 
+    fn adjust_object_handles(
+        explicit_arguments: &mut Vec<ExplicitArgument>,
+        objects: &HashMap<u64, DeviceObject>,
+    ) {
+        for arg in explicit_arguments.iter_mut() {
+            for object in &arg.objects {
+                unsafe {
+                    std::ptr::write_unaligned::<u64>(
+                        arg.data.ptr.as_ptr().add(object.offset_into_argument) as _,
+                        objects[&object.handle].handle(),
+                    )
+                };
+            }
+        }
+    }
+
     fn bind_texrefs_get_arguments(
         &self,
         libcuda: &mut CudaDynamicFns,
@@ -1045,17 +2055,6 @@ impl DeviceArguments {
         }
         Ok(())
     }
-
-    fn copy_allocations_to_host(
-        &self,
-        libcuda: &mut CudaDynamicFns,
-        stream: CUstream,
-    ) -> Result<HashMap<*mut c_void, Vec<u8>>, LogEntry> {
-        self.memory_allocations
-            .iter()
-            .map(|(key, buffer)| Ok((*key, BufferRef::copy(libcuda, stream, buffer)?)))
-            .collect::<Result<HashMap<_, _>, LogEntry>>()
-    }
 }
 
 #[allow(non_snake_case)]
@@ -1136,51 +2135,160 @@ impl Drop for DeviceBuffer {
     }
 }
 
+#[allow(non_snake_case)]
+enum DeviceObject {
+    Texture {
+        handle: CUtexObject,
+        cuTexObjectDestroy: extern "system" fn(CUtexObject) -> CUresult,
+    },
+    Surface {
+        handle: CUsurfObject,
+        cuSurfObjectDestroy: extern "system" fn(CUsurfObject) -> CUresult,
+    },
+}
+
+impl DeviceObject {
+    #[allow(non_snake_case)]
+    fn new_texture(
+        libcuda: &mut CudaDynamicFns,
+        texture: &TextureObjectDetails,
+        device_allocations: &HashMap<*mut c_void, DeviceBuffer>,
+    ) -> Result<Self, LogEntry> {
+        let cuTexObjectDestroy = try_get_cuda_function!(libcuda, cuTexObjectDestroy)?;
+        let resource = texture.resource.to_device(device_allocations);
+        let texture_desc = texture.texture_desc();
+        let view = texture
+            .view
+            .as_ref()
+            .map_or(ptr::null(), |view| view as *const _);
+        let mut handle = 0;
+        cuda_call!(libcuda.cuTexObjectCreate(&mut handle, &resource, &texture_desc, view));
+        Ok(DeviceObject::Texture {
+            handle,
+            cuTexObjectDestroy,
+        })
+    }
+
+    #[allow(non_snake_case)]
+    fn new_surface(
+        libcuda: &mut CudaDynamicFns,
+        surface: &SurfaceObjectDetails,
+        device_allocations: &HashMap<*mut c_void, DeviceBuffer>,
+    ) -> Result<Self, LogEntry> {
+        let cuSurfObjectDestroy = try_get_cuda_function!(libcuda, cuSurfObjectDestroy)?;
+        let resource = surface.resource.to_device(device_allocations);
+        let mut handle = 0;
+        cuda_call!(libcuda.cuSurfObjectCreate(&mut handle, &resource));
+        Ok(DeviceObject::Surface {
+            handle,
+            cuSurfObjectDestroy,
+        })
+    }
+
+    fn handle(&self) -> u64 {
+        match self {
+            DeviceObject::Texture { handle, .. } | DeviceObject::Surface { handle, .. } => *handle,
+        }
+    }
+}
+
+impl Drop for DeviceObject {
+    #[allow(unused_must_use)]
+    fn drop(&mut self) {
+        match self {
+            DeviceObject::Texture {
+                handle,
+                cuTexObjectDestroy,
+            } => {
+                (cuTexObjectDestroy)(*handle);
+            }
+            DeviceObject::Surface {
+                handle,
+                cuSurfObjectDestroy,
+            } => {
+                (cuSurfObjectDestroy)(*handle);
+            }
+        }
+    }
+}
+
 fn launch_kernel(
     fn_table: &mut CudaDynamicFns,
     side_by_side_func: CUfunction,
-    grid_dim_x: u32,
-    grid_dim_y: u32,
-    grid_dim_z: u32,
-    block_dim_x: u32,
-    block_dim_y: u32,
-    block_dim_z: u32,
-    shared_mem_bytes: u32,
+    launch_params: &KernelLaunchParams,
+    cooperative: bool,
     kernel_params: &mut [*mut c_void],
 ) -> Result<(), LogEntry> {
-    cuda_call!(fn_table.cuLaunchKernel(
-        side_by_side_func,
-        grid_dim_x,
-        grid_dim_y,
-        grid_dim_z,
-        block_dim_x,
-        block_dim_y,
-        block_dim_z,
-        shared_mem_bytes,
-        ptr::null_mut(),
-        kernel_params.as_mut_ptr(),
-        ptr::null_mut()
-    ));
+    if cooperative {
+        cuda_call!(fn_table.cuLaunchCooperativeKernel(
+            side_by_side_func,
+            launch_params.gridDimX,
+            launch_params.gridDimY,
+            launch_params.gridDimZ,
+            launch_params.blockDimX,
+            launch_params.blockDimY,
+            launch_params.blockDimZ,
+            launch_params.sharedMemBytes,
+            ptr::null_mut(),
+            kernel_params.as_mut_ptr()
+        ));
+    } else {
+        cuda_call!(fn_table.cuLaunchKernel(
+            side_by_side_func,
+            launch_params.gridDimX,
+            launch_params.gridDimY,
+            launch_params.gridDimZ,
+            launch_params.blockDimX,
+            launch_params.blockDimY,
+            launch_params.blockDimZ,
+            launch_params.sharedMemBytes,
+            ptr::null_mut(),
+            kernel_params.as_mut_ptr(),
+            ptr::null_mut()
+        ));
+    }
     cuda_call!(fn_table.cuStreamSynchronize(ptr::null_mut()));
     Ok(())
 }
 
+fn memset(
+    fn_table: &mut CudaDynamicFns,
+    dst: CUdeviceptr,
+    params: &CUDA_MEMSET_NODE_PARAMS,
+) -> Result<(), LogEntry> {
+    let (value, width, height) = (params.value, params.width, params.height);
+    // Pitch is unused if height is 1
+    let pitch = if height == 1 {
+        width * params.elementSize as usize
+    } else {
+        params.pitch
+    };
+    match params.elementSize {
+        1 => cuda_call!(fn_table.cuMemsetD2D8_v2(dst, pitch, value as u8, width, height)),
+        2 => cuda_call!(fn_table.cuMemsetD2D16_v2(dst, pitch, value as u16, width, height)),
+        4 => cuda_call!(fn_table.cuMemsetD2D32_v2(dst, pitch, value, width, height)),
+        _ => {
+            return Err(LogEntry::ErrorBox(
+                format!("Unsupported memset element size {}", params.elementSize).into(),
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn memcpy(fn_table: &mut CudaDynamicFns, params: &CUDA_MEMCPY3D) -> Result<(), LogEntry> {
+    cuda_call!(fn_table.cuMemcpy3D_v2(params));
+    Ok(())
+}
+
+// Returns true if the mismatch is above the dump threshold
 fn compare_and_notify_if_mismatch(
     fn_logger: &mut log::FunctionLogger,
-    name: &str,
-    dump_writer: &DumpWriter,
-    output_original: &HostArguments,
+    output_original: &HostMemoryAllocations,
     output_side_by_side: &HashMap<*mut c_void, Vec<u8>>,
     side_by_side_dump_threshold: Option<f32>,
-    module: &str,
-    input: &HostArguments,
-    parameters: KernelLaunchParams,
-) {
-    let mut original = output_original
-        .memory_allocations
-        .0
-        .iter()
-        .collect::<Vec<_>>();
+) -> bool {
+    let mut original = output_original.0.iter().collect::<Vec<_>>();
     original.sort_by_key(|(k, _)| **k);
     let mut side_by_side = output_side_by_side.iter().collect::<Vec<_>>();
     side_by_side.sort_by_key(|(k, _)| **k);
@@ -1207,9 +2315,7 @@ fn compare_and_notify_if_mismatch(
             }
         }
     }
-    if should_dump {
-        dump_writer.save_kernel_launch(fn_logger, name, module, parameters, input, output_original)
-    }
+    should_dump
 }
 
 unsafe fn diff_count(mut b1: &[u8], mut b2: &[u8]) -> u64 {
@@ -1275,7 +2381,13 @@ mod tests {
     use rand::{Rng, SeedableRng};
     use rand_chacha::ChaCha8Rng;
 
-    use super::{diff_count, diff_count_scalar};
+    use super::{diff_count, diff_count_scalar, pair_graph_nodes, topological_order};
+    use cuda_types::CUgraphNode;
+    use std::collections::HashMap;
+
+    fn node(id: usize) -> CUgraphNode {
+        id as _
+    }
 
     #[test]
     fn diff_count_is_correct() {
@@ -1294,4 +2406,61 @@ mod tests {
         }
         unsafe { assert_eq!(diff_count(&vec1, &vec2), diff_count_scalar(&vec1, &vec2)) }
     }
+
+    #[test]
+    fn topological_order_runs_dependencies_first() {
+        let graph = vec![
+            (node(1), vec![node(3)]),
+            (node(2), vec![]),
+            (node(3), vec![node(2)]),
+            (node(4), vec![node(1), node(2)]),
+        ];
+        let order = topological_order(&graph);
+        let position = |id| order.iter().position(|n| *n == node(id)).unwrap();
+        assert_eq!(order.len(), 4);
+        assert!(position(2) < position(3));
+        assert!(position(3) < position(1));
+        assert!(position(1) < position(4));
+    }
+
+    #[test]
+    fn graph_nodes_are_paired_by_dependencies() {
+        // 1 and 2 are roots, 3 and 4 both depend on 1, 5 depends on 2 and 4
+        let previous = vec![
+            (node(1), vec![]),
+            (node(2), vec![]),
+            (node(3), vec![node(1)]),
+            (node(4), vec![node(1)]),
+            (node(5), vec![node(2), node(4)]),
+        ];
+        // Same topology, but nodes were created in a different order
+        let current = vec![
+            (node(11), vec![]),
+            (node(13), vec![node(11)]),
+            (node(12), vec![]),
+            (node(15), vec![node(12), node(14)]),
+            (node(14), vec![node(11)]),
+        ];
+        let pairs = pair_graph_nodes(&previous, &current).unwrap();
+        let expected = (1..=5)
+            .map(|id| (node(id + 10), node(id)))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn graph_nodes_with_different_topology_are_not_paired() {
+        let previous = vec![
+            (node(1), vec![]),
+            (node(2), vec![node(1)]),
+            (node(3), vec![node(1)]),
+        ];
+        let chain = vec![
+            (node(11), vec![]),
+            (node(12), vec![node(11)]),
+            (node(13), vec![node(12)]),
+        ];
+        assert_eq!(pair_graph_nodes(&previous, &chain), None);
+        assert_eq!(pair_graph_nodes(&previous, &chain[..2]), None);
+    }
 }
@@ -0,0 +1,445 @@
+// Machine-readable alternative to log.txt. Every intercepted call is written
+// out as a single CallRecord, either as a line of JSON or as a length-prefixed
+// binary record. The same types are used by zluda_dump to write traces and by
+// tools that read them back, so they don't have to scrape the text log
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const BINARY_MAGIC: &[u8; 8] = b"ZLUDALOG";
+const BINARY_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LogFormat {
+    Text,
+    Jsonl,
+    Binary,
+}
+
+impl LogFormat {
+    pub(crate) fn file_name(self) -> &'static str {
+        match self {
+            LogFormat::Text => "log.txt",
+            LogFormat::Jsonl => "log.jsonl",
+            LogFormat::Binary => "log.bin",
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "jsonl" => Ok(LogFormat::Jsonl),
+            "binary" => Ok(LogFormat::Binary),
+            _ => Err(format!(
+                "Unknown log format: {}, expected text, jsonl or binary",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct CallRecord {
+    // Position of the call in the trace, starting from 0
+    pub index: u64,
+    // Start of the call in nanoseconds since UNIX epoch
+    pub timestamp_ns: u64,
+    pub duration_ns: u64,
+    pub thread_id: u64,
+    // Name of the CUDA function or {guid}::index for export table functions
+    pub function: String,
+    // Arguments as written by format.rs, in declaration order. Empty if the
+    // function has no arguments writer
+    pub arguments: Vec<Argument>,
+    // Not set if the function is missing from the underlying CUDA library
+    pub result: Option<CallResult>,
+    // Handles and pointers passed to or returned from the call. Calls working
+    // on the same CUDA object share its id
+    pub objects: Vec<u64>,
+    // Additional log entries (errors, warnings) reported during the call
+    pub messages: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Argument {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct CallResult {
+    pub code: i32,
+    pub name: String,
+}
+
+impl CallRecord {
+    pub fn argument(&self, name: &str) -> Option<&str> {
+        self.arguments
+            .iter()
+            .find(|argument| argument.name == name)
+            .map(|argument| argument.value.as_str())
+    }
+
+    pub fn succeeded(&self) -> bool {
+        matches!(self.result, Some(CallResult { code: 0, .. }))
+    }
+
+    fn write_binary(&self, buffer: &mut Vec<u8>) {
+        let start = buffer.len();
+        buffer.extend_from_slice(&0u32.to_le_bytes());
+        buffer.extend_from_slice(&self.index.to_le_bytes());
+        buffer.extend_from_slice(&self.timestamp_ns.to_le_bytes());
+        buffer.extend_from_slice(&self.duration_ns.to_le_bytes());
+        buffer.extend_from_slice(&self.thread_id.to_le_bytes());
+        write_binary_str(buffer, &self.function);
+        buffer.extend_from_slice(&(self.arguments.len() as u32).to_le_bytes());
+        for argument in self.arguments.iter() {
+            write_binary_str(buffer, &argument.name);
+            write_binary_str(buffer, &argument.value);
+        }
+        match &self.result {
+            Some(result) => {
+                buffer.push(1);
+                buffer.extend_from_slice(&result.code.to_le_bytes());
+                write_binary_str(buffer, &result.name);
+            }
+            None => buffer.push(0),
+        }
+        buffer.extend_from_slice(&(self.objects.len() as u32).to_le_bytes());
+        for object in self.objects.iter() {
+            buffer.extend_from_slice(&object.to_le_bytes());
+        }
+        buffer.extend_from_slice(&(self.messages.len() as u32).to_le_bytes());
+        for message in self.messages.iter() {
+            write_binary_str(buffer, message);
+        }
+        let record_len = (buffer.len() - start - 4) as u32;
+        buffer[start..start + 4].copy_from_slice(&record_len.to_le_bytes());
+    }
+
+    // Returns None at the end of the input
+    fn read_binary(reader: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut record_len = [0u8; 4];
+        match reader.read_exact(&mut record_len) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let mut record = vec![0u8; u32::from_le_bytes(record_len) as usize];
+        reader.read_exact(&mut record)?;
+        let mut record = BinaryRecord(&record);
+        let index = record.u64()?;
+        let timestamp_ns = record.u64()?;
+        let duration_ns = record.u64()?;
+        let thread_id = record.u64()?;
+        let function = record.string()?;
+        let arguments = (0..record.u32()?)
+            .map(|_| {
+                Ok(Argument {
+                    name: record.string()?,
+                    value: record.string()?,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let result = if record.bool()? {
+            Some(CallResult {
+                code: record.u32()? as i32,
+                name: record.string()?,
+            })
+        } else {
+            None
+        };
+        let objects = (0..record.u32()?)
+            .map(|_| record.u64())
+            .collect::<io::Result<Vec<_>>>()?;
+        let messages = (0..record.u32()?)
+            .map(|_| record.string())
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Some(CallRecord {
+            index,
+            timestamp_ns,
+            duration_ns,
+            thread_id,
+            function,
+            arguments,
+            result,
+            objects,
+            messages,
+        }))
+    }
+}
+
+fn write_binary_str(buffer: &mut Vec<u8>, s: &str) {
+    buffer.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(s.as_bytes());
+}
+
+struct BinaryRecord<'a>(&'a [u8]);
+
+impl<'a> BinaryRecord<'a> {
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(invalid_data("Truncated binary log record"));
+        }
+        let (result, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(result)
+    }
+
+    fn bool(&mut self) -> io::Result<bool> {
+        Ok(self.bytes(1)?[0] != 0)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).map_err(invalid_data)
+    }
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+pub(crate) struct Emitter {
+    format: LogFormat,
+    file: File,
+    // Reused between calls, so we don't reallocate on every function call
+    buffer: Vec<u8>,
+    next_index: u64,
+}
+
+impl Emitter {
+    pub(crate) fn new(path: &Path, format: LogFormat) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+        if format == LogFormat::Binary {
+            file.write_all(BINARY_MAGIC)?;
+            file.write_all(&BINARY_VERSION.to_le_bytes())?;
+        }
+        Ok(Self {
+            format,
+            file,
+            buffer: Vec::new(),
+            next_index: 0,
+        })
+    }
+
+    pub(crate) fn emit(
+        &mut self,
+        start: SystemTime,
+        function: String,
+        arguments: Vec<Argument>,
+        result: Option<CallResult>,
+        messages: Vec<String>,
+    ) -> io::Result<()> {
+        let mut objects = Vec::new();
+        for object in arguments.iter().filter_map(|arg| object_id(&arg.value)) {
+            if !objects.contains(&object) {
+                objects.push(object);
+            }
+        }
+        let record = CallRecord {
+            index: self.next_index,
+            timestamp_ns: start
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos() as u64,
+            duration_ns: start.elapsed().unwrap_or_default().as_nanos() as u64,
+            thread_id: thread_id::get() as u64,
+            function,
+            arguments,
+            result,
+            objects,
+            messages,
+        };
+        self.next_index += 1;
+        self.buffer.clear();
+        match self.format {
+            LogFormat::Text => unreachable!(),
+            LogFormat::Jsonl => {
+                serde_json::to_writer(&mut self.buffer, &record)?;
+                self.buffer.push(b'\n');
+            }
+            LogFormat::Binary => record.write_binary(&mut self.buffer),
+        }
+        // Single write per call, so a crash does not leave a partial record
+        self.file.write_all(&self.buffer)
+    }
+}
+
+// Handles and pointers are written by format.rs as hex numbers
+fn object_id(value: &str) -> Option<u64> {
+    let hex = value.strip_prefix("0x")?;
+    u64::from_str_radix(hex, 16).ok().filter(|id| *id != 0)
+}
+
+// Reads JSONL or binary logs, the format is detected from the file content
+pub struct Reader<R> {
+    inner: R,
+    format: LogFormat,
+    line: String,
+}
+
+impl Reader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let format = if inner.fill_buf()?.starts_with(BINARY_MAGIC) {
+            let mut header = [0u8; BINARY_MAGIC.len() + 4];
+            inner.read_exact(&mut header)?;
+            let version = u32::from_le_bytes(header[BINARY_MAGIC.len()..].try_into().unwrap());
+            if version != BINARY_VERSION {
+                return Err(invalid_data(format!(
+                    "Unsupported binary log version {}",
+                    version
+                )));
+            }
+            LogFormat::Binary
+        } else {
+            LogFormat::Jsonl
+        };
+        Ok(Self {
+            inner,
+            format,
+            line: String::new(),
+        })
+    }
+
+    pub fn format(&self) -> LogFormat {
+        self.format
+    }
+
+    fn next_record(&mut self) -> io::Result<Option<CallRecord>> {
+        match self.format {
+            LogFormat::Binary => CallRecord::read_binary(&mut self.inner),
+            LogFormat::Jsonl | LogFormat::Text => loop {
+                self.line.clear();
+                if self.inner.read_line(&mut self.line)? == 0 {
+                    return Ok(None);
+                }
+                if !self.line.trim().is_empty() {
+                    return serde_json::from_str(&self.line)
+                        .map(Some)
+                        .map_err(invalid_data);
+                }
+            },
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = io::Result<CallRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+// Whole trace loaded into memory, for tools that want to query it
+pub struct Trace {
+    pub calls: Vec<CallRecord>,
+}
+
+impl Trace {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let calls = Reader::open(path)?.collect::<io::Result<Vec<_>>>()?;
+        Ok(Self { calls })
+    }
+
+    pub fn calls_to<'a>(&'a self, function: &'a str) -> impl Iterator<Item = &'a CallRecord> {
+        self.calls
+            .iter()
+            .filter(move |call| call.function == function)
+    }
+
+    pub fn on_thread(&self, thread_id: u64) -> impl Iterator<Item = &CallRecord> {
+        self.calls
+            .iter()
+            .filter(move |call| call.thread_id == thread_id)
+    }
+
+    // All calls that received or returned the given handle or pointer
+    pub fn using_object(&self, object: u64) -> impl Iterator<Item = &CallRecord> {
+        self.calls
+            .iter()
+            .filter(move |call| call.objects.contains(&object))
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &CallRecord> {
+        self.calls.iter().filter(|call| !call.succeeded())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Argument, CallRecord, CallResult, LogFormat, Reader};
+    use std::io::Cursor;
+
+    fn record() -> CallRecord {
+        CallRecord {
+            index: 3,
+            timestamp_ns: 1_600_000_000_000_000_000,
+            duration_ns: 1234,
+            thread_id: 7,
+            function: "cuModuleGetFunction".to_owned(),
+            arguments: vec![
+                Argument {
+                    name: "hfunc".to_owned(),
+                    value: "0x1234".to_owned(),
+                },
+                Argument {
+                    name: "name".to_owned(),
+                    value: "\"kernel\"".to_owned(),
+                },
+            ],
+            result: Some(CallResult {
+                code: 0,
+                name: "CUDA_SUCCESS".to_owned(),
+            }),
+            objects: vec![0x1234],
+            messages: vec!["Unknown function".to_owned()],
+        }
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(super::BINARY_MAGIC);
+        buffer.extend_from_slice(&super::BINARY_VERSION.to_le_bytes());
+        record().write_binary(&mut buffer);
+        record().write_binary(&mut buffer);
+        let reader = Reader::new(Cursor::new(buffer)).unwrap();
+        assert_eq!(reader.format(), LogFormat::Binary);
+        let records = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records, vec![record(), record()]);
+    }
+
+    #[test]
+    fn jsonl_round_trip() {
+        let mut buffer = serde_json::to_vec(&record()).unwrap();
+        buffer.push(b'\n');
+        let reader = Reader::new(Cursor::new(buffer)).unwrap();
+        assert_eq!(reader.format(), LogFormat::Jsonl);
+        let records = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records, vec![record()]);
+    }
+}
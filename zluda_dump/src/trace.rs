@@ -1,11 +1,12 @@
 use crate::log::{FunctionLogger, LogEntry};
 use crate::{dark_api, log, side_by_side, Settings};
 use cuda_types::*;
-use ptx::{ast, DisplayParseError, ModuleParserExt};
-use ptx::{ast::PtxError, Token};
+use ptx::{ast, Diagnostic, ModuleParserExt};
 use serde::{Serialize, Serializer};
 use std::alloc::Layout;
+use std::cell::Cell;
 use std::ffi::CString;
+use std::ptr;
 use std::sync::Arc;
 use std::{
     collections::HashMap,
@@ -27,6 +28,11 @@ pub(crate) struct StateTracker {
     pub(crate) modules: HashMap<CUmodule, ParsedModule>,
     pub(crate) functions: HashMap<CUfunction, RecordedFunction>,
     pub(crate) texrefs: HashMap<CUtexref, RecordedTexref>,
+    pub(crate) texture_objects: HashMap<CUtexObject, RecordedTextureObject>,
+    pub(crate) surface_objects: HashMap<CUsurfObject, CUDA_RESOURCE_DESC>,
+    // Nodes of instantiated graphs, copied when the graph is instantiated, so
+    // later changes to the source graph do not affect them
+    pub(crate) graphs: HashMap<CUgraphExec, side_by_side::RecordedGraph>,
     pub(crate) dark_api: dark_api::DarkApiState,
     pub(crate) override_cc_major: Option<u32>,
 }
@@ -186,10 +192,16 @@ enum CUmoduleFileIndex {
 impl StateTracker {
     pub(crate) fn new(settings: &Settings) -> Self {
         StateTracker {
-            writer: DumpWriter::new(settings.dump_dir.clone()),
+            writer: DumpWriter::new(
+                settings.dump_dir.clone(),
+                settings.filter.max_dump_size.map(|size| size.0),
+            ),
             modules: HashMap::new(),
             functions: HashMap::new(),
             texrefs: HashMap::new(),
+            texture_objects: HashMap::new(),
+            surface_objects: HashMap::new(),
+            graphs: HashMap::new(),
             module_counter: 0,
             dark_api: dark_api::DarkApiState::new(),
             override_cc_major: settings.override_cc_major,
@@ -265,15 +277,21 @@ impl StateTracker {
         };
         let (ast, errors) = ptx::ModuleParser::parse_unchecked(module_text);
         if !errors.is_empty() {
+            let diagnostics = errors
+                .iter()
+                .map(|error| Diagnostic::from_parse_error(module_text, error))
+                .collect::<Vec<_>>();
             fn_logger.log(log::LogEntry::ModuleParsingError(
                 DumpWriter::get_file_name2(self.module_counter, file_index, "log"),
             ));
             fn_logger.log_io_error(self.writer.save_module_error_log(
-                module_text,
                 self.module_counter,
                 file_index,
-                &*errors,
+                &*diagnostics,
             ));
+            for diagnostic in diagnostics {
+                fn_logger.log(log::LogEntry::ModuleDiagnostic(diagnostic));
+            }
         } else {
             for directive in ast.directives {
                 match directive {
@@ -444,6 +462,56 @@ impl StateTracker {
             Ok(())
         });
     }
+
+    pub(crate) unsafe fn record_texture_object(
+        &mut self,
+        texture_object: CUtexObject,
+        resource: *const CUDA_RESOURCE_DESC,
+        texture: *const CUDA_TEXTURE_DESC,
+        view: *const CUDA_RESOURCE_VIEW_DESC,
+    ) {
+        let view = if view == ptr::null() {
+            None
+        } else {
+            Some(*view)
+        };
+        self.texture_objects.insert(
+            texture_object,
+            RecordedTextureObject {
+                resource: *resource,
+                texture: *texture,
+                view,
+            },
+        );
+    }
+
+    pub(crate) fn remove_texture_object(&mut self, texture_object: CUtexObject) {
+        self.texture_objects.remove(&texture_object);
+    }
+
+    pub(crate) unsafe fn record_surface_object(
+        &mut self,
+        surface_object: CUsurfObject,
+        resource: *const CUDA_RESOURCE_DESC,
+    ) {
+        self.surface_objects.insert(surface_object, *resource);
+    }
+
+    pub(crate) fn remove_surface_object(&mut self, surface_object: CUsurfObject) {
+        self.surface_objects.remove(&surface_object);
+    }
+
+    pub(crate) fn record_graph_exec(
+        &mut self,
+        graph_exec: CUgraphExec,
+        graph: side_by_side::RecordedGraph,
+    ) {
+        self.graphs.insert(graph_exec, graph);
+    }
+
+    pub(crate) fn remove_graph_exec(&mut self, graph_exec: CUgraphExec) {
+        self.graphs.remove(&graph_exec);
+    }
 }
 
 #[derive(PartialEq, Eq)]
@@ -472,6 +540,13 @@ impl ParsedModule {
     }
 }
 
+#[derive(Clone, Copy)]
+pub(crate) struct RecordedTextureObject {
+    pub(crate) resource: CUDA_RESOURCE_DESC,
+    pub(crate) texture: CUDA_TEXTURE_DESC,
+    pub(crate) view: Option<CUDA_RESOURCE_VIEW_DESC>,
+}
+
 #[derive(Clone, Copy)]
 pub(crate) struct RecordedTexref {
     pub(crate) address: Option<TexrefAddress>,
@@ -538,11 +613,44 @@ impl TexrefAddress {
 // This structs writes out information about CUDA execution to the dump dir
 pub(crate) struct DumpWriter {
     dump_dir: Option<PathBuf>,
+    max_size: Option<u64>,
+    written_size: Cell<u64>,
+    limit_reached: Cell<bool>,
 }
 
 impl DumpWriter {
-    fn new(dump_dir: Option<PathBuf>) -> Self {
-        Self { dump_dir }
+    fn new(dump_dir: Option<PathBuf>, max_size: Option<u64>) -> Self {
+        Self {
+            dump_dir,
+            max_size,
+            written_size: Cell::new(0),
+            limit_reached: Cell::new(false),
+        }
+    }
+
+    // Returns false if writing `size` more bytes would go over the limit.
+    // We report an error only the first time, afterwards dumps are silently
+    // skipped
+    fn reserve(&self, size: usize) -> io::Result<bool> {
+        let max_size = match self.max_size {
+            Some(max_size) => max_size,
+            None => return Ok(true),
+        };
+        let new_size = self.written_size.get() + size as u64;
+        if new_size > max_size {
+            if self.limit_reached.replace(true) {
+                return Ok(false);
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "Dump directory size limit of {} bytes reached, skipping further dumps",
+                    max_size
+                ),
+            ));
+        }
+        self.written_size.set(new_size);
+        Ok(true)
     }
 
     fn save_module2(
@@ -556,6 +664,9 @@ impl DumpWriter {
             None => return Ok(()),
             Some(d) => d.clone(),
         };
+        if !self.reserve(buffer.len())? {
+            return Ok(());
+        }
         dump_file.push(Self::get_file_name2(
             cu_module_index,
             index,
@@ -612,23 +723,26 @@ impl DumpWriter {
         ))
     }
 
-    fn save_module_error_log<'input>(
+    fn save_module_error_log(
         &self,
-        module_text: &str,
         cu_module_index: usize,
         module_file: CUmoduleFileIndex,
-        errors: &[ptx::ParseError<usize, Token<'input>, PtxError>],
+        diagnostics: &[Diagnostic],
     ) -> io::Result<()> {
         let mut log_file = match &self.dump_dir {
             None => return Ok(()),
             Some(d) => d.clone(),
         };
+        let log = diagnostics
+            .iter()
+            .map(|diagnostic| format!("{}\n", diagnostic))
+            .collect::<String>();
+        if !self.reserve(log.len())? {
+            return Ok(());
+        }
         log_file.push(Self::get_file_name2(cu_module_index, module_file, "log"));
         let mut file = File::create(log_file)?;
-        for error in errors {
-            let pretty_print_error = unsafe { DisplayParseError::new(error, module_text) };
-            writeln!(file, "{}", pretty_print_error)?;
-        }
+        file.write_all(log.as_bytes())?;
         Ok(())
     }
 
@@ -645,6 +759,16 @@ impl DumpWriter {
         } else {
             return Ok(());
         };
+        let buffers_size = input
+            .memory_allocations
+            .0
+            .values()
+            .chain(output.memory_allocations.0.values())
+            .map(|buffer| buffer.data().len())
+            .sum::<usize>();
+        if !self.reserve(module.len() + buffers_size)? {
+            return Ok(());
+        }
         dump_dir.push(name);
         let mut suffix = 1;
         while dump_dir.exists() {
@@ -693,7 +817,7 @@ impl DumpWriter {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone, Copy)]
 #[allow(non_snake_case)]
 pub(crate) struct KernelLaunchParams {
     pub(crate) gridDimX: u32,
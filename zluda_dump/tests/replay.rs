@@ -0,0 +1,69 @@
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output};
+use std::{fs, io};
+
+// Both tests replay the dump with the system CUDA library, so they need a GPU
+
+const ADD_ONE_OUTPUT: &str = "139955804307968";
+
+fn dump_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/replay/add_one")
+}
+
+fn run_replay(dump: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_zluda_replay"))
+        .arg(dump)
+        .output()
+        .unwrap()
+}
+
+fn copy_dir(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn replay_add_one() {
+    let output = run_replay(&dump_dir());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(
+        stdout.contains("1 of 1 kernel launches passed"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn replay_reports_mismatch() {
+    let dump = std::env::temp_dir().join(format!("zluda_replay_mismatch_{}", process::id()));
+    copy_dir(&dump_dir(), &dump).unwrap();
+    let mut expected = Vec::new();
+    for value in [2u32, 3, 4, 6].iter() {
+        expected.extend_from_slice(&value.to_le_bytes());
+    }
+    fs::write(dump.join("post").join(ADD_ONE_OUTPUT), expected).unwrap();
+    let output = run_replay(&dump);
+    fs::remove_dir_all(&dump).unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(
+        stdout.contains(&format!("{}[12]: expected [06], got [05]", ADD_ONE_OUTPUT)),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("0 of 1 kernel launches passed"),
+        "{}",
+        stdout
+    );
+}
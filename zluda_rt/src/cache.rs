@@ -1,6 +1,7 @@
 use crate::context::ContextData;
 use crate::{OptixCell, ProgramData};
 use data_encoding::HEXLOWER;
+use hip_common::cache::KernelKey;
 use hip_common::raytracing::VariablesBlock;
 use hip_common::unwrap_or_return;
 use rustc_hash::FxHashMap;
@@ -37,12 +38,15 @@ impl KernelRepository {
         input_attributes: &str,
         hiprt_version: &str,
     ) -> rusqlite::Result<()> {
-        self.0.save_program(
-            now,
+        let key = KernelKey {
             hash,
             compiler_version,
             git_hash,
             device,
+        };
+        self.0.save_program(
+            now,
+            &key,
             binary,
             rusqlite::params![
                 hip_common::cache::SqlCStrRef(program_name),
@@ -63,12 +67,15 @@ impl KernelRepository {
         input_attributes: &str,
         hiprt_version: &str,
     ) -> rusqlite::Result<Option<Vec<u8>>> {
-        self.0.try_load_program(
-            now,
+        let key = KernelKey {
             hash,
             compiler_version,
             git_hash,
             device,
+        };
+        self.0.try_load_program(
+            now,
+            &key,
             rusqlite::params![
                 hip_common::cache::SqlCStrRef(program_name),
                 input_attributes,